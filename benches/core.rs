@@ -0,0 +1,143 @@
+//! Criterion benchmarks for the core expression operations over the corpus.
+//!
+//! Run with `cargo bench --features bench` to also report the instrumentation counters: the
+//! nodes allocated, hashes computed, nodes evaluated, nodes rewritten and rules fired per call.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use criterion::{
+    criterion_group, criterion_main, measurement::WallTime, BenchmarkGroup, Criterion,
+};
+use symbolica::{
+    constants::Value,
+    corpus,
+    evaluation::Bindings,
+    instrument::{self, Counters},
+    parse::parse,
+    rewrite::Rule,
+    symbols::OpArgument,
+    template::hole,
+};
+
+/// Benchmarks `f` within `group` and, when counting is enabled, prints the mean counters per call.
+fn bench<R>(group: &mut BenchmarkGroup<WallTime>, name: &str, mut f: impl FnMut() -> R) {
+    let mut counters = Counters::default();
+    let mut calls = 0;
+    group.bench_function(name, |b| {
+        b.iter_custom(|iterations| {
+            let before = instrument::counters();
+            let start = Instant::now();
+            for _ in 0..iterations {
+                black_box(f());
+            }
+            let elapsed = start.elapsed();
+            counters = counters + (instrument::counters() - before);
+            calls += iterations;
+            elapsed
+        })
+    });
+
+    if cfg!(feature = "bench") && calls > 0 {
+        let per_call = |count: u64| count as f64 / calls as f64;
+        println!(
+            "{:<24} {:>8.1} nodes  {:>8.1} hashes  {:>8.1} evals  {:>8.1} rewritten  {:>8.1} fired",
+            name,
+            per_call(counters.nodes_allocated),
+            per_call(counters.hashes_computed),
+            per_call(counters.nodes_evaluated),
+            per_call(counters.nodes_rewritten),
+            per_call(counters.rules_fired),
+        );
+    }
+}
+
+fn exprs() -> Vec<OpArgument> {
+    corpus::iter().map(|entry| entry.expr.clone()).collect()
+}
+
+fn parsing(c: &mut Criterion) {
+    let sources = exprs()
+        .iter()
+        .map(|expr| expr.to_string())
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("parse");
+    bench(&mut group, "parse", || {
+        sources.iter().map(|src| parse(src)).collect::<Vec<_>>()
+    });
+    bench(&mut group, "hash", || {
+        sources
+            .iter()
+            .map(|src| parse(src).unwrap().hash())
+            .fold(0, u64::wrapping_add)
+    });
+    group.finish();
+}
+
+fn evaluation(c: &mut Criterion) {
+    let exprs = exprs();
+    let mut bindings = Bindings::default();
+    bindings.insert("x", 0.75);
+    bindings.insert("y", -1.25);
+    bindings.insert("z", 0.5);
+
+    let mut group = c.benchmark_group("evaluate");
+    bench(&mut group, "evaluate", || {
+        exprs
+            .iter()
+            .map(|expr| expr.evaluate(&bindings).unwrap())
+            .sum::<f64>()
+    });
+    bench(&mut group, "differentiate", || {
+        exprs
+            .iter()
+            .map(|expr| expr.derivative("x"))
+            .collect::<Vec<_>>()
+    });
+    group.finish();
+}
+
+fn simplification(c: &mut Criterion) {
+    let exprs = exprs();
+    let a = hole("a");
+    let one = OpArgument::from(Value::integer(1));
+    let two = OpArgument::from(Value::integer(2));
+    let rules = [
+        Rule::new("double", &a + &a, &two * &a).unwrap(),
+        Rule::new("unit", &a * &one, a.clone()).unwrap(),
+        Rule::new(
+            "pythagoras",
+            a.sin().pow(&two) + a.cos().pow(&two),
+            one.clone(),
+        )
+        .unwrap(),
+    ];
+
+    let mut group = c.benchmark_group("simplify");
+    bench(&mut group, "fold", || {
+        exprs.iter().map(OpArgument::fold).collect::<Vec<_>>()
+    });
+    bench(&mut group, "canonicalize", || {
+        exprs
+            .iter()
+            .map(OpArgument::canonicalize)
+            .collect::<Vec<_>>()
+    });
+    bench(&mut group, "rewrite", || {
+        exprs
+            .iter()
+            .map(|expr| expr.rewrite(&rules))
+            .collect::<Vec<_>>()
+    });
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().measurement_time(Duration::from_secs(2));
+    targets = parsing, evaluation, simplification
+}
+criterion_main!(benches);
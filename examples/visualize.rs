@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use eframe::{
     egui::{
         self,
@@ -7,26 +9,55 @@ use eframe::{
     epaint::Color32,
     run_native, CreationContext, NativeOptions,
 };
-use symbolica::symbols::OpArgument;
+use symbolica::{evaluation::Bindings, sample::Curve, symbols::OpArgument};
 
 const RES: usize = 100;
 
+/// The reserved symbol that advances with wall-clock time, in seconds.
+const TIME: &str = "t";
+/// The symbol plotted along the horizontal axis.
+const ABSCISSA: &str = "x";
+/// The range covered by the sliders of free parameters.
+const PARAMETER_RANGE: std::ops::RangeInclusive<f64> = -10.0..=10.0;
+
 #[derive(Default)]
 struct PlotInfo {
     expr: String,
     op_tree: Option<OpArgument>,
+    curve: Option<Curve>,
 }
 
 impl PlotInfo {
     fn parse_plots(&mut self) {
-        // TODO: Parse expr to set op_tree
-        todo!();
+        self.op_tree = self.expr.parse().ok();
+        self.curve = self
+            .op_tree
+            .as_ref()
+            .and_then(|tree| Curve::new(tree, ABSCISSA).ok());
+    }
+
+    fn is_animated(&self) -> bool {
+        self.op_tree
+            .as_ref()
+            .is_some_and(|tree| tree.free_variables().contains(TIME))
     }
 
-    fn parametrized(&self, x: f64) -> f64 {
+    /// The free symbols of the plot that are neither the abscissa nor the time.
+    fn parameters(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.curve
+            .iter()
+            .flat_map(Curve::parameters)
+            .copied()
+            .filter(|name| *name != TIME)
+    }
+
+    fn points(&self, span: std::ops::Range<f64>, bindings: &Bindings) -> Vec<[f64; 2]> {
         // TODO: Implement auto-parametrization
         // Perhaps require \(x\) and \(y\) as coordinates.
-        todo!();
+        match &self.curve {
+            Some(curve) => curve.sample(span, RES, bindings),
+            None => Vec::new(),
+        }
     }
 }
 
@@ -34,6 +65,7 @@ impl PlotInfo {
 struct App {
     plots: Vec<PlotInfo>,
     updated_plots: bool,
+    parameters: BTreeMap<&'static str, f64>,
 }
 
 impl App {
@@ -46,6 +78,13 @@ impl App {
     fn parse_plots(&mut self) {
         self.plots.iter_mut().for_each(PlotInfo::parse_plots);
         self.updated_plots = false;
+
+        let mut parameters = BTreeMap::new();
+        for name in self.plots.iter().flat_map(PlotInfo::parameters) {
+            let value = self.parameters.get(name).copied().unwrap_or(1.0);
+            parameters.insert(name, value);
+        }
+        self.parameters = parameters;
     }
 }
 
@@ -59,10 +98,11 @@ impl eframe::App for App {
                         self.updated_plots = true;
                     }
 
-                    if resp.dragged() {
-                        if resp.drag_delta().y >= 1.0 && plot_id + 1 < self.plots.len() {
-                            self.plots.swap(plot_id, plot_id + 1);
-                        }
+                    if resp.dragged()
+                        && resp.drag_delta().y >= 1.0
+                        && plot_id + 1 < self.plots.len()
+                    {
+                        self.plots.swap(plot_id, plot_id + 1);
                     }
                 }
 
@@ -70,6 +110,10 @@ impl eframe::App for App {
                     self.parse_plots();
                 }
 
+                for (name, value) in self.parameters.iter_mut() {
+                    ui.add(egui::Slider::new(value, PARAMETER_RANGE).text(*name));
+                }
+
                 let resp = ui.button("+");
                 if resp.clicked() {
                     self.plots.push(PlotInfo::default());
@@ -79,17 +123,18 @@ impl eframe::App for App {
             });
         });
 
+        let mut bindings = self.parameters.iter().map(|(k, v)| (*k, *v)).collect::<Bindings>();
+        bindings.insert(TIME, ctx.input(|i| i.time));
+        if self.plots.iter().any(PlotInfo::is_animated) {
+            ctx.request_repaint();
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             Plot::new("plot").show(ui, |plot_ui| {
                 let bounds = plot_ui.plot_bounds();
                 let span = bounds.min()[0]..bounds.max()[0];
                 for plot in self.plots.iter() {
-                    let mut plot_points = vec![[0.; 2]; RES];
-                    for i in 0..RES {
-                        let x = (i as f64) / (RES as f64 - 1.0) * (span.end - span.start) + span.start;
-                        plot_points[i] = [x, plot.parametrized(x)];
-                    }
-                    plot_ui.line(Line::new(plot_points));
+                    plot_ui.line(Line::new(plot.points(span.clone(), &bindings)));
                 }
             });
         });
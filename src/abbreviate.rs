@@ -0,0 +1,169 @@
+//! This module describes printing large expressions with their repeated subexpressions named.
+//!
+//! Derivatives and expansions repeat the same subexpressions many times, which the graph shares
+//! but printing writes out in full. [`OpArgument::abbreviate`] finds the subexpressions that
+//! occur more than once, in the same way the compiler shares registers, and names each of them
+//! `t1`, `t2`, ... in the order they are needed, so that
+//! `sin(x)^2 * cos(x) + sin(x)^2` prints as `t1*cos(x)+t1 where t1 = (sin(x))^2/1`. The result
+//! is an ordinary expression in the names, so [`crate::render`] draws it the same way.
+
+use std::fmt::Display;
+
+use ahash::HashMap;
+
+use crate::{
+    rewrite::Substitution,
+    symbols::{
+        intern, variable, OpArgument,
+        OpArgumentKind::{Leaf, Op},
+    },
+};
+
+/// An expression with its repeated subexpressions replaced by named variables.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Abbreviated {
+    expr: OpArgument,
+    definitions: Vec<(&'static str, OpArgument)>,
+}
+
+impl Abbreviated {
+    /// The expression, in terms of the names.
+    pub fn expr(&self) -> &OpArgument {
+        &self.expr
+    }
+
+    /// The names and what they stand for, each only in terms of the names before it.
+    pub fn definitions(&self) -> &[(&'static str, OpArgument)] {
+        &self.definitions
+    }
+
+    /// The expression with every name replaced by what it stands for.
+    pub fn expand(&self) -> OpArgument {
+        self.definitions
+            .iter()
+            .rev()
+            .fold(self.expr.clone(), |expr, (name, definition)| {
+                expr.substitute(&Substitution::from_iter([(*name, definition.clone())]))
+            })
+    }
+}
+
+impl Display for Abbreviated {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.expr, f)?;
+        for (i, (name, definition)) in self.definitions.iter().enumerate() {
+            let separator = if i == 0 { " where" } else { "," };
+            write!(f, "{} {} = {}", separator, name, definition)?;
+        }
+        Ok(())
+    }
+}
+
+struct Abbreviator {
+    /// How many times each operation occurs as an argument, or as the root.
+    occurrences: HashMap<u64, usize>,
+    sizes: HashMap<u64, usize>,
+    min_size: usize,
+    taken: Vec<&'static str>,
+    definitions: Vec<(&'static str, OpArgument)>,
+}
+
+impl Abbreviator {
+    /// Counts the occurrences of `node`, and of its arguments the first time it is seen.
+    fn count(&mut self, node: &OpArgument) -> usize {
+        let Op(op) = &node.value else {
+            return 1;
+        };
+        let seen = self.occurrences.entry(node.hash()).or_insert(0);
+        *seen += 1;
+        if *seen > 1 {
+            return self.sizes[&node.hash()];
+        }
+        let size = 1 + op
+            .arguments
+            .iter()
+            .map(|arg| self.count(arg))
+            .sum::<usize>();
+        self.sizes.insert(node.hash(), size);
+        size
+    }
+
+    fn fresh(&mut self) -> &'static str {
+        let name = (self.definitions.len() + 1..)
+            .map(|k| intern(&format!("t{}", k)))
+            .find(|name| !self.taken.contains(name))
+            .expect("Whoa there, we ran out of names");
+        self.taken.push(name);
+        name
+    }
+
+    fn abbreviate(&mut self, node: &OpArgument) -> OpArgument {
+        node.map_bottom_up(|node, rebuilt| {
+            if let Leaf(_) = node.value {
+                return rebuilt;
+            }
+            let repeated = self.occurrences[&node.hash()] > 1;
+            if !repeated || self.sizes[&node.hash()] < self.min_size {
+                return rebuilt;
+            }
+            let name = self.fresh();
+            self.definitions.push((name, rebuilt));
+            variable(name)
+        })
+    }
+}
+
+impl OpArgument {
+    /// Names every subexpression of at least `min_size` nodes that occurs more than once,
+    /// innermost first, avoiding the names of free variables.
+    pub fn abbreviate(&self, min_size: usize) -> Abbreviated {
+        let mut abbreviator = Abbreviator {
+            occurrences: HashMap::default(),
+            sizes: HashMap::default(),
+            min_size,
+            taken: self.free_variables().iter().collect(),
+            definitions: Vec::new(),
+        };
+        abbreviator.count(self);
+        let expr = match &self.value {
+            Op(_) => abbreviator.abbreviate(self),
+            Leaf(_) => self.clone(),
+        };
+        Abbreviated {
+            expr,
+            definitions: abbreviator.definitions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::parse;
+
+    #[test]
+    fn test_abbreviate() {
+        let p = |input: &str| parse(input).unwrap();
+
+        let abbreviated = p("sin(x)^2 * cos(x) + sin(x)^2").abbreviate(3);
+        assert_eq!(
+            abbreviated.to_string(),
+            format!("{} where t1 = {}", p("t1 * cos(x) + t1"), p("sin(x)^2"))
+        );
+        assert_eq!(abbreviated.expand(), p("sin(x)^2 * cos(x) + sin(x)^2"));
+
+        // Small repeats stay, names avoid the free variables, and inner names come first.
+        let expr = p("(t1 + exp(t1 * y))^2 / (t1 + exp(t1 * y)) + (t1 * y)");
+        let abbreviated = expr.abbreviate(3);
+        let names = abbreviated
+            .definitions()
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["t2", "t3"]);
+        assert_eq!(abbreviated.definitions()[0].1, p("t1 * y"));
+        assert_eq!(abbreviated.definitions()[1].1, p("t1 + exp(t2)"));
+        assert_eq!(abbreviated.expr(), &p("t3^2 / t3 + t2"));
+        assert_eq!(abbreviated.expand(), expr);
+        assert!(p("x * x").abbreviate(1).definitions().is_empty());
+    }
+}
@@ -0,0 +1,1012 @@
+//! This module describes real algebraic numbers, so that the roots solvers find can be computed
+//! with exactly rather than floated right away.
+//!
+//! An [`AlgebraicNumber`] is the one root of a monic squarefree polynomial with rational
+//! coefficients and no rational roots that lies in an open interval with rational endpoints.
+//! The polynomial changes sign across the interval, so bisection narrows it, and Sturm sequences
+//! count the roots in an interval, which is how roots are isolated in the first place.
+//!
+//! Sums and products need no resultants: the powers of `α + β` or `αβ` live in the
+//! `mn`-dimensional space `ℚ[α, β]/(p, q)`, so the first linear dependency among them is a
+//! polynomial the result is a root of. That root is then told apart from the others by
+//! narrowing `α` and `β` until interval arithmetic encloses exactly one of them, and comes out
+//! rational when it is.
+//!
+//! Algebraic numbers are interned like the names of symbols, so equal numbers are the same
+//! [`Value::Algebraic`] however they were computed, and the hashes and equality of expressions
+//! treat them exactly. Each number keeps its minimal polynomial, found by trying the products
+//! of subsets of its approximate complex roots as exact factors, and hashes it together with
+//! which of its real roots the number is, so digests are stable between runs. All arithmetic is
+//! on `i128` rationals and gives up when it overflows, which leaves an expression unfolded, or a
+//! number with a polynomial that may not be minimal.
+
+use std::{
+    cmp::Ordering,
+    fmt::{Debug, Display},
+    hash::Hash,
+};
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+use crate::{
+    constants::Value,
+    fold::Ratio,
+    rational::{coefficients, div_rem, gcd, trim, RationalError},
+    roots::{deflate, evaluate, rational_root},
+    symbols::{
+        OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        Operation,
+        OperationKind::{self, *},
+    },
+};
+
+/// How many times the intervals of the operands are halved to isolate a result, or to tell two
+/// numbers apart, before giving up.
+const MAX_BISECTIONS: usize = 96;
+
+/// Integer powers of algebraic numbers are only folded up to this exponent.
+const MAX_EXPONENT: i128 = 16;
+
+/// Factors are only searched for up to this degree, past which a number keeps the polynomial it
+/// was found with.
+const MAX_FACTORED_DEGREE: usize = 16;
+
+/// A polynomial, lowest degree first.
+type Poly = Vec<Ratio>;
+
+type Complex = (f64, f64);
+
+/// A real root of a polynomial with rational coefficients.
+pub struct AlgebraicNumber {
+    id: u32,
+    /// Monic and irreducible, unless it was too large to factor, in which case it is still
+    /// squarefree and without rational roots.
+    polynomial: Poly,
+    /// How many real roots of the polynomial are smaller.
+    index: usize,
+}
+
+static NUMBERS: Lazy<Mutex<Vec<&'static AlgebraicNumber>>> = Lazy::new(Default::default);
+
+/// An open interval around each number, and no other root of its polynomial, indexed by id and
+/// narrowed as comparisons need.
+static INTERVALS: Lazy<Mutex<Vec<(Ratio, Ratio)>>> = Lazy::new(Default::default);
+
+fn compare(a: Ratio, b: Ratio) -> Option<Ordering> {
+    Some(a.checked_add(-b)?.num.cmp(&0))
+}
+
+fn midpoint(lo: Ratio, hi: Ratio) -> Option<Ratio> {
+    lo.checked_add(hi)?.checked_mul(Ratio::new(1, 2)?)
+}
+
+fn to_f64(r: Ratio) -> f64 {
+    r.num as f64 / r.den as f64
+}
+
+fn sign(p: &[Ratio], x: Ratio) -> Option<i128> {
+    Some(evaluate(p, x)?.num.signum())
+}
+
+fn monic(p: &[Ratio]) -> Option<Poly> {
+    let lead = p.last()?.recip()?;
+    p.iter().map(|c| c.checked_mul(lead)).collect()
+}
+
+fn derivative(p: &[Ratio]) -> Option<Poly> {
+    p.iter()
+        .enumerate()
+        .skip(1)
+        .map(|(k, c)| c.checked_mul(Ratio::new(k as i128, 1)?))
+        .collect()
+}
+
+/// `p` with every repeated factor reduced to a single one.
+fn squarefree(p: &[Ratio]) -> Option<Poly> {
+    let common = gcd(p, &trim(derivative(p)?))?;
+    monic(&div_rem(p, &common)?.0)
+}
+
+/// The squarefree factors of `p` with the multiplicities of their roots, by Yun's algorithm,
+/// so `p` is a constant times the product of every factor to its multiplicity. The factors are
+/// monic, and constant ones are left out.
+pub(crate) fn squarefree_factors(p: &[Ratio]) -> Option<Vec<(Poly, usize)>> {
+    let p = trim(p.to_vec());
+    let dp = trim(derivative(&p)?);
+    let common = gcd(&p, &dp)?;
+    let (mut b, mut c) = (div_rem(&p, &common)?.0, div_rem(&dp, &common)?.0);
+    let mut factors = Vec::new();
+    let mut multiplicity = 1;
+    while b.len() > 1 {
+        // d = c - b′, whose common factor with b is the factor of this multiplicity.
+        let db = derivative(&b)?;
+        let d = (0..c.len().max(db.len()))
+            .map(|i| {
+                let (x, y) = (c.get(i), db.get(i));
+                x.copied()
+                    .unwrap_or(Ratio::ZERO)
+                    .checked_add(-y.copied().unwrap_or(Ratio::ZERO))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let factor = gcd(&b, &trim(d.clone()))?;
+        (b, c) = (div_rem(&b, &factor)?.0, div_rem(&trim(d), &factor)?.0);
+        if factor.len() > 1 {
+            factors.push((factor, multiplicity));
+        }
+        multiplicity += 1;
+    }
+    Some(factors)
+}
+
+/// The Sturm sequence of the squarefree `p`, with each remainder scaled to a leading
+/// coefficient of `±1`, which keeps the signs it is read for.
+fn sturm(p: &[Ratio]) -> Option<Vec<Poly>> {
+    let mut chain = vec![p.to_vec(), derivative(p)?];
+    loop {
+        let (_, remainder) = div_rem(&chain[chain.len() - 2], &chain[chain.len() - 1])?;
+        let Some(lead) = remainder.last() else {
+            return Some(chain);
+        };
+        let scale = Ratio::new(-lead.den, lead.num.abs())?;
+        chain.push(
+            remainder
+                .iter()
+                .map(|c| c.checked_mul(scale))
+                .collect::<Option<_>>()?,
+        );
+    }
+}
+
+fn variations(chain: &[Poly], x: Ratio) -> Option<usize> {
+    let (mut count, mut last) = (0, 0);
+    for p in chain {
+        match sign(p, x)? {
+            0 => {}
+            s => {
+                count += (last != 0 && s != last) as usize;
+                last = s;
+            }
+        }
+    }
+    Some(count)
+}
+
+/// The number of distinct roots in `(lo, hi]` of the polynomial `chain` is the Sturm sequence
+/// of.
+fn count(chain: &[Poly], lo: Ratio, hi: Ratio) -> Option<usize> {
+    Some(variations(chain, lo)?.saturating_sub(variations(chain, hi)?))
+}
+
+/// A bound on the absolute values of the roots of `p`.
+fn bound(p: &[Ratio]) -> Option<Ratio> {
+    let lead = p.last()?.recip()?;
+    let largest = p[..p.len() - 1].iter().try_fold(Ratio::ONE, |largest, c| {
+        let r = c.checked_mul(lead)?;
+        let r = Ratio::new(r.num.abs(), r.den)?;
+        Some(match compare(r, largest)? {
+            Ordering::Greater => r,
+            _ => largest,
+        })
+    })?;
+    largest.checked_add(Ratio::ONE)
+}
+
+/// Divides the rational roots out of the squarefree `p`, returning what is left and the roots.
+fn strip(mut p: Poly) -> Option<(Poly, Vec<Ratio>)> {
+    let mut roots = Vec::new();
+    while p.len() > 1 {
+        let Some(root) = rational_root(&p) else {
+            break;
+        };
+        p = deflate(&p, root)?;
+        roots.push(root);
+    }
+    Some((p, roots))
+}
+
+/// Open intervals around each real root of `p`, which has no rational roots, in increasing
+/// order.
+fn isolate(p: &[Ratio], chain: &[Poly]) -> Option<Vec<(Ratio, Ratio)>> {
+    let b = bound(p)?;
+    let (mut pending, mut isolated) = (vec![(-b, b)], Vec::new());
+    while let Some((lo, hi)) = pending.pop() {
+        match count(chain, lo, hi)? {
+            0 => {}
+            1 => isolated.push((lo, hi)),
+            _ => {
+                let mid = midpoint(lo, hi)?;
+                // Only a rational root missed by `strip` can be hit.
+                (sign(p, mid)? != 0).then_some(())?;
+                pending.push((mid, hi));
+                pending.push((lo, mid));
+            }
+        }
+    }
+    Some(isolated)
+}
+
+fn complex_mul((a, b): Complex, (c, d): Complex) -> Complex {
+    (a * c - b * d, a * d + b * c)
+}
+
+fn complex_div((a, b): Complex, (c, d): Complex) -> Complex {
+    let norm = c * c + d * d;
+    ((a * c + b * d) / norm, (b * c - a * d) / norm)
+}
+
+/// Approximations of the complex roots of the monic `p`, by the Durand–Kerner iteration.
+fn complex_roots(p: &[f64]) -> Vec<Complex> {
+    let degree = p.len() - 1;
+    let radius = p
+        .iter()
+        .fold(1.0, |radius: f64, c| radius.max(c.abs() + 1.0));
+    let mut roots = (0..degree)
+        .map(|k| {
+            let angle = 0.4 + std::f64::consts::TAU * k as f64 / degree as f64;
+            (radius * angle.cos(), radius * angle.sin())
+        })
+        .collect::<Vec<_>>();
+    for _ in 0..1000 {
+        let mut moved = 0.0f64;
+        for i in 0..degree {
+            let z = roots[i];
+            let value = p.iter().rev().fold((0.0, 0.0), |acc, &c| {
+                let (re, im) = complex_mul(acc, z);
+                (re + c, im)
+            });
+            let others = (0..degree).filter(|&j| j != i).fold((1.0, 0.0), |acc, j| {
+                complex_mul(acc, (z.0 - roots[j].0, z.1 - roots[j].1))
+            });
+            let step = complex_div(value, others);
+            roots[i] = (z.0 - step.0, z.1 - step.1);
+            moved = moved.max(step.0.hypot(step.1) / z.0.hypot(z.1).max(1.0));
+        }
+        if moved <= 1e-15 || moved.is_nan() {
+            break;
+        }
+    }
+    roots
+}
+
+/// The monic polynomial with these roots, if all its coefficients are close to integers.
+fn integer_polynomial(roots: impl Iterator<Item = Complex>) -> Option<Poly> {
+    let mut product = vec![(1.0, 0.0)];
+    for root in roots {
+        let mut next = vec![(0.0, 0.0); product.len() + 1];
+        for (k, &c) in product.iter().enumerate() {
+            let (re, im) = complex_mul(c, root);
+            next[k] = (next[k].0 - re, next[k].1 - im);
+            next[k + 1] = (next[k + 1].0 + c.0, next[k + 1].1 + c.1);
+        }
+        product = next;
+    }
+    product
+        .into_iter()
+        .map(|(re, im)| {
+            let rounded = re.round();
+            let tolerance = 1e-6 * re.abs().max(1.0);
+            let close = im.abs() <= tolerance && (re - rounded).abs() <= tolerance;
+            (close && rounded.abs() < 1e30)
+                .then(|| Ratio::new(rounded as i128, 1))
+                .flatten()
+        })
+        .collect()
+}
+
+/// Every way to choose `size` of `0..count`, in lexicographic order.
+fn combinations(count: usize, size: usize) -> impl Iterator<Item = Vec<usize>> {
+    let mut next = (size <= count).then(|| (0..size).collect::<Vec<_>>());
+    std::iter::from_fn(move || {
+        let current = next.take()?;
+        if let Some(i) = (0..size).rev().find(|&i| current[i] < count - size + i) {
+            let mut following = current.clone();
+            following[i] += 1;
+            for j in i + 1..size {
+                following[j] = following[j - 1] + 1;
+            }
+            next = Some(following);
+        }
+        Some(current)
+    })
+}
+
+fn power(r: Ratio, exponent: usize) -> Option<Ratio> {
+    (0..exponent).try_fold(Ratio::ONE, |acc, _| acc.checked_mul(r))
+}
+
+/// The minimal polynomial of the root of `p` in `(lo, hi)`, where `p` is monic, squarefree and
+/// without rational roots.
+///
+/// Scaling `x` by the common denominator of the coefficients makes `p` an integer polynomial,
+/// whose monic factors have integer coefficients too, so the products of subsets of its
+/// approximate roots that round to integers are the only candidates, and exact division tells
+/// which are factors. The part that keeps the root is split until it has no factor of at most
+/// half its degree.
+fn minimal(p: &[Ratio], lo: Ratio, hi: Ratio) -> Option<Poly> {
+    if p.len() - 1 > MAX_FACTORED_DEGREE {
+        return None;
+    }
+    let scale = p.iter().try_fold(1, |scale: i128, c| {
+        scale.checked_mul(Ratio::new(scale, c.den)?.den)
+    })?;
+    let scale = Ratio::new(scale, 1)?;
+    let mut factor = p
+        .iter()
+        .enumerate()
+        .map(|(k, c)| c.checked_mul(power(scale, p.len() - 1 - k)?))
+        .collect::<Option<Poly>>()?;
+    let (lo, hi) = (lo.checked_mul(scale)?, hi.checked_mul(scale)?);
+    let mut roots = complex_roots(&factor.iter().map(|&c| to_f64(c)).collect::<Vec<_>>());
+    'split: loop {
+        // Factors of degree one would be rational roots.
+        for size in 2..=roots.len() / 2 {
+            for subset in combinations(roots.len(), size) {
+                let Some(candidate) = integer_polynomial(subset.iter().map(|&i| roots[i])) else {
+                    continue;
+                };
+                let (quotient, remainder) = div_rem(&factor, &candidate)?;
+                if !remainder.is_empty() {
+                    continue;
+                }
+                let inside = count(&sturm(&candidate)?, lo, hi)? > 0;
+                let kept = (0..roots.len()).filter(|i| subset.contains(i) == inside);
+                (factor, roots) = (
+                    if inside { candidate } else { quotient },
+                    kept.map(|i| roots[i]).collect(),
+                );
+                continue 'split;
+            }
+        }
+        break;
+    }
+    let degree = factor.len() - 1;
+    factor
+        .iter()
+        .enumerate()
+        .map(|(k, c)| c.checked_mul(power(scale, degree - k)?.recip()?))
+        .collect()
+}
+
+/// The real roots of `p`, which is squarefree and has no rational roots, in increasing order.
+fn irrational_roots(p: &[Ratio]) -> Option<Vec<Real>> {
+    let p = monic(p)?;
+    let chain = sturm(&p)?;
+    isolate(&p, &chain)?
+        .into_iter()
+        .map(|interval| {
+            Some(Real::Algebraic(AlgebraicNumber::intern(
+                p.clone(),
+                interval,
+            )?))
+        })
+        .collect()
+}
+
+/// Like [`irrational_roots`], as constants.
+pub(crate) fn algebraic_roots(p: &[Ratio]) -> Option<Vec<OpArgument>> {
+    irrational_roots(p)?
+        .into_iter()
+        .map(Real::to_oparg)
+        .collect()
+}
+
+impl AlgebraicNumber {
+    /// The number that is the root of `polynomial` in `(lo, hi)`, made once. `polynomial` must
+    /// be monic, squarefree, without rational roots and have a single root in the interval.
+    fn intern(polynomial: Poly, (lo, hi): (Ratio, Ratio)) -> Option<&'static AlgebraicNumber> {
+        let mut numbers = NUMBERS.lock();
+        for &number in numbers.iter() {
+            if number.is_root_of(&polynomial, lo, hi)? {
+                return Some(number);
+            }
+        }
+        let polynomial = minimal(&polynomial, lo, hi).unwrap_or(polynomial);
+        let index = count(&sturm(&polynomial)?, -bound(&polynomial)?, lo)?;
+        let number = Box::leak(Box::new(AlgebraicNumber {
+            id: numbers.len() as u32,
+            polynomial,
+            index,
+        }));
+        numbers.push(number);
+        INTERVALS.lock().push((lo, hi));
+        Some(number)
+    }
+
+    /// Whether the root of `polynomial` in `(lo, hi)` is this number, which it is when the two
+    /// polynomials share a root where the intervals overlap.
+    fn is_root_of(&self, polynomial: &[Ratio], lo: Ratio, hi: Ratio) -> Option<bool> {
+        let (a, b) = self.interval();
+        let lo = if compare(a, lo)?.is_gt() { a } else { lo };
+        let hi = if compare(b, hi)?.is_lt() { b } else { hi };
+        if !compare(lo, hi)?.is_lt() {
+            return Some(false);
+        }
+        let common = gcd(&self.polynomial, polynomial)?;
+        if common.len() < 2 {
+            return Some(false);
+        }
+        Some(count(&sturm(&common)?, lo, hi)? > 0)
+    }
+
+    fn interval(&self) -> (Ratio, Ratio) {
+        INTERVALS.lock()[self.id as usize]
+    }
+
+    /// Halves the interval around the root.
+    fn bisect(&self) -> Option<()> {
+        let mut intervals = INTERVALS.lock();
+        let interval = &mut intervals[self.id as usize];
+        let (lo, hi) = *interval;
+        let mid = midpoint(lo, hi)?;
+        *interval = match sign(&self.polynomial, mid)? == sign(&self.polynomial, lo)? {
+            true => (mid, hi),
+            false => (lo, mid),
+        };
+        Some(())
+    }
+
+    /// The degree of the polynomial this is a root of, which is the degree of the number unless
+    /// the polynomial was too large to factor.
+    pub fn degree(&self) -> usize {
+        self.polynomial.len() - 1
+    }
+
+    /// The polynomial in `var` this is a root of.
+    pub fn polynomial(&self, var: &'static str) -> OpArgument {
+        crate::rational::polynomial(&self.polynomial, var)
+            .expect("Oops, the polynomial of an algebraic number should build back")
+    }
+
+    /// The nearest `f64`, or close to it.
+    pub fn approximate(&self) -> f64 {
+        let (lo, hi) = self.interval();
+        let positive_below = sign(&self.polynomial, lo) == Some(1);
+        let (mut lo, mut hi) = (to_f64(lo), to_f64(hi));
+        let p = self
+            .polynomial
+            .iter()
+            .map(|&c| to_f64(c))
+            .collect::<Vec<_>>();
+        loop {
+            let mid = (lo + hi) / 2.0;
+            if mid <= lo || mid >= hi {
+                return mid;
+            }
+            let value = p.iter().rev().fold(0.0, |acc, c| acc * mid + c);
+            match (value > 0.0) == positive_below {
+                true => lo = mid,
+                false => hi = mid,
+            }
+        }
+    }
+
+    /// Bounds on the number, which may round inwards.
+    pub(crate) fn bounds(&self) -> (f64, f64) {
+        let (lo, hi) = self.interval();
+        (to_f64(lo), to_f64(hi))
+    }
+}
+
+impl PartialEq for AlgebraicNumber {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for AlgebraicNumber {}
+
+impl Hash for AlgebraicNumber {
+    /// Hashes the polynomial and which of its real roots this is, rather than the order numbers
+    /// were made in, so hashes are the same in every run.
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        for c in &self.polynomial {
+            state.write_i128(c.num);
+            state.write_i128(c.den);
+        }
+        state.write_usize(self.index);
+    }
+}
+
+impl PartialOrd for AlgebraicNumber {
+    /// Compares exactly, unless telling the numbers apart overflows.
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        // Every number is interned, so the `'static` references are in the table.
+        let (a, b) = {
+            let numbers = NUMBERS.lock();
+            (numbers[self.id as usize], numbers[other.id as usize])
+        };
+        Real::Algebraic(a).compare(Real::Algebraic(b))
+    }
+}
+
+impl Display for AlgebraicNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("root(")?;
+        for (k, c) in self.polynomial.iter().enumerate().rev() {
+            if *c == Ratio::ZERO {
+                continue;
+            }
+            let magnitude = Ratio::new(c.num.abs(), c.den).expect("Uh-oh, a zero denominator");
+            match (k == self.degree(), c.num < 0) {
+                (true, _) => {}
+                (false, false) => f.write_str("+")?,
+                (false, true) => f.write_str("-")?,
+            }
+            if magnitude != Ratio::ONE || k == 0 {
+                match magnitude.den {
+                    1 => write!(f, "{}", magnitude.num)?,
+                    den => write!(f, "{}/{}", magnitude.num, den)?,
+                }
+            }
+            match k {
+                0 => {}
+                1 => f.write_str("x")?,
+                k => write!(f, "x^{}", k)?,
+            }
+        }
+        write!(f, ", {:.6})", self.approximate())
+    }
+}
+
+impl Debug for AlgebraicNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// A rational or algebraic constant.
+#[derive(Clone, Copy, Debug)]
+enum Real {
+    Rational(Ratio),
+    Algebraic(&'static AlgebraicNumber),
+}
+
+impl Real {
+    fn of(node: &OpArgument) -> Option<Real> {
+        if let Some(r) = Ratio::of(node) {
+            return Some(Real::Rational(r));
+        }
+        match &node.value {
+            Leaf(value) => match **value {
+                Value::Algebraic(a) => Some(Real::Algebraic(a)),
+                _ => None,
+            },
+            Op(op) if op.op == Negation => Real::of(&op.arguments[0])?.neg(),
+            Op(_) => None,
+        }
+    }
+
+    fn to_oparg(self) -> Option<OpArgument> {
+        match self {
+            Real::Rational(r) => r.to_oparg(),
+            Real::Algebraic(a) => Some(Value::Algebraic(a).into()),
+        }
+    }
+
+    /// A polynomial the number is a root of, and an interval around it that is open unless it
+    /// is a single rational.
+    fn defined(self) -> Option<(Poly, (Ratio, Ratio))> {
+        match self {
+            Real::Rational(r) => Some((vec![-r, Ratio::ONE], (r, r))),
+            Real::Algebraic(a) => Some((a.polynomial.clone(), a.interval())),
+        }
+    }
+
+    fn bisect(self) -> Option<()> {
+        match self {
+            Real::Rational(_) => Some(()),
+            Real::Algebraic(a) => a.bisect(),
+        }
+    }
+
+    /// The root of the squarefree, monic `s` in `(lo, hi)`, which must have just one there.
+    fn isolated(s: Poly, (lo, hi): (Ratio, Ratio)) -> Option<Real> {
+        let (rest, rationals) = strip(s)?;
+        for r in rationals {
+            if compare(lo, r)?.is_lt() && compare(r, hi)?.is_lt() {
+                return Some(Real::Rational(r));
+            }
+        }
+        let rest = monic(&rest)?;
+        (count(&sturm(&rest)?, lo, hi)? == 1).then_some(())?;
+        Some(Real::Algebraic(AlgebraicNumber::intern(rest, (lo, hi))?))
+    }
+
+    fn neg(self) -> Option<Real> {
+        let a = match self {
+            Real::Rational(r) => return Some(Real::Rational(-r)),
+            Real::Algebraic(a) => a,
+        };
+        // -α is a root of p(-x).
+        let reflected = a
+            .polynomial
+            .iter()
+            .enumerate()
+            .map(|(k, &c)| if k % 2 == 1 { -c } else { c })
+            .collect::<Vec<_>>();
+        let (lo, hi) = a.interval();
+        Real::isolated(monic(&reflected)?, (-hi, -lo))
+    }
+
+    fn recip(self) -> Option<Real> {
+        let a = match self {
+            Real::Rational(r) => return Some(Real::Rational(r.recip()?)),
+            Real::Algebraic(a) => a,
+        };
+        // 1/α is a root of xⁿp(1/x), once the interval around α leaves out zero.
+        let (lo, hi) = loop {
+            let (lo, hi) = a.interval();
+            if lo.num.signum() * hi.num.signum() == 1 {
+                break (lo, hi);
+            }
+            a.bisect()?;
+        };
+        let reversed = a.polynomial.iter().rev().copied().collect::<Vec<_>>();
+        Real::isolated(monic(&reversed)?, (hi.recip()?, lo.recip()?))
+    }
+
+    fn add(self, other: Real) -> Option<Real> {
+        match (self, other) {
+            (Real::Rational(a), Real::Rational(b)) => Some(Real::Rational(a.checked_add(b)?)),
+            _ => self.combine(other, Addition),
+        }
+    }
+
+    fn mul(self, other: Real) -> Option<Real> {
+        match (self, other) {
+            (Real::Rational(a), Real::Rational(b)) => Some(Real::Rational(a.checked_mul(b)?)),
+            (Real::Rational(r), _) | (_, Real::Rational(r)) if r == Ratio::ZERO => {
+                Some(Real::Rational(Ratio::ZERO))
+            }
+            _ => self.combine(other, Multiplication),
+        }
+    }
+
+    fn pow(self, k: i128) -> Option<Real> {
+        if k.abs() > MAX_EXPONENT {
+            return None;
+        }
+        let mut power = Real::Rational(Ratio::ONE);
+        for _ in 0..k.abs() {
+            power = power.mul(self)?;
+        }
+        match k < 0 {
+            true => power.recip(),
+            false => Some(power),
+        }
+    }
+
+    /// The sum or product of `self` and `other`.
+    fn combine(self, other: Real, op: OperationKind) -> Option<Real> {
+        let (p, _) = self.defined()?;
+        let (q, _) = other.defined()?;
+        let s = squarefree(&annihilator(&p, &q, op)?)?;
+        let chain = sturm(&s)?;
+        for _ in 0..MAX_BISECTIONS {
+            let (_, (a, b)) = self.defined()?;
+            let (_, (c, d)) = other.defined()?;
+            let (lo, hi) = match op {
+                Addition => (a.checked_add(c)?, b.checked_add(d)?),
+                _ => {
+                    let corners = [
+                        a.checked_mul(c)?,
+                        a.checked_mul(d)?,
+                        b.checked_mul(c)?,
+                        b.checked_mul(d)?,
+                    ];
+                    let mut sorted = corners;
+                    for i in 1..sorted.len() {
+                        for j in (1..=i).rev() {
+                            if compare(sorted[j - 1], sorted[j])?.is_gt() {
+                                sorted.swap(j - 1, j);
+                            }
+                        }
+                    }
+                    (sorted[0], sorted[3])
+                }
+            };
+            let isolated = sign(&s, lo)? != 0 && sign(&s, hi)? != 0 && count(&chain, lo, hi)? == 1;
+            if isolated {
+                return Real::isolated(s, (lo, hi));
+            }
+            self.bisect()?;
+            other.bisect()?;
+        }
+        None
+    }
+
+    fn compare(self, other: Real) -> Option<Ordering> {
+        if let (Real::Algebraic(a), Real::Algebraic(b)) = (self, other) {
+            if a == b {
+                return Some(Ordering::Equal);
+            }
+        }
+        if let (Real::Rational(a), Real::Rational(b)) = (self, other) {
+            return compare(a, b);
+        }
+        // Interned numbers that differ, and rationals and irrationals, are eventually told
+        // apart by their intervals.
+        for _ in 0..MAX_BISECTIONS {
+            let (_, (a, b)) = self.defined()?;
+            let (_, (c, d)) = other.defined()?;
+            if compare(b, c)?.is_le() {
+                return Some(Ordering::Less);
+            }
+            if compare(d, a)?.is_le() {
+                return Some(Ordering::Greater);
+            }
+            self.bisect()?;
+            other.bisect()?;
+        }
+        None
+    }
+}
+
+/// A nonzero polynomial that `α + β` or `αβ` is a root of, for the roots `α` of the monic `p`
+/// and `β` of the monic `q`: the first linear dependency among the powers of the result, in the
+/// basis `αⁱβʲ` of `ℚ[α, β]/(p, q)`.
+fn annihilator(p: &[Ratio], q: &[Ratio], op: OperationKind) -> Option<Poly> {
+    let (m, n) = (p.len() - 1, q.len() - 1);
+    // Multiplies by α or β, reducing αᵐ = -Σ pₖαᵏ.
+    let times = |v: &[Ratio], by_alpha: bool| -> Option<Poly> {
+        let mut w = vec![Ratio::ZERO; m * n];
+        for i in 0..m {
+            for j in 0..n {
+                let c = v[i * n + j];
+                if c == Ratio::ZERO {
+                    continue;
+                }
+                let (degree, modulus) = if by_alpha { (i, p) } else { (j, q) };
+                let at = |k: usize| if by_alpha { k * n + j } else { i * n + k };
+                match degree + 1 < modulus.len() - 1 {
+                    true => w[at(degree + 1)] = w[at(degree + 1)].checked_add(c)?,
+                    false => {
+                        for (k, &r) in modulus[..modulus.len() - 1].iter().enumerate() {
+                            w[at(k)] = w[at(k)].checked_add(-c.checked_mul(r)?)?;
+                        }
+                    }
+                }
+            }
+        }
+        Some(w)
+    };
+    let step = |v: &[Ratio]| -> Option<Poly> {
+        match op {
+            Addition => times(v, true)?
+                .iter()
+                .zip(times(v, false)?)
+                .map(|(a, b)| a.checked_add(b))
+                .collect(),
+            _ => times(&times(v, true)?, false),
+        }
+    };
+
+    // Rows reduced against the earlier ones, with their pivots and the powers they combine.
+    let mut rows: Vec<(usize, Poly, Poly)> = Vec::new();
+    let mut power = vec![Ratio::ZERO; m * n];
+    power[0] = Ratio::ONE;
+    for k in 0..=m * n {
+        let (mut row, mut combination) = (power.clone(), vec![Ratio::ZERO; k + 1]);
+        combination[k] = Ratio::ONE;
+        for (pivot, reduced, combined) in &rows {
+            let factor = row[*pivot];
+            if factor == Ratio::ZERO {
+                continue;
+            }
+            for (x, &y) in row.iter_mut().zip(reduced) {
+                *x = x.checked_add(-factor.checked_mul(y)?)?;
+            }
+            for (x, &y) in combination.iter_mut().zip(combined) {
+                *x = x.checked_add(-factor.checked_mul(y)?)?;
+            }
+        }
+        match row.iter().position(|&c| c != Ratio::ZERO) {
+            None => return monic(&trim(combination)),
+            Some(pivot) => {
+                let scale = row[pivot].recip()?;
+                let scaled = |v: &[Ratio]| {
+                    v.iter()
+                        .map(|c| c.checked_mul(scale))
+                        .collect::<Option<Poly>>()
+                };
+                rows.push((pivot, scaled(&row)?, scaled(&combination)?));
+            }
+        }
+        power = step(&power)?;
+    }
+    None
+}
+
+/// The exact result of an arithmetic operation on rational and algebraic constants, at least
+/// one of them algebraic.
+pub(crate) fn fold_root(op: &Operation) -> Option<OpArgument> {
+    let algebraic = |node: &OpArgument| matches!(Real::of(node), Some(Real::Algebraic(_)));
+    if !op.arguments.iter().any(algebraic) {
+        return None;
+    }
+    let arg = |i: usize| Real::of(&op.arguments[i]);
+    let result = match op.op {
+        Addition => arg(0)?.add(arg(1)?)?,
+        Subtraction => arg(0)?.add(arg(1)?.neg()?)?,
+        Multiplication => arg(0)?.mul(arg(1)?)?,
+        Division => arg(0)?.mul(arg(1)?.recip()?)?,
+        Negation => arg(0)?.neg()?,
+        Pow => match arg(1)? {
+            Real::Rational(k) if k.den == 1 => arg(0)?.pow(k.num)?,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    result.to_oparg()
+}
+
+/// The exact order of two real constants built from rationals and algebraic numbers, which is
+/// `None` for anything else or when telling them apart overflows.
+pub fn compare_exact(a: &OpArgument, b: &OpArgument) -> Option<Ordering> {
+    Real::of(&a.fold())?.compare(Real::of(&b.fold())?)
+}
+
+/// The distinct real roots of `expr` as a polynomial in `var` with rational coefficients, in
+/// increasing order. Rational roots are rationals, and the others are algebraic numbers.
+pub fn real_roots(expr: &OpArgument, var: &'static str) -> Result<Vec<OpArgument>, RationalError> {
+    let p = trim(coefficients(expr, var)?);
+    if p.len() < 2 {
+        return Ok(Vec::new());
+    }
+    let roots = (|| {
+        let (rest, rationals) = strip(squarefree(&p)?)?;
+        let mut roots = rationals
+            .into_iter()
+            .map(Real::Rational)
+            .collect::<Vec<_>>();
+        if rest.len() > 1 {
+            roots.extend(irrational_roots(&rest)?);
+        }
+        for i in 1..roots.len() {
+            for j in (1..=i).rev() {
+                if roots[j - 1].compare(roots[j])?.is_gt() {
+                    roots.swap(j - 1, j);
+                }
+            }
+        }
+        roots
+            .into_iter()
+            .map(Real::to_oparg)
+            .collect::<Option<Vec<_>>>()
+    })();
+    roots.ok_or(RationalError::Overflow)
+}
+
+/// Reads back the algebraic number stored as its polynomial and interval, checking that the
+/// interval isolates a root.
+pub(crate) fn decode(polynomial: Poly, interval: (Ratio, Ratio)) -> Option<OpArgument> {
+    let polynomial = trim(polynomial);
+    (polynomial.len() > 1).then_some(())?;
+    let s = squarefree(&polynomial)?;
+    let (lo, hi) = interval;
+    let isolating = compare(lo, hi)?.is_lt()
+        && sign(&s, lo)? != 0
+        && sign(&s, hi)? != 0
+        && count(&sturm(&s)?, lo, hi)? == 1;
+    isolating.then_some(())?;
+    Real::isolated(s, interval)?.to_oparg()
+}
+
+/// The coefficients and interval of `number`, for storing it.
+pub(crate) fn encode(number: &AlgebraicNumber) -> (&[Ratio], (Ratio, Ratio)) {
+    (&number.polynomial, number.interval())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cmp::Ordering,
+        hash::{DefaultHasher, Hash, Hasher},
+    };
+
+    use super::{compare_exact, real_roots};
+    use crate::{
+        constants::Value,
+        parse::parse,
+        serialize::{read_expressions, write_expressions},
+        symbols::OpArgumentKind::Leaf,
+    };
+
+    #[test]
+    fn test_algebraic_numbers() {
+        let p = |input: &str| parse(input).unwrap();
+
+        // x³ - 2 has the one real root ∛2, and (x - 1)(x² - 2) has 1 among its.
+        let cube = real_roots(&p("x^3 - 2"), "x").unwrap();
+        assert_eq!(cube.len(), 1);
+        let Leaf(value) = &cube[0].value else {
+            panic!("∛2 should be an algebraic leaf");
+        };
+        let Value::Algebraic(alpha) = **value else {
+            panic!("∛2 should be algebraic");
+        };
+        assert_eq!(alpha.degree(), 3);
+        assert!((alpha.approximate() - 2f64.cbrt()).abs() < 1e-15);
+        assert_eq!(alpha.to_string(), "root(x^3-2, 1.259921)");
+
+        let roots = real_roots(&p("x^3 - x^2 - 2*x + 2"), "x").unwrap();
+        assert_eq!(roots.len(), 3);
+        assert_eq!(roots[1], p("1").fold());
+        let (minus, plus) = (&roots[0], &roots[2]);
+
+        // Arithmetic is exact, and equal numbers are the same leaf.
+        assert_eq!((minus + plus).fold(), p("0").fold());
+        assert_eq!((minus * plus).fold(), p("-2").fold());
+        assert_eq!((-plus).fold(), *minus);
+        assert_eq!(plus.pow(&p("2")).fold(), p("2").fold());
+        assert_eq!(cube[0].pow(&p("3")).fold(), p("2").fold());
+        let sqrt2 = real_roots(&p("x^2 - 2"), "x").unwrap();
+        assert_eq!(sqrt2[1], *plus);
+        let sqrt3 = &real_roots(&p("x^2 - 3"), "x").unwrap()[1];
+        let sum = (plus + sqrt3).fold();
+        let expected = 2f64.sqrt() + 3f64.sqrt();
+        assert!((sum.evaluate(&Default::default()).unwrap() - expected).abs() < 1e-14);
+        assert_eq!(((&sum - sqrt3) * (&sum - sqrt3)).fold(), p("2").fold());
+        assert_eq!((p("1") / (plus - sqrt3)).fold(), (-(plus + sqrt3)).fold());
+
+        // Comparisons are exact too.
+        assert_eq!(compare_exact(plus, sqrt3), Some(Ordering::Less));
+        assert_eq!(compare_exact(plus, &p("7/5")), Some(Ordering::Greater));
+        assert_eq!(
+            compare_exact(&(plus * plus), &p("2")),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(compare_exact(&p("x"), plus), None);
+
+        let mut bytes = Vec::new();
+        write_expressions(&mut bytes, &[&sum]).unwrap();
+        assert_eq!(read_expressions(bytes.as_slice()).unwrap(), [sum]);
+    }
+
+    #[test]
+    fn test_minimal_polynomial() {
+        let p = |input: &str| parse(input).unwrap();
+
+        // (x² - 2)(x² - 3) is reducible, so its roots keep the quadratic factors instead.
+        let roots = real_roots(&p("x^4 - 5*x^2 + 6"), "x").unwrap();
+        assert_eq!(roots.len(), 4);
+        let Leaf(value) = &roots[2].value else {
+            panic!("√2 should be an algebraic leaf");
+        };
+        let Value::Algebraic(sqrt2) = **value else {
+            panic!("√2 should be algebraic");
+        };
+        assert_eq!(sqrt2.degree(), 2);
+        assert_eq!(roots[2], real_roots(&p("x^2 - 2"), "x").unwrap()[1]);
+
+        // The hash is the polynomial and the index of the root, whenever the number was made.
+        let mut expected = DefaultHasher::new();
+        for (num, den) in [(-2, 1), (0, 1), (1, 1)] {
+            expected.write_i128(num);
+            expected.write_i128(den);
+        }
+        expected.write_usize(1);
+        let mut actual = DefaultHasher::new();
+        sqrt2.hash(&mut actual);
+        assert_eq!(actual.finish(), expected.finish());
+
+        // x⁶ - 5x³ + 6 splits into x³ - 2 and x³ - 3 without any real rational roots.
+        let roots = real_roots(&p("x^6 - 5*x^3 + 6"), "x").unwrap();
+        assert_eq!(
+            roots,
+            [
+                real_roots(&p("x^3 - 2"), "x").unwrap(),
+                real_roots(&p("x^3 - 3"), "x").unwrap()
+            ]
+            .concat()
+        );
+        for root in &roots {
+            let Leaf(value) = &root.value else {
+                panic!("cube roots should be algebraic leaves");
+            };
+            assert!(matches!(**value, Value::Algebraic(number) if number.degree() == 3));
+        }
+    }
+}
@@ -0,0 +1,438 @@
+//! This module describes static analyses of how accurately our computational graph evaluates in
+//! floating point.
+//!
+//! [`error_bound`] bounds, to first order in the unit roundoff `u = f64::EPSILON / 2`, the
+//! relative error of evaluating an expression in `f64` for all inputs in the given ranges. Every
+//! operation but negation rounds its result once, contributing `u`, and scales the relative
+//! errors of its operands by its condition number `|x ∂f/∂x / f|`, which is bounded over the
+//! ranges with interval arithmetic. Shared subexpressions are analyzed once.
+
+use std::fmt::Display;
+
+use ahash::HashMap;
+use smallvec::smallvec;
+
+use crate::{
+    constants::Value,
+    evaluation::EvaluationError,
+    interval::{Interval, IntervalBindings},
+    provenance::{self, Step},
+    symbols::{
+        OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        OperationKind::{self, *},
+        StackVec,
+    },
+};
+
+/// Additions and subtractions that amplify the error of their operands by more than this are
+/// reported as cancellations.
+pub const CANCELLATION_THRESHOLD: f64 = 1e3;
+
+/// An addition or subtraction whose operands nearly cancel, losing significant digits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cancellation {
+    pub node: OpArgument,
+    /// A bound on the factor by which the node amplifies the relative error of its operands.
+    pub amplification: f64,
+    /// An equivalent expression that avoids the cancellation, when one is known.
+    pub suggestion: Option<OpArgument>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorReport {
+    /// An enclosure of the values the expression takes over the input ranges.
+    pub range: Interval,
+    /// A first-order bound on the relative error of evaluating the expression in `f64`. It is
+    /// infinite when the analysis cannot bound the error, e.g. when the result may be zero.
+    pub relative_error: f64,
+    pub cancellations: Vec<Cancellation>,
+}
+
+impl ErrorReport {
+    /// The number of significant bits the bound allows to be wrong.
+    pub fn bits_lost(&self) -> f64 {
+        (self.relative_error / (f64::EPSILON / 2.0)).log2().max(0.0)
+    }
+}
+
+impl Display for ErrorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "range {}, relative error <= {:.3e}",
+            self.range, self.relative_error
+        )?;
+        for cancellation in &self.cancellations {
+            write!(
+                f,
+                "\ncancellation in {} (x{:.3e})",
+                cancellation.node, cancellation.amplification
+            )?;
+            if let Some(suggestion) = &cancellation.suggestion {
+                write!(f, ", try {}", suggestion)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn unpack(node: &OpArgument) -> Option<(OperationKind, &[OpArgument])> {
+    match &node.value {
+        Op(op) => Some((op.op, &op.arguments)),
+        Leaf(_) => None,
+    }
+}
+
+fn is_integer(node: &OpArgument, k: u64) -> bool {
+    matches!(&node.value, Leaf(value) if **value == Value::integer(k))
+}
+
+/// Returns `a` if `node` is `a * a` or `a ^ 2`.
+fn square_root(node: &OpArgument) -> Option<&OpArgument> {
+    match unpack(node)? {
+        (Multiplication, [a, b]) if a == b => Some(a),
+        (Pow, [a, two]) if is_integer(two, 2) => Some(a),
+        _ => None,
+    }
+}
+
+/// Rewrites the subtraction `lhs - rhs` into an equivalent form without catastrophic
+/// cancellation, for a handful of well known patterns.
+pub(crate) fn stable_difference(lhs: &OpArgument, rhs: &OpArgument) -> Option<OpArgument> {
+    let two = || OpArgument::from(Value::integer(2));
+
+    // (a + b) - a = b and a - (a + b) = -b
+    if let Some((Addition, [a, b])) = unpack(lhs) {
+        if a == rhs {
+            return Some(b.clone());
+        } else if b == rhs {
+            return Some(a.clone());
+        }
+    }
+    if let Some((Addition, [a, b])) = unpack(rhs) {
+        if a == lhs {
+            return Some(-b);
+        } else if b == lhs {
+            return Some(-a);
+        }
+    }
+
+    // a² - b² = (a - b)(a + b)
+    if let (Some(a), Some(b)) = (square_root(lhs), square_root(rhs)) {
+        return Some((a - b) * (a + b));
+    }
+
+    // ln a - ln b = ln(a / b)
+    if let (Some((Ln, [a])), Some((Ln, [b]))) = (unpack(lhs), unpack(rhs)) {
+        return Some((a / b).ln());
+    }
+
+    // 1 - cos x = 2 sin²(x / 2)
+    let half_angle = |x: &OpArgument| two() * (x / two()).sin().pow(&two());
+    match (unpack(lhs), unpack(rhs)) {
+        (_, Some((Cos, [x]))) if is_integer(lhs, 1) => Some(half_angle(x)),
+        (Some((Cos, [x])), _) if is_integer(rhs, 1) => Some(-half_angle(x)),
+        _ => None,
+    }
+}
+
+/// Bounds `|x ∂f/∂x / f|` over the ranges of each argument `x` of `op`.
+fn condition_numbers(op: OperationKind, args: &[Interval], result: Interval) -> StackVec<f64> {
+    let two = Interval::point(2.0);
+    let factors: StackVec<f64> = match op {
+        Addition | Subtraction => {
+            let denominator = result.mignitude();
+            args.iter().map(|a| a.magnitude() / denominator).collect()
+        }
+        Multiplication | Division => smallvec![1.0, 1.0],
+        Negation => smallvec![1.0],
+        Pow => smallvec![args[1].magnitude(), (args[1] * args[0].ln()).magnitude()],
+        Exp => smallvec![args[0].magnitude()],
+        Ln => smallvec![1.0 / result.mignitude()],
+        Atan => {
+            let one = Interval::point(1.0);
+            smallvec![(args[0] / ((one + args[0] * args[0]) * result)).magnitude()]
+        }
+        Sin => smallvec![(args[0] * args[0].cos() / args[0].sin()).magnitude()],
+        Cos => smallvec![(args[0] * args[0].tan()).magnitude()],
+        Tan => smallvec![(two * args[0] / (two * args[0]).sin()).magnitude()],
+        Sign => smallvec![0.0],
+    };
+    // 0 / 0 arises for operands that are identically zero, which contribute no error.
+    factors
+        .into_iter()
+        .map(|k| if k.is_nan() { 0.0 } else { k })
+        .collect()
+}
+
+/// Whether converting `value` to `f64` rounds it.
+fn is_rounded(value: &Value) -> bool {
+    match *value {
+        Value::Rational(num, den) => den.get() != 1 || num >= 1 << f64::MANTISSA_DIGITS,
+        Value::Pi | Value::E | Value::Algebraic(_) => true,
+        Value::I | Value::Inf | Value::Undefined | Value::Variable(_) => false,
+    }
+}
+
+/// Returns `(a, b)` if `node` computes `a - b`, either directly or as `a + -b`.
+fn difference(node: &OpArgument) -> Option<(&OpArgument, &OpArgument)> {
+    match unpack(node)? {
+        (Subtraction, [a, b]) => Some((a, b)),
+        (Addition, [a, b]) => match unpack(b)? {
+            (Negation, [b]) => Some((a, b)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+struct Analyzer<'a> {
+    ranges: &'a IntervalBindings<'a>,
+    /// The range and error bound, in units of the unit roundoff, of every visited operation,
+    /// keyed by its hash.
+    memo: HashMap<u64, (Interval, f64)>,
+    cancellations: Vec<Cancellation>,
+}
+
+impl Analyzer<'_> {
+    fn analyze(&mut self, node: &OpArgument) -> Result<(Interval, f64), EvaluationError> {
+        let op = match &node.value {
+            Op(op) => op,
+            Leaf(leaf) => {
+                let range = leaf.evaluate_interval(self.ranges)?;
+                return Ok((range, if is_rounded(leaf) { 1.0 } else { 0.0 }));
+            }
+        };
+
+        let key = node.hash();
+        if let Some(&cached) = self.memo.get(&key) {
+            return Ok(cached);
+        }
+
+        let (ranges, errors): (StackVec<_>, StackVec<_>) = op
+            .arguments
+            .iter()
+            .map(|arg| self.analyze(arg))
+            .collect::<Result<StackVec<_>, _>>()?
+            .into_iter()
+            .unzip();
+
+        let range = Interval::apply(op.op, &ranges);
+        let conditions = condition_numbers(op.op, &ranges, range);
+        let rounding = if op.op == Negation { 0.0 } else { 1.0 };
+        let error = conditions
+            .iter()
+            .zip(&errors)
+            .filter(|(_, &e)| e != 0.0)
+            .map(|(k, e)| k * e)
+            .fold(rounding, |acc, term| acc + term);
+
+        if matches!(op.op, Addition | Subtraction) {
+            let amplification = conditions
+                .iter()
+                .zip(&errors)
+                .filter(|(_, &e)| e != 0.0)
+                .map(|(&k, _)| k)
+                .fold(0.0, f64::max);
+            if amplification > CANCELLATION_THRESHOLD {
+                self.cancellations.push(Cancellation {
+                    node: node.clone(),
+                    amplification,
+                    suggestion: difference(node).and_then(|(a, b)| stable_difference(a, b)),
+                });
+            }
+        }
+
+        self.memo.insert(key, (range, error));
+        Ok((range, error))
+    }
+}
+
+impl<'a> Analyzer<'a> {
+    fn new(ranges: &'a IntervalBindings<'a>) -> Self {
+        Analyzer {
+            ranges,
+            memo: HashMap::default(),
+            cancellations: Vec::new(),
+        }
+    }
+}
+
+/// Bounds the relative error of evaluating `expr` in `f64` when every variable lies in its range
+/// in `input_ranges`, and reports the subtractions that lose precision to cancellation.
+pub fn error_bound(
+    expr: &OpArgument,
+    input_ranges: &IntervalBindings,
+) -> Result<ErrorReport, EvaluationError> {
+    let mut analyzer = Analyzer::new(input_ranges);
+    let (range, error) = analyzer.analyze(expr)?;
+
+    Ok(ErrorReport {
+        range,
+        relative_error: error * f64::EPSILON / 2.0,
+        cancellations: analyzer.cancellations,
+    })
+}
+
+/// Equivalent forms of `node` that may evaluate more accurately.
+fn rewrites(node: &OpArgument) -> Vec<OpArgument> {
+    let Some((a, b)) = difference(node) else {
+        return Vec::new();
+    };
+    let mut rewrites: Vec<_> = stable_difference(a, b).into_iter().collect();
+    // (p + q) - b = p + (q - b) = (p - b) + q, giving the cancelling terms a chance to meet.
+    if let Some((Addition, [p, q])) = unpack(a) {
+        rewrites.push(p + (q - b));
+        rewrites.push((p - b) + q);
+    }
+    rewrites
+}
+
+struct Stabilizer<'a> {
+    analyzer: Analyzer<'a>,
+    memo: HashMap<u64, OpArgument>,
+}
+
+impl Stabilizer<'_> {
+    fn error(&mut self, node: &OpArgument) -> Result<f64, EvaluationError> {
+        Ok(self.analyzer.analyze(node)?.1)
+    }
+
+    fn stabilize(&mut self, node: &OpArgument) -> Result<OpArgument, EvaluationError> {
+        if let Some(stable) = self.memo.get(&node.hash()) {
+            return Ok(stable.clone());
+        }
+        node.try_map_bottom_up(|node, rebuilt| self.stabilize_root(node, rebuilt))
+    }
+
+    /// Replaces `rebuilt`, which is `node` with stable arguments, by its most accurate rewrite.
+    fn stabilize_root(
+        &mut self,
+        node: &OpArgument,
+        rebuilt: OpArgument,
+    ) -> Result<OpArgument, EvaluationError> {
+        if let Leaf(_) = rebuilt.value {
+            return Ok(rebuilt);
+        }
+        if let Some(stable) = self.memo.get(&node.hash()) {
+            return Ok(stable.clone());
+        }
+
+        let mut best_error = self.error(&rebuilt)?;
+        let mut best = rebuilt;
+        for candidate in rewrites(&best) {
+            let candidate = self.stabilize(&candidate)?;
+            let error = self.error(&candidate)?;
+            if error < best_error {
+                (best, best_error) = (candidate, error);
+            }
+        }
+        provenance::record(|| Step::Stabilize, node, &best);
+
+        self.memo.insert(node.hash(), best.clone());
+        Ok(best)
+    }
+}
+
+/// Rewrites `expr` into an equivalent expression that evaluates more accurately in `f64` when
+/// every variable lies in its range in `input_ranges`.
+///
+/// Working bottom up, each node is replaced by whichever of its known rewrites (those suggested
+/// by [`error_bound`], plus reassociations that bring cancelling terms together) has the
+/// smallest [`error_bound`]. This is a greedy, local search rather than a full equality
+/// saturation, so it only finds improvements reachable one rewrite at a time.
+pub fn stabilize(
+    expr: &OpArgument,
+    input_ranges: &IntervalBindings,
+) -> Result<OpArgument, EvaluationError> {
+    let mut stabilizer = Stabilizer {
+        analyzer: Analyzer::new(input_ranges),
+        memo: HashMap::default(),
+    };
+    stabilizer.stabilize(expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{error_bound, stabilize};
+    use crate::{
+        constants::Value,
+        interval::{Interval, IntervalBindings},
+        symbols::{variable, OpArgument},
+    };
+
+    #[test]
+    fn test_error_bound() {
+        let x = variable("x");
+        let y = variable("y");
+        let one = OpArgument::from(Value::integer(1));
+
+        let mut ranges = IntervalBindings::default();
+        ranges.insert("x", Interval::new(1e6, 1e8));
+        ranges.insert("y", Interval::new(1.0, 2.0));
+
+        // Products and quotients of exact inputs are correctly rounded, twice.
+        let report = error_bound(&(&x * &y / &y), &ranges).unwrap();
+        assert_eq!(report.relative_error, f64::EPSILON);
+        assert!(report.cancellations.is_empty());
+
+        // Subtracting exact inputs rounds once, however close they are.
+        assert!(error_bound(&(&x - &y), &ranges)
+            .unwrap()
+            .cancellations
+            .is_empty());
+
+        let cancelling = (&x + &one) - &x;
+        let report = error_bound(&cancelling, &ranges).unwrap();
+        assert_eq!(report.cancellations.len(), 1);
+        assert_eq!(report.cancellations[0].node, cancelling);
+        assert_eq!(report.cancellations[0].suggestion, Some(one.clone()));
+        assert!(report.bits_lost() > 20.0);
+
+        let squares = x.pow(&OpArgument::from(Value::integer(2))) - (&x + &y) * (&x + &y);
+        let report = error_bound(&squares, &ranges).unwrap();
+        assert_eq!(
+            report.cancellations[0].suggestion,
+            Some((&x - (&x + &y)) * (&x + (&x + &y)))
+        );
+
+        ranges.insert("x", Interval::new(-1e-4, 1e-4));
+        let report = error_bound(&(&one - x.cos()), &ranges).unwrap();
+        assert_eq!(report.relative_error, f64::INFINITY);
+        let half = &x / OpArgument::from(Value::integer(2));
+        assert!(report.cancellations[0]
+            .suggestion
+            .as_ref()
+            .is_some_and(|s| s.to_string().contains(&half.sin().to_string())));
+    }
+
+    #[test]
+    fn test_stabilize() {
+        let x = variable("x");
+        let y = variable("y");
+        let one = OpArgument::from(Value::integer(1));
+        let two = OpArgument::from(Value::integer(2));
+
+        let mut ranges = IntervalBindings::default();
+        ranges.insert("x", Interval::new(1e6, 1e8));
+        ranges.insert("y", Interval::new(1.0, 2.0));
+
+        assert_eq!(stabilize(&(&x * &y), &ranges).unwrap(), &x * &y);
+        assert_eq!(
+            stabilize(&(((&x + &one) - &x) * &y), &ranges).unwrap(),
+            &one * &y
+        );
+        // Only reachable by reassociating first.
+        assert_eq!(
+            stabilize(&((&y + &x) + &one - &x), &ranges).unwrap(),
+            &y + &one
+        );
+
+        ranges.insert("x", Interval::new(1e-3, 1e-2));
+        let stable = stabilize(&(&one - x.cos()), &ranges).unwrap();
+        assert_eq!(stable, &two * (&x / &two).sin().pow(&two));
+        let report = error_bound(&stable, &ranges).unwrap();
+        assert!(report.relative_error < 1e-14);
+    }
+}
@@ -0,0 +1,120 @@
+//! This module describes renaming the variables of an expression, so that it can be shared in a
+//! bug report without revealing what it models.
+//!
+//! [`anonymize`] renames the variables `x1`, `x2`, ... in the order they first appear, reading
+//! left to right, and changes nothing else, so the anonymized expression behaves exactly as the
+//! original did. The renaming is kept, so that results computed from the anonymized expression
+//! can be translated back with [`Anonymized::restore`].
+
+use ahash::HashSet;
+
+use crate::{
+    constants::Value,
+    rewrite::Substitution,
+    symbols::{
+        intern, variable, OpArgument,
+        OpArgumentKind::{Leaf, Op},
+    },
+};
+
+/// An expression with its variables renamed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Anonymized {
+    expr: OpArgument,
+    names: Vec<(&'static str, &'static str)>,
+}
+
+impl Anonymized {
+    /// The expression, in terms of the new names.
+    pub fn expr(&self) -> &OpArgument {
+        &self.expr
+    }
+
+    /// The original name and the new name of every variable, in order of the new names.
+    pub fn names(&self) -> &[(&'static str, &'static str)] {
+        &self.names
+    }
+
+    /// `expr`, written in terms of the new names, with the original names put back.
+    pub fn restore(&self, expr: &OpArgument) -> OpArgument {
+        let substitution = self
+            .names
+            .iter()
+            .map(|&(original, new)| (new, variable(original)))
+            .collect::<Substitution>();
+        expr.substitute(&substitution)
+    }
+}
+
+/// Collects the variables of `node` in the order they first appear, visiting each shared
+/// subexpression once.
+fn collect(node: &OpArgument, seen: &mut HashSet<u64>, order: &mut Vec<&'static str>) {
+    if !seen.insert(node.hash()) {
+        return;
+    }
+    match &node.value {
+        Leaf(value) => {
+            if let Value::Variable(name) = **value {
+                order.push(name);
+            }
+        }
+        Op(op) => op
+            .arguments
+            .iter()
+            .for_each(|arg| collect(arg, seen, order)),
+    }
+}
+
+/// Renames the variables of `expr` to `x1`, `x2`, ... in the order they first appear.
+pub fn anonymize(expr: &OpArgument) -> Anonymized {
+    let mut order = Vec::new();
+    collect(expr, &mut HashSet::default(), &mut order);
+    let names = order
+        .into_iter()
+        .enumerate()
+        .map(|(i, original)| (original, intern(&format!("x{}", i + 1))))
+        .collect::<Vec<_>>();
+    let substitution = names
+        .iter()
+        .map(|&(original, new)| (original, variable(new)))
+        .collect::<Substitution>();
+    Anonymized {
+        expr: expr.substitute(&substitution),
+        names,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::anonymize;
+    use crate::parse::parse;
+
+    #[test]
+    fn test_anonymize() {
+        let p = |input: &str| parse(input).unwrap();
+
+        let expr = p("revenue * exp(-churn * t) + x1 / revenue - sin(t)");
+        let anonymized = anonymize(&expr);
+        assert_eq!(
+            anonymized.expr(),
+            &p("x1 * exp(-x2 * x3) + x4 / x1 - sin(x3)")
+        );
+        assert_eq!(
+            anonymized.names(),
+            [
+                ("revenue", "x1"),
+                ("churn", "x2"),
+                ("t", "x3"),
+                ("x1", "x4")
+            ]
+        );
+        assert_eq!(anonymized.restore(anonymized.expr()), expr);
+        assert_eq!(
+            anonymized.restore(&anonymized.expr().derivative("x3")),
+            expr.derivative("t")
+        );
+
+        let constant = p("2 * pi");
+        assert_eq!(anonymize(&constant).expr(), &constant);
+    }
+}
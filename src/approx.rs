@@ -0,0 +1,487 @@
+//! This module describes how to approximate expressions by polynomials on an interval.
+//!
+//! [`chebyshev`] interpolates an expression at the Chebyshev nodes of the interval, which is
+//! within a small factor of the best polynomial approximation of its degree. [`minimax`]
+//! refines that with the Remez exchange algorithm until the error equioscillates, which makes
+//! it the polynomial with the smallest maximum error. Either way the result is an exact
+//! polynomial in the variable, together with the largest error it was measured to have.
+//!
+//! [`pade`] instead matches the Taylor series of an expression at a point with a rational
+//! function, which keeps approximating it well much further from the point than the truncated
+//! series, especially towards a pole.
+
+use std::{f64::consts::PI, fmt::Display};
+
+use crate::{
+    constants::Value,
+    continued_fraction::signed_rational,
+    evaluation::{Bindings, EvaluationError},
+    interval::Interval,
+    recurrence::determinant,
+    series::{Series, SeriesError},
+    symbols::{variable, OpArgument},
+};
+
+/// Coefficients are snapped to rationals with at most this denominator.
+pub(crate) const COEFFICIENT_DENOMINATOR: u64 = 1 << 48;
+
+/// The error is measured at this many points per degree of freedom of the polynomial.
+const SAMPLES_PER_COEFFICIENT: usize = 64;
+
+/// The reasons an approximation can fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ApproxError {
+    Evaluation(EvaluationError),
+    /// The range is empty, a single point, or unbounded.
+    InvalidRange(Interval),
+    /// The expression is not finite at this point of the range.
+    NotFinite(f64),
+    /// A coefficient is too large to be represented exactly.
+    Unrepresentable(f64),
+    Series(SeriesError),
+    /// The Taylor coefficients admit no approximant of the requested degrees.
+    Singular,
+}
+
+impl Display for ApproxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApproxError::Evaluation(err) => err.fmt(f),
+            ApproxError::InvalidRange(range) => {
+                write!(f, "cannot approximate on [{}, {}]", range.lo, range.hi)
+            }
+            ApproxError::NotFinite(x) => write!(f, "the expression is not finite at {}", x),
+            ApproxError::Unrepresentable(c) => {
+                write!(f, "the coefficient {} is too large to represent", c)
+            }
+            ApproxError::Series(err) => err.fmt(f),
+            ApproxError::Singular => f.write_str("no approximant of these degrees exists"),
+        }
+    }
+}
+
+impl std::error::Error for ApproxError {}
+
+impl From<EvaluationError> for ApproxError {
+    fn from(err: EvaluationError) -> Self {
+        ApproxError::Evaluation(err)
+    }
+}
+
+impl From<SeriesError> for ApproxError {
+    fn from(err: SeriesError) -> Self {
+        ApproxError::Series(err)
+    }
+}
+
+/// A polynomial approximating an expression on a range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Approximation {
+    pub polynomial: OpArgument,
+    /// The largest absolute error of `polynomial` found on the range.
+    pub max_error: f64,
+}
+
+/// Evaluates an expression in one variable, in the coordinate `t ∈ [-1, 1]` of the range.
+struct Target<'e> {
+    expr: &'e OpArgument,
+    var: &'static str,
+    range: Interval,
+}
+
+impl Target<'_> {
+    fn x(&self, t: f64) -> f64 {
+        let (lo, hi) = (self.range.lo, self.range.hi);
+        (lo + hi) / 2.0 + t * (hi - lo) / 2.0
+    }
+
+    fn at(&self, t: f64) -> Result<f64, ApproxError> {
+        let x = self.x(t);
+        let y = self.expr.evaluate(&Bindings::from_iter([(self.var, x)]))?;
+        match y.is_finite() {
+            true => Ok(y),
+            false => Err(ApproxError::NotFinite(x)),
+        }
+    }
+}
+
+/// Evaluates the polynomial with `coefficients`, lowest degree first, at `t`.
+fn horner(coefficients: &[f64], t: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |acc, c| acc * t + c)
+}
+
+/// The coefficients of the Chebyshev interpolant of degree `degree`, in the monomial basis.
+fn interpolate(target: &Target, degree: usize) -> Result<Vec<f64>, ApproxError> {
+    let n = degree + 1;
+    let angle = |j: usize| PI * (j as f64 + 0.5) / n as f64;
+    let values = (0..n)
+        .map(|j| target.at(angle(j).cos()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut monomial = vec![0.0; n];
+    // T₀, and T₁ once the loop has run, as monomial coefficients.
+    let (mut previous, mut current) = (vec![0.0; n], vec![0.0; n]);
+    current[0] = 1.0;
+    for k in 0..n {
+        let weight = match k {
+            0 => 1.0,
+            _ => 2.0,
+        } / n as f64;
+        let c = weight
+            * values
+                .iter()
+                .enumerate()
+                .map(|(j, f)| f * (k as f64 * angle(j)).cos())
+                .sum::<f64>();
+        monomial
+            .iter_mut()
+            .zip(&current)
+            .for_each(|(m, t)| *m += c * t);
+
+        // T_{k+1} = 2t·T_k - T_{k-1}, with T₁ = t.
+        let mut next = vec![0.0; n];
+        for i in 0..n {
+            let shifted = if i > 0 { current[i - 1] } else { 0.0 };
+            next[i] = match k {
+                0 => shifted,
+                _ => 2.0 * shifted - previous[i],
+            };
+        }
+        (previous, current) = (current, next);
+    }
+    Ok(monomial)
+}
+
+/// Solves `matrix · x = rhs` by Gaussian elimination with partial pivoting.
+pub(crate) fn solve_linear(mut matrix: Vec<Vec<f64>>, mut rhs: Vec<f64>) -> Option<Vec<f64>> {
+    let n = rhs.len();
+    for col in 0..n {
+        let pivot =
+            (col..n).max_by(|&a, &b| matrix[a][col].abs().total_cmp(&matrix[b][col].abs()))?;
+        if matrix[pivot][col] == 0.0 {
+            return None;
+        }
+        matrix.swap(col, pivot);
+        rhs.swap(col, pivot);
+        for row in col + 1..n {
+            let (above, below) = matrix.split_at_mut(row);
+            let factor = below[0][col] / above[col][col];
+            below[0][col..]
+                .iter_mut()
+                .zip(&above[col][col..])
+                .for_each(|(a, p)| *a -= factor * p);
+            rhs[row] -= factor * rhs[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let known = (row + 1..n).map(|k| matrix[row][k] * x[k]).sum::<f64>();
+        x[row] = (rhs[row] - known) / matrix[row][row];
+    }
+    Some(x)
+}
+
+/// The points of the unit interval the error is measured at.
+fn grid(degree: usize) -> Vec<f64> {
+    let count = SAMPLES_PER_COEFFICIENT * (degree + 2);
+    (0..=count)
+        .map(|i| -(PI * i as f64 / count as f64).cos())
+        .collect()
+}
+
+/// Picks `count` points of alternating error sign, each the extremum of its run of `grid`.
+fn alternation(grid: &[f64], errors: &[f64], count: usize) -> Option<Vec<f64>> {
+    let mut extrema: Vec<(f64, f64)> = Vec::new();
+    for (&t, &e) in grid.iter().zip(errors) {
+        match extrema.last_mut() {
+            Some((_, last)) if last.signum() == e.signum() => {
+                if e.abs() > last.abs() {
+                    *extrema.last_mut()? = (t, e);
+                }
+            }
+            _ => extrema.push((t, e)),
+        }
+    }
+    // Drop the smaller end until exactly `count` alternating points remain.
+    while extrema.len() > count {
+        match extrema[0].1.abs() < extrema[extrema.len() - 1].1.abs() {
+            true => extrema.remove(0),
+            false => extrema.pop()?,
+        };
+    }
+    (extrema.len() == count).then(|| extrema.into_iter().map(|(t, _)| t).collect())
+}
+
+/// One Remez exchange: the polynomial whose error alternates with equal magnitude on
+/// `reference`.
+fn exchange(target: &Target, reference: &[f64]) -> Result<Option<Vec<f64>>, ApproxError> {
+    let n = reference.len() - 1;
+    let matrix = reference
+        .iter()
+        .enumerate()
+        .map(|(i, &t)| {
+            let mut row = (0..n).map(|k| t.powi(k as i32)).collect::<Vec<_>>();
+            row.push(if i % 2 == 0 { 1.0 } else { -1.0 });
+            row
+        })
+        .collect();
+    let rhs = reference
+        .iter()
+        .map(|&t| target.at(t))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(solve_linear(matrix, rhs).map(|mut solution| {
+        solution.pop();
+        solution
+    }))
+}
+
+/// Rewrites the polynomial in `t = αx + β` as a polynomial in `x`.
+fn compose(coefficients: &[f64], alpha: f64, beta: f64) -> Vec<f64> {
+    let mut result = vec![0.0; coefficients.len()];
+    for &c in coefficients.iter().rev() {
+        // result ← result·(αx + β) + c
+        let mut next = vec![0.0; coefficients.len()];
+        for (k, r) in result.iter().enumerate() {
+            next[k] += r * beta;
+            if k + 1 < next.len() {
+                next[k + 1] += r * alpha;
+            }
+        }
+        next[0] += c;
+        result = next;
+    }
+    result
+}
+
+fn constant(c: f64) -> Result<OpArgument, ApproxError> {
+    signed_rational(c, COEFFICIENT_DENOMINATOR).ok_or(ApproxError::Unrepresentable(c))
+}
+
+/// Builds the approximation from its coefficients in `t`, measuring its error on the range.
+fn approximation(target: &Target, coefficients: &[f64]) -> Result<Approximation, ApproxError> {
+    let (lo, hi) = (target.range.lo, target.range.hi);
+    let alpha = 2.0 / (hi - lo);
+    let in_x = compose(coefficients, alpha, -(lo + hi) / (hi - lo));
+
+    let x = variable(target.var);
+    let zero = OpArgument::from(Value::integer(0));
+    let mut polynomial: Option<OpArgument> = None;
+    for (k, &c) in in_x.iter().enumerate() {
+        let c = constant(c)?;
+        if c == zero {
+            continue;
+        }
+        let term = match k {
+            0 => c,
+            1 => c * &x,
+            k => c * x.pow(&Value::integer(k as u64).into()),
+        };
+        polynomial = Some(match polynomial {
+            Some(sum) => sum + term,
+            None => term,
+        });
+    }
+    let polynomial = polynomial.unwrap_or(zero);
+
+    let mut max_error: f64 = 0.0;
+    for t in grid(coefficients.len()) {
+        let bindings = Bindings::from_iter([(target.var, target.x(t))]);
+        max_error = max_error.max((polynomial.evaluate(&bindings)? - target.at(t)?).abs());
+    }
+    Ok(Approximation {
+        polynomial,
+        max_error,
+    })
+}
+
+fn target<'e>(
+    expr: &'e OpArgument,
+    var: &'static str,
+    range: Interval,
+) -> Result<Target<'e>, ApproxError> {
+    match range.lo.is_finite() && range.hi.is_finite() && range.lo < range.hi {
+        true => Ok(Target { expr, var, range }),
+        false => Err(ApproxError::InvalidRange(range)),
+    }
+}
+
+/// Approximates `expr` as a polynomial of degree `degree` in `var` on `range`, by interpolating
+/// at the Chebyshev nodes.
+pub fn chebyshev(
+    expr: &OpArgument,
+    var: &'static str,
+    range: Interval,
+    degree: usize,
+) -> Result<Approximation, ApproxError> {
+    let target = target(expr, var, range)?;
+    approximation(&target, &interpolate(&target, degree)?)
+}
+
+/// Like [`chebyshev`], but refined by up to `iterations` steps of the Remez exchange algorithm
+/// towards the polynomial with the smallest maximum error. It is never worse than
+/// [`chebyshev`].
+pub fn minimax(
+    expr: &OpArgument,
+    var: &'static str,
+    range: Interval,
+    degree: usize,
+    iterations: usize,
+) -> Result<Approximation, ApproxError> {
+    let target = target(expr, var, range)?;
+    let grid = grid(degree);
+    let values = grid
+        .iter()
+        .map(|&t| target.at(t))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut coefficients = interpolate(&target, degree)?;
+    let mut best = approximation(&target, &coefficients)?;
+    for _ in 0..iterations {
+        let errors = grid
+            .iter()
+            .zip(&values)
+            .map(|(&t, f)| horner(&coefficients, t) - f)
+            .collect::<Vec<_>>();
+        let Some(reference) = alternation(&grid, &errors, degree + 2) else {
+            break;
+        };
+        let Some(refined) = exchange(&target, &reference)? else {
+            break;
+        };
+        coefficients = refined;
+        let candidate = approximation(&target, &coefficients)?;
+        if candidate.max_error < best.max_error {
+            best = candidate;
+        }
+    }
+    Ok(best)
+}
+
+/// The Padé approximant `P/Q` of `expr` in `var` around `point`, with `P` of degree `m` and
+/// `Q` of degree `n`, whose Taylor series agrees with that of `expr` up to degree `m + n`.
+pub fn pade(
+    expr: &OpArgument,
+    var: &'static str,
+    point: &OpArgument,
+    (m, n): (usize, usize),
+) -> Result<OpArgument, ApproxError> {
+    let series = expr.taylor(var, point, m + n + 1)?;
+    let zero = OpArgument::from(Value::integer(0));
+    let c = |i: usize, j: usize| match i.checked_sub(j) {
+        Some(k) => series.coefficients()[k].clone(),
+        None => zero.clone(),
+    };
+
+    // The denominator q₀ = 1, q₁, …, qₙ cancels the terms of degree m + 1 to m + n of Q·f,
+    // which is a linear system solved by Cramer's rule.
+    let matrix = (1..=n)
+        .map(|k| (1..=n).map(|j| c(m + k, j)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut denominator = vec![OpArgument::from(Value::integer(1))];
+    if n > 0 {
+        let det = determinant(&matrix).fold();
+        if det == zero {
+            return Err(ApproxError::Singular);
+        }
+        for j in 0..n {
+            let mut replaced = matrix.clone();
+            for (k, row) in replaced.iter_mut().enumerate() {
+                row[j] = -c(m + k + 1, 0);
+            }
+            denominator.push((determinant(&replaced) / &det).fold());
+        }
+    }
+    let numerator = (0..=m)
+        .map(|k| {
+            (0..=k.min(n))
+                .map(|j| &denominator[j] * c(k, j))
+                .reduce(|acc, term| acc + term)
+                .expect("Oops, q₀ contributes to every coefficient")
+        })
+        .collect::<Vec<_>>();
+
+    let x = variable(var);
+    let t = match point.fold() == zero {
+        true => x,
+        false => x - point,
+    };
+    let numerator = Series::new(numerator).to_polynomial(&t);
+    Ok(match n {
+        0 => numerator,
+        _ => numerator / Series::new(denominator).to_polynomial(&t),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chebyshev, minimax, pade, ApproxError};
+    use crate::{evaluation::Bindings, interval::Interval, parse::parse};
+
+    #[test]
+    fn test_polynomial_approximation() {
+        let exp = parse("exp(x)").unwrap();
+        let unit = Interval::new(0.0, 1.0);
+
+        let interpolant = chebyshev(&exp, "x", unit, 5).unwrap();
+        assert!(interpolant.max_error < 2e-6, "{}", interpolant.max_error);
+        let at = |x: f64| Bindings::from_iter([("x", x)]);
+        for x in [0.0, 0.3, 0.75, 1.0] {
+            let error = (interpolant.polynomial.evaluate(&at(x)).unwrap() - x.exp()).abs();
+            assert!(error <= interpolant.max_error * 1.01);
+        }
+
+        let best = minimax(&exp, "x", unit, 5, 8).unwrap();
+        assert!(best.max_error <= interpolant.max_error);
+        // The minimax error of e^x with degree 5 on [0, 1] is about 1.13e-6.
+        assert!(best.max_error < 1.135e-6, "{}", best.max_error);
+
+        // A polynomial is reproduced exactly.
+        let cubic = parse("2*x^3 - x + 1/4").unwrap();
+        let exact = chebyshev(&cubic, "x", Interval::new(-2.0, 3.0), 3).unwrap();
+        assert!(exact.max_error < 1e-9);
+
+        let sine = minimax(
+            &parse("sin(x)").unwrap(),
+            "x",
+            Interval::new(-2.0, 2.0),
+            7,
+            8,
+        );
+        assert!(sine.unwrap().max_error < 1e-4);
+
+        assert_eq!(
+            chebyshev(&exp, "x", Interval::new(1.0, 1.0), 3),
+            Err(ApproxError::InvalidRange(Interval::new(1.0, 1.0)))
+        );
+        assert!(matches!(
+            chebyshev(&parse("ln(x)").unwrap(), "x", Interval::new(-1.0, 1.0), 3),
+            Err(ApproxError::NotFinite(x)) if x < 0.0
+        ));
+
+        // The [2/2] approximant of e^x is (12 + 6x + x²) / (12 - 6x + x²).
+        let zero = parse("0").unwrap();
+        let approximant = pade(&exp, "x", &zero, (2, 2)).unwrap();
+        for x in [-1.0, 0.5, 2.0] {
+            let expected = (12.0 + 6.0 * x + x * x) / (12.0 - 6.0 * x + x * x);
+            let actual = approximant.evaluate(&at(x)).unwrap();
+            assert!(
+                (actual - expected).abs() < 1e-12,
+                "{} at {}",
+                approximant,
+                x
+            );
+        }
+        // Around 1, ln(x) ≈ (x - 1) / (1 + (x - 1)/2), which beats its series of the same order
+        // far from 1.
+        let one = parse("1").unwrap();
+        let ln = pade(&parse("ln(x)").unwrap(), "x", &one, (1, 1)).unwrap();
+        let value = ln.evaluate(&at(3.0)).unwrap();
+        assert!((value - 1.0).abs() < 1e-12);
+        let series = 2.0 - 2f64.powi(2) / 2.0;
+        assert!((value - 3f64.ln()).abs() < (series - 3f64.ln()).abs());
+        assert_eq!(
+            pade(&parse("cos(x)").unwrap(), "x", &zero, (1, 1)),
+            Err(ApproxError::Singular)
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! This module describes how to evaluate expressions elementwise over n-dimensional arrays.
+//!
+//! Arrays bound to the variables of an expression broadcast against each other the way NumPy's
+//! do, and the expression is compiled once and run on every element of the broadcast shape.
+//! With the `ndarray` feature, [`evaluate_ndarray`] does this for `ArrayD<f64>` directly.
+//! Without it, the row-major [`Array`] plays the same part, so the crate can evaluate over
+//! arrays without depending on `ndarray`.
+
+use std::fmt::Display;
+
+use ahash::HashMap;
+
+#[cfg(feature = "ndarray")]
+use ndarray::{ArrayD, IxDyn};
+
+use crate::{codegen::CodegenError, compile::compile, symbols::OpArgument};
+
+/// The reasons an array can fail to be built or evaluated.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArrayError {
+    Codegen(CodegenError),
+    /// The data does not have as many elements as the shape.
+    Length {
+        shape: Vec<usize>,
+        len: usize,
+    },
+    /// Two shapes cannot be broadcast against each other.
+    Incompatible(Vec<usize>, Vec<usize>),
+}
+
+impl Display for ArrayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArrayError::Codegen(err) => err.fmt(f),
+            ArrayError::Length { shape, len } => {
+                write!(f, "{} elements do not fill the shape {:?}", len, shape)
+            }
+            ArrayError::Incompatible(a, b) => {
+                write!(f, "the shapes {:?} and {:?} do not broadcast", a, b)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ArrayError {}
+
+impl From<CodegenError> for ArrayError {
+    fn from(err: CodegenError) -> Self {
+        ArrayError::Codegen(err)
+    }
+}
+
+/// An n-dimensional array of `f64`, stored in row-major order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Array {
+    shape: Vec<usize>,
+    data: Vec<f64>,
+}
+
+impl Array {
+    pub fn new(shape: Vec<usize>, data: Vec<f64>) -> Result<Self, ArrayError> {
+        if shape.iter().product::<usize>() != data.len() {
+            return Err(ArrayError::Length {
+                shape,
+                len: data.len(),
+            });
+        }
+        Ok(Array { shape, data })
+    }
+
+    /// The zero-dimensional array holding `x`.
+    pub fn scalar(x: f64) -> Self {
+        Array {
+            shape: Vec::new(),
+            data: vec![x],
+        }
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    pub fn data(&self) -> &[f64] {
+        &self.data
+    }
+
+    /// The shape and the row-major data.
+    pub fn into_raw(self) -> (Vec<usize>, Vec<f64>) {
+        (self.shape, self.data)
+    }
+
+    /// The element at `index`, if it is in bounds.
+    pub fn get(&self, index: &[usize]) -> Option<f64> {
+        if index.len() != self.shape.len() || index.iter().zip(&self.shape).any(|(i, n)| i >= n) {
+            return None;
+        }
+        let offset = index
+            .iter()
+            .zip(&self.shape)
+            .fold(0, |offset, (i, n)| offset * n + i);
+        Some(self.data[offset])
+    }
+}
+
+/// The strides of an array of shape `from` viewed with the shape `shape` it broadcasts to, with
+/// a zero stride along every broadcast axis.
+fn strides(from: &[usize], shape: &[usize]) -> Vec<usize> {
+    let mut strides = vec![0; shape.len()];
+    let mut stride = 1;
+    for (axis, &n) in from.iter().enumerate().rev() {
+        if n != 1 {
+            strides[shape.len() - from.len() + axis] = stride;
+        }
+        stride *= n;
+    }
+    strides
+}
+
+/// The shape `a` and `b` broadcast to: aligned from the last axis, each pair of lengths must
+/// agree or one of them must be 1.
+fn broadcast(a: &[usize], b: &[usize]) -> Result<Vec<usize>, ArrayError> {
+    let rank = a.len().max(b.len());
+    let axis = |shape: &[usize], k: usize| match (k + shape.len()).checked_sub(rank) {
+        Some(i) => shape[i],
+        None => 1,
+    };
+    (0..rank)
+        .map(|k| match (axis(a, k), axis(b, k)) {
+            (m, n) if m == n || n == 1 => Ok(m),
+            (1, n) => Ok(n),
+            _ => Err(ArrayError::Incompatible(a.to_vec(), b.to_vec())),
+        })
+        .collect()
+}
+
+/// Evaluates `expr` on every element of its `bindings` broadcast against each other. Every
+/// variable of `expr` must be bound.
+pub fn evaluate_array(
+    expr: &OpArgument,
+    bindings: &HashMap<&'static str, Array>,
+) -> Result<Array, ArrayError> {
+    let inputs = bindings
+        .iter()
+        .map(|(&name, array)| (name, array.shape(), array.data()))
+        .collect();
+    let (shape, data) = evaluate_broadcast(expr, inputs)?;
+    Ok(Array { shape, data })
+}
+
+/// Evaluates `expr` on every element of its `bindings` broadcast against each other, like
+/// [`evaluate_array`]. Arrays already in standard layout are read in place.
+#[cfg(feature = "ndarray")]
+pub fn evaluate_ndarray(
+    expr: &OpArgument,
+    bindings: &HashMap<&'static str, ArrayD<f64>>,
+) -> Result<ArrayD<f64>, ArrayError> {
+    let standard = bindings
+        .iter()
+        .map(|(&name, array)| (name, array.as_standard_layout()))
+        .collect::<Vec<_>>();
+    let inputs = standard
+        .iter()
+        .map(|(name, array)| {
+            let data = array.as_slice().expect("standard layout is contiguous");
+            (*name, array.shape(), data)
+        })
+        .collect();
+    let (shape, data) = evaluate_broadcast(expr, inputs)?;
+    Ok(ArrayD::from_shape_vec(IxDyn(&shape), data).expect("the data fills the broadcast shape"))
+}
+
+#[cfg(feature = "ndarray")]
+impl From<ArrayD<f64>> for Array {
+    fn from(array: ArrayD<f64>) -> Self {
+        let shape = array.shape().to_vec();
+        let data = array.as_standard_layout().iter().copied().collect();
+        Array { shape, data }
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl From<Array> for ArrayD<f64> {
+    fn from(array: Array) -> Self {
+        ArrayD::from_shape_vec(IxDyn(&array.shape), array.data)
+            .expect("an array's data fills its shape")
+    }
+}
+
+/// Evaluates `expr` over row-major `inputs` of the given shapes broadcast against each other,
+/// returning the broadcast shape and the row-major results.
+fn evaluate_broadcast(
+    expr: &OpArgument,
+    mut inputs: Vec<(&'static str, &[usize], &[f64])>,
+) -> Result<(Vec<usize>, Vec<f64>), ArrayError> {
+    inputs.sort_unstable_by_key(|(name, _, _)| *name);
+    let params = inputs.iter().map(|(name, _, _)| *name).collect::<Vec<_>>();
+    let program = compile(expr, &params)?;
+
+    let shape = inputs
+        .iter()
+        .try_fold(Vec::new(), |shape, (_, from, _)| broadcast(&shape, from))?;
+    let strides = inputs
+        .iter()
+        .map(|(_, from, _)| strides(from, &shape))
+        .collect::<Vec<_>>();
+    let len = shape.iter().product::<usize>();
+
+    let mut index = vec![0; shape.len()];
+    let mut offsets = vec![0; inputs.len()];
+    let mut params = vec![0.0; inputs.len()];
+    let (mut registers, mut out) = (Vec::new(), [0.0]);
+    let mut data = Vec::with_capacity(len);
+    for _ in 0..len {
+        for ((param, (_, _, input)), &offset) in params.iter_mut().zip(&inputs).zip(&offsets) {
+            *param = input[offset];
+        }
+        program.evaluate_into(&params, &mut registers, &mut out);
+        data.push(out[0]);
+
+        // Step the index like an odometer, moving every input's offset along with it.
+        for axis in (0..shape.len()).rev() {
+            index[axis] += 1;
+            for (offset, strides) in offsets.iter_mut().zip(&strides) {
+                *offset += strides[axis];
+            }
+            if index[axis] < shape[axis] {
+                break;
+            }
+            for (offset, strides) in offsets.iter_mut().zip(&strides) {
+                *offset -= strides[axis] * shape[axis];
+            }
+            index[axis] = 0;
+        }
+    }
+    Ok((shape, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use ahash::HashMap;
+
+    use super::{evaluate_array, Array, ArrayError};
+    use crate::{codegen::CodegenError, parse::parse};
+
+    #[test]
+    fn test_evaluate_array() {
+        let p = |input: &str| parse(input).unwrap();
+        let column = Array::new(vec![3, 1], vec![1.0, 2.0, 3.0]).unwrap();
+        let row = Array::new(vec![2], vec![10.0, 20.0]).unwrap();
+        let bindings = HashMap::from_iter([("x", column.clone()), ("y", row.clone())]);
+
+        // An outer sum, like x[:, None] + y in NumPy.
+        let sum = evaluate_array(&p("x + y"), &bindings).unwrap();
+        assert_eq!(sum.shape(), [3, 2]);
+        assert_eq!(sum.data(), [11.0, 21.0, 12.0, 22.0, 13.0, 23.0]);
+        assert_eq!(sum.get(&[2, 1]), Some(23.0));
+        assert_eq!(sum.get(&[3, 0]), None);
+
+        let scaled = HashMap::from_iter([("x", column.clone()), ("k", Array::scalar(2.0))]);
+        let doubled = evaluate_array(&p("k * x^2"), &scaled).unwrap();
+        assert_eq!(doubled.into_raw(), (vec![3, 1], vec![2.0, 8.0, 18.0]));
+
+        let constant = evaluate_array(&p("2 + 3"), &HashMap::default()).unwrap();
+        assert_eq!(constant, Array::scalar(5.0));
+
+        let long = Array::new(vec![3], vec![0.0; 3]).unwrap();
+        assert_eq!(
+            evaluate_array(&p("x + y"), &HashMap::from_iter([("x", row), ("y", long)])),
+            Err(ArrayError::Incompatible(vec![2], vec![3]))
+        );
+        assert_eq!(
+            evaluate_array(&p("x + z"), &bindings),
+            Err(ArrayError::Codegen(CodegenError::UnknownVariable("z")))
+        );
+        assert_eq!(
+            Array::new(vec![2, 2], vec![1.0]),
+            Err(ArrayError::Length {
+                shape: vec![2, 2],
+                len: 1
+            })
+        );
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_evaluate_ndarray() {
+        use ndarray::{arr1, arr2, ArrayD, IxDyn};
+
+        use super::evaluate_ndarray;
+
+        let p = |input: &str| parse(input).unwrap();
+        let grid = arr2(&[[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]).into_dyn();
+        let row = arr1(&[10.0, 20.0, 30.0]).into_dyn();
+        // A transposed view is not in standard layout, so it is copied before evaluating.
+        let column = arr2(&[[1.0, -1.0]]).reversed_axes().into_dyn();
+        let bindings = HashMap::from_iter([("x", grid), ("y", row), ("z", column)]);
+
+        let result = evaluate_ndarray(&p("x * z + y"), &bindings).unwrap();
+        let expected = arr2(&[[11.0, 22.0, 33.0], [6.0, 15.0, 24.0]]).into_dyn();
+        assert_eq!(result, expected);
+
+        let array = Array::from(bindings["z"].clone());
+        assert_eq!(array.shape(), [2, 1]);
+        assert_eq!(array.data(), [1.0, -1.0]);
+        assert_eq!(ArrayD::from(array), bindings["z"]);
+
+        let scalar = evaluate_ndarray(&p("2^3"), &HashMap::default()).unwrap();
+        assert_eq!(scalar, ArrayD::from_elem(IxDyn(&[]), 8.0));
+        assert_eq!(
+            evaluate_ndarray(&p("x + w"), &bindings),
+            Err(ArrayError::Codegen(CodegenError::UnknownVariable("w")))
+        );
+    }
+}
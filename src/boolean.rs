@@ -0,0 +1,273 @@
+//! This module describes boolean combinations of conditions.
+//!
+//! A [`Formula`] combines atoms of any type, such as [`crate::condition::Condition`]s or
+//! [`crate::linear::LinearConstraint`]s, with negation, conjunction and disjunction.
+//! [`Formula::simplify`] rewrites a formula as a minimal sum of products by Quine–McCluskey:
+//! the truth table over its distinct atoms is reduced to its prime implicants, and a smallest
+//! cover of those is kept, so conditions built up piece by piece shed their redundant clauses.
+//! Atoms are treated as independent, so `x > 0 ∧ x > 1` is not simplified to `x > 1`.
+
+use std::fmt::Display;
+
+/// Formulas with more distinct atoms than this are only flattened by [`Formula::simplify`],
+/// since their truth tables grow exponentially.
+const MAX_ATOMS: usize = 12;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Formula<A> {
+    True,
+    False,
+    Atom(A),
+    Not(Box<Formula<A>>),
+    And(Vec<Formula<A>>),
+    Or(Vec<Formula<A>>),
+}
+
+/// A product of literals, with a bit per atom: `mask` marks the atoms that do not appear and
+/// `value` the ones that appear unnegated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Implicant {
+    mask: u32,
+    value: u32,
+}
+
+impl Implicant {
+    fn covers(&self, minterm: u32) -> bool {
+        minterm & !self.mask == self.value
+    }
+}
+
+/// The prime implicants of the function true exactly on `minterms`.
+fn prime_implicants(minterms: &[u32]) -> Vec<Implicant> {
+    let mut current = minterms
+        .iter()
+        .map(|&value| Implicant { mask: 0, value })
+        .collect::<Vec<_>>();
+    let mut primes = Vec::new();
+    while !current.is_empty() {
+        let mut combined = vec![false; current.len()];
+        let mut next = Vec::new();
+        for i in 0..current.len() {
+            for j in i + 1..current.len() {
+                let (a, b) = (current[i], current[j]);
+                let difference = a.value ^ b.value;
+                if a.mask == b.mask && difference.count_ones() == 1 {
+                    combined[i] = true;
+                    combined[j] = true;
+                    next.push(Implicant {
+                        mask: a.mask | difference,
+                        value: a.value & !difference,
+                    });
+                }
+            }
+        }
+        primes.extend(
+            current
+                .iter()
+                .zip(&combined)
+                .filter(|(_, &combined)| !combined)
+                .map(|(&implicant, _)| implicant),
+        );
+        next.sort_unstable();
+        next.dedup();
+        current = next;
+    }
+    primes
+}
+
+/// A small set of `primes` covering every minterm: essential ones first, then greedily.
+fn cover(minterms: &[u32], primes: &[Implicant]) -> Vec<Implicant> {
+    let mut uncovered = minterms.to_vec();
+    let mut chosen = Vec::new();
+    while !uncovered.is_empty() {
+        let essential = uncovered.iter().find_map(|&m| {
+            let mut covering = primes.iter().filter(|p| p.covers(m));
+            let first = covering.next();
+            covering.next().is_none().then_some(first).flatten()
+        });
+        let best = essential.or_else(|| {
+            primes.iter().max_by_key(|p| {
+                let count = uncovered.iter().filter(|&&m| p.covers(m)).count();
+                // Prefer fewer literals, then the first prime, among equal counts.
+                (count, p.mask.count_ones(), std::cmp::Reverse(**p))
+            })
+        });
+        let best = *best.expect("Oops, every minterm is covered by some prime implicant");
+        uncovered.retain(|&m| !best.covers(m));
+        chosen.push(best);
+    }
+    chosen
+}
+
+impl<A: Clone + PartialEq> Formula<A> {
+    pub fn atom(atom: A) -> Self {
+        Formula::Atom(atom)
+    }
+
+    pub fn not(&self) -> Self {
+        Formula::Not(Box::new(self.clone()))
+    }
+
+    pub fn and(&self, other: &Formula<A>) -> Self {
+        Formula::And(vec![self.clone(), other.clone()])
+    }
+
+    pub fn or(&self, other: &Formula<A>) -> Self {
+        Formula::Or(vec![self.clone(), other.clone()])
+    }
+
+    /// The value of the formula, given the value of each atom.
+    pub fn evaluate(&self, atom: &impl Fn(&A) -> bool) -> bool {
+        match self {
+            Formula::True => true,
+            Formula::False => false,
+            Formula::Atom(a) => atom(a),
+            Formula::Not(f) => !f.evaluate(atom),
+            Formula::And(fs) => fs.iter().all(|f| f.evaluate(atom)),
+            Formula::Or(fs) => fs.iter().any(|f| f.evaluate(atom)),
+        }
+    }
+
+    /// The distinct atoms of the formula, in order of first appearance.
+    pub fn atoms(&self) -> Vec<&A> {
+        fn collect<'a, A: PartialEq>(formula: &'a Formula<A>, atoms: &mut Vec<&'a A>) {
+            match formula {
+                Formula::Atom(a) if !atoms.contains(&a) => atoms.push(a),
+                Formula::Not(f) => collect(f, atoms),
+                Formula::And(fs) | Formula::Or(fs) => fs.iter().for_each(|f| collect(f, atoms)),
+                _ => {}
+            }
+        }
+        let mut atoms = Vec::new();
+        collect(self, &mut atoms);
+        atoms
+    }
+
+    /// Removes nested conjunctions and disjunctions, double negations and constants.
+    fn flatten(&self) -> Self {
+        let join = |fs: &[Formula<A>], and: bool| {
+            let (unit, zero) = match and {
+                true => (Formula::True, Formula::False),
+                false => (Formula::False, Formula::True),
+            };
+            let mut parts = Vec::new();
+            for f in fs.iter().map(Formula::flatten) {
+                match f {
+                    f if f == zero => return zero,
+                    f if f == unit => {}
+                    Formula::And(inner) if and => parts.extend(inner),
+                    Formula::Or(inner) if !and => parts.extend(inner),
+                    f if !parts.contains(&f) => parts.push(f),
+                    _ => {}
+                }
+            }
+            match parts.len() {
+                0 => unit,
+                1 => parts.pop().unwrap(),
+                _ if and => Formula::And(parts),
+                _ => Formula::Or(parts),
+            }
+        };
+        match self {
+            Formula::Not(f) => match f.flatten() {
+                Formula::True => Formula::False,
+                Formula::False => Formula::True,
+                Formula::Not(inner) => *inner,
+                f => Formula::Not(Box::new(f)),
+            },
+            Formula::And(fs) => join(fs, true),
+            Formula::Or(fs) => join(fs, false),
+            f => f.clone(),
+        }
+    }
+
+    /// An equivalent minimal disjunction of conjunctions of atoms and their negations, or just
+    /// the flattened formula if it has too many atoms for that.
+    pub fn simplify(&self) -> Self {
+        let atoms = self.atoms();
+        if atoms.len() > MAX_ATOMS {
+            return self.flatten();
+        }
+        let minterms = (0..1u32 << atoms.len())
+            .filter(|m| {
+                self.evaluate(&|a| {
+                    let k = atoms.iter().position(|b| *b == a).unwrap();
+                    m >> k & 1 == 1
+                })
+            })
+            .collect::<Vec<_>>();
+        if minterms.is_empty() {
+            return Formula::False;
+        }
+        let terms = cover(&minterms, &prime_implicants(&minterms))
+            .into_iter()
+            .map(|p| {
+                let literals = (0..atoms.len())
+                    .filter(|k| p.mask >> k & 1 == 0)
+                    .map(|k| match p.value >> k & 1 {
+                        1 => Formula::Atom(atoms[k].clone()),
+                        _ => Formula::Not(Box::new(Formula::Atom(atoms[k].clone()))),
+                    })
+                    .collect();
+                Formula::And(literals)
+            })
+            .collect();
+        Formula::Or(terms).flatten()
+    }
+}
+
+impl<A: Display> Display for Formula<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |f: &mut std::fmt::Formatter<'_>, fs: &[Formula<A>], sep: &str| {
+            for (k, part) in fs.iter().enumerate() {
+                if k > 0 {
+                    f.write_str(sep)?;
+                }
+                match part {
+                    Formula::And(_) | Formula::Or(_) => write!(f, "({})", part)?,
+                    _ => write!(f, "{}", part)?,
+                }
+            }
+            Ok(())
+        };
+        match self {
+            Formula::True => f.write_str("⊤"),
+            Formula::False => f.write_str("⊥"),
+            Formula::Atom(a) => a.fmt(f),
+            Formula::Not(inner) => match **inner {
+                Formula::And(_) | Formula::Or(_) => write!(f, "¬({})", inner),
+                _ => write!(f, "¬{}", inner),
+            },
+            Formula::And(fs) => join(f, fs, " ∧ "),
+            Formula::Or(fs) => join(f, fs, " ∨ "),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Formula;
+
+    #[test]
+    fn test_simplify() {
+        let [a, b, c] = ["a", "b", "c"].map(Formula::atom);
+
+        // Absorption and resolution: a ∨ (a ∧ b) = a, (a ∧ b) ∨ (a ∧ ¬b) = a.
+        assert_eq!(a.or(&a.and(&b)).simplify(), a);
+        assert_eq!(a.and(&b).or(&a.and(&b.not())).simplify(), a);
+        assert_eq!(a.or(&a.not()).simplify(), Formula::True);
+        assert_eq!(a.and(&a.not()).and(&c).simplify(), Formula::False);
+
+        // Consensus: (a ∧ b) ∨ (¬a ∧ c) ∨ (b ∧ c) drops the redundant b ∧ c.
+        let consensus = a.and(&b).or(&a.not().and(&c)).or(&b.and(&c));
+        let simplified = consensus.simplify();
+        assert_eq!(simplified.to_string(), "(a ∧ b) ∨ (¬a ∧ c)");
+        for m in 0..8 {
+            let at = |x: &&str| m >> ["a", "b", "c"].iter().position(|y| y == x).unwrap() & 1 == 1;
+            assert_eq!(consensus.evaluate(&at), simplified.evaluate(&at));
+        }
+
+        assert_eq!(a.not().not().and(&Formula::True).simplify(), a);
+        assert_eq!(a.and(&b).not().to_string(), "¬(a ∧ b)");
+    }
+}
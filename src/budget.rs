@@ -0,0 +1,315 @@
+//! This module describes budgets for passes that can run for a long time.
+//!
+//! A [`Budget`] bounds a computation by a deadline, by a number of steps, or by a
+//! [`CancellationToken`] another thread can trigger, so interactive front ends can abort a
+//! runaway pass. Each pass decides what a step is and spends one at a time; once the budget is
+//! exhausted it stops and returns the best result it has as a [`Partial`]. A budget stays
+//! exhausted once it has run out, so it can be shared by several passes in turn.
+//!
+//! Passes that build up large results also report the size of their working state, such as
+//! the terms of an expansion, which a budget can cap so that runaway growth fails cleanly
+//! instead of exhausting memory.
+//!
+//! A budget can also carry a progress callback, which front ends use to draw progress bars.
+//! It is called with a [`Progress`] every few dozen steps and whenever a pass completes an
+//! iteration of its main loop.
+
+use std::{
+    cell::Cell,
+    fmt::{Debug, Display},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// The clock and the token are only consulted every this many steps, since reading them costs
+/// more than most steps.
+const CHECK_INTERVAL: u64 = 64;
+
+/// A flag shared between a computation and whoever may want to cancel it. Clones share the
+/// flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Why a budget ran out.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Exhausted {
+    Cancelled,
+    TimedOut,
+    OutOfSteps,
+    /// The working state outgrew the size limit.
+    TooLarge,
+}
+
+impl Display for Exhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Exhausted::Cancelled => f.write_str("the computation was cancelled"),
+            Exhausted::TimedOut => f.write_str("the computation timed out"),
+            Exhausted::OutOfSteps => f.write_str("the computation ran out of steps"),
+            Exhausted::TooLarge => f.write_str("the computation outgrew its size limit"),
+        }
+    }
+}
+
+impl std::error::Error for Exhausted {}
+
+/// The best result a pass had when its budget ran out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Partial<T> {
+    pub best: T,
+    pub reason: Exhausted,
+}
+
+impl<T> Display for Partial<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}, stopping early", self.reason)
+    }
+}
+
+impl<T: std::fmt::Debug> std::error::Error for Partial<T> {}
+
+/// How far a computation has got.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// The steps spent, such as nodes processed.
+    pub steps: u64,
+    /// The iterations of the main loop completed, such as rewrite passes or box splits.
+    pub iterations: u64,
+    /// The size of the working state after the last iteration, such as the terms of an
+    /// expansion or the boxes left to split.
+    pub size: Option<usize>,
+}
+
+type Callback = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+/// The limits on a computation, and how much of them it has used.
+#[derive(Clone, Default)]
+pub struct Budget {
+    deadline: Option<Instant>,
+    steps: Option<u64>,
+    max_size: Option<usize>,
+    token: Option<CancellationToken>,
+    callback: Option<Callback>,
+    progress: Cell<Progress>,
+    exhausted: Cell<Option<Exhausted>>,
+}
+
+impl Debug for Budget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Budget")
+            .field("deadline", &self.deadline)
+            .field("steps", &self.steps)
+            .field("max_size", &self.max_size)
+            .field("token", &self.token)
+            .field("progress", &self.progress.get())
+            .field("exhausted", &self.exhausted.get())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Budget {
+    /// A budget that never runs out.
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    pub fn with_deadline(self, deadline: Instant) -> Self {
+        Budget {
+            deadline: Some(deadline),
+            ..self
+        }
+    }
+
+    /// Sets the deadline `timeout` from now.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.with_deadline(Instant::now() + timeout)
+    }
+
+    pub fn with_steps(self, steps: u64) -> Self {
+        Budget {
+            steps: Some(steps),
+            ..self
+        }
+    }
+
+    /// Limits the size of the working state of a pass, in whatever units it reports.
+    pub fn with_max_size(self, max_size: usize) -> Self {
+        Budget {
+            max_size: Some(max_size),
+            ..self
+        }
+    }
+
+    pub fn with_token(self, token: CancellationToken) -> Self {
+        Budget {
+            token: Some(token),
+            ..self
+        }
+    }
+
+    /// Calls `callback` as the computation progresses, which may be on another thread.
+    pub fn with_progress(self, callback: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        Budget {
+            callback: Some(Arc::new(callback)),
+            ..self
+        }
+    }
+
+    /// How many steps have been spent.
+    pub fn spent(&self) -> u64 {
+        self.progress.get().steps
+    }
+
+    pub fn progress(&self) -> Progress {
+        self.progress.get()
+    }
+
+    fn report(&self) {
+        if let Some(callback) = &self.callback {
+            callback(&self.progress.get());
+        }
+    }
+
+    /// Checks that a working state of `size` is within the size limit.
+    pub fn fits(&self, size: usize) -> Result<(), Exhausted> {
+        if self.max_size.is_some_and(|max_size| size > max_size) {
+            self.exhausted.set(Some(Exhausted::TooLarge));
+            return Err(Exhausted::TooLarge);
+        }
+        Ok(())
+    }
+
+    /// Records that an iteration of the main loop completed, leaving a working state of
+    /// `size`, and checks it against the size limit.
+    pub fn iterate(&self, size: usize) -> Result<(), Exhausted> {
+        let progress = self.progress.get();
+        self.progress.set(Progress {
+            iterations: progress.iterations + 1,
+            size: Some(size),
+            ..progress
+        });
+        self.report();
+        self.fits(size)
+    }
+
+    /// Why the budget ran out, if it has.
+    pub fn exhausted(&self) -> Option<Exhausted> {
+        self.exhausted.get()
+    }
+
+    /// Spends a step, or reports why there is none left.
+    pub fn spend(&self) -> Result<(), Exhausted> {
+        if let Some(reason) = self.exhausted.get() {
+            return Err(reason);
+        }
+        let progress = self.progress.get();
+        let reason = if self.steps.is_some_and(|steps| progress.steps >= steps) {
+            Some(Exhausted::OutOfSteps)
+        } else if !progress.steps.is_multiple_of(CHECK_INTERVAL) {
+            None
+        } else if self.token.as_ref().is_some_and(|t| t.is_cancelled()) {
+            Some(Exhausted::Cancelled)
+        } else if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            Some(Exhausted::TimedOut)
+        } else {
+            self.report();
+            None
+        };
+        match reason {
+            Some(reason) => {
+                self.exhausted.set(Some(reason));
+                Err(reason)
+            }
+            None => {
+                self.progress.set(Progress {
+                    steps: progress.steps + 1,
+                    ..progress
+                });
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::{Budget, CancellationToken, Exhausted, Progress};
+
+    #[test]
+    fn test_budget() {
+        let budget = Budget::unlimited();
+        assert!((0..1000).all(|_| budget.spend().is_ok()));
+        assert_eq!((budget.spent(), budget.exhausted()), (1000, None));
+
+        let budget = Budget::unlimited().with_steps(3);
+        assert!((0..3).all(|_| budget.spend().is_ok()));
+        assert_eq!(budget.spend(), Err(Exhausted::OutOfSteps));
+        assert_eq!(budget.exhausted(), Some(Exhausted::OutOfSteps));
+
+        let token = CancellationToken::new();
+        let budget = Budget::unlimited()
+            .with_timeout(Duration::from_secs(3600))
+            .with_token(token.clone());
+        assert_eq!(budget.spend(), Ok(()));
+        token.cancel();
+        // Cancellation is noticed at the next check of the token, and then sticks.
+        let spent = (0..1000).take_while(|_| budget.spend().is_ok()).count();
+        assert!(spent < 64);
+        assert_eq!(budget.spend(), Err(Exhausted::Cancelled));
+
+        let budget = Budget::unlimited().with_timeout(Duration::ZERO);
+        assert_eq!(budget.spend(), Err(Exhausted::TimedOut));
+
+        // A budget with a callback can be handed to a worker thread.
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let budget = Budget::unlimited().with_progress({
+            let reports = reports.clone();
+            move |progress| reports.lock().unwrap().push(*progress)
+        });
+        let budget = std::thread::spawn(move || {
+            (0..100).for_each(|_| budget.spend().unwrap());
+            budget.iterate(7).unwrap();
+            budget
+        })
+        .join()
+        .unwrap();
+        assert_eq!(budget.progress().iterations, 1);
+        assert_eq!(
+            *reports.lock().unwrap(),
+            [
+                Progress::default(),
+                Progress {
+                    steps: 64,
+                    ..Default::default()
+                },
+                Progress {
+                    steps: 100,
+                    iterations: 1,
+                    size: Some(7),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! This module describes the exponent normal form of our computational graph.
+//!
+//! [`OpArgument::canonicalize`] writes every quotient `a/b` as the product `a·b^(-1)` and merges
+//! the rational exponents of products and powers of a common base, so that `x/x`, `x·x^(-1)`,
+//! `x²/x` and `√x·√x` are all instances of the single rule `xᵃ·xᵇ = x^(a+b)` rather than of a
+//! rule each. Square roots already are the powers `x^(1/2)`, which is what `sqrt(x)` parses to
+//! and how such powers are displayed. [`OpArgument::decanonicalize`] turns negative exponents
+//! back into quotients for printing.
+//!
+//! Like the algebra it replaces, merging is an identity wherever both sides are defined, so
+//! `x/x` becomes `1` without recording that `x` must not be zero. A power of a power is only
+//! merged when the outer exponent is an integer, since `(x²)^(1/2)` is `|x|` rather than `x`.
+//! Only neighbouring factors are merged and none are reordered, so products of symbols declared
+//! with [`crate::symbols::declare_non_commutative`] keep their meaning.
+
+use crate::{
+    constants::Value,
+    fold::Ratio,
+    provenance::{self, Step},
+    symbols::{OpArgument, OpArgumentKind::Op, OperationKind::*},
+};
+
+/// Splits `node` into a base and its rational exponent, which is `1` for anything that is not
+/// such a power.
+fn split(node: &OpArgument) -> (&OpArgument, Ratio) {
+    match &node.value {
+        Op(op) if op.op == Pow => match Ratio::of(&op.arguments[1]) {
+            Some(exponent) => (&op.arguments[0], exponent),
+            None => (node, Ratio::ONE),
+        },
+        _ => (node, Ratio::ONE),
+    }
+}
+
+/// `base` raised to `exponent`, without the trivial exponents.
+fn power(base: &OpArgument, exponent: Ratio) -> Option<OpArgument> {
+    match exponent {
+        Ratio::ONE => Some(base.clone()),
+        Ratio::ZERO => Some(Value::integer(1).into()),
+        _ => Some(base.pow(&exponent.to_oparg()?)),
+    }
+}
+
+fn is_constant(node: &OpArgument) -> bool {
+    Ratio::of(node).is_some()
+}
+
+/// The canonical form of `node`, whose arguments are already canonical.
+fn canonicalize_root(node: &OpArgument) -> Option<OpArgument> {
+    let Op(op) = &node.value else {
+        return None;
+    };
+    let arg = |i: usize| &op.arguments[i];
+    match op.op {
+        Division => {
+            let reciprocal = match Ratio::of(arg(1)) {
+                // Exact quotients of constants, such as the `1/2` of a square root, are
+                // exponents to merge, except 1/0, which folding makes undefined.
+                Some(c) if c != Ratio::ZERO => match Ratio::of(arg(0)) {
+                    Some(n) => return n.checked_mul(c.recip()?)?.to_oparg(),
+                    None => c.recip()?.to_oparg()?,
+                },
+                _ => {
+                    let (base, exponent) = split(arg(1));
+                    power(base, -exponent)?
+                }
+            };
+            let product = arg(0) * &reciprocal;
+            Some(canonicalize_root(&product).unwrap_or(product))
+        }
+        Multiplication => {
+            let ((a, m), (b, n)) = (split(arg(0)), split(arg(1)));
+            // Products of constants are for folding.
+            (a == b && !is_constant(a)).then_some(())?;
+            power(a, m.checked_add(n)?)
+        }
+        Pow => {
+            let outer = Ratio::of(arg(1))?;
+            let (base, inner) = split(arg(0));
+            match outer {
+                Ratio::ONE => Some(arg(0).clone()),
+                _ if outer.den == 1 && inner != Ratio::ONE => {
+                    power(base, inner.checked_mul(outer)?)
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The friendly form of `node`, whose arguments are already friendly.
+fn decanonicalize_root(node: &OpArgument) -> Option<OpArgument> {
+    let Op(op) = &node.value else {
+        return None;
+    };
+    // The positive power `base^(-exponent)`, when `node` has a negative rational exponent.
+    let denominator = |node: &OpArgument| {
+        let (base, exponent) = split(node);
+        (exponent.num < 0 && !is_constant(base))
+            .then(|| power(base, -exponent))
+            .flatten()
+    };
+    // The denominator `d` of a factor `1/d`, which is what such powers have already become.
+    let reciprocal = |node: &OpArgument| match &node.value {
+        Op(op) if op.op == Division && Ratio::of(&op.arguments[0]) == Some(Ratio::ONE) => {
+            Some(op.arguments[1].clone())
+        }
+        _ => None,
+    };
+    match op.op {
+        Multiplication => match (reciprocal(&op.arguments[0]), reciprocal(&op.arguments[1])) {
+            (_, Some(den)) => Some(&op.arguments[0] / &den),
+            (Some(den), None) => Some(&op.arguments[1] / &den),
+            (None, None) => None,
+        },
+        Pow => Some(OpArgument::from(Value::integer(1)) / &denominator(node)?),
+        _ => None,
+    }
+}
+
+/// Rewrites `node` bottom up with `root`, recording each step.
+fn bottom_up(node: &OpArgument, root: fn(&OpArgument) -> Option<OpArgument>) -> OpArgument {
+    node.map_bottom_up(|node, rebuilt| {
+        let result = root(&rebuilt).unwrap_or(rebuilt);
+        provenance::record(|| Step::Canonicalize, node, &result);
+        result
+    })
+}
+
+impl OpArgument {
+    /// Rewrites quotients as products with negative powers and merges the exponents of a
+    /// common base.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    pub fn canonicalize(&self) -> OpArgument {
+        bottom_up(self, canonicalize_root)
+    }
+
+    /// Rewrites products with negative powers as quotients, undoing
+    /// [`OpArgument::canonicalize`] for display.
+    pub fn decanonicalize(&self) -> OpArgument {
+        bottom_up(self, decanonicalize_root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        constants::Value,
+        evaluation::Bindings,
+        parse::parse,
+        provenance::{disable, enable, explain, Step},
+        symbols::OpArgument,
+    };
+
+    #[test]
+    fn test_canonicalize() {
+        let p = |input: &str| parse(input).unwrap();
+        let canonical = |input: &str| p(input).canonicalize();
+        let one = OpArgument::from(Value::integer(1));
+
+        assert_eq!(canonical("x / y"), p("x * y^(-1)"));
+        assert_eq!(
+            canonical("x / 4"),
+            p("x") * OpArgument::from(Value::rational(1, 4))
+        );
+        assert_eq!(canonical("x / x"), one);
+        assert_eq!(canonical("x * x^(-1)"), one);
+        assert_eq!(canonical("x^2 / x"), p("x"));
+        assert_eq!(canonical("sqrt(x) * sqrt(x)"), p("x"));
+        assert_eq!(canonical("(x^3)^2 / y^(1/2)"), canonical("x^6 * y^(-1/2)"));
+        assert_eq!(canonical("1 / sin(x)^2"), p("1 * sin(x)^(-2)"));
+        // Not every power of a power is the product of the exponents.
+        assert_eq!(canonical("(x^2)^(1/2)"), p("sqrt(x^2)"));
+        assert_eq!(canonical("2 * 2"), p("2 * 2"));
+
+        let expr = p("(x + 1) / (y^2 * sqrt(x)) - 3 / (x * y)");
+        let at = Bindings::from_iter([("x", 1.7), ("y", -0.3)]);
+        let expected = expr.evaluate(&at).unwrap();
+        assert!((expr.canonicalize().evaluate(&at).unwrap() - expected).abs() < 1e-12);
+
+        // Printing restores the friendly forms.
+        assert_eq!(
+            canonical("(x + 1) / (y^2 * sqrt(x))").decanonicalize(),
+            p("(x + 1) / (y^2 * sqrt(x))")
+        );
+        assert_eq!(canonical("x / y^2").decanonicalize(), p("x / y^2"));
+        assert_eq!(canonical("y^(-1/2)").decanonicalize(), p("1 / sqrt(y)"));
+        assert_eq!(p("sqrt(x + y)").to_string(), "sqrt(x+y)");
+
+        enable();
+        let explanation = explain(&canonical("x / y"));
+        disable();
+        assert_eq!(explanation.origin.unwrap().0, Step::Canonicalize);
+    }
+}
@@ -0,0 +1,111 @@
+//! Spreadsheet formulas, for handing calculations to Excel, LibreOffice Calc or Google Sheets.
+//!
+//! Variables become the cell references they are mapped to, and the result is a formula that
+//! starts with `=`. Spreadsheets give unary minus a higher precedence than `^`, so that `-2^2`
+//! is `4`, and evaluate `^` from the left, so negated powers and compound exponents are always
+//! parenthesized. Non-integer rationals are written as parenthesized quotients so they stay
+//! exact.
+
+use ahash::HashMap;
+
+use super::CodegenError;
+use crate::{
+    constants::Value,
+    render::needs_parens,
+    symbols::{
+        OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        OperationKind::*,
+    },
+};
+
+fn formula(expr: &OpArgument, cells: &HashMap<&str, &str>) -> Result<String, CodegenError> {
+    let op = match &expr.value {
+        Leaf(value) => {
+            return match **value {
+                Value::Rational(num, den) if den.get() == 1 => Ok(num.to_string()),
+                Value::Rational(num, den) => Ok(format!("({}/{})", num, den)),
+                Value::Pi => Ok("PI()".to_owned()),
+                Value::E => Ok("EXP(1)".to_owned()),
+                Value::Variable(name) => cells
+                    .get(name)
+                    .map(|cell| cell.to_string())
+                    .ok_or(CodegenError::UnknownVariable(name)),
+                value => Err(CodegenError::UnsupportedConstant(value)),
+            }
+        }
+        Op(op) => op,
+    };
+    let arg = |i: usize| formula(&op.arguments[i], cells);
+    let grouped = |i: usize| -> Result<String, CodegenError> {
+        Ok(match &op.arguments[i].value {
+            Op(_) => format!("({})", arg(i)?),
+            Leaf(_) => arg(i)?,
+        })
+    };
+    Ok(match op.op {
+        Addition | Subtraction | Multiplication | Division => {
+            let operand = |i: usize| match needs_parens(op.op, &op.arguments[i], i) {
+                true => grouped(i),
+                false => arg(i),
+            };
+            format!("{}{}{}", operand(0)?, op.op, operand(1)?)
+        }
+        Negation => format!("-{}", grouped(0)?),
+        Pow if matches!(&op.arguments[1].value, Leaf(v) if **v == Value::rational(1, 2)) => {
+            format!("SQRT({})", arg(0)?)
+        }
+        Pow => format!("{}^{}", grouped(0)?, grouped(1)?),
+        Exp => format!("EXP({})", arg(0)?),
+        Sin => format!("SIN({})", arg(0)?),
+        Cos => format!("COS({})", arg(0)?),
+        Tan => format!("TAN({})", arg(0)?),
+        Ln => format!("LN({})", arg(0)?),
+        Atan => format!("ATAN({})", arg(0)?),
+        Sign => format!("SIGN({})", arg(0)?),
+    })
+}
+
+/// The spreadsheet formula computing `expr`, with each variable replaced by its cell in
+/// `cells`.
+pub fn to_excel_formula(
+    expr: &OpArgument,
+    cells: &HashMap<&str, &str>,
+) -> Result<String, CodegenError> {
+    Ok(format!("={}", formula(expr, cells)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use ahash::HashMap;
+
+    use super::to_excel_formula;
+    use crate::{codegen::CodegenError, constants::Value, parse::parse};
+
+    #[test]
+    fn test_to_excel_formula() {
+        let cells = HashMap::from_iter([("x", "A1"), ("y", "B2"), ("rate", "Sheet2!$C$3")]);
+        let excel = |input: &str| to_excel_formula(&parse(input).unwrap(), &cells);
+
+        assert_eq!(
+            excel("x^2 + 3*y - sin(rate)"),
+            Ok("=A1^2+3*B2-SIN(Sheet2!$C$3)".to_owned())
+        );
+        assert_eq!(excel("x / (y * 2)"), Ok("=A1/(B2*2)".to_owned()));
+        assert_eq!(excel("x - (y - 1)"), Ok("=A1-(B2-1)".to_owned()));
+        // Unary minus binds tighter than ^ in spreadsheets.
+        assert_eq!(excel("-x^2"), Ok("=-(A1^2)".to_owned()));
+        assert_eq!(excel("(-x)^2"), Ok("=(-A1)^2".to_owned()));
+        assert_eq!(excel("x^(y^2)"), Ok("=A1^(B2^2)".to_owned()));
+        assert_eq!(
+            excel("0.25 * pi * exp(e) + sqrt(x)"),
+            Ok("=(1/4)*PI()*EXP(EXP(1))+SQRT(A1)".to_owned())
+        );
+
+        assert_eq!(excel("x + z"), Err(CodegenError::UnknownVariable("z")));
+        assert_eq!(
+            excel("x * i"),
+            Err(CodegenError::UnsupportedConstant(Value::I))
+        );
+    }
+}
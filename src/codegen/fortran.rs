@@ -0,0 +1,286 @@
+//! Fortran 90 subroutines evaluating a compiled program, for kernels in legacy Fortran codes.
+//!
+//! The generated `pure subroutine` takes the parameters as `inputs(n)` and writes the results to
+//! `outputs(m)`, both double precision and in the order of [`Program::params`] and
+//! [`Program::outputs`], with a comment naming each. Literals carry the `d0` exponent so they
+//! are not silently rounded to single precision, and powers with small integer exponents use
+//! integer exponents, which compilers turn into multiplications and which are defined for
+//! negative bases. Fortran 90 has no literals for infinity or NaN, so programs containing them
+//! are rejected. A compiled [`crate::sum::Sum`] becomes a subroutine with a `do` loop over its
+//! index.
+
+use std::fmt::Write;
+
+use super::CodegenError;
+use crate::{
+    compile::{compile_many, Instruction, Program, SumProgram},
+    constants::Value,
+    symbols::{OpArgument, OperationKind::*},
+};
+
+/// Registers are declared this many to a line, to stay well within the 132 columns of free
+/// form source.
+const DECLARATIONS_PER_LINE: usize = 8;
+
+/// The double precision literal for `value`, such as `3.0d0` or `2.5d-1`.
+fn literal(value: f64) -> Result<String, CodegenError> {
+    if value.is_nan() {
+        return Err(CodegenError::UnsupportedConstant(Value::Undefined));
+    }
+    if value.is_infinite() {
+        return Err(CodegenError::UnsupportedConstant(Value::Inf));
+    }
+    let text = format!("{:e}", value);
+    let (mantissa, exponent) = text.split_once('e').unwrap();
+    let mantissa = match mantissa.contains('.') {
+        true => mantissa.to_owned(),
+        false => format!("{}.0", mantissa),
+    };
+    Ok(format!("{}d{}", mantissa, exponent))
+}
+
+/// The exponent of `x**n` for constants `n` that are small integers.
+fn integer_exponent(program: &Program, register: usize) -> Option<i32> {
+    match program.instructions()[register] {
+        Instruction::Constant(c) if c.fract() == 0.0 && c.abs() <= 64.0 => Some(c as i32),
+        _ => None,
+    }
+}
+
+/// The names the generated subroutines declare or call, which a subroutine can't also have.
+const RESERVED: [&str; 15] = [
+    "inputs", "outputs", "k", "dp", "kind", "real", "nint", "merge", "sign", "exp", "log", "sin",
+    "cos", "tan", "atan",
+];
+
+/// Whether `name` is a Fortran 90 name that clashes with nothing in the generated subroutine.
+/// Fortran ignores case, so neither do the clashes.
+fn is_subroutine_name(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    let register = lower
+        .strip_prefix('r')
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name.len() <= 31
+        && !register
+        && !RESERVED.contains(&lower.as_str())
+}
+
+/// Writes the declarations of the subroutine `name` with `registers` registers, up to the blank
+/// line before its first statement.
+fn header(
+    source: &mut String,
+    name: &str,
+    params: &[&'static str],
+    outputs: usize,
+    registers: usize,
+) -> Result<(), CodegenError> {
+    if !is_subroutine_name(name) {
+        return Err(CodegenError::UnsupportedFunctionName(name.to_owned()));
+    }
+
+    let _ = writeln!(source, "pure subroutine {}(inputs, outputs)", name);
+    source.push_str("  implicit none\n  integer, parameter :: dp = kind(0.0d0)\n");
+    let _ = writeln!(
+        source,
+        "  real(dp), intent(in) :: inputs({})",
+        params.len().max(1)
+    );
+    let _ = writeln!(source, "  real(dp), intent(out) :: outputs({})", outputs);
+    let registers = (0..registers)
+        .map(|r| format!("r{}", r))
+        .collect::<Vec<_>>();
+    for line in registers.chunks(DECLARATIONS_PER_LINE) {
+        let _ = writeln!(source, "  real(dp) :: {}", line.join(", "));
+    }
+    for (i, param) in params.iter().enumerate() {
+        let _ = writeln!(source, "  ! inputs({}): {}", i + 1, param);
+    }
+    Ok(())
+}
+
+/// The expression computing `program`'s register `r`, reading the parameter `p` with `input(p)`
+/// and naming its registers from `offset` on.
+fn expression(
+    program: &Program,
+    r: usize,
+    input: impl Fn(usize) -> String,
+    offset: usize,
+) -> Result<String, CodegenError> {
+    Ok(match program.instructions()[r] {
+        Instruction::Input(p) => input(p),
+        Instruction::Constant(c) => literal(c)?,
+        Instruction::Unary(op, a) => match op {
+            Negation => format!("-r{}", a + offset),
+            Ln => format!("log(r{})", a + offset),
+            Exp | Sin | Cos | Tan | Atan => format!("{}(r{})", op, a + offset),
+            // Fortran's `sign` never returns zero.
+            Sign => format!("merge(0d0, sign(1d0, r{0}), r{0} == 0d0)", a + offset),
+            op => return Err(CodegenError::UnsupportedOperation(op)),
+        },
+        Instruction::Binary(op, a, b) => match op {
+            Pow => match integer_exponent(program, b) {
+                Some(n) if n < 0 => format!("r{}**({})", a + offset, n),
+                Some(n) => format!("r{}**{}", a + offset, n),
+                None => format!("r{}**r{}", a + offset, b + offset),
+            },
+            Addition | Subtraction | Multiplication | Division => {
+                format!("r{} {} r{}", a + offset, op, b + offset)
+            }
+            op => return Err(CodegenError::UnsupportedOperation(op)),
+        },
+    })
+}
+
+fn input(p: usize) -> String {
+    format!("inputs({})", p + 1)
+}
+
+/// Generates a subroutine called `name` evaluating every output of `program`.
+pub fn program_to_fortran(program: &Program, name: &str) -> Result<String, CodegenError> {
+    let mut source = String::new();
+    header(
+        &mut source,
+        name,
+        program.params(),
+        program.outputs().len(),
+        program.instructions().len(),
+    )?;
+    source.push('\n');
+
+    for r in 0..program.instructions().len() {
+        let _ = writeln!(source, "  r{} = {}", r, expression(program, r, input, 0)?);
+    }
+
+    source.push('\n');
+    for (i, output) in program.outputs().iter().enumerate() {
+        let _ = writeln!(source, "  outputs({}) = r{}", i + 1, output);
+    }
+    let _ = writeln!(source, "end subroutine {}", name);
+    Ok(source)
+}
+
+/// Generates a subroutine called `name` evaluating the compiled sum with a `do` loop over the
+/// integer `k`, writing it to `outputs(1)`. The instructions of the body that don't read the
+/// index are computed before the loop.
+pub fn sum_to_fortran(sum: &SumProgram, name: &str) -> Result<String, CodegenError> {
+    let (bounds, body) = (sum.bounds(), sum.body());
+    let offset = bounds.instructions().len();
+    let total = offset + body.instructions().len();
+    let mut source = String::new();
+    header(&mut source, name, sum.params(), 1, total + 1)?;
+    source.push_str("  integer :: k\n");
+    let _ = writeln!(source, "  ! k: {}", sum.index());
+    source.push('\n');
+
+    for r in 0..bounds.instructions().len() {
+        let _ = writeln!(source, "  r{} = {}", r, expression(bounds, r, input, 0)?);
+    }
+    let index = sum.params().len();
+    let body_input = |p: usize| match p == index {
+        true => "real(k, dp)".to_owned(),
+        false => input(p),
+    };
+    let varying = sum.varying();
+    for r in (0..body.instructions().len()).filter(|&r| !varying[r]) {
+        let expr = expression(body, r, body_input, offset)?;
+        let _ = writeln!(source, "  r{} = {}", r + offset, expr);
+    }
+    let _ = writeln!(source, "  r{} = 0.0d0", total);
+    let [lo, hi] = [0, 1].map(|i| bounds.outputs()[i]);
+    let _ = writeln!(source, "  do k = nint(r{}), nint(r{})", lo, hi);
+    for r in (0..body.instructions().len()).filter(|&r| varying[r]) {
+        let expr = expression(body, r, body_input, offset)?;
+        let _ = writeln!(source, "    r{} = {}", r + offset, expr);
+    }
+    let output = body.outputs()[0] + offset;
+    let _ = writeln!(source, "    r{} = r{} + r{}", total, total, output);
+    source.push_str("  end do\n\n");
+    let _ = writeln!(source, "  outputs(1) = r{}", total);
+    let _ = writeln!(source, "end subroutine {}", name);
+    Ok(source)
+}
+
+/// Generates a subroutine called `name` evaluating `exprs` from the `inputs`.
+pub fn to_fortran(
+    exprs: &[OpArgument],
+    inputs: &[&'static str],
+    name: &str,
+) -> Result<String, CodegenError> {
+    program_to_fortran(&compile_many(exprs, inputs)?, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{literal, program_to_fortran, sum_to_fortran, to_fortran};
+    use crate::{
+        codegen::CodegenError,
+        compile::{compile_many_with, compile_sum, CompileOptions},
+        constants::Value,
+        parse::parse,
+        sum::Sum,
+        symbols::{variable, OpArgument},
+    };
+
+    #[test]
+    fn test_to_fortran() {
+        assert_eq!(literal(0.25), Ok("2.5d-1".to_owned()));
+        assert_eq!(literal(3.0), Ok("3.0d0".to_owned()));
+        assert_eq!(literal(-1.5e-20), Ok("-1.5d-20".to_owned()));
+
+        let x = variable("x");
+        let y = variable("y");
+        let shared = x.sin();
+        let f = &shared * &shared + y.ln();
+        let g = &y / OpArgument::from(Value::integer(4));
+
+        let source = to_fortran(&[f.clone(), g], &["x", "y"], "kernel").unwrap();
+        assert!(source.starts_with("pure subroutine kernel(inputs, outputs)\n  implicit none\n"));
+        assert!(source.contains("  real(dp), intent(in) :: inputs(2)\n"));
+        assert!(source.contains("  real(dp), intent(out) :: outputs(2)\n"));
+        assert!(source.contains("  ! inputs(2): y\n"));
+        assert!(source.contains("  r0 = inputs(1)\n  r1 = sin(r0)\n  r2 = r1 * r1\n"));
+        assert!(source.contains("  r4 = log(r3)\n"));
+        assert!(source.contains(" = 4.0d0\n"));
+        assert!(source.contains("  outputs(1) = r5\n"));
+        assert!(source.ends_with("end subroutine kernel\n"));
+        assert_eq!(source.matches("sin(").count(), 1);
+
+        // Without power chains, small integer exponents stay integers.
+        let powers = parse("x^3 + y^x").unwrap();
+        let program =
+            compile_many_with(&[powers], &["x", "y"], CompileOptions::unoptimized()).unwrap();
+        let source = program_to_fortran(&program, "powers").unwrap();
+        assert!(source.contains("  r2 = r0**3\n"));
+        assert!(source.contains("  r4 = r3**r0\n"));
+
+        // Sums loop over their index, with the parts that don't read it hoisted.
+        let sum = Sum {
+            body: parse("cos(x) / j^2").unwrap(),
+            index: "j",
+            lo: parse("1").unwrap(),
+            hi: parse("n").unwrap(),
+        };
+        let source = sum_to_fortran(&compile_sum(&sum, &["x", "n"]).unwrap(), "basel").unwrap();
+        assert!(source.contains("  integer :: k\n  ! k: j\n"));
+        assert!(source.contains("  r3 = cos(r2)\n  r7 = 0.0d0\n  do k = nint(r0), nint(r1)\n"));
+        assert!(source.contains("    r4 = real(k, dp)\n"));
+        assert!(source.contains("    r7 = r7 + r6\n  end do\n\n  outputs(1) = r7\n"));
+
+        assert_eq!(
+            to_fortran(&[f], &["x"], "kernel"),
+            Err(CodegenError::UnknownVariable("y"))
+        );
+        for name in ["2d", "a-b", "Outputs", "R12", "K", "Sin"] {
+            assert_eq!(
+                to_fortran(std::slice::from_ref(&x), &["x"], name),
+                Err(CodegenError::UnsupportedFunctionName(name.to_owned()))
+            );
+        }
+        assert_eq!(
+            to_fortran(&[x + OpArgument::from(Value::Inf)], &["x"], "kernel"),
+            Err(CodegenError::UnsupportedConstant(Value::Inf))
+        );
+    }
+}
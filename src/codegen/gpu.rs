@@ -0,0 +1,303 @@
+//! This module describes how to run the shaders of [`super::wgsl`] on a GPU through `wgpu`.
+//!
+//! A [`GpuEvaluator`] compiles an expression once and then evaluates it over as many batches as
+//! needed. Batches larger than the device lets a single storage buffer hold are split into
+//! chunks, each evaluated by its own dispatch.
+
+use std::{fmt::Display, sync::mpsc};
+
+use wgpu::util::DeviceExt;
+
+use super::{
+    wgsl::{needs_infinity, program_to_wgsl, WORKGROUP_SIZE},
+    CodegenError,
+};
+use crate::{compile::compile, symbols::OpArgument};
+
+/// The most workgroups a single dispatch dimension may count.
+const MAX_GROUPS: u32 = 65535;
+
+/// The reasons evaluating on the GPU can fail.
+#[derive(Debug)]
+pub enum GpuError {
+    /// The expression could not be turned into a shader.
+    Codegen(CodegenError),
+    /// No adapter is available, e.g. because the machine has no GPU or drivers.
+    NoAdapter,
+    /// The adapter refused to hand out a device.
+    Device(wgpu::RequestDeviceError),
+    /// The results could not be read back from the device.
+    Readback(wgpu::BufferAsyncError),
+    /// The batch does not have one column per input, all equally long.
+    Columns,
+}
+
+impl Display for GpuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GpuError::Codegen(error) => write!(f, "{}", error),
+            GpuError::NoAdapter => f.write_str("no GPU adapter is available"),
+            GpuError::Device(error) => write!(f, "could not open the GPU: {}", error),
+            GpuError::Readback(error) => write!(f, "could not read the results back: {}", error),
+            GpuError::Columns => f.write_str("the batch needs one equally long column per input"),
+        }
+    }
+}
+
+impl std::error::Error for GpuError {}
+
+impl From<CodegenError> for GpuError {
+    fn from(error: CodegenError) -> Self {
+        GpuError::Codegen(error)
+    }
+}
+
+/// An expression compiled to a compute pipeline on one device.
+pub struct GpuEvaluator {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+    /// The uniform holding `+∞`, for shaders with constants too large for `f32`.
+    infinity: Option<wgpu::Buffer>,
+    inputs: usize,
+    /// The most elements one chunk may hold.
+    chunk: usize,
+}
+
+impl GpuEvaluator {
+    /// Compiles `expr` over the `inputs` buffers for the default adapter.
+    pub fn new(expr: &OpArgument, inputs: &[&'static str]) -> Result<Self, GpuError> {
+        let program = compile(expr, inputs)?;
+        let shader = program_to_wgsl(&program)?;
+
+        // Compute shaders need Vulkan, Metal, DirectX 12 or WebGPU; OpenGL rarely has them.
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::PRIMARY,
+            ..Default::default()
+        });
+        let adapter =
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+                .ok_or(GpuError::NoAdapter)?;
+        let limits = adapter.limits();
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: limits.clone(),
+            },
+            None,
+        ))
+        .map_err(GpuError::Device)?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(shader.into()),
+        });
+        // Spelled out, since a layout derived from the shader would drop unused inputs.
+        let storage = |read_only| wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+        let uniform = wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+        let types = std::iter::repeat_n(storage(true), inputs.len())
+            .chain(Some(storage(false)))
+            .chain(needs_infinity(&program).then_some(uniform));
+        let entries: Vec<_> = types
+            .enumerate()
+            .map(|(i, ty)| wgpu::BindGroupLayoutEntry {
+                binding: i as u32,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty,
+                count: None,
+            })
+            .collect();
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &entries,
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: "main",
+        });
+        let infinity = needs_infinity(&program).then(|| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: &f32::INFINITY.to_ne_bytes(),
+                usage: wgpu::BufferUsages::UNIFORM,
+            })
+        });
+
+        let bytes = (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+        let groups = WORKGROUP_SIZE as u64 * MAX_GROUPS as u64 * MAX_GROUPS as u64;
+        let chunk = (bytes / 4).min(groups) as usize;
+        Ok(GpuEvaluator {
+            device,
+            queue,
+            layout,
+            pipeline,
+            infinity,
+            inputs: inputs.len(),
+            chunk,
+        })
+    }
+
+    /// Evaluates the expression at every index of `columns`, which hold one equally long
+    /// slice per input.
+    pub fn evaluate(&self, columns: &[&[f32]]) -> Result<Vec<f32>, GpuError> {
+        let len = columns.first().map_or(0, |column| column.len());
+        if columns.len() != self.inputs || columns.iter().any(|column| column.len() != len) {
+            return Err(GpuError::Columns);
+        }
+
+        let mut results = Vec::with_capacity(len);
+        let mut start = 0;
+        while start < len {
+            let end = len.min(start + self.chunk);
+            let chunk: Vec<_> = columns.iter().map(|column| &column[start..end]).collect();
+            results.extend(self.evaluate_chunk(&chunk, end - start)?);
+            start = end;
+        }
+        Ok(results)
+    }
+
+    /// Like [`GpuEvaluator::evaluate`], rounding the inputs to single precision and widening
+    /// the results again.
+    pub fn evaluate_f64(&self, columns: &[&[f64]]) -> Result<Vec<f64>, GpuError> {
+        let narrowed: Vec<Vec<f32>> = columns
+            .iter()
+            .map(|column| column.iter().map(|&x| x as f32).collect())
+            .collect();
+        let narrowed: Vec<&[f32]> = narrowed.iter().map(Vec::as_slice).collect();
+        let results = self.evaluate(&narrowed)?;
+        Ok(results.into_iter().map(f64::from).collect())
+    }
+
+    fn evaluate_chunk(&self, columns: &[&[f32]], len: usize) -> Result<Vec<f32>, GpuError> {
+        let size = (len * 4) as u64;
+        let buffers: Vec<_> = columns
+            .iter()
+            .map(|column| {
+                let contents: Vec<u8> = column.iter().flat_map(|x| x.to_ne_bytes()).collect();
+                self.device
+                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: None,
+                        contents: &contents,
+                        usage: wgpu::BufferUsages::STORAGE,
+                    })
+            })
+            .collect();
+        let output = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let entries: Vec<_> = buffers
+            .iter()
+            .chain(Some(&output))
+            .chain(&self.infinity)
+            .enumerate()
+            .map(|(i, buffer)| wgpu::BindGroupEntry {
+                binding: i as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.layout,
+            entries: &entries,
+        });
+
+        let groups = len.div_ceil(WORKGROUP_SIZE as usize) as u32;
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(groups.min(MAX_GROUPS), groups.div_ceil(MAX_GROUPS), 1);
+        }
+        encoder.copy_buffer_to_buffer(&output, 0, &staging, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = staging.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .expect("mapping finishes once the device is idle")
+            .map_err(GpuError::Readback)?;
+
+        let results = slice
+            .get_mapped_range()
+            .chunks_exact(4)
+            .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        staging.unmap();
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GpuError, GpuEvaluator};
+    use crate::{compile::compile, parse::parse};
+
+    #[test]
+    fn test_gpu() {
+        // 10^39 overflows single precision, so the shader reads it from the infinity uniform.
+        let expr = parse("sin(x) * y + exp(-(10^39) * (x^2 + 1))").unwrap();
+        let evaluator = match GpuEvaluator::new(&expr, &["x", "y"]) {
+            Err(GpuError::NoAdapter) => return,
+            evaluator => evaluator.unwrap(),
+        };
+
+        let xs: Vec<f32> = (0..1000).map(|i| i as f32 / 100.0).collect();
+        let ys: Vec<f32> = (0..1000).map(|i| 1.0 - i as f32 / 500.0).collect();
+        let results = evaluator.evaluate(&[&xs, &ys]).unwrap();
+        let program = compile(&expr, &["x", "y"]).unwrap();
+        for ((&x, &y), result) in xs.iter().zip(&ys).zip(results) {
+            let expected = program.evaluate_f32(&[x, y])[0];
+            assert!((result - expected).abs() <= 1e-5 * expected.abs().max(1.0));
+        }
+        assert_eq!(evaluator.evaluate(&[&[], &[]]).unwrap(), vec![]);
+        assert!(matches!(
+            evaluator.evaluate(&[&[1.0]]),
+            Err(GpuError::Columns)
+        ));
+        assert!(matches!(
+            evaluator.evaluate(&[&[1.0], &[]]),
+            Err(GpuError::Columns)
+        ));
+
+        let square = GpuEvaluator::new(&parse("t^2").unwrap(), &["t"]).unwrap();
+        assert_eq!(
+            square.evaluate_f64(&[&[3.0, -0.5]]).unwrap(),
+            vec![9.0, 0.25]
+        );
+    }
+}
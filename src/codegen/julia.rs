@@ -0,0 +1,265 @@
+//! Julia functions evaluating a compiled program, for running derivations in Julia pipelines.
+//!
+//! The generated function takes one `Real` per parameter and returns its single output, or a
+//! tuple of them. Every input and constant is converted to `T`, the floating point type the
+//! arguments promote to, so the function is type-stable and stays in `Float32` or `BigFloat`
+//! when called with them. It is scalar, so `f.(xs, ys)` broadcasts it over arrays. A compiled
+//! [`crate::sum::Sum`] becomes a function with a `for` loop over its index.
+
+use std::fmt::Write;
+
+use super::{argument_names, CodegenError};
+use crate::{
+    compile::{compile_many, Instruction, Program, SumProgram},
+    symbols::{OpArgument, OperationKind::*},
+};
+
+const KEYWORDS: [&str; 28] = [
+    "baremodule",
+    "begin",
+    "break",
+    "catch",
+    "const",
+    "continue",
+    "do",
+    "else",
+    "elseif",
+    "end",
+    "export",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "global",
+    "if",
+    "import",
+    "let",
+    "local",
+    "macro",
+    "module",
+    "quote",
+    "return",
+    "struct",
+    "true",
+    "try",
+    "using",
+];
+
+/// Whether `name` can be used as is for an argument, without clashing with a register.
+fn is_argument_name(name: &str) -> bool {
+    let register = name
+        .strip_prefix('r')
+        .is_some_and(|n| n.chars().all(|c| c.is_ascii_digit()));
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !register
+        && name != "T"
+        && !KEYWORDS.contains(&name)
+}
+
+/// The Julia literal for `value`.
+fn literal(value: f64) -> String {
+    match value {
+        value if value.is_nan() => "NaN".to_owned(),
+        f64::INFINITY => "Inf".to_owned(),
+        f64::NEG_INFINITY => "-Inf".to_owned(),
+        value => format!("{:?}", value),
+    }
+}
+
+/// Writes the signature of the function `name` and the definition of `T`.
+fn header(source: &mut String, name: &str, args: &[String], params: &[&'static str]) {
+    assert!(
+        is_argument_name(name),
+        "Hold on, {:?} is not a name we can give a Julia function",
+        name
+    );
+    for (arg, param) in args.iter().zip(params) {
+        if arg != param {
+            let _ = writeln!(source, "# {}: {}", arg, param);
+        }
+    }
+    let signature = args
+        .iter()
+        .map(|arg| format!("{}::Real", arg))
+        .collect::<Vec<_>>();
+    let _ = writeln!(source, "function {}({})", name, signature.join(", "));
+    let types = match args.is_empty() {
+        true => "Float64".to_owned(),
+        false => args
+            .iter()
+            .map(|arg| format!("typeof({})", arg))
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+    let _ = writeln!(source, "    T = float(promote_type({}))", types);
+}
+
+/// The expression computing `program`'s register `r`, reading its inputs from `args` and
+/// naming its registers from `offset` on.
+fn expression(program: &Program, r: usize, args: &[String], offset: usize) -> String {
+    match program.instructions()[r] {
+        Instruction::Input(p) => format!("T({})", args[p]),
+        Instruction::Constant(c) => format!("T({})", literal(c)),
+        Instruction::Unary(op, a) => match op {
+            Negation => format!("-r{}", a + offset),
+            Ln => format!("log(r{})", a + offset),
+            _ => format!("{}(r{})", op, a + offset),
+        },
+        Instruction::Binary(op, a, b) => match (op, program.instructions()[b]) {
+            // Integer literal exponents are computed by multiplication, even for negative
+            // bases.
+            (Pow, Instruction::Constant(c)) if c.fract() == 0.0 && c.abs() <= 64.0 => {
+                format!("r{}^({})", a + offset, c as i64)
+            }
+            _ => format!("r{} {} r{}", a + offset, op, b + offset),
+        },
+    }
+}
+
+/// Generates a function called `name` evaluating every output of `program`.
+pub fn program_to_julia(program: &Program, name: &str) -> String {
+    let args = argument_names(program.params(), is_argument_name);
+    let mut source = String::new();
+    header(&mut source, name, &args, program.params());
+
+    for r in 0..program.instructions().len() {
+        let _ = writeln!(source, "    r{} = {}", r, expression(program, r, &args, 0));
+    }
+
+    let outputs = program
+        .outputs()
+        .iter()
+        .map(|r| format!("r{}", r))
+        .collect::<Vec<_>>();
+    match outputs.as_slice() {
+        [output] => {
+            let _ = writeln!(source, "    return {}", output);
+        }
+        outputs => {
+            let _ = writeln!(source, "    return ({},)", outputs.join(", "));
+        }
+    }
+    source.push_str("end\n");
+    source
+}
+
+/// Generates a function called `name` evaluating the compiled sum with a `for` loop. The loop
+/// variable is named after the index where possible, and the instructions of the body that
+/// don't read it are computed before the loop.
+pub fn sum_to_julia(sum: &SumProgram, name: &str) -> String {
+    let names = [sum.params(), &[sum.index()]].concat();
+    let mut args = argument_names(&names, is_argument_name);
+    let index = args.pop().expect("Oops, the index has a name");
+    let mut source = String::new();
+    header(&mut source, name, &args, sum.params());
+    if index != sum.index() {
+        let _ = writeln!(source, "    # {}: {}", index, sum.index());
+    }
+
+    let (bounds, body) = (sum.bounds(), sum.body());
+    for r in 0..bounds.instructions().len() {
+        let _ = writeln!(source, "    r{} = {}", r, expression(bounds, r, &args, 0));
+    }
+    let offset = bounds.instructions().len();
+    let total = offset + body.instructions().len();
+    args.push(index.clone());
+    let varying = sum.varying();
+    for r in (0..body.instructions().len()).filter(|&r| !varying[r]) {
+        let _ = writeln!(
+            source,
+            "    r{} = {}",
+            r + offset,
+            expression(body, r, &args, offset)
+        );
+    }
+    let _ = writeln!(source, "    r{} = zero(T)", total);
+    let [lo, hi] = [0, 1].map(|i| bounds.outputs()[i]);
+    let _ = writeln!(source, "    for {} in Int(r{}):Int(r{})", index, lo, hi);
+    for r in (0..body.instructions().len()).filter(|&r| varying[r]) {
+        let _ = writeln!(
+            source,
+            "        r{} = {}",
+            r + offset,
+            expression(body, r, &args, offset)
+        );
+    }
+    let _ = writeln!(
+        source,
+        "        r{} += r{}",
+        total,
+        body.outputs()[0] + offset
+    );
+    source.push_str("    end\n");
+    let _ = writeln!(source, "    return r{}", total);
+    source.push_str("end\n");
+    source
+}
+
+/// Generates a function called `name` evaluating `exprs` from the `inputs`.
+pub fn to_julia(
+    exprs: &[OpArgument],
+    inputs: &[&'static str],
+    name: &str,
+) -> Result<String, CodegenError> {
+    Ok(program_to_julia(&compile_many(exprs, inputs)?, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sum_to_julia, to_julia};
+    use crate::{
+        codegen::CodegenError,
+        compile::compile_sum,
+        constants::Value,
+        parse::parse,
+        sum::Sum,
+        symbols::{intern, variable, OpArgument},
+    };
+
+    #[test]
+    fn test_to_julia() {
+        let x = variable("x");
+        let y = variable("y");
+        let shared = x.sin();
+        let f = &shared * &shared + y.ln();
+
+        let source = to_julia(std::slice::from_ref(&f), &["x", "y"], "kernel").unwrap();
+        assert!(source.starts_with(
+            "function kernel(x::Real, y::Real)\n    T = float(promote_type(typeof(x), typeof(y)))\n"
+        ));
+        assert!(source.contains("    r0 = T(x)\n    r1 = sin(r0)\n    r2 = r1 * r1\n"));
+        assert!(source.contains("    r4 = log(r3)\n"));
+        assert!(source.ends_with("    return r5\nend\n"));
+
+        // Awkward names become positional, and several outputs are a tuple.
+        let scoped = variable(intern("a.b"));
+        let g = parse("x^3 - 1/4").unwrap().fold() + OpArgument::from(Value::Inf) * scoped;
+        let source = to_julia(&[g, x.sin()], &["x", "a.b"], "both").unwrap();
+        assert!(source.starts_with("# p2: a.b\nfunction both(x::Real, p2::Real)\n"));
+        assert!(source.contains(" = T(0.25)\n"));
+        assert!(source.contains(" = T(Inf)\n"));
+        assert!(source.contains("return (r"));
+        let (r0, p1) = (variable(intern("r0")), variable(intern("p1")));
+        let source = to_julia(&[&r0 * &p1], &["r0", "p1"], "f").unwrap();
+        assert!(source.starts_with("# p2: r0\nfunction f(p2::Real, p1::Real)\n"));
+
+        assert_eq!(
+            to_julia(&[f], &["x"], "kernel"),
+            Err(CodegenError::UnknownVariable("y"))
+        );
+
+        // Sums loop over their index, with the parts that don't read it hoisted.
+        let sum = Sum {
+            body: parse("x^k / (k + 1)").unwrap(),
+            index: "k",
+            lo: parse("0").unwrap(),
+            hi: parse("n - 1").unwrap(),
+        };
+        let source = sum_to_julia(&compile_sum(&sum, &["x", "n"]).unwrap(), "series");
+        assert!(source.starts_with("function series(x::Real, n::Real)\n"));
+        assert!(source.contains("    r3 = r1 - r2\n    r4 = T(x)\n    r7 = T(1.0)\n"));
+        assert!(source.contains(" = zero(T)\n    for k in Int(r0):Int(r3)\n        r5 = T(k)\n"));
+        assert!(source.ends_with(" += r9\n    end\n    return r10\nend\n"));
+    }
+}
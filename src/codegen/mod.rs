@@ -0,0 +1,115 @@
+//! This module describes how to turn our computational graph into source code for other
+//! languages and devices.
+
+use std::fmt::Display;
+
+use ahash::{HashSet, HashSetExt};
+
+use crate::{constants::Value, symbols::OperationKind};
+
+pub mod excel;
+pub mod fortran;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod julia;
+#[cfg(feature = "onnx")]
+pub mod onnx;
+pub mod python;
+pub mod smtlib;
+pub mod wgsl;
+
+/// The reasons generating code for an expression can fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CodegenError {
+    /// The expression references a variable that is not one of the generated code's inputs.
+    UnknownVariable(&'static str),
+    /// The expression contains a constant the target cannot represent.
+    UnsupportedConstant(Value),
+    /// The expression contains an operation the target cannot express.
+    UnsupportedOperation(OperationKind),
+    /// The expression contains a variable whose name the target cannot spell.
+    UnsupportedName(&'static str),
+    /// The target only computes a single output, but the program has this many.
+    UnsupportedOutputs(usize),
+    /// The name asked for the generated function is not a valid one in the target, or clashes
+    /// with a name the generated code uses.
+    UnsupportedFunctionName(String),
+    /// The index of a sum is also one of the parameters, which would make it two inputs.
+    IndexIsParameter(&'static str),
+}
+
+impl Display for CodegenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodegenError::UnknownVariable(name) => {
+                write!(f, "variable {} is not an input", name)
+            }
+            CodegenError::UnsupportedConstant(value) => {
+                write!(f, "constant {} is not supported by the target", value)
+            }
+            CodegenError::UnsupportedOperation(op) => {
+                write!(f, "operation {} is not supported by the target", op)
+            }
+            CodegenError::UnsupportedName(name) => {
+                write!(f, "variable name {} is not supported by the target", name)
+            }
+            CodegenError::UnsupportedOutputs(count) => {
+                write!(f, "the target computes one output, not {}", count)
+            }
+            CodegenError::UnsupportedFunctionName(name) => {
+                write!(f, "{:?} cannot name a function in the target", name)
+            }
+            CodegenError::IndexIsParameter(index) => {
+                write!(f, "the index {} of the sum is also a parameter", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CodegenError {}
+
+/// Argument names for `params` in a target whose identifiers `usable` accepts. Usable names are
+/// kept, the first time they occur, and every other parameter gets the first `p<k>` from its
+/// position on that no other argument has.
+pub(crate) fn argument_names(params: &[&str], usable: impl Fn(&str) -> bool) -> Vec<String> {
+    let mut taken = HashSet::new();
+    let kept = params
+        .iter()
+        .map(|param| usable(param) && taken.insert(param.to_string()))
+        .collect::<Vec<_>>();
+    params
+        .iter()
+        .zip(kept)
+        .enumerate()
+        .map(|(i, (param, kept))| {
+            if kept {
+                return param.to_string();
+            }
+            let fresh = (i + 1..)
+                .map(|k| format!("p{}", k))
+                .find(|name| usable(name) && !taken.contains(name))
+                .expect("Oops, there is always a free name");
+            taken.insert(fresh.clone());
+            fresh
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::argument_names;
+
+    #[test]
+    fn test_argument_names() {
+        let usable = |name: &str| !name.starts_with('r') && !name.contains('.');
+        assert_eq!(argument_names(&["r0", "p1"], usable), ["p2", "p1"]);
+        assert_eq!(
+            argument_names(&["x", "a.b", "x"], usable),
+            ["x", "p2", "p3"]
+        );
+        assert_eq!(
+            argument_names(&["p2", "r0", "p3"], usable),
+            ["p2", "p4", "p3"]
+        );
+    }
+}
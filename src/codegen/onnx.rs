@@ -0,0 +1,266 @@
+//! ONNX models evaluating an expression elementwise over batches of inputs.
+//!
+//! The generated model has one 1-D `double` input per parameter, named after it, and one 1-D
+//! `double` output per program output, named `output0`, `output1`, …, all sharing the symbolic
+//! batch dimension `N`, so no parameter may be called `output0`, `output1`, …. Every instruction
+//! of the compiled program becomes one ONNX node writing the tensor `r{register}`, prefixed with
+//! underscores when a parameter would have the same name, so shared subexpressions are computed
+//! once by runtimes as well.
+//! Constants are scalar `Constant` nodes, which broadcast against the batch.
+//!
+//! The model is serialized directly in the protobuf wire format of `onnx.proto`, targeting IR
+//! version 8 and the default operator set 13, without depending on an ONNX crate.
+
+use super::CodegenError;
+use crate::{
+    compile::{compile_many, Instruction, Program},
+    symbols::{OpArgument, OperationKind},
+};
+
+pub const IR_VERSION: u64 = 8;
+pub const OPSET_VERSION: u64 = 13;
+
+/// `TensorProto.DataType.DOUBLE`
+const DOUBLE: u64 = 11;
+/// `AttributeProto.AttributeType.TENSOR`
+const TENSOR: u64 = 4;
+
+/// The name of the ONNX operator computing `op`.
+fn operator(op: OperationKind) -> &'static str {
+    match op {
+        OperationKind::Addition => "Add",
+        OperationKind::Subtraction => "Sub",
+        OperationKind::Multiplication => "Mul",
+        OperationKind::Division => "Div",
+        OperationKind::Negation => "Neg",
+        OperationKind::Pow => "Pow",
+        OperationKind::Exp => "Exp",
+        OperationKind::Ln => "Log",
+        OperationKind::Sin => "Sin",
+        OperationKind::Cos => "Cos",
+        OperationKind::Tan => "Tan",
+        OperationKind::Atan => "Atan",
+        OperationKind::Sign => "Sign",
+    }
+}
+
+/// A protobuf message under construction.
+#[derive(Default)]
+struct Message(Vec<u8>);
+
+impl Message {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn tag(&mut self, field: u64, wire_type: u64) {
+        self.varint(field << 3 | wire_type);
+    }
+
+    fn uint(mut self, field: u64, value: u64) -> Self {
+        self.tag(field, 0);
+        self.varint(value);
+        self
+    }
+
+    fn bytes(mut self, field: u64, bytes: &[u8]) -> Self {
+        self.tag(field, 2);
+        self.varint(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    fn string(self, field: u64, value: &str) -> Self {
+        self.bytes(field, value.as_bytes())
+    }
+
+    fn message(self, field: u64, message: Message) -> Self {
+        self.bytes(field, &message.0)
+    }
+}
+
+/// A `ValueInfoProto` for a 1-D `double` tensor of length `N`.
+fn batch(name: &str) -> Message {
+    let dim = Message::default().string(2, "N");
+    let shape = Message::default().message(1, dim);
+    let tensor = Message::default().uint(1, DOUBLE).message(2, shape);
+    let ty = Message::default().message(1, tensor);
+    Message::default().string(1, name).message(2, ty)
+}
+
+fn node(op_type: &str, inputs: &[String], output: &str) -> Message {
+    let node = inputs
+        .iter()
+        .fold(Message::default(), |node, input| node.string(1, input));
+    node.string(2, output).string(3, output).string(4, op_type)
+}
+
+/// The prefix of the register tensors, the first of `r`, `_r`, `__r`, … that no parameter
+/// name continues with digits.
+fn register_prefix(params: &[&str]) -> String {
+    let clashes = |prefix: &str| {
+        params.iter().any(|param| {
+            param
+                .strip_prefix(prefix)
+                .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        })
+    };
+    (0..)
+        .map(|k| format!("{}r", "_".repeat(k)))
+        .find(|prefix| !clashes(prefix))
+        .expect("Oops, some prefix is always free")
+}
+
+/// Serializes a model evaluating every output of `program` elementwise over its parameters.
+pub fn program_to_onnx(program: &Program) -> Result<Vec<u8>, CodegenError> {
+    let output = |k: usize| format!("output{}", k);
+    let outputs = program.outputs().len();
+    if let Some(&param) = program
+        .params()
+        .iter()
+        .find(|&&param| (0..outputs).any(|k| param == output(k)))
+    {
+        return Err(CodegenError::UnsupportedName(param));
+    }
+    let prefix = register_prefix(program.params());
+    let register = |r: usize| format!("{}{}", prefix, r);
+    let mut graph = Message::default();
+    for (r, instruction) in program.instructions().iter().enumerate() {
+        let node = match *instruction {
+            Instruction::Input(p) => {
+                node("Identity", &[program.params()[p].to_owned()], &register(r))
+            }
+            Instruction::Constant(c) => {
+                let value = Message::default()
+                    .uint(2, DOUBLE)
+                    .bytes(10, &c.to_le_bytes());
+                let attribute = Message::default()
+                    .string(1, "value")
+                    .message(5, value)
+                    .uint(20, TENSOR);
+                node("Constant", &[], &register(r)).message(5, attribute)
+            }
+            Instruction::Unary(op, a) => node(operator(op), &[register(a)], &register(r)),
+            Instruction::Binary(op, a, b) => {
+                node(operator(op), &[register(a), register(b)], &register(r))
+            }
+        };
+        graph = graph.message(1, node);
+    }
+    for (k, &r) in program.outputs().iter().enumerate() {
+        graph = graph.message(1, node("Identity", &[register(r)], &output(k)));
+    }
+
+    graph = graph.string(2, "symbolica");
+    for param in program.params() {
+        graph = graph.message(11, batch(param));
+    }
+    for k in 0..outputs {
+        graph = graph.message(12, batch(&output(k)));
+    }
+
+    let opset = Message::default().string(1, "").uint(2, OPSET_VERSION);
+    Ok(Message::default()
+        .uint(1, IR_VERSION)
+        .string(2, "symbolica")
+        .message(7, graph)
+        .message(8, opset)
+        .0)
+}
+
+/// Serializes a model evaluating each of `exprs` elementwise over the `inputs` tensors.
+pub fn to_onnx(exprs: &[OpArgument], inputs: &[&'static str]) -> Result<Vec<u8>, CodegenError> {
+    program_to_onnx(&compile_many(exprs, inputs)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_onnx, IR_VERSION};
+    use crate::{
+        codegen::CodegenError,
+        constants::Value,
+        symbols::{intern, variable, OpArgument},
+    };
+
+    /// Splits a protobuf message into its fields, reading length-delimited ones as bytes.
+    fn fields(mut bytes: &[u8]) -> Vec<(u64, Vec<u8>)> {
+        let varint = |bytes: &mut &[u8]| {
+            let mut value = 0;
+            for shift in (0..).step_by(7) {
+                let byte = bytes[0];
+                *bytes = &bytes[1..];
+                value |= ((byte & 0x7f) as u64) << shift;
+                if byte < 0x80 {
+                    break;
+                }
+            }
+            value
+        };
+        let mut fields = Vec::new();
+        while !bytes.is_empty() {
+            let tag = varint(&mut bytes);
+            let value = match tag & 7 {
+                0 => varint(&mut bytes).to_le_bytes().to_vec(),
+                _ => {
+                    let len = varint(&mut bytes) as usize;
+                    let (value, rest) = bytes.split_at(len);
+                    bytes = rest;
+                    value.to_vec()
+                }
+            };
+            fields.push((tag >> 3, value));
+        }
+        fields
+    }
+
+    fn op_type(node: &[u8]) -> String {
+        let (_, name) = fields(node).into_iter().find(|(f, _)| *f == 4).unwrap();
+        String::from_utf8(name).unwrap()
+    }
+
+    #[test]
+    fn test_to_onnx() {
+        let x = variable("x");
+        let y = variable("y");
+        let shared = x.sin();
+        let expr = &shared * &shared + y.ln();
+
+        let model = to_onnx(&[expr.clone(), x.clone()], &["x", "y"]).unwrap();
+        let top = fields(&model);
+        assert_eq!(top[0], (1, IR_VERSION.to_le_bytes().to_vec()));
+        let (_, graph) = top.iter().find(|(f, _)| *f == 7).unwrap();
+        let graph = fields(graph);
+        let ops = graph
+            .iter()
+            .filter(|(f, _)| *f == 1)
+            .map(|(_, node)| op_type(node))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ops,
+            ["Identity", "Sin", "Mul", "Identity", "Log", "Add", "Identity", "Identity"]
+        );
+        assert_eq!(graph.iter().filter(|(f, _)| *f == 11).count(), 2);
+        assert_eq!(graph.iter().filter(|(f, _)| *f == 12).count(), 2);
+
+        let constant = to_onnx(&[&x * OpArgument::from(Value::rational(5, 2))], &["x"]).unwrap();
+        assert!(constant.windows(8).any(|w| w == 2.5f64.to_le_bytes()));
+
+        assert_eq!(
+            to_onnx(&[expr], &["x"]),
+            Err(CodegenError::UnknownVariable("y"))
+        );
+
+        // Registers step aside for parameters named like them, but outputs can't.
+        let (r0, output0) = (variable(intern("r0")), variable(intern("output0")));
+        let model = to_onnx(&[r0.sin()], &["r0"]).unwrap();
+        assert!(model.windows(3).any(|w| w == b"_r1"));
+        assert_eq!(
+            to_onnx(&[output0.sin()], &["output0"]),
+            Err(CodegenError::UnsupportedName("output0"))
+        );
+    }
+}
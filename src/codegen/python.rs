@@ -0,0 +1,181 @@
+//! Python functions evaluating a compiled program with NumPy or JAX, for using derived formulas
+//! from Python without bindings.
+//!
+//! The generated source imports its array module and defines a function taking one argument
+//! per parameter. Every operation is a ufunc, so the function evaluates elementwise over arrays
+//! of any shape that broadcast together, and with [`PythonTarget::Jax`] it can be traced by
+//! `jax.jit` and `jax.grad`. Each register of the program is a temporary, so common
+//! subexpressions are computed once.
+
+use std::fmt::Write;
+
+use super::{argument_names, CodegenError};
+use crate::{
+    compile::{compile_many, Instruction, Program},
+    symbols::{OpArgument, OperationKind::*},
+};
+
+/// The array module a generated function computes with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PythonTarget {
+    NumPy,
+    Jax,
+}
+
+impl PythonTarget {
+    fn import(self) -> &'static str {
+        match self {
+            PythonTarget::NumPy => "import numpy as np",
+            PythonTarget::Jax => "import jax.numpy as jnp",
+        }
+    }
+
+    fn alias(self) -> &'static str {
+        match self {
+            PythonTarget::NumPy => "np",
+            PythonTarget::Jax => "jnp",
+        }
+    }
+}
+
+const KEYWORDS: [&str; 35] = [
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+/// Whether `name` can be used as is for an argument, without clashing with a register or the
+/// array module.
+fn is_argument_name(name: &str) -> bool {
+    let register = name
+        .strip_prefix('r')
+        .is_some_and(|n| n.chars().all(|c| c.is_ascii_digit()));
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !register
+        && !["np", "jnp"].contains(&name)
+        && !KEYWORDS.contains(&name)
+}
+
+/// Generates a function called `name` evaluating every output of `program` with `target`.
+pub fn program_to_python(program: &Program, name: &str, target: PythonTarget) -> String {
+    assert!(
+        is_argument_name(name),
+        "Hold on, {:?} is not a name we can give a Python function",
+        name
+    );
+    let np = target.alias();
+
+    let args = argument_names(program.params(), is_argument_name);
+
+    let mut source = String::new();
+    let _ = writeln!(source, "{}\n\n", target.import());
+    let _ = writeln!(source, "def {}({}):", name, args.join(", "));
+    for (arg, param) in args.iter().zip(program.params()) {
+        if arg != param {
+            let _ = writeln!(source, "    # {}: {}", arg, param);
+        }
+    }
+
+    for (r, instruction) in program.instructions().iter().enumerate() {
+        let expr = match *instruction {
+            Instruction::Input(p) => format!("{}.asarray({})", np, args[p]),
+            Instruction::Constant(c) if c.is_nan() => format!("{}.nan", np),
+            Instruction::Constant(c) if c.is_infinite() => {
+                format!("{}{}.inf", if c < 0.0 { "-" } else { "" }, np)
+            }
+            Instruction::Constant(c) => format!("{:?}", c),
+            Instruction::Unary(op, a) => match op {
+                Negation => format!("-r{}", a),
+                Ln => format!("{}.log(r{})", np, a),
+                Atan => format!("{}.arctan(r{})", np, a),
+                _ => format!("{}.{}(r{})", np, op, a),
+            },
+            Instruction::Binary(Pow, a, b) => format!("r{} ** r{}", a, b),
+            Instruction::Binary(op, a, b) => format!("r{} {} r{}", a, op, b),
+        };
+        let _ = writeln!(source, "    r{} = {}", r, expr);
+    }
+
+    let outputs = program
+        .outputs()
+        .iter()
+        .map(|r| format!("r{}", r))
+        .collect::<Vec<_>>();
+    match outputs.as_slice() {
+        [output] => {
+            let _ = writeln!(source, "    return {}", output);
+        }
+        outputs => {
+            let _ = writeln!(source, "    return ({},)", outputs.join(", "));
+        }
+    }
+    source
+}
+
+/// Generates a function called `name` evaluating `exprs` from the `inputs` with `target`.
+pub fn to_python(
+    exprs: &[OpArgument],
+    inputs: &[&'static str],
+    name: &str,
+    target: PythonTarget,
+) -> Result<String, CodegenError> {
+    Ok(program_to_python(
+        &compile_many(exprs, inputs)?,
+        name,
+        target,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_python, PythonTarget};
+    use crate::{
+        codegen::CodegenError,
+        constants::Value,
+        symbols::{intern, variable, OpArgument},
+    };
+
+    #[test]
+    fn test_to_python() {
+        let x = variable("x");
+        let y = variable("y");
+        let shared = x.atan();
+        let f = &shared * &shared + y.ln();
+
+        let source = to_python(
+            std::slice::from_ref(&f),
+            &["x", "y"],
+            "kernel",
+            PythonTarget::NumPy,
+        )
+        .unwrap();
+        assert!(source.starts_with("import numpy as np\n\n\ndef kernel(x, y):\n"));
+        assert!(
+            source.contains("    r0 = np.asarray(x)\n    r1 = np.arctan(r0)\n    r2 = r1 * r1\n")
+        );
+        assert!(source.contains("    r4 = np.log(r3)\n"));
+        assert!(source.ends_with("    return r5\n"));
+        assert_eq!(source.matches("arctan(").count(), 1);
+
+        let scoped = variable(intern("lambda"));
+        let g = x.pow(&OpArgument::from(Value::integer(3))) + OpArgument::from(Value::Inf) * scoped;
+        let source = to_python(&[g, x.exp()], &["x", "lambda"], "both", PythonTarget::Jax).unwrap();
+        assert!(
+            source.starts_with("import jax.numpy as jnp\n\n\ndef both(x, p2):\n    # p2: lambda\n")
+        );
+        assert!(source.contains(" = jnp.inf\n"));
+        assert!(source.contains(" = jnp.exp(r0)\n"));
+        assert!(source.contains("    return (r"));
+        // A renamed argument never takes the name of another.
+        let (r0, p1) = (variable(intern("r0")), variable(intern("p1")));
+        let source = to_python(&[&r0 * &p1], &["r0", "p1"], "f", PythonTarget::NumPy).unwrap();
+        assert!(source.contains("def f(p2, p1):\n    # p2: r0\n"));
+
+        assert_eq!(
+            to_python(&[f], &["x"], "kernel", PythonTarget::NumPy),
+            Err(CodegenError::UnknownVariable("y"))
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! SMT-LIB2 scripts in the `QF_NRA` logic, for checking results with Z3, cvc5 and the like.
+//!
+//! Expressions become `Real` terms built from `+`, `-`, `*` and `/`, with integer powers
+//! multiplied out. `QF_NRA` has no transcendental functions or constants, so expressions using
+//! them cannot be exported. Division is total in SMT-LIB, with `x/0` an unspecified value, so a
+//! simplification that cancels a factor which could be zero may still be reported equivalent.
+
+use super::CodegenError;
+use crate::{
+    constants::Value,
+    constraint::Constraint,
+    fold::Ratio,
+    metadata::VariableSet,
+    symbols::{
+        OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        OperationKind::*,
+    },
+};
+
+/// The words SMT-LIB reserves, which can only be used as symbols when quoted.
+const RESERVED: [&str; 13] = [
+    "!",
+    "_",
+    "as",
+    "BINARY",
+    "DECIMAL",
+    "exists",
+    "HEXADECIMAL",
+    "forall",
+    "let",
+    "match",
+    "NUMERAL",
+    "par",
+    "STRING",
+];
+
+/// A symbol for `name`, quoted unless it is a plain identifier. Quoted symbols cannot contain
+/// `|` or `\`, so names with either have no spelling at all.
+fn symbol(name: &'static str) -> Result<String, CodegenError> {
+    let simple = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && !RESERVED.contains(&name);
+    match simple {
+        _ if name.contains(['|', '\\']) => Err(CodegenError::UnsupportedName(name)),
+        true => Ok(name.to_owned()),
+        false => Ok(format!("|{}|", name)),
+    }
+}
+
+/// A decimal literal for the finite `x`.
+fn real(x: f64) -> String {
+    let digits = format!("{}", x.abs());
+    let digits = match digits.contains('.') {
+        true => digits,
+        false => digits + ".0",
+    };
+    match x < 0.0 {
+        true => format!("(- {})", digits),
+        false => digits,
+    }
+}
+
+/// The `Real` term computing `expr`.
+pub fn to_smtlib(expr: &OpArgument) -> Result<String, CodegenError> {
+    let op = match &expr.value {
+        Leaf(value) => {
+            return match **value {
+                Value::Rational(num, den) if den.get() == 1 => Ok(format!("{}.0", num)),
+                Value::Rational(num, den) => Ok(format!("(/ {}.0 {}.0)", num, den)),
+                Value::Variable(name) => symbol(name),
+                value => Err(CodegenError::UnsupportedConstant(value)),
+            }
+        }
+        Op(op) => op,
+    };
+    let arg = |i: usize| to_smtlib(&op.arguments[i]);
+    Ok(match op.op {
+        Addition | Subtraction | Multiplication | Division => {
+            format!("({} {} {})", op.op, arg(0)?, arg(1)?)
+        }
+        Negation => format!("(- {})", arg(0)?),
+        Pow => {
+            let n = Ratio::of(&op.arguments[1].fold())
+                .filter(|n| n.den == 1)
+                .ok_or(CodegenError::UnsupportedOperation(Pow))?
+                .num;
+            let base = arg(0)?;
+            let product = match n.unsigned_abs() {
+                0 => "1.0".to_owned(),
+                1 => base,
+                k => format!("(* {})", vec![base; k as usize].join(" ")),
+            };
+            match n < 0 {
+                true => format!("(/ 1.0 {})", product),
+                false => product,
+            }
+        }
+        op => return Err(CodegenError::UnsupportedOperation(op)),
+    })
+}
+
+/// A script declaring every variable of `exprs`, asserting `assertions` and checking them.
+fn script(exprs: &[&OpArgument], assertions: &[String]) -> Result<String, CodegenError> {
+    let mut variables = VariableSet::new();
+    for expr in exprs {
+        variables.union_with(&expr.free_variables());
+    }
+    let mut names = variables.iter().collect::<Vec<_>>();
+    names.sort_unstable();
+
+    let mut script = String::from("(set-logic QF_NRA)\n");
+    for name in names {
+        script += &format!("(declare-fun {} () Real)\n", symbol(name)?);
+    }
+    for assertion in assertions {
+        script += &format!("(assert {})\n", assertion);
+    }
+    Ok(script + "(check-sat)\n")
+}
+
+/// A script that is `unsat` exactly when `a` and `b` agree for every real value of their
+/// variables.
+pub fn equivalence_query(a: &OpArgument, b: &OpArgument) -> Result<String, CodegenError> {
+    let assertion = format!("(not (= {} {}))", to_smtlib(a)?, to_smtlib(b)?);
+    script(&[a, b], &[assertion])
+}
+
+/// A script that is `sat` exactly when some real values of the variables satisfy every one of
+/// `constraints`.
+pub fn satisfiability_query(constraints: &[Constraint]) -> Result<String, CodegenError> {
+    let mut assertions = Vec::new();
+    for Constraint { expr, range } in constraints {
+        let term = to_smtlib(expr)?;
+        if range.is_empty() {
+            assertions.push("false".to_owned());
+        } else if range.lo == range.hi {
+            assertions.push(format!("(= {} {})", term, real(range.lo)));
+        } else {
+            if range.lo.is_finite() {
+                assertions.push(format!("(<= {} {})", real(range.lo), term));
+            }
+            if range.hi.is_finite() {
+                assertions.push(format!("(<= {} {})", term, real(range.hi)));
+            }
+        }
+    }
+    let exprs = constraints.iter().map(|c| &c.expr).collect::<Vec<_>>();
+    script(&exprs, &assertions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{equivalence_query, satisfiability_query, to_smtlib};
+    use crate::{
+        codegen::CodegenError, constraint::Constraint, interval::Interval, parse::parse,
+        symbols::OperationKind,
+    };
+
+    #[test]
+    fn test_to_smtlib() {
+        let p = |input: &str| parse(input).unwrap();
+        assert_eq!(
+            to_smtlib(&p("3/4 * x^3 - y / 2")).unwrap(),
+            "(- (* (/ 3.0 4.0) (* x x x)) (/ y 2.0))"
+        );
+        assert_eq!(to_smtlib(&p("-x^(-2)")).unwrap(), "(- (/ 1.0 (* x x)))");
+        assert_eq!(
+            to_smtlib(&p("sin(x)")),
+            Err(CodegenError::UnsupportedOperation(OperationKind::Sin))
+        );
+        assert_eq!(
+            to_smtlib(&p("x^y")),
+            Err(CodegenError::UnsupportedOperation(OperationKind::Pow))
+        );
+
+        let query = equivalence_query(&p("(x + y)^2"), &p("x^2 + 2*x*y + y^2")).unwrap();
+        assert_eq!(
+            query,
+            "(set-logic QF_NRA)\n\
+             (declare-fun x () Real)\n\
+             (declare-fun y () Real)\n\
+             (assert (not (= (* (+ x y) (+ x y)) (+ (+ (* x x) (* (* 2.0 x) y)) (* y y)))))\n\
+             (check-sat)\n"
+        );
+
+        let constraints = [
+            Constraint::less_equal(&p("x^2 + y^2"), &p("1")),
+            Constraint::new(p("x"), Interval::new(-0.5, 0.25)),
+            Constraint::equal(&p("y"), &p("x")),
+        ];
+        let query = satisfiability_query(&constraints).unwrap();
+        assert!(query.contains("(assert (<= (- (+ (* x x) (* y y)) 1.0) 0.0))\n"));
+        assert!(query.contains("(assert (<= (- 0.5) x))\n(assert (<= x 0.25))\n"));
+        assert!(query.contains("(assert (= (- y x) 0.0))\n"));
+    }
+
+    #[test]
+    fn test_symbols() {
+        let var = |name: &'static str| crate::symbols::variable(name);
+        assert_eq!(to_smtlib(&var("x_1")).unwrap(), "x_1");
+        assert_eq!(to_smtlib(&var("let")).unwrap(), "|let|");
+        assert_eq!(to_smtlib(&var("forall")).unwrap(), "|forall|");
+        assert_eq!(to_smtlib(&var("x@1")).unwrap(), "|x@1|");
+        assert_eq!(
+            to_smtlib(&var("a|b")),
+            Err(CodegenError::UnsupportedName("a|b"))
+        );
+        assert_eq!(
+            to_smtlib(&var("a\\b")),
+            Err(CodegenError::UnsupportedName("a\\b"))
+        );
+
+        let query = equivalence_query(&var("let"), &var("let")).unwrap();
+        assert!(query.contains("(declare-fun |let| () Real)\n"));
+    }
+}
@@ -0,0 +1,181 @@
+//! WGSL compute shaders evaluating an expression elementwise over storage buffers.
+//!
+//! The generated shader binds one read-only `array<f32>` per input at `@group(0)` bindings
+//! `0..n` in the order the inputs were given, and the read-write output array at binding `n`.
+//! WGSL has no infinite literals and rejects constant expressions that overflow, so a program
+//! with a constant too large for `f32` also binds a uniform `f32` holding `+∞` at binding
+//! `n + 1` (see [`needs_infinity`]). Invocation `i` writes `output[i]` from element `i` of
+//! every input, counting along `x` first and then `y`, so a batch larger than one dispatch
+//! dimension allows is covered by dispatching `(min(groups, 65535), ceil(groups / 65535), 1)`
+//! workgroups, with `groups = ceil(len / WORKGROUP_SIZE)`.
+//!
+//! Shaders compute in single precision; [`crate::precision::check_f32`] reports programs that
+//! would lose too much accuracy doing so, and [`Program::evaluate_f32`] reproduces the shader's
+//! arithmetic on the CPU. With the `gpu` feature, [`super::gpu`] runs them through `wgpu`.
+
+use std::fmt::Write;
+
+use super::CodegenError;
+use crate::{
+    compile::{compile, Instruction, Program},
+    constants::Value,
+    symbols::{OpArgument, OperationKind::*},
+};
+
+pub const WORKGROUP_SIZE: u32 = 64;
+
+fn literal(value: f64) -> Result<String, CodegenError> {
+    let value = value as f32;
+    if value.is_nan() {
+        Err(CodegenError::UnsupportedConstant(Value::Undefined))
+    } else if value == f32::INFINITY {
+        Ok("infinity".to_owned())
+    } else if value == f32::NEG_INFINITY {
+        Ok("-infinity".to_owned())
+    } else {
+        Ok(format!("{:?}", value))
+    }
+}
+
+/// Whether the shader for `program` binds the `infinity` uniform after its output.
+pub fn needs_infinity(program: &Program) -> bool {
+    program.instructions().iter().any(
+        |instruction| matches!(*instruction, Instruction::Constant(c) if (c as f32).is_infinite()),
+    )
+}
+
+/// Generates a compute shader evaluating the single output of `program` for every element of
+/// its parameter buffers.
+pub fn program_to_wgsl(program: &Program) -> Result<String, CodegenError> {
+    if program.outputs().len() != 1 {
+        return Err(CodegenError::UnsupportedOutputs(program.outputs().len()));
+    }
+
+    let mut shader = String::new();
+    for (i, param) in program.params().iter().enumerate() {
+        let _ = writeln!(
+            shader,
+            "// {}\n@group(0) @binding({}) var<storage, read> input{}: array<f32>;",
+            param, i, i
+        );
+    }
+    let _ = writeln!(
+        shader,
+        "@group(0) @binding({}) var<storage, read_write> output: array<f32>;\n",
+        program.params().len()
+    );
+    if needs_infinity(program) {
+        let _ = writeln!(
+            shader,
+            "@group(0) @binding({}) var<uniform> infinity: f32;\n",
+            program.params().len() + 1
+        );
+    }
+    let _ = writeln!(
+        shader,
+        "@compute @workgroup_size({})\nfn main(\n    @builtin(global_invocation_id) id: vec3<u32>,\n    @builtin(num_workgroups) groups: vec3<u32>,\n) {{",
+        WORKGROUP_SIZE
+    );
+    let _ = writeln!(
+        shader,
+        "    let i = id.x + id.y * groups.x * {}u;\n    if (i >= arrayLength(&output)) {{\n        return;\n    }}",
+        WORKGROUP_SIZE
+    );
+
+    for (r, instruction) in program.instructions().iter().enumerate() {
+        let expr = match *instruction {
+            Instruction::Input(p) => format!("input{}[i]", p),
+            Instruction::Constant(c) => literal(c)?,
+            Instruction::Unary(op, a) => match op {
+                Negation => format!("-r{}", a),
+                Ln => format!("log(r{})", a),
+                _ => format!("{}(r{})", op, a),
+            },
+            Instruction::Binary(op, a, b) => match op {
+                Pow => format!("pow(r{}, r{})", a, b),
+                _ => format!("r{} {} r{}", a, op, b),
+            },
+        };
+        let _ = writeln!(shader, "    let r{}: f32 = {};", r, expr);
+    }
+
+    let _ = writeln!(shader, "    output[i] = r{};\n}}", program.outputs()[0]);
+    Ok(shader)
+}
+
+/// Generates a compute shader evaluating `expr` for every element of the `inputs` buffers.
+pub fn to_wgsl(expr: &OpArgument, inputs: &[&'static str]) -> Result<String, CodegenError> {
+    program_to_wgsl(&compile(expr, inputs)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{program_to_wgsl, to_wgsl};
+    use crate::{
+        codegen::CodegenError,
+        compile::compile_many,
+        constants::Value,
+        parse::parse,
+        symbols::{variable, OpArgument},
+    };
+
+    fn validate(shader: &str) {
+        let module = naga::front::wgsl::parse_str(shader)
+            .unwrap_or_else(|error| panic!("{}\n{}", error.emit_to_string(shader), shader));
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .unwrap_or_else(|error| panic!("{:?}\n{}", error, shader));
+    }
+
+    #[test]
+    fn test_to_wgsl() {
+        let x = variable("x");
+        let y = variable("y");
+        let shared = x.sin();
+        let expr = &shared * &shared + y.ln();
+
+        let shader = to_wgsl(&expr, &["x", "y"]).unwrap();
+        assert!(shader.contains("var<storage, read> input0: array<f32>;"));
+        assert!(shader.contains("@group(0) @binding(2) var<storage, read_write> output"));
+        assert!(shader.contains("let r0: f32 = input0[i];"));
+        assert!(shader.contains("let r1: f32 = sin(r0);"));
+        assert!(shader.contains("let r2: f32 = r1 * r1;"));
+        assert!(shader.contains("let r4: f32 = log(r3);"));
+        assert!(shader.contains("output[i] = r5;"));
+        assert_eq!(shader.matches("sin(").count(), 1);
+        assert!(!shader.contains("infinity"));
+        validate(&shader);
+
+        // Constants beyond single precision read the uniform rather than a literal WGSL rejects.
+        let shader = to_wgsl(
+            &parse("atan(x * 10^39) - inf + sign(x)").unwrap(),
+            &["x", "y"],
+        );
+        let shader = shader.unwrap();
+        assert!(shader.contains("@group(0) @binding(3) var<uniform> infinity: f32;"));
+        assert!(shader.contains(" = infinity;"));
+        assert!(!shader.contains("bitcast"));
+        validate(&shader);
+        assert_eq!(
+            to_wgsl(&(&x * OpArgument::from(Value::Undefined)), &["x"]),
+            Err(CodegenError::UnsupportedConstant(Value::Undefined))
+        );
+
+        assert_eq!(
+            to_wgsl(&expr, &["x"]),
+            Err(CodegenError::UnknownVariable("y"))
+        );
+        let pair = compile_many(&[x.sin(), x.cos()], &["x"]).unwrap();
+        assert_eq!(
+            program_to_wgsl(&pair),
+            Err(CodegenError::UnsupportedOutputs(2))
+        );
+        assert_eq!(
+            to_wgsl(&(x + OpArgument::from(Value::I)), &["x"]),
+            Err(CodegenError::UnsupportedConstant(Value::I))
+        );
+    }
+}
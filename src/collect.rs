@@ -0,0 +1,548 @@
+//! This module describes collecting the terms of an expression by the powers of some of its
+//! variables.
+//!
+//! [`OpArgument::collect`] expands an expression that is a polynomial in the chosen variables,
+//! whose coefficients may be any expressions free of them, and groups its terms by monomial.
+//! The result maps each monomial, written as its exponents in the order the variables were
+//! given, to its folded coefficient, which is what code generators and perturbation analyses
+//! read off. Collecting by a single variable gives the usual univariate coefficients.
+//!
+//! Monomials forget the order of their factors, so products that would have to move symbols
+//! declared with [`crate::symbols::declare_non_commutative`] past each other are refused with
+//! [`CollectError::NonCommutative`].
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use crate::{
+    budget::{Budget, Exhausted},
+    constants::Value,
+    fold::Ratio,
+    series::{one, plus, times, zero},
+    symbols::{
+        is_commutative_symbol, variable, OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        OperationKind::*,
+    },
+};
+
+/// `-c`, without stacking negations.
+fn negate(c: &OpArgument) -> OpArgument {
+    if let Some(r) = Ratio::of(c).and_then(|r| (-r).to_oparg()) {
+        return r;
+    }
+    match &c.value {
+        Op(op) if op.op == Negation => op.arguments[0].clone(),
+        _ => -c,
+    }
+}
+
+/// The reasons an expression can fail to be collected.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CollectError {
+    /// The subexpression is not a polynomial in the variables, such as `sin(x)` or `1/x`.
+    NotPolynomial(OpArgument),
+    /// The budget ran out before the expansion was complete.
+    Interrupted(Exhausted),
+    /// The product multiplies factors that do not commute, which monomials cannot keep in
+    /// order.
+    NonCommutative(OpArgument),
+}
+
+impl Display for CollectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CollectError::NotPolynomial(expr) => write!(f, "{} is not a polynomial", expr),
+            CollectError::Interrupted(reason) => reason.fmt(f),
+            CollectError::NonCommutative(expr) => {
+                write!(f, "{} multiplies factors that do not commute", expr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CollectError {}
+
+impl From<Exhausted> for CollectError {
+    fn from(reason: Exhausted) -> Self {
+        CollectError::Interrupted(reason)
+    }
+}
+
+/// The exponents of the variables of a [`Collected`] in a term, in the order of the variables.
+pub type Monomial = Vec<u32>;
+
+/// The terms of an expression grouped by monomials in some of its variables.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Collected {
+    variables: Vec<&'static str>,
+    /// Nonzero coefficients, in lexicographic order of the monomials.
+    terms: BTreeMap<Monomial, OpArgument>,
+}
+
+impl Collected {
+    fn constant(variables: &[&'static str], c: OpArgument) -> Self {
+        let mut terms = BTreeMap::new();
+        if c != zero() {
+            terms.insert(vec![0; variables.len()], c);
+        }
+        Collected {
+            variables: variables.to_vec(),
+            terms,
+        }
+    }
+
+    /// The collected polynomial with the given nonzero `terms`.
+    pub(crate) fn from_terms(
+        variables: &[&'static str],
+        terms: BTreeMap<Monomial, OpArgument>,
+    ) -> Self {
+        Collected {
+            variables: variables.to_vec(),
+            terms,
+        }
+    }
+
+    fn add(mut self, other: Collected) -> Self {
+        for (monomial, c) in other.terms {
+            let sum = match self.terms.remove(&monomial) {
+                Some(previous) => plus(&previous, &c),
+                None => c,
+            };
+            if sum != zero() {
+                self.terms.insert(monomial, sum);
+            }
+        }
+        self
+    }
+
+    fn divide(mut self, divisor: &OpArgument) -> Self {
+        for coefficient in self.terms.values_mut() {
+            *coefficient = (&*coefficient / divisor).fold();
+        }
+        self
+    }
+
+    fn negate(mut self) -> Self {
+        for coefficient in self.terms.values_mut() {
+            *coefficient = negate(coefficient);
+        }
+        self
+    }
+
+    /// The variables of `monomial` that do not commute.
+    fn non_commuting(&self, monomial: &[u32]) -> Vec<&'static str> {
+        monomial
+            .iter()
+            .zip(&self.variables)
+            .filter(|(&k, var)| k > 0 && !is_commutative_symbol(var))
+            .map(|(_, &var)| var)
+            .collect()
+    }
+
+    /// The product `node`, spending a step of `budget` per pair of terms multiplied and holding
+    /// its terms to the size limit. Coefficients keep their order, but `(c·m)·(d·n)` is only
+    /// `(c·d)·(m·n)` if the monomial `m` commutes with `d` and with `n`, so every other product
+    /// of factors that do not commute is refused.
+    fn mul(
+        &self,
+        other: &Collected,
+        node: &OpArgument,
+        budget: &Budget,
+    ) -> Result<Self, CollectError> {
+        let mut product = Collected::constant(&self.variables, zero());
+        for (a, c) in &self.terms {
+            for (b, d) in &other.terms {
+                budget.spend()?;
+                let (m, n) = (self.non_commuting(a), self.non_commuting(b));
+                let commutes = m.is_empty()
+                    || (d.is_commutative() && (n.is_empty() || (m.len() == 1 && m == n)));
+                if !commutes {
+                    return Err(CollectError::NonCommutative(node.clone()));
+                }
+                let monomial = a.iter().zip(b).map(|(i, j)| i + j).collect();
+                let term = Collected {
+                    variables: self.variables.clone(),
+                    terms: BTreeMap::from([(monomial, times(c, d))]),
+                };
+                product = product.add(term);
+                budget.fits(product.terms.len())?;
+            }
+        }
+        Ok(product)
+    }
+
+    pub fn variables(&self) -> &[&'static str] {
+        &self.variables
+    }
+
+    /// The monomials with a nonzero coefficient and their coefficients, in lexicographic order
+    /// of the exponents.
+    pub fn terms(&self) -> impl Iterator<Item = (&[u32], &OpArgument)> {
+        self.terms
+            .iter()
+            .map(|(monomial, c)| (monomial.as_slice(), c))
+    }
+
+    /// The coefficient of the monomial with `exponents`, which is zero if it does not occur.
+    pub fn coefficient(&self, exponents: &[u32]) -> OpArgument {
+        assert_eq!(
+            exponents.len(),
+            self.variables.len(),
+            "Hold on, a monomial needs one exponent per collected variable"
+        );
+        self.terms.get(exponents).cloned().unwrap_or_else(zero)
+    }
+
+    /// The largest total degree of a term, or `None` for the zero polynomial.
+    pub fn degree(&self) -> Option<u32> {
+        self.terms.keys().map(|m| m.iter().sum()).max()
+    }
+
+    /// The sum of the terms, highest degree first.
+    pub fn to_expression(&self) -> OpArgument {
+        let mut terms = self.terms.iter().collect::<Vec<_>>();
+        terms.sort_by_key(|(m, _)| std::cmp::Reverse(m.iter().sum::<u32>()));
+        terms
+            .into_iter()
+            .map(|(monomial, c)| {
+                monomial
+                    .iter()
+                    .zip(&self.variables)
+                    .filter(|(&k, _)| k > 0)
+                    .map(|(&k, &var)| match k {
+                        1 => variable(var),
+                        k => variable(var).pow(&Value::integer(k as u64).into()),
+                    })
+                    .fold(c.clone(), |term, power| match term == one() {
+                        true => power,
+                        false => &term * &power,
+                    })
+            })
+            .reduce(|sum, term| &sum + &term)
+            .unwrap_or_else(zero)
+    }
+}
+
+fn collect(
+    node: &OpArgument,
+    variables: &[&'static str],
+    budget: &Budget,
+) -> Result<Collected, CollectError> {
+    budget.spend()?;
+    let vars = node.free_variables();
+    if variables.iter().all(|var| !vars.contains(var)) {
+        return Ok(Collected::constant(variables, node.fold()));
+    }
+    let not_polynomial = || CollectError::NotPolynomial(node.clone());
+    let op = match &node.value {
+        Leaf(_) => {
+            let mut monomial = vec![0; variables.len()];
+            for (exponent, var) in monomial.iter_mut().zip(variables) {
+                *exponent = vars.contains(var) as u32;
+            }
+            return Ok(Collected {
+                variables: variables.to_vec(),
+                terms: BTreeMap::from([(monomial, one())]),
+            });
+        }
+        Op(op) => op,
+    };
+    let arg = |i: usize| collect(&op.arguments[i], variables, budget);
+    Ok(match op.op {
+        Addition => arg(0)?.add(arg(1)?),
+        Subtraction => arg(0)?.add(arg(1)?.negate()),
+        Negation => arg(0)?.negate(),
+        Multiplication => arg(0)?.mul(&arg(1)?, node, budget)?,
+        Division => {
+            let divisor = &op.arguments[1];
+            if variables
+                .iter()
+                .any(|var| divisor.free_variables().contains(var))
+            {
+                return Err(not_polynomial());
+            }
+            arg(0)?.divide(divisor)
+        }
+        Pow => {
+            let k = Ratio::of(&op.arguments[1].fold())
+                .filter(|k| k.den == 1 && k.num >= 0)
+                .and_then(|k| u32::try_from(k.num).ok())
+                .ok_or_else(not_polynomial)?;
+            let base = arg(0)?;
+            (0..k).try_fold(Collected::constant(variables, one()), |acc, _| {
+                let product = acc.mul(&base, node, budget)?;
+                budget.iterate(product.terms.len())?;
+                Ok::<_, CollectError>(product)
+            })?
+        }
+        _ => return Err(not_polynomial()),
+    })
+}
+
+impl OpArgument {
+    /// Expands the expression as a polynomial in `variables` and groups its terms by monomial.
+    pub fn collect(&self, variables: &[&'static str]) -> Result<Collected, CollectError> {
+        collect(self, variables, &Budget::unlimited())
+    }
+
+    /// Collects as [`OpArgument::collect`] does, spending a step of `budget` per node visited
+    /// and per pair of terms multiplied, and reporting every product of a power with the
+    /// number of terms so far. Powers of long sums can have very many terms, so the size limit
+    /// of `budget` caps the terms of every intermediate product.
+    pub fn collect_within(
+        &self,
+        variables: &[&'static str],
+        budget: &Budget,
+    ) -> Result<Collected, CollectError> {
+        collect(self, variables, budget)
+    }
+}
+
+/// The coefficient of `var^power` in `expr`, as a polynomial in `var`.
+pub fn coefficient(
+    expr: &OpArgument,
+    var: &'static str,
+    power: u32,
+) -> Result<OpArgument, CollectError> {
+    Ok(expr.collect(&[var])?.coefficient(&[power]))
+}
+
+/// The coefficient of the highest power of `var` in `expr`, which is zero only if `expr` is.
+pub fn leading_coefficient(
+    expr: &OpArgument,
+    var: &'static str,
+) -> Result<OpArgument, CollectError> {
+    let collected = expr.collect(&[var])?;
+    Ok(collected
+        .terms()
+        .last()
+        .map_or_else(zero, |(_, c)| c.clone()))
+}
+
+/// The degree of `expr` in `var` once expanded, or `None` if it is not a polynomial in `var`.
+/// Zero, like every other constant, has degree `0` here.
+pub fn degree(expr: &OpArgument, var: &'static str) -> Option<u32> {
+    Some(expr.collect(&[var]).ok()?.degree().unwrap_or(0))
+}
+
+/// The integer exponent of a power, if it is one.
+fn integer_exponent(exponent: &OpArgument) -> Option<i128> {
+    Ratio::of(&exponent.fold())
+        .filter(|k| k.den == 1)
+        .map(|k| k.num)
+}
+
+/// Whether `expr` is a polynomial in `vars`, with coefficients that may be any expressions free
+/// of them. This is exactly when [`OpArgument::collect`] succeeds, but nothing is expanded.
+pub fn is_polynomial_in(expr: &OpArgument, vars: &[&'static str]) -> bool {
+    let Op(op) = &expr.value else {
+        return true;
+    };
+    if !mentions(expr, vars) {
+        return true;
+    }
+    match op.op {
+        Addition | Subtraction | Multiplication | Negation => {
+            op.arguments.iter().all(|arg| is_polynomial_in(arg, vars))
+        }
+        Division => is_polynomial_in(&op.arguments[0], vars) && !mentions(&op.arguments[1], vars),
+        Pow => {
+            is_polynomial_in(&op.arguments[0], vars)
+                && integer_exponent(&op.arguments[1]).is_some_and(|k| k >= 0)
+        }
+        _ => false,
+    }
+}
+
+/// Whether `expr` mentions any of `vars`.
+fn mentions(expr: &OpArgument, vars: &[&'static str]) -> bool {
+    let mentioned = expr.free_variables();
+    vars.iter().any(|var| mentioned.contains(var))
+}
+
+/// Whether `expr` is a quotient of polynomials in `vars`, once put over a common denominator.
+pub fn is_rational_function(expr: &OpArgument, vars: &[&'static str]) -> bool {
+    let Op(op) = &expr.value else {
+        return true;
+    };
+    if !mentions(expr, vars) {
+        return true;
+    }
+    match op.op {
+        Addition | Subtraction | Multiplication | Division | Negation => op
+            .arguments
+            .iter()
+            .all(|arg| is_rational_function(arg, vars)),
+        Pow => {
+            is_rational_function(&op.arguments[0], vars)
+                && integer_exponent(&op.arguments[1]).is_some()
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        coefficient, degree, is_polynomial_in, is_rational_function, leading_coefficient,
+        CollectError,
+    };
+    use crate::{
+        budget::{Budget, Exhausted},
+        parse::parse,
+        rewrite::Rule,
+        symbols::{declare_non_commutative, OpArgument},
+        template::{hole, typed_hole, HoleKind},
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_collect() {
+        let p = |input: &str| parse(input).unwrap();
+        let expr = p("(x + a*y)^2 - x*(x - b) + 3*y/c + 1");
+
+        let collected = expr.collect(&["x", "y"]).unwrap();
+        let terms = collected
+            .terms()
+            .map(|(m, c)| (m.to_vec(), c.clone()))
+            .collect::<Vec<_>>();
+        let expected: [(Vec<u32>, OpArgument); 5] = [
+            (vec![0, 0], p("1")),
+            (vec![0, 1], p("3/c").fold()),
+            (vec![0, 2], p("a*a").fold()),
+            (vec![1, 0], p("b")),
+            (vec![1, 1], p("a + a").fold()),
+        ];
+        assert_eq!(terms, expected);
+        assert_eq!(collected.coefficient(&[2, 0]), p("0").fold());
+        assert_eq!(collected.degree(), Some(2));
+
+        // Rebuilding gives back the same function.
+        let rebuilt = collected.to_expression();
+        let at = crate::evaluation::Bindings::from_iter([
+            ("x", 0.3),
+            ("y", -1.2),
+            ("a", 2.5),
+            ("b", 0.7),
+            ("c", 1.9),
+        ]);
+        let (want, got) = (expr.evaluate(&at).unwrap(), rebuilt.evaluate(&at).unwrap());
+        assert!((want - got).abs() < 1e-12);
+
+        // One variable at a time is the univariate collect.
+        let by_x = expr.collect(&["x"]).unwrap();
+        assert_eq!(by_x.degree(), Some(1));
+        assert_eq!(by_x.terms().count(), 2);
+
+        assert_eq!(
+            p("x * sin(y)").collect(&["x", "y"]),
+            Err(CollectError::NotPolynomial(p("sin(y)")))
+        );
+        assert_eq!(
+            p("a / x").collect(&["x"]),
+            Err(CollectError::NotPolynomial(p("a / x")))
+        );
+        assert_eq!(p("x - x").collect(&["x"]).unwrap().degree(), None);
+
+        // Every product of the power reports the terms so far.
+        let sizes = Arc::new(Mutex::new(Vec::new()));
+        let budget = Budget::unlimited().with_progress({
+            let sizes = sizes.clone();
+            move |progress| sizes.lock().unwrap().extend(progress.size)
+        });
+        let cube = p("(x + y + z)^3").collect_within(&["x", "y", "z"], &budget);
+        assert_eq!(cube.unwrap().terms().count(), 10);
+        sizes.lock().unwrap().dedup();
+        assert_eq!(*sizes.lock().unwrap(), [3, 6, 10]);
+        assert_eq!(
+            p("(x + y + z)^30")
+                .collect_within(&["x", "y", "z"], &Budget::unlimited().with_steps(1000)),
+            Err(CollectError::Interrupted(Exhausted::OutOfSteps))
+        );
+        assert_eq!(
+            p("(a + b + c + d)^20").collect_within(
+                &["a", "b", "c", "d"],
+                &Budget::unlimited().with_max_size(500)
+            ),
+            Err(CollectError::Interrupted(Exhausted::TooLarge))
+        );
+    }
+
+    #[test]
+    fn test_coefficients() {
+        let p = |input: &str| parse(input).unwrap();
+        let expr = p("a*x^3 - (x - b)^2 + 5");
+
+        assert_eq!(coefficient(&expr, "x", 3), Ok(p("a")));
+        assert_eq!(coefficient(&expr, "x", 2), Ok(p("-1").fold()));
+        assert_eq!(coefficient(&expr, "x", 7), Ok(p("0").fold()));
+        assert_eq!(leading_coefficient(&expr, "x"), Ok(p("a")));
+        assert_eq!(leading_coefficient(&expr, "b"), Ok(p("-1").fold()));
+        assert_eq!(leading_coefficient(&p("y - y"), "y"), Ok(p("0").fold()));
+        assert!(coefficient(&p("exp(x)"), "x", 0).is_err());
+    }
+
+    #[test]
+    fn test_polynomial_predicates() {
+        let p = |input: &str| parse(input).unwrap();
+
+        assert_eq!(degree(&p("(x + 1)^3 - x^3"), "x"), Some(2));
+        assert_eq!(degree(&p("y * sin(z)"), "x"), Some(0));
+        assert_eq!(degree(&p("x - x"), "x"), Some(0));
+        assert_eq!(degree(&p("1 / x"), "x"), None);
+
+        assert!(is_polynomial_in(
+            &p("a*x^2*y + sin(a)/b - x/2"),
+            &["x", "y"]
+        ));
+        assert!(is_polynomial_in(&p("exp(z)"), &["x"]));
+        assert!(!is_polynomial_in(&p("x^(1/2)"), &["x"]));
+        assert!(!is_polynomial_in(&p("y / (x + 1)"), &["x"]));
+        assert!(!is_polynomial_in(&p("2^x"), &["x"]));
+
+        assert!(is_rational_function(
+            &p("y / (x + 1) - x^(-2)"),
+            &["x", "y"]
+        ));
+        assert!(is_rational_function(&p("(x^2 + 1)^3 / cos(a)"), &["x"]));
+        assert!(!is_rational_function(&p("sin(x) / x"), &["x"]));
+        assert!(!is_rational_function(&p("x^(1/2)"), &["x"]));
+    }
+
+    #[test]
+    fn test_non_commutative() {
+        let a = declare_non_commutative("collect_A");
+        let b = declare_non_commutative("collect_B");
+        let p = |input: &str| parse(input).unwrap();
+        assert!(!p("collect_A + 1").is_commutative() && p("x * y").is_commutative());
+
+        // Powers of one operator still collect, and commuting variables move freely.
+        let powers = p("2*collect_A*x*collect_A^2 + x*collect_A");
+        let collected = powers.collect(&[a, "x"]).unwrap();
+        assert_eq!(collected.coefficient(&[3, 1]), p("2"));
+        assert_eq!(collected.coefficient(&[1, 1]), p("1"));
+
+        // (A + B)² = A² + AB + BA + B², which monomials cannot tell apart from 2AB.
+        let square = p("(collect_A + collect_B)^2");
+        assert_eq!(
+            square.collect(&[a, b]),
+            Err(CollectError::NonCommutative(square.clone()))
+        );
+        // An operator may not move past another one in a coefficient either.
+        assert!(p("collect_A * collect_B").collect(&[a]).is_err());
+        assert!(p("collect_B * collect_A").collect(&[a]).is_ok());
+
+        // Canonical forms never reorder factors.
+        let word = p("collect_A * collect_B * collect_A / collect_B");
+        assert_eq!(
+            word.canonicalize(),
+            p("collect_A * collect_B * collect_A * collect_B^(-1)")
+        );
+
+        // Rules that commute factors can be limited to factors that commute.
+        let (c, e) = (typed_hole("c", HoleKind::Commuting), hole("e"));
+        let commute = Rule::new("commute", &e * &c, &c * &e).unwrap();
+        assert_eq!(commute.apply(&p("collect_A * x")), Some(p("x * collect_A")));
+        assert_eq!(commute.apply(&p("collect_A * collect_B")), None);
+    }
+}
@@ -0,0 +1,197 @@
+//! This module describes how to compare two versions of an expression.
+//!
+//! [`structural_diff`] walks both expressions together from the root. Subexpressions with equal
+//! hashes are identical and skipped without being visited, so comparing a large expression with
+//! a lightly rewritten copy of itself only visits the paths leading to the changes.
+
+use std::fmt::Display;
+
+use ahash::HashSet;
+
+use crate::symbols::{
+    OpArgument,
+    OpArgumentKind::{Leaf, Op},
+};
+
+/// The position of a subexpression, as the argument indices leading to it from the root.
+pub type Path = Vec<usize>;
+
+/// One difference between two expressions.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    /// `old` was replaced by the unrelated `new`.
+    Replaced {
+        path: Path,
+        old: OpArgument,
+        new: OpArgument,
+    },
+    /// `old` was kept, but is now inside `new`.
+    Inserted {
+        path: Path,
+        old: OpArgument,
+        new: OpArgument,
+    },
+    /// `new` was kept from inside `old`, and the rest of `old` was removed.
+    Removed {
+        path: Path,
+        old: OpArgument,
+        new: OpArgument,
+    },
+    /// The arguments of the operation at `path` were swapped.
+    Reordered { path: Path },
+}
+
+impl Change {
+    pub fn path(&self) -> &[usize] {
+        match self {
+            Change::Replaced { path, .. }
+            | Change::Inserted { path, .. }
+            | Change::Removed { path, .. }
+            | Change::Reordered { path } => path,
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at {:?}: ", self.path())?;
+        match self {
+            Change::Replaced { old, new, .. } => write!(f, "replaced {} with {}", old, new),
+            Change::Inserted { old, new, .. } => write!(f, "wrapped {} into {}", old, new),
+            Change::Removed { old, new, .. } => write!(f, "reduced {} to {}", old, new),
+            Change::Reordered { .. } => f.write_str("swapped arguments"),
+        }
+    }
+}
+
+/// The differences between two expressions, in the order they occur from left to right.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diff {
+    pub changes: Vec<Change>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl Display for Diff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return f.write_str("no changes");
+        }
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `needle` is a subexpression of `haystack`.
+fn contains(haystack: &OpArgument, needle: &OpArgument, visited: &mut HashSet<u64>) -> bool {
+    if haystack == needle {
+        return true;
+    }
+    if haystack.depth() <= needle.depth() || !visited.insert(haystack.hash()) {
+        return false;
+    }
+    match &haystack.value {
+        Op(op) => op
+            .arguments
+            .iter()
+            .any(|arg| contains(arg, needle, visited)),
+        Leaf(_) => false,
+    }
+}
+
+fn walk(old: &OpArgument, new: &OpArgument, path: &mut Path, changes: &mut Vec<Change>) {
+    if old == new {
+        return;
+    }
+
+    if let (Op(a), Op(b)) = (&old.value, &new.value) {
+        if a.op == b.op {
+            let args = (&a.arguments, &b.arguments);
+            if args.0.len() == 2 && args.0[0] == args.1[1] && args.0[1] == args.1[0] {
+                changes.push(Change::Reordered { path: path.clone() });
+                return;
+            }
+            for (i, (a, b)) in args.0.iter().zip(args.1).enumerate() {
+                path.push(i);
+                walk(a, b, path, changes);
+                path.pop();
+            }
+            return;
+        }
+    }
+
+    let (path, old, new) = (path.clone(), old.clone(), new.clone());
+    changes.push(if contains(&new, &old, &mut HashSet::default()) {
+        Change::Inserted { path, old, new }
+    } else if contains(&old, &new, &mut HashSet::default()) {
+        Change::Removed { path, old, new }
+    } else {
+        Change::Replaced { path, old, new }
+    });
+}
+
+/// Reports the subexpressions of `old` that differ in `new`, matching operations of the same
+/// kind argument by argument.
+pub fn structural_diff(old: &OpArgument, new: &OpArgument) -> Diff {
+    let mut diff = Diff::default();
+    walk(old, new, &mut Vec::new(), &mut diff.changes);
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{structural_diff, Change};
+    use crate::{
+        constants::Value,
+        symbols::{variable, OpArgument},
+    };
+
+    #[test]
+    fn test_structural_diff() {
+        let x = variable("x");
+        let y = variable("y");
+        let two = OpArgument::from(Value::integer(2));
+
+        let old = (&x * &y).sin() + x.exp() * (&x + &y);
+        assert!(structural_diff(&old, &old).is_empty());
+
+        let new = (&x * &y).sin() + (&two * x.exp()) * (&y + &x);
+        let diff = structural_diff(&old, &new);
+        assert_eq!(
+            diff.changes,
+            [
+                Change::Inserted {
+                    path: vec![1, 0],
+                    old: x.exp(),
+                    new: &two * x.exp(),
+                },
+                Change::Reordered { path: vec![1, 1] },
+            ]
+        );
+
+        let diff = structural_diff(&old, &(&x * &y).cos());
+        assert_eq!(
+            diff.changes,
+            [Change::Replaced {
+                path: vec![],
+                old: old.clone(),
+                new: (&x * &y).cos(),
+            }]
+        );
+
+        let diff = structural_diff(&old, &(&x * &y).sin());
+        assert_eq!(
+            diff.to_string(),
+            format!("at []: reduced {} to {}", old, (&x * &y).sin())
+        );
+    }
+}
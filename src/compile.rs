@@ -0,0 +1,899 @@
+//! This module describes how to compile our computational graph into a flat program.
+//!
+//! A [`Program`] is a list of instructions in static single assignment form: instruction `i`
+//! writes register `i` and only reads registers written before it. Every distinct
+//! subexpression, across all of the compiled outputs, is computed by exactly one instruction.
+//!
+//! A [`SumProgram`] compiles a finite [`Sum`] to a program for its bounds and one for its body,
+//! run once per term in a loop rather than unrolled, so its size does not grow with the number
+//! of terms. The instructions of the body that do not read the index are computed once, before
+//! the loop.
+
+use std::fmt::Display;
+
+use ahash::HashMap;
+
+use crate::{
+    codegen::CodegenError,
+    constants::Value,
+    evaluation::{EvaluationError, Scalar},
+    sum::{integer_bound, Sum},
+    symbols::{
+        OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        OperationKind,
+    },
+};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Instruction {
+    /// Reads the parameter with the given index.
+    Input(usize),
+    Constant(f64),
+    Unary(OperationKind, usize),
+    Binary(OperationKind, usize, usize),
+}
+
+impl Instruction {
+    /// The registers this instruction reads.
+    pub fn operands(&self) -> impl Iterator<Item = usize> {
+        let (a, b) = match *self {
+            Instruction::Input(_) | Instruction::Constant(_) => (None, None),
+            Instruction::Unary(_, a) => (Some(a), None),
+            Instruction::Binary(_, a, b) => (Some(a), Some(b)),
+        };
+        a.into_iter().chain(b)
+    }
+}
+
+/// A compiled, straight-line evaluator for one or more expressions over named parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Program {
+    pub(crate) params: Vec<&'static str>,
+    pub(crate) instructions: Vec<Instruction>,
+    pub(crate) outputs: Vec<usize>,
+}
+
+impl Program {
+    pub fn params(&self) -> &[&'static str] {
+        &self.params
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// The registers holding the value of each compiled expression.
+    pub fn outputs(&self) -> &[usize] {
+        &self.outputs
+    }
+
+    /// Evaluates every output, using `registers` as scratch space so that repeated calls do not
+    /// allocate. Constants are rounded to `T` and every instruction is computed in `T`.
+    pub fn evaluate_into<T: Scalar>(&self, params: &[T], registers: &mut Vec<T>, out: &mut [T]) {
+        assert_eq!(
+            params.len(),
+            self.params.len(),
+            "Oops, this program takes {} parameters but was given {}",
+            self.params.len(),
+            params.len()
+        );
+        assert_eq!(out.len(), self.outputs.len());
+
+        registers.clear();
+        registers.reserve(self.instructions.len());
+        for &instruction in &self.instructions {
+            let value = execute(instruction, params, registers);
+            registers.push(value);
+        }
+
+        out.iter_mut()
+            .zip(&self.outputs)
+            .for_each(|(out, &r)| *out = registers[r]);
+    }
+
+    /// Evaluates every output at the given parameter values.
+    pub fn evaluate(&self, params: &[f64]) -> Vec<f64> {
+        let mut out = vec![0.0; self.outputs.len()];
+        self.evaluate_into(params, &mut Vec::new(), &mut out);
+        out
+    }
+
+    /// Evaluates every output in single precision. See [`crate::precision`] for checking whether
+    /// that is accurate enough.
+    pub fn evaluate_f32(&self, params: &[f32]) -> Vec<f32> {
+        let mut out = vec![0.0; self.outputs.len()];
+        self.evaluate_into(params, &mut Vec::new(), &mut out);
+        out
+    }
+}
+
+/// The value `instruction` computes from the registers before it.
+fn execute<T: Scalar>(instruction: Instruction, params: &[T], registers: &[T]) -> T {
+    match instruction {
+        Instruction::Input(i) => params[i],
+        Instruction::Constant(c) => T::from_f64(c),
+        Instruction::Unary(op, a) => op.apply(&[registers[a]]),
+        Instruction::Binary(op, a, b) => op.apply(&[registers[a], registers[b]]),
+    }
+}
+
+/// A compiled finite sum, evaluated by looping over its index.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SumProgram {
+    pub(crate) index: &'static str,
+    /// The lower and upper bound, as its two outputs.
+    pub(crate) bounds: Program,
+    /// The summand, over the parameters followed by the index.
+    pub(crate) body: Program,
+}
+
+impl SumProgram {
+    pub fn params(&self) -> &[&'static str] {
+        self.bounds.params()
+    }
+
+    pub fn index(&self) -> &'static str {
+        self.index
+    }
+
+    pub fn bounds(&self) -> &Program {
+        &self.bounds
+    }
+
+    pub fn body(&self) -> &Program {
+        &self.body
+    }
+
+    /// Which registers of the body depend on the index, and so are recomputed every iteration.
+    pub fn varying(&self) -> Vec<bool> {
+        let index = self.params().len();
+        let mut varying = Vec::with_capacity(self.body.instructions.len());
+        for instruction in &self.body.instructions {
+            let reads_index = match *instruction {
+                Instruction::Input(i) => i == index,
+                _ => instruction.operands().any(|a| varying[a]),
+            };
+            varying.push(reads_index);
+        }
+        varying
+    }
+
+    /// Evaluates the sum at the given parameter values. The bounds must evaluate to integers
+    /// of magnitude at most `2⁵³`, and the sum is empty if the upper one is below the lower one.
+    pub fn evaluate(&self, params: &[f64]) -> Result<f64, EvaluationError> {
+        let [lo, hi] = self.bounds.evaluate(params)[..] else {
+            unreachable!("Oops, the bounds program has two outputs")
+        };
+        let (lo, hi) = (
+            integer_bound(self.index, lo)?,
+            integer_bound(self.index, hi)?,
+        );
+        if hi < lo {
+            return Ok(0.0);
+        }
+
+        let varying = self.varying();
+        let mut inputs = params.to_vec();
+        inputs.push(lo as f64);
+        let mut registers = Vec::new();
+        let mut out = [0.0];
+        self.body.evaluate_into(&inputs, &mut registers, &mut out);
+        let mut total = out[0];
+        let output = self.body.outputs[0];
+        for k in lo + 1..=hi {
+            inputs[params.len()] = k as f64;
+            for (r, &instruction) in self.body.instructions.iter().enumerate() {
+                if varying[r] {
+                    registers[r] = execute(instruction, &inputs, &registers);
+                }
+            }
+            total += registers[output];
+        }
+        Ok(total)
+    }
+}
+
+/// Compiles `sum` into a loop over its index. The bounds may only read `params`, and the body
+/// `params` and the index, so indexed symbols such as `a[k]` are reported as unknown variables,
+/// and the index must not be one of `params`.
+pub fn compile_sum(sum: &Sum, params: &[&'static str]) -> Result<SumProgram, CodegenError> {
+    if params.contains(&sum.index) {
+        return Err(CodegenError::IndexIsParameter(sum.index));
+    }
+    let bounds = compile_many(&[sum.lo.clone(), sum.hi.clone()], params)?;
+    let mut body_params = params.to_vec();
+    body_params.push(sum.index);
+    let body = compile(&sum.body, &body_params)?;
+    Ok(SumProgram {
+        index: sum.index,
+        bounds,
+        body,
+    })
+}
+
+/// Identifies an instruction up to the registers it reads, for value numbering.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+enum InstructionKey {
+    Input(usize),
+    Constant(u64),
+    Unary(u32, usize),
+    Binary(u32, usize, usize),
+}
+
+impl From<Instruction> for InstructionKey {
+    fn from(instruction: Instruction) -> Self {
+        match instruction {
+            Instruction::Input(i) => InstructionKey::Input(i),
+            Instruction::Constant(c) => InstructionKey::Constant(c.to_bits()),
+            Instruction::Unary(op, a) => InstructionKey::Unary(op.opcode(), a),
+            Instruction::Binary(op, a, b) => InstructionKey::Binary(op.opcode(), a, b),
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Rewriter {
+    pub(crate) instructions: Vec<Instruction>,
+    numbering: HashMap<InstructionKey, usize>,
+}
+
+impl Rewriter {
+    fn constant_value(&self, r: usize) -> Option<f64> {
+        match self.instructions[r] {
+            Instruction::Constant(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// Emits `instruction`, simplifying it against the instructions already emitted, and
+    /// returns the register holding its value.
+    pub(crate) fn emit(&mut self, instruction: Instruction) -> usize {
+        use OperationKind::*;
+
+        let instruction = match instruction {
+            Instruction::Unary(op, a) => {
+                if let Some(a) = self.constant_value(a) {
+                    Instruction::Constant(op.eval(&[a]))
+                } else if let (Negation, Instruction::Unary(Negation, inner)) =
+                    (op, self.instructions[a])
+                {
+                    return inner;
+                } else {
+                    instruction
+                }
+            }
+            Instruction::Binary(op, a, b) => {
+                match (op, self.constant_value(a), self.constant_value(b)) {
+                    (_, Some(a), Some(b)) => Instruction::Constant(op.eval(&[a, b])),
+                    (Pow, _, Some(1.0)) => return a,
+                    (Pow, _, Some(2.0)) => Instruction::Binary(Multiplication, a, a),
+                    (Pow, _, Some(-1.0)) => {
+                        let one = self.emit(Instruction::Constant(1.0));
+                        Instruction::Binary(Division, one, a)
+                    }
+                    (Multiplication | Division, _, Some(1.0)) => return a,
+                    (Multiplication, Some(1.0), _) => return b,
+                    (Division, _, _) => match self.instructions[a] {
+                        // (n / d) / b = n / (d * b)
+                        Instruction::Binary(Division, n, d) => {
+                            let denominator = self.emit(Instruction::Binary(Multiplication, d, b));
+                            Instruction::Binary(Division, n, denominator)
+                        }
+                        _ => instruction,
+                    },
+                    _ => instruction,
+                }
+            }
+            _ => instruction,
+        };
+
+        let key = InstructionKey::from(instruction);
+        if let Some(&r) = self.numbering.get(&key) {
+            return r;
+        }
+        self.instructions.push(instruction);
+        let r = self.instructions.len() - 1;
+        self.numbering.insert(key, r);
+        r
+    }
+}
+
+impl Program {
+    /// Applies local rewrites to the instruction stream: constant folding, `x^2 -> x*x` and
+    /// other cheap powers, `a/b/c -> a/(b*c)`, multiplicative identities, and removal of
+    /// duplicate and unused instructions. Every backend emitting from a [`Program`] sees the
+    /// result.
+    pub fn peephole(&self) -> Program {
+        let mut rewriter = Rewriter::default();
+        let mut renamed = Vec::with_capacity(self.instructions.len());
+        for instruction in &self.instructions {
+            let instruction = match *instruction {
+                Instruction::Unary(op, a) => Instruction::Unary(op, renamed[a]),
+                Instruction::Binary(op, a, b) => Instruction::Binary(op, renamed[a], renamed[b]),
+                other => other,
+            };
+            renamed.push(rewriter.emit(instruction));
+        }
+        let outputs = self.outputs.iter().map(|&r| renamed[r]).collect::<Vec<_>>();
+
+        // Dead code elimination: keep only what the outputs transitively read.
+        let instructions = rewriter.instructions;
+        let mut live = vec![false; instructions.len()];
+        outputs.iter().for_each(|&r| live[r] = true);
+        for r in (0..instructions.len()).rev() {
+            if live[r] {
+                instructions[r].operands().for_each(|a| live[a] = true);
+            }
+        }
+
+        let mut compacted = Vec::new();
+        let mut index = vec![usize::MAX; instructions.len()];
+        for (r, instruction) in instructions.into_iter().enumerate() {
+            if !live[r] {
+                continue;
+            }
+            index[r] = compacted.len();
+            compacted.push(match instruction {
+                Instruction::Unary(op, a) => Instruction::Unary(op, index[a]),
+                Instruction::Binary(op, a, b) => Instruction::Binary(op, index[a], index[b]),
+                other => other,
+            });
+        }
+
+        Program {
+            params: self.params.clone(),
+            instructions: compacted,
+            outputs: outputs.into_iter().map(|r| index[r]).collect(),
+        }
+    }
+}
+
+impl Display for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (r, instruction) in self.instructions.iter().enumerate() {
+            match *instruction {
+                Instruction::Input(i) => writeln!(f, "r{} = {}", r, self.params[i])?,
+                Instruction::Constant(c) => writeln!(f, "r{} = {:?}", r, c)?,
+                Instruction::Unary(op, a) => writeln!(f, "r{} = {} r{}", r, op, a)?,
+                Instruction::Binary(op, a, b) => writeln!(f, "r{} = r{} {} r{}", r, a, op, b)?,
+            }
+        }
+        let outputs = self.outputs.iter().map(|r| format!("r{}", r));
+        write!(f, "return {}", outputs.collect::<Vec<_>>().join(", "))
+    }
+}
+
+/// Optimizations applied while lowering expressions to instructions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CompileOptions {
+    /// Evaluate polynomial sums in one variable in Horner form.
+    pub horner: bool,
+    /// Lower integer powers to chains of multiplications (binary exponentiation).
+    pub power_chains: bool,
+    /// Run [`Program::peephole`] on the lowered program.
+    pub peephole: bool,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        CompileOptions {
+            horner: true,
+            power_chains: true,
+            peephole: true,
+        }
+    }
+}
+
+impl CompileOptions {
+    /// Lowers every node exactly as written.
+    pub fn unoptimized() -> Self {
+        CompileOptions {
+            horner: false,
+            power_chains: false,
+            peephole: false,
+        }
+    }
+}
+
+/// Returns `k` if `node` is the integer constant `k`.
+fn integer(node: &OpArgument) -> Option<u64> {
+    match &node.value {
+        Leaf(value) => match **value {
+            Value::Rational(num, den) if den.get() == 1 => Some(num),
+            _ => None,
+        },
+        Op(_) => None,
+    }
+}
+
+/// Returns `(base, k)` if `node` is `base ^ k` for an integer constant `k`.
+fn integer_power(node: &OpArgument) -> Option<(&OpArgument, u64)> {
+    match &node.value {
+        Op(op) if op.op == OperationKind::Pow => {
+            Some((&op.arguments[0], integer(&op.arguments[1])?))
+        }
+        _ => None,
+    }
+}
+
+/// Appends the terms of the sum `node` to `terms`, each with whether it is subtracted.
+fn flatten_sum<'a>(node: &'a OpArgument, negative: bool, terms: &mut Vec<(bool, &'a OpArgument)>) {
+    match &node.value {
+        Op(op) if op.op == OperationKind::Addition => {
+            flatten_sum(&op.arguments[0], negative, terms);
+            flatten_sum(&op.arguments[1], negative, terms);
+        }
+        Op(op) if op.op == OperationKind::Subtraction => {
+            flatten_sum(&op.arguments[0], negative, terms);
+            flatten_sum(&op.arguments[1], !negative, terms);
+        }
+        Op(op) if op.op == OperationKind::Negation => {
+            flatten_sum(&op.arguments[0], !negative, terms)
+        }
+        _ => terms.push((negative, node)),
+    }
+}
+
+/// A term `±coefficients * var^degree` of a polynomial in `var`.
+struct Monomial<'a> {
+    negative: bool,
+    degree: u64,
+    coefficients: Vec<&'a OpArgument>,
+}
+
+impl<'a> Monomial<'a> {
+    /// Splits the product `node` into powers of `var` and factors free of `var`.
+    fn split(&mut self, node: &'a OpArgument, var: &'static str) -> Option<()> {
+        let is_var =
+            |node: &OpArgument| matches!(&node.value, Leaf(v) if **v == Value::Variable(var));
+
+        if is_var(node) {
+            self.degree += 1;
+            return Some(());
+        }
+        if let Some((base, k)) = integer_power(node) {
+            if is_var(base) {
+                self.degree += k;
+                return Some(());
+            }
+        }
+        match &node.value {
+            Op(op) if op.op == OperationKind::Multiplication => {
+                self.split(&op.arguments[0], var)?;
+                self.split(&op.arguments[1], var)
+            }
+            Op(op) if op.op == OperationKind::Negation => {
+                self.negative = !self.negative;
+                self.split(&op.arguments[0], var)
+            }
+            _ if node.free_variables().contains(var) => None,
+            _ => {
+                self.coefficients.push(node);
+                Some(())
+            }
+        }
+    }
+}
+
+/// Writes the sum `terms` as a polynomial in `var`, if every term is a monomial in it.
+fn monomials<'a>(terms: &[(bool, &'a OpArgument)], var: &'static str) -> Option<Vec<Monomial<'a>>> {
+    terms
+        .iter()
+        .map(|&(negative, term)| {
+            let mut monomial = Monomial {
+                negative,
+                degree: 0,
+                coefficients: Vec::new(),
+            };
+            monomial.split(term, var)?;
+            Some(monomial)
+        })
+        .collect()
+}
+
+struct Compiler<'a> {
+    params: &'a [&'static str],
+    options: CompileOptions,
+    instructions: Vec<Instruction>,
+    registers: HashMap<u64, usize>,
+    constants: HashMap<u64, usize>,
+    powers: HashMap<(usize, u64), usize>,
+}
+
+impl Compiler<'_> {
+    fn push(&mut self, instruction: Instruction) -> usize {
+        self.instructions.push(instruction);
+        self.instructions.len() - 1
+    }
+
+    fn constant(&mut self, value: f64) -> usize {
+        match self.constants.get(&value.to_bits()) {
+            Some(&r) => r,
+            None => {
+                let r = self.push(Instruction::Constant(value));
+                self.constants.insert(value.to_bits(), r);
+                r
+            }
+        }
+    }
+
+    /// Computes `base^k` by repeated squaring, reusing powers already computed.
+    fn power(&mut self, base: usize, k: u64) -> usize {
+        match k {
+            0 => return self.constant(1.0),
+            1 => return base,
+            _ => {}
+        }
+        if let Some(&r) = self.powers.get(&(base, k)) {
+            return r;
+        }
+
+        let half = self.power(base, k / 2);
+        let mut r = self.push(Instruction::Binary(
+            OperationKind::Multiplication,
+            half,
+            half,
+        ));
+        if k % 2 == 1 {
+            r = self.push(Instruction::Binary(OperationKind::Multiplication, r, base));
+        }
+        self.powers.insert((base, k), r);
+        r
+    }
+
+    /// Compiles a sum in Horner form in the variable that gives it the highest degree, if it is
+    /// a polynomial of degree at least two in some variable.
+    fn horner(&mut self, node: &OpArgument) -> Result<Option<usize>, CodegenError> {
+        let mut terms = Vec::new();
+        flatten_sum(node, false, &mut terms);
+        if terms.len() < 2 {
+            return Ok(None);
+        }
+
+        let best = node
+            .free_variables()
+            .iter()
+            .filter_map(|var| Some((var, monomials(&terms, var)?)))
+            .max_by_key(|(_, monomials)| monomials.iter().map(|m| m.degree).max());
+        let Some((var, mut monomials)) = best else {
+            return Ok(None);
+        };
+        if monomials.iter().all(|m| m.degree < 2) {
+            return Ok(None);
+        }
+
+        let x = self.compile(&Value::Variable(var).into())?;
+        monomials.sort_by_key(|m| std::cmp::Reverse(m.degree));
+
+        // Each coefficient register is paired with whether it is negated; `None` stands for 1.
+        let mut acc: Option<(bool, Option<usize>)> = None;
+        let mut degree = monomials[0].degree;
+        for monomial in &monomials {
+            if let Some((negative, r)) = acc {
+                let r = match (r, degree - monomial.degree) {
+                    (r, 0) => r.unwrap_or_else(|| self.constant(1.0)),
+                    (None, gap) => self.power(x, gap),
+                    (Some(r), gap) => {
+                        let shift = self.power(x, gap);
+                        self.push(Instruction::Binary(OperationKind::Multiplication, r, shift))
+                    }
+                };
+                acc = Some((negative, Some(r)));
+            }
+            degree = monomial.degree;
+
+            let mut coefficient = None;
+            for factor in &monomial.coefficients {
+                let f = self.compile(factor)?;
+                coefficient = Some(match coefficient {
+                    Some(c) => self.push(Instruction::Binary(OperationKind::Multiplication, c, f)),
+                    None => f,
+                });
+            }
+
+            acc = Some(match acc {
+                None => (monomial.negative, coefficient),
+                Some((negative, r)) => {
+                    let r = r.expect("the accumulator is materialized after a shift");
+                    let c = coefficient.unwrap_or_else(|| self.constant(1.0));
+                    let op = match negative == monomial.negative {
+                        true => OperationKind::Addition,
+                        false => OperationKind::Subtraction,
+                    };
+                    (negative, Some(self.push(Instruction::Binary(op, r, c))))
+                }
+            });
+        }
+
+        let (negative, r) = acc.expect("a sum has at least one term");
+        let mut r = match (r, degree) {
+            (r, 0) => r.unwrap_or_else(|| self.constant(1.0)),
+            (None, degree) => self.power(x, degree),
+            (Some(r), degree) => {
+                let shift = self.power(x, degree);
+                self.push(Instruction::Binary(OperationKind::Multiplication, r, shift))
+            }
+        };
+        if negative {
+            r = self.push(Instruction::Unary(OperationKind::Negation, r));
+        }
+        Ok(Some(r))
+    }
+
+    fn compile(&mut self, node: &OpArgument) -> Result<usize, CodegenError> {
+        if let Some(&r) = self.registers.get(&node.hash()) {
+            return Ok(r);
+        }
+
+        let r = match &node.value {
+            Leaf(value) => match **value {
+                Value::Variable(name) => match self.params.iter().position(|p| *p == name) {
+                    Some(i) => self.push(Instruction::Input(i)),
+                    None => return Err(CodegenError::UnknownVariable(name)),
+                },
+                Value::I => return Err(CodegenError::UnsupportedConstant(**value)),
+                _ => {
+                    let value = value
+                        .evaluate(&Default::default())
+                        .expect("real constants evaluate without bindings");
+                    self.constant(value)
+                }
+            },
+            Op(op) => {
+                let lowered = match op.op {
+                    OperationKind::Pow if self.options.power_chains => match integer_power(node) {
+                        Some((base, k)) => {
+                            let base = self.compile(base)?;
+                            Some(self.power(base, k))
+                        }
+                        None => None,
+                    },
+                    OperationKind::Addition | OperationKind::Subtraction if self.options.horner => {
+                        self.horner(node)?
+                    }
+                    _ => None,
+                };
+
+                match lowered {
+                    Some(r) => r,
+                    None => {
+                        let args = op
+                            .arguments
+                            .iter()
+                            .map(|arg| self.compile(arg))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        match args[..] {
+                            [a] => self.push(Instruction::Unary(op.op, a)),
+                            [a, b] => self.push(Instruction::Binary(op.op, a, b)),
+                            _ => unreachable!("operations take one or two arguments"),
+                        }
+                    }
+                }
+            }
+        };
+
+        self.registers.insert(node.hash(), r);
+        Ok(r)
+    }
+}
+
+/// Compiles several expressions into one program with the given optimizations, sharing every
+/// common subexpression between them.
+pub fn compile_many_with(
+    exprs: &[OpArgument],
+    params: &[&'static str],
+    options: CompileOptions,
+) -> Result<Program, CodegenError> {
+    let mut compiler = Compiler {
+        params,
+        options,
+        instructions: Vec::new(),
+        registers: HashMap::default(),
+        constants: HashMap::default(),
+        powers: HashMap::default(),
+    };
+    let outputs = exprs
+        .iter()
+        .map(|expr| compiler.compile(expr))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let program = Program {
+        params: params.to_vec(),
+        instructions: compiler.instructions,
+        outputs,
+    };
+    Ok(match options.peephole {
+        true => program.peephole(),
+        false => program,
+    })
+}
+
+/// Compiles several expressions into one program, sharing every common subexpression between
+/// them.
+pub fn compile_many(
+    exprs: &[OpArgument],
+    params: &[&'static str],
+) -> Result<Program, CodegenError> {
+    compile_many_with(exprs, params, CompileOptions::default())
+}
+
+/// Compiles a single expression.
+pub fn compile(expr: &OpArgument, params: &[&'static str]) -> Result<Program, CodegenError> {
+    compile_many(std::slice::from_ref(expr), params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        compile, compile_many, compile_many_with, compile_sum, CompileOptions, Instruction,
+    };
+    use crate::{
+        codegen::CodegenError,
+        evaluation::{Bindings, EvaluationError},
+        parse::parse,
+        sum::Sum,
+        symbols::{variable, OperationKind},
+    };
+
+    #[test]
+    fn test_compile_many() {
+        let x = variable("x");
+        let y = variable("y");
+        let shared = x.sin() * y.cos();
+        let exprs = [&shared + &x, &shared * &y, shared.exp()];
+
+        let program = compile_many(&exprs, &["x", "y"]).unwrap();
+        // x, y, sin, cos, the shared product, and one instruction per output.
+        assert_eq!(program.instructions().len(), 8);
+        assert_eq!(program.outputs().len(), 3);
+
+        let mut bindings = Bindings::default();
+        bindings.insert("x", 0.3);
+        bindings.insert("y", -1.7);
+        let expected = exprs
+            .iter()
+            .map(|expr| expr.evaluate(&bindings).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(program.evaluate(&[0.3, -1.7]), expected);
+
+        assert_eq!(
+            compile(&exprs[0], &["x"]),
+            Err(CodegenError::UnknownVariable("y"))
+        );
+    }
+
+    fn arithmetic(program: &super::Program) -> usize {
+        program
+            .instructions()
+            .iter()
+            .filter(|i| matches!(i, Instruction::Unary(..) | Instruction::Binary(..)))
+            .count()
+    }
+
+    #[test]
+    fn test_horner_and_power_chains() {
+        let exprs = [
+            parse("3*x^4 + 2*x^3 - x^2 + 5*x + 7").unwrap(),
+            parse("-x^10 + y*x^2 - 1").unwrap(),
+            parse("x^13").unwrap(),
+            parse("(x + y)^2 - x*x").unwrap(),
+        ];
+
+        for expr in &exprs {
+            let naive = compile_many_with(
+                std::slice::from_ref(expr),
+                &["x", "y"],
+                CompileOptions::unoptimized(),
+            )
+            .unwrap();
+            let optimized = compile(expr, &["x", "y"]).unwrap();
+
+            for (x, y) in [(0.5, 2.0), (-1.5, 0.25), (3.0, -2.0)] {
+                let want = naive.evaluate(&[x, y])[0];
+                let got = optimized.evaluate(&[x, y])[0];
+                assert!(
+                    (want - got).abs() <= 1e-9 * want.abs().max(1.0),
+                    "{} != {}",
+                    got,
+                    want
+                );
+            }
+            assert!(!optimized
+                .instructions()
+                .iter()
+                .any(|i| matches!(i, Instruction::Binary(OperationKind::Pow, ..))));
+        }
+
+        // Naively, the polynomial takes 3 calls to `pow`, 3 multiplications and 4 additions; in
+        // Horner form, it takes 4 multiplications and 4 additions.
+        let polynomial = compile(&exprs[0], &["x", "y"]).unwrap();
+        assert_eq!(arithmetic(&polynomial), 8, "{}", polynomial);
+        // x^13 = ((x^2 * x)^2)^2 * x needs five multiplications.
+        let power = compile(&exprs[2], &["x", "y"]).unwrap();
+        assert_eq!(arithmetic(&power), 5, "{}", power);
+    }
+
+    #[test]
+    fn test_peephole() {
+        let options = CompileOptions {
+            peephole: false,
+            ..Default::default()
+        };
+        let exprs = [
+            parse("x^(1 + 1) + (y / x) / (y + 1)").unwrap(),
+            parse("--(exp(2) * x) / 1").unwrap(),
+        ];
+        let program = compile_many_with(&exprs, &["x", "y"], options).unwrap();
+        let optimized = program.peephole();
+
+        for (x, y) in [(0.5, 2.0), (-1.5, 0.25)] {
+            let want = program.evaluate(&[x, y]);
+            let got = optimized.evaluate(&[x, y]);
+            for (want, got) in want.iter().zip(&got) {
+                assert!((want - got).abs() <= 1e-12 * want.abs().max(1.0));
+            }
+        }
+
+        let expected = "\
+r0 = x
+r1 = 1.0
+r2 = r0 * r0
+r3 = y
+r4 = r3 + r1
+r5 = r0 * r4
+r6 = r3 / r5
+r7 = r2 + r6
+r8 = 7.38905609893065
+r9 = r8 * r0
+return r7, r9";
+        assert_eq!(optimized.to_string(), expected);
+        assert_eq!(compile_many(&exprs, &["x", "y"]).unwrap(), optimized);
+    }
+
+    #[test]
+    fn test_compile_sum() {
+        let sum = Sum {
+            body: parse("sin(x) * k^2 + exp(x) / (k + 1)").unwrap(),
+            index: "k",
+            lo: parse("1").unwrap(),
+            hi: parse("n").unwrap(),
+        };
+        let program = compile_sum(&sum, &["x", "n"]).unwrap();
+        // x, sin(x), exp(x) and 1 are computed once, outside the loop.
+        let varying = program.varying();
+        assert_eq!(
+            varying.iter().filter(|&&v| !v).count(),
+            4,
+            "{}",
+            program.body()
+        );
+
+        for (x, n) in [(0.3, 0.0), (-1.2, 1.0), (2.5, 1000.0)] {
+            let bindings = Bindings::from_iter([("x", x), ("n", n)]);
+            let want = sum.evaluate(&bindings).unwrap();
+            let got = program.evaluate(&[x, n]).unwrap();
+            assert!((want - got).abs() <= 1e-9 * want.abs().max(1.0));
+        }
+        // The bounds are parameters, so they are checked when the program runs.
+        for n in [2.5, f64::INFINITY, 1e300] {
+            assert_eq!(
+                program.evaluate(&[0.3, n]),
+                Err(EvaluationError::InvalidBound("k"))
+            );
+        }
+
+        let indexed = Sum {
+            body: parse("a[k]").unwrap(),
+            ..sum
+        };
+        assert!(matches!(
+            compile_sum(&indexed, &["x", "n"]),
+            Err(CodegenError::UnknownVariable(_))
+        ));
+        assert_eq!(
+            compile_sum(&indexed, &["x", "k"]),
+            Err(CodegenError::IndexIsParameter("k"))
+        );
+    }
+}
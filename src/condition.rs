@@ -0,0 +1,363 @@
+//! This module describes side conditions that an expression is only valid under.
+//!
+//! Some simplifications are only sound where the original expression was defined: cancelling
+//! `(x - 1)` from `(x - 1)·(x + 1) / (x - 1)` is only correct for `x ≠ 1`. A [`Conditional`]
+//! pairs an expression with the [`Condition`]s such simplifications introduced, so that they
+//! are checked when it is evaluated and carried through every further simplification instead
+//! of being silently dropped.
+
+use std::fmt::Display;
+
+use crate::{
+    constants::Value,
+    evaluation::{Bindings, EvaluationError},
+    interval::IntervalBindings,
+    provenance::{self, Step},
+    symbols::{
+        OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        OperationKind::{Division, Multiplication, Pow},
+    },
+};
+
+/// A fact about the value of an expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    Nonzero(OpArgument),
+    Nonnegative(OpArgument),
+}
+
+impl Condition {
+    /// The expression the condition constrains.
+    pub fn expr(&self) -> &OpArgument {
+        match self {
+            Condition::Nonzero(expr) | Condition::Nonnegative(expr) => expr,
+        }
+    }
+
+    fn with_expr(&self, expr: OpArgument) -> Condition {
+        match self {
+            Condition::Nonzero(_) => Condition::Nonzero(expr),
+            Condition::Nonnegative(_) => Condition::Nonnegative(expr),
+        }
+    }
+
+    fn admits(&self, value: f64) -> bool {
+        match self {
+            Condition::Nonzero(_) => value != 0.0,
+            Condition::Nonnegative(_) => value >= 0.0,
+        }
+    }
+
+    /// Whether the condition holds for the variables given in `bindings`.
+    pub fn check(&self, bindings: &Bindings) -> Result<bool, EvaluationError> {
+        Ok(self.admits(self.expr().evaluate(bindings)?))
+    }
+
+    /// Whether the condition provably holds for every assignment of the variables within their
+    /// ranges in `ranges`.
+    pub fn holds_within(&self, ranges: &IntervalBindings) -> bool {
+        let Ok(range) = self.expr().evaluate_interval(ranges) else {
+            return false;
+        };
+        !range.is_empty()
+            && match self {
+                Condition::Nonzero(_) => !range.contains(0.0),
+                Condition::Nonnegative(_) => range.lo >= 0.0,
+            }
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Condition::Nonzero(expr) => write!(f, "{} ≠ 0", expr),
+            Condition::Nonnegative(expr) => write!(f, "{} ≥ 0", expr),
+        }
+    }
+}
+
+/// The reasons evaluating a [`Conditional`] can fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConditionalError {
+    Evaluation(EvaluationError),
+    /// The bindings lie outside the domain the expression is valid on.
+    Violated(Condition),
+}
+
+impl Display for ConditionalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConditionalError::Evaluation(err) => err.fmt(f),
+            ConditionalError::Violated(condition) => {
+                write!(f, "the expression is only valid for {}", condition)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConditionalError {}
+
+impl From<EvaluationError> for ConditionalError {
+    fn from(err: EvaluationError) -> Self {
+        ConditionalError::Evaluation(err)
+    }
+}
+
+/// An expression together with the conditions under which it is valid.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conditional {
+    expr: OpArgument,
+    conditions: Vec<Condition>,
+}
+
+impl From<OpArgument> for Conditional {
+    fn from(expr: OpArgument) -> Self {
+        Conditional {
+            expr,
+            conditions: Vec::new(),
+        }
+    }
+}
+
+impl Conditional {
+    pub fn expr(&self) -> &OpArgument {
+        &self.expr
+    }
+
+    pub fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    /// Adds `condition`, unless it is already present.
+    pub fn with(mut self, condition: Condition) -> Self {
+        if !self.conditions.contains(&condition) {
+            self.conditions.push(condition);
+        }
+        self
+    }
+
+    /// Transforms the expression, keeping the conditions. `f` must not widen the domain the
+    /// expression is valid on.
+    pub fn map(self, f: impl FnOnce(&OpArgument) -> OpArgument) -> Self {
+        Conditional {
+            expr: f(&self.expr),
+            conditions: self.conditions,
+        }
+    }
+
+    /// Folds the expression and the conditions, dropping the conditions that fold to constants
+    /// satisfying them.
+    pub fn fold(&self) -> Self {
+        let conditions = self
+            .conditions
+            .iter()
+            .map(|condition| condition.with_expr(condition.expr().fold()))
+            .filter(|condition| !condition.holds_within(&IntervalBindings::default()))
+            .fold(Vec::new(), |mut kept, condition| {
+                if !kept.contains(&condition) {
+                    kept.push(condition);
+                }
+                kept
+            });
+        Conditional {
+            expr: self.expr.fold(),
+            conditions,
+        }
+    }
+
+    /// Drops the conditions that hold for every assignment of the variables within their
+    /// ranges in `ranges`, such as the assumptions of a context.
+    pub fn discharge(&self, ranges: &IntervalBindings) -> Self {
+        Conditional {
+            expr: self.expr.clone(),
+            conditions: self
+                .conditions
+                .iter()
+                .filter(|condition| !condition.holds_within(ranges))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Cancels common factors of quotients, and roots raised back to their own power, adding
+    /// the conditions under which that is valid.
+    pub fn cancel(&self) -> Self {
+        let mut conditions = self.conditions.clone();
+        let expr = cancel(&self.expr, &mut conditions);
+        conditions
+            .into_iter()
+            .fold(Conditional::from(expr), Conditional::with)
+    }
+
+    /// Evaluates the expression, after checking that `bindings` satisfy every condition.
+    pub fn evaluate(&self, bindings: &Bindings) -> Result<f64, ConditionalError> {
+        for condition in &self.conditions {
+            if !condition.check(bindings)? {
+                return Err(ConditionalError::Violated(condition.clone()));
+            }
+        }
+        Ok(self.expr.evaluate(bindings)?)
+    }
+}
+
+impl Display for Conditional {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expr)?;
+        for (i, condition) in self.conditions.iter().enumerate() {
+            let separator = if i == 0 { ", valid for" } else { " and" };
+            write!(f, "{} {}", separator, condition)?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects the factors of a product tree into `factors`.
+fn factors(node: &OpArgument, factors: &mut Vec<OpArgument>) {
+    match &node.value {
+        Op(op) if op.op == Multiplication => {
+            op.arguments
+                .iter()
+                .for_each(|arg| self::factors(arg, factors));
+        }
+        _ => factors.push(node.clone()),
+    }
+}
+
+fn product(factors: Vec<OpArgument>) -> OpArgument {
+    factors
+        .into_iter()
+        .reduce(|acc, factor| acc * factor)
+        .unwrap_or_else(|| Value::integer(1).into())
+}
+
+/// Reads `n` out of the constant `1/n`, or `n` itself.
+fn reciprocal_of(node: &OpArgument) -> Option<u64> {
+    match &node.value {
+        Leaf(value) => match **value {
+            Value::Rational(1, den) => Some(den.get()),
+            _ => None,
+        },
+        Op(_) => None,
+    }
+}
+
+fn integer(node: &OpArgument) -> Option<u64> {
+    match &node.value {
+        Leaf(value) => match **value {
+            Value::Rational(num, den) if den.get() == 1 => Some(num),
+            _ => None,
+        },
+        Op(_) => None,
+    }
+}
+
+fn cancel_root(node: &OpArgument, conditions: &mut Vec<Condition>) -> Option<OpArgument> {
+    let Op(op) = &node.value else {
+        return None;
+    };
+    let arg = |i: usize| &op.arguments[i];
+
+    match op.op {
+        Division => {
+            let (mut numerator, mut denominator) = (Vec::new(), Vec::new());
+            factors(arg(0), &mut numerator);
+            factors(arg(1), &mut denominator);
+
+            let mut cancelled = false;
+            numerator.retain(|factor| {
+                let Some(i) = denominator.iter().position(|d| d == factor) else {
+                    return true;
+                };
+                denominator.remove(i);
+                conditions.push(Condition::Nonzero(factor.clone()));
+                cancelled = true;
+                false
+            });
+            if !cancelled {
+                return None;
+            }
+            Some(match denominator.is_empty() {
+                true => product(numerator),
+                false => product(numerator) / product(denominator),
+            })
+        }
+        // (a^(1/n))^n = a, which for even n only holds where the root is real.
+        Pow => {
+            let n = integer(arg(1))?;
+            let Op(inner) = &arg(0).value else {
+                return None;
+            };
+            if inner.op != Pow || reciprocal_of(&inner.arguments[1]) != Some(n) || n == 0 {
+                return None;
+            }
+            let base = inner.arguments[0].clone();
+            if n % 2 == 0 {
+                conditions.push(Condition::Nonnegative(base.clone()));
+            }
+            Some(base)
+        }
+        _ => None,
+    }
+}
+
+fn cancel(node: &OpArgument, conditions: &mut Vec<Condition>) -> OpArgument {
+    node.map_bottom_up(|node, rebuilt| {
+        let result = cancel_root(&rebuilt, conditions).unwrap_or(rebuilt);
+        provenance::record(|| Step::Fold, node, &result);
+        result
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Condition, Conditional, ConditionalError};
+    use crate::{
+        evaluation::Bindings,
+        interval::{Interval, IntervalBindings},
+        parse::parse,
+        symbols::variable,
+    };
+
+    #[test]
+    fn test_cancel() {
+        let x = variable("x");
+        let expr = parse("(x - 1) * (x + 1) / (x - 1)").unwrap();
+
+        let cancelled = Conditional::from(expr).cancel();
+        assert_eq!(cancelled.expr(), &parse("x + 1").unwrap());
+        let condition = Condition::Nonzero(parse("x - 1").unwrap());
+        assert_eq!(cancelled.conditions(), std::slice::from_ref(&condition));
+        assert_eq!(
+            cancelled.to_string(),
+            format!("{}, valid for {} ≠ 0", cancelled.expr(), condition.expr())
+        );
+
+        let at = |value| Bindings::from_iter([("x", value)]);
+        assert_eq!(cancelled.evaluate(&at(2.0)), Ok(3.0));
+        assert_eq!(
+            cancelled.evaluate(&at(1.0)),
+            Err(ConditionalError::Violated(condition.clone()))
+        );
+
+        // Conditions survive further transformations, and accumulate.
+        let root = cancelled.map(|e| {
+            e.pow(&parse("1/2").unwrap().fold())
+                .pow(&parse("2").unwrap())
+        });
+        let both = root.cancel();
+        assert_eq!(both.expr(), &(&x + parse("1").unwrap()));
+        assert_eq!(
+            both.conditions(),
+            [condition, Condition::Nonnegative(parse("x + 1").unwrap())]
+        );
+        let ranges = IntervalBindings::from_iter([("x", Interval::new(2.0, 3.0))]);
+        assert!(both.discharge(&ranges).conditions().is_empty());
+
+        // Conditions on constants are decided by folding.
+        let constant = Conditional::from(parse("2 * 3 * x / (2 * 3)").unwrap()).cancel();
+        assert_eq!(constant.conditions().len(), 2);
+        assert_eq!(constant.fold().expr(), &x);
+        assert!(constant.fold().conditions().is_empty());
+    }
+}
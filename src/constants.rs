@@ -6,6 +6,8 @@ use std::{
     num::NonZeroU64,
 };
 
+use crate::{algebraic::AlgebraicNumber, symbols::base_name};
+
 /// The [`Value`] struct represents a symbol within some computational context.
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub enum Value {
@@ -14,7 +16,33 @@ pub enum Value {
     E,
     I,
     Inf,
+    /// The result of an indeterminate form such as `∞ - ∞` or `0/0`.
+    Undefined,
     Variable(&'static str),
+    /// A real root of a polynomial with rational coefficients, see [`crate::algebraic`].
+    Algebraic(&'static AlgebraicNumber),
+}
+
+pub(crate) fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+impl Value {
+    /// Constructs the rational `num/den` in lowest terms.
+    pub fn rational(num: u64, den: u64) -> Value {
+        let divisor = gcd(num, den).max(1);
+        let den = NonZeroU64::new(den / divisor)
+            .expect("Whoa there, a rational can't have a zero denominator");
+        Value::Rational(num / divisor, den)
+    }
+
+    /// Constructs the integer `num`.
+    pub fn integer(num: u64) -> Value {
+        Value::Rational(num, NonZeroU64::MIN)
+    }
 }
 
 impl Hash for Value {
@@ -26,6 +54,8 @@ impl Hash for Value {
             Value::I => 3,
             Value::Inf => 4,
             Value::Variable(_) => 5,
+            Value::Undefined => 6,
+            Value::Algebraic(_) => 7,
         };
 
         state.write_u32(disc_code);
@@ -35,6 +65,8 @@ impl Hash for Value {
             state.write_u64(den.get());
         } else if let Value::Variable(str) = self {
             state.write(str.as_bytes());
+        } else if let Value::Algebraic(number) = self {
+            number.hash(state);
         }
     }
 }
@@ -47,13 +79,19 @@ impl Display for Value {
             Value::E => f.write_char('e'),
             Value::I => f.write_char('i'),
             Value::Inf => f.write_char('∞'),
-            Value::Variable(v) => f.write_str(v),
+            Value::Undefined => f.write_str("undefined"),
+            Value::Variable(v) => f.write_str(base_name(v)),
+            Value::Algebraic(number) => Display::fmt(number, f),
         }
     }
 }
 
 impl Debug for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        Display::fmt(self, f)
+        match self {
+            // Keep the scope, so that symbols of different contexts can be told apart.
+            Value::Variable(v) => f.write_str(v),
+            _ => Display::fmt(self, f),
+        }
     }
 }
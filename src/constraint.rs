@@ -0,0 +1,279 @@
+//! This module describes interval constraint propagation.
+//!
+//! A [`Constraint`] requires an expression to lie in an interval, which covers equations and
+//! inequalities alike. [`contract`] shrinks the ranges of the variables to boxes that still hold
+//! every solution, following HC4: each constraint is evaluated over intervals bottom up, the
+//! result is intersected with the required range, and that range is projected back down the
+//! tree, narrowing every argument to the values that could have produced it. Repeating this
+//! over all constraints until nothing shrinks gives a box that is either empty, proving there
+//! is no solution, or an enclosure of the solutions that is much tighter than the input.
+//!
+//! Projections through `sin`, `cos`, `tan` and through exponents are not inverted, since their
+//! preimages are unions of intervals. They still narrow the other parts of the tree.
+
+use crate::{
+    constants::Value,
+    evaluation::EvaluationError,
+    interval::{Interval, IntervalBindings},
+    symbols::{
+        OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        OperationKind::*,
+    },
+};
+
+use std::f64::consts::FRAC_PI_2;
+
+/// Requires an expression to take a value within an interval.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Constraint {
+    pub expr: OpArgument,
+    pub range: Interval,
+}
+
+impl Constraint {
+    pub fn new(expr: OpArgument, range: Interval) -> Self {
+        Constraint { expr, range }
+    }
+
+    /// `lhs = rhs`
+    pub fn equal(lhs: &OpArgument, rhs: &OpArgument) -> Self {
+        Constraint::new(lhs - rhs, Interval::point(0.0))
+    }
+
+    /// `lhs ≤ rhs`
+    pub fn less_equal(lhs: &OpArgument, rhs: &OpArgument) -> Self {
+        Constraint::new(lhs - rhs, Interval::new(f64::NEG_INFINITY, 0.0))
+    }
+
+    /// `lhs ≥ rhs`
+    pub fn greater_equal(lhs: &OpArgument, rhs: &OpArgument) -> Self {
+        Constraint::new(lhs - rhs, Interval::new(0.0, f64::INFINITY))
+    }
+}
+
+/// What [`contract`] could establish about the constraints on the box it left.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Contraction {
+    /// No point of the original box satisfies every constraint.
+    Infeasible,
+    /// Every point of the box satisfies every constraint.
+    Satisfied,
+    /// The box encloses every solution, but may contain points that are not.
+    Undecided,
+}
+
+/// The interval enclosure of every node of an expression, mirroring its tree.
+struct Node {
+    value: Interval,
+    arguments: Vec<Node>,
+}
+
+fn forward(expr: &OpArgument, ranges: &IntervalBindings) -> Result<Node, EvaluationError> {
+    match &expr.value {
+        Op(op) => {
+            let arguments = op
+                .arguments
+                .iter()
+                .map(|arg| forward(arg, ranges))
+                .collect::<Result<Vec<_>, _>>()?;
+            let values = arguments.iter().map(|arg| arg.value).collect::<Vec<_>>();
+            Ok(Node {
+                value: Interval::apply(op.op, &values),
+                arguments,
+            })
+        }
+        Leaf(leaf) => Ok(Node {
+            value: leaf.evaluate_interval(ranges)?,
+            arguments: Vec::new(),
+        }),
+    }
+}
+
+/// The values of `x` with `xⁿ` in `r`, for `n > 0`, within `x`.
+fn root(x: Interval, r: Interval, n: f64) -> Interval {
+    let nonnegative = Interval::new(0.0, f64::INFINITY);
+    let positive = r.intersect(&nonnegative).powf(Interval::point(1.0 / n));
+    if n.fract() != 0.0 {
+        // Fractional powers are only defined for nonnegative bases.
+        return x.intersect(&positive);
+    }
+    let negative = match n % 2.0 == 0.0 {
+        true => -positive,
+        false => -(-r).intersect(&nonnegative).powf(Interval::point(1.0 / n)),
+    };
+    x.intersect(&positive).hull(&x.intersect(&negative))
+}
+
+/// Narrows `ranges` to the values of the variables for which `expr`, whose enclosures are
+/// `node`, can lie in `target`. Returns `false` once that is provably impossible.
+fn backward(
+    expr: &OpArgument,
+    node: &Node,
+    target: Interval,
+    ranges: &mut IntervalBindings,
+) -> bool {
+    let t = node.value.intersect(&target);
+    if t.is_empty() {
+        return false;
+    }
+    let op = match &expr.value {
+        Leaf(leaf) => {
+            if let Value::Variable(name) = **leaf {
+                let range = ranges.get_mut(name).expect(
+                    "Oops, every variable of a constraint was bound during its forward pass",
+                );
+                *range = range.intersect(&t);
+                return !range.is_empty();
+            }
+            return true;
+        }
+        Op(op) => op,
+    };
+    let a = node.arguments[0].value;
+    let b = node.arguments.get(1).map(|arg| arg.value);
+    let targets = match (op.op, b) {
+        (Addition, Some(b)) => vec![t - b, t - a],
+        (Subtraction, Some(b)) => vec![t + b, a - t],
+        (Multiplication, Some(b)) => vec![t / b, t / a],
+        (Division, Some(b)) => vec![t * b, a / t],
+        (Pow, Some(b)) if b.lo == b.hi && b.lo != 0.0 => {
+            let a = match b.lo > 0.0 {
+                true => root(a, t, b.lo),
+                false => root(a, Interval::point(1.0) / t, -b.lo),
+            };
+            vec![a, b]
+        }
+        (Pow, Some(b)) => vec![a, b],
+        (Negation, _) => vec![-t],
+        (Exp, _) => vec![t.ln()],
+        (Ln, _) => vec![t.exp()],
+        (Atan, _) => vec![t.intersect(&Interval::new(-FRAC_PI_2, FRAC_PI_2)).tan()],
+        (_, Some(b)) => vec![a, b],
+        (_, None) => vec![a],
+    };
+    op.arguments
+        .iter()
+        .zip(&node.arguments)
+        .zip(targets)
+        .all(|((arg, node), target)| backward(arg, node, target, ranges))
+}
+
+/// Whether a bound moved by more than rounding noise.
+fn moved(old: f64, new: f64) -> bool {
+    old != new && (!old.is_finite() || (old - new).abs() > 1e-9 * (1.0 + old.abs()))
+}
+
+/// Shrinks `ranges` to a box enclosing every point of it that satisfies all `constraints`,
+/// propagating for at most `max_rounds` passes over them. Every variable of the constraints
+/// must be bound.
+pub fn contract(
+    constraints: &[Constraint],
+    ranges: &mut IntervalBindings,
+    max_rounds: usize,
+) -> Result<Contraction, EvaluationError> {
+    for _ in 0..max_rounds {
+        let before = ranges.clone();
+        for constraint in constraints {
+            let node = forward(&constraint.expr, ranges)?;
+            if !backward(&constraint.expr, &node, constraint.range, ranges) {
+                for range in ranges.values_mut() {
+                    *range = Interval::EMPTY;
+                }
+                return Ok(Contraction::Infeasible);
+            }
+        }
+        let progressed = ranges.iter().any(|(name, new)| {
+            let old = before[name];
+            moved(old.lo, new.lo) || moved(old.hi, new.hi)
+        });
+        if !progressed {
+            break;
+        }
+    }
+
+    for constraint in constraints {
+        let value = constraint.expr.evaluate_interval(ranges)?;
+        if value.intersect(&constraint.range) != value {
+            return Ok(Contraction::Undecided);
+        }
+    }
+    Ok(Contraction::Satisfied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{contract, Constraint, Contraction};
+    use crate::{
+        interval::{Interval, IntervalBindings},
+        parse::parse,
+    };
+
+    #[test]
+    fn test_contract() {
+        let p = |input: &str| parse(input).unwrap();
+        let close =
+            |a: Interval, lo: f64, hi: f64| (a.lo - lo).abs() < 1e-9 && (a.hi - hi).abs() < 1e-9;
+
+        // x + y = 3 and x - y = 1 on [0, 10]²: propagation alone stalls before the solution.
+        let mut ranges = IntervalBindings::from_iter([
+            ("x", Interval::new(0.0, 10.0)),
+            ("y", Interval::new(0.0, 10.0)),
+        ]);
+        let linear = [
+            Constraint::equal(&p("x + y"), &p("3")),
+            Constraint::equal(&p("x - y"), &p("1")),
+        ];
+        assert_eq!(
+            contract(&linear, &mut ranges, 100),
+            Ok(Contraction::Undecided)
+        );
+        assert!(close(ranges["x"], 1.0, 3.0) && close(ranges["y"], 0.0, 2.0));
+
+        // The nonnegative square root of 4.
+        let mut ranges = IntervalBindings::from_iter([("x", Interval::new(0.0, 10.0))]);
+        let square = [Constraint::equal(&p("x^2"), &p("4"))];
+        contract(&square, &mut ranges, 100).unwrap();
+        assert!(close(ranges["x"], 2.0, 2.0));
+
+        let mut ranges = IntervalBindings::from_iter([
+            ("x", Interval::new(-5.0, 5.0)),
+            ("y", Interval::new(-5.0, 5.0)),
+        ]);
+        // The unit disc touches y ≥ x² + 1 only at (0, 1).
+        let circle = [
+            Constraint::less_equal(&p("x^2 + y^2"), &p("1")),
+            Constraint::greater_equal(&p("y"), &p("x^2 + 1")),
+        ];
+        contract(&circle, &mut ranges, 100).unwrap();
+        assert!(ranges["x"].width() < 1e-3 && ranges["y"].contains(1.0));
+        assert!(ranges["y"].hi <= 1.0 + 1e-9);
+
+        let mut ranges = IntervalBindings::from_iter([("x", Interval::new(-5.0, 5.0))]);
+        let bounded = [Constraint::less_equal(&p("exp(x)"), &p("1"))];
+        contract(&bounded, &mut ranges, 100).unwrap();
+        assert!(close(ranges["x"], -5.0, 0.0));
+        let loose = [Constraint::less_equal(&p("exp(x)"), &p("100"))];
+        assert_eq!(
+            contract(&loose, &mut ranges, 100),
+            Ok(Contraction::Satisfied)
+        );
+
+        let mut ranges = IntervalBindings::from_iter([("x", Interval::new(-5.0, 5.0))]);
+        let impossible = [Constraint::less_equal(&p("x^2 + 1"), &p("0"))];
+        assert_eq!(
+            contract(&impossible, &mut ranges, 100),
+            Ok(Contraction::Infeasible)
+        );
+        assert!(ranges["x"].is_empty());
+
+        // ∞ - ∞ encloses nothing, so it cannot narrow x but must not abort either.
+        let mut ranges = IntervalBindings::from_iter([("x", Interval::new(-5.0, 5.0))]);
+        let indeterminate = [Constraint::less_equal(&p("x + (inf - inf)"), &p("1"))];
+        assert_eq!(
+            contract(&indeterminate, &mut ranges, 100),
+            Ok(Contraction::Undecided)
+        );
+        assert_eq!(ranges["x"], Interval::new(-5.0, 5.0));
+    }
+}
@@ -0,0 +1,661 @@
+//! This module describes contexts, which own a scope of symbols and the assumptions made about
+//! them.
+//!
+//! Symbols created through a [`Context`] are distinct from the symbols of every other context
+//! and from global variables of the same name, although they print the same. This lets
+//! independent models in one process each have their own `x`. Dropping a context frees its
+//! tables; the interned symbol names themselves stay allocated for as long as the process runs,
+//! since expressions built in the context may outlive it. Scopes are never reused, so such
+//! expressions keep symbols of their own rather than taking on those of a later context.
+//!
+//! Contexts are copy-on-write: cloning one, or taking a [`Snapshot`] of it, is constant time,
+//! and the tables are only copied when a clone that shares them is modified. Clones keep the
+//! scope of the original, so threads can each work on their own clone of a context, and
+//! speculative work can be rolled back with [`Context::restore`].
+//!
+//! A context also holds named expressions and user rewrite rules, and the whole workspace can
+//! be saved to and loaded from a file in the binary format of [`crate::serialize`]. Loading
+//! creates a new scope, so a file can be loaded any number of times without the copies
+//! interfering.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    interval::{Interval, IntervalBindings},
+    operations::with_simplification,
+    parse::{parse, ParseError},
+    rewrite::{Rule, Substitution},
+    serialize::{
+        decode, read_f64, read_header, read_string, read_u32, read_u8, write_f64, write_header,
+        write_str, write_u32, DecodeError, Encoder,
+    },
+    symbols::{intern, variable, OpArgument, SCOPE_SEPARATOR},
+};
+
+/// The magic number of a saved context.
+pub const CONTEXT_MAGIC: [u8; 4] = *b"SYMC";
+
+/// A fact assumed about the value of a symbol.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Assumption {
+    Positive,
+    Nonnegative,
+    Nonzero,
+    Integer,
+    /// The value lies in the interval.
+    Within(Interval),
+}
+
+impl Assumption {
+    /// An interval containing every value the assumption allows.
+    pub fn range(&self) -> Interval {
+        match self {
+            Assumption::Positive | Assumption::Nonnegative => Interval::new(0.0, f64::INFINITY),
+            Assumption::Nonzero | Assumption::Integer => Interval::ENTIRE,
+            Assumption::Within(range) => *range,
+        }
+    }
+}
+
+/// How [`Context::hash`] hashes expressions.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Hashing {
+    /// The cached [`OpArgument::hash`], which is fast but seeded anew by every process.
+    #[default]
+    Random,
+    /// A fixed-key hash of the structure, reproducible across runs and platforms.
+    Deterministic,
+}
+
+/// What the operators simplify as they build nodes, within [`Context::enter`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Simplification {
+    /// Nodes are built exactly as written.
+    #[default]
+    Off,
+    /// Identity elements are dropped, as in `x + 0`, `1·x` and `x^1`, and operations on
+    /// constants are folded.
+    Light,
+    /// Also `x - x`, `x/x`, `0·x` and `x^0` cancel for `x` that are not constants, double
+    /// negations cancel, and every node is folded.
+    /// The result may be defined at points where the expression written is not.
+    Aggressive,
+}
+
+static NEXT_SCOPE: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Debug, Default)]
+struct Tables {
+    /// Maps the name of every symbol to its scoped name, in name order.
+    symbols: BTreeMap<&'static str, &'static str>,
+    assumptions: BTreeMap<&'static str, Vec<Assumption>>,
+    expressions: BTreeMap<String, OpArgument>,
+    rules: Vec<Rule>,
+}
+
+/// A symbol table, the assumptions made about its symbols, and the named expressions and
+/// rewrite rules of a workspace.
+#[derive(Clone, Debug)]
+pub struct Context {
+    scope: u64,
+    tables: Arc<Tables>,
+    hashing: Hashing,
+    simplification: Simplification,
+}
+
+/// The state of a [`Context`] at some point, to be returned to with [`Context::restore`].
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    scope: u64,
+    tables: Arc<Tables>,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context {
+            scope: NEXT_SCOPE.fetch_add(1, Ordering::Relaxed),
+            tables: Arc::default(),
+            hashing: Hashing::default(),
+            simplification: Simplification::default(),
+        }
+    }
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            scope: self.scope,
+            tables: Arc::clone(&self.tables),
+        }
+    }
+
+    /// Discards every change made since `snapshot` was taken.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        assert_eq!(
+            self.scope, snapshot.scope,
+            "Oops, a context can only be restored from a snapshot of itself or one of its clones"
+        );
+        self.tables = Arc::clone(&snapshot.tables);
+    }
+
+    /// The symbol called `name` in this context, declaring it on first use.
+    pub fn symbol(&mut self, name: &str) -> OpArgument {
+        if let Some(&scoped) = self.tables.symbols.get(name) {
+            return variable(scoped);
+        }
+        assert!(
+            !name.contains(SCOPE_SEPARATOR),
+            "Whoa there, symbol names can't contain {:?}, but got {:?}",
+            SCOPE_SEPARATOR,
+            name
+        );
+        let scoped = intern(&format!("{}{}{}", name, SCOPE_SEPARATOR, self.scope));
+        Arc::make_mut(&mut self.tables)
+            .symbols
+            .insert(intern(name), scoped);
+        variable(scoped)
+    }
+
+    /// The symbol called `name`, if it has been declared.
+    pub fn lookup(&self, name: &str) -> Option<OpArgument> {
+        self.tables
+            .symbols
+            .get(name)
+            .map(|&scoped| variable(scoped))
+    }
+
+    /// The names of the declared symbols, in order.
+    pub fn symbols(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.tables.symbols.keys().copied()
+    }
+
+    /// Records `assumption` about the symbol `name`, declaring it if need be.
+    pub fn assume(&mut self, name: &str, assumption: Assumption) {
+        self.symbol(name);
+        let tables = Arc::make_mut(&mut self.tables);
+        let scoped = tables.symbols[name];
+        let assumptions = tables.assumptions.entry(scoped).or_default();
+        if !assumptions.contains(&assumption) {
+            assumptions.push(assumption);
+        }
+    }
+
+    pub fn assumptions(&self, name: &str) -> &[Assumption] {
+        self.tables
+            .symbols
+            .get(name)
+            .and_then(|scoped| self.tables.assumptions.get(scoped))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// An interval containing every value the assumptions about `name` allow.
+    pub fn range(&self, name: &str) -> Interval {
+        self.assumptions(name)
+            .iter()
+            .fold(Interval::ENTIRE, |range, assumption| {
+                range.intersect(&assumption.range())
+            })
+    }
+
+    /// The range of every declared symbol, for the interval evaluator and the error analyses.
+    pub fn ranges(&self) -> IntervalBindings<'static> {
+        self.tables
+            .symbols
+            .iter()
+            .map(|(name, &scoped)| (scoped, self.range(name)))
+            .collect()
+    }
+
+    /// Parses `input`, reading every identifier as a symbol of this context.
+    pub fn parse(&mut self, input: &str) -> Result<OpArgument, ParseError> {
+        let expr = self.enter(|| parse(input))?;
+        let substitution: Substitution = expr
+            .free_variables()
+            .iter()
+            .map(|name| (name, self.symbol(name)))
+            .collect();
+        Ok(expr.substitute(&substitution))
+    }
+
+    /// Names `expr`, replacing any expression previously given that name.
+    pub fn define(&mut self, name: &str, expr: OpArgument) {
+        Arc::make_mut(&mut self.tables)
+            .expressions
+            .insert(name.to_owned(), expr);
+    }
+
+    pub fn expression(&self, name: &str) -> Option<&OpArgument> {
+        self.tables.expressions.get(name)
+    }
+
+    /// The named expressions, in name order.
+    pub fn expressions(&self) -> impl Iterator<Item = (&str, &OpArgument)> {
+        self.tables
+            .expressions
+            .iter()
+            .map(|(name, expr)| (name.as_str(), expr))
+    }
+
+    pub fn add_rule(&mut self, rule: Rule) {
+        Arc::make_mut(&mut self.tables).rules.push(rule);
+    }
+
+    /// The user rewrite rules, in the order they were added.
+    pub fn rules(&self) -> &[Rule] {
+        &self.tables.rules
+    }
+
+    /// Writes the symbols, assumptions, named expressions and rules of the context.
+    pub fn write_to(&self, mut w: impl Write) -> io::Result<()> {
+        let tables = &self.tables;
+        write_header(&mut w, CONTEXT_MAGIC)?;
+
+        write_u32(&mut w, tables.symbols.len() as u32)?;
+        for name in tables.symbols.keys() {
+            write_str(&mut w, name)?;
+        }
+
+        write_u32(&mut w, tables.assumptions.len() as u32)?;
+        for (name, &scoped) in &tables.symbols {
+            let Some(assumptions) = tables.assumptions.get(scoped) else {
+                continue;
+            };
+            write_str(&mut w, name)?;
+            write_u32(&mut w, assumptions.len() as u32)?;
+            for assumption in assumptions {
+                match assumption {
+                    Assumption::Positive => w.write_all(&[0])?,
+                    Assumption::Nonnegative => w.write_all(&[1])?,
+                    Assumption::Nonzero => w.write_all(&[2])?,
+                    Assumption::Integer => w.write_all(&[3])?,
+                    Assumption::Within(range) => {
+                        w.write_all(&[4])?;
+                        write_f64(&mut w, range.lo)?;
+                        write_f64(&mut w, range.hi)?;
+                    }
+                }
+            }
+        }
+
+        write_u32(&mut w, tables.expressions.len() as u32)?;
+        for name in tables.expressions.keys() {
+            write_str(&mut w, name)?;
+        }
+        write_u32(&mut w, tables.rules.len() as u32)?;
+        for rule in &tables.rules {
+            write_str(&mut w, rule.name())?;
+        }
+
+        let unscoped: BTreeMap<_, _> = tables.symbols.iter().map(|(&n, &s)| (s, n)).collect();
+        let local = |name| unscoped.get(name).copied();
+        let mut encoder = Encoder::new(&local);
+        let roots = tables
+            .expressions
+            .values()
+            .chain(
+                tables
+                    .rules
+                    .iter()
+                    .flat_map(|rule| [rule.lhs(), rule.rhs()]),
+            )
+            .map(|expr| encoder.node(expr))
+            .collect::<Vec<_>>();
+        encoder.finish(&mut w, &roots)
+    }
+
+    /// The symbol called `name`, as [`Context::symbol`], for a name read from a file, which
+    /// may hold anything.
+    fn read_symbol(&mut self, name: &str) -> Result<OpArgument, DecodeError> {
+        match name.contains(SCOPE_SEPARATOR) {
+            true => Err(DecodeError::Malformed("symbol")),
+            false => Ok(self.symbol(name)),
+        }
+    }
+
+    /// Reads a context written by [`Context::write_to`], in a new scope.
+    pub fn read_from(mut r: impl Read) -> Result<Context, DecodeError> {
+        let mut ctx = Context::new();
+        read_header(&mut r, CONTEXT_MAGIC)?;
+
+        for _ in 0..read_u32(&mut r)? {
+            ctx.read_symbol(&read_string(&mut r)?)?;
+        }
+
+        for _ in 0..read_u32(&mut r)? {
+            let name = read_string(&mut r)?;
+            ctx.read_symbol(&name)?;
+            for _ in 0..read_u32(&mut r)? {
+                let assumption = match read_u8(&mut r)? {
+                    0 => Assumption::Positive,
+                    1 => Assumption::Nonnegative,
+                    2 => Assumption::Nonzero,
+                    3 => Assumption::Integer,
+                    4 => {
+                        let (lo, hi) = (read_f64(&mut r)?, read_f64(&mut r)?);
+                        let range = Interval { lo, hi };
+                        if range.is_empty() {
+                            return Err(DecodeError::Malformed("interval"));
+                        }
+                        Assumption::Within(range)
+                    }
+                    _ => return Err(DecodeError::Malformed("assumption")),
+                };
+                ctx.assume(&name, assumption);
+            }
+        }
+
+        let names = (0..read_u32(&mut r)?)
+            .map(|_| read_string(&mut r))
+            .collect::<Result<Vec<_>, _>>()?;
+        let rule_names = (0..read_u32(&mut r)?)
+            .map(|_| read_string(&mut r))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let roots = decode(&mut r, &mut |name| ctx.read_symbol(name))?;
+        if roots.len() != names.len() + 2 * rule_names.len() {
+            return Err(DecodeError::Malformed("context"));
+        }
+        let mut roots = roots.into_iter();
+        for (name, expr) in names.iter().zip(roots.by_ref()) {
+            ctx.define(name, expr);
+        }
+        for name in rule_names {
+            let (lhs, rhs) = (roots.next().unwrap(), roots.next().unwrap());
+            let rule = Rule::new(name, lhs, rhs).map_err(|_| DecodeError::Malformed("rule"))?;
+            ctx.add_rule(rule);
+        }
+
+        Ok(ctx)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_to(&mut w)?;
+        w.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Context, DecodeError> {
+        Context::read_from(BufReader::new(File::open(path)?))
+    }
+
+    pub fn hashing(&self) -> Hashing {
+        self.hashing
+    }
+
+    /// Chooses how [`Context::hash`] hashes. Golden tests and on-disk caches need
+    /// [`Hashing::Deterministic`].
+    pub fn set_hashing(&mut self, hashing: Hashing) {
+        self.hashing = hashing;
+    }
+
+    /// A 128-bit [`OpArgument::digest`] of `expr` that reads the symbols of this context by
+    /// their names, so it does not depend on the scope the context was given in this run.
+    pub fn digest(&self, expr: &OpArgument) -> u128 {
+        let unscoped: Substitution = self
+            .tables
+            .symbols
+            .iter()
+            .map(|(&name, &scoped)| (scoped, variable(name)))
+            .collect();
+        expr.substitute(&unscoped).digest()
+    }
+
+    /// Hashes `expr` as chosen by [`Context::set_hashing`].
+    pub fn hash(&self, expr: &OpArgument) -> u64 {
+        match self.hashing {
+            Hashing::Random => expr.hash(),
+            Hashing::Deterministic => self.digest(expr) as u64,
+        }
+    }
+
+    pub fn simplification(&self) -> Simplification {
+        self.simplification
+    }
+
+    /// Chooses what the operators simplify within [`Context::enter`] and [`Context::parse`].
+    pub fn set_simplification(&mut self, simplification: Simplification) {
+        self.simplification = simplification;
+    }
+
+    /// Runs `f` with the operators on this thread simplifying as chosen by
+    /// [`Context::set_simplification`].
+    pub fn enter<R>(&self, f: impl FnOnce() -> R) -> R {
+        with_simplification(self.simplification, f)
+    }
+
+    /// Whether `expr` only uses symbols of this context.
+    pub fn owns(&self, expr: &OpArgument) -> bool {
+        expr.free_variables()
+            .iter()
+            .all(|name| self.tables.symbols.values().any(|&scoped| scoped == name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Assumption, Context, Hashing, Simplification};
+    use crate::{
+        constants::Value,
+        interval::Interval,
+        rewrite::Rule,
+        serialize::DecodeError,
+        symbols::{variable, OpArgument},
+        template::hole,
+    };
+
+    #[test]
+    fn test_scopes() {
+        let mut first = Context::new();
+        let mut second = Context::new();
+
+        let x1 = first.symbol("x");
+        let x2 = second.symbol("x");
+        assert_ne!(x1, x2);
+        assert_ne!(x1, variable("x"));
+        assert_eq!(first.symbol("x"), x1);
+        assert_eq!(x1.to_string(), "x");
+
+        let expr = first.parse("x * y + sin(x)").unwrap();
+        assert_eq!(expr, &x1 * first.lookup("y").unwrap() + x1.sin());
+        assert!(first.owns(&expr));
+        assert!(!second.owns(&expr));
+        assert_eq!(first.symbols().collect::<Vec<_>>(), ["x", "y"]);
+        assert!(second.lookup("y").is_none());
+
+        // An expression outliving its context never aliases the symbols of a later one.
+        let orphan = Context::new().symbol("x");
+        for _ in 0..100 {
+            assert_ne!(Context::new().symbol("x"), orphan);
+        }
+    }
+
+    #[test]
+    fn test_assumptions() {
+        let mut ctx = Context::new();
+        ctx.assume("x", Assumption::Positive);
+        ctx.assume("x", Assumption::Within(Interval::new(-1.0, 3.0)));
+        ctx.assume("x", Assumption::Positive);
+        assert_eq!(ctx.assumptions("x").len(), 2);
+        assert_eq!(ctx.range("x"), Interval::new(0.0, 3.0));
+        assert_eq!(ctx.range("y"), Interval::ENTIRE);
+
+        let x = ctx.symbol("x");
+        let expr = x.exp();
+        let range = expr.evaluate_interval(&ctx.ranges()).unwrap();
+        assert!(range.contains(1.0) && range.contains(3f64.exp()) && !range.contains(0.5));
+    }
+
+    #[test]
+    fn test_hashing() {
+        let (mut first, mut second) = (Context::new(), Context::new());
+        let a = first.parse("x * y + sin(x)").unwrap();
+        let b = second.parse("x * y + sin(x)").unwrap();
+        assert_eq!(first.hashing(), Hashing::Random);
+        assert_ne!(first.hash(&a), second.hash(&b));
+
+        // Deterministic hashes ignore the scope, which changes from run to run.
+        first.set_hashing(Hashing::Deterministic);
+        second.set_hashing(Hashing::Deterministic);
+        assert_eq!(first.hash(&a), second.hash(&b));
+        assert_eq!(first.digest(&a), second.digest(&b));
+        let product = first.parse("x * y").unwrap();
+        assert_ne!(first.hash(&a), first.hash(&product));
+
+        let global = crate::parse::parse("x + 1").unwrap();
+        let scoped = first.parse("x + 1").unwrap();
+        assert_eq!(
+            first.digest(&scoped),
+            0xfbab_4592_2c2d_073a_f013_39b0_adf4_46a2
+        );
+        assert_eq!(first.digest(&global), global.digest());
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let mut ctx = Context::new();
+        let x = ctx.symbol("x");
+        let snapshot = ctx.snapshot();
+
+        ctx.symbol("y");
+        ctx.assume("x", Assumption::Integer);
+        assert_eq!(ctx.symbols().count(), 2);
+        ctx.restore(&snapshot);
+        assert_eq!(ctx.symbols().collect::<Vec<_>>(), ["x"]);
+        assert!(ctx.assumptions("x").is_empty());
+
+        // Clones share the scope, but not later changes.
+        let handles = (0..4)
+            .map(|i| {
+                let mut ctx = ctx.clone();
+                std::thread::spawn(move || {
+                    ctx.assume("x", Assumption::Within(Interval::new(0.0, i as f64)));
+                    (ctx.symbol("x"), ctx.range("x"))
+                })
+            })
+            .collect::<Vec<_>>();
+        for (i, handle) in handles.into_iter().enumerate() {
+            let (symbol, range) = handle.join().unwrap();
+            assert_eq!(symbol, x);
+            assert_eq!(range, Interval::new(0.0, i as f64));
+        }
+        assert!(ctx.assumptions("x").is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut ctx = Context::new();
+        let x = ctx.symbol("x");
+        let y = ctx.symbol("y");
+        ctx.assume("x", Assumption::Positive);
+        ctx.assume("y", Assumption::Within(Interval::new(-1.0, 2.5)));
+        ctx.define("f", (&x * &y).sin() + variable("global"));
+        ctx.define("g", x.exp());
+        let a = hole("a");
+        ctx.add_rule(
+            Rule::new("double", &a + &a, OpArgument::from(Value::integer(2)) * &a).unwrap(),
+        );
+
+        let path =
+            std::env::temp_dir().join(format!("symbolica-context-{}.bin", std::process::id()));
+        ctx.save(&path).unwrap();
+        let loaded = Context::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.symbols().collect::<Vec<_>>(), ["x", "y"]);
+        assert_eq!(loaded.assumptions("x"), [Assumption::Positive]);
+        assert_eq!(loaded.range("y"), Interval::new(-1.0, 2.5));
+        assert_eq!(loaded.rules(), ctx.rules());
+
+        // The loaded context has its own scope, but the same structure.
+        let (lx, ly) = (loaded.lookup("x").unwrap(), loaded.lookup("y").unwrap());
+        assert_ne!(lx, x);
+        assert_eq!(
+            loaded.expression("f"),
+            Some(&((&lx * &ly).sin() + variable("global")))
+        );
+        assert_eq!(loaded.expressions().count(), 2);
+        assert!(loaded.owns(&loaded.expression("g").unwrap().clone()));
+
+        assert!(matches!(
+            Context::read_from(&b"SYME\x01"[..]),
+            Err(DecodeError::BadMagic)
+        ));
+
+        // Scoped names can't be smuggled in, whether declared or only used.
+        let mut corrupt = Context::new();
+        let symbol = corrupt.symbol("corrupt");
+        corrupt.define("f", symbol);
+        let mut bytes = Vec::new();
+        corrupt.write_to(&mut bytes).unwrap();
+        let at = bytes
+            .windows(7)
+            .enumerate()
+            .filter(|(_, w)| w == b"corrupt");
+        let positions = at.map(|(i, _)| i).collect::<Vec<_>>();
+        assert_eq!(positions.len(), 2);
+        for i in positions {
+            let mut bad = bytes.clone();
+            bad[i + 4] = b'@';
+            assert!(matches!(
+                Context::read_from(&bad[..]),
+                Err(DecodeError::Malformed("symbol"))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_simplification() {
+        let mut ctx = Context::new();
+        let x = ctx.symbol("x");
+        let zero = OpArgument::from(Value::integer(0));
+        let one = OpArgument::from(Value::integer(1));
+
+        assert_eq!(ctx.simplification(), Simplification::Off);
+        assert_ne!(ctx.enter(|| &x + &zero), x);
+
+        ctx.set_simplification(Simplification::Light);
+        assert_eq!(ctx.enter(|| (&x + &zero) * &one), x);
+        assert_eq!(
+            ctx.parse("x^1 - 0 + 2 * 3/4").unwrap(),
+            ctx.parse("x + 3/2").unwrap()
+        );
+        assert_ne!(ctx.enter(|| &x * &zero), zero);
+        // The policy only holds within `enter`.
+        assert_ne!(&x + &zero, x);
+
+        ctx.set_simplification(Simplification::Aggressive);
+        assert_eq!(ctx.enter(|| &x * &zero + (&x - &x)), zero);
+        assert_eq!(
+            ctx.parse("--(x / x) + sin(x + 2*pi)^0").unwrap(),
+            ctx.parse("2").unwrap()
+        );
+        assert_eq!(ctx.parse("sin(x + 2*pi)").unwrap(), x.sin());
+        // Indeterminate constants do not cancel.
+        let undefined = OpArgument::from(Value::Undefined);
+        for indeterminate in ["0/0", "inf - inf", "inf/inf", "0 * inf"] {
+            assert_eq!(
+                ctx.parse(indeterminate).unwrap(),
+                undefined,
+                "{}",
+                indeterminate
+            );
+        }
+        assert_eq!(
+            ctx.parse("3/3 + (2 - 2) * 5 + 0/7 + 4^0").unwrap(),
+            ctx.parse("2").unwrap()
+        );
+    }
+}
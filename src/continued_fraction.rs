@@ -0,0 +1,183 @@
+//! This module describes continued fractions, and the best rational approximations they give.
+//!
+//! The convergents of the continued fraction `a₀ + 1/(a₁ + 1/(a₂ + …))` of `x` are the best
+//! approximations of `x` for their denominators, so cutting the expansion off where the
+//! denominators grow too large snaps floating point inputs like `0.3333333` back to the exact
+//! rationals they were meant to be.
+
+use std::sync::Arc;
+
+use crate::{
+    constants::Value,
+    symbols::{
+        OpArgument,
+        OpArgumentKind::{Leaf, Op},
+    },
+};
+
+/// The terms of the continued fraction of the nonnegative `x`, computed as they are needed.
+fn terms(x: f64) -> impl Iterator<Item = u64> {
+    let mut y = Some(x);
+    std::iter::from_fn(move || {
+        let current = y.filter(|y| y.is_finite() && *y >= 0.0 && *y < u64::MAX as f64)?;
+        let a = current.floor();
+        y = (current != a).then(|| 1.0 / (current - a));
+        Some(a as u64)
+    })
+}
+
+/// The first `max_terms` terms of the continued fraction of the nonnegative `x`, stopping
+/// early if it is exhausted.
+pub fn expand(x: f64, max_terms: usize) -> Vec<u64> {
+    terms(x).take(max_terms).collect()
+}
+
+/// The continued fraction of the rational `num/den`, which is always finite.
+pub fn expand_rational(mut num: u64, mut den: u64) -> Vec<u64> {
+    let mut terms = Vec::new();
+    while den != 0 {
+        terms.push(num / den);
+        (num, den) = (den, num % den);
+    }
+    terms
+}
+
+/// The convergents `p/q` of the continued fraction with `terms`, stopping before one
+/// overflows.
+pub fn convergents(terms: &[u64]) -> Vec<(u64, u64)> {
+    let (mut p0, mut q0, mut p1, mut q1) = (0u64, 1u64, 1u64, 0u64);
+    let mut convergents = Vec::with_capacity(terms.len());
+    for &a in terms {
+        let next = || {
+            Some((
+                a.checked_mul(p1)?.checked_add(p0)?,
+                a.checked_mul(q1)?.checked_add(q0)?,
+            ))
+        };
+        let Some((p, q)) = next() else {
+            break;
+        };
+        (p0, q0, p1, q1) = (p1, q1, p, q);
+        convergents.push((p, q));
+    }
+    convergents
+}
+
+impl Value {
+    /// The rational closest to the nonnegative `x` with a denominator of at most
+    /// `max_denominator`, or `None` if `x` is negative, not finite, or too large.
+    pub fn approximate_rational(x: f64, max_denominator: u64) -> Option<Value> {
+        if max_denominator == 0 {
+            return None;
+        }
+        let (mut p0, mut q0, mut p1, mut q1) = (0u64, 1u64, 1u64, 0u64);
+        for a in terms(x) {
+            let q = a.checked_mul(q1).and_then(|q| q.checked_add(q0));
+            let p = a.checked_mul(p1).and_then(|p| p.checked_add(p0));
+            match (p, q) {
+                (Some(p), Some(q)) if q <= max_denominator => (p0, q0, p1, q1) = (p1, q1, p, q),
+                _ => break,
+            }
+        }
+        if q1 == 0 {
+            return None;
+        }
+
+        // The semiconvergent between the last two convergents may be closer still.
+        let k = (max_denominator - q0) / q1;
+        let best = match k.checked_mul(p1).and_then(|p| p.checked_add(p0)) {
+            Some(p) if k > 0 => {
+                let q = q0 + k * q1;
+                let error = |p: u64, q: u64| (x - p as f64 / q as f64).abs();
+                if error(p, q) < error(p1, q1) {
+                    (p, q)
+                } else {
+                    (p1, q1)
+                }
+            }
+            _ => (p1, q1),
+        };
+        Some(Value::rational(best.0, best.1))
+    }
+
+    /// Like [`Value::approximate_rational`], but only if the approximation is within
+    /// `tolerance` of `x`.
+    pub fn snap(x: f64, max_denominator: u64, tolerance: f64) -> Option<Value> {
+        Value::approximate_rational(x, max_denominator).filter(|value| match value {
+            Value::Rational(num, den) => (x - *num as f64 / den.get() as f64).abs() <= tolerance,
+            _ => false,
+        })
+    }
+}
+
+/// The closest rational to `x` with a denominator of at most `max_denominator`, negated for
+/// negative `x`.
+pub(crate) fn signed_rational(x: f64, max_denominator: u64) -> Option<OpArgument> {
+    let magnitude = OpArgument::from(Value::approximate_rational(x.abs(), max_denominator)?);
+    Some(if x < 0.0 { -magnitude } else { magnitude })
+}
+
+impl OpArgument {
+    /// Replaces every rational constant with its closest approximation whose denominator is at
+    /// most `max_denominator`, if that is within `tolerance`, so decimals typed as `0.3333333`
+    /// become `1/3`.
+    pub fn snap_rationals(&self, max_denominator: u64, tolerance: f64) -> OpArgument {
+        self.map_bottom_up(|_, rebuilt| match &rebuilt.value {
+            Leaf(value) => match **value {
+                Value::Rational(num, den) if den.get() > 1 => {
+                    let x = num as f64 / den.get() as f64;
+                    match Value::snap(x, max_denominator, tolerance) {
+                        Some(snapped) if snapped != **value => Leaf(Arc::new(snapped)).into(),
+                        _ => rebuilt,
+                    }
+                }
+                _ => rebuilt,
+            },
+            Op(_) => rebuilt,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convergents, expand, expand_rational};
+    use crate::{constants::Value, parse::parse};
+
+    #[test]
+    fn test_continued_fractions() {
+        assert_eq!(expand_rational(415, 93), [4, 2, 6, 7]);
+        assert_eq!(expand(std::f64::consts::PI, 5), [3, 7, 15, 1, 292]);
+        assert_eq!(expand(2.5, 10), [2, 2]);
+        assert_eq!(
+            convergents(&expand(std::f64::consts::PI, 4)),
+            [(3, 1), (22, 7), (333, 106), (355, 113)]
+        );
+
+        let approximate = Value::approximate_rational;
+        assert_eq!(approximate(0.3333333, 1000), Some(Value::rational(1, 3)));
+        assert_eq!(
+            approximate(std::f64::consts::PI, 100),
+            Some(Value::rational(311, 99))
+        );
+        assert_eq!(
+            approximate(std::f64::consts::PI, 1000),
+            Some(Value::rational(355, 113))
+        );
+        assert_eq!(approximate(0.0, 10), Some(Value::integer(0)));
+        assert_eq!(approximate(-0.5, 10), None);
+        assert_eq!(approximate(f64::NAN, 10), None);
+
+        assert_eq!(
+            Value::snap(0.3333333, 100, 1e-6),
+            Some(Value::rational(1, 3))
+        );
+        assert_eq!(Value::snap(0.3337, 100, 1e-6), None);
+
+        let typed = parse("0.3333333 * x + 0.142857").unwrap();
+        assert_eq!(
+            typed.snap_rationals(1000, 1e-6).fold(),
+            parse("1/3 * x + 1/7").unwrap().fold()
+        );
+        assert_eq!(typed.snap_rationals(1000, 1e-9), typed);
+    }
+}
@@ -0,0 +1,392 @@
+//! This module describes transfer functions, the rational functions that relate the inputs and
+//! outputs of linear time-invariant systems.
+//!
+//! A [`TransferFunction`] is a ratio of polynomials in the Laplace variable `s` of a continuous
+//! system or the `z` of a discrete one. Connecting systems in series, in parallel or in a
+//! feedback loop combines their transfer functions exactly, and the poles and zeros come from
+//! the exact roots of the polynomials, with irrational real roots isolated as algebraic
+//! numbers.
+
+use std::fmt::Display;
+
+use crate::{
+    evaluation::{Bindings, EvaluationError},
+    fold::Ratio,
+    polynomial::{add, mul, neg, trimmed},
+    roots::roots,
+    series::{one, zero, Series},
+    symbols::{variable, OpArgument, OpArgumentKind::Op, OperationKind::*},
+};
+
+pub use crate::roots::Root;
+
+/// The reasons a transfer function can fail to be built or analysed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControlError {
+    Evaluation(EvaluationError),
+    /// The expression is not a ratio of polynomials in the variable.
+    NotRational(OpArgument),
+    /// The coefficient of the given degree is not a rational constant, so its roots have no
+    /// closed form here.
+    IrrationalCoefficient(usize),
+    /// The polynomial has a factor with more than one pair of complex roots, which have no
+    /// closed form here.
+    Unsolvable,
+}
+
+impl Display for ControlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ControlError::Evaluation(err) => err.fmt(f),
+            ControlError::NotRational(expr) => {
+                write!(f, "{} is not a ratio of polynomials", expr)
+            }
+            ControlError::IrrationalCoefficient(k) => {
+                write!(f, "the coefficient of degree {} is not rational", k)
+            }
+            ControlError::Unsolvable => f.write_str("the polynomial has no closed-form roots"),
+        }
+    }
+}
+
+impl std::error::Error for ControlError {}
+
+impl From<EvaluationError> for ControlError {
+    fn from(err: EvaluationError) -> Self {
+        ControlError::Evaluation(err)
+    }
+}
+
+/// Whether a system evolves in continuous time, with transfer functions in `s`, or in discrete
+/// time, with transfer functions in `z`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Domain {
+    Continuous,
+    Discrete,
+}
+
+/// The complex gain of a system at one frequency.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrequencyResponse {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl FrequencyResponse {
+    pub fn magnitude(&self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// The phase in radians, in `(-π, π]`.
+    pub fn phase(&self) -> f64 {
+        self.im.atan2(self.re)
+    }
+}
+
+/// Splits `node` into a numerator and a denominator polynomial in `var`.
+fn rational(node: &OpArgument, var: &str) -> Option<(Vec<OpArgument>, Vec<OpArgument>)> {
+    if !node.free_variables().contains(var) {
+        return Some((vec![node.clone()], vec![one()]));
+    }
+    let Op(op) = &node.value else {
+        // The variable itself.
+        return Some((vec![zero(), one()], vec![one()]));
+    };
+    let arg = |i: usize| rational(&op.arguments[i], var);
+    match op.op {
+        Addition | Subtraction => {
+            let ((a, b), (c, d)) = (arg(0)?, arg(1)?);
+            let c = match op.op {
+                Subtraction => neg(&c),
+                _ => c,
+            };
+            Some((add(&mul(&a, &d), &mul(&c, &b)), mul(&b, &d)))
+        }
+        Negation => arg(0).map(|(a, b)| (neg(&a), b)),
+        Multiplication => {
+            let ((a, b), (c, d)) = (arg(0)?, arg(1)?);
+            Some((mul(&a, &c), mul(&b, &d)))
+        }
+        Division => {
+            let ((a, b), (c, d)) = (arg(0)?, arg(1)?);
+            Some((mul(&a, &d), mul(&b, &c)))
+        }
+        Pow => {
+            let n = Ratio::of(&op.arguments[1].fold()).filter(|n| n.den == 1)?;
+            let (a, b) = arg(0)?;
+            let power = |p: &[OpArgument]| {
+                (0..n.num.unsigned_abs()).fold(vec![one()], |acc, _| mul(&acc, p))
+            };
+            match n.num >= 0 {
+                true => Some((power(&a), power(&b))),
+                false => Some((power(&b), power(&a))),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Evaluates the polynomial with `coefficients`, lowest degree first, at the complex `x`.
+fn evaluate(
+    coefficients: &[OpArgument],
+    (re, im): (f64, f64),
+    bindings: &Bindings,
+) -> Result<(f64, f64), EvaluationError> {
+    coefficients.iter().rev().try_fold((0.0, 0.0), |(a, b), c| {
+        Ok((a * re - b * im + c.evaluate(bindings)?, a * im + b * re))
+    })
+}
+
+/// The transfer function `numerator(var) / denominator(var)`, with both polynomials given
+/// lowest degree first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransferFunction {
+    numerator: Vec<OpArgument>,
+    denominator: Vec<OpArgument>,
+    var: &'static str,
+    domain: Domain,
+}
+
+impl TransferFunction {
+    pub fn new(
+        numerator: &[OpArgument],
+        denominator: &[OpArgument],
+        var: &'static str,
+        domain: Domain,
+    ) -> Self {
+        let denominator = trimmed(denominator);
+        assert!(
+            !denominator.is_empty(),
+            "Whoa there, a transfer function cannot have a zero denominator"
+        );
+        TransferFunction {
+            numerator: trimmed(numerator),
+            denominator,
+            var,
+            domain,
+        }
+    }
+
+    /// The transfer function `expr`, which must be built from polynomials in `var` by
+    /// arithmetic and integer powers.
+    pub fn from_expr(
+        expr: &OpArgument,
+        var: &'static str,
+        domain: Domain,
+    ) -> Result<Self, ControlError> {
+        let (numerator, denominator) =
+            rational(expr, var).ok_or_else(|| ControlError::NotRational(expr.clone()))?;
+        let denominator = trimmed(&denominator);
+        if denominator.is_empty() {
+            return Err(ControlError::NotRational(expr.clone()));
+        }
+        Ok(TransferFunction::new(&numerator, &denominator, var, domain))
+    }
+
+    pub fn numerator(&self) -> &[OpArgument] {
+        &self.numerator
+    }
+
+    pub fn denominator(&self) -> &[OpArgument] {
+        &self.denominator
+    }
+
+    pub fn variable(&self) -> &'static str {
+        self.var
+    }
+
+    pub fn domain(&self) -> Domain {
+        self.domain
+    }
+
+    /// The transfer function as an expression in its variable.
+    pub fn expr(&self) -> OpArgument {
+        let x = variable(self.var);
+        let numerator = Series::new(self.numerator.clone()).to_polynomial(&x);
+        match &self.denominator[..] {
+            [c] if *c == one() => numerator,
+            denominator => numerator / Series::new(denominator.to_vec()).to_polynomial(&x),
+        }
+    }
+
+    fn compatible(&self, other: &TransferFunction) {
+        assert!(
+            self.var == other.var && self.domain == other.domain,
+            "Hold on, {:?} systems in {} and {:?} systems in {} cannot be connected",
+            self.domain,
+            self.var,
+            other.domain,
+            other.var
+        );
+    }
+
+    /// The system that feeds the output of `self` into `other`.
+    pub fn series(&self, other: &TransferFunction) -> TransferFunction {
+        self.compatible(other);
+        TransferFunction::new(
+            &mul(&self.numerator, &other.numerator),
+            &mul(&self.denominator, &other.denominator),
+            self.var,
+            self.domain,
+        )
+    }
+
+    /// The system that sums the outputs of `self` and `other` driven by the same input.
+    pub fn parallel(&self, other: &TransferFunction) -> TransferFunction {
+        self.compatible(other);
+        TransferFunction::new(
+            &add(
+                &mul(&self.numerator, &other.denominator),
+                &mul(&other.numerator, &self.denominator),
+            ),
+            &mul(&self.denominator, &other.denominator),
+            self.var,
+            self.domain,
+        )
+    }
+
+    /// The closed loop `G / (1 + G·H)` of `self` as `G`, with `sensor` as `H` subtracted from
+    /// the input.
+    pub fn feedback(&self, sensor: &TransferFunction) -> TransferFunction {
+        self.compatible(sensor);
+        TransferFunction::new(
+            &mul(&self.numerator, &sensor.denominator),
+            &add(
+                &mul(&self.denominator, &sensor.denominator),
+                &mul(&self.numerator, &sensor.numerator),
+            ),
+            self.var,
+            self.domain,
+        )
+    }
+
+    fn roots_of(coefficients: &[OpArgument]) -> Result<Vec<Root>, ControlError> {
+        let ratios = coefficients
+            .iter()
+            .enumerate()
+            .map(|(k, c)| Ratio::of(&c.fold()).ok_or(ControlError::IrrationalCoefficient(k)))
+            .collect::<Result<Vec<_>, _>>()?;
+        roots(&ratios).ok_or(ControlError::Unsolvable)
+    }
+
+    /// The roots of the denominator. Poles shared with the numerator are not cancelled.
+    pub fn poles(&self) -> Result<Vec<Root>, ControlError> {
+        TransferFunction::roots_of(&self.denominator)
+    }
+
+    /// The roots of the numerator.
+    pub fn zeros(&self) -> Result<Vec<Root>, ControlError> {
+        TransferFunction::roots_of(&self.numerator)
+    }
+
+    /// The gain at the angular frequency `omega`, at `s = iω` for continuous systems and at
+    /// `z = e^(iω)` for discrete ones, where `omega` is in radians per sample.
+    pub fn frequency_response(
+        &self,
+        omega: f64,
+        bindings: &Bindings,
+    ) -> Result<FrequencyResponse, ControlError> {
+        let x = match self.domain {
+            Domain::Continuous => (0.0, omega),
+            Domain::Discrete => (omega.cos(), omega.sin()),
+        };
+        let (a, b) = evaluate(&self.numerator, x, bindings)?;
+        let (c, d) = evaluate(&self.denominator, x, bindings)?;
+        let norm = c * c + d * d;
+        Ok(FrequencyResponse {
+            re: (a * c + b * d) / norm,
+            im: (b * c - a * d) / norm,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ControlError, Domain, Root, TransferFunction};
+    use crate::{evaluation::Bindings, parse::parse};
+
+    #[test]
+    fn test_transfer_functions() {
+        let p = |input: &str| parse(input).unwrap().fold();
+        let tf = |input: &str| TransferFunction::from_expr(&p(input), "s", Domain::Continuous);
+        let real = |value: &str, multiplicity| Root::Real {
+            value: p(value),
+            multiplicity,
+        };
+
+        let plant = tf("1 / (s * (s + 1))").unwrap();
+        assert_eq!(plant.numerator(), [p("1")]);
+        assert_eq!(plant.denominator(), [p("0"), p("1"), p("1")]);
+        assert_eq!(plant.poles().unwrap(), [real("0", 1), real("-1", 1)]);
+
+        // Unity feedback around 1/(s(s + 1)) gives 1/(s² + s + 1), with poles at e^(±2πi/3).
+        let closed = plant.feedback(&tf("1").unwrap());
+        assert_eq!(closed.denominator(), [p("1"), p("1"), p("1")]);
+        let [Root::Conjugate { modulus, angle, .. }] = &closed.poles().unwrap()[..] else {
+            panic!("s² + s + 1 has complex roots");
+        };
+        let none = Bindings::default();
+        assert!((modulus.evaluate(&none).unwrap() - 1.0).abs() < 1e-15);
+        assert!((angle.evaluate(&none).unwrap() - 2.0 * std::f64::consts::FRAC_PI_3).abs() < 1e-15);
+
+        let lead = tf("(s + 2) / (s + 3)^2").unwrap();
+        assert_eq!(lead.zeros().unwrap(), [real("-2", 1)]);
+        assert_eq!(lead.poles().unwrap(), [real("-3", 2)]);
+        let sum = tf("1 / (s + 1)")
+            .unwrap()
+            .parallel(&tf("1 / (s + 2)").unwrap());
+        assert_eq!(sum.numerator(), [p("3"), p("2")]);
+        assert_eq!(
+            plant
+                .series(&lead)
+                .expr()
+                .evaluate(&[("s", 1.0)].into_iter().collect()),
+            Ok(3.0 / 32.0)
+        );
+
+        // A first-order low pass k/(s + 1) is 3 dB down with a phase of -π/4 at ω = 1.
+        let low_pass = tf("k / (s + 1)").unwrap();
+        let response = low_pass
+            .frequency_response(1.0, &[("k", 1.0)].into_iter().collect())
+            .unwrap();
+        assert!((response.magnitude() - 0.5f64.sqrt()).abs() < 1e-15);
+        assert!((response.phase() + std::f64::consts::FRAC_PI_4).abs() < 1e-15);
+        assert_eq!(
+            low_pass.zeros(),
+            Err(ControlError::IrrationalCoefficient(0))
+        );
+
+        // The two-tap average (1 + z⁻¹)/2 = (z + 1)/(2z) cancels the Nyquist frequency.
+        let average =
+            TransferFunction::from_expr(&p("(z + 1) / (2 * z)"), "z", Domain::Discrete).unwrap();
+        let nyquist = average
+            .frequency_response(std::f64::consts::PI, &none)
+            .unwrap();
+        assert!(nyquist.magnitude() < 1e-15);
+        assert_eq!(tf("sin(s)"), Err(ControlError::NotRational(p("sin(s)"))));
+
+        // Unity feedback around 1/(s(s + 1)(s + 2)) gives s³ + 3s² + 2s + 1, which has no
+        // rational roots: one real pole, isolated exactly, and a complex pair.
+        let closed = tf("1 / (s * (s + 1) * (s + 2))")
+            .unwrap()
+            .feedback(&tf("1").unwrap());
+        let characteristic = |(re, im): (f64, f64)| {
+            let (a, b) = super::evaluate(closed.denominator(), (re, im), &none).unwrap();
+            a.hypot(b)
+        };
+        let poles = closed.poles().unwrap();
+        let [Root::Real { value, .. }, Root::Conjugate { modulus, angle, .. }] = &poles[..] else {
+            panic!("s³ + 3s² + 2s + 1 has one real root, got {:?}", poles);
+        };
+        assert!(characteristic((value.evaluate(&none).unwrap(), 0.0)) < 1e-14);
+        let (r, theta) = (
+            modulus.evaluate(&none).unwrap(),
+            angle.evaluate(&none).unwrap(),
+        );
+        assert!(characteristic((r * theta.cos(), r * theta.sin())) < 1e-14);
+        assert_eq!(
+            tf("1 / (s^4 + s + 1)").unwrap().poles(),
+            Err(ControlError::Unsolvable)
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! This module describes a corpus of expressions for benchmarks and tests.
+//!
+//! The built-in corpus, returned by [`iter`], covers the shapes that stress different parts of
+//! the crate: dense polynomials, rational functions, trigonometric identities and deeply
+//! nested compositions. Its expressions use the variables `x`, `y` and `z`. Corpora in the
+//! same line-based format, `category name: expression` with `#` comments, can be read with
+//! [`load`].
+
+use std::{fmt::Display, str::FromStr};
+
+use once_cell::sync::Lazy;
+
+use crate::{
+    parse::{parse, ParseError},
+    symbols::{intern, OpArgument},
+};
+
+/// The broad shape of an expression in a corpus.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Category {
+    Polynomial,
+    Rational,
+    Trigonometric,
+    Nested,
+}
+
+impl FromStr for Category {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "polynomial" => Ok(Category::Polynomial),
+            "rational" => Ok(Category::Rational),
+            "trigonometric" => Ok(Category::Trigonometric),
+            "nested" => Ok(Category::Nested),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A named expression of a corpus.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub name: &'static str,
+    pub category: Category,
+    pub expr: OpArgument,
+}
+
+/// The reasons reading a corpus can fail. Lines are numbered from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CorpusError {
+    /// The line is not of the form `category name: expression`.
+    Malformed(usize),
+    UnknownCategory(usize, String),
+    Parse(usize, ParseError),
+}
+
+impl Display for CorpusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CorpusError::Malformed(line) => write!(f, "line {} is not an entry", line),
+            CorpusError::UnknownCategory(line, category) => {
+                write!(f, "unknown category {} on line {}", category, line)
+            }
+            CorpusError::Parse(line, err) => write!(f, "{} on line {}", err, line),
+        }
+    }
+}
+
+impl std::error::Error for CorpusError {}
+
+/// Reads the entries of a corpus, skipping blank lines and `#` comments.
+pub fn load(text: &str) -> Result<Vec<Entry>, CorpusError> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line, text)| {
+            let (header, source) = text.split_once(':').ok_or(CorpusError::Malformed(line))?;
+            let (category, name) = header
+                .trim()
+                .split_once(char::is_whitespace)
+                .ok_or(CorpusError::Malformed(line))?;
+            let category = category
+                .parse()
+                .map_err(|()| CorpusError::UnknownCategory(line, category.to_owned()))?;
+            Ok(Entry {
+                name: intern(name.trim()),
+                category,
+                expr: parse(source).map_err(|err| CorpusError::Parse(line, err))?,
+            })
+        })
+        .collect()
+}
+
+static CORPUS: Lazy<Vec<Entry>> =
+    Lazy::new(|| load(include_str!("corpus.txt")).expect("Oops, the built-in corpus is broken"));
+
+/// The entries of the built-in corpus.
+pub fn iter() -> impl Iterator<Item = &'static Entry> {
+    CORPUS.iter()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{iter, load, Category, CorpusError};
+    use crate::{
+        parse::ParseError,
+        verify::{verify_derivative, VerifyError, VerifyOptions},
+    };
+
+    #[test]
+    fn test_corpus() {
+        for category in [
+            Category::Polynomial,
+            Category::Rational,
+            Category::Trigonometric,
+            Category::Nested,
+        ] {
+            assert!(iter().any(|entry| entry.category == category));
+        }
+
+        // Every expression of the corpus should differentiate correctly in every variable.
+        for entry in iter() {
+            for var in entry.expr.free_variables().iter() {
+                match verify_derivative(&entry.expr, var, VerifyOptions::default()) {
+                    Ok(()) | Err(VerifyError::NoValidPoints) => {}
+                    Err(err) => panic!("Uh-oh, d{}/d{}: {}", entry.name, var, err),
+                }
+            }
+        }
+
+        let entries = load("# comment\n\nrational half: x / 2\n").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            (entries[0].name, entries[0].category),
+            ("half", Category::Rational)
+        );
+        assert_eq!(load("x + 1").unwrap_err(), CorpusError::Malformed(1));
+        assert_eq!(
+            load("\nmatrix m: x").unwrap_err(),
+            CorpusError::UnknownCategory(2, "matrix".to_owned())
+        );
+        assert_eq!(
+            load("nested n: x +").unwrap_err(),
+            CorpusError::Parse(1, ParseError::UnexpectedEnd)
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! This module describes symbolic differentiation.
+//!
+//! Derivatives follow the chain rule through every operation, folding as they go and leaving
+//! out the terms of parts that do not depend on the variable, so the result stays close to the
+//! size of the input. Each derivative is recorded as a [`Step::Derivative`] for
+//! [`crate::provenance::explain`].
+
+use crate::{
+    constants::Value,
+    provenance::{record, Step},
+    series::{one, plus, times, zero},
+    symbols::{OpArgument, OpArgumentKind::Op, OperationKind::*},
+};
+
+fn negated(a: &OpArgument) -> OpArgument {
+    match *a == zero() {
+        true => zero(),
+        false => (-a).fold(),
+    }
+}
+
+fn over(a: &OpArgument, b: &OpArgument) -> OpArgument {
+    match (*a == zero(), *b == one()) {
+        (true, _) => zero(),
+        (_, true) => a.clone(),
+        _ => (a / b).fold(),
+    }
+}
+
+fn power(base: &OpArgument, exponent: &OpArgument) -> OpArgument {
+    match exponent {
+        e if *e == zero() => one(),
+        e if *e == one() => base.clone(),
+        e => base.pow(e),
+    }
+}
+
+fn derivative(node: &OpArgument, var: &str) -> OpArgument {
+    if !node.free_variables().contains(var) {
+        return zero();
+    }
+    let Op(op) = &node.value else {
+        // The variable itself.
+        return one();
+    };
+    let u = &op.arguments[0];
+    let du = || derivative(u, var);
+    match op.op {
+        Addition => plus(&du(), &derivative(&op.arguments[1], var)),
+        Subtraction => plus(&du(), &negated(&derivative(&op.arguments[1], var))),
+        Negation => negated(&du()),
+        Multiplication => {
+            let v = &op.arguments[1];
+            plus(&times(&du(), v), &times(u, &derivative(v, var)))
+        }
+        Division => {
+            // (u/v)' = u'/v - u·v'/v²
+            let v = &op.arguments[1];
+            let dv = derivative(v, var);
+            let quotient = over(&du(), v);
+            match dv == zero() {
+                true => quotient,
+                false => plus(
+                    &quotient,
+                    &negated(&over(&times(u, &dv), &v.pow(&Value::integer(2).into()))),
+                ),
+            }
+        }
+        Pow => {
+            let v = &op.arguments[1];
+            let dv = derivative(v, var);
+            // (uᵛ)' = v·u^(v - 1)·u' + uᵛ·ln(u)·v'
+            let base = match du() {
+                du if du == zero() => zero(),
+                du => times(&times(v, &power(u, &(v - one()).fold())), &du),
+            };
+            let exponent = match dv == zero() {
+                true => zero(),
+                false => times(&times(node, &u.ln()), &dv),
+            };
+            plus(&base, &exponent)
+        }
+        Exp => times(node, &du()),
+        Ln => over(&du(), u),
+        Sin => times(&u.cos(), &du()),
+        Cos => negated(&times(&u.sin(), &du())),
+        Tan => over(&du(), &u.cos().pow(&Value::integer(2).into())),
+        Atan => over(&du(), &plus(&one(), &u.pow(&Value::integer(2).into()))),
+        // The sign is constant on either side of zero, where it has no derivative.
+        Sign => zero(),
+    }
+}
+
+impl OpArgument {
+    /// The derivative of the expression with respect to `var`.
+    pub fn derivative(&self, var: &'static str) -> OpArgument {
+        let result = derivative(self, var);
+        record(|| Step::Derivative(var), self, &result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        evaluation::Bindings,
+        parse::parse,
+        provenance::{disable, enable, explain, Step},
+    };
+
+    #[test]
+    fn test_derivative() {
+        let p = |input: &str| parse(input).unwrap();
+        assert_eq!(p("3 * x^2 + y").derivative("x"), p("3 * (2 * x)").fold());
+        assert_eq!(p("y * z").derivative("x"), p("0").fold());
+        assert_eq!(p("exp(x)").derivative("x"), p("exp(x)"));
+
+        // Check the chain, product and quotient rules against central differences.
+        let exprs = [
+            "sin(x^2) * ln(x)",
+            "atan(x) / (1 + x)",
+            "x^x - tan(2 * x)",
+            "-cos(x) / exp(3 * x)",
+        ];
+        let at = |x: f64| Bindings::from_iter([("x", x)]);
+        for expr in exprs {
+            let (f, df) = (p(expr), p(expr).derivative("x"));
+            for x in [0.3, 0.7, 1.1] {
+                let h = 1e-6;
+                let numeric =
+                    (f.evaluate(&at(x + h)).unwrap() - f.evaluate(&at(x - h)).unwrap()) / (2.0 * h);
+                let exact = df.evaluate(&at(x)).unwrap();
+                assert!(
+                    (numeric - exact).abs() < 1e-6,
+                    "{} gave {} at {}, not {}",
+                    df,
+                    exact,
+                    x,
+                    numeric
+                );
+            }
+        }
+
+        enable();
+        let f = p("sin(x)");
+        let df = f.derivative("x");
+        let explanation = explain(&df);
+        disable();
+        assert_eq!(explanation.origin.unwrap().0, Step::Derivative("x"));
+    }
+}
@@ -0,0 +1,230 @@
+//! This module describes linear differential operators with expression coefficients.
+//!
+//! A [`DifferentialOperator`] is a sum `Σ cₐ·∂ᵃ` of partial derivatives `∂ᵃ`, each a multiset of
+//! variables to differentiate by, with coefficients that may depend on those variables. [`d`]
+//! is the derivative `D[x]`, and expressions are the operators that multiply by them, so
+//! `D[x]² + x·D[x] + 1` is built with the usual operators and applied with
+//! [`DifferentialOperator::apply`]. Multiplying operators composes them: coefficients are
+//! moved to the left with the product rule, so `D[x]·x` is `x·D[x] + 1`.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    ops::{Add, Mul, Neg, Sub},
+};
+
+use crate::{
+    series::{one, plus, times, zero},
+    symbols::OpArgument,
+};
+
+/// The variables of a partial derivative, sorted, with repeats for higher derivatives.
+type Derivative = Vec<&'static str>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DifferentialOperator {
+    /// Nonzero coefficients, by derivative.
+    terms: BTreeMap<Derivative, OpArgument>,
+}
+
+/// The derivative `D[var]`.
+pub fn d(var: &'static str) -> DifferentialOperator {
+    DifferentialOperator {
+        terms: BTreeMap::from([(vec![var], one())]),
+    }
+}
+
+impl From<OpArgument> for DifferentialOperator {
+    /// The operator that multiplies by `c`.
+    fn from(c: OpArgument) -> Self {
+        DifferentialOperator::zero().add_term(vec![], c.fold())
+    }
+}
+
+impl DifferentialOperator {
+    pub fn zero() -> Self {
+        DifferentialOperator {
+            terms: BTreeMap::new(),
+        }
+    }
+
+    pub fn identity() -> Self {
+        one().into()
+    }
+
+    fn add_term(mut self, derivative: Derivative, c: OpArgument) -> Self {
+        let sum = match self.terms.remove(&derivative) {
+            Some(previous) => plus(&previous, &c),
+            None => c,
+        };
+        if sum != zero() {
+            self.terms.insert(derivative, sum);
+        }
+        self
+    }
+
+    /// The coefficient of the partial derivative by `vars`, in any order.
+    pub fn coefficient(&self, vars: &[&'static str]) -> OpArgument {
+        let mut derivative = vars.to_vec();
+        derivative.sort_unstable();
+        self.terms.get(&derivative).cloned().unwrap_or_else(zero)
+    }
+
+    /// The highest order of a derivative, or `None` for the zero operator.
+    pub fn order(&self) -> Option<usize> {
+        self.terms.keys().map(Vec::len).max()
+    }
+
+    /// `self` composed with itself `k` times.
+    pub fn pow(&self, k: u32) -> DifferentialOperator {
+        (0..k).fold(DifferentialOperator::identity(), |acc, _| &acc * self)
+    }
+
+    /// Applies the operator to `f`.
+    pub fn apply(&self, f: &OpArgument) -> OpArgument {
+        self.terms
+            .iter()
+            .map(|(derivative, c)| {
+                let df = derivative
+                    .iter()
+                    .fold(f.clone(), |df, &var| df.derivative(var));
+                times(c, &df)
+            })
+            .fold(zero(), |sum, term| plus(&sum, &term))
+    }
+
+    /// `D[var]·self`, with the coefficients moved to the left by the product rule
+    /// `D[x]·(c·∂) = c'·∂ + c·D[x]·∂`.
+    fn differentiate(&self, var: &'static str) -> DifferentialOperator {
+        self.terms
+            .iter()
+            .fold(DifferentialOperator::zero(), |acc, (derivative, c)| {
+                let mut higher = derivative.clone();
+                let at = higher.partition_point(|&v| v <= var);
+                higher.insert(at, var);
+                acc.add_term(derivative.clone(), c.derivative(var))
+                    .add_term(higher, c.clone())
+            })
+    }
+}
+
+impl Add<&DifferentialOperator> for &DifferentialOperator {
+    type Output = DifferentialOperator;
+
+    fn add(self, rhs: &DifferentialOperator) -> DifferentialOperator {
+        rhs.terms.iter().fold(self.clone(), |acc, (derivative, c)| {
+            acc.add_term(derivative.clone(), c.clone())
+        })
+    }
+}
+
+impl Neg for &DifferentialOperator {
+    type Output = DifferentialOperator;
+
+    fn neg(self) -> DifferentialOperator {
+        DifferentialOperator {
+            terms: self
+                .terms
+                .iter()
+                .map(|(derivative, c)| (derivative.clone(), (-c).fold()))
+                .collect(),
+        }
+    }
+}
+
+impl Sub<&DifferentialOperator> for &DifferentialOperator {
+    type Output = DifferentialOperator;
+
+    fn sub(self, rhs: &DifferentialOperator) -> DifferentialOperator {
+        self + &-rhs
+    }
+}
+
+impl Mul<&DifferentialOperator> for &DifferentialOperator {
+    type Output = DifferentialOperator;
+
+    /// The composition, applying `rhs` first.
+    fn mul(self, rhs: &DifferentialOperator) -> DifferentialOperator {
+        self.terms
+            .iter()
+            .fold(DifferentialOperator::zero(), |acc, (derivative, c)| {
+                let inner = derivative
+                    .iter()
+                    .fold(rhs.clone(), |inner, &var| inner.differentiate(var));
+                inner.terms.into_iter().fold(acc, |acc, (derivative, e)| {
+                    acc.add_term(derivative, times(c, &e))
+                })
+            })
+    }
+}
+
+impl Display for DifferentialOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.terms.is_empty() {
+            return f.write_str("0");
+        }
+        // Highest order first, as operators are usually written.
+        let mut terms = self.terms.iter().collect::<Vec<_>>();
+        terms.sort_by_key(|(derivative, _)| std::cmp::Reverse(derivative.len()));
+        for (i, (derivative, c)) in terms.into_iter().enumerate() {
+            if i > 0 {
+                f.write_str(" + ")?;
+            }
+            let mut factors = Vec::new();
+            if *c != one() || derivative.is_empty() {
+                factors.push(format!("({})", c));
+            }
+            let mut rest = derivative.as_slice();
+            while let Some(&var) = rest.first() {
+                let k = rest.iter().take_while(|&&v| v == var).count();
+                factors.push(match k {
+                    1 => format!("D[{}]", var),
+                    k => format!("D[{}]^{}", var, k),
+                });
+                rest = &rest[k..];
+            }
+            f.write_str(&factors.join("*"))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{d, DifferentialOperator};
+    use crate::{evaluation::Bindings, parse::parse};
+
+    #[test]
+    fn test_differential_operators() {
+        let p = |input: &str| parse(input).unwrap();
+        let scalar = |input: &str| DifferentialOperator::from(p(input));
+        let dx = d("x");
+
+        // D[x]·x = x·D[x] + 1
+        let composed = &dx * &scalar("x");
+        assert_eq!(composed, &(&scalar("x") * &dx) + &scalar("1"));
+        assert_eq!(
+            (&dx.pow(2) + &(&scalar("y") * &(&dx * &d("y")))).to_string(),
+            "D[x]^2 + (y)*D[x]*D[y]"
+        );
+
+        // (D[x]² + x·D[x] + 1)(sin(x)) = x·cos(x)
+        let l = &(&dx.pow(2) + &(&scalar("x") * &dx)) + &DifferentialOperator::identity();
+        assert_eq!(l.order(), Some(2));
+        let at = Bindings::from_iter([("x", 0.8), ("y", -0.3)]);
+        let applied = l.apply(&p("sin(x)")).evaluate(&at).unwrap();
+        assert!((applied - 0.8 * 0.8_f64.cos()).abs() < 1e-12);
+
+        // Composing and then applying is applying twice.
+        let f = p("exp(x*y) + x^3");
+        let m = &(&scalar("y") * &dx) - &(&scalar("x^2") * &d("y"));
+        let once = (&l * &m).apply(&f).evaluate(&at).unwrap();
+        let twice = l.apply(&m.apply(&f)).evaluate(&at).unwrap();
+        assert!((once - twice).abs() < 1e-10 * twice.abs().max(1.0));
+
+        // Partial derivatives commute.
+        assert_eq!(&dx * &d("y"), &d("y") * &dx);
+        assert_eq!((&dx * &d("y")).coefficient(&["y", "x"]), p("1"));
+        assert_eq!(&dx - &dx, DifferentialOperator::zero());
+    }
+}
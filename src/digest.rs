@@ -0,0 +1,256 @@
+//! This module describes 128-bit structural digests of expressions.
+//!
+//! Expressions compare equal when their 64-bit hashes do, which is fast but leaves a small
+//! chance of two different expressions being mistaken for each other in a large workload.
+//! [`OpArgument::digest`] is a second, 128-bit fingerprint made of two SipHash-2-4 streams
+//! under independent fixed keys, mixing the opcode and arity of each node with the digests of
+//! its arguments. It is cached on every operation node like the hash, and with the `wide_hash`
+//! feature enabled it is what equality compares, so the hash-consed sets and maps of the
+//! equivalence layer only merge expressions whose digests agree.
+//!
+//! Every integer is fed to the hasher in little-endian order and the keys are constants, so a
+//! digest is the same in every run and on every platform.
+
+use std::hash::{Hash, Hasher};
+
+use crate::symbols::{
+    OpArgument,
+    OpArgumentKind::{Leaf, Op},
+};
+
+/// The keys of the two halves of a digest.
+const KEYS: [(u64, u64); 2] = [
+    (0x736f_6d65_7073_6575, 0x646f_7261_6e64_6f6d),
+    (0x6c79_6765_6e65_7261, 0x7465_6462_7974_6573),
+];
+
+/// A SipHash-2-4 hasher, which writes integers in little-endian order.
+#[derive(Clone, Debug)]
+pub(crate) struct SipHasher {
+    v: [u64; 4],
+    /// Bytes written since the last full word, in the low bytes.
+    tail: u64,
+    length: usize,
+}
+
+impl SipHasher {
+    pub(crate) fn new_with_keys(k0: u64, k1: u64) -> Self {
+        SipHasher {
+            v: [
+                k0 ^ 0x736f_6d65_7073_6575,
+                k1 ^ 0x646f_7261_6e64_6f6d,
+                k0 ^ 0x6c79_6765_6e65_7261,
+                k1 ^ 0x7465_6462_7974_6573,
+            ],
+            tail: 0,
+            length: 0,
+        }
+    }
+
+    fn round(&mut self) {
+        let [v0, v1, v2, v3] = &mut self.v;
+        *v0 = v0.wrapping_add(*v1);
+        *v1 = v1.rotate_left(13) ^ *v0;
+        *v0 = v0.rotate_left(32);
+        *v2 = v2.wrapping_add(*v3);
+        *v3 = v3.rotate_left(16) ^ *v2;
+        *v0 = v0.wrapping_add(*v3);
+        *v3 = v3.rotate_left(21) ^ *v0;
+        *v2 = v2.wrapping_add(*v1);
+        *v1 = v1.rotate_left(17) ^ *v2;
+        *v2 = v2.rotate_left(32);
+    }
+
+    fn compress(&mut self, word: u64) {
+        self.v[3] ^= word;
+        self.round();
+        self.round();
+        self.v[0] ^= word;
+    }
+}
+
+impl Hasher for SipHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.tail |= (byte as u64) << (8 * (self.length % 8));
+            self.length += 1;
+            if self.length.is_multiple_of(8) {
+                let word = std::mem::take(&mut self.tail);
+                self.compress(word);
+            }
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.write(&[i]);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        let mut state = self.clone();
+        state.compress(state.tail | (self.length as u64) << 56);
+        state.v[2] ^= 0xff;
+        (0..4).for_each(|_| state.round());
+        state.v.iter().fold(0, |acc, v| acc ^ v)
+    }
+}
+
+/// The digest of whatever `write` feeds the hasher.
+fn keyed(write: impl Fn(&mut SipHasher)) -> u128 {
+    let [low, high] = KEYS.map(|(k0, k1)| {
+        let mut hasher = SipHasher::new_with_keys(k0, k1);
+        write(&mut hasher);
+        hasher.finish()
+    });
+    (high as u128) << 64 | low as u128
+}
+
+impl OpArgument {
+    /// A 128-bit fingerprint of the structure of the expression, identical across runs and
+    /// platforms.
+    pub fn digest(&self) -> u128 {
+        match &self.value {
+            Op(op) => *op.digest.get_or_init(|| {
+                keyed(|hasher| {
+                    hasher.write_u8(1);
+                    hasher.write_u32(op.op.opcode());
+                    hasher.write_u64(op.arguments.len() as u64);
+                    for arg in &op.arguments {
+                        let digest = arg.digest();
+                        hasher.write_u64(digest as u64);
+                        hasher.write_u64((digest >> 64) as u64);
+                    }
+                })
+            }),
+            Leaf(value) => keyed(|hasher| {
+                hasher.write_u8(0);
+                value.hash(hasher);
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::Hasher;
+
+    use ahash::{HashMap, HashSet};
+    use smallvec::smallvec;
+
+    use super::SipHasher;
+    use crate::{
+        constants::Value,
+        random::Rng,
+        symbols::{
+            variable, OpArgument,
+            OpArgumentKind::{Leaf, Op},
+            Operation, OperationKind,
+        },
+    };
+
+    #[test]
+    fn test_siphash() {
+        // The test vector of the SipHash paper.
+        let k0 = u64::from_le_bytes([0, 1, 2, 3, 4, 5, 6, 7]);
+        let k1 = u64::from_le_bytes([8, 9, 10, 11, 12, 13, 14, 15]);
+        let mut hasher = SipHasher::new_with_keys(k0, k1);
+        hasher.write(&(0..15).collect::<Vec<u8>>());
+        assert_eq!(hasher.finish(), 0xa129_ca61_49be_45e5);
+
+        let x = variable("x");
+        assert_eq!((&x + &x).digest(), (variable("x") + variable("x")).digest());
+        assert_ne!((&x - &x).digest(), (&x + &x).digest());
+        assert_ne!(x.digest(), variable("y").digest());
+        // Pinned, since digests may be persisted.
+        assert_eq!(
+            (&x + OpArgument::from(Value::integer(1))).digest(),
+            0xfbab_4592_2c2d_073a_f013_39b0_adf4_46a2
+        );
+    }
+
+    /// A string that identifies the tree of `expr` exactly.
+    fn structure(expr: &OpArgument) -> String {
+        match &expr.value {
+            Op(op) => {
+                let args = op.arguments.iter().map(structure).collect::<Vec<_>>();
+                format!("({:?} {})", op.op, args.join(" "))
+            }
+            Leaf(value) => match **value {
+                Value::Rational(num, den) => format!("{}/{}", num, den),
+                value => value.to_string(),
+            },
+        }
+    }
+
+    fn random_expr(rng: &mut Rng, depth: u32) -> OpArgument {
+        use OperationKind::*;
+        let ops = [
+            Addition,
+            Subtraction,
+            Multiplication,
+            Division,
+            Pow,
+            Negation,
+            Exp,
+            Sin,
+            Cos,
+            Ln,
+        ];
+        let choice = rng.next_u64();
+        if depth == 0 || choice.is_multiple_of(4) {
+            return match choice / 4 % 3 {
+                0 => variable(["x", "y", "z"][(choice / 12 % 3) as usize]),
+                1 => Value::integer(choice / 12 % 8).into(),
+                _ => Value::rational(choice / 12 % 5, 1 + choice / 60 % 4).into(),
+            };
+        }
+        let op = ops[(choice / 4 % ops.len() as u64) as usize];
+        let arguments = (0..op.argcount())
+            .map(|_| random_expr(rng, depth - 1))
+            .collect();
+        Operation::new(op, arguments).into()
+    }
+
+    #[test]
+    fn test_collisions() {
+        // Many small, closely related trees: no two distinct ones may share a hash or digest.
+        let mut rng = Rng::new(935);
+        let mut hashes = HashMap::default();
+        let mut digests = HashMap::default();
+        let mut distinct = HashSet::default();
+        for _ in 0..50_000 {
+            let expr = random_expr(&mut rng, 5);
+            let key = structure(&expr);
+            let by_hash = hashes.entry(expr.hash()).or_insert_with(|| key.clone());
+            assert_eq!(*by_hash, key, "64-bit hash collision");
+            let by_digest = digests.entry(expr.digest()).or_insert_with(|| key.clone());
+            assert_eq!(*by_digest, key, "128-bit digest collision");
+            distinct.insert(key);
+        }
+        assert!(distinct.len() > 30_000);
+        assert_eq!(distinct.len(), digests.len());
+
+        // Long chains that only differ deep down.
+        let x = variable("x");
+        let chain = |leaf: OpArgument| {
+            (0..500).fold(leaf, |expr, _| {
+                Operation::new(OperationKind::Sin, smallvec![&expr + &x]).into()
+            })
+        };
+        let a = chain(Value::integer(1).into());
+        let b = chain(Value::integer(2).into());
+        assert_ne!(a.digest(), b.digest());
+        assert_eq!(a.digest(), chain(Value::integer(1).into()).digest());
+    }
+}
@@ -15,7 +15,6 @@ pub(crate) fn hash_oparg(val: &OpArgumentKind, hasher: &mut impl Hasher) {
 }
 
 fn hash_op(op: &Operation, hasher: &mut impl Hasher) {
-    dbg!(op);
     op.hash(hasher);
 }
 
@@ -23,6 +22,7 @@ fn hash_leaf(leaf: &Value, hasher: &mut impl Hasher) {
     leaf.hash(hasher);
 }
 
+#[allow(dead_code)]
 pub(crate) struct EquivalenceGraph<'a> {
     backing_graph: &'a OpArgument,
     eclasses: Vec<EquivalenceClass<'a>>,
@@ -37,11 +37,13 @@ impl<'a> From<&'a OpArgument> for EquivalenceGraph<'a> {
     }
 }
 
+#[allow(dead_code)]
 pub(crate) struct EquivalenceClass<'a> {
     graphset: HashSet<&'a OpArgument>,
 }
 
 impl<'a> From<&'a OpArgument> for EquivalenceClass<'a> {
+    #[allow(clippy::mutable_key_type)]
     fn from(oparg: &'a OpArgument) -> Self {
         let mut graphset = HashSet::new();
         graphset.insert(oparg);
@@ -49,3 +51,94 @@ impl<'a> From<&'a OpArgument> for EquivalenceClass<'a> {
         EquivalenceClass { graphset }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::hash::{Hash, Hasher};
+
+    use super::hash_oparg;
+    use crate::symbols::{variable, OpArgument};
+
+    /// A hasher that records every byte it is fed instead of mixing them.
+    #[derive(Default)]
+    struct Recorder {
+        bytes: Vec<u8>,
+        writes: usize,
+    }
+
+    impl Hasher for Recorder {
+        fn finish(&self) -> u64 {
+            0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            self.bytes.extend_from_slice(bytes);
+            self.writes += 1;
+        }
+    }
+
+    #[test]
+    fn test_hash_structure() {
+        let x = variable("x");
+        let y = variable("y");
+
+        assert_eq!((&x + &y).hash(), (variable("x") + variable("y")).hash());
+        assert_ne!((&x + &y).hash(), (&y + &x).hash());
+        assert_ne!((&x + &y).hash(), (&x * &y).hash());
+        assert_ne!(x.sin().hash(), x.cos().hash());
+        assert_ne!((-&x).hash(), x.hash());
+    }
+
+    #[test]
+    fn test_hash_deep_expression() {
+        // Hashing used to format every node it visited, which made deep expressions
+        // quadratic (and extremely noisy on stderr) to hash.
+        let x = variable("x");
+        let mut expr: OpArgument = x.sin();
+        let mut hashes = vec![expr.hash()];
+        for _ in 0..2000 {
+            expr = (&expr * &x).sin();
+            // Children are already hashed, so this only mixes their cached hashes.
+            hashes.push(expr.hash());
+        }
+
+        let hash = expr.hash();
+        assert_eq!(hashes.last(), Some(&hash));
+        hashes.sort_unstable();
+        hashes.dedup();
+        assert_eq!(hashes.len(), 2001);
+        assert_ne!((&expr + &x).hash(), hash);
+    }
+
+    #[test]
+    fn test_hash_writes() {
+        // Hashing must only feed the hasher the opcode, the arity and the children's cached
+        // hashes; any rendering of the nodes would show up here as extra bytes.
+        let x = variable("x");
+        let mut expr = x.sin();
+        for _ in 0..100 {
+            expr = (&expr * &x).sin();
+        }
+        let product = &expr * &x.cos();
+
+        let mut recorder = Recorder::default();
+        hash_oparg(&product.value, &mut recorder);
+        let mut expected = 3u32.to_ne_bytes().to_vec();
+        expected.extend(2u64.to_ne_bytes());
+        expected.extend(expr.hash().to_ne_bytes());
+        expected.extend(x.cos().hash().to_ne_bytes());
+        assert_eq!(recorder.bytes, expected);
+        assert_eq!(recorder.writes, 4);
+
+        let mut recorder = Recorder::default();
+        Hash::hash(&x, &mut recorder);
+        assert_eq!(recorder.bytes, x.hash().to_ne_bytes());
+
+        // Leaves hash their discriminant and raw contents.
+        let mut recorder = Recorder::default();
+        hash_oparg(&x.value, &mut recorder);
+        let mut expected = 5u32.to_ne_bytes().to_vec();
+        expected.extend(b"x");
+        assert_eq!(recorder.bytes, expected);
+    }
+}
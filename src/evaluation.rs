@@ -0,0 +1,428 @@
+//! This module describes how to numerically evaluate our computational graph.
+
+use std::{
+    fmt::Display,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+use std::sync::Arc;
+
+use ahash::HashMap;
+
+use crate::{
+    constants::Value,
+    instrument,
+    symbols::{OpArgument, OpArgumentKind, Operation, StackVec},
+};
+
+/// A map from variable names to the numeric values they take during evaluation.
+pub type Bindings<'a> = HashMap<&'a str, f64>;
+
+/// A floating point type that expressions can be numerically evaluated in.
+pub trait Scalar:
+    Copy
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    /// Rounds `value` to the nearest representable value.
+    fn from_f64(value: f64) -> Self;
+    fn powf(self, exponent: Self) -> Self;
+    fn exp(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn ln(self) -> Self;
+    fn atan(self) -> Self;
+    /// `-1`, `0` or `1`, with the sign of the value, and NaN for NaN.
+    fn sign(self) -> Self;
+}
+
+macro_rules! impl_scalar {
+    ($($t:ident),*) => {$(
+        impl Scalar for $t {
+            #[inline]
+            fn from_f64(value: f64) -> Self {
+                value as $t
+            }
+
+            #[inline]
+            fn powf(self, exponent: Self) -> Self {
+                $t::powf(self, exponent)
+            }
+
+            #[inline]
+            fn exp(self) -> Self {
+                $t::exp(self)
+            }
+
+            #[inline]
+            fn sin(self) -> Self {
+                $t::sin(self)
+            }
+
+            #[inline]
+            fn cos(self) -> Self {
+                $t::cos(self)
+            }
+
+            #[inline]
+            fn tan(self) -> Self {
+                $t::tan(self)
+            }
+
+            #[inline]
+            fn ln(self) -> Self {
+                $t::ln(self)
+            }
+
+            #[inline]
+            fn atan(self) -> Self {
+                $t::atan(self)
+            }
+
+            #[inline]
+            fn sign(self) -> Self {
+                match self == 0.0 {
+                    true => 0.0,
+                    false => self.signum(),
+                }
+            }
+        }
+    )*};
+}
+
+impl_scalar!(f32, f64);
+
+/// The reasons evaluating an [`OpArgument`] can fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EvaluationError {
+    /// The expression references a variable with no binding.
+    Unbound(&'static str),
+    /// The expression contains a constant with no real-valued representation.
+    NotReal(Value),
+    /// A bound of the sum or product over this index is not an integer an `f64` index can
+    /// count to, that is one of magnitude at most `2⁵³`.
+    InvalidBound(&'static str),
+}
+
+impl Display for EvaluationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvaluationError::Unbound(name) => write!(f, "variable {} has no binding", name),
+            EvaluationError::NotReal(value) => write!(f, "constant {} is not real", value),
+            EvaluationError::InvalidBound(index) => {
+                write!(f, "the bounds over {} are not integers within 2^53", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EvaluationError {}
+
+impl Value {
+    /// Evaluates a single leaf, looking up variables in `bindings`.
+    pub fn evaluate(&self, bindings: &Bindings) -> Result<f64, EvaluationError> {
+        match *self {
+            Value::Rational(num, den) => Ok(num as f64 / den.get() as f64),
+            Value::Pi => Ok(std::f64::consts::PI),
+            Value::E => Ok(std::f64::consts::E),
+            Value::Inf => Ok(f64::INFINITY),
+            Value::Undefined => Ok(f64::NAN),
+            Value::Algebraic(number) => Ok(number.approximate()),
+            Value::I => Err(EvaluationError::NotReal(*self)),
+            Value::Variable(name) => bindings
+                .get(name)
+                .copied()
+                .ok_or(EvaluationError::Unbound(name)),
+        }
+    }
+}
+
+impl OpArgument {
+    /// Evaluates the expression with the variables given in `bindings`.
+    pub fn evaluate(&self, bindings: &Bindings) -> Result<f64, EvaluationError> {
+        instrument::node_evaluated();
+        match &self.value {
+            OpArgumentKind::Op(op) => {
+                let args = op
+                    .arguments
+                    .iter()
+                    .map(|arg| arg.evaluate(bindings))
+                    .collect::<Result<StackVec<f64>, _>>()?;
+                Ok(op.op.eval(&args))
+            }
+            OpArgumentKind::Leaf(leaf) => leaf.evaluate(bindings),
+        }
+    }
+}
+
+impl OpArgument {
+    /// Evaluates the expression once per row of the equally long input `columns`, each named
+    /// by the variable it binds.
+    pub fn evaluate_batch(&self, columns: &[(&str, &[f64])]) -> Result<Vec<f64>, EvaluationError> {
+        let rows = columns.first().map_or(0, |(_, column)| column.len());
+        assert!(
+            columns.iter().all(|(_, column)| column.len() == rows),
+            "Hold on, every column passed to OpArgument::evaluate_batch needs the same length"
+        );
+
+        let mut bindings = Bindings::default();
+        (0..rows)
+            .map(|row| {
+                for (name, column) in columns {
+                    bindings.insert(name, column[row]);
+                }
+                self.evaluate(&bindings)
+            })
+            .collect()
+    }
+}
+
+/// The first node of an expression found evaluating to NaN or an infinity from finite
+/// arguments, as reported by [`OpArgument::evaluate_checked`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Traceback {
+    pub node: OpArgument,
+    /// The values of the arguments of the node, which are all finite.
+    pub arguments: Vec<f64>,
+    pub value: f64,
+    /// The index of the argument followed at each step from the root down to the node.
+    pub path: Vec<usize>,
+}
+
+impl Display for Traceback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} evaluated to {} from the arguments {:?} at the path {:?}",
+            self.node, self.value, self.arguments, self.path
+        )
+    }
+}
+
+/// The reasons [`OpArgument::evaluate_checked`] can fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CheckedError {
+    Evaluation(EvaluationError),
+    NonFinite(Box<Traceback>),
+}
+
+impl Display for CheckedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckedError::Evaluation(err) => err.fmt(f),
+            CheckedError::NonFinite(traceback) => traceback.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for CheckedError {}
+
+impl From<EvaluationError> for CheckedError {
+    fn from(err: EvaluationError) -> Self {
+        CheckedError::Evaluation(err)
+    }
+}
+
+fn evaluate_checked(
+    node: &OpArgument,
+    bindings: &Bindings,
+    path: &mut Vec<usize>,
+) -> Result<f64, CheckedError> {
+    let (value, arguments) = match &node.value {
+        OpArgumentKind::Op(op) => {
+            let mut args = Vec::with_capacity(op.arguments.len());
+            for (i, arg) in op.arguments.iter().enumerate() {
+                path.push(i);
+                args.push(evaluate_checked(arg, bindings, path)?);
+                path.pop();
+            }
+            (op.op.eval(&args), args)
+        }
+        OpArgumentKind::Leaf(leaf) => (leaf.evaluate(bindings)?, Vec::new()),
+    };
+    if !value.is_finite() {
+        return Err(CheckedError::NonFinite(Box::new(Traceback {
+            node: node.clone(),
+            arguments,
+            value,
+            path: path.clone(),
+        })));
+    }
+    Ok(value)
+}
+
+impl OpArgument {
+    /// Evaluates the expression like [`OpArgument::evaluate`], but stops at the first node
+    /// whose value is NaN or infinite and reports it. This is slower, and meant for finding
+    /// where a non-finite result came from.
+    pub fn evaluate_checked(&self, bindings: &Bindings) -> Result<f64, CheckedError> {
+        evaluate_checked(self, bindings, &mut Vec::new())
+    }
+}
+
+/// Evaluates one expression repeatedly under changing bindings, caching the value of every
+/// operation node and recomputing only the nodes that depend on a changed variable.
+pub struct EvalSession<'e> {
+    expr: &'e OpArgument,
+    bindings: Bindings<'e>,
+    cache: HashMap<*const Operation, (&'e Operation, f64)>,
+}
+
+impl<'e> EvalSession<'e> {
+    pub fn new(expr: &'e OpArgument) -> Self {
+        EvalSession {
+            expr,
+            bindings: Bindings::default(),
+            cache: HashMap::default(),
+        }
+    }
+
+    pub fn bindings(&self) -> &Bindings<'e> {
+        &self.bindings
+    }
+
+    /// Binds `name` to `value`, invalidating the cached values that depend on it.
+    pub fn set(&mut self, name: &'e str, value: f64) {
+        let previous = self.bindings.insert(name, value);
+        if previous.is_none_or(|prev| prev.to_bits() != value.to_bits()) {
+            self.cache
+                .retain(|_, (op, _)| !op.metadata().free_variables.contains(name));
+        }
+    }
+
+    /// The number of operation nodes whose values are currently cached.
+    pub fn cached_nodes(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn evaluate(&mut self) -> Result<f64, EvaluationError> {
+        let expr = self.expr;
+        self.evaluate_node(expr)
+    }
+
+    fn evaluate_node(&mut self, node: &'e OpArgument) -> Result<f64, EvaluationError> {
+        let op = match &node.value {
+            OpArgumentKind::Op(op) => op,
+            OpArgumentKind::Leaf(leaf) => return leaf.evaluate(&self.bindings),
+        };
+
+        let key = Arc::as_ptr(op);
+        if let Some(&(_, value)) = self.cache.get(&key) {
+            return Ok(value);
+        }
+
+        instrument::node_evaluated();
+        let args = op
+            .arguments
+            .iter()
+            .map(|arg| self.evaluate_node(arg))
+            .collect::<Result<StackVec<f64>, _>>()?;
+        let value = op.op.eval(&args);
+        self.cache.insert(key, (op, value));
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU64;
+
+    use super::{Bindings, CheckedError, EvalSession, EvaluationError};
+    use crate::{
+        constants::Value,
+        symbols::{variable, OpArgument},
+    };
+
+    #[test]
+    fn test_evaluate() {
+        let x = variable("x");
+        let y = variable("y");
+        let half: OpArgument = Value::Rational(1, NonZeroU64::new(2).unwrap()).into();
+        let expr = (&x * &y).sin() + x.pow(&half);
+
+        let mut bindings = Bindings::default();
+        bindings.insert("x", 4.0);
+        assert_eq!(expr.evaluate(&bindings), Err(EvaluationError::Unbound("y")));
+
+        bindings.insert("y", 0.5);
+        assert_eq!(expr.evaluate(&bindings), Ok(2f64.sin() + 2.0));
+
+        let i: OpArgument = Value::I.into();
+        assert_eq!(
+            (x + i).evaluate(&bindings),
+            Err(EvaluationError::NotReal(Value::I))
+        );
+    }
+
+    #[test]
+    fn test_evaluate_batch() {
+        let x = variable("x");
+        let y = variable("y");
+        let expr = &x * &y - x;
+
+        let xs = [1.0, 2.0, 3.0];
+        let ys = [4.0, 5.0, 6.0];
+        assert_eq!(
+            expr.evaluate_batch(&[("x", &xs), ("y", &ys)]),
+            Ok(vec![3.0, 8.0, 15.0])
+        );
+        assert_eq!(expr.evaluate_batch(&[]), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_evaluate_checked() {
+        let x = variable("x");
+        let y = variable("y");
+        let inner = (&x - &y).ln();
+        let expr = x.sin() + &inner * y.exp();
+
+        let bindings = Bindings::from_iter([("x", 1.0), ("y", 2.0)]);
+        assert!(expr.evaluate(&bindings).unwrap().is_nan());
+        let Err(CheckedError::NonFinite(traceback)) = expr.evaluate_checked(&bindings) else {
+            panic!("Oops, ln(-1) should have been caught");
+        };
+        assert_eq!(traceback.node, inner);
+        assert_eq!(traceback.arguments, [-1.0]);
+        assert_eq!(traceback.path, [1, 0]);
+        assert!(traceback.value.is_nan());
+
+        let bindings = Bindings::from_iter([("x", 3.0), ("y", 2.0)]);
+        assert_eq!(
+            expr.evaluate_checked(&bindings),
+            expr.evaluate(&bindings).map_err(Into::into)
+        );
+        assert_eq!(
+            expr.evaluate_checked(&Bindings::from_iter([("x", 3.0)])),
+            Err(CheckedError::Evaluation(EvaluationError::Unbound("y")))
+        );
+    }
+
+    #[test]
+    fn test_eval_session() {
+        let x = variable("x");
+        let y = variable("y");
+        let expr = x.sin() * y.cos() + x.exp();
+
+        let mut session = EvalSession::new(&expr);
+        session.set("x", 1.0);
+        assert_eq!(session.evaluate(), Err(EvaluationError::Unbound("y")));
+
+        session.set("y", 2.0);
+        assert_eq!(session.evaluate(), Ok(1f64.sin() * 2f64.cos() + 1f64.exp()));
+        assert_eq!(session.cached_nodes(), 5);
+
+        // Only `cos(y)` and its ancestors depend on `y`.
+        session.set("y", 3.0);
+        assert_eq!(session.cached_nodes(), 2);
+        assert_eq!(session.evaluate(), expr.evaluate(session.bindings()));
+        assert_eq!(session.cached_nodes(), 5);
+
+        // Rebinding to the same value keeps everything.
+        session.set("x", 1.0);
+        assert_eq!(session.cached_nodes(), 5);
+    }
+}
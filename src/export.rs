@@ -0,0 +1,499 @@
+//! This module describes how to export numeric samples of our computational graph, and the
+//! graph itself for web pages.
+//!
+//! [`write_csv`] writes the samples as text, and with the `parquet` feature, `write_parquet`
+//! writes them as a Parquet file with a `DOUBLE` column per name, which pandas and Polars read
+//! with their types intact.
+//!
+//! [`write_html`] emits a snippet for dashboards: the math between `\[` and `\]`, which
+//! KaTeX's auto-render finds, and a `<script type="application/json">` holding [`to_json`],
+//! for scripts that want the expression itself.
+//!
+//! [`write_graphml`] writes the graph as GraphML for Gephi, NetworkX and the like, with each
+//! shared subexpression a single node, so the sharing shows up as nodes with several parents.
+
+use std::{
+    fmt::{Display, Write as _},
+    io::{self, Write},
+};
+
+use ahash::HashMap;
+
+use crate::{
+    constants::Value,
+    evaluation::{Bindings, EvaluationError},
+    json::{kind_name, string},
+    symbols::{
+        OpArgument,
+        OpArgumentKind::{Leaf, Op},
+    },
+};
+
+/// The reasons exporting sampled data can fail.
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Evaluation(EvaluationError),
+    #[cfg(feature = "parquet")]
+    Parquet(parquet::errors::ParquetError),
+}
+
+impl Display for ExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportError::Io(err) => Display::fmt(err, f),
+            ExportError::Evaluation(err) => Display::fmt(err, f),
+            #[cfg(feature = "parquet")]
+            ExportError::Parquet(err) => Display::fmt(err, f),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<io::Error> for ExportError {
+    fn from(err: io::Error) -> Self {
+        ExportError::Io(err)
+    }
+}
+
+impl From<EvaluationError> for ExportError {
+    fn from(err: EvaluationError) -> Self {
+        ExportError::Evaluation(err)
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl From<parquet::errors::ParquetError> for ExportError {
+    fn from(err: parquet::errors::ParquetError) -> Self {
+        ExportError::Parquet(err)
+    }
+}
+
+/// A rectangular grid of sample points, formed by the Cartesian product of its axes.
+#[derive(Clone, Debug, Default)]
+pub struct Grid<'a> {
+    axes: Vec<(&'a str, Vec<f64>)>,
+}
+
+impl<'a> Grid<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an axis sampling the variable `name` at the given points.
+    pub fn axis(mut self, name: &'a str, samples: impl IntoIterator<Item = f64>) -> Self {
+        self.axes.push((name, samples.into_iter().collect()));
+        self
+    }
+
+    /// Adds an axis sampling the variable `name` at `count` evenly spaced points from `start`
+    /// to `end` inclusive.
+    pub fn linspace(self, name: &'a str, start: f64, end: f64, count: usize) -> Self {
+        let step = if count > 1 {
+            (end - start) / (count - 1) as f64
+        } else {
+            0.0
+        };
+        self.axis(name, (0..count).map(|i| start + step * i as f64))
+    }
+
+    /// The number of points in the grid.
+    pub fn len(&self) -> usize {
+        if self.axes.is_empty() {
+            return 0;
+        }
+        self.axes.iter().map(|(_, samples)| samples.len()).product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Calls `f` with the bindings of every point in the grid, with the last axis varying
+    /// fastest.
+    pub fn try_for_each<E>(
+        &self,
+        mut f: impl FnMut(&Bindings<'a>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut bindings = Bindings::default();
+        let mut indices = vec![0usize; self.axes.len()];
+
+        for _ in 0..self.len() {
+            for ((name, samples), &i) in self.axes.iter().zip(&indices) {
+                bindings.insert(name, samples[i]);
+            }
+            f(&bindings)?;
+
+            for (axis, i) in indices.iter_mut().enumerate().rev() {
+                *i += 1;
+                if *i < self.axes[axis].1.len() {
+                    break;
+                }
+                *i = 0;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_csv_field(writer: &mut impl Write, field: &str) -> io::Result<()> {
+    if field.contains([',', '"', '\n', '\r']) {
+        write!(writer, "\"{}\"", field.replace('"', "\"\""))
+    } else {
+        writer.write_all(field.as_bytes())
+    }
+}
+
+/// Evaluates each named expression at every point of `grid` and writes one CSV row per point.
+///
+/// The header holds the grid's axis names followed by the expression names.
+pub fn write_csv(
+    writer: &mut impl Write,
+    grid: &Grid,
+    columns: &[(&str, &OpArgument)],
+) -> Result<(), ExportError> {
+    let names = grid
+        .axes
+        .iter()
+        .map(|(name, _)| *name)
+        .chain(columns.iter().map(|(name, _)| *name));
+    for (i, name) in names.enumerate() {
+        if i > 0 {
+            writer.write_all(b",")?;
+        }
+        write_csv_field(writer, name)?;
+    }
+    writer.write_all(b"\n")?;
+
+    grid.try_for_each(|bindings| {
+        let inputs = grid.axes.iter().map(|(name, _)| Ok(bindings[name]));
+        let outputs = columns.iter().map(|(_, expr)| expr.evaluate(bindings));
+        for (i, value) in inputs.chain(outputs).enumerate() {
+            if i > 0 {
+                writer.write_all(b",")?;
+            }
+            write!(writer, "{}", value?)?;
+        }
+        writer.write_all(b"\n")?;
+        Ok(())
+    })
+}
+
+/// Evaluates each named expression at every point of `grid` and writes a Parquet file with one
+/// required `DOUBLE` column per axis and expression, in that order, as a single row group.
+#[cfg(feature = "parquet")]
+pub fn write_parquet<W: Write + Send>(
+    writer: W,
+    grid: &Grid,
+    columns: &[(&str, &OpArgument)],
+) -> Result<(), ExportError> {
+    use std::sync::Arc;
+
+    use parquet::{
+        basic::{Repetition, Type as PhysicalType},
+        data_type::DoubleType,
+        file::{properties::WriterProperties, writer::SerializedFileWriter},
+        schema::types::Type,
+    };
+
+    let names = grid
+        .axes
+        .iter()
+        .map(|(name, _)| *name)
+        .chain(columns.iter().map(|(name, _)| *name));
+    let fields = names
+        .map(|name| {
+            Type::primitive_type_builder(name, PhysicalType::DOUBLE)
+                .with_repetition(Repetition::REQUIRED)
+                .build()
+                .map(Arc::new)
+        })
+        .collect::<Result<_, _>>()?;
+    let schema = Type::group_type_builder("schema")
+        .with_fields(fields)
+        .build()?;
+
+    let mut data = vec![Vec::with_capacity(grid.len()); grid.axes.len() + columns.len()];
+    grid.try_for_each(|bindings| {
+        let inputs = grid.axes.iter().map(|(name, _)| Ok(bindings[name]));
+        let outputs = columns.iter().map(|(_, expr)| expr.evaluate(bindings));
+        for (column, value) in data.iter_mut().zip(inputs.chain(outputs)) {
+            column.push(value?);
+        }
+        Ok::<_, ExportError>(())
+    })?;
+
+    let properties = Arc::new(WriterProperties::builder().build());
+    let mut file = SerializedFileWriter::new(writer, Arc::new(schema), properties)?;
+    let mut row_group = file.next_row_group()?;
+    for values in &data {
+        let mut column = row_group
+            .next_column()?
+            .expect("Oops, the schema should have a column for every name");
+        column
+            .typed::<DoubleType>()
+            .write_batch(values, None, None)?;
+        column.close()?;
+    }
+    row_group.close()?;
+    file.close()?;
+    Ok(())
+}
+
+/// `expr` as a JSON object with its plain `text`, its `latex` and its `expression`, in the
+/// format of [`crate::json`].
+pub fn to_json(expr: &OpArgument) -> String {
+    format!(
+        "{{\"text\":{},\"latex\":{},\"expression\":{}}}",
+        string(&expr.to_string()),
+        string(&expr.to_latex()),
+        expr.to_json()
+    )
+}
+
+/// Writes an HTML snippet showing `expr` as display math for KaTeX, with [`to_json`] of it
+/// alongside.
+pub fn write_html(writer: &mut impl Write, expr: &OpArgument) -> io::Result<()> {
+    let latex = expr
+        .to_latex()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    writeln!(writer, "<div class=\"symbolica\">")?;
+    writeln!(
+        writer,
+        "<div class=\"symbolica-math\">\\[{}\\]</div>",
+        latex
+    )?;
+    writeln!(
+        writer,
+        "<script type=\"application/json\" class=\"symbolica-expression\">{}</script>",
+        to_json(expr)
+    )?;
+    writeln!(writer, "</div>")
+}
+
+/// `text` with the characters XML reserves escaped.
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The nodes and edges of a graph, each shared subexpression once.
+#[derive(Default)]
+struct GraphMl {
+    ids: HashMap<u64, usize>,
+    nodes: String,
+    edges: String,
+}
+
+impl GraphMl {
+    /// Adds `expr` after its arguments, returning its id.
+    fn node(&mut self, expr: &OpArgument) -> usize {
+        if let Some(&id) = self.ids.get(&expr.hash()) {
+            return id;
+        }
+        let (kind, value) = match &expr.value {
+            Leaf(value) => match **value {
+                Value::Rational(..) => ("rational", value.to_string()),
+                Value::Variable(_) => ("variable", value.to_string()),
+                Value::Algebraic(_) => ("algebraic", value.to_string()),
+                _ => ("constant", value.to_string()),
+            },
+            Op(op) => (kind_name(op.op), String::new()),
+        };
+        let children = match &expr.value {
+            Op(op) => op.arguments.iter().map(|arg| self.node(arg)).collect(),
+            Leaf(_) => Vec::new(),
+        };
+        let id = self.ids.len();
+        self.ids.insert(expr.hash(), id);
+        let _ = write!(
+            self.nodes,
+            "    <node id=\"n{}\"><data key=\"kind\">{}</data><data key=\"hash\">{:016x}</data>",
+            id,
+            kind,
+            expr.hash()
+        );
+        if !value.is_empty() {
+            let _ = write!(
+                self.nodes,
+                "<data key=\"value\">{}</data>",
+                xml_escape(&value)
+            );
+        }
+        self.nodes.push_str("</node>\n");
+        for (position, child) in children.into_iter().enumerate() {
+            let _ = writeln!(
+                self.edges,
+                "    <edge source=\"n{}\" target=\"n{}\"><data key=\"position\">{}</data></edge>",
+                id, child, position
+            );
+        }
+        id
+    }
+}
+
+/// Writes `expr` as a GraphML document. Every distinct subexpression is one node, with its
+/// `kind`, its `hash` in hexadecimal and, for leaves, its `value`; edges go from operations to
+/// their arguments, with the argument's `position`.
+pub fn write_graphml(writer: &mut impl Write, expr: &OpArgument) -> io::Result<()> {
+    let mut graph = GraphMl::default();
+    graph.node(expr);
+    writeln!(writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        writer,
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">"
+    )?;
+    for (key, domain, kind) in [
+        ("kind", "node", "string"),
+        ("hash", "node", "string"),
+        ("value", "node", "string"),
+        ("position", "edge", "int"),
+    ] {
+        writeln!(
+            writer,
+            "  <key id=\"{0}\" for=\"{1}\" attr.name=\"{0}\" attr.type=\"{2}\"/>",
+            key, domain, kind
+        )?;
+    }
+    writeln!(
+        writer,
+        "  <graph id=\"expression\" edgedefault=\"directed\">"
+    )?;
+    writer.write_all(graph.nodes.as_bytes())?;
+    writer.write_all(graph.edges.as_bytes())?;
+    writeln!(writer, "  </graph>\n</graphml>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_json, write_csv, write_graphml, write_html, ExportError, Grid};
+    use crate::{evaluation::EvaluationError, parse::parse, symbols::variable};
+
+    #[test]
+    fn test_write_csv() {
+        let x = variable("x");
+        let y = variable("y");
+        let sum = &x + &y;
+        let product = &x * &y;
+        let grid = Grid::new().axis("x", [1.0, 2.0]).linspace("y", 0.0, 1.0, 3);
+        assert_eq!(grid.len(), 6);
+
+        let mut out = Vec::new();
+        write_csv(&mut out, &grid, &[("x+y", &sum), ("x*y", &product)]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "x,y,x+y,x*y\n\
+             1,0,1,0\n\
+             1,0.5,1.5,0.5\n\
+             1,1,2,1\n\
+             2,0,2,0\n\
+             2,0.5,2.5,1\n\
+             2,1,3,2\n"
+        );
+
+        let z = variable("z");
+        let mut out = Vec::new();
+        assert!(matches!(
+            write_csv(&mut out, &grid, &[("z", &z)]),
+            Err(ExportError::Evaluation(EvaluationError::Unbound("z")))
+        ));
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_write_parquet() {
+        use parquet::{
+            file::reader::{FileReader, SerializedFileReader},
+            record::RowAccessor,
+        };
+
+        let x = variable("x");
+        let y = variable("y");
+        let grid = Grid::new().axis("x", [1.0, 2.0]).linspace("y", 0.0, 1.0, 3);
+        let path = std::env::temp_dir().join(format!("symbolica-{}.parquet", std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        super::write_parquet(file, &grid, &[("x*y", &(&x * &y))]).unwrap();
+
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let schema = reader.metadata().file_metadata().schema_descr();
+        let names = schema
+            .columns()
+            .iter()
+            .map(|c| c.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["x", "y", "x*y"]);
+        let rows = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| {
+                let row = row.unwrap();
+                (0..3)
+                    .map(|i| row.get_double(i).unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[4], [2.0, 0.5, 1.0]);
+
+        let z = variable("z");
+        assert!(matches!(
+            super::write_parquet(Vec::new(), &grid, &[("z", &z)]),
+            Err(ExportError::Evaluation(EvaluationError::Unbound("z")))
+        ));
+    }
+
+    #[test]
+    fn test_write_graphml() {
+        let expr = parse("sin(x) * sin(x) + x").unwrap();
+        let mut out = Vec::new();
+        write_graphml(&mut out, &expr).unwrap();
+        let graphml = String::from_utf8(out).unwrap();
+
+        // x, sin(x), the product and the sum, with x and sin(x) shared.
+        assert_eq!(graphml.matches("<node ").count(), 4);
+        assert_eq!(graphml.matches("<edge ").count(), 5);
+        assert!(graphml.contains(&format!(
+            "<node id=\"n0\"><data key=\"kind\">variable</data><data key=\"hash\">{:016x}</data><data key=\"value\">x</data></node>",
+            variable("x").hash()
+        )));
+        assert!(graphml.contains("<node id=\"n1\"><data key=\"kind\">sin</data>"));
+        assert!(graphml
+            .contains("<edge source=\"n2\" target=\"n1\"><data key=\"position\">1</data></edge>"));
+        assert!(graphml
+            .contains("<edge source=\"n3\" target=\"n0\"><data key=\"position\">1</data></edge>"));
+        assert!(graphml.ends_with("  </graph>\n</graphml>\n"));
+    }
+
+    #[test]
+    fn test_write_html() {
+        let expr = parse("sin(x) / 2 + -y").unwrap();
+        assert_eq!(
+            to_json(&expr),
+            r#"{"text":"sin(x)/2/1+-y","latex":"\\frac{\\sin\\left(x\\right)}{2} + -y","expression":{"kind":"add","args":[{"kind":"div","args":[{"kind":"sin","args":[{"kind":"variable","name":"x"}]},{"kind":"rational","value":"2"}]},{"kind":"neg","args":[{"kind":"variable","name":"y"}]}]}}"#
+        );
+
+        let mut out = Vec::new();
+        write_html(&mut out, &variable("a<b")).unwrap();
+        let html = String::from_utf8(out).unwrap();
+        assert!(html.starts_with(
+            "<div class=\"symbolica\">\n<div class=\"symbolica-math\">\\[\\mathrm{a&lt;b}\\]</div>"
+        ));
+        assert!(html.contains(
+            r#"<script type="application/json" class="symbolica-expression">{"text":"a\u003cb""#
+        ));
+
+        // Nothing in a name can close the script element early.
+        let mut out = Vec::new();
+        write_html(&mut out, &variable("</script>")).unwrap();
+        let html = String::from_utf8(out).unwrap();
+        assert_eq!(html.matches("</script>").count(), 1);
+        assert!(html.contains("\\u003c/script>"));
+    }
+}
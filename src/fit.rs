@@ -0,0 +1,256 @@
+//! This module describes how to fit the parameters of a model expression to data.
+//!
+//! [`least_squares`] minimizes the sum of squared residuals with the Levenberg–Marquardt
+//! method. The Jacobian of the model with respect to its parameters is derived symbolically and
+//! compiled together with the model into one program, so every iteration evaluates the
+//! residuals and their derivatives at each sample in a single pass with shared
+//! subexpressions.
+
+use std::fmt::Display;
+
+use crate::{
+    approx::solve_linear, codegen::CodegenError, compile::compile_many, symbols::OpArgument,
+};
+
+/// The damping is scaled by this factor after every rejected step, and divided by it after
+/// every accepted one.
+const DAMPING_FACTOR: f64 = 10.0;
+
+/// The reasons a fit can fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FitError {
+    Codegen(CodegenError),
+    /// The model is not finite at the initial parameters.
+    NotFinite,
+    /// There are fewer samples than parameters.
+    Underdetermined {
+        samples: usize,
+        params: usize,
+    },
+}
+
+impl Display for FitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FitError::Codegen(err) => err.fmt(f),
+            FitError::NotFinite => f.write_str("the model is not finite at the initial guess"),
+            FitError::Underdetermined { samples, params } => write!(
+                f,
+                "{} samples cannot determine {} parameters",
+                samples, params
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FitError {}
+
+impl From<CodegenError> for FitError {
+    fn from(err: CodegenError) -> Self {
+        FitError::Codegen(err)
+    }
+}
+
+/// The parameters found by a fit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fit {
+    pub params: Vec<(&'static str, f64)>,
+    /// The sum of squared residuals at the fitted parameters.
+    pub residual: f64,
+    pub iterations: usize,
+}
+
+impl Fit {
+    /// The fitted value of `param`.
+    pub fn get(&self, param: &str) -> Option<f64> {
+        self.params
+            .iter()
+            .find(|(name, _)| *name == param)
+            .map(|&(_, value)| value)
+    }
+}
+
+/// Fits the parameters of `model`, starting from the guesses in `initial`, so that it
+/// reproduces `targets` at the `columns` of samples, each named by the variable it binds.
+pub fn least_squares(
+    model: &OpArgument,
+    columns: &[(&'static str, &[f64])],
+    targets: &[f64],
+    initial: &[(&'static str, f64)],
+    max_iterations: usize,
+) -> Result<Fit, FitError> {
+    assert!(
+        columns
+            .iter()
+            .all(|(_, column)| column.len() == targets.len()),
+        "Hold on, every column passed to least_squares needs as many samples as there are targets"
+    );
+    let n = initial.len();
+    if targets.len() < n {
+        return Err(FitError::Underdetermined {
+            samples: targets.len(),
+            params: n,
+        });
+    }
+
+    // The program computes the model and then its derivative by each parameter, from the
+    // parameters followed by the sample variables.
+    let names = initial
+        .iter()
+        .map(|&(name, _)| name)
+        .chain(columns.iter().map(|&(name, _)| name))
+        .collect::<Vec<_>>();
+    let exprs = std::iter::once(model.clone())
+        .chain(initial.iter().map(|&(name, _)| model.derivative(name)))
+        .collect::<Vec<_>>();
+    let program = compile_many(&exprs, &names)?;
+
+    let mut inputs = initial.iter().map(|&(_, value)| value).collect::<Vec<_>>();
+    inputs.resize(names.len(), 0.0);
+    let (mut registers, mut out) = (Vec::new(), vec![0.0; n + 1]);
+    // The sum of squared residuals, and the normal equations JᵀJ and -Jᵀr.
+    let mut system = |inputs: &mut [f64], jacobian: bool| {
+        let (mut sse, mut jtj, mut jtr) = (0.0, vec![vec![0.0; n]; n], vec![0.0; n]);
+        for (row, target) in targets.iter().enumerate() {
+            for (input, (_, column)) in inputs[n..].iter_mut().zip(columns) {
+                *input = column[row];
+            }
+            program.evaluate_into(inputs, &mut registers, &mut out);
+            let r = out[0] - target;
+            sse += r * r;
+            if jacobian {
+                for (i, gi) in out[1..].iter().enumerate() {
+                    jtr[i] -= gi * r;
+                    for (j, gj) in out[1..].iter().enumerate() {
+                        jtj[i][j] += gi * gj;
+                    }
+                }
+            }
+        }
+        (sse, jtj, jtr)
+    };
+
+    let (mut sse, mut jtj, mut jtr) = system(&mut inputs, true);
+    if !sse.is_finite() {
+        return Err(FitError::NotFinite);
+    }
+    let mut damping = 1e-3;
+    let mut iterations = 0;
+    while iterations < max_iterations && sse > 0.0 {
+        iterations += 1;
+        let mut damped = jtj.clone();
+        for (i, row) in damped.iter_mut().enumerate() {
+            row[i] += damping * jtj[i][i].max(f64::MIN_POSITIVE);
+        }
+        let Some(step) = solve_linear(damped, jtr.clone()) else {
+            damping *= DAMPING_FACTOR;
+            continue;
+        };
+        let mut trial = inputs.clone();
+        trial.iter_mut().zip(&step).for_each(|(p, d)| *p += d);
+        let (trial_sse, _, _) = system(&mut trial, false);
+        if trial_sse.is_finite() && trial_sse < sse {
+            let converged = sse - trial_sse <= f64::EPSILON * sse;
+            inputs = trial;
+            (sse, jtj, jtr) = system(&mut inputs, true);
+            damping /= DAMPING_FACTOR;
+            if converged {
+                break;
+            }
+        } else if damping > 1e16 {
+            break;
+        } else {
+            damping *= DAMPING_FACTOR;
+        }
+    }
+
+    Ok(Fit {
+        params: initial
+            .iter()
+            .zip(&inputs)
+            .map(|(&(name, _), &value)| (name, value))
+            .collect(),
+        residual: sse,
+        iterations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{least_squares, FitError};
+    use crate::{codegen::CodegenError, parse::parse};
+
+    #[test]
+    fn test_least_squares() {
+        let p = |input: &str| parse(input).unwrap();
+        let ts = (0..12).map(|t| t as f64 * 0.25).collect::<Vec<_>>();
+
+        // Exact exponential decay data is recovered from a poor guess.
+        let decay = ts
+            .iter()
+            .map(|t| 3.0 * f64::exp(-1.5 * t))
+            .collect::<Vec<_>>();
+        let model = p("a * exp(-k * t)");
+        let fit = least_squares(
+            &model,
+            &[("t", &ts)],
+            &decay,
+            &[("a", 1.0), ("k", 0.2)],
+            200,
+        )
+        .unwrap();
+        assert!((fit.get("a").unwrap() - 3.0).abs() < 1e-9);
+        assert!((fit.get("k").unwrap() - 1.5).abs() < 1e-9);
+        assert!(fit.residual < 1e-18);
+
+        // A line through noisy points matches the closed-form regression.
+        let noise = [
+            0.1, -0.2, 0.05, 0.0, -0.1, 0.15, -0.05, 0.2, -0.15, 0.1, 0.0, -0.1,
+        ];
+        let ys = ts
+            .iter()
+            .zip(noise)
+            .map(|(t, e)| 2.0 * t + 1.0 + e)
+            .collect::<Vec<_>>();
+        let line = least_squares(
+            &p("m * t + c"),
+            &[("t", &ts)],
+            &ys,
+            &[("m", 0.0), ("c", 0.0)],
+            50,
+        )
+        .unwrap();
+        let n = ts.len() as f64;
+        let (mean_t, mean_y) = (ts.iter().sum::<f64>() / n, ys.iter().sum::<f64>() / n);
+        let slope = ts
+            .iter()
+            .zip(&ys)
+            .map(|(t, y)| (t - mean_t) * (y - mean_y))
+            .sum::<f64>()
+            / ts.iter().map(|t| (t - mean_t).powi(2)).sum::<f64>();
+        assert!((line.get("m").unwrap() - slope).abs() < 1e-9);
+        assert!((line.get("c").unwrap() - (mean_y - slope * mean_t)).abs() < 1e-9);
+
+        assert_eq!(
+            least_squares(
+                &p("a * t"),
+                &[("t", &ts[..1])],
+                &ys[..1],
+                &[("a", 1.0), ("b", 0.0)],
+                5
+            ),
+            Err(FitError::Underdetermined {
+                samples: 1,
+                params: 2
+            })
+        );
+        assert_eq!(
+            least_squares(&p("a * s"), &[("t", &ts)], &ys, &[("a", 1.0)], 5),
+            Err(FitError::Codegen(CodegenError::UnknownVariable("s")))
+        );
+        assert_eq!(
+            least_squares(&p("ln(a) * t"), &[("t", &ts)], &ys, &[("a", -1.0)], 5),
+            Err(FitError::NotFinite)
+        );
+    }
+}
@@ -0,0 +1,664 @@
+//! This module describes constant folding on our computational graph.
+//!
+//! Folding is exact: arithmetic on rationals is carried out as long as the result still fits
+//! in a [`Value::Rational`], and trigonometric functions of rational multiples of `π` are
+//! reduced by their period and evaluated exactly at multiples of `π/6` and `π/4`. Exponentials
+//! of multiples of `iπ` are folded with Euler's formula at quarter turns, and `exp`, `e^x` and
+//! `ln` cancel where that is valid. Sums, products, quotients and integer powers of
+//! [`Value::Algebraic`] numbers are carried out exactly as well. Nothing is ever rounded to a
+//! float, so whatever cannot be folded exactly is left as it is.
+//!
+//! `∞` and `-∞` follow the arithmetic of the extended reals, so `1/∞` folds to `0` and
+//! `2·∞` to `∞`. Indeterminate forms such as `∞ - ∞`, `0·∞` and `1/0` fold to
+//! [`Value::Undefined`], which then absorbs every operation it is an argument of.
+
+use smallvec::smallvec;
+
+use crate::{
+    algebraic,
+    constants::Value,
+    provenance::{self, Step},
+    symbols::{
+        OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        Operation,
+        OperationKind::{self, *},
+    },
+};
+
+/// A signed rational in lowest terms with a positive denominator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Ratio {
+    pub(crate) num: i128,
+    pub(crate) den: i128,
+}
+
+impl Ratio {
+    pub(crate) const ZERO: Ratio = Ratio { num: 0, den: 1 };
+    pub(crate) const ONE: Ratio = Ratio { num: 1, den: 1 };
+
+    pub(crate) fn new(num: i128, den: i128) -> Option<Ratio> {
+        if den == 0 {
+            return None;
+        }
+        let (mut a, mut b) = (num.unsigned_abs(), den.unsigned_abs());
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        let divisor = a.max(1) as i128;
+        let sign = den.signum();
+        Some(Ratio {
+            num: sign * num / divisor,
+            den: sign * den / divisor,
+        })
+    }
+
+    /// Reads the constant `±num/den` out of `node`.
+    pub(crate) fn of(node: &OpArgument) -> Option<Ratio> {
+        match &node.value {
+            Leaf(value) => match **value {
+                Value::Rational(num, den) => Ratio::new(num as i128, den.get() as i128),
+                _ => None,
+            },
+            Op(op) if op.op == Negation => Ratio::of(&op.arguments[0]).map(|r| -r),
+            Op(_) => None,
+        }
+    }
+
+    /// Builds `node` back, if the numerator and denominator fit in a [`Value::Rational`].
+    pub(crate) fn to_oparg(self) -> Option<OpArgument> {
+        let num = u64::try_from(self.num.unsigned_abs()).ok()?;
+        let den = u64::try_from(self.den).ok()?;
+        let value = OpArgument::from(Value::rational(num, den));
+        Some(if self.num < 0 { -value } else { value })
+    }
+
+    pub(crate) fn checked_add(self, rhs: Ratio) -> Option<Ratio> {
+        let num = (self.num.checked_mul(rhs.den)?).checked_add(rhs.num.checked_mul(self.den)?)?;
+        Ratio::new(num, self.den.checked_mul(rhs.den)?)
+    }
+
+    pub(crate) fn checked_mul(self, rhs: Ratio) -> Option<Ratio> {
+        Ratio::new(
+            self.num.checked_mul(rhs.num)?,
+            self.den.checked_mul(rhs.den)?,
+        )
+    }
+
+    pub(crate) fn recip(self) -> Option<Ratio> {
+        Ratio::new(self.den, self.num)
+    }
+
+    fn powi(self, exponent: i128) -> Option<Ratio> {
+        let base = if exponent < 0 { self.recip()? } else { self };
+        let exponent = u32::try_from(exponent.unsigned_abs()).ok()?;
+        Ratio::new(
+            base.num.checked_pow(exponent)?,
+            base.den.checked_pow(exponent)?,
+        )
+    }
+
+    /// The exact `n`th root of a nonnegative `self`, if its numerator and denominator are both
+    /// perfect `n`th powers.
+    fn root(self, n: u32) -> Option<Ratio> {
+        let exact = |x: i128| {
+            let guess = (x as f64).powf(1.0 / n as f64).round() as i128;
+            (guess.max(1) - 1..=guess + 1).find(|r| r.checked_pow(n) == Some(x))
+        };
+        (self.num >= 0 && n > 0).then_some(())?;
+        Ratio::new(exact(self.num)?, exact(self.den)?)
+    }
+
+    /// The remainder of `self` modulo the positive integer `period`, in `[0, period)`.
+    fn rem(self, period: i128) -> Ratio {
+        let modulus = period * self.den;
+        Ratio {
+            num: self.num.rem_euclid(modulus),
+            den: self.den,
+        }
+    }
+}
+
+impl std::ops::Neg for Ratio {
+    type Output = Ratio;
+    fn neg(self) -> Ratio {
+        Ratio {
+            num: -self.num,
+            den: self.den,
+        }
+    }
+}
+
+/// A product `coefficient · π^pi · i^i` of a rational and the constants `π` and `i`.
+struct Monomial {
+    coefficient: Ratio,
+    pi: u32,
+    i: u32,
+}
+
+/// Reads a [`Monomial`] out of `node`, built from rationals, `π` and `i` by negation,
+/// multiplication and division by rationals.
+fn monomial(node: &OpArgument) -> Option<Monomial> {
+    let constant = |coefficient| Monomial {
+        coefficient,
+        pi: 0,
+        i: 0,
+    };
+    match &node.value {
+        Leaf(value) => match **value {
+            Value::Pi => Some(Monomial {
+                pi: 1,
+                ..constant(Ratio::ONE)
+            }),
+            Value::I => Some(Monomial {
+                i: 1,
+                ..constant(Ratio::ONE)
+            }),
+            _ => Ratio::of(node).map(constant),
+        },
+        Op(op) => {
+            let arg = |i: usize| &op.arguments[i];
+            match op.op {
+                Negation => {
+                    let inner = monomial(arg(0))?;
+                    Some(Monomial {
+                        coefficient: -inner.coefficient,
+                        ..inner
+                    })
+                }
+                Multiplication => {
+                    let (lhs, rhs) = (monomial(arg(0))?, monomial(arg(1))?);
+                    Some(Monomial {
+                        coefficient: lhs.coefficient.checked_mul(rhs.coefficient)?,
+                        pi: lhs.pi + rhs.pi,
+                        i: lhs.i + rhs.i,
+                    })
+                }
+                Division => {
+                    let lhs = monomial(arg(0))?;
+                    let divisor = Ratio::of(arg(1))?.recip()?;
+                    Some(Monomial {
+                        coefficient: lhs.coefficient.checked_mul(divisor)?,
+                        ..lhs
+                    })
+                }
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Reads `q` out of a node of the form `q·π`.
+fn pi_multiple(node: &OpArgument) -> Option<Ratio> {
+    let m = monomial(node)?;
+    (m.pi == 1 && m.i == 0).then_some(m.coefficient)
+}
+
+/// Reads `q` out of a node of the form `q·iπ`.
+fn i_pi_multiple(node: &OpArgument) -> Option<Ratio> {
+    let m = monomial(node)?;
+    (m.pi == 1 && m.i == 1).then_some(m.coefficient)
+}
+
+/// Builds the node `q·π` in the form it is usually written: `π`, `q·π` or `q·π/d`.
+fn pi_times(q: Ratio) -> Option<OpArgument> {
+    let pi = OpArgument::from(Value::Pi);
+    let numerator = match q.num.abs() {
+        1 => pi,
+        num => Ratio { num, den: 1 }.to_oparg()? * pi,
+    };
+    let node = match q.den {
+        1 => numerator,
+        den => numerator / Ratio { num: den, den: 1 }.to_oparg()?,
+    };
+    Some(if q.num < 0 { -node } else { node })
+}
+
+/// `1/2 · √n`, written as `n^(1/2) / 2`.
+fn half_root(n: u64) -> OpArgument {
+    let root = OpArgument::from(Value::integer(n)).pow(&Value::rational(1, 2).into());
+    root / OpArgument::from(Value::integer(2))
+}
+
+/// The exact sine of `degrees`, a multiple of 15 in `[0, 360)`, if it has a short closed form.
+fn sin_degrees(degrees: i128) -> Option<OpArgument> {
+    let negative = degrees >= 180;
+    let degrees = degrees % 180;
+    let value = match degrees.min(180 - degrees) {
+        0 => return Some(Value::integer(0).into()),
+        30 => Value::rational(1, 2).into(),
+        45 => half_root(2),
+        60 => half_root(3),
+        90 => Value::integer(1).into(),
+        _ => return None,
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// The exact tangent of `degrees`, a multiple of 15 in `[0, 180)`, if it is finite and has a
+/// short closed form.
+fn tan_degrees(degrees: i128) -> Option<OpArgument> {
+    let three = || OpArgument::from(Value::integer(3));
+    let negative = degrees > 90;
+    let value = match degrees.min(180 - degrees) {
+        0 => return Some(Value::integer(0).into()),
+        30 => three().pow(&Value::rational(1, 2).into()) / three(),
+        45 => Value::integer(1).into(),
+        60 => three().pow(&Value::rational(1, 2).into()),
+        _ => return None,
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// Evaluates `op(q·π)` exactly, for `q` already reduced by the period of `op`.
+fn trig_at(op: OperationKind, q: Ratio) -> Option<OpArgument> {
+    let degrees = q.checked_mul(Ratio { num: 180, den: 1 })?;
+    if degrees.den != 1 || degrees.num % 15 != 0 {
+        return None;
+    }
+    match op {
+        Sin => sin_degrees(degrees.num),
+        Cos => sin_degrees((degrees.num + 90) % 360),
+        Tan => tan_degrees(degrees.num),
+        _ => None,
+    }
+}
+
+/// Reduces the argument `arg` of the periodic function `op` by its period, returning the
+/// reduced argument if anything changed. Handles `q·π` itself and `x ± q·π` and `q·π ± x`.
+fn reduce_period(op: OperationKind, arg: &OpArgument) -> Option<OpArgument> {
+    let period = if op == Tan { 1 } else { 2 };
+    let reduce = |node: &OpArgument| {
+        let q = pi_multiple(node)?;
+        let reduced = q.rem(period);
+        (reduced != q).then_some(reduced)
+    };
+
+    if let Some(q) = reduce(arg) {
+        return match q == Ratio::ZERO {
+            true => Some(Value::integer(0).into()),
+            false => pi_times(q),
+        };
+    }
+
+    let Op(sum) = &arg.value else {
+        return None;
+    };
+    if sum.op != Addition && sum.op != Subtraction {
+        return None;
+    }
+    let (lhs, rhs) = (&sum.arguments[0], &sum.arguments[1]);
+    let (q, pi_on_left) = match (reduce(lhs), reduce(rhs)) {
+        (Some(q), _) => (q, true),
+        (_, Some(q)) => (q, false),
+        _ => return None,
+    };
+
+    Some(match (q == Ratio::ZERO, pi_on_left, sum.op) {
+        (true, true, Subtraction) => -rhs,
+        (true, true, _) => rhs.clone(),
+        (true, false, _) => lhs.clone(),
+        (false, true, op) => Operation::new(op, smallvec![pi_times(q)?, rhs.clone()]).into(),
+        (false, false, op) => Operation::new(op, smallvec![lhs.clone(), pi_times(q)?]).into(),
+    })
+}
+
+/// The number of quarter turns `k` with `exp(q·iπ) = iᵏ`, if `q` is a multiple of `1/2`.
+fn quarter_turns(q: Ratio) -> Option<i128> {
+    let turns = q.checked_mul(Ratio { num: 2, den: 1 })?;
+    (turns.den == 1).then_some(turns.num.rem_euclid(4))
+}
+
+/// Multiplies `node` by `iᵏ`, or returns `iᵏ` itself if there is no `node`.
+fn rotate(node: Option<OpArgument>, k: i128) -> OpArgument {
+    let i = OpArgument::from(Value::I);
+    let scaled = match node {
+        Some(node) if k % 2 == 1 => i * node,
+        Some(node) => node,
+        None if k % 2 == 1 => i,
+        None => Value::integer(1).into(),
+    };
+    if k >= 2 {
+        -scaled
+    } else {
+        scaled
+    }
+}
+
+/// Folds `exp(exponent)`, or equivalently `e^exponent`.
+fn fold_exp(exponent: &OpArgument) -> Option<OpArgument> {
+    let one = || OpArgument::from(Value::integer(1));
+    match Ratio::of(exponent) {
+        Some(Ratio::ZERO) => return Some(one()),
+        Some(Ratio::ONE) => return Some(Value::E.into()),
+        Some(_) => return None,
+        None => {}
+    }
+    if let Some(x @ Infinite { .. }) = Extended::of(exponent) {
+        return fold_infinite(Exp, &[x]);
+    }
+    if let Some(q) = i_pi_multiple(exponent) {
+        return Some(rotate(None, quarter_turns(q)?));
+    }
+
+    let Op(op) = &exponent.value else {
+        return None;
+    };
+    let arg = |i: usize| &op.arguments[i];
+    match op.op {
+        Ln => Some(arg(0).clone()),
+        // exp(a ± q·iπ) = exp(a) · iᵏ
+        Addition | Subtraction => {
+            let (rest, q) = match (i_pi_multiple(arg(0)), i_pi_multiple(arg(1))) {
+                (_, Some(q)) if op.op == Subtraction => (arg(0).exp(), -q),
+                (_, Some(q)) => (arg(0).exp(), q),
+                (Some(q), _) if op.op == Subtraction => ((-arg(1)).exp(), q),
+                (Some(q), _) => (arg(1).exp(), q),
+                _ => return None,
+            };
+            let k = quarter_turns(q)?;
+            Some(rotate(Some(fold_root(&rest).unwrap_or(rest)), k))
+        }
+        _ => None,
+    }
+}
+
+/// Folds `ln(arg)`, undoing exponentials of rationals.
+fn fold_ln(arg: &OpArgument) -> Option<OpArgument> {
+    match &arg.value {
+        Leaf(value) => match **value {
+            Value::E => Some(Value::integer(1).into()),
+            _ if Ratio::of(arg) == Some(Ratio::ONE) => Some(Value::integer(0).into()),
+            _ if Ratio::of(arg) == Some(Ratio::ZERO) => Some(-OpArgument::from(Value::Inf)),
+            _ => None,
+        },
+        Op(op) => {
+            let exponent = match op.op {
+                Exp => &op.arguments[0],
+                Pow if op.arguments[0] == OpArgument::from(Value::E) => &op.arguments[1],
+                _ => return None,
+            };
+            // Only real exponents come back out unchanged, since ln is multivalued.
+            Ratio::of(exponent).map(|_| exponent.clone())
+        }
+    }
+}
+
+/// A constant of the extended reals, or the result of an indeterminate form.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Extended {
+    Finite(Ratio),
+    Infinite { negative: bool },
+    Undefined,
+}
+
+use Extended::{Finite, Infinite, Undefined};
+
+impl Extended {
+    /// Reads `±r`, `±∞` or `undefined` out of `node`.
+    fn of(node: &OpArgument) -> Option<Extended> {
+        match &node.value {
+            Leaf(value) => match **value {
+                Value::Inf => Some(Infinite { negative: false }),
+                Value::Undefined => Some(Undefined),
+                _ => Ratio::of(node).map(Finite),
+            },
+            Op(op) if op.op == Negation => Extended::of(&op.arguments[0]).map(|x| -x),
+            Op(_) => None,
+        }
+    }
+
+    fn to_oparg(self) -> Option<OpArgument> {
+        match self {
+            Finite(r) => r.to_oparg(),
+            Infinite { negative } => {
+                let inf = OpArgument::from(Value::Inf);
+                Some(if negative { -inf } else { inf })
+            }
+            Undefined => Some(Value::Undefined.into()),
+        }
+    }
+
+    fn signum(self) -> Option<i128> {
+        match self {
+            Finite(r) => Some(r.num.signum()),
+            Infinite { negative } => Some(if negative { -1 } else { 1 }),
+            Undefined => None,
+        }
+    }
+
+    fn is_infinite(self) -> bool {
+        matches!(self, Infinite { .. })
+    }
+}
+
+impl std::ops::Neg for Extended {
+    type Output = Extended;
+    fn neg(self) -> Extended {
+        match self {
+            Finite(r) => Finite(-r),
+            Infinite { negative } => Infinite {
+                negative: !negative,
+            },
+            Undefined => Undefined,
+        }
+    }
+}
+
+/// Folds `op` applied to constants of the extended reals, at least one of them infinite.
+fn fold_infinite(op: OperationKind, args: &[Extended]) -> Option<OpArgument> {
+    let infinite = |sign: i128| Infinite { negative: sign < 0 };
+    let folded = match (op, args) {
+        (Negation, &[x]) => -x,
+        (Addition, &[Infinite { negative: a }, Infinite { negative: b }]) if a != b => Undefined,
+        (Addition, &[x @ Infinite { .. }, _] | &[_, x @ Infinite { .. }]) => x,
+        (Subtraction, &[a, b]) => return fold_infinite(Addition, &[a, -b]),
+        (Multiplication, &[a, b]) => match a.signum()? * b.signum()? {
+            0 => Undefined,
+            sign => infinite(sign),
+        },
+        (Division, &[Finite(_), Infinite { .. }]) => Finite(Ratio::ZERO),
+        (Division, &[a @ Infinite { .. }, Finite(r)]) => match r.num.signum() {
+            0 => Undefined,
+            sign => infinite(a.signum()? * sign),
+        },
+        (Division, _) => Undefined,
+        (Pow, &[Infinite { negative }, Finite(r)]) => match (r.num.signum(), negative) {
+            (0, _) => Undefined,
+            (-1, _) if r.den == 1 || !negative => Finite(Ratio::ZERO),
+            (_, false) => infinite(1),
+            // Odd integer powers of -∞ keep its sign; other powers are not real.
+            (_, true) if r.den == 1 => infinite(if r.num % 2 == 0 { 1 } else { -1 }),
+            (_, true) => Undefined,
+        },
+        (Pow, &[Finite(b), Infinite { negative }]) => {
+            let b = if negative { b.recip()? } else { b };
+            let magnitude = b.num.abs().cmp(&b.den);
+            match (b.num.signum(), magnitude) {
+                (1, std::cmp::Ordering::Greater) => infinite(1),
+                (_, std::cmp::Ordering::Less) => Finite(Ratio::ZERO),
+                // 1^∞ is indeterminate, and powers of negative bases oscillate.
+                _ => Undefined,
+            }
+        }
+        (Pow, &[Infinite { negative: false }, Infinite { negative }]) => match negative {
+            false => infinite(1),
+            true => Finite(Ratio::ZERO),
+        },
+        (Exp, &[Infinite { negative }]) => match negative {
+            false => infinite(1),
+            true => Finite(Ratio::ZERO),
+        },
+        (Ln, &[Infinite { negative: false }]) => infinite(1),
+        (Atan, &[x]) => {
+            let half_pi = pi_times(Ratio::new(x.signum()?, 2)?)?;
+            return Some(half_pi);
+        }
+        (Sign, &[x]) => Finite(Ratio::new(x.signum()?, 1)?),
+        _ => Undefined,
+    };
+    folded.to_oparg()
+}
+
+/// Folds the root of `node`, whose arguments are already folded.
+pub(crate) fn fold_root(node: &OpArgument) -> Option<OpArgument> {
+    let Op(op) = &node.value else {
+        return None;
+    };
+    let arg = |i: usize| &op.arguments[i];
+    let rational = |i: usize| Ratio::of(arg(i));
+
+    let extended = op.arguments.iter().map(Extended::of).collect::<Vec<_>>();
+    if extended.contains(&Some(Undefined)) {
+        return Some(Value::Undefined.into());
+    }
+    if extended
+        .iter()
+        .any(|x| x.is_some_and(Extended::is_infinite))
+    {
+        if let Some(args) = extended.into_iter().collect::<Option<Vec<_>>>() {
+            return fold_infinite(op.op, &args);
+        }
+    }
+
+    if let Some(exact) = algebraic::fold_root(op) {
+        return Some(exact);
+    }
+
+    let folded = match op.op {
+        Addition => rational(0)?.checked_add(rational(1)?)?,
+        Subtraction => rational(0)?.checked_add(-rational(1)?)?,
+        Multiplication => rational(0)?.checked_mul(rational(1)?)?,
+        Division if rational(1) == Some(Ratio::ZERO) => return Some(Value::Undefined.into()),
+        Division => rational(0)?.checked_mul(rational(1)?.recip()?)?,
+        Negation => {
+            // Only `-0` and double negations of rationals need folding: `-r` is already
+            // canonical.
+            match &arg(0).value {
+                Op(inner) if inner.op == Negation => {}
+                Leaf(_) if rational(0) == Some(Ratio::ZERO) => {}
+                _ => return None,
+            }
+            -rational(0)?
+        }
+        Pow if arg(0) == &OpArgument::from(Value::E) => return fold_exp(arg(1)),
+        Pow => {
+            let exponent = rational(1)?;
+            let mut base = rational(0)?;
+            if base == Ratio::ZERO && exponent.num < 0 {
+                return Some(Value::Undefined.into());
+            }
+            if exponent.den != 1 {
+                base = base.root(u32::try_from(exponent.den).ok()?)?;
+            }
+            base.powi(exponent.num)?
+        }
+        Sin | Cos | Tan => {
+            return match reduce_period(op.op, arg(0)) {
+                Some(reduced) => {
+                    let rebuilt = Operation::new(op.op, smallvec![reduced]).into();
+                    Some(fold_root(&rebuilt).unwrap_or(rebuilt))
+                }
+                None if rational(0) == Some(Ratio::ZERO) => trig_at(op.op, Ratio::ZERO),
+                None => trig_at(op.op, pi_multiple(arg(0))?),
+            };
+        }
+        Exp => return fold_exp(arg(0)),
+        Ln => return fold_ln(arg(0)),
+        Atan => (rational(0)? == Ratio::ZERO).then_some(Ratio::ZERO)?,
+        Sign => Ratio::new(rational(0)?.num.signum(), 1)?,
+    };
+    folded.to_oparg()
+}
+
+impl OpArgument {
+    /// Folds constant subexpressions exactly, bottom up.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    pub fn fold(&self) -> OpArgument {
+        self.map_bottom_up(|node, rebuilt| {
+            let result = fold_root(&rebuilt).unwrap_or(rebuilt);
+            provenance::record(|| Step::Fold, node, &result);
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        constants::Value,
+        parse::parse,
+        symbols::{variable, OpArgument},
+    };
+
+    #[test]
+    fn test_fold() {
+        let fold = |input: &str| parse(input).unwrap().fold();
+        let rational = |num, den| OpArgument::from(Value::rational(num, den));
+
+        assert_eq!(fold("1/2 + 1/3 * 2"), rational(7, 6));
+        assert_eq!(fold("(2/3)^-2 - 3"), -rational(3, 4));
+        assert_eq!(fold("4^(1/2) + (8/27)^(-2/3)"), rational(17, 4));
+        assert_eq!(fold("2^(1/2)"), fold("2").pow(&rational(1, 2)));
+        assert_eq!(fold("atan(0)"), rational(0, 1));
+        assert_eq!(fold("sign(-3/2)"), -rational(1, 1));
+        assert_eq!(fold("sign(2 - 2)"), rational(0, 1));
+        assert_eq!(fold("-(2 - 2)"), rational(0, 1));
+        assert_eq!(fold("x * (2 - 2)"), variable("x") * rational(0, 1));
+
+        assert_eq!(fold("sin(pi/6)"), rational(1, 2));
+        assert_eq!(fold("cos(pi/4)"), fold("2^(1/2) / 2"));
+        assert_eq!(fold("tan(pi/3)"), fold("3^(1/2)"));
+        assert_eq!(fold("sin(7*pi/6)"), -rational(1, 2));
+        assert_eq!(fold("cos(-2*pi/3)"), -rational(1, 2));
+        assert_eq!(fold("tan(3*pi/4)"), -rational(1, 1));
+        assert_eq!(fold("sin(pi)"), rational(0, 1));
+        assert_eq!(fold("cos(1 - 1)"), rational(1, 1));
+        assert_eq!(fold("sin(13*pi/6)"), rational(1, 2));
+        // No short closed form, and no float fallback either.
+        assert_eq!(fold("sin(pi/5)"), parse("sin(pi/5)").unwrap());
+        assert_eq!(fold("tan(pi/2)"), parse("tan(pi/2)").unwrap());
+
+        assert_eq!(fold("sin(x + 2*pi)"), fold("sin(x)"));
+        assert_eq!(fold("cos(4*pi - x)"), fold("cos(-x)"));
+        assert_eq!(fold("tan(x + pi)"), fold("tan(x)"));
+        assert_eq!(fold("sin(x + 5*pi/2)"), fold("sin(x + pi/2)"));
+
+        let x = variable("x");
+        let i = OpArgument::from(Value::I);
+        assert_eq!(fold("exp(i*pi)"), -rational(1, 1));
+        assert_eq!(fold("e^(i*pi) + 1"), rational(0, 1));
+        assert_eq!(fold("exp(pi*i/2)"), i);
+        assert_eq!(fold("exp(-3*i*pi/2)"), i);
+        assert_eq!(fold("exp(3*i*pi/2)"), -&i);
+        assert_eq!(fold("exp(x + 2*i*pi)"), x.exp());
+        assert_eq!(fold("exp(x - i*pi/2)"), -(&i * x.exp()));
+        assert_eq!(fold("exp(i*pi/3)"), parse("exp(i*pi/3)").unwrap());
+        assert_eq!(fold("ln(e)"), rational(1, 1));
+        assert_eq!(fold("ln(e^(2/3))"), rational(2, 3));
+        assert_eq!(fold("e^ln(x)"), x);
+        assert_eq!(fold("exp(ln(x))"), x);
+        assert_eq!(fold("ln(exp(x))"), x.exp().ln());
+
+        let inf = OpArgument::from(Value::Inf);
+        let undefined = OpArgument::from(Value::Undefined);
+        assert_eq!(fold("1/inf"), rational(0, 1));
+        assert_eq!(fold("-2 * inf"), -&inf);
+        assert_eq!(fold("inf - -inf"), inf);
+        assert_eq!(fold("(-inf)^3"), -&inf);
+        assert_eq!(fold("(1/2)^inf"), rational(0, 1));
+        assert_eq!(fold("2^-inf"), rational(0, 1));
+        assert_eq!(fold("exp(-inf)"), rational(0, 1));
+        assert_eq!(fold("e^inf"), inf);
+        assert_eq!(fold("ln(0)"), -&inf);
+        assert_eq!(fold("atan(-inf)"), fold("-(pi/2)"));
+        assert_eq!(fold("sign(-inf)"), -rational(1, 1));
+        assert_eq!(fold("inf - inf"), undefined);
+        assert_eq!(fold("0 * inf"), undefined);
+        assert_eq!(fold("1^inf"), undefined);
+        assert_eq!(fold("x / 0"), undefined);
+        assert_eq!(fold("sin(x + (inf - inf))"), undefined);
+        // Whether x + ∞ is ∞ depends on x.
+        assert_eq!(fold("x + inf"), &x + &inf);
+    }
+}
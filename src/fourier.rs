@@ -0,0 +1,276 @@
+//! This module describes how to compute truncated Fourier series of expressions.
+//!
+//! The series of `f` with period `T` is `a₀/2 + Σ aₙ·cos(nωx) + bₙ·sin(nωx)` with `ω = 2π/T`,
+//! whose coefficients are integrals of `f` over `[-T/2, T/2]`. When `f` is a polynomial in the
+//! variable they are integrated exactly, by parts, so they keep symbolic coefficients and
+//! periods. Otherwise they are computed by Gauss–Legendre quadrature, which needs the period
+//! and every other variable of `f` to be numeric.
+
+use std::fmt::Display;
+
+use crate::{
+    approx::COEFFICIENT_DENOMINATOR,
+    constants::Value,
+    continued_fraction::signed_rational,
+    evaluation::{Bindings, EvaluationError},
+    sum::polynomial,
+    symbols::{variable, OpArgument},
+};
+
+/// The nodes and weights of the five-point Gauss–Legendre rule on `[-1, 1]`.
+const GAUSS_LEGENDRE: [(f64, f64); 5] = [
+    (0.0, 0.568_888_888_888_888_9),
+    (-0.538_469_310_105_683, 0.478_628_670_499_366_47),
+    (0.538_469_310_105_683, 0.478_628_670_499_366_47),
+    (-0.906_179_845_938_664, 0.236_926_885_056_189_08),
+    (0.906_179_845_938_664, 0.236_926_885_056_189_08),
+];
+
+/// The period is split into this many panels per term of the series for quadrature.
+const PANELS_PER_TERM: usize = 16;
+
+/// The reasons a Fourier series can fail to be computed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FourierError {
+    Evaluation(EvaluationError),
+    /// The period depends on the variable, or is not a positive number where it must be one.
+    InvalidPeriod(OpArgument),
+    /// A coefficient computed numerically is too large to be represented exactly.
+    Unrepresentable(f64),
+}
+
+impl Display for FourierError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FourierError::Evaluation(err) => err.fmt(f),
+            FourierError::InvalidPeriod(period) => write!(f, "{} is not a valid period", period),
+            FourierError::Unrepresentable(c) => {
+                write!(f, "the coefficient {} is too large to represent", c)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FourierError {}
+
+impl From<EvaluationError> for FourierError {
+    fn from(err: EvaluationError) -> Self {
+        FourierError::Evaluation(err)
+    }
+}
+
+fn integer(n: i64) -> OpArgument {
+    let magnitude = OpArgument::from(Value::integer(n.unsigned_abs()));
+    if n < 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+fn scaled(c: &OpArgument, term: Option<OpArgument>) -> Option<OpArgument> {
+    term.map(|term| c * term)
+}
+
+/// The coefficient `c`, or `None` if it is the zero of a missing degree.
+fn present(c: &OpArgument) -> Option<OpArgument> {
+    (*c != integer(0)).then(|| c.clone())
+}
+
+fn add(a: Option<OpArgument>, b: Option<OpArgument>) -> Option<OpArgument> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, b) => a.or(b),
+    }
+}
+
+/// The coefficients `(a₀/2, [(aₙ, bₙ)])` of a series, where `None` is zero.
+type Coefficients = (
+    Option<OpArgument>,
+    Vec<(Option<OpArgument>, Option<OpArgument>)>,
+);
+
+/// Integrates the coefficients of the polynomial `p` exactly, by parts.
+fn exact(p: &[OpArgument], period: &OpArgument, n_terms: usize) -> Coefficients {
+    let half = period / integer(2);
+    let power = |k: usize| match k {
+        0 => integer(1),
+        1 => half.clone(),
+        k => half.pow(&integer(k as i64)),
+    };
+    let two_over_period = integer(2) / period;
+
+    // Only even powers survive on the symmetric interval: ∫ x^k = 2·L^(k+1)/(k+1).
+    let constant = p
+        .iter()
+        .enumerate()
+        .filter(|(k, _)| k % 2 == 0)
+        .map(|(k, c)| {
+            let integral = integer(2) * power(k + 1) / integer(k as i64 + 1);
+            scaled(&integral, present(c))
+        })
+        .fold(None, add)
+        .map(|sum| sum / period);
+
+    let terms = (1..=n_terms)
+        .map(|n| {
+            // With λ = nπ/L, the integrals Cₖ = ∫ xᵏ·cos(λx) and Sₖ = ∫ xᵏ·sin(λx) satisfy
+            // Cₖ = -(k/λ)·Sₖ₋₁ and Sₖ = -(-1)ⁿ·Lᵏ·(1 - (-1)ᵏ)/λ + (k/λ)·Cₖ₋₁, from C₀ = S₀ = 0.
+            let lambda = integer(2 * n as i64) * OpArgument::from(Value::Pi) / period;
+            let sign = if n % 2 == 0 { 1 } else { -1 };
+            let (mut cos, mut sin): (Option<OpArgument>, Option<OpArgument>) = (None, None);
+            let (mut a, mut b) = (None, None);
+            for (k, c) in p.iter().enumerate().skip(1) {
+                let step = integer(k as i64) / &lambda;
+                let boundary = (k % 2 == 1).then(|| integer(-2 * sign) * power(k) / &lambda);
+                (cos, sin) = (scaled(&-&step, sin), add(boundary, scaled(&step, cos)));
+                if let Some(c) = present(c) {
+                    a = add(a, scaled(&c, cos.clone()));
+                    b = add(b, scaled(&c, sin.clone()));
+                }
+            }
+            (scaled(&two_over_period, a), scaled(&two_over_period, b))
+        })
+        .collect();
+    (constant, terms)
+}
+
+/// Integrates `f(x)·g(x)` over `[-L, L]` numerically, for each weight `g` in `weights`.
+fn quadrature(
+    expr: &OpArgument,
+    var: &'static str,
+    half: f64,
+    panels: usize,
+    weights: &[&dyn Fn(f64) -> f64],
+) -> Result<Vec<f64>, FourierError> {
+    let width = 2.0 * half / panels as f64;
+    let mut sums = vec![0.0; weights.len()];
+    for panel in 0..panels {
+        let centre = -half + (panel as f64 + 0.5) * width;
+        for (node, weight) in GAUSS_LEGENDRE {
+            let x = centre + node * width / 2.0;
+            let f = expr.evaluate(&Bindings::from_iter([(var, x)]))?;
+            for (sum, g) in sums.iter_mut().zip(weights) {
+                *sum += weight * width / 2.0 * f * g(x);
+            }
+        }
+    }
+    Ok(sums)
+}
+
+fn numeric(
+    expr: &OpArgument,
+    var: &'static str,
+    period: &OpArgument,
+    n_terms: usize,
+) -> Result<Coefficients, FourierError> {
+    let t = period
+        .evaluate(&Bindings::default())
+        .ok()
+        .filter(|t| t.is_finite() && *t > 0.0)
+        .ok_or_else(|| FourierError::InvalidPeriod(period.clone()))?;
+    let panels = PANELS_PER_TERM * (n_terms + 1);
+    let exact = |c: f64| -> Result<Option<OpArgument>, FourierError> {
+        let c =
+            signed_rational(c, COEFFICIENT_DENOMINATOR).ok_or(FourierError::Unrepresentable(c))?;
+        Ok((c.fold() != integer(0)).then_some(c))
+    };
+
+    let one = |_: f64| 1.0;
+    let constant = quadrature(expr, var, t / 2.0, panels, &[&one])?[0] / t;
+    let terms = (1..=n_terms)
+        .map(|n| {
+            let omega = 2.0 * std::f64::consts::PI * n as f64 / t;
+            let cos = |x: f64| (omega * x).cos();
+            let sin = |x: f64| (omega * x).sin();
+            let sums = quadrature(expr, var, t / 2.0, panels, &[&cos, &sin])?;
+            Ok((exact(2.0 * sums[0] / t)?, exact(2.0 * sums[1] / t)?))
+        })
+        .collect::<Result<Vec<_>, FourierError>>()?;
+    Ok((exact(constant)?, terms))
+}
+
+/// The Fourier series of `expr` in `var` with period `period`, truncated after the harmonics
+/// `cos(nωx)` and `sin(nωx)` for `n = n_terms`, and centred on `[-period/2, period/2]`.
+pub fn fourier_series(
+    expr: &OpArgument,
+    var: &'static str,
+    period: &OpArgument,
+    n_terms: usize,
+) -> Result<OpArgument, FourierError> {
+    if period.free_variables().contains(var) {
+        return Err(FourierError::InvalidPeriod(period.clone()));
+    }
+    let (constant, terms) = match polynomial(expr, var) {
+        Some(p) => exact(&p, period, n_terms),
+        None => numeric(expr, var, period, n_terms)?,
+    };
+
+    let x = variable(var);
+    let zero = integer(0);
+    let nonzero = |c: Option<OpArgument>| c.map(|c| c.fold()).filter(|c| *c != zero);
+    let mut series = nonzero(constant);
+    for (n, (a, b)) in terms.into_iter().enumerate() {
+        let angle =
+            (integer(2 * (n as i64 + 1)) * OpArgument::from(Value::Pi) / period).fold() * &x;
+        series = add(series, nonzero(a).map(|a| a * angle.cos()));
+        series = add(series, nonzero(b).map(|b| b * angle.sin()));
+    }
+    Ok(series.unwrap_or(zero))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fourier_series, FourierError};
+    use crate::{evaluation::Bindings, parse::parse};
+
+    #[test]
+    fn test_fourier_series() {
+        let p = |input: &str| parse(input).unwrap();
+        let at = |x: f64| Bindings::from_iter([("x", x)]);
+        let check = |series: &crate::symbols::OpArgument, expected: &dyn Fn(f64) -> f64| {
+            for x in [-2.5, -1.0, 0.3, 2.0] {
+                let actual = series.evaluate(&at(x)).unwrap();
+                assert!(
+                    (actual - expected(x)).abs() < 1e-9,
+                    "{} gave {} at {}, not {}",
+                    series,
+                    actual,
+                    x,
+                    expected(x)
+                );
+            }
+        };
+
+        // x = 2·Σ (-1)^(n+1)·sin(nx)/n on (-π, π), integrated exactly.
+        let sawtooth = fourier_series(&p("x"), "x", &p("2 * pi"), 3).unwrap();
+        check(&sawtooth, &|x| {
+            2.0 * x.sin() - (2.0 * x).sin() + 2.0 / 3.0 * (3.0 * x).sin()
+        });
+
+        // x² = 1/3 + Σ 4(-1)ⁿ·cos(nπx)/(nπ)² on (-1, 1), with a symbolic scale kept.
+        let parabola = fourier_series(&p("a * x^2"), "x", &p("2"), 2).unwrap();
+        let pi2 = std::f64::consts::PI.powi(2);
+        let bindings = |x: f64| Bindings::from_iter([("x", x), ("a", 3.0)]);
+        for x in [-0.5, 0.25, 0.9] {
+            let expected = 3.0
+                * (1.0 / 3.0 - 4.0 * (std::f64::consts::PI * x).cos() / pi2
+                    + (2.0 * std::f64::consts::PI * x).cos() / pi2);
+            let actual = parabola.evaluate(&bindings(x)).unwrap();
+            assert!((actual - expected).abs() < 1e-12);
+        }
+
+        // sin³x = (3·sin x - sin 3x)/4, by quadrature.
+        let cubed = fourier_series(&p("sin(x)^3"), "x", &p("2 * pi"), 4).unwrap();
+        check(&cubed, &|x| x.sin().powi(3));
+
+        assert_eq!(
+            fourier_series(&p("exp(x)"), "x", &p("x"), 2),
+            Err(FourierError::InvalidPeriod(p("x")))
+        );
+        assert_eq!(
+            fourier_series(&p("exp(x)"), "x", &p("-1"), 2),
+            Err(FourierError::InvalidPeriod(p("-1")))
+        );
+    }
+}
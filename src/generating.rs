@@ -0,0 +1,263 @@
+//! This module describes the generating functions of sequences defined by linear recurrences.
+//!
+//! The ordinary generating function `Σ a(n)·xⁿ` of a [`Recurrence`] is a rational function
+//! whose denominator is `1 - c₁x - … - c_d·xᵈ`, and every proper rational function is the
+//! ordinary generating function of some recurrence, so [`OrdinaryGeneratingFunction`] converts
+//! both ways. The exponential generating function `Σ a(n)·xⁿ/n!` is built from the closed form
+//! of the recurrence instead, since `Σ nʲ·rⁿ·xⁿ/n!` is `e^(rx)` times a Touchard polynomial in
+//! `rx`.
+
+use std::fmt::Display;
+
+use crate::{
+    constants::Value,
+    polynomial::trimmed,
+    recurrence::{solution, Basis, Recurrence, RecurrenceError},
+    series::{Series, SeriesError},
+    symbols::OpArgument,
+};
+
+/// The reasons a generating function can fail to define a recurrence.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GeneratingError {
+    /// The numerator has a degree at least that of the denominator.
+    Improper,
+    Series(SeriesError),
+}
+
+impl Display for GeneratingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GeneratingError::Improper => f.write_str(
+                "the numerator must have a lower degree than the denominator to define a recurrence",
+            ),
+            GeneratingError::Series(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for GeneratingError {}
+
+impl From<SeriesError> for GeneratingError {
+    fn from(err: SeriesError) -> Self {
+        GeneratingError::Series(err)
+    }
+}
+
+fn zero() -> OpArgument {
+    Value::integer(0).into()
+}
+
+/// The rational function `numerator(x) / denominator(x)`, as the ordinary generating function
+/// of its power series coefficients. Both polynomials are given lowest degree first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrdinaryGeneratingFunction {
+    numerator: Vec<OpArgument>,
+    denominator: Vec<OpArgument>,
+}
+
+impl OrdinaryGeneratingFunction {
+    pub fn new(numerator: &[OpArgument], denominator: &[OpArgument]) -> Self {
+        OrdinaryGeneratingFunction {
+            numerator: trimmed(numerator),
+            denominator: trimmed(denominator),
+        }
+    }
+
+    /// The generating function of the terms of `recurrence`.
+    pub fn of_recurrence(recurrence: &Recurrence) -> Self {
+        let denominator = std::iter::once(Value::integer(1).into())
+            .chain(recurrence.coefficients().iter().map(|c| -c))
+            .collect::<Vec<OpArgument>>();
+        // Every term past the initial ones cancels in denominator·Σ a(n)·xⁿ.
+        let numerator = (0..recurrence.order())
+            .map(|k| {
+                (0..=k)
+                    .map(|i| &denominator[i] * &recurrence.initial()[k - i])
+                    .reduce(|acc, term| acc + term)
+                    .expect("Oops, every coefficient has at least one product")
+            })
+            .collect::<Vec<_>>();
+        OrdinaryGeneratingFunction::new(&numerator, &denominator)
+    }
+
+    pub fn numerator(&self) -> &[OpArgument] {
+        &self.numerator
+    }
+
+    pub fn denominator(&self) -> &[OpArgument] {
+        &self.denominator
+    }
+
+    /// The generating function as an expression in `x`.
+    pub fn expr(&self, x: &OpArgument) -> OpArgument {
+        let numerator = Series::new(self.numerator.clone()).to_polynomial(x);
+        match &self.denominator[..] {
+            [one] if *one == Value::integer(1).into() => numerator,
+            denominator => numerator / Series::new(denominator.to_vec()).to_polynomial(x),
+        }
+    }
+
+    /// The first `count` coefficients of the power series of the generating function.
+    pub fn series(&self, count: usize) -> Result<Series, SeriesError> {
+        Series::polynomial(&self.numerator, count)
+            .checked_div(&Series::polynomial(&self.denominator, count))
+    }
+
+    /// The recurrence whose terms the power series coefficients are.
+    pub fn to_recurrence(&self) -> Result<Recurrence, GeneratingError> {
+        let order = self.denominator.len().saturating_sub(1);
+        if self.numerator.len() > order {
+            return Err(GeneratingError::Improper);
+        }
+        let initial = self.series(order)?.coefficients().to_vec();
+        let lead = self.denominator.first().ok_or(SeriesError::NotInvertible)?;
+        let coefficients = self.denominator[1..]
+            .iter()
+            .map(|q| (-(q / lead)).fold())
+            .collect();
+        Ok(Recurrence::new(coefficients, initial))
+    }
+}
+
+/// The Stirling numbers of the second kind `S(j, k)` for `k = 0, …, j`.
+fn stirling(j: usize) -> Vec<u64> {
+    let mut row = vec![1];
+    for _ in 0..j {
+        let mut next = vec![0; row.len() + 1];
+        for (k, s) in row.iter().enumerate() {
+            next[k] += k as u64 * s;
+            next[k + 1] += s;
+        }
+        row = next;
+    }
+    row
+}
+
+/// The exponential generating function of `basis`, as an expression in `x`.
+fn exponential(basis: &Basis, x: &OpArgument) -> OpArgument {
+    let scale = |k: usize, base: &OpArgument| match k {
+        0 => None,
+        1 => Some(base * x),
+        k => Some((base * x).pow(&Value::integer(k as u64).into())),
+    };
+    stirling(basis.degree())
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, s)| s != 0)
+        .map(|(k, s)| {
+            // Σ nʲ·zⁿ·xⁿ/n! = Σₖ S(j, k)·(zx)ᵏ·e^(zx), and for z = ρe^(iθ) its real and
+            // imaginary parts are ρᵏxᵏ·e^(ρx·cos θ) times the cosine and sine of kθ + ρx·sin θ.
+            let (base, f) = match basis {
+                Basis::Power { root, .. } => (root, (root * x).exp()),
+                Basis::Cosine { modulus, angle, .. } | Basis::Sine { modulus, angle, .. } => {
+                    let growth = (modulus * x * angle.cos()).exp();
+                    let phase = OpArgument::from(Value::integer(k as u64)) * angle
+                        + modulus * x * angle.sin();
+                    let wave = match basis {
+                        Basis::Cosine { .. } => phase.cos(),
+                        _ => phase.sin(),
+                    };
+                    (modulus, growth * wave)
+                }
+            };
+            let term = match scale(k, base) {
+                Some(power) => power * f,
+                None => f,
+            };
+            match s {
+                1 => term,
+                s => OpArgument::from(Value::integer(s)) * term,
+            }
+        })
+        .reduce(|acc, term| acc + term)
+        .expect("Oops, S(j, j) = 1 for every j")
+}
+
+/// The exponential generating function `Σ a(n)·xⁿ/n!` of the terms of `recurrence`, as an
+/// expression in `x`.
+pub fn exponential_generating_function(
+    recurrence: &Recurrence,
+    x: &OpArgument,
+) -> Result<OpArgument, RecurrenceError> {
+    Ok(solution(recurrence)?
+        .into_iter()
+        .map(|(weight, basis)| weight * exponential(&basis, x))
+        .reduce(|acc, term| acc + term)
+        .unwrap_or_else(zero)
+        .fold())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{exponential_generating_function, GeneratingError, OrdinaryGeneratingFunction};
+    use crate::{evaluation::Bindings, parse::parse, recurrence::Recurrence, symbols::variable};
+
+    #[test]
+    fn test_generating_functions() {
+        let p = |input: &str| parse(input).unwrap().fold();
+        let recurrence = |coefficients: &[&str], initial: &[&str]| {
+            Recurrence::new(
+                coefficients.iter().map(|c| p(c)).collect(),
+                initial.iter().map(|a| p(a)).collect(),
+            )
+        };
+        let x = variable("x");
+
+        // Fibonacci has the generating function x / (1 - x - x²).
+        let fibonacci = recurrence(&["1", "1"], &["0", "1"]);
+        let ordinary = OrdinaryGeneratingFunction::of_recurrence(&fibonacci);
+        assert_eq!(ordinary.numerator(), [p("0"), p("1")]);
+        assert_eq!(ordinary.denominator(), [p("1"), p("-1"), p("-1")]);
+        assert_eq!(
+            ordinary.series(12).unwrap().coefficients(),
+            fibonacci.terms(12)
+        );
+        assert_eq!(ordinary.to_recurrence().unwrap(), fibonacci);
+        let at = |value| Bindings::from_iter([("x", value)]);
+        assert_eq!(ordinary.expr(&x).evaluate(&at(0.5)), Ok(2.0));
+
+        // 1 / (1 - 2x)² generates (n + 1)·2ⁿ.
+        let squared = OrdinaryGeneratingFunction::new(&[p("1")], &[p("1"), p("-4"), p("4")]);
+        assert_eq!(
+            squared.to_recurrence().unwrap().terms(4),
+            [p("1"), p("4"), p("12"), p("32")]
+        );
+        assert_eq!(
+            OrdinaryGeneratingFunction::new(&[p("1"), p("1")], &[p("1"), p("1")]).to_recurrence(),
+            Err(GeneratingError::Improper)
+        );
+
+        // The exponential generating functions agree with their series near 0.
+        let check = |recurrence: &Recurrence| {
+            let egf = exponential_generating_function(recurrence, &x).unwrap();
+            for value in [-0.5, 0.25, 1.0] {
+                let mut factorial = 1.0;
+                let expected = recurrence
+                    .terms(40)
+                    .iter()
+                    .enumerate()
+                    .map(|(n, a)| {
+                        factorial *= n.max(1) as f64;
+                        a.evaluate(&Default::default()).unwrap() * f64::powi(value, n as i32)
+                            / factorial
+                    })
+                    .sum::<f64>();
+                let actual = egf.evaluate(&at(value)).unwrap();
+                assert!(
+                    (actual - expected).abs() <= 1e-9 * expected.abs().max(1.0),
+                    "{} gave {} at x = {}, not {}",
+                    egf,
+                    actual,
+                    value,
+                    expected
+                );
+            }
+        };
+        check(&fibonacci);
+        check(&recurrence(&["4", "-4"], &["1", "4"]));
+        check(&recurrence(&["6", "-11", "6"], &["0", "1", "5"]));
+        check(&recurrence(&["0", "-1"], &["1", "0"]));
+        check(&recurrence(&["2", "-2"], &["1", "3/2"]));
+    }
+}
@@ -0,0 +1,151 @@
+//! This module describes array-valued symbols with symbolic integer indices.
+//!
+//! An indexed symbol such as `a[k + 1]` is a variable whose name records its base and indices,
+//! so everything that works on variables works on it unchanged: `a[3]` is bound like any other
+//! variable when evaluating, and differentiating by it is a partial derivative. The indices are
+//! remembered alongside the name, so [`OpArgument::substitute_indices`] can also replace the
+//! variables they mention, turning `a[k + 1]` into `a[4]` when `k` is `3`. That is how a
+//! [`crate::sum::Sum`] over `k` expands a stencil such as `a[k - 1] - 2·a[k] + a[k + 1]`.
+
+use ahash::HashMap;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+use crate::{
+    fold::Ratio,
+    metadata::VariableSet,
+    rewrite::Substitution,
+    symbols::{intern, variable, OpArgument},
+};
+
+/// The base and indices of an indexed symbol.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Indexed {
+    pub base: &'static str,
+    pub indices: Vec<OpArgument>,
+}
+
+/// The indexed symbols created so far, by name.
+static INDEXED: Lazy<Mutex<HashMap<&'static str, Indexed>>> = Lazy::new(Default::default);
+
+fn render(index: &OpArgument) -> String {
+    match Ratio::of(index) {
+        Some(Ratio { num, den: 1 }) => num.to_string(),
+        _ => index.to_string(),
+    }
+}
+
+/// The interned name of the symbol `base[indices]`, such as `a[k + 1]`. The indices are
+/// folded, so indices that fold to the same expression name the same symbol.
+pub fn indexed_name(base: &str, indices: &[OpArgument]) -> &'static str {
+    assert!(
+        !indices.is_empty(),
+        "Hold on, the indexed symbol {} needs at least one index",
+        base
+    );
+    assert!(
+        !base.contains(['[', ']']),
+        "Whoa there, {} can't be the base of an indexed symbol",
+        base
+    );
+    let indices = indices.iter().map(OpArgument::fold).collect::<Vec<_>>();
+    let rendered = indices.iter().map(render).collect::<Vec<_>>();
+    let name = intern(&format!("{}[{}]", base, rendered.join(", ")));
+    INDEXED.lock().entry(name).or_insert_with(|| Indexed {
+        base: intern(base),
+        indices,
+    });
+    name
+}
+
+/// The indexed symbol `base[indices]`.
+pub fn indexed(base: &str, indices: &[OpArgument]) -> OpArgument {
+    variable(indexed_name(base, indices))
+}
+
+/// The base and indices of the symbol `name`, if it was created by [`indexed`].
+pub fn as_indexed(name: &str) -> Option<Indexed> {
+    INDEXED.lock().get(name).cloned()
+}
+
+impl OpArgument {
+    /// The variables that the indices of the indexed symbols in `self` mention, which
+    /// [`OpArgument::free_variables`] does not include.
+    pub fn index_variables(&self) -> VariableSet {
+        let mut vars = VariableSet::new();
+        for name in self.free_variables().iter() {
+            for index in as_indexed(name).into_iter().flat_map(|s| s.indices) {
+                vars.union_with(&index.free_variables());
+                vars.union_with(&index.index_variables());
+            }
+        }
+        vars
+    }
+
+    /// Like [`OpArgument::substitute`], but also substitutes into the indices of indexed
+    /// symbols, so `a[k + 1]` becomes `a[4]` when `k` is replaced by `3`.
+    pub fn substitute_indices(&self, substitution: &Substitution) -> OpArgument {
+        let mut extended = substitution.clone();
+        for name in self.free_variables().iter() {
+            let Some(symbol) = as_indexed(name) else {
+                continue;
+            };
+            let mentioned = symbol
+                .indices
+                .iter()
+                .any(|index| substitution.keys().any(|var| depends(index, var)));
+            if mentioned && !substitution.contains_key(name) {
+                let indices = symbol
+                    .indices
+                    .iter()
+                    .map(|index| index.substitute_indices(substitution))
+                    .collect::<Vec<_>>();
+                extended.insert(name, indexed(symbol.base, &indices));
+            }
+        }
+        self.substitute(&extended)
+    }
+}
+
+/// Whether `expr` mentions `var`, directly or in an index.
+pub(crate) fn depends(expr: &OpArgument, var: &str) -> bool {
+    expr.free_variables().contains(var) || expr.index_variables().contains(var)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{as_indexed, indexed, indexed_name};
+    use crate::{
+        evaluation::Bindings, parse::parse, rewrite::Substitution, series::one, symbols::OpArgument,
+    };
+
+    #[test]
+    fn test_indexed_symbols() {
+        let p = |input: &str| parse(input).unwrap();
+        let int = |n: u64| OpArgument::from(crate::constants::Value::integer(n));
+
+        assert_eq!(p("a[k + 1]"), indexed("a", &[p("k + 1")]));
+        assert_eq!(p("a[3]"), indexed("a", &[int(3)]));
+        assert_eq!(p("a[3]").to_string(), "a[3]");
+        let symbol = as_indexed(indexed_name("m", &[p("k"), p("2")])).unwrap();
+        assert_eq!(
+            (symbol.base, symbol.indices),
+            ("m", vec![p("k"), p("2").fold()])
+        );
+
+        // Substituting the index renames the symbol, but leaves other symbols alone.
+        let stencil = p("a[k - 1] - 2*a[k] + a[k + 1] + b");
+        let at = stencil.substitute_indices(&Substitution::from_iter([("k", int(3))]));
+        assert_eq!(at, p("a[2] - 2*a[3] + a[4] + b"));
+        assert!(stencil.index_variables().contains("k"));
+        assert!(!stencil.free_variables().contains("k"));
+        let nested = p("a[b[k]]").substitute_indices(&Substitution::from_iter([("k", one())]));
+        assert_eq!(nested, p("a[b[1]]"));
+
+        // Once the indices are numbers, indexed symbols are ordinary variables.
+        let bindings =
+            Bindings::from_iter([("a[2]", 1.0), ("a[3]", 4.0), ("a[4]", 9.0), ("b", 0.5)]);
+        assert_eq!(at.evaluate(&bindings), Ok(2.5));
+        assert_eq!(at.derivative("a[3]").fold(), p("-2").fold());
+    }
+}
@@ -0,0 +1,185 @@
+//! This module exposes instrumentation counters for profiling our computational graph.
+//!
+//! The counters are only maintained when the `bench` feature is enabled; otherwise every hook
+//! compiles to nothing and [`counters`] always reports zeros.
+//!
+//! For observing long-running work rather than counting it, the `tracing` feature opens a span
+//! around parsing and around each simplification pass ([`OpArgument::fold`],
+//! [`OpArgument::canonicalize`], [`OpArgument::simplify_radicals`] and
+//! [`OpArgument::rewrite`]), and emits a debug event every time a rewrite rule fires.
+//!
+//! [`OpArgument::fold`]: crate::symbols::OpArgument::fold
+//! [`OpArgument::canonicalize`]: crate::symbols::OpArgument::canonicalize
+//! [`OpArgument::simplify_radicals`]: crate::symbols::OpArgument::simplify_radicals
+//! [`OpArgument::rewrite`]: crate::symbols::OpArgument::rewrite
+
+/// A snapshot of the instrumentation counters.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Counters {
+    /// Number of graph nodes constructed.
+    pub nodes_allocated: u64,
+    /// Number of node hashes computed (cache misses of [`OpArgument::hash`]).
+    ///
+    /// [`OpArgument::hash`]: crate::symbols::OpArgument::hash
+    pub hashes_computed: u64,
+    /// Number of nodes visited during numeric evaluation.
+    pub nodes_evaluated: u64,
+    /// Number of nodes visited by rewrite passes, each of which tries every rule on the node.
+    pub nodes_rewritten: u64,
+    /// Number of times a rewrite rule matched and replaced a node.
+    pub rules_fired: u64,
+}
+
+impl std::ops::Add for Counters {
+    type Output = Counters;
+
+    fn add(self, rhs: Counters) -> Counters {
+        Counters {
+            nodes_allocated: self.nodes_allocated + rhs.nodes_allocated,
+            hashes_computed: self.hashes_computed + rhs.hashes_computed,
+            nodes_evaluated: self.nodes_evaluated + rhs.nodes_evaluated,
+            nodes_rewritten: self.nodes_rewritten + rhs.nodes_rewritten,
+            rules_fired: self.rules_fired + rhs.rules_fired,
+        }
+    }
+}
+
+impl std::ops::Sub for Counters {
+    type Output = Counters;
+
+    fn sub(self, rhs: Counters) -> Counters {
+        Counters {
+            nodes_allocated: self.nodes_allocated.saturating_sub(rhs.nodes_allocated),
+            hashes_computed: self.hashes_computed.saturating_sub(rhs.hashes_computed),
+            nodes_evaluated: self.nodes_evaluated.saturating_sub(rhs.nodes_evaluated),
+            nodes_rewritten: self.nodes_rewritten.saturating_sub(rhs.nodes_rewritten),
+            rules_fired: self.rules_fired.saturating_sub(rhs.rules_fired),
+        }
+    }
+}
+
+#[cfg(feature = "bench")]
+mod counters {
+    use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+
+    use super::Counters;
+
+    static NODES_ALLOCATED: AtomicU64 = AtomicU64::new(0);
+    static HASHES_COMPUTED: AtomicU64 = AtomicU64::new(0);
+    static NODES_EVALUATED: AtomicU64 = AtomicU64::new(0);
+    static NODES_REWRITTEN: AtomicU64 = AtomicU64::new(0);
+    static RULES_FIRED: AtomicU64 = AtomicU64::new(0);
+
+    #[inline]
+    pub(crate) fn node_allocated() {
+        NODES_ALLOCATED.fetch_add(1, Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn hash_computed() {
+        HASHES_COMPUTED.fetch_add(1, Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn node_evaluated() {
+        NODES_EVALUATED.fetch_add(1, Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn node_rewritten() {
+        NODES_REWRITTEN.fetch_add(1, Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn rule_fired() {
+        RULES_FIRED.fetch_add(1, Relaxed);
+    }
+
+    pub fn counters() -> Counters {
+        Counters {
+            nodes_allocated: NODES_ALLOCATED.load(Relaxed),
+            hashes_computed: HASHES_COMPUTED.load(Relaxed),
+            nodes_evaluated: NODES_EVALUATED.load(Relaxed),
+            nodes_rewritten: NODES_REWRITTEN.load(Relaxed),
+            rules_fired: RULES_FIRED.load(Relaxed),
+        }
+    }
+
+    pub fn reset() {
+        NODES_ALLOCATED.store(0, Relaxed);
+        HASHES_COMPUTED.store(0, Relaxed);
+        NODES_EVALUATED.store(0, Relaxed);
+        NODES_REWRITTEN.store(0, Relaxed);
+        RULES_FIRED.store(0, Relaxed);
+    }
+}
+
+#[cfg(not(feature = "bench"))]
+mod counters {
+    use super::Counters;
+
+    #[inline(always)]
+    pub(crate) fn node_allocated() {}
+
+    #[inline(always)]
+    pub(crate) fn hash_computed() {}
+
+    #[inline(always)]
+    pub(crate) fn node_evaluated() {}
+
+    #[inline(always)]
+    pub(crate) fn node_rewritten() {}
+
+    #[inline(always)]
+    pub(crate) fn rule_fired() {}
+
+    pub fn counters() -> Counters {
+        Counters::default()
+    }
+
+    pub fn reset() {}
+}
+
+/// Returns the current value of every counter. The counters are process-wide.
+pub use counters::counters;
+/// Zeroes every counter.
+pub use counters::reset;
+pub(crate) use counters::{
+    hash_computed, node_allocated, node_evaluated, node_rewritten, rule_fired,
+};
+
+#[cfg(all(test, feature = "bench"))]
+mod tests {
+    use super::counters;
+    use crate::{
+        constants::Value,
+        evaluation::Bindings,
+        rewrite::Rule,
+        symbols::{variable, OpArgument},
+        template::hole,
+    };
+
+    #[test]
+    fn test_counters() {
+        let before = counters();
+        let x = variable("x");
+        let expr = x.sin() * x.cos();
+        let mut bindings = Bindings::default();
+        bindings.insert("x", 1.0);
+        expr.evaluate(&bindings).unwrap();
+        expr.hash();
+        expr.hash();
+        let a = hole("a");
+        let two = OpArgument::from(Value::integer(2));
+        let square = Rule::new("square", &a * &a, a.pow(&two)).unwrap();
+        (&expr * &expr).rewrite(&[square]);
+
+        // Other tests run concurrently, so only lower bounds are meaningful.
+        let delta = counters() - before;
+        assert!(delta.nodes_allocated >= 4);
+        assert!(delta.nodes_evaluated >= 5);
+        assert!(delta.hashes_computed >= 1);
+        assert!(delta.nodes_rewritten >= 4);
+        assert!(delta.rules_fired >= 1);
+    }
+}
@@ -0,0 +1,466 @@
+//! This module describes how to evaluate our computational graph over intervals.
+//!
+//! Every operation returns an interval containing the value of the operation at every point of
+//! its argument intervals. Bounds are widened by one ulp after each operation, so the result
+//! also contains the exactly rounded real value. Points outside an operation's real domain, such
+//! as the negative part of the argument of `ln`, are ignored.
+
+use std::{
+    fmt::Display,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+use ahash::HashMap;
+
+use crate::{
+    constants::Value,
+    evaluation::{EvaluationError, Scalar},
+    symbols::{
+        OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        OperationKind::{self, *},
+        StackVec,
+    },
+};
+
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+/// A closed interval of real numbers, possibly unbounded or empty.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+}
+
+/// A map from variable names to the ranges they take during interval evaluation.
+pub type IntervalBindings<'a> = HashMap<&'a str, Interval>;
+
+impl Interval {
+    pub const ENTIRE: Interval = Interval {
+        lo: f64::NEG_INFINITY,
+        hi: f64::INFINITY,
+    };
+    pub const EMPTY: Interval = Interval {
+        lo: f64::INFINITY,
+        hi: f64::NEG_INFINITY,
+    };
+
+    pub fn new(lo: f64, hi: f64) -> Self {
+        assert!(
+            lo <= hi,
+            "Whoa there, an interval needs lo <= hi, but got [{}, {}]",
+            lo,
+            hi
+        );
+        Interval { lo, hi }
+    }
+
+    /// The interval `[lo, hi]` an operation computed. An indeterminate form such as `∞ - ∞` at
+    /// an endpoint makes that bound NaN, and then nothing narrower than the entire line is
+    /// known to enclose the result.
+    fn computed(lo: f64, hi: f64) -> Self {
+        match lo.is_nan() || hi.is_nan() {
+            true => Interval::ENTIRE,
+            false => Interval::new(lo, hi),
+        }
+    }
+
+    pub fn point(x: f64) -> Self {
+        Interval::new(x, x)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lo.partial_cmp(&self.hi).is_none_or(|o| o.is_gt())
+    }
+
+    pub fn contains(&self, x: f64) -> bool {
+        self.lo <= x && x <= self.hi
+    }
+
+    pub fn width(&self) -> f64 {
+        self.hi - self.lo
+    }
+
+    pub fn midpoint(&self) -> f64 {
+        match (self.lo.is_finite(), self.hi.is_finite()) {
+            (true, true) => self.lo + (self.hi - self.lo) / 2.0,
+            _ if self.contains(0.0) => 0.0,
+            _ => self.lo.max(self.hi.min(0.0)),
+        }
+    }
+
+    /// The largest absolute value in the interval.
+    pub fn magnitude(&self) -> f64 {
+        self.lo.abs().max(self.hi.abs())
+    }
+
+    /// The smallest absolute value in the interval.
+    pub fn mignitude(&self) -> f64 {
+        if self.contains(0.0) {
+            0.0
+        } else {
+            self.lo.abs().min(self.hi.abs())
+        }
+    }
+
+    /// The smallest interval containing both `self` and `other`.
+    pub fn hull(&self, other: &Interval) -> Interval {
+        match (self.is_empty(), other.is_empty()) {
+            (true, _) => *other,
+            (_, true) => *self,
+            _ => Interval::new(self.lo.min(other.lo), self.hi.max(other.hi)),
+        }
+    }
+
+    pub fn intersect(&self, other: &Interval) -> Interval {
+        let (lo, hi) = (self.lo.max(other.lo), self.hi.min(other.hi));
+        if lo <= hi {
+            Interval { lo, hi }
+        } else {
+            Interval::EMPTY
+        }
+    }
+
+    /// Rounds the bounds outward by one ulp.
+    fn widen(self) -> Interval {
+        if self.is_empty() {
+            return self;
+        }
+        Interval {
+            lo: if self.lo.is_finite() {
+                self.lo.next_down()
+            } else {
+                self.lo
+            },
+            hi: if self.hi.is_finite() {
+                self.hi.next_up()
+            } else {
+                self.hi
+            },
+        }
+    }
+
+    fn monotone(self, f: impl Fn(f64) -> f64) -> Interval {
+        if self.is_empty() {
+            return self;
+        }
+        Interval::computed(f(self.lo), f(self.hi)).widen()
+    }
+
+    pub fn exp(self) -> Interval {
+        self.monotone(f64::exp)
+    }
+
+    pub fn ln(self) -> Interval {
+        self.intersect(&Interval::new(0.0, f64::INFINITY))
+            .monotone(f64::ln)
+    }
+
+    /// Whether `self` may contain `offset + k * period` for some integer `k`. Since `offset`,
+    /// `period` and the test itself are rounded, points just outside `self` count as well, by a
+    /// margin of a few ulps of the largest of them.
+    fn contains_periodic(&self, offset: f64, period: f64) -> bool {
+        let slack = (self.lo.abs().max(self.hi.abs()) + offset.abs() + period) * 8.0 * f64::EPSILON;
+        let (lo, hi) = (self.lo - slack, self.hi + slack);
+        let k = ((lo - offset) / period).ceil();
+        [k - 1.0, k].into_iter().any(|k| {
+            let point = offset + k * period;
+            lo <= point && point <= hi
+        })
+    }
+
+    /// `f(self)` for `f` either `sin` or `cos`, whose maxima lie at `max_at + 2kπ` and minima
+    /// at `max_at + π + 2kπ`. The endpoints are evaluated directly, since shifting the argument
+    /// would add a rounding error that grows with its size.
+    fn periodic(self, f: fn(f64) -> f64, max_at: f64) -> Interval {
+        if self.is_empty() {
+            return self;
+        }
+        if self.width() >= TAU || !self.width().is_finite() {
+            return Interval::new(-1.0, 1.0);
+        }
+        let (a, b) = (f(self.lo), f(self.hi));
+        let mut range = Interval::computed(a.min(b), a.max(b)).widen();
+        if self.contains_periodic(max_at, TAU) {
+            range.hi = 1.0;
+        }
+        if self.contains_periodic(max_at + PI, TAU) {
+            range.lo = -1.0;
+        }
+        range.intersect(&Interval::new(-1.0, 1.0))
+    }
+
+    pub fn sin(self) -> Interval {
+        self.periodic(f64::sin, FRAC_PI_2)
+    }
+
+    pub fn cos(self) -> Interval {
+        self.periodic(f64::cos, 0.0)
+    }
+
+    pub fn tan(self) -> Interval {
+        if self.is_empty() {
+            return self;
+        }
+        if self.width() >= PI || !self.width().is_finite() || self.contains_periodic(FRAC_PI_2, PI)
+        {
+            return Interval::ENTIRE;
+        }
+        self.monotone(f64::tan)
+    }
+
+    pub fn powf(self, exponent: Interval) -> Interval {
+        if self.is_empty() || exponent.is_empty() {
+            return Interval::EMPTY;
+        }
+        if exponent.lo == exponent.hi && exponent.lo.fract() == 0.0 {
+            return self.powi(exponent.lo);
+        }
+        let base = self.intersect(&Interval::new(0.0, f64::INFINITY));
+        (exponent * base.ln()).exp()
+    }
+
+    /// Raises `self` to an integer power `n`.
+    fn powi(self, n: f64) -> Interval {
+        if n == 0.0 {
+            return Interval::point(1.0);
+        } else if n < 0.0 {
+            return Interval::point(1.0) / self.powi(-n);
+        }
+        let (a, b) = (self.lo.powf(n), self.hi.powf(n));
+        if n % 2.0 == 1.0 || self.lo >= 0.0 {
+            Interval::computed(a, b).widen()
+        } else if self.hi <= 0.0 {
+            Interval::computed(b, a).widen()
+        } else {
+            // Even powers are exactly zero at zero.
+            Interval::new(0.0, a.max(b).next_up())
+        }
+    }
+
+    /// Applies `op` to its argument intervals.
+    pub fn apply(op: OperationKind, a: &[Interval]) -> Interval {
+        match op {
+            Addition => a[0] + a[1],
+            Subtraction => a[0] - a[1],
+            Multiplication => a[0] * a[1],
+            Division => a[0] / a[1],
+            Negation => -a[0],
+            Pow => a[0].powf(a[1]),
+            Exp => a[0].exp(),
+            Sin => a[0].sin(),
+            Cos => a[0].cos(),
+            Tan => a[0].tan(),
+            Ln => a[0].ln(),
+            Atan => a[0].monotone(f64::atan),
+            Sign => a[0].monotone(Scalar::sign),
+        }
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            f.write_str("∅")
+        } else {
+            write!(f, "[{}, {}]", self.lo, self.hi)
+        }
+    }
+}
+
+impl From<f64> for Interval {
+    fn from(x: f64) -> Self {
+        Interval::point(x)
+    }
+}
+
+impl Add for Interval {
+    type Output = Interval;
+
+    fn add(self, rhs: Interval) -> Interval {
+        if self.is_empty() || rhs.is_empty() {
+            return Interval::EMPTY;
+        }
+        Interval::computed(self.lo + rhs.lo, self.hi + rhs.hi).widen()
+    }
+}
+
+impl Sub for Interval {
+    type Output = Interval;
+
+    fn sub(self, rhs: Interval) -> Interval {
+        self + -rhs
+    }
+}
+
+impl Neg for Interval {
+    type Output = Interval;
+
+    fn neg(self) -> Interval {
+        Interval {
+            lo: -self.hi,
+            hi: -self.lo,
+        }
+    }
+}
+
+impl Mul for Interval {
+    type Output = Interval;
+
+    fn mul(self, rhs: Interval) -> Interval {
+        if self.is_empty() || rhs.is_empty() {
+            return Interval::EMPTY;
+        }
+        // 0 * inf only arises at an endpoint, where the limit of the product is 0.
+        let product = |a: f64, b: f64| if a == 0.0 || b == 0.0 { 0.0 } else { a * b };
+        let products = [
+            product(self.lo, rhs.lo),
+            product(self.lo, rhs.hi),
+            product(self.hi, rhs.lo),
+            product(self.hi, rhs.hi),
+        ];
+        let lo = products.into_iter().fold(f64::INFINITY, f64::min);
+        let hi = products.into_iter().fold(f64::NEG_INFINITY, f64::max);
+        Interval::new(lo, hi).widen()
+    }
+}
+
+impl Div for Interval {
+    type Output = Interval;
+
+    fn div(self, rhs: Interval) -> Interval {
+        if self.is_empty() || rhs.is_empty() {
+            return Interval::EMPTY;
+        }
+        if rhs.contains(0.0) {
+            return Interval::ENTIRE;
+        }
+        self * Interval::computed(1.0 / rhs.hi, 1.0 / rhs.lo).widen()
+    }
+}
+
+impl Value {
+    /// Encloses a single leaf, looking up variables in `bindings`.
+    pub fn evaluate_interval(
+        &self,
+        bindings: &IntervalBindings,
+    ) -> Result<Interval, EvaluationError> {
+        match *self {
+            Value::Rational(num, den) => {
+                let x = num as f64 / den.get() as f64;
+                let exact = den.get() == 1 && num < 1 << f64::MANTISSA_DIGITS;
+                Ok(if exact {
+                    Interval::point(x)
+                } else {
+                    Interval::point(x).widen()
+                })
+            }
+            Value::Pi => Ok(Interval::point(PI).widen()),
+            Value::E => Ok(Interval::point(std::f64::consts::E).widen()),
+            Value::Inf => Ok(Interval::point(f64::INFINITY)),
+            Value::Undefined => Ok(Interval::EMPTY),
+            Value::Algebraic(number) => {
+                let (lo, hi) = number.bounds();
+                Ok(Interval::new(lo, hi).widen())
+            }
+            Value::I => Err(EvaluationError::NotReal(*self)),
+            Value::Variable(name) => bindings
+                .get(name)
+                .copied()
+                .ok_or(EvaluationError::Unbound(name)),
+        }
+    }
+}
+
+impl OpArgument {
+    /// Computes an interval containing the value of the expression for every assignment of the
+    /// variables within their ranges in `bindings`.
+    pub fn evaluate_interval(
+        &self,
+        bindings: &IntervalBindings,
+    ) -> Result<Interval, EvaluationError> {
+        match &self.value {
+            Op(op) => {
+                let args = op
+                    .arguments
+                    .iter()
+                    .map(|arg| arg.evaluate_interval(bindings))
+                    .collect::<Result<StackVec<Interval>, _>>()?;
+                Ok(Interval::apply(op.op, &args))
+            }
+            Leaf(leaf) => leaf.evaluate_interval(bindings),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Interval, IntervalBindings};
+    use crate::{evaluation::Bindings, symbols::variable};
+
+    #[test]
+    fn test_interval_arithmetic() {
+        let a = Interval::new(-1.0, 2.0);
+        let b = Interval::new(3.0, 4.0);
+
+        let sum = a + b;
+        assert!(sum.contains(2.0) && sum.contains(6.0) && !sum.contains(6.1));
+        let product = a * b;
+        assert!(product.contains(-4.0) && product.contains(8.0) && !product.contains(-4.1));
+        assert_eq!(b / a, Interval::ENTIRE);
+        assert!((a * a).lo < -1.9);
+        assert!(a.powf(Interval::point(2.0)).lo == 0.0);
+
+        let sin = Interval::new(0.0, 2.0).sin();
+        assert_eq!(sin.hi, 1.0);
+        assert!(sin.lo <= 0.0 && sin.lo > -1e-12);
+        let cos = Interval::new(3.0, 3.5).cos();
+        assert_eq!(cos.lo, -1.0);
+        assert_eq!(Interval::new(1.0, 2.0).tan(), Interval::ENTIRE);
+        assert!(Interval::new(-1.0, 0.0).ln().hi == f64::NEG_INFINITY);
+        // Large arguments must not lose the enclosure to a rounded shift by π/2.
+        for i in 0..10000 {
+            let x = 1e5 + i as f64 * 0.37;
+            assert!(Interval::point(x).cos().contains(x.cos()), "cos({})", x);
+            assert!(Interval::point(x).sin().contains(x.sin()), "sin({})", x);
+        }
+        assert!(Interval::point(100000.0)
+            .cos()
+            .contains(-0.9993608074382124));
+
+        // ∞ - ∞ is indeterminate, which encloses nothing narrower than the whole line.
+        let inf = Interval::point(f64::INFINITY);
+        assert_eq!(inf - inf, Interval::ENTIRE);
+        assert_eq!(
+            Interval::new(f64::INFINITY, f64::INFINITY) + Interval::new(f64::NEG_INFINITY, 1.0),
+            Interval::ENTIRE
+        );
+    }
+
+    #[test]
+    fn test_evaluate_interval() {
+        let x = variable("x");
+        let y = variable("y");
+        let expr = (&x * &y).sin() + (&x - &y).exp() / &y;
+
+        let mut ranges = IntervalBindings::default();
+        ranges.insert("x", Interval::new(0.5, 1.5));
+        ranges.insert("y", Interval::new(1.0, 2.0));
+        let enclosure = expr.evaluate_interval(&ranges).unwrap();
+
+        let mut bindings = Bindings::default();
+        for i in 0..=10 {
+            for j in 0..=10 {
+                bindings.insert("x", 0.5 + i as f64 / 10.0);
+                bindings.insert("y", 1.0 + j as f64 / 10.0);
+                assert!(enclosure.contains(expr.evaluate(&bindings).unwrap()));
+            }
+        }
+
+        let indeterminate = crate::parse::parse("inf - inf").unwrap();
+        assert_eq!(
+            indeterminate.evaluate_interval(&ranges),
+            Ok(Interval::ENTIRE)
+        );
+    }
+}
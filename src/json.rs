@@ -0,0 +1,489 @@
+//! This module describes a JSON format for our computational graph, for services that are not
+//! written in Rust.
+//!
+//! Every node is an object with a `kind`. Operations have one of the kinds `add`, `sub`, `mul`,
+//! `div`, `neg`, `pow`, `exp`, `sin`, `cos`, `tan`, `ln` and `atan`, and their operands in
+//! `args`:
+//!
+//! ```json
+//! {"kind": "mul", "args": [{"kind": "rational", "value": "1/2"}, {"kind": "variable", "name": "x"}]}
+//! ```
+//!
+//! Leaves are tagged by their kind:
+//!
+//! - `{"kind": "rational", "value": "3/4"}`, with the value a string, so that no precision is
+//!   lost to the doubles of JavaScript. Integers are written without a denominator, and a
+//!   leading `-` is read as a negation.
+//! - `{"kind": "variable", "name": "x"}`.
+//! - `{"kind": "pi"}`, `{"kind": "e"}`, `{"kind": "i"}`, `{"kind": "inf"}` and
+//!   `{"kind": "undefined"}`.
+//! - `{"kind": "algebraic", "polynomial": ["-2", "0", "1"], "interval": ["1", "2"]}`, the root
+//!   of the polynomial with the given rational coefficients, lowest degree first, that lies in
+//!   the open interval.
+//!
+//! Readers ignore members they do not know, so the format can grow without breaking them.
+//! Shared subexpressions are written out in full; the binary format in [`crate::serialize`]
+//! stores them once.
+
+use std::fmt::{Display, Write};
+
+use crate::{
+    algebraic,
+    constants::Value,
+    fold::Ratio,
+    symbols::{
+        intern, variable, OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        Operation, OperationKind,
+        OperationKind::*,
+        StackVec,
+    },
+};
+
+/// The reasons reading the JSON format can fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JsonError {
+    /// The input is not JSON, at the given byte offset.
+    Syntax(usize),
+    /// The input is JSON, but not an expression.
+    Malformed(&'static str),
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::Syntax(offset) => write!(f, "invalid JSON at byte {}", offset),
+            JsonError::Malformed(what) => write!(f, "malformed {}", what),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+const KINDS: [(OperationKind, &str); 13] = [
+    (Addition, "add"),
+    (Subtraction, "sub"),
+    (Multiplication, "mul"),
+    (Division, "div"),
+    (Negation, "neg"),
+    (Pow, "pow"),
+    (Exp, "exp"),
+    (Sin, "sin"),
+    (Cos, "cos"),
+    (Tan, "tan"),
+    (Ln, "ln"),
+    (Atan, "atan"),
+    (Sign, "sign"),
+];
+
+/// `text` as a JSON string, with `<` escaped as well, so that it can sit in a `<script>`.
+pub(crate) fn string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() || c == '<' => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// The `kind` of operations of kind `op`.
+pub(crate) fn kind_name(op: OperationKind) -> &'static str {
+    let (_, name) = KINDS.iter().find(|(kind, _)| *kind == op).unwrap();
+    name
+}
+
+fn ratio(r: &Ratio) -> String {
+    match r.den {
+        1 => string(&r.num.to_string()),
+        den => string(&format!("{}/{}", r.num, den)),
+    }
+}
+
+fn write_node(out: &mut String, expr: &OpArgument) {
+    let leaf = |kind: &str| format!("{{\"kind\":\"{}\"}}", kind);
+    let text = match &expr.value {
+        Leaf(value) => match **value {
+            Value::Rational(num, den) => {
+                let r = Ratio::new(num as i128, den.get() as i128)
+                    .expect("Oops, a rational should have a nonzero denominator");
+                format!("{{\"kind\":\"rational\",\"value\":{}}}", ratio(&r))
+            }
+            Value::Variable(name) => format!("{{\"kind\":\"variable\",\"name\":{}}}", string(name)),
+            Value::Pi => leaf("pi"),
+            Value::E => leaf("e"),
+            Value::I => leaf("i"),
+            Value::Inf => leaf("inf"),
+            Value::Undefined => leaf("undefined"),
+            Value::Algebraic(number) => {
+                let (polynomial, (lo, hi)) = algebraic::encode(number);
+                let coefficients = polynomial.iter().map(ratio).collect::<Vec<_>>();
+                format!(
+                    "{{\"kind\":\"algebraic\",\"polynomial\":[{}],\"interval\":[{},{}]}}",
+                    coefficients.join(","),
+                    ratio(&lo),
+                    ratio(&hi)
+                )
+            }
+        },
+        Op(op) => {
+            let _ = write!(out, "{{\"kind\":\"{}\",\"args\":[", kind_name(op.op));
+            for (i, arg) in op.arguments.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_node(out, arg);
+            }
+            out.push_str("]}");
+            return;
+        }
+    };
+    out.push_str(&text);
+}
+
+impl OpArgument {
+    /// Writes `self` in the JSON format described in [`crate::json`].
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        write_node(&mut out, self);
+        out
+    }
+
+    /// Reads an expression in the JSON format described in [`crate::json`].
+    pub fn from_json(input: &str) -> Result<OpArgument, JsonError> {
+        let mut reader = Reader {
+            input: input.as_bytes(),
+            position: 0,
+        };
+        let json = reader.value()?;
+        reader.whitespace();
+        if reader.position != input.len() {
+            return Err(JsonError::Syntax(reader.position));
+        }
+        node(&json)
+    }
+}
+
+/// A parsed JSON value.
+enum Json {
+    Null,
+    Bool,
+    Number,
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+struct Reader<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn error<T>(&self) -> Result<T, JsonError> {
+        Err(JsonError::Syntax(self.position))
+    }
+
+    fn whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.position).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), JsonError> {
+        self.whitespace();
+        match self.peek() == Some(byte) {
+            true => {
+                self.position += 1;
+                Ok(())
+            }
+            false => self.error(),
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, JsonError> {
+        match self.input[self.position..].starts_with(word.as_bytes()) {
+            true => {
+                self.position += word.len();
+                Ok(value)
+            }
+            false => self.error(),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, JsonError> {
+        self.whitespace();
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b't') => self.literal("true", Json::Bool),
+            Some(b'f') => self.literal("false", Json::Bool),
+            Some(b'-' | b'0'..=b'9') => {
+                let start = self.position;
+                while matches!(
+                    self.peek(),
+                    Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+                ) {
+                    self.position += 1;
+                }
+                let text = std::str::from_utf8(&self.input[start..self.position]).unwrap();
+                match text.parse::<f64>() {
+                    Ok(_) => Ok(Json::Number),
+                    Err(_) => Err(JsonError::Syntax(start)),
+                }
+            }
+            _ => self.error(),
+        }
+    }
+
+    /// Reads the items of a list between `open` and `close`, separated by commas.
+    fn list(
+        &mut self,
+        open: u8,
+        close: u8,
+        mut item: impl FnMut(&mut Self) -> Result<(), JsonError>,
+    ) -> Result<(), JsonError> {
+        self.expect(open)?;
+        self.whitespace();
+        if self.peek() == Some(close) {
+            self.position += 1;
+            return Ok(());
+        }
+        loop {
+            item(self)?;
+            self.whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(c) if c == close => {
+                    self.position += 1;
+                    return Ok(());
+                }
+                _ => return self.error(),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, JsonError> {
+        let mut members = Vec::new();
+        self.list(b'{', b'}', |reader| {
+            reader.whitespace();
+            let key = reader.string()?;
+            reader.expect(b':')?;
+            members.push((key, reader.value()?));
+            Ok(())
+        })?;
+        Ok(Json::Object(members))
+    }
+
+    fn array(&mut self) -> Result<Json, JsonError> {
+        let mut items = Vec::new();
+        self.list(b'[', b']', |reader| {
+            items.push(reader.value()?);
+            Ok(())
+        })?;
+        Ok(Json::Array(items))
+    }
+
+    fn hex(&mut self) -> Result<u32, JsonError> {
+        let digits = self.input.get(self.position..self.position + 4);
+        let code = digits
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok());
+        match code {
+            Some(code) => {
+                self.position += 4;
+                Ok(code)
+            }
+            None => self.error(),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        if self.peek() != Some(b'"') {
+            return self.error();
+        }
+        self.position += 1;
+        let mut bytes = Vec::new();
+        loop {
+            let Some(byte) = self.peek() else {
+                return self.error();
+            };
+            self.position += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let Some(escaped) = self.peek() else {
+                        return self.error();
+                    };
+                    self.position += 1;
+                    let c = match escaped {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.hex()?;
+                            // A surrogate pair spells a character outside the basic plane.
+                            if (0xd800..0xdc00).contains(&code)
+                                && self.input[self.position..].starts_with(b"\\u")
+                            {
+                                self.position += 2;
+                                let low = self.hex()?;
+                                code = 0x10000
+                                    + ((code - 0xd800) << 10)
+                                    + (low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+                            match char::from_u32(code) {
+                                Some(c) => c,
+                                None => return self.error(),
+                            }
+                        }
+                        _ => return self.error(),
+                    };
+                    bytes.extend(c.to_string().as_bytes());
+                }
+                byte => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| JsonError::Syntax(self.position))
+    }
+}
+
+/// Reads a rational written as `"n"` or `"n/d"`.
+fn read_ratio(json: &Json) -> Result<Ratio, JsonError> {
+    let malformed = JsonError::Malformed("rational");
+    let text = json.str().ok_or(malformed.clone())?;
+    let (num, den) = text.split_once('/').unwrap_or((text, "1"));
+    let parse = |digits: &str| digits.trim().parse::<i128>().map_err(|_| malformed.clone());
+    Ratio::new(parse(num)?, parse(den)?).ok_or(malformed.clone())
+}
+
+fn node(json: &Json) -> Result<OpArgument, JsonError> {
+    let kind = json
+        .get("kind")
+        .and_then(Json::str)
+        .ok_or(JsonError::Malformed("node"))?;
+    Ok(match kind {
+        "rational" => {
+            let r = read_ratio(json.get("value").ok_or(JsonError::Malformed("rational"))?)?;
+            r.to_oparg().ok_or(JsonError::Malformed("rational"))?
+        }
+        "variable" => {
+            let name = json.get("name").and_then(Json::str);
+            variable(intern(name.ok_or(JsonError::Malformed("variable"))?))
+        }
+        "pi" => Value::Pi.into(),
+        "e" => Value::E.into(),
+        "i" => Value::I.into(),
+        "inf" => Value::Inf.into(),
+        "undefined" => Value::Undefined.into(),
+        "algebraic" => {
+            let malformed = JsonError::Malformed("algebraic number");
+            let list = |key: &str| json.get(key).and_then(Json::array).ok_or(malformed.clone());
+            let polynomial = list("polynomial")?
+                .iter()
+                .map(read_ratio)
+                .collect::<Result<Vec<_>, _>>()?;
+            let [lo, hi] = list("interval")? else {
+                return Err(malformed);
+            };
+            algebraic::decode(polynomial, (read_ratio(lo)?, read_ratio(hi)?)).ok_or(malformed)?
+        }
+        kind => {
+            let (op, _) = KINDS
+                .iter()
+                .find(|(_, name)| *name == kind)
+                .ok_or(JsonError::Malformed("kind"))?;
+            let args = json
+                .get("args")
+                .and_then(Json::array)
+                .ok_or(JsonError::Malformed("arguments"))?;
+            if args.len() != op.argcount() {
+                return Err(JsonError::Malformed("arguments"));
+            }
+            let arguments = args.iter().map(node).collect::<Result<StackVec<_>, _>>()?;
+            Operation::new(*op, arguments).into()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsonError;
+    use crate::{algebraic::real_roots, parse::parse, symbols::OpArgument};
+
+    #[test]
+    fn test_json() {
+        let expr = parse("sin(x) * 3/4 - -y^(1/2) + pi / inf").unwrap();
+        let json = expr.to_json();
+        assert_eq!(OpArgument::from_json(&json), Ok(expr));
+        assert_eq!(
+            parse("x / 2").unwrap().to_json(),
+            r#"{"kind":"div","args":[{"kind":"variable","name":"x"},{"kind":"rational","value":"2"}]}"#
+        );
+
+        // Whitespace, unknown members, escapes and negative rationals are all fine.
+        let typed = r#" { "kind" : "add", "note": [1, 2.5e3, null, true],
+            "args": [ {"kind": "variable", "name": "x"}, {"kind": "rational", "value": "-3/6"} ] } "#;
+        assert_eq!(
+            OpArgument::from_json(typed),
+            Ok(parse("x + -(1/2)").unwrap().fold())
+        );
+
+        let root = &real_roots(&parse("x^2 - 2").unwrap(), "x").unwrap()[1];
+        assert_eq!(OpArgument::from_json(&root.to_json()).as_ref(), Ok(root));
+
+        assert_eq!(
+            OpArgument::from_json("{\"kind\": \"add\""),
+            Err(JsonError::Syntax(14))
+        );
+        assert_eq!(
+            OpArgument::from_json(r#"{"kind": "sin", "args": []}"#),
+            Err(JsonError::Malformed("arguments"))
+        );
+        assert_eq!(
+            OpArgument::from_json(r#"{"kind": "rational", "value": "1/0"}"#),
+            Err(JsonError::Malformed("rational"))
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! This module describes functions over our computational graph: expressions with bound
+//! parameters.
+//!
+//! A [`Lambda`] binds its parameters in its body, so they are not free variables of the
+//! function. Substituting into a lambda renames its parameters whenever a replacement mentions
+//! them, so free variables are never captured.
+
+use std::fmt::Display;
+
+use crate::{
+    metadata::VariableSet,
+    rewrite::Substitution,
+    symbols::{intern, variable, OpArgument},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lambda {
+    params: Vec<&'static str>,
+    body: OpArgument,
+}
+
+/// Returns a variant of `name` that is not in `avoid`, by appending primes.
+fn fresh(name: &'static str, avoid: &VariableSet) -> &'static str {
+    let mut candidate = name;
+    while avoid.contains(candidate) {
+        candidate = intern(&format!("{}'", candidate));
+    }
+    candidate
+}
+
+impl Lambda {
+    pub fn new(params: Vec<&'static str>, body: OpArgument) -> Self {
+        assert!(
+            params
+                .iter()
+                .enumerate()
+                .all(|(i, p)| !params[..i].contains(p)),
+            "Whoa there, a lambda can't bind the same parameter twice: {:?}",
+            params
+        );
+        Lambda { params, body }
+    }
+
+    pub fn params(&self) -> &[&'static str] {
+        &self.params
+    }
+
+    pub fn body(&self) -> &OpArgument {
+        &self.body
+    }
+
+    pub fn arity(&self) -> usize {
+        self.params.len()
+    }
+
+    /// The variables the body uses that are not parameters.
+    pub fn free_variables(&self) -> VariableSet {
+        self.body
+            .free_variables()
+            .iter()
+            .filter(|var| !self.params.contains(var))
+            .collect()
+    }
+
+    /// Substitutes `args` for the parameters in the body.
+    pub fn apply(&self, args: &[OpArgument]) -> OpArgument {
+        assert_eq!(
+            args.len(),
+            self.arity(),
+            "Oops, this lambda takes {} arguments but was given {}",
+            self.arity(),
+            args.len()
+        );
+        let substitution: Substitution = self
+            .params
+            .iter()
+            .copied()
+            .zip(args.iter().cloned())
+            .collect();
+        self.body.substitute(&substitution)
+    }
+
+    /// Substitutes into the free variables of the lambda, renaming parameters that a
+    /// replacement mentions so that they do not capture it.
+    pub fn substitute(&self, substitution: &Substitution) -> Lambda {
+        let mut avoid = self.body.free_variables();
+        let mut inner: Substitution = substitution
+            .iter()
+            .filter(|(name, _)| !self.params.contains(name))
+            .map(|(&name, expr)| {
+                avoid.union_with(&expr.free_variables());
+                (name, expr.clone())
+            })
+            .collect();
+
+        let captured = |param: &str| {
+            inner
+                .values()
+                .any(|expr| expr.free_variables().contains(param))
+        };
+        let renames = self
+            .params
+            .iter()
+            .filter(|param| captured(param))
+            .copied()
+            .collect::<Vec<_>>();
+        let mut params = self.params.clone();
+        for param in renames {
+            let renamed = fresh(param, &avoid);
+            avoid.insert(renamed);
+            inner.insert(param, variable(renamed));
+            *params.iter_mut().find(|p| **p == param).unwrap() = renamed;
+        }
+
+        Lambda {
+            params,
+            body: self.body.substitute(&inner),
+        }
+    }
+
+    /// The function `x ↦ self(g(x))`, taking the parameters of `g`, renamed where they would
+    /// capture a free variable of `self`.
+    pub fn compose(&self, g: &Lambda) -> Lambda {
+        assert_eq!(
+            self.arity(),
+            1,
+            "Hold on, only a function of one argument can be composed after another"
+        );
+        let free = self.free_variables();
+        let mut avoid = free.clone();
+        avoid.union_with(&g.body.free_variables());
+        for &param in &g.params {
+            avoid.insert(param);
+        }
+        let mut renames = Substitution::default();
+        let params = g
+            .params
+            .iter()
+            .map(|&param| match free.contains(param) {
+                true => {
+                    let renamed = fresh(param, &avoid);
+                    avoid.insert(renamed);
+                    renames.insert(param, variable(renamed));
+                    renamed
+                }
+                false => param,
+            })
+            .collect();
+        Lambda {
+            params,
+            body: self.apply(&[g.body.substitute(&renames)]),
+        }
+    }
+
+    /// The function applying `self` `n` times, so `iterate(0)` is the identity.
+    pub fn iterate(&self, n: usize) -> Lambda {
+        let identity = Lambda::new(self.params.clone(), variable(self.params[0]));
+        (0..n).fold(identity, |acc, _| self.compose(&acc))
+    }
+}
+
+impl Display for Lambda {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}) ↦ {}", self.params.join(", "), self.body)
+    }
+}
+
+/// The function `x ↦ f(g(x))`.
+pub fn compose(f: &Lambda, g: &Lambda) -> Lambda {
+    f.compose(g)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compose, Lambda};
+    use crate::{
+        constants::Value,
+        rewrite::Substitution,
+        symbols::{variable, OpArgument},
+    };
+
+    #[test]
+    fn test_lambda() {
+        let x = variable("x");
+        let y = variable("y");
+        let two = OpArgument::from(Value::integer(2));
+
+        let f = Lambda::new(vec!["x", "y"], &x * &y + &x);
+        assert_eq!(f.apply(&[y.clone(), x.clone()]), &y * &x + &y);
+        assert!(f.free_variables().is_empty());
+
+        // Substituting x for the free y must not capture it under the parameter x.
+        let g = Lambda::new(vec!["x"], &x + &y);
+        let mut substitution = Substitution::default();
+        substitution.insert("y", x.clone());
+        let h = g.substitute(&substitution);
+        assert_eq!(h.params(), ["x'"]);
+        assert_eq!(h.apply(std::slice::from_ref(&two)), &two + &x);
+
+        // Bound parameters are not substituted.
+        substitution.insert("x", y.clone());
+        substitution.remove("y");
+        assert_eq!(g.substitute(&substitution), g);
+    }
+
+    #[test]
+    fn test_compose() {
+        let x = variable("x");
+        let two = OpArgument::from(Value::integer(2));
+
+        let square = Lambda::new(vec!["x"], x.pow(&two));
+        let sin = Lambda::new(vec!["x"], x.sin());
+        assert_eq!(compose(&square, &sin).body(), &x.sin().pow(&two));
+        assert_eq!(compose(&sin, &square).body(), &x.pow(&two).sin());
+
+        let double = Lambda::new(vec!["x"], &two * &x);
+        assert_eq!(double.iterate(0).body(), &x);
+        assert_eq!(double.iterate(3).body(), &(&two * (&two * (&two * &x))));
+
+        // The free y of the outer function must not be captured by the parameter y.
+        let y = variable("y");
+        let shift = Lambda::new(vec!["x"], &x + &y);
+        let scale = Lambda::new(vec!["y"], &two * &y);
+        let composed = compose(&shift, &scale);
+        assert_eq!(composed.params(), ["y'"]);
+        assert_eq!(composed.body(), &(&two * variable("y'") + &y));
+        assert_eq!(composed.apply(std::slice::from_ref(&x)), &two * &x + &y);
+    }
+}
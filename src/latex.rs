@@ -0,0 +1,185 @@
+//! This module describes how to write our computational graph as LaTeX math.
+//!
+//! [`OpArgument::to_latex`] emits math mode source that KaTeX and MathJax render as well as TeX
+//! does. It follows the Typst printer: quotients are `\frac`, so they need no parentheses,
+//! products are juxtaposed unless a number follows, Greek names become their letters, a letter
+//! followed by digits gets the digits as a subscript, and longer names are set upright.
+
+use crate::{
+    constants::Value,
+    render::needs_parens,
+    symbols::{
+        base_name, OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        Operation,
+        OperationKind::*,
+    },
+};
+
+/// The Greek letters, with an uppercase command in LaTeX when they look different from Latin
+/// ones.
+const GREEK: [(&str, bool); 24] = [
+    ("alpha", false),
+    ("beta", false),
+    ("gamma", true),
+    ("delta", true),
+    ("epsilon", false),
+    ("zeta", false),
+    ("eta", false),
+    ("theta", true),
+    ("iota", false),
+    ("kappa", false),
+    ("lambda", true),
+    ("mu", false),
+    ("nu", false),
+    ("xi", true),
+    ("omicron", false),
+    ("pi", true),
+    ("rho", false),
+    ("sigma", true),
+    ("tau", false),
+    ("upsilon", true),
+    ("phi", true),
+    ("chi", false),
+    ("psi", true),
+    ("omega", true),
+];
+
+fn letter(name: &str) -> Option<String> {
+    if name.chars().count() == 1 && name.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Some(name.to_string());
+    }
+    let lower = name.to_lowercase();
+    let &(_, has_upper) = GREEK.iter().find(|(greek, _)| *greek == lower)?;
+    match name == lower {
+        true => Some(format!("\\{}", name)),
+        false if has_upper && name[1..] == lower[1..] => Some(format!("\\{}", name)),
+        false => None,
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\\' => "\\textbackslash{}".to_string(),
+            '_' | '{' | '}' | '&' | '%' | '$' | '#' => format!("\\{}", c),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+fn name(name: &str) -> String {
+    let split = name
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(name.len());
+    let (head, digits) = name.split_at(split);
+    let subscript = digits.chars().all(|c| c.is_ascii_digit());
+    match (letter(head), subscript) {
+        (Some(head), true) if !digits.is_empty() => format!("{}_{{{}}}", head, digits),
+        (Some(head), true) => head,
+        _ => format!("\\mathrm{{{}}}", escape(name)),
+    }
+}
+
+fn value(value: &Value) -> String {
+    match *value {
+        Value::Rational(num, den) if den.get() == 1 => num.to_string(),
+        Value::Rational(num, den) => format!("\\frac{{{}}}{{{}}}", num, den),
+        Value::Pi => "\\pi".to_string(),
+        Value::E => "e".to_string(),
+        Value::I => "i".to_string(),
+        Value::Inf => "\\infty".to_string(),
+        Value::Undefined => "\\text{undefined}".to_string(),
+        Value::Variable(v) => name(base_name(v)),
+        Value::Algebraic(number) => format!("\\text{{{}}}", escape(&number.to_string())),
+    }
+}
+
+fn parenthesized(text: String) -> String {
+    format!("\\left({}\\right)", text)
+}
+
+fn operation(op: &Operation) -> String {
+    let arg = |i: usize| latex(&op.arguments[i]);
+    match op.op {
+        Division => format!("\\frac{{{}}}{{{}}}", arg(0), arg(1)),
+        Pow if matches!(&op.arguments[1].value, Leaf(v) if **v == Value::rational(1, 2)) => {
+            format!("\\sqrt{{{}}}", arg(0))
+        }
+        Pow => {
+            let base = match &op.arguments[0].value {
+                Op(_) => parenthesized(arg(0)),
+                Leaf(_) => arg(0),
+            };
+            format!("{}^{{{}}}", base, arg(1))
+        }
+        Negation => match &op.arguments[0].value {
+            Op(child) if matches!(child.op, Addition | Subtraction | Multiplication) => {
+                format!("-{}", parenthesized(arg(0)))
+            }
+            _ => format!("-{}", arg(0)),
+        },
+        Addition | Subtraction | Multiplication => {
+            let part = |i: usize| {
+                let is_fraction = matches!(&op.arguments[i].value, Op(o) if o.op == Division);
+                match needs_parens(op.op, &op.arguments[i], i) && !is_fraction {
+                    true => parenthesized(arg(i)),
+                    false => arg(i),
+                }
+            };
+            let (lhs, rhs) = (part(0), part(1));
+            let operator = match op.op {
+                Addition => " + ",
+                Subtraction => " - ",
+                // A number after a factor would run into it.
+                _ if rhs.starts_with(|c: char| c.is_ascii_digit()) => " \\cdot ",
+                _ => " ",
+            };
+            format!("{}{}{}", lhs, operator, rhs)
+        }
+        Exp => format!("e^{{{}}}", arg(0)),
+        Atan => format!("\\arctan{}", parenthesized(arg(0))),
+        Sign => format!("\\operatorname{{sgn}}{}", parenthesized(arg(0))),
+        Sin | Cos | Tan | Ln => format!("\\{}{}", op.op, parenthesized(arg(0))),
+    }
+}
+
+fn latex(expr: &OpArgument) -> String {
+    match &expr.value {
+        Op(op) => operation(op),
+        Leaf(v) => value(v),
+    }
+}
+
+impl OpArgument {
+    /// Writes `self` as LaTeX math mode source.
+    pub fn to_latex(&self) -> String {
+        latex(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::parse;
+
+    #[test]
+    fn test_to_latex() {
+        let latex = |input: &str| parse(input).unwrap().to_latex();
+
+        assert_eq!(latex("(x + 1) / (2 * y)"), "\\frac{x + 1}{2 y}");
+        assert_eq!(
+            latex("3 * x^2 - sin(Theta)"),
+            "3 x^{2} - \\sin\\left(\\Theta\\right)"
+        );
+        assert_eq!(latex("x * 2 * (a - b)"), "x \\cdot 2 \\left(a - b\\right)");
+        assert_eq!(latex("(a + b)^(n + 1)"), "\\left(a + b\\right)^{n + 1}");
+        assert_eq!(
+            latex("exp(-x1) + sqrt(max_rate)"),
+            "e^{-x_{1}} + \\sqrt{\\mathrm{max\\_rate}}"
+        );
+        assert_eq!(
+            latex("0.25 * inf + Alpha"),
+            "\\frac{1}{4} \\infty + \\mathrm{Alpha}"
+        );
+    }
+}
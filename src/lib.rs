@@ -4,3 +4,81 @@ pub mod operations;
 pub mod equivalencies;
 pub mod rewrite;
 pub mod operation_properties;
+pub mod evaluation;
+pub mod export;
+pub mod render;
+pub mod parse;
+pub mod instrument;
+pub mod metadata;
+pub mod digest;
+pub mod statistics;
+pub mod codegen;
+pub mod compile;
+pub mod precision;
+#[cfg(feature = "precise")]
+pub mod precise;
+pub mod interval;
+pub mod analysis;
+pub mod template;
+pub mod lambda;
+pub mod solve;
+pub mod context;
+pub mod serialize;
+pub mod compare;
+pub mod provenance;
+pub mod fold;
+pub mod condition;
+pub mod set;
+pub mod sum;
+pub(crate) mod roots;
+pub(crate) mod polynomial;
+pub mod recurrence;
+pub mod series;
+pub mod generating;
+pub mod number;
+pub mod continued_fraction;
+pub mod approx;
+pub mod fourier;
+pub mod signal;
+pub mod control;
+pub mod derivative;
+pub mod neural;
+pub mod array;
+pub mod matrix;
+pub mod search;
+pub mod fit;
+pub(crate) mod random;
+pub mod uncertainty;
+pub mod numeric;
+pub mod optimize;
+pub mod constraint;
+pub mod linear;
+pub mod boolean;
+pub mod tape;
+pub mod memo;
+pub mod sample;
+pub mod canonical;
+pub mod collect;
+pub mod rational;
+pub mod radical;
+pub mod algebraic;
+pub mod recognize;
+pub mod abbreviate;
+pub mod pretty;
+pub mod typst;
+pub mod latex;
+pub mod json;
+pub mod piecewise;
+pub mod mathml;
+pub mod modelica;
+pub mod matlab;
+pub mod verify;
+pub mod anonymize;
+pub mod corpus;
+pub mod budget;
+pub mod trig;
+pub mod special;
+pub mod quaternion;
+pub mod differential;
+pub mod indexed;
+pub mod sparse;
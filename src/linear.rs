@@ -0,0 +1,430 @@
+//! This module describes conjunctions of linear constraints with rational coefficients.
+//!
+//! Each [`LinearConstraint`] is kept as `c₁x₁ + … + cₙxₙ + c ⋈ 0`, with `⋈` one of `<`, `≤` or
+//! `=`, and exact rational coefficients. [`eliminate`] removes a variable by Fourier–Motzkin
+//! elimination: an equation mentioning it is solved for it and substituted into the rest, and
+//! otherwise every lower bound on it is paired with every upper bound. The result holds
+//! exactly where some value of the variable satisfies the original constraints, so eliminating
+//! every variable decides feasibility, and eliminating some of them projects the region onto
+//! the others.
+//!
+//! The number of constraints can grow quadratically with each elimination, which is fine for
+//! the handful of assumptions this is meant for. Duplicates are dropped along the way.
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use crate::{
+    constants::Value,
+    evaluation::{Bindings, EvaluationError},
+    fold::Ratio,
+    series::{plus, times},
+    symbols::{
+        variable, OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        OperationKind::*,
+    },
+};
+
+/// The reasons linear constraints can fail to be built or eliminated.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinearError {
+    /// The expression is not affine in its variables with rational coefficients.
+    NotLinear(OpArgument),
+    /// A coefficient grew too large to represent exactly.
+    Overflow,
+}
+
+impl Display for LinearError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinearError::NotLinear(expr) => write!(f, "{} is not linear", expr),
+            LinearError::Overflow => f.write_str("a coefficient overflowed"),
+        }
+    }
+}
+
+impl std::error::Error for LinearError {}
+
+/// How the left-hand side of a normalized [`LinearConstraint`] compares to zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Relation {
+    Less,
+    LessEqual,
+    Equal,
+}
+
+impl Display for Relation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Relation::Less => "<",
+            Relation::LessEqual => "≤",
+            Relation::Equal => "=",
+        })
+    }
+}
+
+/// `Σ terms[x]·x + constant`
+#[derive(Clone, Debug, PartialEq)]
+struct Affine {
+    terms: BTreeMap<&'static str, Ratio>,
+    constant: Ratio,
+}
+
+impl Affine {
+    fn constant(constant: Ratio) -> Self {
+        Affine {
+            terms: BTreeMap::new(),
+            constant,
+        }
+    }
+
+    fn is_constant(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    fn coefficient(&self, var: &str) -> Ratio {
+        self.terms.get(var).copied().unwrap_or(Ratio::ZERO)
+    }
+
+    fn plus(&self, rhs: &Affine) -> Option<Affine> {
+        let mut sum = self.clone();
+        for (&var, &c) in &rhs.terms {
+            let c = sum.coefficient(var).checked_add(c)?;
+            match c == Ratio::ZERO {
+                true => sum.terms.remove(var),
+                false => sum.terms.insert(var, c),
+            };
+        }
+        sum.constant = sum.constant.checked_add(rhs.constant)?;
+        Some(sum)
+    }
+
+    fn scaled(&self, k: Ratio) -> Option<Affine> {
+        if k == Ratio::ZERO {
+            return Some(Affine::constant(Ratio::ZERO));
+        }
+        let terms = self
+            .terms
+            .iter()
+            .map(|(&var, &c)| Some((var, c.checked_mul(k)?)))
+            .collect::<Option<_>>()?;
+        Some(Affine {
+            terms,
+            constant: self.constant.checked_mul(k)?,
+        })
+    }
+
+    /// Reads `expr` as an affine function of its variables.
+    fn of(expr: &OpArgument) -> Result<Affine, LinearError> {
+        let not_linear = || LinearError::NotLinear(expr.clone());
+        let overflow = |a: Option<Affine>| a.ok_or(LinearError::Overflow);
+        let op = match &expr.value {
+            Leaf(value) => {
+                return match **value {
+                    Value::Variable(name) => Ok(Affine {
+                        terms: BTreeMap::from_iter([(name, Ratio::ONE)]),
+                        constant: Ratio::ZERO,
+                    }),
+                    _ => Ratio::of(expr).map(Affine::constant).ok_or_else(not_linear),
+                }
+            }
+            Op(op) => op,
+        };
+        let a = Affine::of(&op.arguments[0])?;
+        let b = || Affine::of(&op.arguments[1]);
+        match op.op {
+            Addition => overflow(a.plus(&b()?)),
+            Subtraction => overflow(a.plus(&overflow(b()?.scaled(-Ratio::ONE))?)),
+            Negation => overflow(a.scaled(-Ratio::ONE)),
+            Multiplication => match (a, b()?) {
+                (a, b) if a.is_constant() => overflow(b.scaled(a.constant)),
+                (a, b) if b.is_constant() => overflow(a.scaled(b.constant)),
+                _ => Err(not_linear()),
+            },
+            Division => match b()? {
+                b if b.is_constant() && b.constant != Ratio::ZERO => {
+                    overflow(a.scaled(b.constant.recip().ok_or(LinearError::Overflow)?))
+                }
+                _ => Err(not_linear()),
+            },
+            _ => Err(not_linear()),
+        }
+    }
+}
+
+/// A linear equation or inequality, normalized to `lhs ⋈ 0`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinearConstraint {
+    lhs: Affine,
+    relation: Relation,
+}
+
+impl LinearConstraint {
+    /// The constraint `lhs ⋈ rhs`.
+    pub fn new(
+        lhs: &OpArgument,
+        relation: Relation,
+        rhs: &OpArgument,
+    ) -> Result<Self, LinearError> {
+        let rhs = Affine::of(rhs)?
+            .scaled(-Ratio::ONE)
+            .ok_or(LinearError::Overflow)?;
+        let lhs = Affine::of(lhs)?.plus(&rhs).ok_or(LinearError::Overflow)?;
+        LinearConstraint { lhs, relation }.normalized()
+    }
+
+    /// `lhs = rhs`
+    pub fn equal(lhs: &OpArgument, rhs: &OpArgument) -> Result<Self, LinearError> {
+        LinearConstraint::new(lhs, Relation::Equal, rhs)
+    }
+
+    /// `lhs ≤ rhs`
+    pub fn less_equal(lhs: &OpArgument, rhs: &OpArgument) -> Result<Self, LinearError> {
+        LinearConstraint::new(lhs, Relation::LessEqual, rhs)
+    }
+
+    /// `lhs < rhs`
+    pub fn less(lhs: &OpArgument, rhs: &OpArgument) -> Result<Self, LinearError> {
+        LinearConstraint::new(lhs, Relation::Less, rhs)
+    }
+
+    /// `lhs ≥ rhs`
+    pub fn greater_equal(lhs: &OpArgument, rhs: &OpArgument) -> Result<Self, LinearError> {
+        LinearConstraint::new(rhs, Relation::LessEqual, lhs)
+    }
+
+    /// `lhs > rhs`
+    pub fn greater(lhs: &OpArgument, rhs: &OpArgument) -> Result<Self, LinearError> {
+        LinearConstraint::new(rhs, Relation::Less, lhs)
+    }
+
+    /// Scales the constraint so that its leading coefficient, or else its constant, is `±1`,
+    /// making equal constraints compare equal.
+    fn normalized(self) -> Result<Self, LinearError> {
+        let lead = match self.lhs.terms.values().next() {
+            Some(&c) => c,
+            None if self.lhs.constant != Ratio::ZERO => self.lhs.constant,
+            None => Ratio::ONE,
+        };
+        let scale = match (lead.num < 0, self.relation) {
+            // Equations may be flipped, inequalities only scaled by positive factors.
+            (true, Relation::Equal) => lead,
+            _ => Ratio::new(lead.num.abs(), lead.den).ok_or(LinearError::Overflow)?,
+        };
+        let lhs = scale
+            .recip()
+            .and_then(|k| self.lhs.scaled(k))
+            .ok_or(LinearError::Overflow)?;
+        // Keep every coefficient small enough to be written back as an expression.
+        let fits =
+            |c: &Ratio| c.num.unsigned_abs() <= u64::MAX as u128 && c.den <= u64::MAX as i128;
+        if !lhs.terms.values().chain([&lhs.constant]).all(fits) {
+            return Err(LinearError::Overflow);
+        }
+        Ok(LinearConstraint {
+            lhs,
+            relation: self.relation,
+        })
+    }
+
+    pub fn relation(&self) -> Relation {
+        self.relation
+    }
+
+    /// The variables with a nonzero coefficient, in sorted order.
+    pub fn variables(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.lhs.terms.keys().copied()
+    }
+
+    /// The left-hand side of the normalized constraint, which is compared to zero.
+    pub fn lhs(&self) -> OpArgument {
+        let constant = |c: Ratio| {
+            c.to_oparg()
+                .expect("Oops, normalized coefficients always fit in a rational")
+        };
+        self.lhs
+            .terms
+            .iter()
+            .fold(constant(self.lhs.constant), |sum, (&var, &c)| {
+                plus(&times(&constant(c), &variable(var)), &sum)
+            })
+    }
+
+    /// Whether the constraint holds at the point `bindings`, up to rounding.
+    pub fn holds(&self, bindings: &Bindings) -> Result<bool, EvaluationError> {
+        let value = self.lhs().evaluate(bindings)?;
+        Ok(match self.relation {
+            Relation::Less => value < 0.0,
+            Relation::LessEqual => value <= 0.0,
+            Relation::Equal => value == 0.0,
+        })
+    }
+
+    /// For a constraint without variables, whether it holds.
+    fn trivially(&self) -> Option<bool> {
+        let c = self.lhs.constant.num;
+        self.lhs.is_constant().then_some(match self.relation {
+            Relation::Less => c < 0,
+            Relation::LessEqual => c <= 0,
+            Relation::Equal => c == 0,
+        })
+    }
+}
+
+impl Display for LinearConstraint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} 0", self.lhs(), self.relation)
+    }
+}
+
+/// Adds `constraint` to `system` unless it is already there or holds trivially, returning
+/// `false` if it trivially fails.
+fn insert(system: &mut Vec<LinearConstraint>, constraint: LinearConstraint) -> bool {
+    match constraint.trivially() {
+        Some(holds) => holds,
+        None => {
+            if !system.contains(&constraint) {
+                system.push(constraint);
+            }
+            true
+        }
+    }
+}
+
+/// The constraints on the other variables under which some value of `var` satisfies all of
+/// `constraints`. A system that can never be satisfied comes back as the single constraint
+/// `1 ≤ 0`.
+pub fn eliminate(
+    constraints: &[LinearConstraint],
+    var: &str,
+) -> Result<Vec<LinearConstraint>, LinearError> {
+    let infeasible = || {
+        vec![LinearConstraint {
+            lhs: Affine::constant(Ratio::ONE),
+            relation: Relation::LessEqual,
+        }]
+    };
+    let (with, without): (Vec<_>, Vec<_>) = constraints
+        .iter()
+        .partition(|c| c.lhs.coefficient(var) != Ratio::ZERO);
+    let mut system = Vec::new();
+    for constraint in without {
+        if !insert(&mut system, constraint.clone()) {
+            return Ok(infeasible());
+        }
+    }
+
+    // Combines `a·p + b·q` with the relation of the pair.
+    let combine = |p: &LinearConstraint, a: Ratio, q: &LinearConstraint, b: Ratio| {
+        let lhs = p
+            .lhs
+            .scaled(a)
+            .and_then(|p| p.plus(&q.lhs.scaled(b)?))
+            .ok_or(LinearError::Overflow)?;
+        let relation = match (p.relation, q.relation) {
+            (Relation::Equal, r) | (r, Relation::Equal) => r,
+            (Relation::Less, _) | (_, Relation::Less) => Relation::Less,
+            _ => Relation::LessEqual,
+        };
+        LinearConstraint { lhs, relation }.normalized()
+    };
+
+    if let Some(pivot) = with.iter().find(|c| c.relation == Relation::Equal) {
+        // Substitute var = -(rest of pivot) / c into every other constraint.
+        let c = pivot.lhs.coefficient(var);
+        for constraint in with.iter().filter(|c| !std::ptr::eq(**c, *pivot)) {
+            let k = constraint.lhs.coefficient(var);
+            let factor = k
+                .checked_mul(c.recip().ok_or(LinearError::Overflow)?)
+                .ok_or(LinearError::Overflow)?;
+            let combined = combine(constraint, Ratio::ONE, pivot, -factor)?;
+            if !insert(&mut system, combined) {
+                return Ok(infeasible());
+            }
+        }
+        return Ok(system);
+    }
+
+    let (upper, lower): (Vec<&LinearConstraint>, Vec<_>) = with
+        .into_iter()
+        .partition(|c| c.lhs.coefficient(var).num > 0);
+    for p in &upper {
+        for q in &lower {
+            let (a, b) = (p.lhs.coefficient(var), q.lhs.coefficient(var));
+            if !insert(&mut system, combine(p, -b, q, a)?) {
+                return Ok(infeasible());
+            }
+        }
+    }
+    Ok(system)
+}
+
+/// Eliminates every one of `vars` in turn, projecting the region of `constraints` onto the
+/// remaining variables.
+pub fn project(
+    constraints: &[LinearConstraint],
+    vars: &[&str],
+) -> Result<Vec<LinearConstraint>, LinearError> {
+    vars.iter()
+        .try_fold(constraints.to_vec(), |system, var| eliminate(&system, var))
+}
+
+/// Whether some assignment of real values to the variables satisfies every constraint.
+pub fn is_feasible(constraints: &[LinearConstraint]) -> Result<bool, LinearError> {
+    let mut system = constraints.to_vec();
+    while let Some(var) = system.iter().find_map(|c| c.variables().next()) {
+        system = eliminate(&system, var)?;
+    }
+    Ok(system.iter().all(|c| c.trivially() != Some(false)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{eliminate, is_feasible, project, LinearConstraint, LinearError, Relation};
+    use crate::{evaluation::Bindings, parse::parse};
+
+    #[test]
+    fn test_fourier_motzkin() {
+        let p = |input: &str| parse(input).unwrap();
+        let le = |a: &str, b: &str| LinearConstraint::less_equal(&p(a), &p(b)).unwrap();
+        let ge = |a: &str, b: &str| LinearConstraint::greater_equal(&p(a), &p(b)).unwrap();
+
+        // The triangle x, y ≥ 0, x + y ≤ 1 projects onto 0 ≤ x ≤ 1.
+        let triangle = [ge("x", "0"), ge("y", "0"), le("x + y", "1")];
+        let shadow = eliminate(&triangle, "y").unwrap();
+        assert_eq!(shadow.len(), 2);
+        assert!(shadow.contains(&ge("x", "0")));
+        assert!(shadow.contains(&le("x", "1")));
+        assert_eq!(project(&triangle, &["x", "y"]).unwrap(), []);
+        assert!(is_feasible(&triangle).unwrap());
+
+        let at = Bindings::from_iter([("x", 0.25)]);
+        assert!(shadow.iter().all(|c| c.holds(&at).unwrap()));
+        assert_eq!(shadow[0].relation(), Relation::LessEqual);
+
+        assert!(!is_feasible(&[ge("x", "1"), le("x + y", "0"), ge("y", "0")]).unwrap());
+
+        // Strictness survives elimination.
+        let lt = |a: &str, b: &str| LinearConstraint::less(&p(a), &p(b)).unwrap();
+        assert!(is_feasible(&[le("x", "1"), ge("x", "1")]).unwrap());
+        assert!(!is_feasible(&[lt("x", "1"), ge("x", "1")]).unwrap());
+
+        // Equations are substituted rather than split.
+        let eq = LinearConstraint::equal(&p("x"), &p("2 * y")).unwrap();
+        let system = [eq.clone(), le("x + y", "3"), ge("y", "2")];
+        assert!(!is_feasible(&system).unwrap());
+        assert_eq!(
+            eliminate(&[eq, le("x + y", "3")], "x").unwrap(),
+            [le("y", "1")]
+        );
+
+        assert_eq!(
+            LinearConstraint::less_equal(&p("x * y"), &p("1")),
+            Err(LinearError::NotLinear(p("x * y")))
+        );
+        assert_eq!(
+            le("x / 2 - 1", "y").to_string(),
+            le("x", "2 * y + 2").to_string()
+        );
+    }
+}
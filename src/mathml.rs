@@ -0,0 +1,524 @@
+//! This module describes reading the content MathML of SBML kinetic laws into our computational
+//! graph.
+//!
+//! [`read_mathml`] takes a `<math>` element, or anything inside one, and supports the subset
+//! SBML uses for rate laws: `<cn>` numbers, including `e-notation` and `rational`, `<ci>`
+//! identifiers, `<csymbol>`s such as `time`, the constants, arithmetic, `<power>`, `<root>`,
+//! `<exp>`, `<ln>`, `<log>`, the trigonometric functions and `<piecewise>`. Numbers are read
+//! exactly, so `0.1` is `1/10`.
+//!
+//! The graph has no conditional operations, so a law is read as a [`Piecewise`], with each
+//! `<piecewise>` lifted out of the operations it appears in.
+
+use std::fmt::Display;
+
+use crate::{
+    boolean::Formula,
+    condition::Condition,
+    constants::Value,
+    piecewise::{conjoin, Piecewise, Relation},
+    symbols::{intern, variable, OpArgument},
+};
+
+/// The reasons reading MathML can fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MathMlError {
+    /// The input is not well-formed XML, at the given byte offset.
+    Syntax(usize),
+    /// The element is MathML, but outside the subset SBML kinetic laws use.
+    Unsupported(String),
+    /// The element does not have the children or content it needs.
+    Malformed(&'static str),
+}
+
+impl Display for MathMlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MathMlError::Syntax(offset) => write!(f, "invalid XML at byte {}", offset),
+            MathMlError::Unsupported(name) => write!(f, "unsupported MathML element <{}>", name),
+            MathMlError::Malformed(what) => write!(f, "malformed {}", what),
+        }
+    }
+}
+
+impl std::error::Error for MathMlError {}
+
+/// An XML element, with its namespace prefix removed.
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    /// The text before each child, and after the last.
+    text: Vec<String>,
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn text(&self) -> String {
+        self.text.concat()
+    }
+}
+
+struct Reader<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn error<T>(&self) -> Result<T, MathMlError> {
+        Err(MathMlError::Syntax(self.position))
+    }
+
+    fn whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// Skips whitespace, comments, processing instructions and doctypes.
+    fn skip(&mut self) -> Result<(), MathMlError> {
+        loop {
+            self.whitespace();
+            let end = match self.rest() {
+                rest if rest.starts_with("<!--") => "-->",
+                rest if rest.starts_with("<?") => "?>",
+                rest if rest.starts_with("<!") => ">",
+                _ => return Ok(()),
+            };
+            match self.rest().find(end) {
+                Some(at) => self.position += at + end.len(),
+                None => return self.error(),
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, MathMlError> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || "_-.:".contains(c)))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return self.error();
+        }
+        self.position += len;
+        let name = &rest[..len];
+        Ok(name.rsplit(':').next().unwrap_or(name).to_string())
+    }
+
+    fn unescape(&self, text: &str) -> Result<String, MathMlError> {
+        let mut out = String::new();
+        let mut rest = text;
+        while let Some(at) = rest.find('&') {
+            out.push_str(&rest[..at]);
+            let end = rest[at..]
+                .find(';')
+                .ok_or(MathMlError::Syntax(self.position))?;
+            let entity = &rest[at + 1..at + end];
+            let c = match entity {
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "amp" => Some('&'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#').and_then(|dec| dec.parse().ok()),
+                }
+                .and_then(char::from_u32),
+            };
+            out.push(c.ok_or(MathMlError::Syntax(self.position))?);
+            rest = &rest[at + end + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+
+    fn element(&mut self) -> Result<Element, MathMlError> {
+        self.skip()?;
+        if !self.rest().starts_with('<') {
+            return self.error();
+        }
+        self.position += 1;
+        let mut element = Element {
+            name: self.name()?,
+            attributes: Vec::new(),
+            children: Vec::new(),
+            text: vec![String::new()],
+        };
+        loop {
+            self.whitespace();
+            if self.rest().starts_with("/>") {
+                self.position += 2;
+                return Ok(element);
+            }
+            if self.rest().starts_with('>') {
+                self.position += 1;
+                break;
+            }
+            let key = self.name()?;
+            self.whitespace();
+            let rest = self.rest();
+            let Some(quote) = rest.strip_prefix('=').map(str::trim_start) else {
+                return self.error();
+            };
+            self.position += rest.len() - quote.len();
+            let delimiter = match quote.chars().next() {
+                Some(c @ ('"' | '\'')) => c,
+                _ => return self.error(),
+            };
+            let Some(len) = quote[1..].find(delimiter) else {
+                return self.error();
+            };
+            let value = self.unescape(&quote[1..1 + len])?;
+            self.position += len + 2;
+            element.attributes.push((key, value));
+        }
+        loop {
+            let rest = self.rest();
+            let len = rest.find('<').unwrap_or(rest.len());
+            let text = self.unescape(&rest[..len])?;
+            element.text.last_mut().unwrap().push_str(&text);
+            self.position += len;
+            match self.rest() {
+                "" => return self.error(),
+                rest if rest.starts_with("</") => {
+                    self.position += 2;
+                    if self.name()? != element.name {
+                        return self.error();
+                    }
+                    self.whitespace();
+                    if !self.rest().starts_with('>') {
+                        return self.error();
+                    }
+                    self.position += 1;
+                    return Ok(element);
+                }
+                rest if rest.starts_with("<!--") || rest.starts_with("<?") => self.skip()?,
+                _ => {
+                    element.children.push(self.element()?);
+                    element.text.push(String::new());
+                }
+            }
+        }
+    }
+}
+
+/// Reads the text of a `<cn>`, an integer or decimal, possibly with an exponent, exactly.
+pub(crate) fn number(text: &str) -> Result<OpArgument, MathMlError> {
+    let malformed = MathMlError::Malformed("number");
+    let text = text.trim();
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(at) => (
+            &text[..at],
+            text[at + 1..]
+                .parse::<i32>()
+                .map_err(|_| malformed.clone())?,
+        ),
+        None => (text, 0),
+    };
+    let (whole, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", whole, frac);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(malformed);
+    }
+    let exponent = exponent - frac.len() as i32;
+    let power = 10u64
+        .checked_pow(exponent.unsigned_abs())
+        .ok_or(malformed.clone())?;
+    let mantissa = digits.parse::<u64>().map_err(|_| malformed.clone())?;
+    let value = match exponent >= 0 {
+        true => Value::integer(mantissa.checked_mul(power).ok_or(malformed)?),
+        false => Value::rational(mantissa, power),
+    };
+    let value = OpArgument::from(value);
+    Ok(if negative { -value } else { value })
+}
+
+fn cn(element: &Element) -> Result<OpArgument, MathMlError> {
+    match element.attribute("type").unwrap_or("real") {
+        "integer" | "real" | "double" => number(&element.text()),
+        "e-notation" | "rational" => {
+            // The two parts are the text either side of a `<sep/>`.
+            let ([sep], [a, b]) = (element.children.as_slice(), element.text.as_slice()) else {
+                return Err(MathMlError::Malformed("number"));
+            };
+            if sep.name != "sep" {
+                return Err(MathMlError::Malformed("number"));
+            }
+            let (a, b) = (number(a)?, number(b)?);
+            Ok(match element.attribute("type") {
+                Some("rational") => a / b,
+                _ => a * OpArgument::from(Value::integer(10)).pow(&b),
+            }
+            .fold())
+        }
+        kind => Err(MathMlError::Unsupported(format!("cn type=\"{}\"", kind))),
+    }
+}
+
+/// The operands of an `<apply>`, without its qualifiers such as `<degree>` and `<logbase>`.
+fn operands(children: &[Element]) -> impl Iterator<Item = &Element> {
+    children
+        .iter()
+        .filter(|child| !matches!(child.name.as_str(), "degree" | "logbase"))
+}
+
+fn qualifier<'a>(children: &'a [Element], name: &str) -> Result<Option<&'a Element>, MathMlError> {
+    match children.iter().find(|child| child.name == name) {
+        Some(qualifier) => match qualifier.children.as_slice() {
+            [value] => Ok(Some(value)),
+            _ => Err(MathMlError::Malformed("qualifier")),
+        },
+        None => Ok(None),
+    }
+}
+
+fn expr(element: &Element) -> Result<Piecewise, MathMlError> {
+    let constant = |value: Value| Ok(OpArgument::from(value).into());
+    match element.name.as_str() {
+        "math" | "semantics" => match element.children.first() {
+            Some(child) => expr(child),
+            None => Err(MathMlError::Malformed("math")),
+        },
+        "cn" => Ok(cn(element)?.into()),
+        "csymbol"
+            if element
+                .attribute("definitionURL")
+                .is_some_and(|url| url.ends_with("/delay")) =>
+        {
+            Err(MathMlError::Unsupported("csymbol delay".to_string()))
+        }
+        "ci" | "csymbol" => match element.text().trim() {
+            "" => Err(MathMlError::Malformed("identifier")),
+            name => Ok(variable(intern(name)).into()),
+        },
+        "pi" => constant(Value::Pi),
+        "exponentiale" => constant(Value::E),
+        "imaginaryi" => constant(Value::I),
+        "infinity" => constant(Value::Inf),
+        "notanumber" => constant(Value::Undefined),
+        "piecewise" => piecewise(element),
+        "apply" => apply(element),
+        name => Err(MathMlError::Unsupported(name.to_string())),
+    }
+}
+
+fn piecewise(element: &Element) -> Result<Piecewise, MathMlError> {
+    let mut pieces = Vec::new();
+    for child in &element.children {
+        let (value, condition) = match (child.name.as_str(), child.children.as_slice()) {
+            ("piece", [value, condition]) => (expr(value)?, formula(condition)?),
+            ("otherwise", [value]) => (expr(value)?, Formula::True),
+            _ => return Err(MathMlError::Malformed("piecewise")),
+        };
+        pieces.extend(
+            value
+                .pieces
+                .into_iter()
+                .map(|(inner, value)| (conjoin(&condition, &inner), value)),
+        );
+    }
+    Ok(Piecewise { pieces })
+}
+
+fn apply(element: &Element) -> Result<Piecewise, MathMlError> {
+    let Some((operator, rest)) = element.children.split_first() else {
+        return Err(MathMlError::Malformed("apply"));
+    };
+    let args = operands(rest).map(expr).collect::<Result<Vec<_>, _>>()?;
+    let unary = |f: fn(&OpArgument) -> OpArgument| match args.len() {
+        1 => Ok(Piecewise::combine(args.clone(), |x| f(&x[0]))),
+        _ => Err(MathMlError::Malformed("apply")),
+    };
+    let binary = |f: fn(&OpArgument, &OpArgument) -> OpArgument| match args.len() {
+        2 => Ok(Piecewise::combine(args.clone(), |x| f(&x[0], &x[1]))),
+        _ => Err(MathMlError::Malformed("apply")),
+    };
+    let nary = |f: fn(&OpArgument, &OpArgument) -> OpArgument| match args.len() {
+        0 => Err(MathMlError::Malformed("apply")),
+        _ => Ok(Piecewise::combine(args.clone(), |x| {
+            x[1..].iter().fold(x[0].clone(), |acc, x| f(&acc, x))
+        })),
+    };
+    match operator.name.as_str() {
+        "plus" => nary(|a, b| a + b),
+        "times" => nary(|a, b| a * b),
+        "minus" if args.len() == 1 => unary(|a| -a),
+        "minus" => binary(|a, b| a - b),
+        "divide" => binary(|a, b| a / b),
+        "power" => binary(OpArgument::pow),
+        "exp" => unary(OpArgument::exp),
+        "ln" => unary(OpArgument::ln),
+        "sin" => unary(OpArgument::sin),
+        "cos" => unary(OpArgument::cos),
+        "tan" => unary(OpArgument::tan),
+        "arctan" => unary(OpArgument::atan),
+        "root" | "log" => {
+            let (name, default) = match operator.name.as_str() {
+                "root" => ("degree", 2),
+                _ => ("logbase", 10),
+            };
+            let qualifier = match qualifier(rest, name)? {
+                Some(value) => expr(value)?,
+                None => OpArgument::from(Value::integer(default)).into(),
+            };
+            let mut args = args;
+            if args.len() != 1 {
+                return Err(MathMlError::Malformed("apply"));
+            }
+            args.push(qualifier);
+            Ok(match operator.name.as_str() {
+                "root" => Piecewise::combine(args, |x| {
+                    x[0].pow(&(OpArgument::from(Value::integer(1)) / &x[1]))
+                }),
+                _ => Piecewise::combine(args, |x| x[0].ln() / x[1].ln()),
+            })
+        }
+        name => Err(MathMlError::Unsupported(name.to_string())),
+    }
+}
+
+/// Reads a condition: a relation, a combination of conditions, or `<true/>` or `<false/>`.
+fn formula(element: &Element) -> Result<Formula<Condition>, MathMlError> {
+    match element.name.as_str() {
+        "true" => return Ok(Formula::True),
+        "false" => return Ok(Formula::False),
+        "apply" => {}
+        name => return Err(MathMlError::Unsupported(name.to_string())),
+    }
+    let Some((operator, rest)) = element.children.split_first() else {
+        return Err(MathMlError::Malformed("apply"));
+    };
+    let logical = |combine: fn(Vec<Formula<Condition>>) -> Formula<Condition>| {
+        Ok(combine(rest.iter().map(formula).collect::<Result<_, _>>()?))
+    };
+    match operator.name.as_str() {
+        "and" => return logical(Formula::And),
+        "or" => return logical(Formula::Or),
+        "not" => match rest {
+            [arg] => return Ok(formula(arg)?.not()),
+            _ => return Err(MathMlError::Malformed("apply")),
+        },
+        "xor" => match rest {
+            [a, b] => {
+                let (a, b) = (formula(a)?, formula(b)?);
+                return Ok(a.and(&b.not()).or(&a.not().and(&b)));
+            }
+            _ => return Err(MathMlError::Malformed("apply")),
+        },
+        _ => {}
+    }
+
+    // Relations chain, so `a < b < c` is `a < b ∧ b < c`.
+    let args = rest
+        .iter()
+        .map(|arg| match expr(arg)?.expr() {
+            Some(arg) => Ok(arg.clone()),
+            None => Err(MathMlError::Unsupported(
+                "piecewise in a condition".to_string(),
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if args.len() < 2 {
+        return Err(MathMlError::Malformed("relation"));
+    }
+    let relation = match operator.name.as_str() {
+        "lt" => Relation::Lt,
+        "leq" => Relation::Leq,
+        "gt" => Relation::Gt,
+        "geq" => Relation::Geq,
+        "eq" => Relation::Eq,
+        "neq" => Relation::Neq,
+        name => return Err(MathMlError::Unsupported(name.to_string())),
+    };
+    let links = args
+        .windows(2)
+        .map(|pair| relation.formula(&pair[0], &pair[1]))
+        .collect::<Vec<_>>();
+    Ok(match <[_; 1]>::try_from(links) {
+        Ok([link]) => link,
+        Err(links) => Formula::And(links),
+    })
+}
+
+/// Reads a kinetic law, or any other expression, from SBML MathML.
+pub fn read_mathml(input: &str) -> Result<Piecewise, MathMlError> {
+    let mut reader = Reader { input, position: 0 };
+    let element = reader.element()?;
+    reader.skip()?;
+    if !reader.rest().is_empty() {
+        return reader.error();
+    }
+    expr(&element)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_mathml, MathMlError};
+    use crate::{evaluation::Bindings, parse::parse};
+
+    #[test]
+    fn test_read_mathml() {
+        let p = |input: &str| parse(input).unwrap();
+        let law = read_mathml(
+            r#"<?xml version="1.0"?>
+            <math xmlns="http://www.w3.org/1998/Math/MathML">
+              <!-- Michaelis-Menten -->
+              <apply><divide/>
+                <apply><times/><ci> Vmax </ci><ci>S</ci></apply>
+                <apply><plus/><ci>Km</ci><ci>S</ci><cn type="e-notation"> 1 <sep/> -3 </cn></apply>
+              </apply>
+            </math>"#,
+        )
+        .unwrap();
+        assert_eq!(law.expr(), Some(&p("(Vmax * S) / (Km + S + 0.001)")));
+
+        let law = read_mathml(
+            "<math><apply><times/><ci>k</ci><piecewise>
+               <piece><cn>0.5</cn><apply><lt/><csymbol definitionURL=\"http://www.sbml.org/sbml/symbols/time\">t</csymbol><cn>10</cn></apply></piece>
+               <otherwise><apply><root/><degree><cn>3</cn></degree><ci>x</ci></apply></otherwise>
+             </piecewise></apply></math>",
+        )
+        .unwrap();
+        assert_eq!(law.pieces().len(), 2);
+        assert_eq!(law.pieces()[0].1, p("k * 0.5"));
+        assert_eq!(law.pieces()[1].1, p("k * x^(1/3)"));
+        let bindings = |t: f64| Bindings::from_iter([("k", 2.0), ("t", t), ("x", 8.0)]);
+        assert_eq!(law.evaluate(&bindings(1.0)), Ok(Some(1.0)));
+        assert_eq!(law.evaluate(&bindings(10.0)), Ok(Some(4.0)));
+        assert_eq!(law.derivative("k").evaluate(&bindings(1.0)), Ok(Some(0.5)));
+
+        let law = read_mathml(
+            "<math><piecewise><piece><cn type=\"rational\">1<sep/>3</cn>
+               <apply><and/><apply><geq/><ci>x</ci><cn>0</cn></apply><apply><not/><apply><eq/><ci>x</ci><cn>1</cn></apply></apply></apply>
+             </piece></piecewise></math>",
+        )
+        .unwrap();
+        let bindings = |x: f64| Bindings::from_iter([("x", x)]);
+        assert_eq!(law.evaluate(&bindings(2.0)), Ok(Some(1.0 / 3.0)));
+        assert_eq!(law.evaluate(&bindings(1.0)), Ok(None));
+        assert_eq!(law.evaluate(&bindings(-1.0)), Ok(None));
+
+        assert_eq!(
+            read_mathml("<math><apply><abs/><ci>x</ci></apply></math>"),
+            Err(MathMlError::Unsupported("abs".to_string()))
+        );
+        assert_eq!(
+            read_mathml("<math><ci>x</ci>"),
+            Err(MathMlError::Syntax(16))
+        );
+    }
+}
@@ -0,0 +1,380 @@
+//! This module describes how to read and write our computational graph as MATLAB or GNU Octave
+//! expressions, for formulas ported from MATLAB scripts.
+//!
+//! MATLAB's precedences differ from [`crate::parse`]'s: `^` is evaluated from the left, so
+//! `2^3^2` is `64`, and binds tighter than unary minus, which binds tighter than `*`, so `-a^2`
+//! is `-(a^2)`. The element-wise operators `.*`, `./` and `.^` mean the same as `*`, `/` and `^`
+//! on scalars, `log` is the natural logarithm, and `i`, `j` and suffixes like `2i` are the
+//! imaginary unit. MATLAB has no constant for `e`; it is written `exp(1)`.
+//!
+//! [`OpArgument::to_matlab`] writes the element-wise operators, so that the result also works on
+//! arrays.
+
+use crate::{
+    constants::Value,
+    mathml,
+    parse::ParseError,
+    render::needs_parens,
+    symbols::{
+        intern, variable, OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        OperationKind::*,
+    },
+};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token<'a> {
+    Number(&'a str),
+    Imaginary(&'a str),
+    Ident(&'a str),
+    Symbol(&'static str),
+}
+
+/// Longer symbols first, so that `.*` is not read as a stray `.`.
+const SYMBOLS: [&str; 10] = [".*", "./", ".^", "+", "-", "*", "/", "^", "(", ")"];
+
+struct Lexer<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn tokens(input: &'a str) -> Result<Vec<(usize, Token<'a>)>, ParseError> {
+        let mut lexer = Lexer { input, pos: 0 };
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next_token()? {
+            tokens.push(token);
+        }
+        Ok(tokens)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !pred(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn next_token(&mut self) -> Result<Option<(usize, Token<'a>)>, ParseError> {
+        self.take_while(char::is_whitespace);
+        let start = self.pos;
+        let Some(c) = self.rest().chars().next() else {
+            return Ok(None);
+        };
+
+        let digit_follows = |rest: &str| rest.chars().nth(1).is_some_and(|c| c.is_ascii_digit());
+        let token = if c.is_ascii_digit() || (c == '.' && digit_follows(self.rest())) {
+            self.take_while(|c| c.is_ascii_digit());
+            // `2.*x` is `2 .* x`, not `2. * x`.
+            if self.rest().starts_with('.')
+                && !SYMBOLS[..3].iter().any(|s| self.rest().starts_with(s))
+            {
+                self.pos += 1;
+                self.take_while(|c| c.is_ascii_digit());
+            }
+            let mut exponent = self.rest().chars();
+            if let Some('e' | 'E') = exponent.next() {
+                let sign = usize::from(matches!(exponent.clone().next(), Some('+' | '-')));
+                if exponent.nth(sign).is_some_and(|c| c.is_ascii_digit()) {
+                    self.pos += 1 + sign;
+                    self.take_while(|c| c.is_ascii_digit());
+                }
+            }
+            let number = &self.input[start..self.pos];
+            let mut suffix = self.rest().chars();
+            match (suffix.next(), suffix.next()) {
+                (Some('i' | 'j'), next)
+                    if !next.is_some_and(|c| c.is_alphanumeric() || c == '_') =>
+                {
+                    self.pos += 1;
+                    Token::Imaginary(number)
+                }
+                _ => Token::Number(number),
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            Token::Ident(self.take_while(|c| c.is_alphanumeric() || c == '_'))
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| self.rest().starts_with(*s)) {
+            self.pos += symbol.len();
+            Token::Symbol(symbol)
+        } else {
+            return Err(ParseError::UnexpectedCharacter(start, c));
+        };
+
+        Ok(Some((start, token)))
+    }
+}
+
+fn function(name: &str) -> Option<fn(&OpArgument) -> OpArgument> {
+    Some(match name {
+        "sin" => OpArgument::sin,
+        "cos" => OpArgument::cos,
+        "tan" => OpArgument::tan,
+        "atan" => OpArgument::atan,
+        "sign" => OpArgument::sign,
+        "exp" => OpArgument::exp,
+        "log" => OpArgument::ln,
+        "log10" => |x| x.ln() / OpArgument::from(Value::integer(10)).ln(),
+        "log2" => |x| x.ln() / OpArgument::from(Value::integer(2)).ln(),
+        "sqrt" => |x| x.pow(&Value::rational(1, 2).into()),
+        _ => return None,
+    })
+}
+
+fn constant(name: &str) -> Option<Value> {
+    match name {
+        "pi" => Some(Value::Pi),
+        "i" | "j" => Some(Value::I),
+        "Inf" | "inf" => Some(Value::Inf),
+        "NaN" | "nan" => Some(Value::Undefined),
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<(usize, Token<'a>)>,
+    next: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn bump(&mut self) -> Result<(usize, Token<'a>), ParseError> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .cloned()
+            .ok_or(ParseError::UnexpectedEnd)?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, symbols: &[&str]) -> Option<&'static str> {
+        match self.tokens.get(self.next) {
+            Some(&(_, Token::Symbol(symbol))) if symbols.contains(&symbol) => {
+                self.next += 1;
+                Some(symbol)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), ParseError> {
+        match self.bump()? {
+            (_, Token::Symbol(s)) if s == symbol => Ok(()),
+            (pos, _) => Err(ParseError::UnexpectedToken(pos)),
+        }
+    }
+
+    fn sum(&mut self) -> Result<OpArgument, ParseError> {
+        let mut lhs = self.product()?;
+        while let Some(op) = self.eat(&["+", "-"]) {
+            lhs = match op {
+                "+" => lhs + self.product()?,
+                _ => lhs - self.product()?,
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn product(&mut self) -> Result<OpArgument, ParseError> {
+        let mut lhs = self.unary()?;
+        while let Some(op) = self.eat(&["*", "/", ".*", "./"]) {
+            lhs = match op.trim_start_matches('.') {
+                "*" => lhs * self.unary()?,
+                _ => lhs / self.unary()?,
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<OpArgument, ParseError> {
+        match self.eat(&["-", "+"]) {
+            Some("-") => Ok(-self.unary()?),
+            Some(_) => self.unary(),
+            None => self.power(),
+        }
+    }
+
+    /// Powers, from the left, with a signed exponent as in `2^-1`.
+    fn power(&mut self) -> Result<OpArgument, ParseError> {
+        let mut base = self.atom()?;
+        while self.eat(&["^", ".^"]).is_some() {
+            let exponent = match self.eat(&["-", "+"]) {
+                Some("-") => -self.atom()?,
+                _ => self.atom()?,
+            };
+            base = base.pow(&exponent);
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<OpArgument, ParseError> {
+        let number = |pos: usize, text: &str| {
+            mathml::number(text).map_err(|_| ParseError::NumberTooLarge(pos))
+        };
+        match self.bump()? {
+            (pos, Token::Number(text)) => number(pos, text),
+            (pos, Token::Imaginary(text)) => Ok(number(pos, text)? * OpArgument::from(Value::I)),
+            (_, Token::Symbol("(")) => {
+                let inner = self.sum()?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            (pos, Token::Ident(name)) if self.eat(&["("]).is_some() => {
+                let f = function(name)
+                    .ok_or_else(|| ParseError::UnknownFunction(pos, name.to_owned()))?;
+                let arg = self.sum()?;
+                self.expect(")")?;
+                Ok(match name == "exp" && arg == Value::integer(1).into() {
+                    true => Value::E.into(),
+                    false => f(&arg),
+                })
+            }
+            (_, Token::Ident(name)) => Ok(match constant(name) {
+                Some(value) => value.into(),
+                None => variable(intern(name)),
+            }),
+            (pos, Token::Symbol(_)) => Err(ParseError::UnexpectedToken(pos)),
+        }
+    }
+}
+
+/// Parses a MATLAB or GNU Octave expression.
+pub fn parse_matlab(input: &str) -> Result<OpArgument, ParseError> {
+    let mut parser = Parser {
+        tokens: Lexer::tokens(input)?,
+        next: 0,
+    };
+    let expr = parser.sum()?;
+    match parser.tokens.get(parser.next) {
+        Some((pos, _)) => Err(ParseError::UnexpectedToken(*pos)),
+        None => Ok(expr),
+    }
+}
+
+fn write(expr: &OpArgument) -> String {
+    let op = match &expr.value {
+        Leaf(value) => {
+            return match **value {
+                Value::Rational(num, den) if den.get() == 1 => num.to_string(),
+                Value::Rational(num, den) => format!("({}/{})", num, den),
+                Value::Pi => "pi".to_owned(),
+                Value::E => "exp(1)".to_owned(),
+                Value::I => "1i".to_owned(),
+                Value::Inf => "Inf".to_owned(),
+                Value::Undefined => "NaN".to_owned(),
+                Value::Variable(name) => name.to_owned(),
+                // MATLAB has no exact algebraic numbers, so this is as close as a double gets.
+                Value::Algebraic(number) => format!("{:?}", number.approximate()),
+            };
+        }
+        Op(op) => op,
+    };
+    let arg = |i: usize| write(&op.arguments[i]);
+    // Negative leaves need parentheses too, or `^` would bind before their minus sign.
+    let grouped = |i: usize| match (&op.arguments[i].value, arg(i)) {
+        (Op(_), text) => format!("({})", text),
+        (Leaf(_), text) if text.starts_with('-') => format!("({})", text),
+        (Leaf(_), text) => text,
+    };
+    match op.op {
+        Addition | Subtraction | Multiplication | Division => {
+            let operand = |i: usize| match needs_parens(op.op, &op.arguments[i], i) {
+                true => grouped(i),
+                false => arg(i),
+            };
+            let symbol = match op.op {
+                Addition => "+",
+                Subtraction => "-",
+                Multiplication => ".*",
+                _ => "./",
+            };
+            format!("{}{}{}", operand(0), symbol, operand(1))
+        }
+        // `^` binds tighter than unary minus, so only sums and products need parentheses.
+        Negation => match &op.arguments[0].value {
+            Op(inner) if inner.op != Pow => format!("-{}", grouped(0)),
+            _ => format!("-{}", arg(0)),
+        },
+        Pow if matches!(&op.arguments[1].value, Leaf(v) if **v == Value::rational(1, 2)) => {
+            format!("sqrt({})", arg(0))
+        }
+        Pow => format!("{}.^{}", grouped(0), grouped(1)),
+        Exp => format!("exp({})", arg(0)),
+        Sin => format!("sin({})", arg(0)),
+        Cos => format!("cos({})", arg(0)),
+        Tan => format!("tan({})", arg(0)),
+        Ln => format!("log({})", arg(0)),
+        Atan => format!("atan({})", arg(0)),
+        Sign => format!("sign({})", arg(0)),
+    }
+}
+
+impl OpArgument {
+    /// Writes `self` as a MATLAB or GNU Octave expression, with element-wise operators.
+    pub fn to_matlab(&self) -> String {
+        write(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_matlab;
+    use crate::{
+        algebraic::real_roots,
+        evaluation::Bindings,
+        parse::{parse, ParseError},
+    };
+
+    #[test]
+    fn test_matlab() {
+        let p = |input: &str| parse(input).unwrap();
+        let m = |input: &str| parse_matlab(input).unwrap();
+
+        assert_eq!(m("2^3^x"), p("(2^3)^x"));
+        assert_eq!(m("-a.^2 .* b"), p("-(a^2) * b"));
+        assert_eq!(m("x^-2./1.5e1"), p("x^(-2) / 15"));
+        assert_eq!(m("2.*x"), p("2 * x"));
+        assert_eq!(
+            m("log(x) + log10(y) + exp(1)"),
+            p("ln(x) + ln(y) / ln(10) + e")
+        );
+        assert_eq!(m("3i - pi"), p("3 * i - pi"));
+
+        for input in [
+            "-x^2 + sin(y) / (2*z)",
+            "(x - y) - (1 - z)",
+            "x^(y^2)",
+            "0.5 * e^(-x)",
+        ] {
+            // Rationals are written as quotients, so they come back as divisions to fold.
+            let expr = p(input).fold();
+            assert_eq!(parse_matlab(&expr.to_matlab()).map(|e| e.fold()), Ok(expr));
+        }
+        assert_eq!(
+            p("-(x+1)^(1/2) * 0.25").fold().to_matlab(),
+            "-sqrt(x+1).*(1/4)"
+        );
+        assert_eq!(p("x - (y - 1)").to_matlab(), "x-(y-1)");
+
+        // (-∛2)^y, whose base must not lose its sign to the power.
+        let base = real_roots(&p("x^3 + 2"), "x").unwrap().remove(0);
+        let power = base.pow(&p("y"));
+        assert!(power.to_matlab().starts_with("(-1.259"));
+        let at = Bindings::from_iter([("y", 2.0)]);
+        let roundtrip = parse_matlab(&power.to_matlab())
+            .unwrap()
+            .evaluate(&at)
+            .unwrap();
+        assert!((roundtrip - power.evaluate(&at).unwrap()).abs() < 1e-12);
+        assert!((roundtrip - 2f64.cbrt().powi(2)).abs() < 1e-12);
+
+        assert_eq!(parse_matlab("x +"), Err(ParseError::UnexpectedEnd));
+        assert_eq!(
+            parse_matlab("abs(x)"),
+            Err(ParseError::UnknownFunction(0, "abs".to_owned()))
+        );
+    }
+}
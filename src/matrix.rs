@@ -0,0 +1,1041 @@
+//! This module describes matrices of expressions.
+//!
+//! A [`Matrix`] stores its entries in row-major order, but evaluates to column-major data, the
+//! layout of `nalgebra`'s `DMatrix::from_vec(rows, cols, data)`, so a symbolic derivation can
+//! hand its numbers straight to a numeric linear algebra stack; [`Matrix::evaluate_into`]
+//! builds the numeric matrix directly. Going the other way, [`Matrix::symbols`] builds a matrix
+//! of fresh variables with the shape of a numeric one. With the `nalgebra` feature, `DMatrix`
+//! and `DVector` are [`FromColumnMajor`] targets, any `nalgebra` matrix of `f64` converts into
+//! a matrix of its exact values, and [`Matrix::symbols_like`] takes the shape from the matrix.
+//!
+//! For matrix calculus, [`Matrix::kronecker`] and [`Matrix::vec`] give the vectorization
+//! identity `vec(AXB) = (Bᵀ⊗A)·vec(X)`, and [`Matrix::block`] and [`Matrix::slice`] assemble
+//! matrices from blocks and take them apart again. [`Matrix::gradient`] differentiates a
+//! scalar with respect to a matrix of variables entry by entry, which reproduces the familiar
+//! identities such as `∂tr(AX)/∂X = Aᵀ` and `∂(xᵀAx)/∂x = (A + Aᵀ)x`.
+//!
+//! [`Matrix::expm`] finds the matrix exponential in closed form, as the polynomial in the matrix
+//! that agrees with `exp` on its eigenvalues (Cayley–Hamilton). That needs the eigenvalues in
+//! closed form, so it works for every 2×2 matrix, for triangular matrices, and for 3×3 matrices
+//! with rational entries whose characteristic polynomial has a rational root. Since the form
+//! changes where symbolic eigenvalues coincide or turn complex, the result is a
+//! [`PiecewiseMatrix`] with a piece for every such case the entries do not rule out.
+
+use std::{
+    fmt::Display,
+    ops::{Add, Mul, Range},
+};
+
+use crate::{
+    boolean::Formula,
+    condition::Condition,
+    constants::Value,
+    evaluation::{Bindings, EvaluationError},
+    fold::Ratio,
+    piecewise::{conjoin, select, Piecewise},
+    recurrence::determinant,
+    roots::{deflate, rational_root},
+    series::{one, plus, times, zero},
+    symbols::{
+        intern, variable, OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        OperationKind::{Multiplication, Negation, Pow},
+    },
+};
+
+/// A numeric matrix that can be built from entries in column-major order.
+pub trait FromColumnMajor: Sized {
+    fn from_column_major(rows: usize, cols: usize, data: Vec<f64>) -> Self;
+}
+
+impl FromColumnMajor for Vec<f64> {
+    fn from_column_major(_: usize, _: usize, data: Vec<f64>) -> Self {
+        data
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl FromColumnMajor for nalgebra::DMatrix<f64> {
+    fn from_column_major(rows: usize, cols: usize, data: Vec<f64>) -> Self {
+        nalgebra::DMatrix::from_vec(rows, cols, data)
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl FromColumnMajor for nalgebra::DVector<f64> {
+    fn from_column_major(rows: usize, cols: usize, data: Vec<f64>) -> Self {
+        assert_eq!(
+            cols, 1,
+            "Hold on, a {}×{} matrix is not a column vector",
+            rows, cols
+        );
+        nalgebra::DVector::from_vec(data)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    entries: Vec<OpArgument>,
+}
+
+impl Matrix {
+    /// The matrix with `entries` in row-major order.
+    pub fn new(rows: usize, cols: usize, entries: Vec<OpArgument>) -> Self {
+        assert_eq!(
+            entries.len(),
+            rows * cols,
+            "Hold on, a {}×{} matrix needs {} entries, not {}",
+            rows,
+            cols,
+            rows * cols,
+            entries.len()
+        );
+        Matrix {
+            rows,
+            cols,
+            entries,
+        }
+    }
+
+    pub fn from_fn(rows: usize, cols: usize, f: impl Fn(usize, usize) -> OpArgument) -> Self {
+        let entries = (0..rows)
+            .flat_map(|i| (0..cols).map(move |j| (i, j)))
+            .map(|(i, j)| f(i, j))
+            .collect();
+        Matrix::new(rows, cols, entries)
+    }
+
+    /// The matrix of the variables `name_i_j`, counting from zero, with the shape `(rows, cols)`
+    /// as `nalgebra` reports it.
+    pub fn symbols(name: &str, (rows, cols): (usize, usize)) -> Self {
+        Matrix::from_fn(rows, cols, |i, j| {
+            variable(intern(&format!("{}_{}_{}", name, i, j)))
+        })
+    }
+
+    /// Like [`Matrix::symbols`], with the shape of `like`.
+    #[cfg(feature = "nalgebra")]
+    pub fn symbols_like<T, R, C, S>(name: &str, like: &nalgebra::Matrix<T, R, C, S>) -> Self
+    where
+        R: nalgebra::Dim,
+        C: nalgebra::Dim,
+        S: nalgebra::RawStorage<T, R, C>,
+    {
+        Matrix::symbols(name, like.shape())
+    }
+
+    pub fn zeros(rows: usize, cols: usize) -> Self {
+        Matrix::from_fn(rows, cols, |_, _| zero())
+    }
+
+    pub fn identity(n: usize) -> Self {
+        Matrix::from_fn(n, n, |i, j| match i == j {
+            true => one(),
+            false => zero(),
+        })
+    }
+
+    /// The Jacobian of `exprs` with respect to `vars`, whose entry `(i, j)` is the derivative
+    /// of `exprs[i]` with respect to `vars[j]`.
+    pub fn jacobian(exprs: &[OpArgument], vars: &[&'static str]) -> Self {
+        Matrix::from_fn(exprs.len(), vars.len(), |i, j| exprs[i].derivative(vars[j]))
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// The shape `(rows, cols)`.
+    pub fn shape(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> &OpArgument {
+        assert!(
+            i < self.rows && j < self.cols,
+            "Whoa there, ({}, {}) is outside a {}×{} matrix",
+            i,
+            j,
+            self.rows,
+            self.cols
+        );
+        &self.entries[i * self.cols + j]
+    }
+
+    /// The entries in row-major order.
+    pub fn entries(&self) -> &[OpArgument] {
+        &self.entries
+    }
+
+    pub fn transpose(&self) -> Matrix {
+        Matrix::from_fn(self.cols, self.rows, |i, j| self.get(j, i).clone())
+    }
+
+    pub fn map(&self, f: impl Fn(&OpArgument) -> OpArgument) -> Matrix {
+        Matrix::new(self.rows, self.cols, self.entries.iter().map(f).collect())
+    }
+
+    /// The sum of the diagonal.
+    pub fn trace(&self) -> OpArgument {
+        assert_eq!(
+            self.rows, self.cols,
+            "Oops, a {}×{} matrix has no trace",
+            self.rows, self.cols
+        );
+        (0..self.rows).fold(zero(), |sum, i| plus(&sum, &self.get(i, i).fold()))
+    }
+
+    /// The matrix of the derivatives of the scalar `expr` with respect to each entry of `wrt`,
+    /// which must all be variables, such as those of [`Matrix::symbols`]. It has the shape of
+    /// `wrt`, so a vector of variables gives a gradient vector.
+    pub fn gradient(expr: &OpArgument, wrt: &Matrix) -> Matrix {
+        wrt.map(|entry| match &entry.value {
+            Leaf(value) if matches!(**value, Value::Variable(_)) => {
+                let Value::Variable(var) = **value else {
+                    unreachable!()
+                };
+                expr.derivative(var).fold()
+            }
+            _ => panic!(
+                "Whoa there, {} is not a variable to differentiate by",
+                entry
+            ),
+        })
+    }
+
+    /// The Kronecker product `self ⊗ other`, made of the blocks `self[i, j]·other`.
+    pub fn kronecker(&self, other: &Matrix) -> Matrix {
+        Matrix::from_fn(self.rows * other.rows, self.cols * other.cols, |i, j| {
+            times(
+                &self.get(i / other.rows, j / other.cols).fold(),
+                &other.get(i % other.rows, j % other.cols).fold(),
+            )
+        })
+    }
+
+    /// The column vector of the columns of `self`, stacked from left to right.
+    pub fn vec(&self) -> Matrix {
+        Matrix::from_fn(self.rows * self.cols, 1, |k, _| {
+            self.get(k % self.rows, k / self.rows).clone()
+        })
+    }
+
+    /// The `rows`×`cols` matrix whose [`Matrix::vec`] is the column vector `v`.
+    pub fn unvec(v: &Matrix, rows: usize, cols: usize) -> Matrix {
+        assert!(
+            v.cols == 1 && v.rows == rows * cols,
+            "Hold on, a {}×{} matrix cannot be unstacked into {}×{}",
+            v.rows,
+            v.cols,
+            rows,
+            cols
+        );
+        Matrix::from_fn(rows, cols, |i, j| v.entries[j * rows + i].clone())
+    }
+
+    /// The matrix made of `blocks`, given row by row. Blocks in a row must have as many rows as
+    /// each other, and blocks in a column as many columns.
+    pub fn block(blocks: &[Vec<Matrix>]) -> Matrix {
+        let heights = blocks
+            .iter()
+            .map(|row| row.first().map_or(0, Matrix::rows))
+            .collect::<Vec<_>>();
+        let widths = blocks
+            .first()
+            .map_or_else(Vec::new, |row| row.iter().map(Matrix::cols).collect());
+        for (row, height) in blocks.iter().zip(&heights) {
+            assert!(
+                row.len() == widths.len()
+                    && row
+                        .iter()
+                        .zip(&widths)
+                        .all(|(block, width)| block.shape() == (*height, *width)),
+                "Whoa there, the blocks of a block matrix must line up in rows and columns"
+            );
+        }
+        let (rows, cols) = (heights.iter().sum(), widths.iter().sum());
+        // Which block and offset every row and column falls in.
+        let locate = |sizes: &[usize], mut k: usize| {
+            let mut b = 0;
+            while k >= sizes[b] {
+                k -= sizes[b];
+                b += 1;
+            }
+            (b, k)
+        };
+        Matrix::from_fn(rows, cols, |i, j| {
+            let ((bi, i), (bj, j)) = (locate(&heights, i), locate(&widths, j));
+            blocks[bi][bj].get(i, j).clone()
+        })
+    }
+
+    /// The submatrix of the rows and columns in the ranges.
+    pub fn slice(&self, rows: Range<usize>, cols: Range<usize>) -> Matrix {
+        assert!(
+            rows.start <= rows.end
+                && rows.end <= self.rows
+                && cols.start <= cols.end
+                && cols.end <= self.cols,
+            "Whoa there, rows {:?} and columns {:?} are not inside a {}×{} matrix",
+            rows,
+            cols,
+            self.rows,
+            self.cols
+        );
+        Matrix::from_fn(rows.len(), cols.len(), |i, j| {
+            self.get(rows.start + i, cols.start + j).clone()
+        })
+    }
+
+    /// Evaluates every entry, returning them in column-major order.
+    pub fn evaluate(&self, bindings: &Bindings) -> Result<Vec<f64>, EvaluationError> {
+        (0..self.cols)
+            .flat_map(|j| (0..self.rows).map(move |i| (i, j)))
+            .map(|(i, j)| self.get(i, j).evaluate(bindings))
+            .collect()
+    }
+
+    /// Evaluates every entry into a numeric matrix, such as `nalgebra`'s `DMatrix<f64>`.
+    pub fn evaluate_into<M: FromColumnMajor>(
+        &self,
+        bindings: &Bindings,
+    ) -> Result<M, EvaluationError> {
+        Ok(M::from_column_major(
+            self.rows,
+            self.cols,
+            self.evaluate(bindings)?,
+        ))
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl<R, C, S> From<&nalgebra::Matrix<f64, R, C, S>> for Matrix
+where
+    R: nalgebra::Dim,
+    C: nalgebra::Dim,
+    S: nalgebra::RawStorage<f64, R, C>,
+{
+    /// The matrix of the exact values of the entries.
+    fn from(matrix: &nalgebra::Matrix<f64, R, C, S>) -> Self {
+        let (rows, cols) = matrix.shape();
+        Matrix::from_fn(rows, cols, |i, j| crate::set::exact(matrix[(i, j)]))
+    }
+}
+
+/// Why a matrix has no exponential in closed form.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExpmError {
+    /// The matrix has this many rows and columns.
+    NotSquare(usize, usize),
+    /// The eigenvalues of the matrix have no closed form here.
+    NoClosedForm,
+}
+
+impl Display for ExpmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpmError::NotSquare(rows, cols) => {
+                write!(f, "a {}×{} matrix has no exponential", rows, cols)
+            }
+            ExpmError::NoClosedForm => {
+                f.write_str("the eigenvalues of the matrix have no closed form")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExpmError {}
+
+/// Matrices guarded by conditions, valued as the first piece whose condition holds, like the
+/// expressions of a [`Piecewise`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PiecewiseMatrix {
+    pieces: Vec<(Formula<Condition>, Matrix)>,
+}
+
+impl From<Matrix> for PiecewiseMatrix {
+    fn from(matrix: Matrix) -> Self {
+        PiecewiseMatrix {
+            pieces: vec![(Formula::True, matrix)],
+        }
+    }
+}
+
+impl PiecewiseMatrix {
+    /// The conditions and matrices, in the order they are tried.
+    pub fn pieces(&self) -> &[(Formula<Condition>, Matrix)] {
+        &self.pieces
+    }
+
+    /// The matrix, if there is only one and it holds everywhere.
+    pub fn matrix(&self) -> Option<&Matrix> {
+        match self.pieces.as_slice() {
+            [(Formula::True, matrix)] => Some(matrix),
+            _ => None,
+        }
+    }
+
+    /// The entry `(i, j)` of every piece.
+    pub fn get(&self, i: usize, j: usize) -> Piecewise {
+        Piecewise {
+            pieces: self
+                .pieces
+                .iter()
+                .map(|(condition, matrix)| (condition.clone(), matrix.get(i, j).clone()))
+                .collect(),
+        }
+    }
+
+    /// Evaluates every entry of the first piece whose condition holds, returning them in
+    /// column-major order, or `None` if no condition holds.
+    pub fn evaluate(&self, bindings: &Bindings) -> Result<Option<Vec<f64>>, EvaluationError> {
+        match select(&self.pieces, bindings)? {
+            Some(matrix) => matrix.evaluate(bindings).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Every partition of `n` items into groups, as the group of each item numbered in order of
+/// first appearance, from the most groups to the fewest, where no group holds two items that
+/// are `apart`.
+fn partitions(n: usize, apart: impl Fn(usize, usize) -> bool) -> Vec<Vec<usize>> {
+    let mut partitions = vec![vec![]];
+    for k in 0..n {
+        partitions = partitions
+            .into_iter()
+            .flat_map(|groups: Vec<usize>| {
+                let fresh = groups.iter().max().map_or(0, |g| g + 1);
+                (0..=fresh)
+                    .rev()
+                    .filter(|&g| (0..k).all(|i| groups[i] != g || !apart(i, k)))
+                    .map(|g| [groups.clone(), vec![g]].concat())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+    }
+    partitions.sort_by_key(|groups| std::cmp::Reverse(groups.iter().max().copied()));
+    partitions
+}
+
+/// `√x`, or `±√x` when `x` is a square `y·y`, since only even functions of it are taken.
+fn sqrt(x: &OpArgument) -> OpArgument {
+    match &x.value {
+        Op(op) if op.op == Multiplication && op.arguments[0] == op.arguments[1] => {
+            op.arguments[0].clone()
+        }
+        _ => x.pow(&Value::rational(1, 2).into()).simplify_radicals(),
+    }
+}
+
+fn constant(r: Ratio) -> Result<OpArgument, ExpmError> {
+    r.to_oparg().ok_or(ExpmError::NoClosedForm)
+}
+
+/// `-x`, if `x` is a negative constant, a negation, or a product with a negated factor.
+fn negated(x: &OpArgument) -> Option<OpArgument> {
+    if let Some(r) = Ratio::of(x) {
+        return (r.num < 0).then(|| (-r).to_oparg()).flatten();
+    }
+    let Op(op) = &x.value else {
+        return None;
+    };
+    let unwrap = |y: &OpArgument| match &y.value {
+        Op(inner) if inner.op == Negation => Some(inner.arguments[0].clone()),
+        _ => None,
+    };
+    match op.op {
+        Negation => unwrap(x),
+        Multiplication => match (unwrap(&op.arguments[0]), unwrap(&op.arguments[1])) {
+            (Some(a), None) => Some(times(&a, &op.arguments[1])),
+            (None, Some(b)) => Some(times(&op.arguments[0], &b)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether `x` is a square `y·y` or an even power `y^(2k)`, so never negative.
+fn is_square(x: &OpArgument) -> bool {
+    match &x.value {
+        Op(op) if op.op == Multiplication => op.arguments[0] == op.arguments[1],
+        Op(op) if op.op == Pow => {
+            Ratio::of(&op.arguments[1]).is_some_and(|r| r.den == 1 && r.num % 2 == 0)
+        }
+        _ => false,
+    }
+}
+
+/// The divided difference `exp[x₀, …, xₖ]`, with equal nodes next to each other, where `k + 1`
+/// equal nodes give `exp(x₀)/k!`.
+fn divided_difference(nodes: &[OpArgument]) -> OpArgument {
+    let (first, last) = (&nodes[0], &nodes[nodes.len() - 1]);
+    if first == last {
+        let factorial = (1..nodes.len() as u64).product::<u64>();
+        return (first.exp() / OpArgument::from(Value::integer(factorial))).fold();
+    }
+    let later = divided_difference(&nodes[1..]);
+    let earlier = divided_difference(&nodes[..nodes.len() - 1]);
+    ((later - earlier) / (last - first)).fold()
+}
+
+impl Matrix {
+    fn scale(&self, c: &OpArgument) -> Matrix {
+        self.map(|entry| times(c, &entry.fold()))
+    }
+
+    /// `self - c·I`.
+    fn shift(&self, c: &OpArgument) -> Matrix {
+        let minus = (-c).fold();
+        Matrix::from_fn(self.rows, self.cols, |i, j| match i == j {
+            true => plus(&self.get(i, j).fold(), &minus),
+            false => self.get(i, j).fold(),
+        })
+    }
+
+    fn rows_of(&self) -> Vec<Vec<OpArgument>> {
+        self.entries
+            .chunks(self.cols)
+            .map(|row| row.to_vec())
+            .collect()
+    }
+
+    /// The diagonal, if every entry above it or every entry below it is zero.
+    fn triangular_eigenvalues(&self) -> Option<Vec<OpArgument>> {
+        let upper = (0..self.rows).all(|i| (0..i).all(|j| self.get(i, j).fold() == zero()));
+        let lower =
+            (0..self.rows).all(|i| (i + 1..self.cols).all(|j| self.get(i, j).fold() == zero()));
+        (upper || lower).then(|| (0..self.rows).map(|i| self.get(i, i).fold()).collect())
+    }
+
+    /// `exp(self)` by Putzer's algorithm, `Σₖ exp[λ₀, …, λₖ]·(A - λ₀)⋯(A - λₖ₋₁)`, from every
+    /// eigenvalue with its multiplicity.
+    fn putzer(&self, eigenvalues: Vec<OpArgument>) -> Matrix {
+        // Equal eigenvalues must be next to each other for the divided differences.
+        let mut nodes: Vec<OpArgument> = Vec::new();
+        for eigenvalue in eigenvalues {
+            match nodes.iter().rposition(|node| *node == eigenvalue) {
+                Some(k) => nodes.insert(k + 1, eigenvalue),
+                None => nodes.push(eigenvalue),
+            }
+        }
+        let mut product = Matrix::identity(self.rows);
+        let mut result = Matrix::zeros(self.rows, self.cols);
+        for k in 0..nodes.len() {
+            result = &result + &product.scale(&divided_difference(&nodes[..=k]));
+            product = &product * &self.shift(&nodes[k]);
+        }
+        result
+    }
+
+    /// `exp(self)` for a triangular matrix, with a piece for every way its `eigenvalues` can
+    /// coincide, since the divided differences of Putzer's algorithm need equal eigenvalues
+    /// merged. Eigenvalues whose difference is a constant are merged or kept apart up front,
+    /// so only symbolic differences give rise to pieces.
+    fn triangular_expm(&self, eigenvalues: Vec<OpArgument>) -> PiecewiseMatrix {
+        let difference = |a: &OpArgument, b: &OpArgument| (a - b).fold();
+        // One representative for each set of eigenvalues that differ by zero.
+        let mut distinct: Vec<OpArgument> = Vec::new();
+        let cluster: Vec<usize> = eigenvalues
+            .iter()
+            .map(|eigenvalue| {
+                let same = distinct.iter().position(|other| {
+                    Ratio::of(&difference(eigenvalue, other)).is_some_and(|r| r.num == 0)
+                });
+                same.unwrap_or_else(|| {
+                    distinct.push(eigenvalue.clone());
+                    distinct.len() - 1
+                })
+            })
+            .collect();
+        let differences: Vec<Vec<OpArgument>> = (0..distinct.len())
+            .map(|j| {
+                (0..j)
+                    .map(|i| difference(&distinct[i], &distinct[j]))
+                    .collect()
+            })
+            .collect();
+        // Constant differences are nonzero by now, so those eigenvalues never coincide.
+        let apart = |i: usize, j: usize| Ratio::of(&differences[j][i]).is_some();
+
+        let mut pieces = Vec::new();
+        for groups in partitions(distinct.len(), apart) {
+            let mut condition = Formula::True;
+            for j in 0..groups.len() {
+                for i in (0..j).filter(|&i| !apart(i, j)) {
+                    let nonzero = Formula::atom(Condition::Nonzero(differences[j][i].clone()));
+                    let relation = match groups[i] == groups[j] {
+                        true => nonzero.not(),
+                        false => nonzero,
+                    };
+                    condition = conjoin(&condition, &relation);
+                }
+            }
+            // Every eigenvalue stands in for the others of its group.
+            let representative =
+                |g: usize| distinct[groups.iter().position(|&h| h == g).unwrap()].clone();
+            let merged = cluster.iter().map(|&c| representative(groups[c])).collect();
+            let always = condition == Formula::True;
+            pieces.push((condition, self.putzer(merged)));
+            if always {
+                break;
+            }
+        }
+        PiecewiseMatrix { pieces }
+    }
+
+    /// `exp(A) = e^t·(cosh(s)·I + sinh(s)/s·(A - t·I))` for a 2×2 matrix with eigenvalues
+    /// `t ± s`. When `s² < 0` the bracket is `cos(ω)·I + sin(ω)/ω·(A - t·I)` with `ω² = -s²`,
+    /// and when `s = 0` it is `A - (t - 1)·I`, so unless the sign of `s²` is known there is a
+    /// piece for each.
+    fn expm_2x2(&self) -> PiecewiseMatrix {
+        let (a, b, c, d) = (
+            self.get(0, 0),
+            self.get(0, 1),
+            self.get(1, 0),
+            self.get(1, 1),
+        );
+        let two = OpArgument::from(Value::integer(2));
+        let t = ((a + d) / &two).fold();
+        let half = ((a - d) / &two).fold();
+        let squared = plus(&times(&half, &half), &times(&b.fold(), &c.fold()));
+        let traceless = self.shift(&t);
+        let exp = |cosh: OpArgument, sinhc: OpArgument| {
+            let result = &Matrix::identity(2).scale(&cosh.fold()) + &traceless.scale(&sinhc.fold());
+            result.scale(&t.exp().fold())
+        };
+        let coincident = || exp(one(), one());
+        let hyperbolic = || {
+            let s = sqrt(&squared);
+            let (ahead, behind) = (s.exp(), (-&s).exp());
+            exp((&ahead + &behind) / &two, (ahead - behind) / (&two * &s))
+        };
+        let trigonometric = |opposite: &OpArgument| {
+            let omega = sqrt(opposite);
+            exp(omega.cos(), omega.sin() / &omega)
+        };
+        if squared == zero() {
+            return coincident().into();
+        }
+        let opposite = negated(&squared).unwrap_or_else(|| (-&squared).fold());
+        let sign = match squared.is_constant() {
+            true => squared.evaluate(&Bindings::default()).ok(),
+            false => None,
+        };
+        match sign {
+            Some(s) if s > 0.0 => return hyperbolic().into(),
+            Some(s) if s < 0.0 => return trigonometric(&opposite).into(),
+            _ => (),
+        }
+        let nonzero = Formula::atom(Condition::Nonzero(squared.clone()));
+        let mut pieces = Vec::new();
+        // `s²` is only provably never positive when it is the negation of a square.
+        if !negated(&squared).is_some_and(|opposite| is_square(&opposite)) {
+            let positive = Formula::atom(Condition::Nonnegative(squared.clone())).and(&nonzero);
+            pieces.push((positive, hyperbolic()));
+        }
+        pieces.push((nonzero, trigonometric(&opposite)));
+        pieces.push((Formula::True, coincident()));
+        PiecewiseMatrix { pieces }
+    }
+
+    /// `exp(A)` for a 3×3 matrix with rational entries, from a rational root `λ` of its
+    /// characteristic polynomial and the roots of the quadratic left over.
+    fn expm_3x3(&self) -> Result<PiecewiseMatrix, ExpmError> {
+        let rows = self.rows_of();
+        let minor = |i: usize, j: usize| {
+            determinant(&[
+                vec![rows[i][i].clone(), rows[i][j].clone()],
+                vec![rows[j][i].clone(), rows[j][j].clone()],
+            ])
+        };
+        let ratio = |x: OpArgument| Ratio::of(&x.fold()).ok_or(ExpmError::NoClosedForm);
+        // x³ - tr(A)·x² + (sum of principal minors)·x - det(A)
+        let characteristic = [
+            ratio(-determinant(&rows))?,
+            ratio(minor(0, 1) + minor(0, 2) + minor(1, 2))?,
+            ratio(-(&rows[0][0] + &rows[1][1] + &rows[2][2]))?,
+            Ratio::ONE,
+        ];
+        let lambda = rational_root(&characteristic).ok_or(ExpmError::NoClosedForm)?;
+        let [c, b, _] = deflate(&characteristic, lambda).ok_or(ExpmError::NoClosedForm)?[..] else {
+            unreachable!("Oops, deflating a cubic left something other than a quadratic");
+        };
+        // The other roots are μ ± √D.
+        let checked = |x: Option<Ratio>| x.ok_or(ExpmError::NoClosedForm);
+        let mu = checked(Ratio::new(-1, 2).and_then(|half| b.checked_mul(half)))?;
+        let discriminant = checked(mu.checked_mul(mu).and_then(|m| m.checked_add(-c)))?;
+        let (lambda_arg, mu_arg) = (constant(lambda)?, constant(mu)?);
+        if discriminant.num >= 0 {
+            let offset = sqrt(&constant(discriminant)?);
+            let eigenvalues =
+                [&mu_arg + &offset, &mu_arg - &offset].map(|root| root.simplify_radicals().fold());
+            let eigenvalues = [vec![lambda_arg], eigenvalues.to_vec()].concat();
+            return Ok(self.putzer(eigenvalues).into());
+        }
+
+        // For eigenvalues μ ± iq, the real polynomial agreeing with exp on the spectrum is
+        // r(x) = e^λ·((x - μ)² + q²)/Δ + (x - λ)·(u·x + v), with d = μ - λ, Δ = d² + q², and
+        // u·z + v = e^z/(z - λ) at z = μ + iq.
+        let q = sqrt(&constant(-discriminant)?);
+        let d_ratio = checked(mu.checked_add(-lambda))?;
+        let delta = checked(d_ratio.checked_mul(d_ratio))?;
+        let delta = constant(checked(delta.checked_add(-discriminant))?)?;
+        let d = constant(d_ratio)?;
+        let (cos, sin, e_mu) = (q.cos(), q.sin(), mu_arg.exp());
+        let u = (&e_mu * (&d * &sin - &q * &cos) / (&delta * &q)).fold();
+        let v = (&e_mu * (&d * &cos + &q * &sin) / &delta - &u * &mu_arg).fold();
+        let around = self.shift(&mu_arg);
+        let quadratic =
+            &(&around * &around) + &Matrix::identity(3).scale(&constant(-discriminant)?);
+        let first = quadratic.scale(&(lambda_arg.exp() / &delta).fold());
+        let linear = &self.scale(&u) + &Matrix::identity(3).scale(&v);
+        Ok((&first + &(&self.shift(&lambda_arg) * &linear)).into())
+    }
+
+    /// The matrix exponential `exp(A) = I + A + A²/2! + …` in closed form, in pieces when the
+    /// form depends on whether symbolic eigenvalues coincide or are real.
+    pub fn expm(&self) -> Result<PiecewiseMatrix, ExpmError> {
+        if self.rows != self.cols {
+            return Err(ExpmError::NotSquare(self.rows, self.cols));
+        }
+        if let Some(eigenvalues) = self.triangular_eigenvalues() {
+            return Ok(self.triangular_expm(eigenvalues));
+        }
+        match self.rows {
+            2 => Ok(self.expm_2x2()),
+            3 => self.expm_3x3(),
+            _ => Err(ExpmError::NoClosedForm),
+        }
+    }
+}
+
+impl Add<&Matrix> for &Matrix {
+    type Output = Matrix;
+
+    fn add(self, rhs: &Matrix) -> Matrix {
+        assert_eq!(
+            self.shape(),
+            rhs.shape(),
+            "Oops, matrices of different shapes cannot be added"
+        );
+        let entries = self
+            .entries
+            .iter()
+            .zip(&rhs.entries)
+            .map(|(a, b)| plus(&a.fold(), &b.fold()))
+            .collect();
+        Matrix::new(self.rows, self.cols, entries)
+    }
+}
+
+impl Mul<&Matrix> for &Matrix {
+    type Output = Matrix;
+
+    fn mul(self, rhs: &Matrix) -> Matrix {
+        assert_eq!(
+            self.cols, rhs.rows,
+            "Oops, a {}×{} matrix cannot multiply a {}×{} one",
+            self.rows, self.cols, rhs.rows, rhs.cols
+        );
+        Matrix::from_fn(self.rows, rhs.cols, |i, j| {
+            (0..self.cols).fold(zero(), |sum, k| {
+                plus(&sum, &times(&self.get(i, k).fold(), &rhs.get(k, j).fold()))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExpmError, Matrix};
+    use crate::{evaluation::Bindings, parse::parse, symbols::intern};
+
+    #[test]
+    fn test_matrix() {
+        let p = |input: &str| parse(input).unwrap().fold();
+        let a = Matrix::symbols("a", (2, 3));
+        assert_eq!(a.shape(), (2, 3));
+        assert_eq!(*a.get(1, 2), p("a_1_2"));
+        assert_eq!(a.transpose().get(2, 1), a.get(1, 2));
+
+        let bindings = Bindings::from_iter([
+            ("a_0_0", 1.0),
+            ("a_0_1", 2.0),
+            ("a_0_2", 3.0),
+            ("a_1_0", 4.0),
+            ("a_1_1", 5.0),
+            ("a_1_2", 6.0),
+        ]);
+        // Column-major, as DMatrix::from_vec expects.
+        assert_eq!(
+            a.evaluate(&bindings),
+            Ok(vec![1.0, 4.0, 2.0, 5.0, 3.0, 6.0])
+        );
+        let gram = &a * &a.transpose();
+        assert_eq!(gram.evaluate(&bindings), Ok(vec![14.0, 32.0, 32.0, 77.0]));
+        assert_eq!(&Matrix::identity(2) * &gram, gram);
+        assert_eq!(&gram + &Matrix::zeros(2, 2), gram);
+
+        // The Jacobian of polar coordinates has determinant r.
+        let polar = [p("r * cos(t)"), p("r * sin(t)")];
+        let jacobian = Matrix::jacobian(&polar, &["r", "t"]);
+        let at = Bindings::from_iter([("r", 2.0), ("t", 0.5)]);
+        let j = jacobian.evaluate(&at).unwrap();
+        assert!((j[0] * j[3] - j[1] * j[2] - 2.0).abs() < 1e-15);
+        assert_eq!(*jacobian.get(0, 0), p("cos(t)"));
+        assert_eq!(
+            gram.evaluate_into::<Vec<f64>>(&bindings),
+            gram.evaluate(&bindings)
+        );
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn test_nalgebra() {
+        use nalgebra::{DMatrix, DVector, Matrix2x3};
+
+        let p = |input: &str| parse(input).unwrap().fold();
+        let numeric = Matrix2x3::new(1.0, 0.5, -2.0, 0.0, 3.0, 1e-30);
+        let a = Matrix::symbols_like("a", &numeric);
+        assert_eq!(a.shape(), (2, 3));
+
+        // The rotation by t, derived symbolically and handed to nalgebra.
+        let rotation = Matrix::new(
+            2,
+            2,
+            vec![p("cos(t)"), p("-sin(t)"), p("sin(t)"), p("cos(t)")],
+        );
+        let at = Bindings::from_iter([("t", 0.25)]);
+        let r: DMatrix<f64> = rotation.evaluate_into(&at).unwrap();
+        assert_eq!(r[(0, 1)], -(0.25f64).sin());
+        assert!((r.transpose() * &r - DMatrix::identity(2, 2)).norm() < 1e-15);
+
+        let exact = Matrix::from(&numeric);
+        assert_eq!(*exact.get(0, 1), p("1/2"));
+        assert_eq!(*exact.get(0, 2), p("-2"));
+        assert_eq!(exact.evaluate_into::<DMatrix<f64>>(&at).unwrap(), numeric);
+        let product = &exact * &exact.transpose();
+        assert_eq!(
+            product.evaluate_into::<DMatrix<f64>>(&at).unwrap(),
+            numeric * numeric.transpose()
+        );
+
+        let v = DVector::from_vec(vec![1.0, 2.0]);
+        let column = &rotation * &Matrix::from(&v);
+        let rotated: DVector<f64> = column.evaluate_into(&at).unwrap();
+        assert!((rotated - &r * v).norm() < 1e-15);
+    }
+
+    #[test]
+    fn test_kronecker() {
+        let p = |input: &str| parse(input).unwrap().fold();
+        let a = Matrix::symbols("a", (2, 3));
+        let x = Matrix::symbols("x", (3, 2));
+        let b = Matrix::symbols("b", (2, 2));
+        let bindings = Bindings::from_iter(
+            ["a", "x", "b"]
+                .into_iter()
+                .flat_map(|name| (0..3).flat_map(move |i| (0..3).map(move |j| (name, i, j))))
+                .enumerate()
+                .map(|(k, (name, i, j))| {
+                    let name = intern(&format!("{}_{}_{}", name, i, j));
+                    (name, (k as f64 * 0.37).sin())
+                }),
+        );
+
+        let kron = Matrix::identity(2).kronecker(&b);
+        assert_eq!(kron.shape(), (4, 4));
+        assert_eq!(*kron.get(3, 2), p("b_1_0"));
+        assert_eq!(*kron.get(0, 3), p("0"));
+
+        // vec(AXB) = (Bᵀ⊗A)·vec(X)
+        let lhs = (&(&a * &x) * &b).vec();
+        let rhs = &b.transpose().kronecker(&a) * &x.vec();
+        let (lhs, rhs) = (
+            lhs.evaluate(&bindings).unwrap(),
+            rhs.evaluate(&bindings).unwrap(),
+        );
+        assert!(lhs.iter().zip(&rhs).all(|(l, r)| (l - r).abs() < 1e-12));
+        assert_eq!(*x.vec().get(4, 0), p("x_1_1"));
+        assert_eq!(Matrix::unvec(&x.vec(), 3, 2), x);
+
+        let blocks = Matrix::block(&[
+            vec![a.clone(), Matrix::identity(2)],
+            vec![Matrix::zeros(1, 3), Matrix::symbols("c", (1, 2))],
+        ]);
+        assert_eq!(blocks.shape(), (3, 5));
+        assert_eq!(*blocks.get(2, 4), p("c_0_1"));
+        assert_eq!(blocks.slice(0..2, 0..3), a);
+        assert_eq!(blocks.slice(0..2, 3..5), Matrix::identity(2));
+    }
+
+    #[test]
+    fn test_gradient() {
+        let a = Matrix::symbols("a", (2, 2));
+        let x = Matrix::symbols("x", (2, 2));
+        let v = Matrix::symbols("v", (2, 1));
+
+        // ∂tr(AX)/∂X = Aᵀ
+        assert_eq!(Matrix::gradient(&(&a * &x).trace(), &x), a.transpose());
+        // ∂tr(XᵀX)/∂X = 2X
+        let squares = (&x.transpose() * &x).trace();
+        let bindings = Bindings::from_iter([
+            ("a_0_0", 0.5),
+            ("a_0_1", -1.0),
+            ("a_1_0", 2.0),
+            ("a_1_1", 0.25),
+            ("x_0_0", 1.5),
+            ("x_0_1", -0.5),
+            ("x_1_0", 3.0),
+            ("x_1_1", 0.75),
+            ("v_0_0", 1.25),
+            ("v_1_0", -2.0),
+        ]);
+        let double = x.map(|e| e * parse("2").unwrap());
+        assert_eq!(
+            Matrix::gradient(&squares, &x).evaluate(&bindings),
+            double.evaluate(&bindings)
+        );
+        // ∂(vᵀAv)/∂v = (A + Aᵀ)v
+        let quadratic = (&(&v.transpose() * &a) * &v).get(0, 0).clone();
+        let gradient = Matrix::gradient(&quadratic, &v);
+        assert_eq!(gradient.shape(), (2, 1));
+        assert_eq!(
+            gradient.evaluate(&bindings),
+            (&(&a + &a.transpose()) * &v).evaluate(&bindings)
+        );
+    }
+
+    #[test]
+    fn test_expm() {
+        let p = |input: &str| parse(input).unwrap().fold();
+        let matrix =
+            |n: usize, entries: &[&str]| Matrix::new(n, n, entries.iter().map(|e| p(e)).collect());
+        // exp(A) by its Taylor series, in the column-major layout of `Matrix::evaluate`.
+        let taylor = |a: &[f64], n: usize| {
+            let mut term = (0..n * n)
+                .map(|k| (k % (n + 1) == 0) as u8 as f64)
+                .collect::<Vec<_>>();
+            let mut sum = term.clone();
+            for k in 1..60 {
+                term = (0..n * n)
+                    .map(|ij| {
+                        (0..n)
+                            .map(|l| term[ij % n + n * l] * a[l + n * (ij / n)])
+                            .sum::<f64>()
+                            / k as f64
+                    })
+                    .collect();
+                sum.iter_mut().zip(&term).for_each(|(s, t)| *s += t);
+            }
+            sum
+        };
+        let at = Bindings::from_iter([("w", 0.7), ("a", 0.3), ("b", -1.1), ("c", -0.4)]);
+        for (n, entries) in [
+            (2, &["0", "-w", "w", "0"][..]),
+            (2, &["a", "b", "c", "a"]),
+            (2, &["a", "1", "0", "a"]),
+            (2, &["1", "1", "-1", "-1"]),
+            (3, &["a", "b", "c", "0", "a", "w", "0", "0", "b"]),
+            (3, &["0", "1", "0", "0", "0", "1", "2", "-1", "2"]),
+            (3, &["0", "1", "0", "0", "0", "1", "0", "2", "0"]),
+            (3, &["1", "2", "0", "2", "1", "0", "0", "0", "3"]),
+            (3, &["1", "-2", "0", "1", "1", "0", "1", "1/2", "2"]),
+        ] {
+            let a = matrix(n, entries);
+            let expected = taylor(&a.evaluate(&at).unwrap(), n);
+            let exp = a.expm().unwrap().evaluate(&at).unwrap().unwrap();
+            assert!(
+                exp.iter()
+                    .zip(&expected)
+                    .all(|(x, y)| (x - y).abs() < 1e-12 * y.abs().max(1.0)),
+                "exp({:?}) = {:?}, not {:?}",
+                entries,
+                exp,
+                expected
+            );
+        }
+
+        // Symbolic matrices have a piece for each kind of spectrum they can take.
+        let general = matrix(2, &["a", "b", "c", "d"]);
+        let triangular = matrix(2, &["a", "b", "0", "d"]);
+        for values in [
+            // Complex, real and coincident eigenvalues.
+            [1.0, 2.0, -3.0, 1.0],
+            [0.5, 2.0, 3.0, -1.0],
+            [0.5, 2.0, 0.0, 0.5],
+            [0.0, 0.0, 0.0, 0.0],
+        ] {
+            let at = Bindings::from_iter(["a", "b", "c", "d"].into_iter().zip(values));
+            for (name, a) in [("general", &general), ("triangular", &triangular)] {
+                let expected = taylor(&a.evaluate(&at).unwrap(), 2);
+                let exp = a.expm().unwrap().evaluate(&at).unwrap().unwrap();
+                assert!(
+                    exp.iter()
+                        .zip(&expected)
+                        .all(|(x, y)| (x - y).abs() < 1e-12 * y.abs().max(1.0)),
+                    "exp({} at {:?}) = {:?}, not {:?}",
+                    name,
+                    values,
+                    exp,
+                    expected
+                );
+            }
+        }
+        assert_eq!(general.expm().unwrap().pieces().len(), 3);
+        assert_eq!(triangular.expm().unwrap().pieces().len(), 2);
+
+        // Constant differences never branch, however many eigenvalues there are.
+        let identity = Matrix::identity(12).expm().unwrap();
+        assert_eq!(identity.pieces().len(), 1);
+        assert_eq!(
+            identity.get(3, 3).evaluate(&at),
+            Ok(Some(std::f64::consts::E))
+        );
+        // b may equal 2 or 3, but not both.
+        let mixed = matrix(3, &["2", "1", "0", "0", "3", "0", "0", "0", "b"]);
+        assert_eq!(mixed.expm().unwrap().pieces().len(), 3);
+        for b in [2.0, 3.0, 5.0] {
+            let at = Bindings::from_iter([("b", b)]);
+            let expected = taylor(&mixed.evaluate(&at).unwrap(), 3);
+            let exp = mixed.expm().unwrap().evaluate(&at).unwrap().unwrap();
+            assert!(exp
+                .iter()
+                .zip(&expected)
+                .all(|(x, y)| (x - y).abs() < 1e-12 * y.abs().max(1.0)));
+        }
+
+        // A rotation generator exponentiates to a rotation, unless it is zero.
+        let rotation = matrix(2, &["0", "-w", "w", "0"]).expm().unwrap();
+        assert_eq!(rotation.pieces().len(), 2);
+        assert_eq!(rotation.pieces()[0].1.get(0, 0), &p("cos(w)"));
+        let at = Bindings::from_iter([("w", 0.0)]);
+        assert_eq!(rotation.get(0, 0).evaluate(&at), Ok(Some(1.0)));
+        assert_eq!(Matrix::zeros(2, 2).expm(), Ok(Matrix::identity(2).into()));
+
+        // A negated product is not a negated square, so it may still be positive.
+        let skew = matrix(2, &["0", "-b", "c", "0"]).expm().unwrap();
+        let at = Bindings::from_iter([("b", 1.0), ("c", -1.0)]);
+        let (cosh, sinh) = (1f64.cosh(), 1f64.sinh());
+        let exp = skew.evaluate(&at).unwrap().unwrap();
+        assert!(exp
+            .iter()
+            .zip([cosh, -sinh, -sinh, cosh])
+            .all(|(x, y)| (x - y).abs() < 1e-12));
+
+        assert_eq!(Matrix::zeros(2, 3).expm(), Err(ExpmError::NotSquare(2, 3)));
+        let irreducible = matrix(3, &["0", "1", "0", "0", "0", "1", "2", "0", "0"]);
+        assert_eq!(irreducible.expm(), Err(ExpmError::NoClosedForm));
+        assert_eq!(
+            Matrix::symbols("m", (3, 3)).expm(),
+            Err(ExpmError::NoClosedForm)
+        );
+    }
+}
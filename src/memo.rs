@@ -0,0 +1,159 @@
+//! This module describes a disk-backed cache of the results of expensive passes.
+//!
+//! A [`DiskCache`] is a directory holding one file per cached result, named after the pass and
+//! the [`OpArgument::digest`] of its input, and containing the input and the result in the
+//! format of [`crate::serialize`]. Digests are the same in every run, so iterative workflows
+//! on large fixed expressions only pay for each pass once. The stored input is compared with
+//! the one looked up, so even a digest collision cannot return a wrong result.
+//!
+//! The cache only ever speeds things up: a missing, unreadable or corrupt entry is a miss, and
+//! a result that cannot be written is still returned. Entries are written to a temporary file
+//! and renamed into place, so processes sharing a directory never see half-written ones.
+//! Expressions over the symbols of a [`crate::context::Context`] carry its per-run scope in
+//! their names, so they only hit within one run.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    serialize::{read_expressions, write_expressions},
+    symbols::OpArgument,
+};
+
+#[derive(Clone, Debug)]
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    /// The cache in the directory `dir`, which is created if it does not exist yet.
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(DiskCache {
+            dir: dir.as_ref().to_owned(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, pass: &str, expr: &OpArgument) -> PathBuf {
+        assert!(
+            !pass.is_empty()
+                && pass
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+            "Hold on, cache pass names must be nonempty and alphanumeric, but got {:?}",
+            pass
+        );
+        self.dir
+            .join(format!("{}-{:032x}.bin", pass, expr.digest()))
+    }
+
+    /// The result of `pass` on `expr` stored in the cache, if any.
+    pub fn get(&self, pass: &str, expr: &OpArgument) -> Option<OpArgument> {
+        let file = File::open(self.path(pass, expr)).ok()?;
+        match read_expressions(BufReader::new(file)).ok()?.as_slice() {
+            [input, result] if input == expr && input.digest() == expr.digest() => {
+                Some(result.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Stores `result` as the result of `pass` on `expr`.
+    pub fn insert(&self, pass: &str, expr: &OpArgument, result: &OpArgument) -> io::Result<()> {
+        let path = self.path(pass, expr);
+        let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+        let mut w = BufWriter::new(File::create(&partial)?);
+        write_expressions(&mut w, &[expr, result])?;
+        w.flush()?;
+        drop(w);
+        fs::rename(&partial, &path)
+    }
+
+    /// The result of `pass` on `expr`, computed by `f` unless it is already in the cache.
+    pub fn memoize(
+        &self,
+        pass: &str,
+        expr: &OpArgument,
+        f: impl FnOnce(&OpArgument) -> OpArgument,
+    ) -> OpArgument {
+        if let Some(result) = self.get(pass, expr) {
+            return result;
+        }
+        let result = f(expr);
+        // A result that cannot be stored is recomputed next time, which is all a cache owes.
+        let _ = self.insert(pass, expr, &result);
+        result
+    }
+
+    /// [`OpArgument::fold`], cached.
+    pub fn fold(&self, expr: &OpArgument) -> OpArgument {
+        self.memoize("fold", expr, OpArgument::fold)
+    }
+
+    /// Removes every entry, and the temporary files left behind by interrupted writes.
+    pub fn clear(&self) -> io::Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|ext| ext == "bin" || ext == "tmp")
+            {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::DiskCache;
+    use crate::parse::parse;
+
+    #[test]
+    fn test_disk_cache() {
+        let dir = std::env::temp_dir().join(format!("symbolica-memo-{}", std::process::id()));
+        let cache = DiskCache::open(&dir).unwrap();
+        let expr = parse("2 * 3 + sin(pi / 6) * x").unwrap();
+
+        let calls = Cell::new(0);
+        let counted = |expr: &crate::symbols::OpArgument| {
+            calls.set(calls.get() + 1);
+            expr.fold()
+        };
+        let first = cache.memoize("fold", &expr, counted);
+        assert_eq!(first, expr.fold());
+        assert_eq!(cache.memoize("fold", &expr, counted), first);
+        assert_eq!(calls.get(), 1);
+
+        // A second handle on the directory, as in a later run, sees the entry.
+        let reopened = DiskCache::open(&dir).unwrap();
+        assert_eq!(reopened.get("fold", &expr), Some(first.clone()));
+        assert_eq!(reopened.fold(&expr), first);
+        assert_eq!(reopened.get("other", &expr), None);
+
+        // Corrupt entries are misses.
+        let entry = std::fs::read_dir(&dir).unwrap().next().unwrap().unwrap();
+        std::fs::write(entry.path(), b"garbage").unwrap();
+        assert_eq!(reopened.get("fold", &expr), None);
+        assert_eq!(cache.memoize("fold", &expr, counted), first);
+        assert_eq!(calls.get(), 2);
+
+        // A write interrupted before its rename leaves a temporary file behind.
+        let partial = dir.join("fold-0.1.tmp");
+        std::fs::write(&partial, b"partial").unwrap();
+        cache.clear().unwrap();
+        assert_eq!(cache.get("fold", &expr), None);
+        assert!(!partial.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+//! This module defines the facts cached on the nodes of our computational graph.
+
+use std::fmt::Debug;
+
+use smallvec::SmallVec;
+
+use crate::{
+    constants::Value,
+    symbols::{lookup_symbol, symbol_index, symbol_name, OpArgument},
+};
+
+/// A set of variables, stored as a bitset over interned symbol indices.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct VariableSet {
+    words: SmallVec<[u64; 2]>,
+}
+
+impl VariableSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn position(index: u32) -> (usize, u64) {
+        ((index / 64) as usize, 1 << (index % 64))
+    }
+
+    pub fn insert(&mut self, name: &'static str) {
+        let (word, bit) = Self::position(symbol_index(name));
+        if self.words.len() <= word {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= bit;
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        let Some(index) = lookup_symbol(name) else {
+            return false;
+        };
+        let (word, bit) = Self::position(index);
+        self.words.get(word).is_some_and(|w| w & bit != 0)
+    }
+
+    /// Adds every variable of `other` to `self`.
+    pub fn union_with(&mut self, other: &VariableSet) {
+        if self.words.len() < other.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        self.words
+            .iter_mut()
+            .zip(&other.words)
+            .for_each(|(a, b)| *a |= b);
+    }
+
+    /// Whether `self` and `other` share any variable.
+    pub fn intersects(&self, other: &VariableSet) -> bool {
+        self.words.iter().zip(&other.words).any(|(a, b)| a & b != 0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// The variables in the set, in interning order.
+    pub fn iter(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| symbol_name((i * 64 + bit) as u32))
+        })
+    }
+}
+
+impl Debug for VariableSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl FromIterator<&'static str> for VariableSet {
+    fn from_iter<T: IntoIterator<Item = &'static str>>(iter: T) -> Self {
+        let mut set = VariableSet::new();
+        iter.into_iter().for_each(|name| set.insert(name));
+        set
+    }
+}
+
+/// Structural facts about a node, derived entirely from its children.
+#[derive(Clone, Debug)]
+pub(crate) struct Metadata {
+    pub(crate) free_variables: VariableSet,
+    pub(crate) depth: usize,
+}
+
+impl Metadata {
+    pub(crate) fn of_value(value: &Value) -> Self {
+        let mut free_variables = VariableSet::new();
+        if let Value::Variable(name) = *value {
+            free_variables.insert(name);
+        }
+
+        Metadata {
+            free_variables,
+            depth: 0,
+        }
+    }
+
+    pub(crate) fn of_operation(arguments: &[OpArgument]) -> Self {
+        let mut free_variables = VariableSet::new();
+        let mut depth = 0;
+        for arg in arguments {
+            free_variables.union_with(&arg.free_variables());
+            depth = depth.max(arg.depth());
+        }
+
+        Metadata {
+            free_variables,
+            depth: depth + 1,
+        }
+    }
+
+    pub(crate) fn is_constant(&self) -> bool {
+        self.free_variables.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        constants::Value,
+        symbols::{variable, OpArgument},
+    };
+
+    #[test]
+    fn test_metadata() {
+        let x = variable("x");
+        let y = variable("y");
+        let two: OpArgument = Value::integer(2).into();
+
+        let expr = (&x * &y).sin() + x.pow(&two);
+        let vars = expr.free_variables();
+        assert!(vars.contains("x") && vars.contains("y") && !vars.contains("z"));
+        assert_eq!(vars.len(), 2);
+        assert_eq!(vars.iter().collect::<Vec<_>>().len(), 2);
+        assert!(!expr.is_constant());
+        assert_eq!(expr.depth(), 3);
+
+        let constant = two.exp();
+        assert!(constant.is_constant());
+        assert!(constant.free_variables().is_empty());
+        assert_eq!(constant.depth(), 1);
+    }
+}
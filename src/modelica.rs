@@ -0,0 +1,537 @@
+//! This module describes how to parse our computational graph from Modelica's expression
+//! syntax, for equations extracted from Modelica models.
+//!
+//! The grammar is Modelica's: `if c then a elseif d then b else c` expressions, the logical
+//! operators `or`, `and` and `not`, the relations `<`, `<=`, `>`, `>=`, `==` and `<>`, and
+//! arithmetic with `^` binding tightest and unary minus applying to a whole term, so `-a^2` is
+//! `-(a^2)`. The element-wise operators `.+`, `.-`, `.*`, `./` and `.^` are the same as their
+//! scalar forms here. Names may be dotted, as in `body.frame.x`, or quoted, as in `'x y'`;
+//! `time` is the independent variable, and `Modelica.Constants.pi`, `Modelica.Constants.e` and
+//! `Modelica.Constants.inf` are the constants.
+//!
+//! `der(e)` is the derivative of `e` with respect to `time`, with the derivative of each
+//! variable `x` in it the variable `der(x)`, so `der(x * y)` is `der(x)*y+x*der(y)`. Since the
+//! graph has no conditional operations, [`parse_modelica`] returns a [`Piecewise`].
+
+use std::fmt::Display;
+
+use crate::{
+    boolean::Formula,
+    condition::Condition,
+    constants::Value,
+    mathml,
+    piecewise::{conjoin, Piecewise, Relation},
+    symbols::{intern, variable, OpArgument},
+};
+
+/// The reasons parsing a Modelica expression can fail. Positions are byte offsets into the
+/// input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ModelicaError {
+    UnexpectedCharacter(usize, char),
+    UnexpectedToken(usize),
+    UnexpectedEnd,
+    UnknownFunction(usize, String),
+    NumberTooLarge(usize),
+    /// A condition is used where a number is needed.
+    ExpectedExpression(usize),
+    /// A number is used where a condition is needed.
+    ExpectedCondition(usize),
+    /// A condition compares `if` expressions, which a [`Condition`] can't hold.
+    ConditionalComparison(usize),
+}
+
+impl Display for ModelicaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelicaError::UnexpectedCharacter(pos, c) => {
+                write!(f, "unexpected character {:?} at {}", c, pos)
+            }
+            ModelicaError::UnexpectedToken(pos) => write!(f, "unexpected token at {}", pos),
+            ModelicaError::UnexpectedEnd => f.write_str("unexpected end of input"),
+            ModelicaError::UnknownFunction(pos, name) => {
+                write!(f, "unknown function {} at {}", name, pos)
+            }
+            ModelicaError::NumberTooLarge(pos) => write!(f, "number at {} is too large", pos),
+            ModelicaError::ExpectedExpression(pos) => {
+                write!(f, "expected an expression at {}", pos)
+            }
+            ModelicaError::ExpectedCondition(pos) => write!(f, "expected a condition at {}", pos),
+            ModelicaError::ConditionalComparison(pos) => {
+                write!(f, "comparison of if expressions at {}", pos)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ModelicaError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token<'a> {
+    Number(&'a str),
+    Ident(&'a str),
+    Keyword(&'a str),
+    Symbol(&'static str),
+}
+
+const KEYWORDS: [&str; 9] = [
+    "if", "then", "elseif", "else", "and", "or", "not", "true", "false",
+];
+
+/// Longer symbols first, so that `<=` is not read as `<`.
+const SYMBOLS: [&str; 18] = [
+    ".+", ".-", ".*", "./", ".^", "<=", ">=", "==", "<>", "+", "-", "*", "/", "^", "<", ">", "(",
+    ")",
+];
+
+struct Lexer<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn tokens(input: &'a str) -> Result<Vec<(usize, Token<'a>)>, ModelicaError> {
+        let mut lexer = Lexer { input, pos: 0 };
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next_token()? {
+            tokens.push(token);
+        }
+        Ok(tokens)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !pred(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    /// A name, with the dots and parts that follow it.
+    fn name(&mut self) -> Result<(), ModelicaError> {
+        loop {
+            match self.rest().chars().next() {
+                Some('\'') => {
+                    let start = self.pos;
+                    self.pos += 1;
+                    self.take_while(|c| c != '\'');
+                    if self.rest().is_empty() {
+                        return Err(ModelicaError::UnexpectedCharacter(start, '\''));
+                    }
+                    self.pos += 1;
+                }
+                _ => {
+                    self.take_while(|c| c.is_alphanumeric() || c == '_');
+                }
+            }
+            let mut rest = self.rest().chars();
+            match (rest.next(), rest.next()) {
+                (Some('.'), Some(c)) if c.is_alphabetic() || c == '_' || c == '\'' => self.pos += 1,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<(usize, Token<'a>)>, ModelicaError> {
+        self.take_while(char::is_whitespace);
+        let start = self.pos;
+        let Some(c) = self.rest().chars().next() else {
+            return Ok(None);
+        };
+
+        let token = if c.is_ascii_digit() {
+            self.take_while(|c| c.is_ascii_digit());
+            if self.rest().starts_with('.') {
+                self.pos += 1;
+                self.take_while(|c| c.is_ascii_digit());
+            }
+            let mut exponent = self.rest().chars();
+            if let Some('e' | 'E') = exponent.next() {
+                let sign = usize::from(matches!(exponent.clone().next(), Some('+' | '-')));
+                if exponent.nth(sign).is_some_and(|c| c.is_ascii_digit()) {
+                    self.pos += 1 + sign;
+                    self.take_while(|c| c.is_ascii_digit());
+                }
+            }
+            Token::Number(&self.input[start..self.pos])
+        } else if c.is_alphabetic() || c == '_' || c == '\'' {
+            self.name()?;
+            let name = &self.input[start..self.pos];
+            match KEYWORDS.contains(&name) {
+                true => Token::Keyword(name),
+                false => Token::Ident(name),
+            }
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| self.rest().starts_with(*s)) {
+            self.pos += symbol.len();
+            Token::Symbol(symbol)
+        } else {
+            return Err(ModelicaError::UnexpectedCharacter(start, c));
+        };
+
+        Ok(Some((start, token)))
+    }
+}
+
+/// What a subexpression is: a number, possibly piecewise, or a condition.
+enum Node {
+    Value(Piecewise),
+    Condition(Formula<Condition>),
+}
+
+/// `e` differentiated with respect to `time`, with the derivative of each variable `x` the
+/// variable `der(x)`.
+fn der(e: &OpArgument) -> OpArgument {
+    let time = intern("time");
+    e.free_variables()
+        .iter()
+        .map(|var| match var == time {
+            true => e.derivative(var),
+            false => e.derivative(var) * variable(intern(&format!("der({})", var))),
+        })
+        .fold(OpArgument::from(Value::integer(0)), |sum, term| sum + term)
+        .fold()
+}
+
+fn function(name: &str) -> Option<fn(&OpArgument) -> OpArgument> {
+    Some(match name {
+        "der" => der,
+        "sin" => OpArgument::sin,
+        "cos" => OpArgument::cos,
+        "tan" => OpArgument::tan,
+        "atan" => OpArgument::atan,
+        "exp" => OpArgument::exp,
+        "log" => OpArgument::ln,
+        "log10" => |x| x.ln() / OpArgument::from(Value::integer(10)).ln(),
+        "sqrt" => |x| x.pow(&Value::rational(1, 2).into()),
+        _ => return None,
+    })
+}
+
+struct Parser<'a> {
+    tokens: Vec<(usize, Token<'a>)>,
+    next: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+
+    /// The position of the next token, or of the end of the input.
+    fn pos(&self) -> usize {
+        self.tokens
+            .get(self.next)
+            .map_or(usize::MAX, |(pos, _)| *pos)
+    }
+
+    fn bump(&mut self) -> Result<(usize, Token<'a>), ModelicaError> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .cloned()
+            .ok_or(ModelicaError::UnexpectedEnd)?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, token: Token) -> bool {
+        let found = self.peek() == Some(&token);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn eat_symbol(&mut self, symbols: &[&str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(&Token::Symbol(symbol)) if symbols.contains(&symbol) => {
+                self.next += 1;
+                Some(symbol)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), ModelicaError> {
+        match self.bump()? {
+            (_, found) if found == token => Ok(()),
+            (pos, _) => Err(ModelicaError::UnexpectedToken(pos)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Piecewise, ModelicaError> {
+        let pos = self.pos();
+        match self.expression()? {
+            Node::Value(value) => Ok(value),
+            Node::Condition(_) => Err(ModelicaError::ExpectedExpression(pos)),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Formula<Condition>, ModelicaError> {
+        let pos = self.pos();
+        match self.expression()? {
+            Node::Condition(condition) => Ok(condition),
+            Node::Value(_) => Err(ModelicaError::ExpectedCondition(pos)),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Node, ModelicaError> {
+        if !self.eat(Token::Keyword("if")) {
+            return self.disjunction();
+        }
+        let mut pieces = Vec::new();
+        loop {
+            let condition = self.condition()?;
+            self.expect(Token::Keyword("then"))?;
+            let value = self.value()?;
+            pieces.extend(
+                value
+                    .pieces
+                    .into_iter()
+                    .map(|(inner, value)| (conjoin(&condition, &inner), value)),
+            );
+            if !self.eat(Token::Keyword("elseif")) {
+                break;
+            }
+        }
+        self.expect(Token::Keyword("else"))?;
+        pieces.extend(self.value()?.pieces);
+        Ok(Node::Value(Piecewise { pieces }))
+    }
+
+    /// Parses operands with `operand` and joins them with the keyword `op`.
+    fn logical(
+        &mut self,
+        op: &str,
+        operand: fn(&mut Self) -> Result<Node, ModelicaError>,
+        join: fn(Vec<Formula<Condition>>) -> Formula<Condition>,
+    ) -> Result<Node, ModelicaError> {
+        let pos = self.pos();
+        let first = operand(self)?;
+        if self.peek() != Some(&Token::Keyword(op)) {
+            return Ok(first);
+        }
+        let mut operands = vec![(pos, first)];
+        while self.eat(Token::Keyword(op)) {
+            operands.push((self.pos(), operand(self)?));
+        }
+        let operands = operands
+            .into_iter()
+            .map(|(pos, node)| match node {
+                Node::Condition(condition) => Ok(condition),
+                Node::Value(_) => Err(ModelicaError::ExpectedCondition(pos)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Node::Condition(join(operands)))
+    }
+
+    fn disjunction(&mut self) -> Result<Node, ModelicaError> {
+        self.logical("or", Self::conjunction, Formula::Or)
+    }
+
+    fn conjunction(&mut self) -> Result<Node, ModelicaError> {
+        self.logical("and", Self::negation, Formula::And)
+    }
+
+    fn negation(&mut self) -> Result<Node, ModelicaError> {
+        if !self.eat(Token::Keyword("not")) {
+            return self.relation();
+        }
+        let pos = self.pos();
+        match self.relation()? {
+            Node::Condition(condition) => Ok(Node::Condition(condition.not())),
+            Node::Value(_) => Err(ModelicaError::ExpectedCondition(pos)),
+        }
+    }
+
+    fn relation(&mut self) -> Result<Node, ModelicaError> {
+        let pos = self.pos();
+        let lhs = self.sum()?;
+        let relation = match self.eat_symbol(&["<", "<=", ">", ">=", "==", "<>"]) {
+            Some("<") => Relation::Lt,
+            Some("<=") => Relation::Leq,
+            Some(">") => Relation::Gt,
+            Some(">=") => Relation::Geq,
+            Some("==") => Relation::Eq,
+            Some(_) => Relation::Neq,
+            None => return Ok(lhs),
+        };
+        let rhs = self.sum()?;
+        match (lhs, rhs) {
+            (Node::Value(lhs), Node::Value(rhs)) => match (lhs.expr(), rhs.expr()) {
+                (Some(lhs), Some(rhs)) => Ok(Node::Condition(relation.formula(lhs, rhs))),
+                _ => Err(ModelicaError::ConditionalComparison(pos)),
+            },
+            _ => Err(ModelicaError::ExpectedExpression(pos)),
+        }
+    }
+
+    /// Parses operands with `operand`, after `first` if it is given, and joins them with the
+    /// symbols in `ops`.
+    fn arithmetic(
+        &mut self,
+        ops: &[&str],
+        operand: fn(&mut Self) -> Result<Node, ModelicaError>,
+        first: Option<Piecewise>,
+    ) -> Result<Node, ModelicaError> {
+        let pos = self.pos();
+        let mut lhs = match first {
+            Some(first) => Node::Value(first),
+            None => operand(self)?,
+        };
+        while let Some(op) = self.eat_symbol(ops) {
+            let Node::Value(a) = lhs else {
+                return Err(ModelicaError::ExpectedExpression(pos));
+            };
+            let rhs_pos = self.pos();
+            let Node::Value(b) = operand(self)? else {
+                return Err(ModelicaError::ExpectedExpression(rhs_pos));
+            };
+            let combine: fn(&[OpArgument]) -> OpArgument = match op.trim_start_matches('.') {
+                "+" => |x| &x[0] + &x[1],
+                "-" => |x| &x[0] - &x[1],
+                "*" => |x| &x[0] * &x[1],
+                _ => |x| &x[0] / &x[1],
+            };
+            lhs = Node::Value(Piecewise::combine(vec![a, b], combine));
+        }
+        Ok(lhs)
+    }
+
+    fn sum(&mut self) -> Result<Node, ModelicaError> {
+        let ops = ["+", "-", ".+", ".-"];
+        let Some(sign) = self.eat_symbol(&ops) else {
+            return self.arithmetic(&ops, Self::product, None);
+        };
+        // The sign applies to the first term, not the whole sum.
+        let pos = self.pos();
+        let Node::Value(first) = self.product()? else {
+            return Err(ModelicaError::ExpectedExpression(pos));
+        };
+        let first = match sign.ends_with('-') {
+            true => first.map(|x| -x),
+            false => first,
+        };
+        self.arithmetic(&ops, Self::product, Some(first))
+    }
+
+    fn product(&mut self) -> Result<Node, ModelicaError> {
+        self.arithmetic(&["*", "/", ".*", "./"], Self::power, None)
+    }
+
+    fn power(&mut self) -> Result<Node, ModelicaError> {
+        let pos = self.pos();
+        let base = self.primary()?;
+        if self.eat_symbol(&["^", ".^"]).is_none() {
+            return Ok(base);
+        }
+        let exponent_pos = self.pos();
+        match (base, self.primary()?) {
+            (Node::Value(a), Node::Value(b)) => {
+                Ok(Node::Value(Piecewise::combine(vec![a, b], |x| {
+                    x[0].pow(&x[1])
+                })))
+            }
+            (Node::Condition(_), _) => Err(ModelicaError::ExpectedExpression(pos)),
+            _ => Err(ModelicaError::ExpectedExpression(exponent_pos)),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Node, ModelicaError> {
+        let value = |value: Value| Ok(Node::Value(OpArgument::from(value).into()));
+        match self.bump()? {
+            (pos, Token::Number(text)) => match mathml::number(text) {
+                Ok(number) => Ok(Node::Value(number.into())),
+                Err(_) => Err(ModelicaError::NumberTooLarge(pos)),
+            },
+            (_, Token::Keyword("true")) => Ok(Node::Condition(Formula::True)),
+            (_, Token::Keyword("false")) => Ok(Node::Condition(Formula::False)),
+            (_, Token::Symbol("(")) => {
+                let inner = self.expression()?;
+                self.expect(Token::Symbol(")"))?;
+                Ok(inner)
+            }
+            (_, Token::Ident("Modelica.Constants.pi")) => value(Value::Pi),
+            (_, Token::Ident("Modelica.Constants.e")) => value(Value::E),
+            (_, Token::Ident("Modelica.Constants.inf")) => value(Value::Inf),
+            (pos, Token::Ident(name)) if self.eat(Token::Symbol("(")) => {
+                let f = function(name)
+                    .ok_or_else(|| ModelicaError::UnknownFunction(pos, name.to_owned()))?;
+                let arg = self.value()?;
+                self.expect(Token::Symbol(")"))?;
+                Ok(Node::Value(Piecewise::combine(vec![arg], |x| f(&x[0]))))
+            }
+            (_, Token::Ident(name)) => Ok(Node::Value(variable(intern(name)).into())),
+            (pos, _) => Err(ModelicaError::UnexpectedToken(pos)),
+        }
+    }
+}
+
+/// Parses an expression written in Modelica's syntax.
+pub fn parse_modelica(input: &str) -> Result<Piecewise, ModelicaError> {
+    let mut parser = Parser {
+        tokens: Lexer::tokens(input)?,
+        next: 0,
+    };
+    let expr = parser.value()?;
+    match parser.tokens.get(parser.next) {
+        Some((pos, _)) => Err(ModelicaError::UnexpectedToken(*pos)),
+        None => Ok(expr),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_modelica, ModelicaError};
+    use crate::{evaluation::Bindings, parse::parse};
+
+    #[test]
+    fn test_parse_modelica() {
+        let p = |input: &str| parse(input).unwrap();
+        let m = |input: &str| parse_modelica(input).unwrap();
+
+        assert_eq!(m("-a^2 * b .+ 1.5e1").expr(), Some(&p("-(a^2 * b) + 15")));
+        assert_eq!(
+            m("log10(x) / sqrt(Modelica.Constants.pi)").expr(),
+            Some(&p("(ln(x) / ln(10)) / sqrt(pi)"))
+        );
+        let der = m("der(x * y + time) + 'q r'.s");
+        let der = der.expr().unwrap();
+        let bindings = Bindings::from_iter([
+            ("x", 2.0),
+            ("y", 3.0),
+            ("der(x)", 5.0),
+            ("der(y)", 7.0),
+            ("'q r'.s", 100.0),
+        ]);
+        assert_eq!(
+            der.evaluate(&bindings),
+            Ok(5.0 * 3.0 + 2.0 * 7.0 + 1.0 + 100.0)
+        );
+
+        let law = m("k * (if x < 0 or not x <= 10 then 0 elseif x == 5 then 1 else x)");
+        assert_eq!(law.pieces().len(), 3);
+        let at = |x: f64| law.evaluate(&Bindings::from_iter([("k", 2.0), ("x", x)]));
+        assert_eq!(at(-1.0), Ok(Some(0.0)));
+        assert_eq!(at(11.0), Ok(Some(0.0)));
+        assert_eq!(at(5.0), Ok(Some(2.0)));
+        assert_eq!(at(3.0), Ok(Some(6.0)));
+
+        assert_eq!(parse_modelica("x +"), Err(ModelicaError::UnexpectedEnd));
+        assert_eq!(
+            parse_modelica("x and y"),
+            Err(ModelicaError::ExpectedCondition(0))
+        );
+        assert_eq!(
+            parse_modelica("1 + (x < 2)"),
+            Err(ModelicaError::ExpectedExpression(4))
+        );
+        assert_eq!(
+            parse_modelica("abs(x)"),
+            Err(ModelicaError::UnknownFunction(0, "abs".to_owned()))
+        );
+    }
+}
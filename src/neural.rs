@@ -0,0 +1,231 @@
+//! This module describes the activations and losses of small neural networks as expressions.
+//!
+//! Every activation is built from the existing operations, so networks assembled from them can
+//! be differentiated, folded and compiled like any other expression. Each [`Activation`] also
+//! knows its derivative in closed form, written in terms of the activation itself where that
+//! is the usual shorter form. The activations are written with `sign(x)` and `|x| = x·sign(x)`
+//! so that no exponential of a large argument can overflow, and [`backpropagate`] compiles a
+//! loss together with its gradient by reverse-mode differentiation.
+
+use crate::{
+    codegen::CodegenError,
+    compile::{compile, Program},
+    constants::Value,
+    symbols::OpArgument,
+};
+
+fn integer(n: u64) -> OpArgument {
+    Value::integer(n).into()
+}
+
+/// An elementwise nonlinearity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Activation {
+    /// `1 / (1 + e^(-x))`
+    Sigmoid,
+    /// `ln(1 + eˣ)`, a smooth rectifier, written as `max(0, x) + ln(1 + e^(-|x|))`.
+    Softplus,
+    /// `max(0, x)`, written as `x·(1 + sign(x)) / 2`. Its derivative at zero is taken as `1/2`.
+    Relu,
+    /// `(eˣ - e^(-x)) / (eˣ + e^(-x))`, written as `sign(x)·(1 - e^(-2|x|)) / (1 + e^(-2|x|))`.
+    Tanh,
+}
+
+impl Activation {
+    /// The activation applied to `x`.
+    pub fn apply(&self, x: &OpArgument) -> OpArgument {
+        let magnitude = || x * x.sign();
+        match self {
+            Activation::Sigmoid => integer(1) / (integer(1) + (-x).exp()),
+            Activation::Softplus => {
+                Activation::Relu.apply(x) + (integer(1) + (-magnitude()).exp()).ln()
+            }
+            Activation::Relu => x * (integer(1) + x.sign()) / integer(2),
+            Activation::Tanh => {
+                let e = (-(integer(2) * magnitude())).exp();
+                x.sign() * (integer(1) - &e) / (integer(1) + e)
+            }
+        }
+    }
+
+    /// The derivative of the activation at `x`.
+    pub fn derivative(&self, x: &OpArgument) -> OpArgument {
+        match self {
+            Activation::Sigmoid => {
+                let s = self.apply(x);
+                &s * (integer(1) - &s)
+            }
+            Activation::Softplus => Activation::Sigmoid.apply(x),
+            Activation::Relu => (integer(1) + x.sign()) / integer(2),
+            Activation::Tanh => integer(1) - self.apply(x).pow(&integer(2)),
+        }
+    }
+}
+
+/// A program computing `loss` followed by its partial derivatives with respect to every one of
+/// `params` in order, by reverse-mode differentiation of the compiled loss. Inputs and targets
+/// are parameters too, so their partial derivatives come along with those of the weights.
+pub fn backpropagate(loss: &OpArgument, params: &[&'static str]) -> Result<Program, CodegenError> {
+    Ok(compile(loss, params)?.with_gradient())
+}
+
+/// The mean squared error `Σ (pₖ - yₖ)² / n` of `predictions` against `targets`.
+pub fn mean_squared_error(predictions: &[OpArgument], targets: &[OpArgument]) -> OpArgument {
+    assert!(
+        predictions.len() == targets.len() && !predictions.is_empty(),
+        "Hold on, {} predictions cannot be compared with {} targets",
+        predictions.len(),
+        targets.len()
+    );
+    let sum = predictions
+        .iter()
+        .zip(targets)
+        .map(|(p, y)| (p - y).pow(&integer(2)))
+        .reduce(|acc, term| acc + term)
+        .expect("Oops, there is at least one prediction");
+    sum / integer(predictions.len() as u64)
+}
+
+/// The cross-entropy `-Σ yₖ·ln(pₖ)` of the predicted `probabilities` against the `targets`
+/// distribution.
+pub fn cross_entropy(probabilities: &[OpArgument], targets: &[OpArgument]) -> OpArgument {
+    assert!(
+        probabilities.len() == targets.len() && !probabilities.is_empty(),
+        "Hold on, {} probabilities cannot be compared with {} targets",
+        probabilities.len(),
+        targets.len()
+    );
+    -probabilities
+        .iter()
+        .zip(targets)
+        .map(|(p, y)| y * p.ln())
+        .reduce(|acc, term| acc + term)
+        .expect("Oops, there is at least one probability")
+}
+
+/// The cross-entropy of one predicted probability `p` of a binary label `y`,
+/// `-(y·ln(p) + (1 - y)·ln(1 - p))`.
+pub fn binary_cross_entropy(p: &OpArgument, y: &OpArgument) -> OpArgument {
+    cross_entropy(&[p.clone(), integer(1) - p], &[y.clone(), integer(1) - y])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        backpropagate, binary_cross_entropy, cross_entropy, mean_squared_error, Activation,
+    };
+    use crate::{compile::compile, evaluation::Bindings, parse::parse, symbols::variable};
+
+    #[test]
+    fn test_neural() {
+        let p = |input: &str| parse(input).unwrap();
+        let x = variable("x");
+        let at = |x: f64| Bindings::from_iter([("x", x)]);
+        let activations = [
+            Activation::Sigmoid,
+            Activation::Softplus,
+            Activation::Relu,
+            Activation::Tanh,
+        ];
+        for activation in activations {
+            let symbolic = activation.apply(&x).derivative("x");
+            let closed = activation.derivative(&x);
+            for value in [-1.5, 0.2, 2.0] {
+                let (a, b) = (
+                    symbolic.evaluate(&at(value)).unwrap(),
+                    closed.evaluate(&at(value)).unwrap(),
+                );
+                assert!(
+                    (a - b).abs() < 1e-12,
+                    "{:?} differs at {}",
+                    activation,
+                    value
+                );
+            }
+        }
+        let tanh = Activation::Tanh.apply(&x).evaluate(&at(0.5)).unwrap();
+        assert!((tanh - 0.5f64.tanh()).abs() < 1e-15);
+        assert_eq!(Activation::Relu.apply(&x).evaluate(&at(-3.0)), Ok(0.0));
+
+        // One sigmoid neuron trained against a squared error, differentiated and compiled.
+        let neuron = Activation::Sigmoid.apply(&p("w * x + b"));
+        let loss = mean_squared_error(&[neuron], &[variable("y")]);
+        let gradient = compile(&loss.derivative("w"), &["w", "b", "x", "y"]).unwrap();
+        let (w, b, x0, y) = (0.5, -0.25, 2.0, 1.0);
+        let s = 1.0 / (1.0 + f64::exp(-(w * x0 + b)));
+        let expected = 2.0 * (s - y) * s * (1.0 - s) * x0;
+        assert!((gradient.evaluate(&[w, b, x0, y])[0] - expected).abs() < 1e-12);
+
+        let none = Bindings::default();
+        let entropy = cross_entropy(&[p("1/4"), p("3/4")], &[p("0"), p("1")]);
+        assert!((entropy.evaluate(&none).unwrap() - (4.0f64 / 3.0).ln()).abs() < 1e-15);
+        let binary = binary_cross_entropy(&p("3/4"), &p("1"));
+        assert_eq!(binary.evaluate(&none), entropy.evaluate(&none));
+    }
+
+    #[test]
+    fn test_activation_stability() {
+        let x = variable("x");
+        let at = |x: f64| Bindings::from_iter([("x", x)]);
+        let sigmoid = |x: f64| 1.0 / (1.0 + (-x).exp());
+        let softplus = |x: f64| x.max(0.0) + (-x.abs()).exp().ln_1p();
+        let references = [
+            (Activation::Sigmoid, sigmoid as fn(f64) -> f64),
+            (Activation::Softplus, softplus),
+            (Activation::Relu, |x| x.max(0.0)),
+            (Activation::Tanh, f64::tanh),
+        ];
+        for (activation, reference) in references {
+            let (value, derivative) = (activation.apply(&x), activation.derivative(&x));
+            for point in [
+                -1e300, -2e154, -800.0, -3.0, 0.0, 1e-300, 0.5, 400.0, 2e154, 1e300,
+            ] {
+                let (a, b) = (value.evaluate(&at(point)).unwrap(), reference(point));
+                assert!(
+                    (a - b).abs() <= 1e-14 * b.abs().max(1.0),
+                    "{:?}({}) = {}, not {}",
+                    activation,
+                    point,
+                    a,
+                    b
+                );
+                assert!(derivative.evaluate(&at(point)).unwrap().is_finite());
+            }
+        }
+        let relu = Activation::Relu.derivative(&x);
+        assert_eq!(relu.evaluate(&at(0.0)), Ok(0.5));
+    }
+
+    #[test]
+    fn test_backpropagation() {
+        let p = |input: &str| parse(input).unwrap();
+        // A tanh hidden unit feeding a sigmoid output, scored by binary cross-entropy.
+        let hidden = Activation::Tanh.apply(&p("u * x + c"));
+        let output = Activation::Sigmoid.apply(&(&p("w") * &hidden + p("b")));
+        let loss = binary_cross_entropy(&output, &variable("y"));
+        let params = ["u", "c", "w", "b", "x", "y"];
+        let program = backpropagate(&loss, &params).unwrap();
+
+        let values = [0.7, -0.2, 1.3, 0.1, 0.9, 1.0];
+        let bindings = Bindings::from_iter(params.into_iter().zip(values));
+        let result = program.evaluate(&values);
+        assert_eq!(result.len(), 1 + params.len());
+        assert!((result[0] - loss.evaluate(&bindings).unwrap()).abs() < 1e-15);
+        for (param, partial) in params.iter().zip(&result[1..]) {
+            let expected = loss.derivative(param).evaluate(&bindings).unwrap();
+            assert!(
+                (partial - expected).abs() < 1e-12,
+                "∂/∂{} = {}, not {}",
+                param,
+                partial,
+                expected
+            );
+        }
+
+        // A saturated hidden unit passes back a zero gradient rather than NaN.
+        let saturated = [1e3, 0.0, 1.3, 0.1, 1e3, 1.0];
+        let result = program.evaluate(&saturated);
+        assert!(result.iter().all(|r| r.is_finite()), "{:?}", result);
+        assert_eq!(result[1], 0.0);
+    }
+}
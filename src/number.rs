@@ -0,0 +1,237 @@
+//! This module describes exact number theory on integer constants.
+//!
+//! The functions on `u64` work on machine integers, and the methods on [`OpArgument`] fold
+//! their arguments first and apply them to the integer constants they fold to, so an
+//! expression like `2^10 - 1` can be factored or inverted modulo a prime directly.
+
+use crate::{constants::Value, fold::Ratio, symbols::OpArgument};
+
+/// Witnesses that decide primality by Miller–Rabin for every `u64`.
+const WITNESSES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    (a as u128 * b as u128 % m as u128) as u64
+}
+
+fn pow_mod(mut base: u64, mut exponent: u64, m: u64) -> u64 {
+    let mut result = 1 % m;
+    base %= m;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Whether `n` is prime, by Miller–Rabin with witnesses that make it deterministic.
+pub fn is_prime(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    if let Some(&p) = WITNESSES.iter().find(|&&p| n.is_multiple_of(p)) {
+        return n == p;
+    }
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    WITNESSES.iter().all(|&a| {
+        let mut x = pow_mod(a, d, n);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        (1..s).any(|_| {
+            x = mul_mod(x, x, n);
+            x == n - 1
+        })
+    })
+}
+
+/// Finds a nontrivial factor of the odd composite `n` with Pollard's rho.
+fn pollard_rho(n: u64) -> u64 {
+    for c in 1u64.. {
+        let f = |x: u64| ((x as u128 * x as u128 + c as u128) % n as u128) as u64;
+        let (mut x, mut y, mut d) = (2, 2, 1);
+        while d == 1 {
+            x = f(x);
+            y = f(f(y));
+            d = gcd(x.abs_diff(y), n);
+        }
+        if d != n {
+            return d;
+        }
+    }
+    unreachable!("Whoa there, Pollard's rho ran out of constants")
+}
+
+fn collect_factors(n: u64, primes: &mut Vec<u64>) {
+    if n == 1 {
+        return;
+    }
+    if is_prime(n) {
+        primes.push(n);
+        return;
+    }
+    let d = pollard_rho(n);
+    collect_factors(d, primes);
+    collect_factors(n / d, primes);
+}
+
+/// The prime factorization of `n`, as primes in increasing order with their exponents. It is
+/// empty for `0` and `1`.
+pub fn factorize(mut n: u64) -> Vec<(u64, u32)> {
+    if n == 0 {
+        return Vec::new();
+    }
+    let mut primes = Vec::new();
+    for p in [2, 3, 5] {
+        while n.is_multiple_of(p) {
+            primes.push(p);
+            n /= p;
+        }
+    }
+    collect_factors(n, &mut primes);
+    primes.sort_unstable();
+
+    let mut factors: Vec<(u64, u32)> = Vec::new();
+    for p in primes {
+        match factors.last_mut() {
+            Some((q, exponent)) if *q == p => *exponent += 1,
+            _ => factors.push((p, 1)),
+        }
+    }
+    factors
+}
+
+/// Euler's totient of `n`, the number of integers in `1..=n` coprime to it.
+pub fn totient(n: u64) -> u64 {
+    factorize(n)
+        .into_iter()
+        .fold(n, |phi, (p, _)| phi / p * (p - 1))
+}
+
+/// The inverse of `a` modulo `m`, if `a` and `m` are coprime.
+pub fn mod_inverse(a: u64, m: u64) -> Option<u64> {
+    if m == 0 {
+        return None;
+    }
+    // The extended Euclidean algorithm, tracking only the coefficients of `a`.
+    let (mut r, mut next_r) = (m as i128, (a % m) as i128);
+    let (mut t, mut next_t) = (0i128, 1i128);
+    while next_r != 0 {
+        let q = r / next_r;
+        (r, next_r) = (next_r, r - q * next_r);
+        (t, next_t) = (next_t, t - q * next_t);
+    }
+    (r == 1).then(|| t.rem_euclid(m as i128) as u64)
+}
+
+/// Reads the integer constant `node` folds to.
+fn integer(node: &OpArgument) -> Option<i128> {
+    Ratio::of(&node.fold())
+        .filter(|r| r.den == 1)
+        .map(|r| r.num)
+}
+
+fn natural(node: &OpArgument) -> Option<u64> {
+    integer(node).and_then(|n| u64::try_from(n).ok())
+}
+
+impl OpArgument {
+    /// Whether the expression folds to a prime, or `None` if it does not fold to an integer.
+    pub fn is_prime(&self) -> Option<bool> {
+        Some(u64::try_from(integer(self)?).is_ok_and(is_prime))
+    }
+
+    /// The prime factorization of the integer the expression folds to, as a product of prime
+    /// powers. Zero has none.
+    pub fn factorize(&self) -> Option<OpArgument> {
+        let n = integer(self).filter(|&n| n != 0)?;
+        let magnitude = u64::try_from(n.unsigned_abs()).ok()?;
+        let product = factorize(magnitude)
+            .into_iter()
+            .map(|(p, exponent)| {
+                let p = OpArgument::from(Value::integer(p));
+                match exponent {
+                    1 => p,
+                    e => p.pow(&Value::integer(e as u64).into()),
+                }
+            })
+            .reduce(|acc, factor| acc * factor)
+            .unwrap_or_else(|| Value::integer(1).into());
+        Some(if n < 0 { -product } else { product })
+    }
+
+    /// Euler's totient of the positive integer the expression folds to.
+    pub fn totient(&self) -> Option<OpArgument> {
+        let n = natural(self).filter(|&n| n > 0)?;
+        Some(Value::integer(totient(n)).into())
+    }
+
+    /// The residue of the rational constant the expression folds to modulo the positive
+    /// integer `modulus`, so `1/a` folds to the inverse of `a`. It is `None` if the
+    /// denominator is not invertible.
+    pub fn mod_inverse(&self, modulus: &OpArgument) -> Option<OpArgument> {
+        let m = natural(modulus).filter(|&m| m > 0)?;
+        let r = Ratio::of(&self.fold())?;
+        let residue = |n: i128| n.rem_euclid(m as i128) as u64;
+        let den = mod_inverse(residue(r.den), m)?;
+        Some(Value::integer(mul_mod(residue(r.num), den, m)).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{factorize, is_prime, mod_inverse, totient};
+    use crate::{constants::Value, parse::parse, symbols::OpArgument};
+
+    #[test]
+    fn test_number_theory() {
+        let primes = (0..50).filter(|&n| is_prime(n)).collect::<Vec<_>>();
+        assert_eq!(
+            primes,
+            [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47]
+        );
+        assert!(is_prime(18_446_744_073_709_551_557));
+        // A Carmichael number, and a strong pseudoprime to base 2.
+        assert!(!is_prime(561) && !is_prime(2047));
+
+        assert_eq!(factorize(360), [(2, 3), (3, 2), (5, 1)]);
+        assert_eq!(
+            factorize(600_851_475_143),
+            [(71, 1), (839, 1), (1471, 1), (6857, 1)]
+        );
+        assert_eq!(factorize(4_294_967_297), [(641, 1), (6_700_417, 1)]);
+        assert!(factorize(1).is_empty());
+
+        assert_eq!(totient(1), 1);
+        assert_eq!(totient(36), 12);
+        assert_eq!(mod_inverse(3, 11), Some(4));
+        assert_eq!(mod_inverse(6, 9), None);
+
+        // The methods fold their arguments first.
+        let p = |input: &str| parse(input).unwrap();
+        let n = |n: u64| OpArgument::from(Value::integer(n));
+        assert_eq!(p("2^5 - 1").is_prime(), Some(true));
+        assert_eq!(p("-7").is_prime(), Some(false));
+        assert_eq!(p("x").is_prime(), None);
+        assert_eq!(
+            p("2^10 - 4").factorize(),
+            Some(n(2).pow(&n(2)) * n(3) * n(5) * n(17))
+        );
+        assert_eq!(p("-6").factorize(), Some(-(n(2) * n(3))));
+        assert_eq!(p("0").factorize(), None);
+        assert_eq!(p("2 * 5 * 7").totient(), Some(n(24)));
+        assert_eq!(p("1/3").mod_inverse(&n(11)), Some(n(4)));
+        assert_eq!(p("-2/3").mod_inverse(&n(11)), Some(n(3)));
+        assert_eq!(p("1/11").mod_inverse(&n(11)), None);
+    }
+}
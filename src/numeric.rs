@@ -0,0 +1,233 @@
+//! This module describes numeric integration by Monte Carlo sampling.
+//!
+//! The integral of `f` over a box is its volume times the mean of `f` at points drawn
+//! uniformly from the box, and the standard error of that mean shrinks like `1/√n` whatever the
+//! dimension, which is what makes sampling the method of choice where quadrature grids grow
+//! exponentially. The expression is compiled once, and the samples can be split across threads,
+//! each drawing from its own reproducible stream.
+
+use std::fmt::Display;
+
+use crate::{
+    codegen::CodegenError, compile::compile, interval::Interval, random::Rng, symbols::OpArgument,
+};
+
+/// The reasons Monte Carlo integration can fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntegrationError {
+    Codegen(CodegenError),
+    /// Fewer than the two samples a standard error needs were asked for.
+    TooFewSamples(usize),
+    /// The samples were to be split between no threads.
+    NoThreads,
+}
+
+impl Display for IntegrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntegrationError::Codegen(err) => err.fmt(f),
+            IntegrationError::TooFewSamples(samples) => write!(
+                f,
+                "Monte Carlo integration needs at least two samples, not {}",
+                samples
+            ),
+            IntegrationError::NoThreads => write!(f, "Monte Carlo integration needs a thread"),
+        }
+    }
+}
+
+impl std::error::Error for IntegrationError {}
+
+impl From<CodegenError> for IntegrationError {
+    fn from(err: CodegenError) -> Self {
+        IntegrationError::Codegen(err)
+    }
+}
+
+/// An estimate of an integral with the standard error of the estimate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Estimate {
+    pub value: f64,
+    pub standard_error: f64,
+}
+
+/// How many samples to draw for [`integrate_mc`], and how.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SamplingOptions {
+    pub samples: usize,
+    pub seed: u64,
+    /// The samples are split evenly between this many threads.
+    pub threads: usize,
+}
+
+impl Default for SamplingOptions {
+    fn default() -> Self {
+        SamplingOptions {
+            samples: 100_000,
+            seed: 0,
+            threads: 1,
+        }
+    }
+}
+
+/// The running count, mean and sum of squared deviations of a stream of values, by Welford's
+/// method.
+#[derive(Clone, Copy, Default)]
+struct Moments {
+    count: f64,
+    mean: f64,
+    squares: f64,
+}
+
+impl Moments {
+    fn push(&mut self, x: f64) {
+        self.count += 1.0;
+        let delta = x - self.mean;
+        self.mean += delta / self.count;
+        self.squares += delta * (x - self.mean);
+    }
+
+    /// Combines the moments of two disjoint streams, by Chan's method.
+    fn merge(self, other: Moments) -> Moments {
+        let count = self.count + other.count;
+        if count == 0.0 {
+            return self;
+        }
+        let delta = other.mean - self.mean;
+        Moments {
+            count,
+            mean: self.mean + delta * other.count / count,
+            squares: self.squares
+                + other.squares
+                + delta * delta * self.count * other.count / count,
+        }
+    }
+}
+
+/// Estimates the integral of `expr` over the box where each variable ranges over its
+/// interval in `ranges`.
+pub fn integrate_mc(
+    expr: &OpArgument,
+    ranges: &[(&'static str, Interval)],
+    options: SamplingOptions,
+) -> Result<Estimate, IntegrationError> {
+    if options.samples < 2 {
+        return Err(IntegrationError::TooFewSamples(options.samples));
+    }
+    if options.threads == 0 {
+        return Err(IntegrationError::NoThreads);
+    }
+    let names = ranges.iter().map(|&(name, _)| name).collect::<Vec<_>>();
+    let program = compile(expr, &names)?;
+    let volume = ranges
+        .iter()
+        .map(|(_, range)| range.hi - range.lo)
+        .product::<f64>();
+
+    let sample = |thread: usize, count: usize| {
+        // Each thread draws from a stream seeded from the shared seed.
+        let mut rng = Rng::new(Rng::new(options.seed ^ thread as u64).next_u64());
+        let (mut inputs, mut registers, mut out) = (vec![0.0; names.len()], Vec::new(), [0.0]);
+        let mut moments = Moments::default();
+        for _ in 0..count {
+            for (input, (_, range)) in inputs.iter_mut().zip(ranges) {
+                *input = range.lo + (range.hi - range.lo) * rng.uniform();
+            }
+            program.evaluate_into(&inputs, &mut registers, &mut out);
+            moments.push(out[0]);
+        }
+        moments
+    };
+    let counts = (0..options.threads).map(|thread| {
+        options.samples / options.threads + usize::from(thread < options.samples % options.threads)
+    });
+    let moments = match options.threads {
+        1 => sample(0, options.samples),
+        _ => std::thread::scope(|scope| {
+            let sample = &sample;
+            counts
+                .enumerate()
+                .map(|(thread, count)| scope.spawn(move || sample(thread, count)))
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|handle| handle.join().expect("Uh-oh, a sampling thread panicked"))
+                .fold(Moments::default(), Moments::merge)
+        }),
+    };
+
+    let variance = moments.squares / (moments.count - 1.0);
+    Ok(Estimate {
+        value: volume * moments.mean,
+        standard_error: volume * (variance / moments.count).sqrt(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{integrate_mc, IntegrationError, SamplingOptions};
+    use crate::{codegen::CodegenError, interval::Interval, parse::parse};
+
+    #[test]
+    fn test_integrate_mc() {
+        let p = |input: &str| parse(input).unwrap();
+        let unit = |name| (name, Interval::new(0.0, 1.0));
+
+        // The volume of the unit ball in six dimensions, π³/6, from its indicator
+        // (1 + sign(1 - r²))/2 written as (1 + u/|u|)/2.
+        let names = ["a", "b", "c", "d", "g", "h"];
+        let r2 = names
+            .iter()
+            .map(|name| format!("{}^2", name))
+            .collect::<Vec<_>>()
+            .join(" + ");
+        let u = format!("(1 - ({}))", r2);
+        let indicator = p(&format!("(1 + {} / ({}^2)^(1/2)) / 2", u, u));
+        let ranges = names
+            .iter()
+            .map(|&name| (name, Interval::new(-1.0, 1.0)))
+            .collect::<Vec<_>>();
+        let options = SamplingOptions {
+            samples: 400_000,
+            seed: 3,
+            threads: 4,
+        };
+        let ball = integrate_mc(&indicator, &ranges, options).unwrap();
+        let exact = std::f64::consts::PI.powi(3) / 6.0;
+        assert!((ball.value - exact).abs() < 4.0 * ball.standard_error);
+        assert!(ball.standard_error < 0.03);
+
+        // A smooth integrand, ∫∫ xy = 1/4, and reproducibility for a fixed seed.
+        let single = SamplingOptions::default();
+        let product = integrate_mc(&p("x * y"), &[unit("x"), unit("y")], single).unwrap();
+        assert!((product.value - 0.25).abs() < 4.0 * product.standard_error);
+        assert_eq!(
+            integrate_mc(&p("x * y"), &[unit("x"), unit("y")], single),
+            Ok(product)
+        );
+        let constant = integrate_mc(&p("3"), &[("x", Interval::new(1.0, 3.0))], single).unwrap();
+        assert_eq!((constant.value, constant.standard_error), (6.0, 0.0));
+
+        assert_eq!(
+            integrate_mc(&p("x * y"), &[unit("x")], single),
+            Err(IntegrationError::Codegen(CodegenError::UnknownVariable(
+                "y"
+            )))
+        );
+        let starved = SamplingOptions {
+            samples: 1,
+            ..single
+        };
+        assert_eq!(
+            integrate_mc(&p("x"), &[unit("x")], starved),
+            Err(IntegrationError::TooFewSamples(1))
+        );
+        let idle = SamplingOptions {
+            threads: 0,
+            ..single
+        };
+        assert_eq!(
+            integrate_mc(&p("x"), &[unit("x")], idle),
+            Err(IntegrationError::NoThreads)
+        );
+    }
+}
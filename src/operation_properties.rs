@@ -1,8 +1,8 @@
 use std::cmp::Ordering;
 
-use crate::symbols::{
-    OperationKind::{self, *},
-    StackVec,
+use crate::{
+    evaluation::Scalar,
+    symbols::OperationKind::{self, *},
 };
 
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -15,10 +15,10 @@ pub enum Associativity {
 impl OperationKind {
     #[inline]
     pub fn is_infix(self) -> bool {
-        match self {
-            Addition | Subtraction | Multiplication | Division | Pow => true,
-            _ => false,
-        }
+        matches!(
+            self,
+            Addition | Subtraction | Multiplication | Division | Pow
+        )
     }
 
     #[inline]
@@ -26,16 +26,13 @@ impl OperationKind {
         match self {
             Negation => 1,
             Addition | Subtraction | Multiplication | Division | Pow => 2,
-            Exp | Sin | Cos | Tan | Ln => 1,
+            Exp | Sin | Cos | Tan | Ln | Atan | Sign => 1,
         }
     }
 
     #[inline]
     pub fn is_prefix(self) -> bool {
-        match self {
-            Negation => true,
-            _ => false,
-        }
+        matches!(self, Negation)
     }
 
     #[inline]
@@ -43,7 +40,7 @@ impl OperationKind {
         match self {
             Pow => Associativity::Right,
             Addition | Subtraction | Multiplication | Division => Associativity::Left,
-            Negation | Exp | Sin | Cos | Tan | Ln => Associativity::Neither,
+            Negation | Exp | Sin | Cos | Tan | Ln | Atan | Sign => Associativity::Neither,
         }
     }
 
@@ -61,6 +58,8 @@ impl OperationKind {
             Cos => |a| a[0].cos(),
             Tan => |a| a[0].tan(),
             Ln => |a| a[0].ln(),
+            Atan => |a| a[0].atan(),
+            Sign => |a| Scalar::sign(a[0]),
         }
     }
 
@@ -69,6 +68,26 @@ impl OperationKind {
         assert_eq!(self.argcount(), a.len(), "Uh-oh, I think you called OperationKind::Eval on {} with arguments: {:?}, but we only needed {} arguments and you gave {}", self, a, self.argcount(), a.len());
         self.eval_fn()(a)
     }
+
+    /// Like [`OperationKind::eval`], but in any [`Scalar`] type.
+    #[inline]
+    pub fn apply<T: Scalar>(self, a: &[T]) -> T {
+        match self {
+            Addition => a[0] + a[1],
+            Subtraction => a[0] - a[1],
+            Multiplication => a[0] * a[1],
+            Division => a[0] / a[1],
+            Negation => -a[0],
+            Pow => a[0].powf(a[1]),
+            Exp => a[0].exp(),
+            Sin => a[0].sin(),
+            Cos => a[0].cos(),
+            Tan => a[0].tan(),
+            Ln => a[0].ln(),
+            Atan => a[0].atan(),
+            Sign => a[0].sign(),
+        }
+    }
 }
 
 impl Ord for OperationKind {
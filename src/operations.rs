@@ -1,290 +1,286 @@
 //! This module describes how to perform mathematical operations with our computational graph.
+//!
+//! The operators and methods build nodes exactly as written, unless they are called within
+//! [`crate::context::Context::enter`] on a context whose [`Simplification`] is not `Off`. Then
+//! each node is simplified before it is built, so that `x + 0` is simply `x` and never
+//! allocated.
 
 use std::{
+    cell::Cell,
     ops::{Add, Div, Mul, Neg, Sub},
-    sync::Arc,
 };
 
-use once_cell::sync::OnceCell;
 use smallvec::smallvec;
 
-use crate::symbols::{
-    OpArgument,
-    OpArgumentKind::{Leaf, Op},
-    Operation,
-    OperationKind::*,
+use crate::{
+    constants::Value,
+    context::Simplification,
+    fold::{fold_root, Ratio},
+    symbols::{
+        OpArgument,
+        OpArgumentKind::Op,
+        Operation,
+        OperationKind::{self, *},
+        StackVec,
+    },
 };
 
-fn construct_oparg(op_argument: &OpArgument) -> OpArgument {
-    OpArgument {
-        hash: OnceCell::new(),
-        value: match &op_argument.value {
-            Op(op) => Op(Arc::clone(op)),
-            Leaf(val) => Leaf(Arc::clone(val)),
-        },
+thread_local! {
+    static POLICY: Cell<Simplification> = const { Cell::new(Simplification::Off) };
+}
+
+/// Runs `f` with the operators on this thread simplifying as `policy` says, and then goes back
+/// to the previous policy, even if `f` panics.
+pub(crate) fn with_simplification<R>(policy: Simplification, f: impl FnOnce() -> R) -> R {
+    struct Restore(Simplification);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            POLICY.set(self.0);
+        }
     }
+
+    let _restore = Restore(POLICY.replace(policy));
+    f()
+}
+
+/// The node `op(arguments)`, simplified as the current policy says.
+pub(crate) fn build(op: OperationKind, arguments: StackVec<OpArgument>) -> OpArgument {
+    match POLICY.get() {
+        Simplification::Off => Op(Operation::new(op, arguments).into()).into(),
+        // The nodes built while simplifying are built as they are, or this would never end.
+        policy => with_simplification(Simplification::Off, || simplify(policy, op, arguments)),
+    }
+}
+
+fn simplify(
+    policy: Simplification,
+    op: OperationKind,
+    arguments: StackVec<OpArgument>,
+) -> OpArgument {
+    let aggressive = policy == Simplification::Aggressive;
+    let constant = |i: usize| Ratio::of(&arguments[i]);
+    let (zero, one) = (Some(Ratio::ZERO), Some(Ratio::ONE));
+    let integer = |n: u64| OpArgument::from(Value::integer(n));
+
+    // The argument that is the whole result, when the other is an identity element.
+    let kept = match op {
+        Addition if constant(0) == zero => Some(1),
+        Addition | Subtraction if constant(1) == zero => Some(0),
+        Multiplication if constant(0) == one => Some(1),
+        Multiplication | Division | Pow if constant(1) == one => Some(0),
+        _ => None,
+    };
+    if let Some(i) = kept {
+        return arguments[i].clone();
+    }
+
+    if aggressive {
+        // These hold wherever the expression written is defined. Constants such as `0·∞`, `0/0`
+        // and `∞ - ∞` are left to folding, which knows when they are not.
+        let symbolic = |i: usize| !arguments[i].is_constant();
+        match op {
+            Multiplication
+                if (constant(0) == zero && symbolic(1)) || (constant(1) == zero && symbolic(0)) =>
+            {
+                return integer(0)
+            }
+            Division if constant(0) == zero && symbolic(1) => return integer(0),
+            Pow if constant(1) == zero && symbolic(0) => return integer(1),
+            Subtraction if arguments[0] == arguments[1] && symbolic(0) => return integer(0),
+            Division if arguments[0] == arguments[1] && symbolic(0) => return integer(1),
+            Negation => {
+                if let Op(inner) = &arguments[0].value {
+                    if inner.op == Negation {
+                        return inner.arguments[0].clone();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let constants = arguments.iter().all(|arg| arg.free_variables().is_empty());
+    let node = Op(Operation::new(op, arguments).into()).into();
+    match aggressive || constants {
+        true => fold_root(&node).unwrap_or(node),
+        false => node,
+    }
+}
+
+fn construct_oparg(op_argument: &OpArgument) -> OpArgument {
+    op_argument.clone()
 }
 
 impl Add<OpArgument> for OpArgument {
     type Output = OpArgument;
     fn add(self, rhs: OpArgument) -> Self::Output {
-        Op(Operation {
-            op: Addition,
-            arguments: smallvec![self, rhs],
-        }
-        .into())
-        .into()
+        build(Addition, smallvec![self, rhs])
     }
 }
 
 impl Mul<OpArgument> for OpArgument {
     type Output = OpArgument;
     fn mul(self, rhs: OpArgument) -> Self::Output {
-        Op(Operation {
-            op: Multiplication,
-            arguments: smallvec![self, rhs],
-        }
-        .into())
-        .into()
+        build(Multiplication, smallvec![self, rhs])
     }
 }
 
 impl Sub<OpArgument> for OpArgument {
     type Output = OpArgument;
     fn sub(self, rhs: OpArgument) -> Self::Output {
-        Op(Operation {
-            op: Subtraction,
-            arguments: smallvec![self, rhs],
-        }
-        .into())
-        .into()
+        build(Subtraction, smallvec![self, rhs])
     }
 }
 
 impl Div<OpArgument> for OpArgument {
     type Output = OpArgument;
     fn div(self, rhs: OpArgument) -> Self::Output {
-        Op(Operation {
-            op: Division,
-            arguments: smallvec![self, rhs],
-        }
-        .into())
-        .into()
+        build(Division, smallvec![self, rhs])
     }
 }
 
 impl Neg for OpArgument {
     type Output = OpArgument;
     fn neg(self) -> Self::Output {
-        Op(Operation {
-            op: Negation,
-            arguments: smallvec![self],
-        }
-        .into())
-        .into()
+        build(Negation, smallvec![self])
     }
 }
 
 impl Add<OpArgument> for &OpArgument {
     type Output = OpArgument;
     fn add(self, rhs: OpArgument) -> Self::Output {
-        Op(Operation {
-            op: Addition,
-            arguments: smallvec![construct_oparg(self), rhs],
-        }
-        .into())
-        .into()
+        build(Addition, smallvec![construct_oparg(self), rhs])
     }
 }
 
 impl Mul<OpArgument> for &OpArgument {
     type Output = OpArgument;
     fn mul(self, rhs: OpArgument) -> Self::Output {
-        Op(Operation {
-            op: Multiplication,
-            arguments: smallvec![construct_oparg(self), rhs],
-        }
-        .into())
-        .into()
+        build(Multiplication, smallvec![construct_oparg(self), rhs])
     }
 }
 
 impl Sub<OpArgument> for &OpArgument {
     type Output = OpArgument;
     fn sub(self, rhs: OpArgument) -> Self::Output {
-        Op(Operation {
-            op: Subtraction,
-            arguments: smallvec![construct_oparg(self), rhs],
-        }
-        .into())
-        .into()
+        build(Subtraction, smallvec![construct_oparg(self), rhs])
     }
 }
 
 impl Div<OpArgument> for &OpArgument {
     type Output = OpArgument;
     fn div(self, rhs: OpArgument) -> Self::Output {
-        Op(Operation {
-            op: Division,
-            arguments: smallvec![construct_oparg(self), rhs],
-        }
-        .into())
-        .into()
+        build(Division, smallvec![construct_oparg(self), rhs])
     }
 }
 
 impl Add<&OpArgument> for OpArgument {
     type Output = OpArgument;
     fn add(self, rhs: &OpArgument) -> Self::Output {
-        Op(Operation {
-            op: Addition,
-            arguments: smallvec![self, construct_oparg(rhs)],
-        }
-        .into())
-        .into()
+        build(Addition, smallvec![self, construct_oparg(rhs)])
     }
 }
 
 impl Mul<&OpArgument> for OpArgument {
     type Output = OpArgument;
     fn mul(self, rhs: &OpArgument) -> Self::Output {
-        Op(Operation {
-            op: Multiplication,
-            arguments: smallvec![self, construct_oparg(rhs)],
-        }
-        .into())
-        .into()
+        build(Multiplication, smallvec![self, construct_oparg(rhs)])
     }
 }
 
 impl Sub<&OpArgument> for OpArgument {
     type Output = OpArgument;
     fn sub(self, rhs: &OpArgument) -> Self::Output {
-        Op(Operation {
-            op: Subtraction,
-            arguments: smallvec![self, construct_oparg(rhs)],
-        }
-        .into())
-        .into()
+        build(Subtraction, smallvec![self, construct_oparg(rhs)])
     }
 }
 
 impl Div<&OpArgument> for OpArgument {
     type Output = OpArgument;
     fn div(self, rhs: &OpArgument) -> Self::Output {
-        Op(Operation {
-            op: Division,
-            arguments: smallvec![self, construct_oparg(rhs)],
-        }
-        .into())
-        .into()
+        build(Division, smallvec![self, construct_oparg(rhs)])
     }
 }
 
 impl Add<&OpArgument> for &OpArgument {
     type Output = OpArgument;
     fn add(self, rhs: &OpArgument) -> Self::Output {
-        Op(Operation {
-            op: Addition,
-            arguments: smallvec![construct_oparg(self), construct_oparg(rhs)],
-        }
-        .into())
-        .into()
+        build(
+            Addition,
+            smallvec![construct_oparg(self), construct_oparg(rhs)],
+        )
     }
 }
 
 impl Mul<&OpArgument> for &OpArgument {
     type Output = OpArgument;
     fn mul(self, rhs: &OpArgument) -> Self::Output {
-        Op(Operation {
-            op: Multiplication,
-            arguments: smallvec![construct_oparg(self), construct_oparg(rhs)],
-        }
-        .into())
-        .into()
+        build(
+            Multiplication,
+            smallvec![construct_oparg(self), construct_oparg(rhs)],
+        )
     }
 }
 
 impl Sub<&OpArgument> for &OpArgument {
     type Output = OpArgument;
     fn sub(self, rhs: &OpArgument) -> Self::Output {
-        Op(Operation {
-            op: Subtraction,
-            arguments: smallvec![construct_oparg(self), construct_oparg(rhs)],
-        }
-        .into())
-        .into()
+        build(
+            Subtraction,
+            smallvec![construct_oparg(self), construct_oparg(rhs)],
+        )
     }
 }
 
 impl Div<&OpArgument> for &OpArgument {
     type Output = OpArgument;
     fn div(self, rhs: &OpArgument) -> Self::Output {
-        Op(Operation {
-            op: Division,
-            arguments: smallvec![construct_oparg(self), construct_oparg(rhs)],
-        }
-        .into())
-        .into()
+        build(
+            Division,
+            smallvec![construct_oparg(self), construct_oparg(rhs)],
+        )
     }
 }
 
 impl Neg for &OpArgument {
     type Output = OpArgument;
     fn neg(self) -> Self::Output {
-        Op(Operation {
-            op: Negation,
-            arguments: smallvec![construct_oparg(self)],
-        }
-        .into())
-        .into()
+        build(Negation, smallvec![construct_oparg(self)])
     }
 }
 
 impl OpArgument {
     pub fn pow(&self, rhs: &OpArgument) -> OpArgument {
-        Op(Operation {
-            op: Pow,
-            arguments: smallvec![construct_oparg(self), construct_oparg(rhs)],
-        }
-        .into())
-        .into()
+        build(Pow, smallvec![construct_oparg(self), construct_oparg(rhs)])
     }
 
     pub fn ln(&self) -> OpArgument {
-        Op(Operation {
-            op: Ln,
-            arguments: smallvec![construct_oparg(self)],
-        }
-        .into())
-        .into()
+        build(Ln, smallvec![construct_oparg(self)])
     }
 
     pub fn exp(&self) -> OpArgument {
-        Op(Operation {
-            op: Exp,
-            arguments: smallvec![construct_oparg(self)],
-        }
-        .into())
-        .into()
+        build(Exp, smallvec![construct_oparg(self)])
     }
 
     pub fn sin(&self) -> OpArgument {
-        Op(Operation {
-            op: Sin,
-            arguments: smallvec![construct_oparg(self)],
-        }
-        .into())
-        .into()
+        build(Sin, smallvec![construct_oparg(self)])
     }
 
     pub fn cos(&self) -> OpArgument {
-        Op(Operation {
-            op: Cos,
-            arguments: smallvec![construct_oparg(self)],
-        }
-        .into())
-        .into()
+        build(Cos, smallvec![construct_oparg(self)])
+    }
+
+    pub fn tan(&self) -> OpArgument {
+        build(Tan, smallvec![construct_oparg(self)])
+    }
+
+    pub fn atan(&self) -> OpArgument {
+        build(Atan, smallvec![construct_oparg(self)])
+    }
+
+    pub fn sign(&self) -> OpArgument {
+        build(Sign, smallvec![construct_oparg(self)])
     }
 }
 
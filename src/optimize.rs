@@ -0,0 +1,421 @@
+//! This module describes how to find the global minimum of an expression over a box.
+//!
+//! [`minimize`] combines interval branch and bound with local descent. The interval evaluator
+//! gives a rigorous lower bound of the expression over each box, so boxes whose bound exceeds
+//! the best value found so far cannot hold the minimum and are discarded, and the box with the
+//! lowest bound is split next. Gradient descent on the compiled expression and its symbolic
+//! gradient finds low values quickly, which tightens the upper bound and prunes more boxes.
+//! When the bounds meet within the tolerance, the minimum is certified to lie between them.
+
+use std::{cmp::Ordering, collections::BinaryHeap, fmt::Display};
+
+use ahash::HashMap;
+
+use crate::{
+    budget::{Budget, Partial},
+    codegen::CodegenError,
+    compile::{compile_many, Program},
+    evaluation::EvaluationError,
+    interval::Interval,
+    symbols::OpArgument,
+};
+
+/// The reasons a minimization can fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OptimizeError {
+    Evaluation(EvaluationError),
+    Codegen(CodegenError),
+    /// The range of this variable is not finite, so there is no box to search.
+    Unbounded(&'static str),
+    /// The budget ran out before the bounds met, leaving the best minimum found.
+    Interrupted(Partial<Minimum>),
+}
+
+impl Display for OptimizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OptimizeError::Evaluation(err) => err.fmt(f),
+            OptimizeError::Codegen(err) => err.fmt(f),
+            OptimizeError::Unbounded(name) => write!(f, "the range of {} is unbounded", name),
+            OptimizeError::Interrupted(partial) => partial.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for OptimizeError {}
+
+impl From<EvaluationError> for OptimizeError {
+    fn from(err: EvaluationError) -> Self {
+        OptimizeError::Evaluation(err)
+    }
+}
+
+impl From<CodegenError> for OptimizeError {
+    fn from(err: CodegenError) -> Self {
+        OptimizeError::Codegen(err)
+    }
+}
+
+/// How far [`minimize`] refines its bounds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MinimizeOptions {
+    /// The search stops once the bounds on the minimum are this close.
+    pub tolerance: f64,
+    /// The most boxes split before giving up on certifying the minimum.
+    pub max_splits: usize,
+    /// The most steps of each local descent.
+    pub descent_steps: usize,
+}
+
+impl Default for MinimizeOptions {
+    fn default() -> Self {
+        MinimizeOptions {
+            tolerance: 1e-9,
+            max_splits: 100_000,
+            descent_steps: 100,
+        }
+    }
+}
+
+/// The lowest point found, and bounds on the global minimum.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Minimum {
+    pub point: Vec<(&'static str, f64)>,
+    /// The global minimum lies in this interval, whose upper bound is the value at `point`.
+    pub enclosure: Interval,
+    /// Whether the enclosure is narrower than the tolerance.
+    pub certified: bool,
+}
+
+/// A box with the lower bound of the expression over it, ordered so the lowest bound is
+/// popped first.
+struct Candidate {
+    lower: f64,
+    ranges: Vec<Interval>,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.lower.total_cmp(&self.lower)
+    }
+}
+
+/// The best point seen, improved by projected gradient descent from new starting points.
+struct Incumbent<'a> {
+    program: &'a Program,
+    names: &'a [&'static str],
+    registers: Vec<f64>,
+    out: Vec<f64>,
+    value: f64,
+    point: Vec<f64>,
+}
+
+impl Incumbent<'_> {
+    fn evaluate(&mut self, point: &[f64]) -> f64 {
+        self.program
+            .evaluate_into(point, &mut self.registers, &mut self.out);
+        self.out[0]
+    }
+
+    /// Descends from `start` within `ranges`, keeping the result if it is the lowest yet.
+    fn descend(&mut self, start: Vec<f64>, ranges: &[Interval], steps: usize) {
+        let mut point = start;
+        let mut value = self.evaluate(&point);
+        if value.is_nan() {
+            return;
+        }
+        let mut step = 1.0;
+        for _ in 0..steps {
+            let gradient = self.out[1..=self.names.len()].to_vec();
+            if gradient.iter().any(|g| !g.is_finite()) {
+                break;
+            }
+            // Backtrack until the projected step decreases the value.
+            let mut improved = false;
+            while step > 1e-12 {
+                let trial = point
+                    .iter()
+                    .zip(&gradient)
+                    .zip(ranges)
+                    .map(|((x, g), range)| (x - step * g).clamp(range.lo, range.hi))
+                    .collect::<Vec<_>>();
+                let trial_value = self.evaluate(&trial);
+                if trial_value < value {
+                    (point, value, improved) = (trial, trial_value, true);
+                    step *= 2.0;
+                    break;
+                }
+                step /= 2.0;
+            }
+            // The outputs now hold the gradient at the accepted point.
+            if !improved {
+                break;
+            }
+        }
+        if value < self.value {
+            (self.value, self.point) = (value, point);
+        }
+    }
+}
+
+/// Bounds the global minimum of `expr` over the box where each variable ranges over its
+/// interval in `bounds`, which must all be finite, or it returns [`OptimizeError::Unbounded`].
+pub fn minimize(
+    expr: &OpArgument,
+    bounds: &[(&'static str, Interval)],
+    options: MinimizeOptions,
+) -> Result<Minimum, OptimizeError> {
+    minimize_within(expr, bounds, options, &Budget::unlimited())
+}
+
+/// Bounds the global minimum as [`minimize`] does, spending a step of `budget` per box split
+/// and reporting each split with the number of boxes left, which the size limit caps. If it
+/// runs out, the best point and the bounds reached so far come back in
+/// [`OptimizeError::Interrupted`].
+pub fn minimize_within(
+    expr: &OpArgument,
+    bounds: &[(&'static str, Interval)],
+    options: MinimizeOptions,
+    budget: &Budget,
+) -> Result<Minimum, OptimizeError> {
+    if let Some(&(name, _)) = bounds
+        .iter()
+        .find(|(_, range)| !(range.lo.is_finite() && range.hi.is_finite()))
+    {
+        return Err(OptimizeError::Unbounded(name));
+    }
+    let names = bounds.iter().map(|&(name, _)| name).collect::<Vec<_>>();
+    let exprs = std::iter::once(expr.clone())
+        .chain(names.iter().map(|name| expr.derivative(name)))
+        .collect::<Vec<_>>();
+    let program = compile_many(&exprs, &names)?;
+    let mut incumbent = Incumbent {
+        program: &program,
+        names: &names,
+        registers: Vec::new(),
+        out: vec![0.0; exprs.len()],
+        value: f64::INFINITY,
+        point: bounds.iter().map(|(_, range)| range.midpoint()).collect(),
+    };
+
+    // The larger of the lower bounds of the natural interval extension and the mean value
+    // form f(c) + ∇f(X)·(X - c), which is much tighter on small boxes.
+    let lower_bound = |ranges: &[Interval]| -> Result<Option<f64>, EvaluationError> {
+        let mut bindings = names
+            .iter()
+            .copied()
+            .zip(ranges.iter().copied())
+            .collect::<HashMap<_, _>>();
+        let natural = expr.evaluate_interval(&bindings)?;
+        // An empty enclosure means the expression has no real value on the box.
+        if natural.is_empty() {
+            return Ok(None);
+        }
+        let slopes = exprs[1..]
+            .iter()
+            .map(|d| d.evaluate_interval(&bindings))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (name, range) in names.iter().zip(ranges) {
+            bindings.insert(name, Interval::point(range.midpoint()));
+        }
+        let mean_value = slopes
+            .iter()
+            .zip(ranges)
+            .fold(expr.evaluate_interval(&bindings)?, |sum, (slope, range)| {
+                sum + *slope * (*range - Interval::point(range.midpoint()))
+            });
+        let lower = [natural.lo, mean_value.lo]
+            .into_iter()
+            .filter(|lo| !lo.is_nan())
+            .fold(f64::NEG_INFINITY, f64::max);
+        Ok(Some(lower))
+    };
+
+    let ranges = bounds.iter().map(|&(_, range)| range).collect::<Vec<_>>();
+    let mut queue = BinaryHeap::new();
+    if let Some(lower) = lower_bound(&ranges)? {
+        let start = incumbent.point.clone();
+        incumbent.descend(start, &ranges, options.descent_steps);
+        queue.push(Candidate { lower, ranges });
+    }
+
+    let mut splits = 0;
+    let global = loop {
+        let Some(candidate) = queue.pop() else {
+            // Every box was pruned, so the incumbent is the minimum.
+            break incumbent.value;
+        };
+        if incumbent.value - candidate.lower <= options.tolerance
+            || splits == options.max_splits
+            || budget.spend().is_err()
+        {
+            break candidate.lower;
+        }
+        if candidate.lower > incumbent.value {
+            continue;
+        }
+        splits += 1;
+
+        let (axis, _) = candidate
+            .ranges
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.width().total_cmp(&b.width()))
+            .expect("Oops, a box has at least one axis");
+        let range = candidate.ranges[axis];
+        let middle = range.midpoint();
+        for half in [
+            Interval::new(range.lo, middle),
+            Interval::new(middle, range.hi),
+        ] {
+            let mut ranges = candidate.ranges.clone();
+            ranges[axis] = half;
+            let Some(lower) = lower_bound(&ranges)? else {
+                continue;
+            };
+            if lower > incumbent.value {
+                continue;
+            }
+            let centre = ranges.iter().map(Interval::midpoint).collect::<Vec<_>>();
+            if incumbent.evaluate(&centre) < incumbent.value {
+                incumbent.descend(centre, &ranges, options.descent_steps);
+            }
+            queue.push(Candidate { lower, ranges });
+        }
+        // Too many boxes exhausts the budget, so the next spend stops with a valid bound.
+        let _ = budget.iterate(queue.len());
+    };
+
+    let lower = global.min(incumbent.value);
+    let minimum = Minimum {
+        point: names.iter().copied().zip(incumbent.point).collect(),
+        enclosure: Interval {
+            lo: lower,
+            hi: incumbent.value,
+        },
+        certified: incumbent.value - lower <= options.tolerance,
+    };
+    match budget.exhausted() {
+        Some(reason) if !minimum.certified => Err(OptimizeError::Interrupted(Partial {
+            best: minimum,
+            reason,
+        })),
+        _ => Ok(minimum),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{minimize, minimize_within, MinimizeOptions, OptimizeError};
+    use crate::{
+        budget::{Budget, Exhausted},
+        interval::Interval,
+        parse::parse,
+    };
+
+    #[test]
+    fn test_minimize() {
+        let p = |input: &str| parse(input).unwrap();
+        let options = MinimizeOptions::default();
+
+        let bowl = minimize(
+            &p("(x - 1)^2 + (y + 1/2)^2 + 3"),
+            &[
+                ("x", Interval::new(-2.0, 2.0)),
+                ("y", Interval::new(-2.0, 2.0)),
+            ],
+            options,
+        )
+        .unwrap();
+        assert!(bowl.certified && bowl.enclosure.contains(3.0));
+        assert!((bowl.point[0].1 - 1.0).abs() < 1e-4 && (bowl.point[1].1 + 0.5).abs() < 1e-4);
+
+        // The six-hump camel function has two global minima among six local ones.
+        let camel = minimize(
+            &p("(4 - 2.1 * x^2 + x^4 / 3) * x^2 + x * y + (4 * y^2 - 4) * y^2"),
+            &[
+                ("x", Interval::new(-3.0, 3.0)),
+                ("y", Interval::new(-2.0, 2.0)),
+            ],
+            MinimizeOptions {
+                tolerance: 1e-6,
+                ..options
+            },
+        )
+        .unwrap();
+        assert!(camel.certified);
+        assert!((camel.enclosure.hi + 1.031_628_453_489_877).abs() < 1e-12);
+
+        // A multimodal function of one variable, with its minimum near 5.1457.
+        let wave = minimize(
+            &p("sin(x) + sin(10 * x / 3)"),
+            &[("x", Interval::new(2.7, 7.5))],
+            options,
+        )
+        .unwrap();
+        assert!(wave.certified);
+        assert!((wave.point[0].1 - 5.145_735).abs() < 1e-5);
+        assert!((wave.enclosure.hi + 1.899_599).abs() < 1e-6);
+
+        // Giving up early still bounds the minimum, just not tightly.
+        let early = minimize(
+            &p("sin(x) + sin(10 * x / 3)"),
+            &[("x", Interval::new(2.7, 7.5))],
+            MinimizeOptions {
+                max_splits: 2,
+                ..options
+            },
+        )
+        .unwrap();
+        assert!(!early.certified && early.enclosure.contains(-1.8996));
+
+        let budget = Budget::unlimited().with_steps(2);
+        let Err(OptimizeError::Interrupted(partial)) = minimize_within(
+            &p("sin(x) + sin(10 * x / 3)"),
+            &[("x", Interval::new(2.7, 7.5))],
+            options,
+            &budget,
+        ) else {
+            panic!("Uh-oh, two splits should not certify the minimum");
+        };
+        assert_eq!(partial.reason, Exhausted::OutOfSteps);
+        assert_eq!(partial.best.enclosure, early.enclosure);
+
+        // An indeterminate term leaves nothing to bound, but must not abort the search.
+        let indeterminate = minimize(
+            &p("x^2 + (inf - inf)"),
+            &[("x", Interval::new(-1.0, 1.0))],
+            MinimizeOptions {
+                max_splits: 8,
+                ..options
+            },
+        )
+        .unwrap();
+        assert!(!indeterminate.certified && indeterminate.enclosure == Interval::ENTIRE);
+
+        // There is no box to search if a range is unbounded.
+        let unbounded = minimize(
+            &p("x^2 + y^2"),
+            &[
+                ("x", Interval::new(-1.0, 1.0)),
+                ("y", Interval::new(0.0, f64::INFINITY)),
+            ],
+            options,
+        );
+        assert!(matches!(unbounded, Err(OptimizeError::Unbounded("y"))));
+    }
+}
@@ -0,0 +1,321 @@
+//! This module describes how to parse our computational graph from infix notation.
+//!
+//! The grammar is the usual one for calculators: `+` and `-` bind loosest, then `*` and `/`,
+//! then unary negation, then right-associative `^`. Function calls take a parenthesized
+//! argument, and `pi`/`π`, `e`, `i`, `inf`/`∞` and `undefined` name the built-in constants.
+//! A name followed by bracketed, comma-separated indices, such as `a[k + 1, j]`, is an indexed
+//! symbol (see [`crate::indexed`]).
+
+use std::{fmt::Display, str::FromStr};
+
+use smallvec::smallvec;
+
+use crate::{
+    constants::Value,
+    indexed::indexed,
+    operations::build,
+    symbols::{intern, OpArgument, OperationKind},
+};
+
+/// The reasons parsing an expression can fail. Positions are byte offsets into the input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    UnexpectedCharacter(usize, char),
+    UnexpectedToken(usize),
+    UnexpectedEnd,
+    UnknownFunction(usize, String),
+    NumberTooLarge(usize),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnexpectedCharacter(pos, c) => {
+                write!(f, "unexpected character {:?} at {}", c, pos)
+            }
+            ParseError::UnexpectedToken(pos) => write!(f, "unexpected token at {}", pos),
+            ParseError::UnexpectedEnd => f.write_str("unexpected end of input"),
+            ParseError::UnknownFunction(pos, name) => {
+                write!(f, "unknown function {} at {}", name, pos)
+            }
+            ParseError::NumberTooLarge(pos) => write!(f, "number at {} is too large", pos),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token<'a> {
+    Number(Value),
+    Ident(&'a str),
+    Symbol(char),
+}
+
+struct Lexer<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn tokens(input: &'a str) -> Result<Vec<(usize, Token<'a>)>, ParseError> {
+        let mut lexer = Lexer { input, pos: 0 };
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next_token()? {
+            tokens.push(token);
+        }
+        Ok(tokens)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !pred(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn next_token(&mut self) -> Result<Option<(usize, Token<'a>)>, ParseError> {
+        self.take_while(char::is_whitespace);
+        let start = self.pos;
+        let Some(c) = self.rest().chars().next() else {
+            return Ok(None);
+        };
+
+        let token = if c.is_ascii_digit() || c == '.' {
+            let whole = self.take_while(|c| c.is_ascii_digit());
+            let frac = match self.rest().strip_prefix('.') {
+                Some(_) => {
+                    self.pos += 1;
+                    self.take_while(|c| c.is_ascii_digit())
+                }
+                None => "",
+            };
+            if whole.is_empty() && frac.is_empty() {
+                return Err(ParseError::UnexpectedCharacter(start, c));
+            }
+
+            let too_large = || ParseError::NumberTooLarge(start);
+            let den = 10u64.checked_pow(frac.len() as u32).ok_or_else(too_large)?;
+            let num = format!("{}{}", whole, frac)
+                .parse::<u64>()
+                .map_err(|_| too_large())?;
+            Token::Number(Value::rational(num, den))
+        } else if c.is_alphabetic() || c == '_' {
+            Token::Ident(self.take_while(|c| c.is_alphanumeric() || c == '_'))
+        } else if "+-*/^(),[]π∞".contains(c) {
+            self.pos += c.len_utf8();
+            match c {
+                'π' => Token::Ident("pi"),
+                '∞' => Token::Ident("inf"),
+                _ => Token::Symbol(c),
+            }
+        } else {
+            return Err(ParseError::UnexpectedCharacter(start, c));
+        };
+
+        Ok(Some((start, token)))
+    }
+}
+
+fn function(name: &str) -> Option<OperationKind> {
+    use OperationKind::*;
+
+    [Exp, Sin, Cos, Tan, Ln, Atan, Sign]
+        .into_iter()
+        .find(|op| op.to_string() == name)
+}
+
+fn constant(name: &str) -> Option<Value> {
+    match name {
+        "pi" => Some(Value::Pi),
+        "e" => Some(Value::E),
+        "i" => Some(Value::I),
+        "inf" => Some(Value::Inf),
+        "undefined" => Some(Value::Undefined),
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<(usize, Token<'a>)>,
+    next: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+
+    fn bump(&mut self) -> Result<(usize, Token<'a>), ParseError> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .cloned()
+            .ok_or(ParseError::UnexpectedEnd)?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        let found = self.peek() == Some(&Token::Symbol(symbol));
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), ParseError> {
+        match self.bump()? {
+            (_, Token::Symbol(c)) if c == symbol => Ok(()),
+            (pos, _) => Err(ParseError::UnexpectedToken(pos)),
+        }
+    }
+
+    fn sum(&mut self) -> Result<OpArgument, ParseError> {
+        let mut lhs = self.product()?;
+        loop {
+            if self.eat('+') {
+                lhs = lhs + self.product()?;
+            } else if self.eat('-') {
+                lhs = lhs - self.product()?;
+            } else {
+                return Ok(lhs);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<OpArgument, ParseError> {
+        let mut lhs = self.unary()?;
+        loop {
+            if self.eat('*') {
+                lhs = lhs * self.unary()?;
+            } else if self.eat('/') {
+                lhs = lhs / self.unary()?;
+            } else {
+                return Ok(lhs);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<OpArgument, ParseError> {
+        if self.eat('-') {
+            Ok(-self.unary()?)
+        } else if self.eat('+') {
+            self.unary()
+        } else {
+            self.power()
+        }
+    }
+
+    fn power(&mut self) -> Result<OpArgument, ParseError> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            Ok(base.pow(&self.unary()?))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn atom(&mut self) -> Result<OpArgument, ParseError> {
+        match self.bump()? {
+            (_, Token::Number(value)) => Ok(value.into()),
+            (_, Token::Symbol('(')) => {
+                let inner = self.sum()?;
+                self.expect(')')?;
+                Ok(inner)
+            }
+            (pos, Token::Ident(name)) => {
+                if name == "sqrt" && self.eat('(') {
+                    // Square roots are powers, as in the canonical form of `crate::canonical`.
+                    let arg = self.sum()?;
+                    self.expect(')')?;
+                    Ok(arg.pow(&Value::rational(1, 2).into()))
+                } else if self.eat('(') {
+                    let op = function(name)
+                        .ok_or_else(|| ParseError::UnknownFunction(pos, name.to_owned()))?;
+                    let arg = self.sum()?;
+                    self.expect(')')?;
+                    Ok(build(op, smallvec![arg]))
+                } else if self.eat('[') {
+                    let mut indices = vec![self.sum()?];
+                    while self.eat(',') {
+                        indices.push(self.sum()?);
+                    }
+                    self.expect(']')?;
+                    Ok(indexed(name, &indices))
+                } else if let Some(value) = constant(name) {
+                    Ok(value.into())
+                } else {
+                    Ok(Value::Variable(intern(name)).into())
+                }
+            }
+            (pos, Token::Symbol(_)) => Err(ParseError::UnexpectedToken(pos)),
+        }
+    }
+}
+
+/// Parses an expression written in infix notation.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(len = input.len()))
+)]
+pub fn parse(input: &str) -> Result<OpArgument, ParseError> {
+    let mut parser = Parser {
+        tokens: Lexer::tokens(input)?,
+        next: 0,
+    };
+    let expr = parser.sum()?;
+    match parser.tokens.get(parser.next) {
+        Some((pos, _)) => Err(ParseError::UnexpectedToken(*pos)),
+        None => Ok(expr),
+    }
+}
+
+impl FromStr for OpArgument {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, ParseError};
+    use crate::{
+        constants::Value,
+        symbols::{variable, OpArgument},
+    };
+
+    #[test]
+    fn test_parse() {
+        let x = variable("x");
+        let y = variable("y");
+
+        let two = OpArgument::from(Value::integer(2));
+
+        assert_eq!(parse("x + y * 2").unwrap(), &x + (&y * &two));
+        assert_eq!(parse("-x^2").unwrap(), -x.pow(&two));
+        assert_eq!(
+            parse("2^3^x").unwrap(),
+            two.pow(&OpArgument::from(Value::integer(3)).pow(&x))
+        );
+        assert_eq!(
+            parse("sin(x - pi) / 0.25").unwrap(),
+            (&x - OpArgument::from(Value::Pi)).sin() / OpArgument::from(Value::rational(1, 4))
+        );
+        assert_eq!(parse("(x)").unwrap(), x);
+
+        assert_eq!(parse("x +"), Err(ParseError::UnexpectedEnd));
+        assert_eq!(parse("x y"), Err(ParseError::UnexpectedToken(2)));
+        assert_eq!(parse("x $"), Err(ParseError::UnexpectedCharacter(2, '$')));
+        assert_eq!(
+            parse("foo(x)"),
+            Err(ParseError::UnknownFunction(0, "foo".to_owned()))
+        );
+    }
+}
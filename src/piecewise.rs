@@ -0,0 +1,172 @@
+//! This module describes expressions defined piece by piece.
+//!
+//! The graph has no conditional operations, so a [`Piecewise`] holds expressions guarded by
+//! [`Formula`]s of [`Condition`]s, with the first piece whose condition holds giving the value.
+//! Readers of formats with conditionals, such as [`crate::mathml`] and [`crate::modelica`],
+//! lift them out of the operations they appear in, so that
+//! `k * piecewise(a if c, b otherwise)` becomes `k * a if c, k * b otherwise`, and each piece is
+//! an ordinary expression to differentiate or compile.
+
+use std::{cell::RefCell, fmt::Display};
+
+use crate::{
+    boolean::Formula,
+    condition::Condition,
+    evaluation::{Bindings, EvaluationError},
+    symbols::OpArgument,
+};
+
+/// Expressions guarded by conditions, valued as the first piece whose condition holds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Piecewise {
+    pub(crate) pieces: Vec<(Formula<Condition>, OpArgument)>,
+}
+
+impl From<OpArgument> for Piecewise {
+    fn from(expr: OpArgument) -> Self {
+        Piecewise {
+            pieces: vec![(Formula::True, expr)],
+        }
+    }
+}
+
+impl Piecewise {
+    /// The conditions and expressions, in the order they are tried.
+    pub fn pieces(&self) -> &[(Formula<Condition>, OpArgument)] {
+        &self.pieces
+    }
+
+    /// The expression, if there is only one and it holds everywhere.
+    pub fn expr(&self) -> Option<&OpArgument> {
+        match self.pieces.as_slice() {
+            [(Formula::True, expr)] => Some(expr),
+            _ => None,
+        }
+    }
+
+    /// Applies `f` to every piece, keeping the conditions.
+    pub fn map(&self, f: impl Fn(&OpArgument) -> OpArgument) -> Self {
+        Piecewise {
+            pieces: self
+                .pieces
+                .iter()
+                .map(|(condition, expr)| (condition.clone(), f(expr)))
+                .collect(),
+        }
+    }
+
+    /// The derivative with respect to `var` within each piece, which is the derivative
+    /// everywhere except on the boundaries between pieces.
+    pub fn derivative(&self, var: &'static str) -> Self {
+        self.map(|expr| expr.derivative(var))
+    }
+
+    /// The value of the first piece whose condition holds, or `None` if none does.
+    pub fn evaluate(&self, bindings: &Bindings) -> Result<Option<f64>, EvaluationError> {
+        match select(&self.pieces, bindings)? {
+            Some(expr) => expr.evaluate(bindings).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Combines the pieces of `args` with `f`, one piece for every choice of a piece from
+    /// each. Taking the choices in lexicographic order keeps the first piece that holds the
+    /// one made of the first pieces that hold.
+    pub(crate) fn combine(args: Vec<Piecewise>, f: impl Fn(&[OpArgument]) -> OpArgument) -> Self {
+        let mut choices = vec![(Formula::True, Vec::new())];
+        for arg in &args {
+            choices = choices
+                .into_iter()
+                .flat_map(
+                    |(condition, exprs): (Formula<Condition>, Vec<OpArgument>)| {
+                        arg.pieces.iter().map(move |(other, expr)| {
+                            let mut exprs = exprs.clone();
+                            exprs.push(expr.clone());
+                            (conjoin(&condition, other), exprs)
+                        })
+                    },
+                )
+                .collect();
+        }
+        Piecewise {
+            pieces: choices
+                .into_iter()
+                .map(|(condition, exprs)| (condition, f(&exprs)))
+                .collect(),
+        }
+    }
+}
+
+/// The first of `pieces` whose condition holds for the variables given in `bindings`.
+pub(crate) fn select<'a, T>(
+    pieces: &'a [(Formula<Condition>, T)],
+    bindings: &Bindings,
+) -> Result<Option<&'a T>, EvaluationError> {
+    let error = RefCell::new(None);
+    let holds = |condition: &Condition| match condition.check(bindings) {
+        Ok(holds) => holds,
+        Err(err) => {
+            error.borrow_mut().get_or_insert(err);
+            false
+        }
+    };
+    for (condition, value) in pieces {
+        let applies = condition.evaluate(&holds);
+        if let Some(err) = error.take() {
+            return Err(err);
+        }
+        if applies {
+            return Ok(Some(value));
+        }
+    }
+    Ok(None)
+}
+
+pub(crate) fn conjoin(a: &Formula<Condition>, b: &Formula<Condition>) -> Formula<Condition> {
+    match (a, b) {
+        (Formula::True, c) | (c, Formula::True) => c.clone(),
+        _ => a.and(b),
+    }
+}
+
+impl Display for Piecewise {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (condition, expr)) in self.pieces.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            match condition {
+                Formula::True => write!(f, "{} otherwise", expr)?,
+                condition => write!(f, "{} if {}", expr, condition)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How two expressions compare in a condition.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Relation {
+    Lt,
+    Leq,
+    Gt,
+    Geq,
+    Eq,
+    Neq,
+}
+
+impl Relation {
+    /// `a` and `b` related this way, as conditions on their difference.
+    pub(crate) fn formula(self, a: &OpArgument, b: &OpArgument) -> Formula<Condition> {
+        let nonzero = |d: OpArgument| Formula::atom(Condition::Nonzero(d));
+        let nonnegative = |d: OpArgument| Formula::atom(Condition::Nonnegative(d));
+        match self {
+            Relation::Lt => nonnegative(b - a).and(&nonzero(b - a)),
+            Relation::Leq => nonnegative(b - a),
+            Relation::Gt => nonnegative(a - b).and(&nonzero(a - b)),
+            Relation::Geq => nonnegative(a - b),
+            Relation::Eq => nonzero(a - b).not(),
+            Relation::Neq => nonzero(a - b),
+        }
+    }
+}
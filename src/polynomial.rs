@@ -0,0 +1,73 @@
+//! This module describes arithmetic on dense polynomials with symbolic coefficients, given
+//! lowest degree first.
+//!
+//! Coefficients are folded as they are computed, like those of a [`crate::series::Series`], so
+//! polynomials with constant coefficients stay constants. Unlike a series, nothing is
+//! truncated: a product has every degree of its factors' degrees combined.
+
+use crate::{
+    series::{one, plus, times, zero},
+    symbols::OpArgument,
+};
+
+/// Folds the polynomial coefficients and drops the vanishing ones of highest degree.
+pub(crate) fn trimmed(coefficients: &[OpArgument]) -> Vec<OpArgument> {
+    let mut coefficients = coefficients
+        .iter()
+        .map(OpArgument::fold)
+        .collect::<Vec<_>>();
+    while coefficients.last() == Some(&zero()) {
+        coefficients.pop();
+    }
+    coefficients
+}
+
+pub(crate) fn add(a: &[OpArgument], b: &[OpArgument]) -> Vec<OpArgument> {
+    (0..a.len().max(b.len()))
+        .map(|i| match (a.get(i), b.get(i)) {
+            (Some(a), Some(b)) => plus(a, b),
+            (a, b) => a.or(b).cloned().expect("Oops, one of them is long enough"),
+        })
+        .collect()
+}
+
+pub(crate) fn scale(a: &[OpArgument], factor: &OpArgument) -> Vec<OpArgument> {
+    a.iter().map(|c| times(c, factor)).collect()
+}
+
+pub(crate) fn neg(a: &[OpArgument]) -> Vec<OpArgument> {
+    scale(a, &-one())
+}
+
+pub(crate) fn mul(a: &[OpArgument], b: &[OpArgument]) -> Vec<OpArgument> {
+    let mut product = vec![zero(); (a.len() + b.len()).saturating_sub(1)];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            product[i + j] = plus(&product[i + j], &times(x, y));
+        }
+    }
+    product
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add, mul, neg, trimmed};
+    use crate::parse::parse;
+
+    #[test]
+    fn test_polynomial() {
+        let p = |inputs: &[&str]| {
+            inputs
+                .iter()
+                .map(|input| parse(input).unwrap().fold())
+                .collect::<Vec<_>>()
+        };
+        // (1 + x)(3 - x) = 3 + 2x - x²
+        let product = mul(&p(&["1", "1"]), &p(&["3", "-1"]));
+        assert_eq!(product, p(&["3", "2", "-1"]));
+        assert_eq!(trimmed(&add(&product, &p(&["0", "-2", "1"]))), p(&["3"]));
+        assert_eq!(trimmed(&add(&product, &neg(&product))), []);
+        assert_eq!(mul(&p(&["a"]), &p(&["1", "1/2"])), p(&["a", "a * (1/2)"]));
+        assert_eq!(mul(&p(&["2"]), &[]), []);
+    }
+}
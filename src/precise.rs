@@ -0,0 +1,428 @@
+//! This module describes how to numerically evaluate our computational graph to any precision,
+//! with the binary floating point numbers of `dashu-float`.
+//!
+//! [`evaluate_precise`] computes every node with [`GUARD_BITS`] more bits than asked for and
+//! rounds only the final result, so it can be used to check `f64` results and to evaluate
+//! expressions that cancel catastrophically in `f64`. `dashu-float` provides the exponential,
+//! the logarithm and powers. π and the circular functions are computed here from their Taylor
+//! series, after reducing the argument with a π as precise as its magnitude requires.
+
+use std::fmt::Display;
+
+use dashu_float::{round::mode::HalfEven, Context, FBig};
+use dashu_int::IBig;
+
+use crate::{
+    algebraic::encode,
+    constants::Value,
+    evaluation::{Bindings, EvaluationError},
+    fold::Ratio,
+    symbols::{
+        OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        OperationKind::*,
+    },
+};
+
+/// A binary floating point number rounding to nearest, ties to even.
+pub type Float = FBig<HalfEven>;
+
+/// The bits carried beyond the requested precision while evaluating.
+pub const GUARD_BITS: usize = 64;
+
+/// The reasons [`evaluate_precise`] can fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PreciseError {
+    Evaluation(EvaluationError),
+    /// The node has no finite real value, as `ln(-1)`, `1/0` or a variable bound to NaN.
+    NotFinite(OpArgument),
+}
+
+impl Display for PreciseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PreciseError::Evaluation(err) => err.fmt(f),
+            PreciseError::NotFinite(node) => write!(f, "{} is not a finite real number", node),
+        }
+    }
+}
+
+impl std::error::Error for PreciseError {}
+
+impl From<EvaluationError> for PreciseError {
+    fn from(err: EvaluationError) -> Self {
+        PreciseError::Evaluation(err)
+    }
+}
+
+/// Evaluates `expr` with the variables given in `bindings`, rounded to `bits` significant bits.
+pub fn evaluate_precise(
+    expr: &OpArgument,
+    bindings: &Bindings,
+    bits: usize,
+) -> Result<Float, PreciseError> {
+    assert!(bits > 0, "Hold on, evaluate_precise needs at least one bit");
+    let mut evaluator = Evaluator {
+        bindings,
+        precision: bits + GUARD_BITS,
+        pi: None,
+    };
+    let value = evaluator.evaluate(expr)?;
+    Ok(value.with_precision(bits).value())
+}
+
+/// `n` as a float of the given precision.
+fn float(n: impl Into<IBig>, precision: usize) -> Float {
+    Float::from(n.into()).with_precision(precision).value()
+}
+
+/// `⌊log₂ |x|⌋ + 1`, the position of the leading bit of the nonzero `x`.
+fn magnitude(x: &Float) -> isize {
+    x.repr().exponent() + x.repr().digits() as isize
+}
+
+/// Whether `term` no longer changes a sum of magnitude about `2^scale` at `precision` bits.
+fn negligible(term: &Float, scale: isize, precision: usize) -> bool {
+    term.repr().is_zero() || magnitude(term) < scale - precision as isize - 2
+}
+
+struct Evaluator<'a> {
+    bindings: &'a Bindings<'a>,
+    precision: usize,
+    /// The most precise π computed so far.
+    pi: Option<Float>,
+}
+
+impl Evaluator<'_> {
+    fn evaluate(&mut self, node: &OpArgument) -> Result<Float, PreciseError> {
+        let op = match &node.value {
+            Leaf(value) => return self.leaf(node, value),
+            Op(op) => op,
+        };
+        let args = op
+            .arguments
+            .iter()
+            .map(|arg| self.evaluate(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        let not_finite = || PreciseError::NotFinite(node.clone());
+        let p = self.precision;
+        let zero = |x: &Float| x.repr().is_zero();
+        let negative = |x: &Float| *x < Float::ZERO;
+
+        Ok(match op.op {
+            Addition => &args[0] + &args[1],
+            Subtraction => &args[0] - &args[1],
+            Multiplication => &args[0] * &args[1],
+            Division if zero(&args[1]) => return Err(not_finite()),
+            Division => &args[0] / &args[1],
+            Negation => -&args[0],
+            Pow => self.pow(&args[0], &args[1]).ok_or_else(not_finite)?,
+            // `eˣ` is out of any sensible range long before `x` reaches `2⁶²`.
+            Exp if !zero(&args[0]) && magnitude(&args[0]) > 62 => match negative(&args[0]) {
+                true => float(0, p),
+                false => return Err(not_finite()),
+            },
+            Exp => args[0].exp(),
+            Ln if zero(&args[0]) || negative(&args[0]) => return Err(not_finite()),
+            Ln => args[0].ln(),
+            Sin => self.sin_cos(&args[0], false),
+            Cos => self.sin_cos(&args[0], true),
+            Tan => &self.sin_cos(&args[0], false) / &self.sin_cos(&args[0], true),
+            Atan => self.atan(&args[0], p),
+            Sign if zero(&args[0]) => float(0, p),
+            Sign if negative(&args[0]) => float(-1, p),
+            Sign => float(1, p),
+        })
+    }
+
+    fn leaf(&mut self, node: &OpArgument, value: &Value) -> Result<Float, PreciseError> {
+        let p = self.precision;
+        Ok(match *value {
+            Value::Rational(num, den) => &float(num, p) / &float(den.get(), p),
+            Value::Pi => self.pi(p),
+            Value::E => float(1, p).exp(),
+            Value::Algebraic(number) => algebraic(encode(number), p),
+            Value::Inf | Value::Undefined => return Err(PreciseError::NotFinite(node.clone())),
+            Value::I => return Err(EvaluationError::NotReal(*value).into()),
+            Value::Variable(name) => {
+                let x = *self
+                    .bindings
+                    .get(name)
+                    .ok_or(EvaluationError::Unbound(name))?;
+                match Float::try_from(x) {
+                    Ok(x) if x.repr().is_finite() => x.with_precision(p).value(),
+                    _ => return Err(PreciseError::NotFinite(node.clone())),
+                }
+            }
+        })
+    }
+
+    /// `base^exponent`, which is real for negative bases only when the exponent is an integer.
+    fn pow(&self, base: &Float, exponent: &Float) -> Option<Float> {
+        let p = self.precision;
+        let integer = exponent.repr().is_int().then(|| exponent.to_int().value());
+        if base.repr().is_zero() {
+            return match *exponent > Float::ZERO {
+                true => Some(float(0, p)),
+                false if exponent.repr().is_zero() => Some(float(1, p)),
+                false => None,
+            };
+        }
+        let negative = *base < Float::ZERO;
+        let odd = negative && integer? % IBig::from(2) != IBig::ZERO;
+        let absolute = if negative { -base } else { base.clone() };
+        // Like `eˣ`, `|base|^exponent = e^(exponent·ln |base|)` is out of any sensible range
+        // long before that logarithm reaches `2⁶²`.
+        let log = exponent * &absolute.ln();
+        if !log.repr().is_zero() && magnitude(&log) > 62 {
+            return (log < Float::ZERO).then(|| float(0, p));
+        }
+        let power = absolute.powf(exponent);
+        Some(if odd { -power } else { power })
+    }
+
+    /// π to `precision` bits, computed with Machin's formula `π = 16 atan(1/5) - 4 atan(1/239)`.
+    fn pi(&mut self, precision: usize) -> Float {
+        if let Some(pi) = self.pi.as_ref().filter(|pi| pi.precision() >= precision) {
+            return pi.clone().with_precision(precision).value();
+        }
+        let q = precision + 16;
+        let fifth = atan_series(&(&float(1, q) / &float(5, q)), q);
+        let small = atan_series(&(&float(1, q) / &float(239, q)), q);
+        let pi = &(&fifth * &float(16, q)) - &(&small * &float(4, q));
+        self.pi = Some(pi.clone());
+        pi.with_precision(precision).value()
+    }
+
+    /// `atan(x)` to `precision` bits.
+    fn atan(&mut self, x: &Float, precision: usize) -> Float {
+        if x.repr().is_zero() {
+            return float(0, precision);
+        }
+        if *x < Float::ZERO {
+            return -self.atan(&-x, precision);
+        }
+        let q = precision + 16;
+        let x = x.clone().with_precision(q).value();
+        if x > float(1, q) {
+            let half_pi = &self.pi(q) / &float(2, q);
+            let rest = self.atan(&(&float(1, q) / &x), q);
+            return (&half_pi - &rest).with_precision(precision).value();
+        }
+        // Three halvings of the angle, `atan x = 2 atan(x / (1 + √(1 + x²)))`, bring `x` below
+        // `tan(π/32)`, where the series converges quickly.
+        let context = Context::<HalfEven>::new(q);
+        let mut x = x;
+        for _ in 0..3 {
+            let root = context.sqrt((&float(1, q) + &(&x * &x)).repr()).value();
+            x = &x / &(&float(1, q) + &root);
+        }
+        (&atan_series(&x, q) * &float(8, q))
+            .with_precision(precision)
+            .value()
+    }
+
+    /// `sin(x)`, or `cos(x)` if `cosine`, reduced to `r = x - nπ/2` with `|r| ≤ π/4`.
+    fn sin_cos(&mut self, x: &Float, cosine: bool) -> Float {
+        let p = self.precision;
+        // Enough bits of π for the bits of `x` above the point to cancel.
+        let q = p + 16 + magnitude(x).max(0) as usize;
+        let x = x.clone().with_precision(q).value();
+        let half_pi = &self.pi(q) / &float(2, q);
+        let n = (&x / &half_pi).round().to_int().value();
+        let r = &x - &(&half_pi * &float(n.clone(), q));
+        let quadrant = i64::try_from(n % IBig::from(4))
+            .expect("Oops, a remainder by 4 should fit in an i64")
+            .rem_euclid(4)
+            + cosine as i64;
+        let value = match quadrant % 2 {
+            0 => sin_series(&r, q),
+            _ => cos_series(&r, q),
+        };
+        match quadrant % 4 {
+            0 | 1 => value,
+            _ => -value,
+        }
+        .with_precision(p)
+        .value()
+    }
+}
+
+/// `atan(x)` from `Σ (-1)ᵏ x²ᵏ⁺¹/(2k+1)` for small `x`.
+fn atan_series(x: &Float, precision: usize) -> Float {
+    if x.repr().is_zero() {
+        return x.clone();
+    }
+    let (scale, square) = (magnitude(x), x * x);
+    let (mut power, mut sum, mut k) = (x.clone(), x.clone(), 1u64);
+    loop {
+        power = -&(&power * &square);
+        let term = &power / &float(2 * k + 1, precision);
+        if negligible(&term, scale, precision) {
+            return sum;
+        }
+        sum = &sum + &term;
+        k += 1;
+    }
+}
+
+/// `sin(r)` from `Σ (-1)ᵏ r²ᵏ⁺¹/(2k+1)!` for `|r| ≤ π/4`.
+fn sin_series(r: &Float, precision: usize) -> Float {
+    if r.repr().is_zero() {
+        return r.clone();
+    }
+    let (scale, square) = (magnitude(r), r * r);
+    let (mut term, mut sum, mut k) = (r.clone(), r.clone(), 1u64);
+    loop {
+        term = -&(&(&term * &square) / &float((2 * k) * (2 * k + 1), precision));
+        if negligible(&term, scale, precision) {
+            return sum;
+        }
+        sum = &sum + &term;
+        k += 1;
+    }
+}
+
+/// `cos(r)` from `Σ (-1)ᵏ r²ᵏ/(2k)!` for `|r| ≤ π/4`, where it is at least `1/√2`.
+fn cos_series(r: &Float, precision: usize) -> Float {
+    let square = r * r;
+    let (mut term, mut sum, mut k) = (float(1, precision), float(1, precision), 1u64);
+    loop {
+        term = -&(&(&term * &square) / &float((2 * k - 1) * (2 * k), precision));
+        if negligible(&term, 0, precision) {
+            return sum;
+        }
+        sum = &sum + &term;
+        k += 1;
+    }
+}
+
+fn ratio(r: Ratio, precision: usize) -> Float {
+    &float(r.num, precision) / &float(r.den, precision)
+}
+
+/// The root of `polynomial` in `(lo, hi)`, bisected down to `precision` bits.
+fn algebraic((polynomial, (lo, hi)): (&[Ratio], (Ratio, Ratio)), precision: usize) -> Float {
+    let coefficients = polynomial
+        .iter()
+        .map(|&c| ratio(c, precision))
+        .collect::<Vec<_>>();
+    let at = |x: &Float| {
+        coefficients
+            .iter()
+            .rev()
+            .fold(float(0, precision), |acc, c| &(&acc * x) + c)
+    };
+    let (mut lo, mut hi) = (ratio(lo, precision), ratio(hi, precision));
+    let positive_below = at(&lo) > Float::ZERO;
+    let two = float(2, precision);
+    loop {
+        let mid = &(&lo + &hi) / &two;
+        if mid <= lo || mid >= hi {
+            return mid;
+        }
+        match (at(&mid) > Float::ZERO) == positive_below {
+            true => lo = mid,
+            false => hi = mid,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evaluate_precise, Float, PreciseError};
+    use crate::{evaluation::Bindings, parse::parse};
+
+    /// The first `digits` significant decimal digits of `x`.
+    fn digits(x: &Float, digits: usize) -> String {
+        let decimal = x.to_decimal().value().with_precision(digits).value();
+        format!("{}", decimal)
+    }
+
+    #[test]
+    fn test_evaluate_precise() {
+        let none = Bindings::default();
+        let pi = evaluate_precise(&parse("pi").unwrap(), &none, 200).unwrap();
+        assert_eq!(
+            digits(&pi, 50),
+            "3.1415926535897932384626433832795028841971693993751"
+        );
+        let e = evaluate_precise(&parse("exp(1)").unwrap(), &none, 200).unwrap();
+        assert_eq!(digits(&e, 40), "2.718281828459045235360287471352662497757");
+        let atan = evaluate_precise(&parse("4 * atan(1)").unwrap(), &none, 200).unwrap();
+        assert_eq!(digits(&atan, 50), digits(&pi, 50));
+
+        // sin(π/6) and cos(π/3) are one half, and tan(π/4) is one.
+        for (expr, value) in [
+            ("sin(pi / 6)", 0.5),
+            ("cos(pi / 3)", 0.5),
+            ("tan(pi / 4)", 1.0),
+        ] {
+            let x = evaluate_precise(&parse(expr).unwrap(), &none, 120).unwrap();
+            let error = &x - &Float::try_from(value).unwrap();
+            assert!(
+                error.repr().is_zero() || super::magnitude(&error) < -110,
+                "{}",
+                expr
+            );
+        }
+        let sqrt2 = evaluate_precise(&parse("2^(1/2)").unwrap(), &none, 200).unwrap();
+        assert_eq!(
+            digits(&sqrt2, 40),
+            "1.41421356237309504880168872420969807857"
+        );
+
+        // A large argument still reduces correctly: sin(10²²) = -0.85220084976718880177...
+        let x = Bindings::from_iter([("x", 1e22)]);
+        let sin = evaluate_precise(&parse("sin(x)").unwrap(), &x, 100).unwrap();
+        assert_eq!(digits(&sin, 20), "-0.85220084976718880177");
+    }
+
+    #[test]
+    fn test_evaluate_precise_cancellation() {
+        // (x + 1) - x is exactly one, which f64 loses entirely at x = 1e17.
+        let expr = parse("(x + 1) - x").unwrap();
+        let bindings = Bindings::from_iter([("x", 1e17)]);
+        assert_eq!(expr.evaluate(&bindings), Ok(0.0));
+        let value = evaluate_precise(&expr, &bindings, 64).unwrap();
+        assert_eq!(value.to_f64().value(), 1.0);
+
+        // 1 - cos(x) at x = 1e-10 is 5e-21, where f64 gives 0.
+        let expr = parse("1 - cos(x)").unwrap();
+        let bindings = Bindings::from_iter([("x", 1e-10)]);
+        let value = evaluate_precise(&expr, &bindings, 64).unwrap();
+        assert!((value.to_f64().value() / 5e-21 - 1.0).abs() < 1e-15);
+    }
+
+    #[test]
+    fn test_evaluate_precise_errors() {
+        let none = Bindings::default();
+        let expr = parse("ln(0 - 1)").unwrap();
+        assert!(matches!(
+            evaluate_precise(&expr, &none, 64),
+            Err(PreciseError::NotFinite(_))
+        ));
+        let expr = parse("1 / x").unwrap();
+        let zero = Bindings::from_iter([("x", 0.0)]);
+        assert!(matches!(
+            evaluate_precise(&expr, &zero, 64),
+            Err(PreciseError::NotFinite(_))
+        ));
+        assert!(matches!(
+            evaluate_precise(&expr, &none, 64),
+            Err(PreciseError::Evaluation(_))
+        ));
+        let expr = parse("(0 - 2)^3").unwrap();
+        let cube = evaluate_precise(&expr, &none, 64).unwrap();
+        assert_eq!(cube.to_f64().value(), -8.0);
+
+        // Huge exponents are out of range without computing the power.
+        let huge = Bindings::from_iter([("x", 1e30)]);
+        assert!(matches!(
+            evaluate_precise(&parse("2^x").unwrap(), &huge, 64),
+            Err(PreciseError::NotFinite(_))
+        ));
+        let tiny = evaluate_precise(&parse("(0 - 1/2)^x").unwrap(), &huge, 64).unwrap();
+        assert!(tiny.repr().is_zero());
+    }
+}
@@ -0,0 +1,195 @@
+//! This module describes how to tell whether a compiled program is accurate enough in `f32`.
+//!
+//! Constants are checked for magnitudes single precision cannot hold. At each sample point the
+//! program is run in `f64` while propagating a first-order relative condition number through
+//! every register: an output with condition number `κ` can be expected to carry a relative
+//! error of about `κ · f32::EPSILON` when evaluated in `f32`.
+
+use std::fmt::Display;
+
+use crate::{
+    compile::{Instruction, Program},
+    symbols::OperationKind::{self, *},
+};
+
+/// A reason to expect `f32` evaluation of a [`Program`] to be inaccurate.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PrecisionWarning {
+    /// A constant, or a value computed at a sample point, exceeds the range of `f32`.
+    Overflow { register: usize, value: f64 },
+    /// A nonzero constant, or a value computed at a sample point, is subnormal or zero in `f32`.
+    Underflow { register: usize, value: f64 },
+    /// At the sample point with index `sample`, the output with index `output` has a condition
+    /// number too large for `f32` to reach the requested tolerance.
+    IllConditioned {
+        output: usize,
+        sample: usize,
+        condition: f64,
+    },
+}
+
+impl Display for PrecisionWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrecisionWarning::Overflow { register, value } => {
+                write!(f, "r{} = {:e} overflows f32", register, value)
+            }
+            PrecisionWarning::Underflow { register, value } => {
+                write!(f, "r{} = {:e} underflows f32", register, value)
+            }
+            PrecisionWarning::IllConditioned {
+                output,
+                sample,
+                condition,
+            } => write!(
+                f,
+                "output {} has condition number {:.3e} at sample {}",
+                output, condition, sample
+            ),
+        }
+    }
+}
+
+fn range_warning(register: usize, value: f64) -> Option<PrecisionWarning> {
+    let magnitude = value.abs();
+    if magnitude.is_finite() && magnitude > f32::MAX as f64 {
+        Some(PrecisionWarning::Overflow { register, value })
+    } else if magnitude != 0.0 && magnitude < f32::MIN_POSITIVE as f64 {
+        Some(PrecisionWarning::Underflow { register, value })
+    } else {
+        None
+    }
+}
+
+/// How much the relative error of each operand is amplified in the relative error of the result,
+/// that is `|x ∂f/∂x / f|` for each operand `x`.
+fn amplification(op: OperationKind, args: [f64; 2], result: f64) -> [f64; 2] {
+    let [a, b] = args;
+    let factors = match op {
+        Addition | Subtraction => [a / result, b / result],
+        Multiplication | Division => [1.0, 1.0],
+        Negation => [1.0, 0.0],
+        Pow => [b, b * a.ln()],
+        Exp => [a, 0.0],
+        Sin => [a / a.tan(), 0.0],
+        Cos => [a * a.tan(), 0.0],
+        Tan => [2.0 * a / (2.0 * a).sin(), 0.0],
+        Ln => [1.0 / result, 0.0],
+        Atan => [a / ((1.0 + a * a) * result), 0.0],
+        Sign => [0.0, 0.0],
+    };
+    factors.map(|factor| match factor.abs() {
+        // A zero operand contributes nothing, whatever the derivative.
+        factor if factor.is_nan() => 0.0,
+        factor => factor,
+    })
+}
+
+/// Checks whether `program` can be evaluated in `f32` with a relative error below `tolerance`,
+/// inspecting its constants and its behaviour at each of the given parameter `samples`.
+///
+/// At most one warning is reported per register and per output; for ill-conditioned outputs it
+/// is the worst sample.
+pub fn check_f32(program: &Program, samples: &[&[f64]], tolerance: f64) -> Vec<PrecisionWarning> {
+    let instructions = program.instructions();
+    let mut flagged = vec![false; instructions.len()];
+    let mut warnings = Vec::new();
+
+    for (register, instruction) in instructions.iter().enumerate() {
+        if let Instruction::Constant(c) = *instruction {
+            if let Some(warning) = range_warning(register, c) {
+                flagged[register] = true;
+                warnings.push(warning);
+            }
+        }
+    }
+
+    let max_condition = tolerance / f32::EPSILON as f64;
+    let mut worst: Vec<Option<(usize, f64)>> = vec![None; program.outputs().len()];
+    let mut values = Vec::with_capacity(instructions.len());
+    let mut conditions = Vec::with_capacity(instructions.len());
+
+    for (sample, params) in samples.iter().enumerate() {
+        program.evaluate_into(params, &mut values, &mut vec![0.0; program.outputs().len()]);
+
+        conditions.clear();
+        for (register, instruction) in instructions.iter().enumerate() {
+            // Every register is rounded once when it is stored.
+            let condition = match *instruction {
+                Instruction::Input(_) | Instruction::Constant(_) => 1.0,
+                Instruction::Unary(op, a) => {
+                    let [fa, _] = amplification(op, [values[a], 0.0], values[register]);
+                    1.0 + fa * conditions[a]
+                }
+                Instruction::Binary(op, a, b) => {
+                    let [fa, fb] = amplification(op, [values[a], values[b]], values[register]);
+                    1.0 + fa * conditions[a] + fb * conditions[b]
+                }
+            };
+            conditions.push(condition);
+
+            if !flagged[register] {
+                if let Some(warning) = range_warning(register, values[register]) {
+                    flagged[register] = true;
+                    warnings.push(warning);
+                }
+            }
+        }
+
+        for (output, &register) in program.outputs().iter().enumerate() {
+            let condition = conditions[register];
+            if condition > max_condition && worst[output].is_none_or(|(_, c)| condition > c) {
+                worst[output] = Some((sample, condition));
+            }
+        }
+    }
+
+    warnings.extend(worst.into_iter().enumerate().filter_map(|(output, worst)| {
+        worst.map(|(sample, condition)| PrecisionWarning::IllConditioned {
+            output,
+            sample,
+            condition,
+        })
+    }));
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_f32, PrecisionWarning};
+    use crate::{
+        compile::compile,
+        constants::Value,
+        symbols::{variable, OpArgument},
+    };
+
+    #[test]
+    fn test_check_f32() {
+        let x = variable("x");
+        let y = variable("y");
+
+        let benign = compile(&(&x * &y + x.sin()), &["x", "y"]).unwrap();
+        assert!(check_f32(&benign, &[&[0.5, 2.0], &[3.0, -1.0]], 1e-5).is_empty());
+        let got = benign.evaluate_f32(&[0.5, 2.0])[0];
+        assert!((got as f64 - benign.evaluate(&[0.5, 2.0])[0]).abs() < 1e-6);
+
+        // (x + 1) - x cancels catastrophically for large x.
+        let one = OpArgument::from(Value::integer(1));
+        let cancelling = compile(&((&x + one) - &x), &["x"]).unwrap();
+        let warnings = check_f32(&cancelling, &[&[1.0], &[1e6], &[1e3]], 1e-3);
+        assert!(matches!(
+            warnings[..],
+            [PrecisionWarning::IllConditioned {
+                output: 0,
+                sample: 1,
+                ..
+            }]
+        ));
+        assert_ne!(cancelling.evaluate_f32(&[1e8]), vec![1.0]);
+
+        let huge = OpArgument::from(Value::integer(u64::MAX));
+        let scaled = compile(&(&x * &huge * &huge), &["x"]).unwrap();
+        let warnings = check_f32(&scaled, &[&[1e3]], 1e-3);
+        assert!(matches!(warnings[..], [PrecisionWarning::Overflow { .. }]));
+    }
+}
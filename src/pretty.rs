@@ -0,0 +1,201 @@
+//! This module describes a terminal printer for our computational graph.
+//!
+//! [`OpArgument::pretty`] prints the same text as [`Display`], optionally with ANSI colors:
+//! parentheses take their color from how deeply they are nested, so matching pairs stand out,
+//! variables are bold and constants have a color of their own. Operations nested more deeply
+//! than a maximum depth print as `…`, so the outline of a huge expression fits on a screen.
+//! With the `pretty_debug` feature, `{:#?}` prints this way too.
+
+use std::{
+    cmp::Ordering,
+    fmt::{Display, Formatter, Result},
+};
+
+use crate::{
+    constants::Value,
+    operation_properties::Associativity,
+    symbols::{
+        OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        Operation, OperationKind,
+    },
+};
+
+const RESET: &str = "\x1b[0m";
+const VARIABLE: &str = "\x1b[1;32m";
+const CONSTANT: &str = "\x1b[33m";
+/// The colors of parentheses, by how deeply they are nested.
+const PARENTHESES: [&str; 4] = ["\x1b[35m", "\x1b[34m", "\x1b[36m", "\x1b[31m"];
+
+/// How [`OpArgument::pretty`] prints.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PrettyOptions {
+    /// Color with ANSI escape codes.
+    pub color: bool,
+    /// Print operations more than this many levels below the root as `…`.
+    pub max_depth: Option<usize>,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        PrettyOptions {
+            color: true,
+            max_depth: None,
+        }
+    }
+}
+
+/// An expression to print with [`PrettyOptions`].
+#[derive(Clone, Copy, Debug)]
+pub struct Pretty<'a> {
+    expr: &'a OpArgument,
+    options: PrettyOptions,
+}
+
+struct Printer<'f, 'a> {
+    f: &'f mut Formatter<'a>,
+    options: PrettyOptions,
+    /// How many parentheses are open.
+    parentheses: usize,
+}
+
+impl Printer<'_, '_> {
+    fn colored(&mut self, color: &str, text: &str) -> Result {
+        match self.options.color {
+            true => write!(self.f, "{}{}{}", color, text, RESET),
+            false => self.f.write_str(text),
+        }
+    }
+
+    fn parenthesized(&mut self, node: &OpArgument, depth: usize) -> Result {
+        let color = PARENTHESES[self.parentheses % PARENTHESES.len()];
+        self.colored(color, "(")?;
+        self.parentheses += 1;
+        self.node(node, depth)?;
+        self.parentheses -= 1;
+        self.colored(color, ")")
+    }
+
+    fn node(&mut self, node: &OpArgument, depth: usize) -> Result {
+        match &node.value {
+            Leaf(value) => match **value {
+                Value::Variable(_) => self.colored(VARIABLE, &value.to_string()),
+                _ => self.colored(CONSTANT, &value.to_string()),
+            },
+            Op(_) if self.options.max_depth.is_some_and(|max| depth > max) => self.f.write_str("…"),
+            Op(op) => self.operation(op, depth),
+        }
+    }
+
+    /// Prints `arg`, in parentheses unless `bare` says its precedence relative to `op` needs
+    /// none.
+    fn operand(
+        &mut self,
+        op: OperationKind,
+        arg: &OpArgument,
+        depth: usize,
+        bare: impl Fn(Ordering) -> bool,
+    ) -> Result {
+        let precedence = match &arg.value {
+            Op(inner) => op.cmp(&inner.op),
+            _ => Ordering::Greater,
+        };
+        match bare(precedence) {
+            true => self.node(arg, depth),
+            false => self.parenthesized(arg, depth),
+        }
+    }
+
+    fn operation(&mut self, op: &Operation, depth: usize) -> Result {
+        let (kind, args, depth) = (op.op, &op.arguments, depth + 1);
+        if kind == OperationKind::Pow
+            && matches!(&args[1].value, Leaf(v) if **v == Value::rational(1, 2))
+        {
+            self.f.write_str("sqrt")?;
+            return self.parenthesized(&args[0], depth);
+        }
+        if kind.is_prefix() {
+            write!(self.f, "{}", kind)?;
+            return self.operand(kind, &args[0], depth, Ordering::is_gt);
+        }
+        if kind.is_infix() {
+            let associativity = kind.associativity();
+            self.operand(kind, &args[0], depth, |precedence| {
+                precedence.is_gt() || (precedence.is_eq() && associativity == Associativity::Left)
+            })?;
+            write!(self.f, "{}", kind)?;
+            return self.operand(kind, &args[1], depth, |precedence| {
+                precedence.is_gt() || (precedence.is_eq() && associativity == Associativity::Right)
+            });
+        }
+
+        write!(self.f, "{}", kind)?;
+        let color = PARENTHESES[self.parentheses % PARENTHESES.len()];
+        self.colored(color, "(")?;
+        self.parentheses += 1;
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.f.write_str(",")?;
+            }
+            self.node(arg, depth)?;
+        }
+        self.parentheses -= 1;
+        self.colored(color, ")")
+    }
+}
+
+impl Display for Pretty<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        Printer {
+            f,
+            options: self.options,
+            parentheses: 0,
+        }
+        .node(self.expr, 0)
+    }
+}
+
+impl OpArgument {
+    /// Prints `self` for a terminal, as `options` say.
+    pub fn pretty(&self, options: PrettyOptions) -> Pretty<'_> {
+        Pretty {
+            expr: self,
+            options,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PrettyOptions;
+    use crate::parse::parse;
+
+    #[test]
+    fn test_pretty() {
+        let plain = PrettyOptions {
+            color: false,
+            max_depth: None,
+        };
+        for input in [
+            "sin(x + 1) * -(y - 2)^3",
+            "sqrt(a / (b + c))",
+            "x - (y - z)",
+        ] {
+            let expr = parse(input).unwrap();
+            assert_eq!(expr.pretty(plain).to_string(), expr.to_string());
+        }
+
+        let expr = parse("sin(x + y * exp(z)) + 2").unwrap();
+        let shallow = PrettyOptions {
+            max_depth: Some(2),
+            ..plain
+        };
+        assert_eq!(expr.pretty(shallow).to_string(), "sin(x+…)+2/1");
+
+        let expr = parse("(x + 1) * 2").unwrap();
+        assert_eq!(
+            expr.pretty(PrettyOptions::default()).to_string(),
+            "\x1b[35m(\x1b[0m\x1b[1;32mx\x1b[0m+\x1b[33m1/1\x1b[0m\x1b[35m)\x1b[0m*\x1b[33m2/1\x1b[0m"
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! This module records where derived expressions came from, to explain results.
+//!
+//! While recording is enabled on a thread, the passes that derive expressions (rule
+//! application, constant folding, differentiation, stabilization) log every step they take as
+//! a [`Derivation`] from an input subexpression to the subexpression that replaced it.
+//! [`explain`] then reconstructs, for any expression, the tree of steps that produced it and
+//! its parts. Steps are keyed by the structural hash of their result, so an expression is
+//! explained however it was later copied or shared. Recording is off by default and costs a
+//! thread-local check per step when off.
+
+use std::{cell::RefCell, fmt::Display};
+
+use ahash::{HashMap, HashSet};
+
+use crate::symbols::{
+    OpArgument,
+    OpArgumentKind::{Leaf, Op},
+};
+
+/// The kind of step that derived an expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// A rewrite rule, by name.
+    Rule(String),
+    /// Constant folding or another built-in simplification.
+    Fold,
+    /// Differentiation with respect to a variable.
+    Derivative(&'static str),
+    /// Rewriting for floating-point accuracy.
+    Stabilize,
+    /// Rewriting quotients into products with negative powers, or back.
+    Canonicalize,
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Step::Rule(name) => write!(f, "rule {}", name),
+            Step::Fold => f.write_str("fold"),
+            Step::Derivative(var) => write!(f, "d/d{}", var),
+            Step::Stabilize => f.write_str("stabilize"),
+            Step::Canonicalize => f.write_str("canonicalize"),
+        }
+    }
+}
+
+/// One recorded step: `step` turned `from` into `result`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Derivation {
+    pub step: Step,
+    pub from: OpArgument,
+    pub result: OpArgument,
+}
+
+thread_local! {
+    static LOG: RefCell<Option<HashMap<u64, Derivation>>> = const { RefCell::new(None) };
+}
+
+/// Starts recording derivations on this thread, keeping any already recorded.
+pub fn enable() {
+    LOG.with_borrow_mut(|log| {
+        log.get_or_insert_with(HashMap::default);
+    });
+}
+
+/// Stops recording on this thread and forgets every recorded derivation.
+pub fn disable() {
+    LOG.with_borrow_mut(|log| *log = None);
+}
+
+pub fn is_enabled() -> bool {
+    LOG.with_borrow(Option::is_some)
+}
+
+/// Logs that `step` turned `from` into `result`, if recording is enabled. The first derivation
+/// of an expression is kept, since later ones only rederive it.
+pub(crate) fn record(step: impl FnOnce() -> Step, from: &OpArgument, result: &OpArgument) {
+    LOG.with_borrow_mut(|log| {
+        let Some(log) = log else {
+            return;
+        };
+        if from != result {
+            log.entry(result.hash()).or_insert_with(|| Derivation {
+                step: step(),
+                from: from.clone(),
+                result: result.clone(),
+            });
+        }
+    });
+}
+
+/// How an expression came to be: the step that produced it, explained in turn, and the
+/// explanations of its derived parts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Explanation {
+    pub expr: OpArgument,
+    pub origin: Option<(Step, Box<Explanation>)>,
+    /// Explanations of the outermost subexpressions that were derived separately.
+    pub parts: Vec<Explanation>,
+}
+
+impl Explanation {
+    /// Whether nothing about the expression was derived.
+    pub fn is_given(&self) -> bool {
+        self.origin.is_none() && self.parts.is_empty()
+    }
+
+    fn fmt_indented(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        let indent = "  ".repeat(depth);
+        writeln!(f, "{}{}", indent, self.expr)?;
+        if let Some((step, from)) = &self.origin {
+            writeln!(f, "{}  by {} from", indent, step)?;
+            from.fmt_indented(f, depth + 2)?;
+        }
+        for part in &self.parts {
+            writeln!(f, "{}  where", indent)?;
+            part.fmt_indented(f, depth + 2)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+fn explain_in(
+    log: &HashMap<u64, Derivation>,
+    expr: &OpArgument,
+    seen: &mut HashSet<u64>,
+) -> Explanation {
+    let origin = log
+        .get(&expr.hash())
+        .filter(|_| seen.insert(expr.hash()))
+        .map(|derivation| {
+            let from = explain_in(log, &derivation.from, seen);
+            (derivation.step.clone(), Box::new(from))
+        });
+
+    let mut parts = Vec::new();
+    if origin.is_none() {
+        if let Op(op) = &expr.value {
+            for arg in &op.arguments {
+                let part = explain_in(log, arg, seen);
+                match (part.origin.is_some(), &arg.value) {
+                    (true, _) => parts.push(part),
+                    (false, Leaf(_)) => {}
+                    (false, Op(_)) => parts.extend(part.parts),
+                }
+            }
+        }
+    }
+
+    Explanation {
+        expr: expr.clone(),
+        origin,
+        parts,
+    }
+}
+
+/// Explains `expr` from the derivations recorded on this thread.
+pub fn explain(expr: &OpArgument) -> Explanation {
+    LOG.with_borrow(|log| match log {
+        Some(log) => explain_in(log, expr, &mut HashSet::default()),
+        None => Explanation {
+            expr: expr.clone(),
+            origin: None,
+            parts: Vec::new(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{disable, enable, explain, Step};
+    use crate::{
+        constants::Value,
+        rewrite::Rule,
+        symbols::{variable, OpArgument},
+        template::hole,
+    };
+
+    #[test]
+    fn test_explain() {
+        let x = variable("x");
+        let y = variable("y");
+        let a = hole("a");
+        let two = OpArgument::from(Value::integer(2));
+        let double = Rule::new("double", &a + &a, &two * &a).unwrap();
+        let square = Rule::new("square", &a * &a, a.pow(&two)).unwrap();
+
+        // Nothing is recorded unless enabled.
+        let first = (&x + &x).rewrite(std::slice::from_ref(&double));
+        assert!(explain(&first).is_given());
+
+        enable();
+        let expr = (&x + &x).exp() + (&y * &y).sin();
+        let rules = [double, square];
+        let result = expr.rewrite(&rules);
+        assert_eq!(result, (&two * &x).exp() + y.pow(&two).sin());
+
+        let explanation = explain(&result);
+        assert!(explanation.origin.is_none());
+        let steps = explanation
+            .parts
+            .iter()
+            .map(|part| {
+                let (step, from) = part.origin.as_ref().unwrap();
+                (part.expr.clone(), step.clone(), from.expr.clone())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            steps,
+            [
+                (&two * &x, Step::Rule("double".to_owned()), &x + &x),
+                (y.pow(&two), Step::Rule("square".to_owned()), &y * &y),
+            ]
+        );
+        assert!(explanation.to_string().contains("by rule square from"));
+
+        disable();
+        assert!(explain(&result).is_given());
+    }
+}
@@ -0,0 +1,250 @@
+//! This module describes quaternions of expressions, for modelling attitudes and rotations.
+//!
+//! A [`Quaternion`] `w + x·i + y·j + z·k` multiplies by the Hamilton product, which does not
+//! commute: `i·j = k` but `j·i = -k`. A unit quaternion `cos(θ/2) + sin(θ/2)·(aᵢ·i + aⱼ·j +
+//! aₖ·k)` represents the rotation by `θ` about the unit axis `a`, and [`Quaternion::rotate`]
+//! applies it to a vector by conjugation, `q·v·q*`.
+
+use std::{
+    fmt::Display,
+    ops::{Add, Mul},
+};
+
+use crate::{
+    constants::Value,
+    evaluation::{Bindings, EvaluationError},
+    matrix::Matrix,
+    series::{one, plus, times, zero},
+    symbols::{intern, variable, OpArgument},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quaternion {
+    pub w: OpArgument,
+    pub x: OpArgument,
+    pub y: OpArgument,
+    pub z: OpArgument,
+}
+
+fn minus(a: &OpArgument, b: &OpArgument) -> OpArgument {
+    match *b == zero() {
+        true => a.clone(),
+        false => plus(a, &(-b).fold()),
+    }
+}
+
+impl Quaternion {
+    pub fn new(w: OpArgument, x: OpArgument, y: OpArgument, z: OpArgument) -> Self {
+        Quaternion { w, x, y, z }
+    }
+
+    /// The quaternion of the variables `name_w`, `name_x`, `name_y` and `name_z`.
+    pub fn symbols(name: &str) -> Self {
+        let part = |suffix: &str| variable(intern(&format!("{}_{}", name, suffix)));
+        Quaternion::new(part("w"), part("x"), part("y"), part("z"))
+    }
+
+    /// The pure quaternion `x·i + y·j + z·k` of a vector.
+    pub fn pure([x, y, z]: [OpArgument; 3]) -> Self {
+        Quaternion::new(zero(), x, y, z)
+    }
+
+    /// The unit quaternion of the rotation by `angle` about `axis`, which must be a unit vector.
+    pub fn from_axis_angle(axis: [OpArgument; 3], angle: &OpArgument) -> Self {
+        let half = (angle / OpArgument::from(Value::integer(2))).fold();
+        let sin = half.sin();
+        let [x, y, z] = axis.map(|a| times(&sin, &a.fold()));
+        Quaternion::new(half.cos(), x, y, z)
+    }
+
+    /// The vector part `(x, y, z)`.
+    pub fn vector(&self) -> [OpArgument; 3] {
+        [self.x.clone(), self.y.clone(), self.z.clone()]
+    }
+
+    /// `w - x·i - y·j - z·k`.
+    pub fn conjugate(&self) -> Quaternion {
+        let negate = |a: &OpArgument| (-a).fold();
+        Quaternion::new(
+            self.w.clone(),
+            negate(&self.x),
+            negate(&self.y),
+            negate(&self.z),
+        )
+    }
+
+    /// `w² + x² + y² + z²`, which is `q·q*`.
+    pub fn norm_squared(&self) -> OpArgument {
+        [&self.w, &self.x, &self.y, &self.z]
+            .into_iter()
+            .fold(zero(), |sum, a| plus(&sum, &times(a, a)))
+    }
+
+    pub fn norm(&self) -> OpArgument {
+        self.norm_squared()
+            .pow(&Value::rational(1, 2).into())
+            .fold()
+    }
+
+    /// `q*/|q|²`, the inverse under multiplication.
+    pub fn inverse(&self) -> Quaternion {
+        let scale = (one() / self.norm_squared()).fold();
+        let conjugate = self.conjugate();
+        Quaternion::new(
+            times(&scale, &conjugate.w),
+            times(&scale, &conjugate.x),
+            times(&scale, &conjugate.y),
+            times(&scale, &conjugate.z),
+        )
+    }
+
+    /// Rotates `v` by the unit quaternion `self`, as the vector part of `q·v·q*`.
+    pub fn rotate(&self, v: [OpArgument; 3]) -> [OpArgument; 3] {
+        (&(self * &Quaternion::pure(v)) * &self.conjugate()).vector()
+    }
+
+    /// The rotation matrix of the unit quaternion `self`, whose product with a column vector is
+    /// [`Quaternion::rotate`].
+    pub fn to_rotation_matrix(&self) -> Matrix {
+        let unit = |k: usize| {
+            std::array::from_fn(|i| match i == k {
+                true => one(),
+                false => zero(),
+            })
+        };
+        let columns = [0, 1, 2].map(|k| self.rotate(unit(k)));
+        Matrix::from_fn(3, 3, |i, j| columns[j][i].clone())
+    }
+
+    /// Evaluates the parts, in the order `[w, x, y, z]`.
+    pub fn evaluate(&self, bindings: &Bindings) -> Result<[f64; 4], EvaluationError> {
+        Ok([
+            self.w.evaluate(bindings)?,
+            self.x.evaluate(bindings)?,
+            self.y.evaluate(bindings)?,
+            self.z.evaluate(bindings)?,
+        ])
+    }
+}
+
+impl Display for Quaternion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "({}) + ({})*i + ({})*j + ({})*k",
+            self.w, self.x, self.y, self.z
+        )
+    }
+}
+
+impl Add<&Quaternion> for &Quaternion {
+    type Output = Quaternion;
+
+    fn add(self, rhs: &Quaternion) -> Quaternion {
+        Quaternion::new(
+            plus(&self.w, &rhs.w),
+            plus(&self.x, &rhs.x),
+            plus(&self.y, &rhs.y),
+            plus(&self.z, &rhs.z),
+        )
+    }
+}
+
+impl Mul<&Quaternion> for &Quaternion {
+    type Output = Quaternion;
+
+    /// The Hamilton product, with `i² = j² = k² = ijk = -1`.
+    fn mul(self, rhs: &Quaternion) -> Quaternion {
+        let (a, b) = (self, rhs);
+        let sum = |terms: [(&OpArgument, &OpArgument, bool); 4]| {
+            terms.into_iter().fold(zero(), |sum, (p, q, positive)| {
+                let term = times(p, q);
+                match positive {
+                    true => plus(&sum, &term),
+                    false => minus(&sum, &term),
+                }
+            })
+        };
+        Quaternion::new(
+            sum([
+                (&a.w, &b.w, true),
+                (&a.x, &b.x, false),
+                (&a.y, &b.y, false),
+                (&a.z, &b.z, false),
+            ]),
+            sum([
+                (&a.w, &b.x, true),
+                (&a.x, &b.w, true),
+                (&a.y, &b.z, true),
+                (&a.z, &b.y, false),
+            ]),
+            sum([
+                (&a.w, &b.y, true),
+                (&a.x, &b.z, false),
+                (&a.y, &b.w, true),
+                (&a.z, &b.x, true),
+            ]),
+            sum([
+                (&a.w, &b.z, true),
+                (&a.x, &b.y, true),
+                (&a.y, &b.x, false),
+                (&a.z, &b.w, true),
+            ]),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Quaternion;
+    use crate::{evaluation::Bindings, parse::parse, symbols::OpArgument};
+
+    #[test]
+    fn test_quaternion() {
+        let p = |input: &str| parse(input).unwrap().fold();
+        let unit = |w: &str, x: &str, y: &str, z: &str| Quaternion::new(p(w), p(x), p(y), p(z));
+        let (i, j, k) = (
+            unit("0", "1", "0", "0"),
+            unit("0", "0", "1", "0"),
+            unit("0", "0", "0", "1"),
+        );
+        assert_eq!(&i * &j, k);
+        assert_eq!(&j * &i, unit("0", "0", "0", "-1"));
+        assert_eq!(&k * &k, unit("-1", "0", "0", "0"));
+
+        let q = Quaternion::symbols("q");
+        let r = Quaternion::symbols("r");
+        let bindings = Bindings::from_iter([
+            ("q_w", 0.5),
+            ("q_x", -1.25),
+            ("q_y", 2.0),
+            ("q_z", 0.75),
+            ("r_w", 1.5),
+            ("r_x", 0.25),
+            ("r_y", -0.5),
+            ("r_z", 1.0),
+            ("t", 0.6),
+        ]);
+        let value = |e: &OpArgument| e.evaluate(&bindings).unwrap();
+        // |qr| = |q|·|r|, and q·q⁻¹ = 1.
+        let norm = value(&(&q * &r).norm());
+        assert!((norm - value(&q.norm()) * value(&r.norm())).abs() < 1e-12);
+        let identity = (&q * &q.inverse()).evaluate(&bindings).unwrap();
+        assert!(identity
+            .iter()
+            .zip([1.0, 0.0, 0.0, 0.0])
+            .all(|(a, b)| (a - b).abs() < 1e-12));
+        assert_eq!(i.conjugate(), unit("0", "-1", "0", "0"));
+
+        // Rotating by t about z turns x towards y.
+        let rotation = Quaternion::from_axis_angle([p("0"), p("0"), p("1")], &p("t"));
+        let rotated = rotation.rotate([p("1"), p("0"), p("0")]).map(|e| value(&e));
+        let t = 0.6_f64;
+        assert!((rotated[0] - t.cos()).abs() < 1e-12);
+        assert!((rotated[1] - t.sin()).abs() < 1e-12);
+        assert!(rotated[2].abs() < 1e-12);
+        let matrix = rotation.to_rotation_matrix().evaluate(&bindings).unwrap();
+        // Column-major, so the first column is the image of x.
+        assert!((matrix[0] - rotated[0]).abs() < 1e-12 && (matrix[1] - rotated[1]).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,277 @@
+//! This module describes simplifying square roots of rationals into the forms written by hand.
+//!
+//! [`OpArgument::simplify_radicals`] works on the surds `a + b√c` with rational `a` and `b` and
+//! a squarefree integer `c`, which sums, products and quotients of rationals and their square
+//! roots reduce to as long as they share their `c`. Square factors come out of roots, so
+//! `√8 = 2√2` and `√(1/2) = √2/2`. Denominators are rationalized by multiplying with the
+//! conjugate, so `1/(1 + √2) = √2 - 1`. Square roots of surds are denested when
+//! `√(a + b√c) = √x ± √y` for rationals `x` and `y`, as in `√(3 + 2√2) = 1 + √2`.
+//!
+//! Integers are only factored when they are at most [`MAX_FACTORED`], and anything else is
+//! left as it is.
+
+use crate::{
+    constants::Value,
+    fold::Ratio,
+    provenance::{self, Step},
+    symbols::{OpArgument, OpArgumentKind::Op, OperationKind::*},
+};
+
+/// Integers are only split into a square and a squarefree part when they are at most this
+/// large, since every candidate factor is tried.
+pub const MAX_FACTORED: u128 = 1 << 40;
+
+/// `a + b√c`, with `c` a squarefree positive integer that is `1` whenever `b` is zero.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Surd {
+    a: Ratio,
+    b: Ratio,
+    c: i128,
+}
+
+impl Surd {
+    fn rational(a: Ratio) -> Self {
+        Surd {
+            a,
+            b: Ratio::ZERO,
+            c: 1,
+        }
+    }
+
+    /// The `c` shared by `self` and `other`, if they have one.
+    fn common(self, other: Surd) -> Option<i128> {
+        match (self.c, other.c) {
+            (1, c) | (c, 1) => Some(c),
+            (c, d) => (c == d).then_some(c),
+        }
+    }
+
+    fn normalized(self) -> Self {
+        match self.b == Ratio::ZERO {
+            true => Surd::rational(self.a),
+            false => self,
+        }
+    }
+
+    fn add(self, other: Surd) -> Option<Surd> {
+        Some(
+            Surd {
+                a: self.a.checked_add(other.a)?,
+                b: self.b.checked_add(other.b)?,
+                c: self.common(other)?,
+            }
+            .normalized(),
+        )
+    }
+
+    fn neg(self) -> Surd {
+        Surd {
+            a: -self.a,
+            b: -self.b,
+            c: self.c,
+        }
+    }
+
+    fn mul(self, other: Surd) -> Option<Surd> {
+        let c = self.common(other)?;
+        let bb = self
+            .b
+            .checked_mul(other.b)?
+            .checked_mul(Ratio::new(c, 1)?)?;
+        Some(
+            Surd {
+                a: self.a.checked_mul(other.a)?.checked_add(bb)?,
+                b: self
+                    .a
+                    .checked_mul(other.b)?
+                    .checked_add(self.b.checked_mul(other.a)?)?,
+                c,
+            }
+            .normalized(),
+        )
+    }
+
+    /// `1/self`, with the denominator rationalized: `1/(a + b√c) = (a - b√c)/(a² - b²c)`.
+    fn recip(self) -> Option<Surd> {
+        let conjugate = Surd {
+            a: self.a,
+            b: -self.b,
+            c: self.c,
+        };
+        let norm = self.mul(conjugate)?;
+        conjugate.mul(Surd::rational(norm.a.recip()?))
+    }
+
+    fn to_oparg(self) -> Option<OpArgument> {
+        if self.b == Ratio::ZERO {
+            return self.a.to_oparg();
+        }
+        let root = OpArgument::from(Value::integer(self.c as u64)).pow(&half());
+        let magnitude = match self.b.num.abs() == self.b.den {
+            true => root,
+            false => Ratio::new(self.b.num.abs(), self.b.den)?.to_oparg()? * root,
+        };
+        Some(match (self.a == Ratio::ZERO, self.b.num < 0) {
+            (true, false) => magnitude,
+            (true, true) => -magnitude,
+            (false, false) => self.a.to_oparg()? + magnitude,
+            (false, true) => self.a.to_oparg()? - magnitude,
+        })
+    }
+}
+
+fn half() -> OpArgument {
+    Value::rational(1, 2).into()
+}
+
+/// Splits `n` into `s²·t` with `t` squarefree, returning `(s, t)`.
+fn squarefree(mut n: u128) -> Option<(u128, u128)> {
+    if n > MAX_FACTORED {
+        return None;
+    }
+    let (mut s, mut t) = (1, 1);
+    let mut d = 2;
+    while d * d <= n {
+        while n.is_multiple_of(d * d) {
+            n /= d * d;
+            s *= d;
+        }
+        if n.is_multiple_of(d) {
+            n /= d;
+            t *= d;
+        }
+        d += 1;
+    }
+    Some((s, t * n))
+}
+
+/// `√r` for a nonnegative rational `r`: `√(p/q) = √(pq)/q`, with the square factors of `pq`
+/// taken out.
+fn square_root(r: Ratio) -> Option<Surd> {
+    if r.num < 0 {
+        return None;
+    }
+    let (s, t) = squarefree(r.num.checked_mul(r.den)? as u128)?;
+    let coefficient = Ratio::new(s as i128, r.den)?;
+    Some(match t {
+        1 => Surd::rational(coefficient),
+        t => Surd {
+            a: Ratio::ZERO,
+            b: coefficient,
+            c: t as i128,
+        }
+        .normalized(),
+    })
+}
+
+fn is_half(node: &OpArgument) -> bool {
+    Ratio::of(node) == Ratio::new(1, 2)
+}
+
+/// Reads the surd that `node`, whose arguments are already simplified, is equal to.
+fn surd(node: &OpArgument) -> Option<Surd> {
+    if let Some(r) = Ratio::of(node) {
+        return Some(Surd::rational(r));
+    }
+    let Op(op) = &node.value else {
+        return None;
+    };
+    let arg = |i: usize| surd(&op.arguments[i]);
+    match op.op {
+        Addition => arg(0)?.add(arg(1)?),
+        Subtraction => arg(0)?.add(arg(1)?.neg()),
+        Negation => Some(arg(0)?.neg()),
+        Multiplication => arg(0)?.mul(arg(1)?),
+        Division => arg(0)?.mul(arg(1)?.recip()?),
+        Pow if is_half(&op.arguments[1]) => square_root(Ratio::of(&op.arguments[0])?),
+        _ => None,
+    }
+}
+
+/// `√(a + b√c) = √x + sign(b)·√y`, with `x` and `y` the roots of `t² - at + b²c/4`, when
+/// they are rational and nonnegative.
+fn denest(s: Surd) -> Option<OpArgument> {
+    let two = Ratio::new(2, 1)?;
+    let square = s.a.checked_mul(s.a)?;
+    let cross = s.b.checked_mul(s.b)?.checked_mul(Ratio::new(s.c, 1)?)?;
+    let d = square_root(square.checked_add(-cross)?)?;
+    (d.b == Ratio::ZERO).then_some(())?;
+    let x = s.a.checked_add(d.a)?.checked_mul(two.recip()?)?;
+    let y = s.a.checked_add(-d.a)?.checked_mul(two.recip()?)?;
+    let (x, y) = (square_root(x)?, square_root(y)?);
+    let negative = s.b.num < 0;
+    match x.add(if negative { y.neg() } else { y }) {
+        Some(sum) => sum.to_oparg(),
+        None if negative => Some(x.to_oparg()? - y.to_oparg()?),
+        None => Some(x.to_oparg()? + y.to_oparg()?),
+    }
+}
+
+/// The simplified form of `node`, whose arguments are already simplified.
+fn simplify_root(node: &OpArgument) -> Option<OpArgument> {
+    if let Some(s) = surd(node) {
+        let simplified = s.to_oparg()?;
+        return (simplified != *node).then_some(simplified);
+    }
+    let Op(op) = &node.value else {
+        return None;
+    };
+    match op.op {
+        Pow if is_half(&op.arguments[1]) => denest(surd(&op.arguments[0])?),
+        // Rationalize the denominator of a quotient whose numerator is not a surd.
+        Division => {
+            let denominator = surd(&op.arguments[1])?;
+            (denominator.b != Ratio::ZERO).then_some(())?;
+            Some(&op.arguments[0] * &denominator.recip()?.to_oparg()?)
+        }
+        _ => None,
+    }
+}
+
+impl OpArgument {
+    /// Takes square factors out of square roots of rationals, rationalizes denominators and
+    /// denests square roots of surds, bottom up.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    pub fn simplify_radicals(&self) -> OpArgument {
+        self.map_bottom_up(|node, rebuilt| {
+            let result = simplify_root(&rebuilt).unwrap_or(rebuilt);
+            provenance::record(|| Step::Fold, node, &result);
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{evaluation::Bindings, parse::parse};
+
+    #[test]
+    fn test_simplify_radicals() {
+        let p = |input: &str| parse(input).unwrap();
+        let simplified = |input: &str| p(input).simplify_radicals();
+
+        assert_eq!(simplified("sqrt(8)"), p("2 * sqrt(2)").fold());
+        assert_eq!(simplified("sqrt(1/2)"), p("1/2 * sqrt(2)").fold());
+        assert_eq!(simplified("sqrt(36/25)"), p("6/5").fold());
+        assert_eq!(simplified("sqrt(12) - sqrt(3)"), p("sqrt(3)"));
+        assert_eq!(simplified("sqrt(3 + 2*sqrt(2))"), p("1 + sqrt(2)"));
+        assert_eq!(simplified("sqrt(5 - 2*sqrt(6))"), p("sqrt(3) - sqrt(2)"));
+        assert_eq!(simplified("1 / (1 + sqrt(2))"), p("-1 + sqrt(2)").fold());
+        assert_eq!(simplified("x / sqrt(3)"), p("x * (1/3 * sqrt(3))").fold());
+        // Not every nested root denests, and √2 + √3 is not a single surd.
+        assert_eq!(
+            simplified("sqrt(1 + sqrt(2))"),
+            p("sqrt(1 + sqrt(2))").fold()
+        );
+        assert_eq!(
+            simplified("sqrt(2) + sqrt(3)"),
+            p("sqrt(2) + sqrt(3)").fold()
+        );
+
+        let expr = p("(sqrt(50) + x) / (3 - sqrt(8)) + sqrt(7 + 4*sqrt(3))");
+        let at = Bindings::from_iter([("x", 0.6)]);
+        let expected = expr.evaluate(&at).unwrap();
+        let got = expr.simplify_radicals().evaluate(&at).unwrap();
+        assert!((got - expected).abs() < 1e-12 * expected.abs());
+    }
+}
@@ -0,0 +1,312 @@
+//! This module describes the normal form of rational functions.
+//!
+//! [`together`] puts a sum of fractions over a common denominator, reusing a denominator that
+//! the terms already share, and leaves every other node alone apart from its arguments.
+//! [`ratsimp`] goes further for rational functions of one variable with rational coefficients:
+//! it expands the numerator and denominator that [`together`] finds, divides out their greatest
+//! common divisor and makes the denominator monic, so that two rational functions are equal
+//! exactly when their normal forms are.
+
+use std::fmt::Display;
+
+use smallvec::smallvec;
+
+use crate::{
+    collect::CollectError,
+    constants::Value,
+    fold::Ratio,
+    series::{one, zero},
+    symbols::{
+        variable, OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        Operation,
+        OperationKind::*,
+        StackVec,
+    },
+};
+
+/// The reasons an expression can fail to be put in rational normal form.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RationalError {
+    /// The subexpression is not a rational function of the variable, such as `sin(x)`.
+    NotRational(OpArgument),
+    /// The subexpression is a coefficient that is not a rational number.
+    NotRationalCoefficient(OpArgument),
+    /// A coefficient grew too large to represent exactly.
+    Overflow,
+}
+
+impl Display for RationalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RationalError::NotRational(expr) => write!(f, "{} is not a rational function", expr),
+            RationalError::NotRationalCoefficient(expr) => {
+                write!(f, "{} is not a rational coefficient", expr)
+            }
+            RationalError::Overflow => f.write_str("a coefficient overflowed"),
+        }
+    }
+}
+
+impl std::error::Error for RationalError {}
+
+impl From<CollectError> for RationalError {
+    fn from(err: CollectError) -> Self {
+        match err {
+            CollectError::NotPolynomial(expr) | CollectError::NonCommutative(expr) => {
+                RationalError::NotRational(expr)
+            }
+            CollectError::Interrupted(_) => {
+                unreachable!("Oops, rational normal forms collect without a budget")
+            }
+        }
+    }
+}
+
+/// A numerator and a denominator, which is `None` when it is `1`.
+type Fraction = (OpArgument, Option<OpArgument>);
+
+fn product(a: &OpArgument, b: &Option<OpArgument>) -> OpArgument {
+    match b {
+        Some(b) if *a == one() => b.clone(),
+        Some(b) => a * b,
+        None => a.clone(),
+    }
+}
+
+fn denominators(b: &Option<OpArgument>, d: &Option<OpArgument>) -> Option<OpArgument> {
+    match (b, d) {
+        (Some(b), Some(d)) => Some(b * d),
+        (Some(x), None) | (None, Some(x)) => Some(x.clone()),
+        (None, None) => None,
+    }
+}
+
+fn fraction(node: &OpArgument) -> Fraction {
+    let Op(op) = &node.value else {
+        return (node.clone(), None);
+    };
+    let arg = |i: usize| fraction(&op.arguments[i]);
+    match op.op {
+        Addition | Subtraction => {
+            let ((a, b), (c, d)) = (arg(0), arg(1));
+            let combine = |x: &OpArgument, y: &OpArgument| match op.op {
+                Addition => x + y,
+                _ => x - y,
+            };
+            match b == d {
+                true => (combine(&a, &c), b),
+                false => (
+                    combine(&product(&a, &d), &product(&c, &b)),
+                    denominators(&b, &d),
+                ),
+            }
+        }
+        Multiplication => {
+            let ((a, b), (c, d)) = (arg(0), arg(1));
+            (&a * &c, denominators(&b, &d))
+        }
+        Division => {
+            let ((a, b), (c, d)) = (arg(0), arg(1));
+            (product(&a, &d), Some(product(&c, &b)))
+        }
+        Negation => {
+            let (a, b) = arg(0);
+            (-a, b)
+        }
+        Pow => match Ratio::of(&op.arguments[1].fold()).filter(|k| k.den == 1) {
+            Some(k) => {
+                let (a, b) = arg(0);
+                let power = |x: &OpArgument| match k.num.unsigned_abs() {
+                    1 => x.clone(),
+                    n => x.pow(&Value::integer(n as u64).into()),
+                };
+                match (k.num < 0, b) {
+                    (false, b) => (power(&a), b.as_ref().map(power)),
+                    (true, Some(b)) => (power(&b), Some(power(&a))),
+                    (true, None) => (one(), Some(power(&a))),
+                }
+            }
+            None => (rebuilt(node), None),
+        },
+        _ => (rebuilt(node), None),
+    }
+}
+
+/// `node` with [`together`] applied to its arguments.
+fn rebuilt(node: &OpArgument) -> OpArgument {
+    match &node.value {
+        Op(op) => {
+            let arguments = op.arguments.iter().map(together).collect::<StackVec<_>>();
+            Operation::new(op.op, arguments).into()
+        }
+        Leaf(_) => node.clone(),
+    }
+}
+
+/// `expr` as a single quotient, with each sum of fractions over a common denominator.
+pub fn together(expr: &OpArgument) -> OpArgument {
+    match fraction(expr) {
+        (numerator, None) => numerator,
+        (numerator, Some(denominator)) => numerator / denominator,
+    }
+}
+
+/// Removes the zero coefficients of the highest degrees.
+pub(crate) fn trim(mut p: Vec<Ratio>) -> Vec<Ratio> {
+    while p.last() == Some(&Ratio::ZERO) {
+        p.pop();
+    }
+    p
+}
+
+/// The quotient and remainder of dividing `a` by the nonzero `b`.
+pub(crate) fn div_rem(a: &[Ratio], b: &[Ratio]) -> Option<(Vec<Ratio>, Vec<Ratio>)> {
+    let lead = b.last()?.recip()?;
+    let mut remainder = a.to_vec();
+    let mut quotient = vec![Ratio::ZERO; a.len().saturating_sub(b.len()) + 1];
+    while remainder.len() >= b.len() {
+        let shift = remainder.len() - b.len();
+        let factor = remainder.last()?.checked_mul(lead)?;
+        quotient[shift] = factor;
+        for (i, &c) in b.iter().enumerate() {
+            remainder[shift + i] = remainder[shift + i].checked_add(-c.checked_mul(factor)?)?;
+        }
+        remainder.pop();
+        remainder = trim(remainder);
+    }
+    Some((trim(quotient), remainder))
+}
+
+/// The monic greatest common divisor of `a` and `b`, not both zero.
+pub(crate) fn gcd(a: &[Ratio], b: &[Ratio]) -> Option<Vec<Ratio>> {
+    let (mut a, mut b) = (a.to_vec(), b.to_vec());
+    while !b.is_empty() {
+        let (_, remainder) = div_rem(&a, &b)?;
+        (a, b) = (b, remainder);
+    }
+    let lead = a.last()?.recip()?;
+    a.iter().map(|c| c.checked_mul(lead)).collect()
+}
+
+/// The coefficients of `expr` as a polynomial in `var`, lowest degree first.
+pub(crate) fn coefficients(
+    expr: &OpArgument,
+    var: &'static str,
+) -> Result<Vec<Ratio>, RationalError> {
+    let collected = expr.collect(&[var])?;
+    let mut p = vec![Ratio::ZERO; collected.degree().map_or(0, |d| d as usize + 1)];
+    for (monomial, c) in collected.terms() {
+        p[monomial[0] as usize] =
+            Ratio::of(c).ok_or_else(|| RationalError::NotRationalCoefficient(c.clone()))?;
+    }
+    Ok(p)
+}
+
+/// The polynomial in `var` with `coefficients`, highest degree first.
+pub(crate) fn polynomial(
+    coefficients: &[Ratio],
+    var: &'static str,
+) -> Result<OpArgument, RationalError> {
+    let mut terms = Vec::new();
+    for (k, c) in coefficients.iter().enumerate().rev() {
+        if *c == Ratio::ZERO {
+            continue;
+        }
+        let power = match k {
+            0 => None,
+            1 => Some(variable(var)),
+            k => Some(variable(var).pow(&Value::integer(k as u64).into())),
+        };
+        let c = c.to_oparg().ok_or(RationalError::Overflow)?;
+        terms.push(match power {
+            None => c,
+            Some(power) if c == one() => power,
+            Some(power) => Operation::new(Multiplication, smallvec![c, power]).into(),
+        });
+    }
+    Ok(terms.into_iter().reduce(|a, b| a + b).unwrap_or_else(zero))
+}
+
+/// The numerator and denominator of the rational function `expr` of `var`, with no common
+/// factor and a monic denominator.
+pub fn ratsimp(
+    expr: &OpArgument,
+    var: &'static str,
+) -> Result<(OpArgument, OpArgument), RationalError> {
+    let (numerator, denominator) = fraction(expr);
+    let numerator = trim(coefficients(&numerator, var)?);
+    let denominator = match denominator {
+        Some(denominator) => trim(coefficients(&denominator, var)?),
+        None => vec![Ratio::ONE],
+    };
+    if denominator.is_empty() {
+        return Err(RationalError::NotRational(expr.clone()));
+    }
+    if numerator.is_empty() {
+        return Ok((zero(), one()));
+    }
+
+    let common = gcd(&numerator, &denominator).ok_or(RationalError::Overflow)?;
+    let divide = |p: &[Ratio]| div_rem(p, &common).map(|(q, _)| q);
+    let (numerator, denominator) = divide(&numerator)
+        .zip(divide(&denominator))
+        .ok_or(RationalError::Overflow)?;
+    let lead = denominator.last().and_then(|c| c.recip());
+    let monic = |p: &[Ratio]| {
+        p.iter()
+            .map(|c| c.checked_mul(lead?))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(RationalError::Overflow)
+    };
+    Ok((
+        polynomial(&monic(&numerator)?, var)?,
+        polynomial(&monic(&denominator)?, var)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ratsimp, together, RationalError};
+    use crate::{evaluation::Bindings, parse::parse};
+
+    #[test]
+    fn test_rational_normal_form() {
+        let p = |input: &str| parse(input).unwrap();
+
+        assert_eq!(together(&p("a/b + c/b")), p("(a + c) / b"));
+        assert_eq!(together(&p("a/b - c/d")), p("(a*d - c*b) / (b*d)"));
+        assert_eq!(together(&p("(a/x)^(-2) * y")), p("x^2 * y / a^2"));
+        assert_eq!(together(&p("sin(1/x + 1)")), p("sin((1 + x) / x)"));
+
+        let expr = p("1/(x - 1) - 1/(x + 1) + x/(x^2 - 1)");
+        let combined = together(&expr);
+        let at = Bindings::from_iter([("x", 0.37)]);
+        let expected = expr.evaluate(&at).unwrap();
+        assert!((combined.evaluate(&at).unwrap() - expected).abs() < 1e-12);
+        let (numerator, denominator) = ratsimp(&expr, "x").unwrap();
+        assert_eq!(numerator, p("x + 2").fold());
+        assert_eq!(denominator, p("x^2 + -1").fold());
+
+        // Common factors cancel, and the denominator is monic.
+        let (numerator, denominator) = ratsimp(&p("(2*x^2 - 2) / (4*x + 4)"), "x").unwrap();
+        assert_eq!(
+            (numerator, denominator),
+            (p("1/2*x + -1/2").fold(), p("1").fold())
+        );
+        assert_eq!(ratsimp(&p("(x - x) / x"), "x").unwrap().0, p("0").fold());
+
+        assert_eq!(
+            ratsimp(&p("sin(x) / x"), "x"),
+            Err(RationalError::NotRational(p("sin(x)")))
+        );
+        assert_eq!(
+            ratsimp(&p("a / x"), "x"),
+            Err(RationalError::NotRationalCoefficient(p("a")))
+        );
+        assert_eq!(
+            ratsimp(&p("x / (x - x)"), "x"),
+            Err(RationalError::NotRational(p("x / (x - x)")))
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! This module describes recognizing floats as the closed forms they were computed from.
+//!
+//! [`recognize`] tries the forms `r`, `r·π`, `r·e` and `√r` for small rationals `r`, finding
+//! each `r` with the continued fraction of `x`, `x/π`, `x/e` or `x²`. That is an integer
+//! relation between `x` and the constant, which is what more general inverse symbolic
+//! calculators search for among many constants at once. When several forms fit, the one with
+//! the smallest numerator and denominator wins, so `0.5` is `1/2` rather than `√(1/4)`.
+
+use std::f64::consts::{E, PI};
+
+use crate::{constants::Value, evaluation::Bindings, symbols::OpArgument};
+
+/// The rationals that closed forms are built from have denominators of at most this.
+pub const MAX_DENOMINATOR: u64 = 1000;
+
+/// The rational `r` within `tolerance` of `x`, with its size `num + den`.
+fn rational(x: f64, tolerance: f64) -> Option<(OpArgument, u64)> {
+    match Value::snap(x, MAX_DENOMINATOR, tolerance)? {
+        Value::Rational(num, den) => Some((Value::Rational(num, den).into(), num + den.get())),
+        _ => None,
+    }
+}
+
+/// A simple closed form within `tolerance` of `x`, if there is one: a rational, a rational
+/// multiple of `π` or `e`, or the square root of a rational with its square factors taken out.
+pub fn recognize(x: f64, tolerance: f64) -> Option<OpArgument> {
+    if !x.is_finite() {
+        return None;
+    }
+    let magnitude = x.abs();
+
+    let mut candidates = Vec::new();
+    candidates.extend(rational(magnitude, tolerance));
+    for (constant, scale) in [(Value::Pi, PI), (Value::E, E)] {
+        if let Some((r, size)) = rational(magnitude / scale, tolerance / scale) {
+            let multiple = match size {
+                // 0 is already a rational, and 1·π is π.
+                1 => continue,
+                2 => constant.into(),
+                _ => r * OpArgument::from(constant),
+            };
+            candidates.push((multiple, size));
+        }
+    }
+    // √r is within `tolerance` of `x` when `r` is within about `2x·tolerance` of `x²`.
+    if let Some((r, size)) = rational(magnitude * magnitude, 2.0 * magnitude * tolerance) {
+        let root = r.pow(&Value::rational(1, 2).into()).simplify_radicals();
+        candidates.push((root, size));
+    }
+
+    let bindings = Bindings::default();
+    let (best, _) = candidates
+        .into_iter()
+        .filter(|(candidate, _)| {
+            candidate
+                .evaluate(&bindings)
+                .is_ok_and(|value| (value - magnitude).abs() <= tolerance)
+        })
+        .min_by_key(|(_, size)| *size)?;
+    Some(if x < 0.0 { -best } else { best }.fold())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::recognize;
+    use crate::parse::parse;
+
+    #[test]
+    fn test_recognize() {
+        let p = |input: &str| Some(parse(input).unwrap().fold());
+        let tolerance = 1e-9;
+
+        assert_eq!(recognize(0.75, tolerance), p("3/4"));
+        assert_eq!(recognize(-0.1428571428571, tolerance), p("-1/7"));
+        assert_eq!(recognize(0.0, tolerance), p("0"));
+        assert_eq!(recognize(std::f64::consts::PI, tolerance), p("pi"));
+        assert_eq!(
+            recognize(std::f64::consts::FRAC_PI_3 * 2.0, tolerance),
+            p("2/3 * pi")
+        );
+        assert_eq!(
+            recognize(-std::f64::consts::E / 2.0, tolerance),
+            p("-(1/2 * e)")
+        );
+        assert_eq!(recognize(2f64.sqrt(), tolerance), p("sqrt(2)"));
+        assert_eq!(recognize(1.5 * 2f64.sqrt(), tolerance), p("3/2 * sqrt(2)"));
+
+        // Nothing simple is that close to 0.1234567, or anything at all to ∞.
+        assert_eq!(recognize(0.1234567, tolerance), None);
+        assert_eq!(recognize(f64::INFINITY, tolerance), None);
+        // With a loose tolerance, 355/113 passes for π, and π is simpler.
+        assert_eq!(recognize(355.0 / 113.0, 1e-6), p("pi"));
+    }
+}
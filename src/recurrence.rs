@@ -0,0 +1,364 @@
+//! This module describes linear recurrences with constant coefficients, and how to solve them.
+//!
+//! A [`Recurrence`] defines `a(n) = c₁·a(n - 1) + … + c_d·a(n - d)` from `d` initial terms.
+//! [`solve_recurrence`] finds its closed form with the characteristic polynomial: every root
+//! `r` of multiplicity `m` contributes `nʲ·rⁿ` for `j < m`, every conjugate pair `ρe^(±iθ)`
+//! contributes `nʲ·ρⁿ·cos(nθ)` and `nʲ·ρⁿ·sin(nθ)`, and the initial terms fix the weights.
+
+use std::fmt::Display;
+
+use crate::{
+    constants::Value,
+    fold::Ratio,
+    roots::{roots, Root},
+    series::{one, zero},
+    symbols::{variable, OpArgument},
+};
+
+/// The reasons a recurrence can fail to have a closed form.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecurrenceError {
+    /// The coefficient at this position is not a rational constant.
+    IrrationalCoefficient(usize),
+    /// The characteristic polynomial has roots with no closed form.
+    UnsolvableCharacteristic,
+}
+
+impl Display for RecurrenceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecurrenceError::IrrationalCoefficient(i) => {
+                write!(f, "coefficient {} is not a rational constant", i)
+            }
+            RecurrenceError::UnsolvableCharacteristic => {
+                f.write_str("the characteristic polynomial has no closed-form roots")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RecurrenceError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recurrence {
+    coefficients: Vec<OpArgument>,
+    initial: Vec<OpArgument>,
+}
+
+impl Recurrence {
+    /// The recurrence `a(n) = Σ coefficients[i]·a(n - 1 - i)`, starting from `a(0), a(1), …`
+    /// given by `initial`.
+    pub fn new(coefficients: Vec<OpArgument>, initial: Vec<OpArgument>) -> Self {
+        assert_eq!(
+            coefficients.len(),
+            initial.len(),
+            "Hold on, a recurrence of order {} needs exactly that many initial terms",
+            coefficients.len()
+        );
+        Recurrence {
+            coefficients,
+            initial,
+        }
+    }
+
+    pub fn coefficients(&self) -> &[OpArgument] {
+        &self.coefficients
+    }
+
+    pub fn initial(&self) -> &[OpArgument] {
+        &self.initial
+    }
+
+    pub fn order(&self) -> usize {
+        self.coefficients.len()
+    }
+
+    /// The first `count` terms, folded. A recurrence of order zero is zero from the start.
+    pub fn terms(&self, count: usize) -> Vec<OpArgument> {
+        let mut terms = self
+            .initial
+            .iter()
+            .map(OpArgument::fold)
+            .collect::<Vec<_>>();
+        while terms.len() < count {
+            let n = terms.len();
+            let next = self
+                .coefficients
+                .iter()
+                .zip(terms[..n].iter().rev())
+                .map(|(c, a)| c * a)
+                .reduce(|acc, term| acc + term)
+                .unwrap_or_else(zero);
+            terms.push(next.fold());
+        }
+        terms.truncate(count);
+        terms
+    }
+}
+
+/// The determinant of a square matrix, by fraction-free (Bareiss) elimination in `O(d³)` steps.
+///
+/// Every step divides exactly by the previous pivot, so rational entries stay rational without
+/// growing out of hand. Pivots that fold to nonzero constants are preferred; where a symbolic
+/// pivot vanishes the result is undefined, even if the determinant itself isn't.
+pub(crate) fn determinant(matrix: &[Vec<OpArgument>]) -> OpArgument {
+    let size = matrix.len();
+    let mut rows = matrix
+        .iter()
+        .map(|row| row.iter().map(OpArgument::fold).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut negated = false;
+    for k in 0..size {
+        let constant = |i: &usize| Ratio::of(&rows[*i][k]).is_some_and(|r| r != Ratio::ZERO);
+        let pivot = (k..size)
+            .find(constant)
+            .or_else(|| (k..size).find(|&i| rows[i][k] != zero()));
+        let Some(pivot) = pivot else {
+            return zero();
+        };
+        if pivot != k {
+            rows.swap(k, pivot);
+            negated = !negated;
+        }
+        let previous = (k > 0).then(|| rows[k - 1][k - 1].clone());
+        for i in k + 1..size {
+            for j in k + 1..size {
+                let cross = &rows[k][k] * &rows[i][j] - &rows[i][k] * &rows[k][j];
+                rows[i][j] = match &previous {
+                    Some(previous) => cross / previous,
+                    None => cross,
+                }
+                .fold();
+            }
+        }
+    }
+    let determinant = rows.last().map_or_else(one, |row| row[size - 1].clone());
+    if negated {
+        (-determinant).fold()
+    } else {
+        determinant
+    }
+}
+
+/// A solution of a recurrence: `nʲ·rⁿ`, or `nʲ·ρⁿ·cos(nθ)` or `nʲ·ρⁿ·sin(nθ)` for a pair of
+/// conjugate roots `ρe^(±iθ)`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Basis {
+    Power {
+        root: OpArgument,
+        degree: usize,
+    },
+    Cosine {
+        modulus: OpArgument,
+        angle: OpArgument,
+        degree: usize,
+    },
+    Sine {
+        modulus: OpArgument,
+        angle: OpArgument,
+        degree: usize,
+    },
+}
+
+impl Basis {
+    pub(crate) fn degree(&self) -> usize {
+        match self {
+            Basis::Power { degree, .. }
+            | Basis::Cosine { degree, .. }
+            | Basis::Sine { degree, .. } => *degree,
+        }
+    }
+
+    /// The solution at `n`.
+    fn at(&self, n: &OpArgument) -> OpArgument {
+        let f = match self {
+            Basis::Power { root, .. } => root.pow(n),
+            Basis::Cosine { modulus, angle, .. } => modulus.pow(n) * (n * angle).cos(),
+            Basis::Sine { modulus, angle, .. } => modulus.pow(n) * (n * angle).sin(),
+        };
+        match self.degree() {
+            0 => f,
+            1 => n * f,
+            j => n.pow(&Value::integer(j as u64).into()) * f,
+        }
+    }
+}
+
+/// Writes the solution of the recurrence as weighted [`Basis`] solutions.
+pub(crate) fn solution(
+    recurrence: &Recurrence,
+) -> Result<Vec<(OpArgument, Basis)>, RecurrenceError> {
+    let d = recurrence.order();
+    if d == 0 {
+        return Ok(Vec::new());
+    }
+
+    // The characteristic polynomial xᵈ - c₁xᵈ⁻¹ - … - c_d, lowest degree first.
+    let mut characteristic = recurrence
+        .coefficients
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            Ratio::of(&c.fold())
+                .map(|c| -c)
+                .ok_or(RecurrenceError::IrrationalCoefficient(i))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    characteristic.reverse();
+    characteristic.push(Ratio::ONE);
+    let roots = roots(&characteristic).ok_or(RecurrenceError::UnsolvableCharacteristic)?;
+
+    let mut basis = Vec::with_capacity(d);
+    for root in roots {
+        match root {
+            Root::Real {
+                value,
+                multiplicity,
+            } => {
+                basis.extend((0..multiplicity).map(|degree| Basis::Power {
+                    root: value.clone(),
+                    degree,
+                }));
+            }
+            Root::Conjugate {
+                modulus,
+                angle,
+                multiplicity,
+            } => {
+                for degree in 0..multiplicity {
+                    basis.push(Basis::Cosine {
+                        modulus: modulus.clone(),
+                        angle: angle.clone(),
+                        degree,
+                    });
+                    basis.push(Basis::Sine {
+                        modulus: modulus.clone(),
+                        angle: angle.clone(),
+                        degree,
+                    });
+                }
+            }
+        }
+    }
+
+    // Fix the weights from the initial terms by Cramer's rule.
+    let matrix = (0..d)
+        .map(|i| {
+            let n = OpArgument::from(Value::integer(i as u64));
+            basis.iter().map(|f| f.at(&n).fold()).collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let denominator = determinant(&matrix);
+    Ok(basis
+        .into_iter()
+        .enumerate()
+        .map(|(j, f)| {
+            let mut replaced = matrix.clone();
+            for (row, a) in replaced.iter_mut().zip(&recurrence.initial) {
+                row[j] = a.clone();
+            }
+            ((determinant(&replaced) / &denominator).fold(), f)
+        })
+        .collect())
+}
+
+/// Solves the recurrence in closed form, as an expression in `index`.
+pub fn solve_recurrence(
+    recurrence: &Recurrence,
+    index: &'static str,
+) -> Result<OpArgument, RecurrenceError> {
+    let n = variable(index);
+    Ok(solution(recurrence)?
+        .into_iter()
+        .map(|(weight, f)| weight * f.at(&n))
+        .reduce(|acc, term| acc + term)
+        .unwrap_or_else(|| Value::integer(0).into())
+        .fold())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{determinant, solve_recurrence, Recurrence, RecurrenceError};
+    use crate::{evaluation::Bindings, parse::parse};
+
+    #[test]
+    fn test_solve_recurrence() {
+        let p = |input: &str| parse(input).unwrap();
+        let recurrence = |coefficients: &[&str], initial: &[&str]| {
+            Recurrence::new(
+                coefficients.iter().map(|c| p(c)).collect(),
+                initial.iter().map(|a| p(a)).collect(),
+            )
+        };
+        let check = |recurrence: &Recurrence| {
+            let closed = solve_recurrence(recurrence, "n").unwrap();
+            for (n, term) in recurrence.terms(12).iter().enumerate() {
+                let expected = term.evaluate(&Default::default()).unwrap();
+                let actual = closed
+                    .evaluate(&Bindings::from_iter([("n", n as f64)]))
+                    .unwrap();
+                assert!(
+                    (actual - expected).abs() <= 1e-9 * expected.abs().max(1.0),
+                    "{} gave {} at n = {}, not {}",
+                    closed,
+                    actual,
+                    n,
+                    expected
+                );
+            }
+        };
+
+        // Fibonacci, solved by Binet's formula.
+        let fibonacci = recurrence(&["1", "1"], &["0", "1"]);
+        assert_eq!(fibonacci.terms(8)[7], p("13").fold());
+        check(&fibonacci);
+        // A repeated root: (n + 1)·2ⁿ.
+        check(&recurrence(&["4", "-4"], &["1", "4"]));
+        // Three rational roots: 1, 2 and 3.
+        check(&recurrence(&["6", "-11", "6"], &["0", "1", "5"]));
+        // Complex roots ±i, so the terms cycle through 1, 0, -1, 0.
+        check(&recurrence(&["0", "-1"], &["1", "0"]));
+        // Complex roots 1 ± i.
+        check(&recurrence(&["2", "-2"], &["1", "3/2"]));
+
+        assert_eq!(
+            solve_recurrence(&recurrence(&["x", "1"], &["0", "1"]), "n"),
+            Err(RecurrenceError::IrrationalCoefficient(0))
+        );
+        assert_eq!(
+            solve_recurrence(
+                &recurrence(&["0", "0", "-1", "-1"], &["0", "0", "0", "1"]),
+                "n"
+            ),
+            Err(RecurrenceError::UnsolvableCharacteristic)
+        );
+    }
+
+    #[test]
+    fn test_determinant() {
+        let p = |input: &str| parse(input).unwrap();
+
+        // A Vandermonde matrix on 1, …, 8: the product of all differences j - i.
+        let vandermonde = (1..=8i32)
+            .map(|x| (0..8).map(|k| p(&x.pow(k).to_string())).collect())
+            .collect::<Vec<_>>();
+        let expected = (1..=8i128)
+            .flat_map(|j| (1..j).map(move |i| j - i))
+            .product::<i128>();
+        assert_eq!(determinant(&vandermonde), p(&expected.to_string()).fold());
+
+        // The first pivot vanishes, so the rows have to be swapped.
+        let swapped =
+            [["0", "2", "1"], ["1", "1", "1"], ["3", "0", "1"]].map(|row| row.map(p).to_vec());
+        assert_eq!(determinant(&swapped), p("1").fold());
+        let singular = [["1", "2"], ["2", "4"]].map(|row| row.map(p).to_vec());
+        assert_eq!(determinant(&singular), p("0").fold());
+
+        let symbolic = [["a", "b"], ["c", "d"]].map(|row| row.map(p).to_vec());
+        let bindings = Bindings::from_iter([("a", 2.0), ("b", 3.0), ("c", 5.0), ("d", 7.0)]);
+        assert_eq!(determinant(&symbolic).evaluate(&bindings).unwrap(), -1.0);
+
+        let empty = Recurrence::new(vec![], vec![]);
+        assert_eq!(empty.terms(3), vec![p("0").fold(); 3]);
+    }
+}
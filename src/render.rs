@@ -0,0 +1,709 @@
+//! This module describes how to typeset our computational graph as an image.
+//!
+//! Expressions are laid out with a small box model (every box has a width and extends above
+//! and below a baseline) and emitted as SVG, so no TeX installation or font files are needed.
+//! [`to_png`] rasterizes the same layout with a built-in bitmap font instead, for apps and bots
+//! that can only show images.
+
+use std::{cmp::Ordering, fmt::Write};
+
+use crate::{
+    constants::Value,
+    operation_properties::Associativity,
+    symbols::{
+        base_name, OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        Operation,
+        OperationKind::{self, *},
+    },
+};
+
+/// Average advance of a glyph, as a fraction of the font size.
+const GLYPH_WIDTH: f64 = 0.6;
+/// Height of the fraction bar above the baseline, as a fraction of the font size.
+const MATH_AXIS: f64 = 0.3;
+/// Scale applied to the font size of exponents.
+const SCRIPT_SCALE: f64 = 0.7;
+/// How far italic glyphs lean, as a horizontal offset per unit of height.
+const ITALIC_SLANT: f64 = 0.2;
+/// Samples per pixel along each axis when rasterizing, which antialiases the edges.
+const SUPERSAMPLING: usize = 4;
+
+/// The glyphs of printable ASCII, from `' '` to `'~'`, as rows of five pixels with the
+/// leftmost in the highest bit. Seven rows stand on the baseline and two hang below it.
+#[rustfmt::skip]
+const ASCII_GLYPHS: [[u8; 9]; 95] = [
+    [0, 0, 0, 0, 0, 0, 0, 0, 0], // ' '
+    [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0, 0b00100, 0, 0], // '!'
+    [0b01010, 0b01010, 0b01010, 0, 0, 0, 0, 0, 0], // '"'
+    [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010, 0, 0], // '#'
+    [0b00100, 0b01111, 0b10100, 0b01110, 0b00101, 0b11110, 0b00100, 0, 0], // '$'
+    [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011, 0, 0], // '%'
+    [0b01100, 0b10010, 0b10100, 0b01000, 0b10101, 0b10010, 0b01101, 0, 0], // '&'
+    [0b00100, 0b00100, 0, 0, 0, 0, 0, 0, 0], // "'"
+    [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010, 0, 0], // '('
+    [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000, 0, 0], // ')'
+    [0, 0b00100, 0b10101, 0b01110, 0b10101, 0b00100, 0, 0, 0], // '*'
+    [0, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0, 0, 0], // '+'
+    [0, 0, 0, 0, 0, 0b00110, 0b00110, 0b00100, 0b01000], // ','
+    [0, 0, 0, 0b11111, 0, 0, 0, 0, 0], // '-'
+    [0, 0, 0, 0, 0, 0b01100, 0b01100, 0, 0], // '.'
+    [0, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0, 0, 0], // '/'
+    [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110, 0, 0], // '0'
+    [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110, 0, 0], // '1'
+    [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111, 0, 0], // '2'
+    [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110, 0, 0], // '3'
+    [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010, 0, 0], // '4'
+    [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110, 0, 0], // '5'
+    [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110, 0, 0], // '6'
+    [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0, 0], // '7'
+    [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110, 0, 0], // '8'
+    [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100, 0, 0], // '9'
+    [0, 0b01100, 0b01100, 0, 0b01100, 0b01100, 0, 0, 0], // ':'
+    [0, 0b01100, 0b01100, 0, 0b01100, 0b00100, 0b01000, 0, 0], // ';'
+    [0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010, 0, 0], // '<'
+    [0, 0, 0b11111, 0, 0b11111, 0, 0, 0, 0], // '='
+    [0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000, 0, 0], // '>'
+    [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0, 0b00100, 0, 0], // '?'
+    [0b01110, 0b10001, 0b00001, 0b01101, 0b10101, 0b10101, 0b01110, 0, 0], // '@'
+    [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001, 0, 0], // 'A'
+    [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110, 0, 0], // 'B'
+    [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110, 0, 0], // 'C'
+    [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100, 0, 0], // 'D'
+    [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111, 0, 0], // 'E'
+    [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000, 0, 0], // 'F'
+    [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111, 0, 0], // 'G'
+    [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001, 0, 0], // 'H'
+    [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110, 0, 0], // 'I'
+    [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100, 0, 0], // 'J'
+    [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001, 0, 0], // 'K'
+    [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111, 0, 0], // 'L'
+    [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001, 0, 0], // 'M'
+    [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001, 0, 0], // 'N'
+    [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110, 0, 0], // 'O'
+    [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000, 0, 0], // 'P'
+    [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101, 0, 0], // 'Q'
+    [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001, 0, 0], // 'R'
+    [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110, 0, 0], // 'S'
+    [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0, 0], // 'T'
+    [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110, 0, 0], // 'U'
+    [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0, 0], // 'V'
+    [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010, 0, 0], // 'W'
+    [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001, 0, 0], // 'X'
+    [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100, 0, 0], // 'Y'
+    [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111, 0, 0], // 'Z'
+    [0b01110, 0b01000, 0b01000, 0b01000, 0b01000, 0b01000, 0b01110, 0, 0], // '['
+    [0, 0b10000, 0b01000, 0b00100, 0b00010, 0b00001, 0, 0, 0], // '\\'
+    [0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b00010, 0b01110, 0, 0], // ']'
+    [0b00100, 0b01010, 0b10001, 0, 0, 0, 0, 0, 0], // '^'
+    [0, 0, 0, 0, 0, 0, 0, 0b11111, 0], // '_'
+    [0b01000, 0b00100, 0b00010, 0, 0, 0, 0, 0, 0], // '`'
+    [0, 0, 0b01110, 0b00001, 0b01111, 0b10001, 0b01111, 0, 0], // 'a'
+    [0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b11110, 0, 0], // 'b'
+    [0, 0, 0b01110, 0b10000, 0b10000, 0b10001, 0b01110, 0, 0], // 'c'
+    [0b00001, 0b00001, 0b01101, 0b10011, 0b10001, 0b10001, 0b01111, 0, 0], // 'd'
+    [0, 0, 0b01110, 0b10001, 0b11111, 0b10000, 0b01110, 0, 0], // 'e'
+    [0b00110, 0b01001, 0b01000, 0b11100, 0b01000, 0b01000, 0b01000, 0, 0], // 'f'
+    [0, 0, 0b01111, 0b10001, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110], // 'g'
+    [0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001, 0, 0], // 'h'
+    [0b00100, 0, 0b01100, 0b00100, 0b00100, 0b00100, 0b01110, 0, 0], // 'i'
+    [0b00010, 0, 0b00110, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100], // 'j'
+    [0b10000, 0b10000, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0, 0], // 'k'
+    [0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110, 0, 0], // 'l'
+    [0, 0, 0b11010, 0b10101, 0b10101, 0b10001, 0b10001, 0, 0], // 'm'
+    [0, 0, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001, 0, 0], // 'n'
+    [0, 0, 0b01110, 0b10001, 0b10001, 0b10001, 0b01110, 0, 0], // 'o'
+    [0, 0, 0b11110, 0b10001, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000], // 'p'
+    [0, 0, 0b01111, 0b10001, 0b10001, 0b10001, 0b01111, 0b00001, 0b00001], // 'q'
+    [0, 0, 0b10110, 0b11001, 0b10000, 0b10000, 0b10000, 0, 0], // 'r'
+    [0, 0, 0b01111, 0b10000, 0b01110, 0b00001, 0b11110, 0, 0], // 's'
+    [0b01000, 0b01000, 0b11100, 0b01000, 0b01000, 0b01001, 0b00110, 0, 0], // 't'
+    [0, 0, 0b10001, 0b10001, 0b10001, 0b10011, 0b01101, 0, 0], // 'u'
+    [0, 0, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0, 0], // 'v'
+    [0, 0, 0b10001, 0b10001, 0b10101, 0b10101, 0b01010, 0, 0], // 'w'
+    [0, 0, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0, 0], // 'x'
+    [0, 0, 0b10001, 0b10001, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110], // 'y'
+    [0, 0, 0b11111, 0b00010, 0b00100, 0b01000, 0b11111, 0, 0], // 'z'
+    [0b00010, 0b00100, 0b00100, 0b01000, 0b00100, 0b00100, 0b00010, 0, 0], // '{'
+    [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0, 0], // '|'
+    [0b01000, 0b00100, 0b00100, 0b00010, 0b00100, 0b00100, 0b01000, 0, 0], // '}'
+    [0, 0, 0b01000, 0b10101, 0b00010, 0, 0, 0, 0], // '~'
+];
+
+/// The glyphs of the other characters expressions are typeset with.
+#[rustfmt::skip]
+const OTHER_GLYPHS: [(char, [u8; 9]); 4] = [
+    ('\u{2212}', [0, 0, 0, 0b11111, 0, 0, 0, 0, 0]),
+    ('\u{22c5}', [0, 0, 0b00100, 0b01110, 0b00100, 0, 0, 0, 0]),
+    ('π', [0, 0, 0b11111, 0b01010, 0b01010, 0b01010, 0b01010, 0, 0]),
+    ('∞', [0, 0, 0b01010, 0b10101, 0b10101, 0b01010, 0, 0, 0]),
+];
+
+/// The glyph of characters without one of their own.
+const MISSING_GLYPH: [u8; 9] = [
+    0b11111, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11111, 0, 0,
+];
+
+fn glyph(c: char) -> [u8; 9] {
+    match c {
+        ' '..='~' => ASCII_GLYPHS[c as usize - ' ' as usize],
+        _ => OTHER_GLYPHS
+            .iter()
+            .find(|(other, _)| *other == c)
+            .map_or(MISSING_GLYPH, |(_, glyph)| *glyph),
+    }
+}
+
+enum Item {
+    Text {
+        x: f64,
+        y: f64,
+        size: f64,
+        italic: bool,
+        text: String,
+    },
+    Line {
+        x1: f64,
+        x2: f64,
+        y: f64,
+        stroke: f64,
+    },
+    Paren {
+        x: f64,
+        top: f64,
+        bottom: f64,
+        width: f64,
+        open: bool,
+        stroke: f64,
+    },
+}
+
+impl Item {
+    /// The ends and the control point of the quadratic curve a parenthesis is drawn as.
+    fn paren_curve(x: f64, top: f64, bottom: f64, width: f64, open: bool) -> [(f64, f64); 3] {
+        let (outer, inner) = match open {
+            true => (x + 0.8 * width, x + 0.2 * width),
+            false => (x + 0.2 * width, x + 0.8 * width),
+        };
+        [(outer, top), (inner, (top + bottom) / 2.0), (outer, bottom)]
+    }
+
+    fn translate(&mut self, dx: f64, dy: f64) {
+        match self {
+            Item::Text { x, y, .. } => {
+                *x += dx;
+                *y += dy;
+            }
+            Item::Line { x1, x2, y, .. } => {
+                *x1 += dx;
+                *x2 += dx;
+                *y += dy;
+            }
+            Item::Paren { x, top, bottom, .. } => {
+                *x += dx;
+                *top += dy;
+                *bottom += dy;
+            }
+        }
+    }
+}
+
+/// A laid out box, with its origin on the baseline at its left edge.
+#[derive(Default)]
+struct Layout {
+    width: f64,
+    ascent: f64,
+    descent: f64,
+    items: Vec<Item>,
+}
+
+impl Layout {
+    fn text(text: &str, size: f64, italic: bool) -> Self {
+        let width = GLYPH_WIDTH * size * text.chars().count() as f64;
+        Layout {
+            width,
+            ascent: 0.75 * size,
+            descent: 0.25 * size,
+            items: vec![Item::Text {
+                x: 0.0,
+                y: 0.0,
+                size,
+                italic,
+                text: text.to_owned(),
+            }],
+        }
+    }
+
+    fn append(&mut self, mut other: Layout, dy: f64) {
+        let dx = self.width;
+        other
+            .items
+            .iter_mut()
+            .for_each(|item| item.translate(dx, dy));
+        self.items.append(&mut other.items);
+        self.width += other.width;
+        self.ascent = self.ascent.max(other.ascent - dy);
+        self.descent = self.descent.max(other.descent + dy);
+    }
+
+    fn row(parts: impl IntoIterator<Item = Layout>) -> Self {
+        let mut row = Layout::default();
+        parts.into_iter().for_each(|part| row.append(part, 0.0));
+        row
+    }
+
+    fn parenthesized(self, size: f64) -> Self {
+        let width = 0.35 * size;
+        let stroke = 0.06 * size;
+        let (top, bottom) = (-self.ascent, self.descent);
+        let paren = |open| Layout {
+            width,
+            ascent: self.ascent,
+            descent: self.descent,
+            items: vec![Item::Paren {
+                x: 0.0,
+                top,
+                bottom,
+                width,
+                open,
+                stroke,
+            }],
+        };
+
+        let (open, close) = (paren(true), paren(false));
+        Layout::row([open, self, close])
+    }
+
+    fn fraction(numerator: Layout, denominator: Layout, size: f64) -> Self {
+        let gap = 0.15 * size;
+        let pad = 0.1 * size;
+        let axis = MATH_AXIS * size;
+        let width = numerator.width.max(denominator.width) + 2.0 * pad;
+
+        let mut layout = Layout {
+            width,
+            ascent: axis + gap + numerator.ascent + numerator.descent,
+            descent: denominator.ascent + denominator.descent + gap - axis,
+            items: vec![Item::Line {
+                x1: 0.0,
+                x2: width,
+                y: -axis,
+                stroke: 0.05 * size,
+            }],
+        };
+
+        let num_dx = (width - numerator.width) / 2.0;
+        let num_dy = -axis - gap - numerator.descent;
+        let den_dx = (width - denominator.width) / 2.0;
+        let den_dy = -axis + gap + denominator.ascent;
+
+        for (part, dx, dy) in [(numerator, num_dx, num_dy), (denominator, den_dx, den_dy)] {
+            let mut items = part.items;
+            items.iter_mut().for_each(|item| item.translate(dx, dy));
+            layout.items.append(&mut items);
+        }
+
+        layout
+    }
+}
+
+fn layout_value(value: &Value, size: f64) -> Layout {
+    match value {
+        Value::Rational(num, den) if den.get() == 1 => Layout::text(&num.to_string(), size, false),
+        Value::Rational(num, den) => Layout::fraction(
+            Layout::text(&num.to_string(), size, false),
+            Layout::text(&den.to_string(), size, false),
+            size,
+        ),
+        Value::Variable(name) => Layout::text(base_name(name), size, true),
+        Value::E | Value::I => Layout::text(&value.to_string(), size, true),
+        Value::Pi | Value::Inf | Value::Undefined | Value::Algebraic(_) => {
+            Layout::text(&value.to_string(), size, false)
+        }
+    }
+}
+
+/// Whether `child` needs parentheses as the `side`th operand of the infix operation `parent`.
+pub(crate) fn needs_parens(parent: OperationKind, child: &OpArgument, side: usize) -> bool {
+    let Op(op) = &child.value else {
+        return false;
+    };
+
+    match parent.cmp(&op.op) {
+        Ordering::Greater => false,
+        Ordering::Equal => {
+            let natural = match side {
+                0 => Associativity::Left,
+                _ => Associativity::Right,
+            };
+            parent.associativity() != natural
+        }
+        Ordering::Less => true,
+    }
+}
+
+fn layout_operation(op: &Operation, size: f64) -> Layout {
+    let arg = |i: usize, size: f64| layout(&op.arguments[i], size);
+    let symbol = |text: &str| {
+        let mut layout = Layout::text(text, size, false);
+        layout.width += 2.0 * 0.2 * size;
+        layout.items[0].translate(0.2 * size, 0.0);
+        layout
+    };
+
+    match op.op {
+        Division => Layout::fraction(arg(0, size), arg(1, size), size),
+        Pow => {
+            let mut base = arg(0, size);
+            if matches!(op.arguments[0].value, Op(_)) {
+                base = base.parenthesized(size);
+            }
+            let exponent = arg(1, size * SCRIPT_SCALE);
+            let shift = (base.ascent - 0.5 * exponent.ascent).max(0.4 * size);
+            base.append(exponent, -shift);
+            base
+        }
+        Negation => {
+            let mut operand = arg(0, size);
+            if let Op(child) = &op.arguments[0].value {
+                if child.op.is_infix() && child.op != Division && child.op != Pow {
+                    operand = operand.parenthesized(size);
+                }
+            }
+            Layout::row([Layout::text("\u{2212}", size, false), operand])
+        }
+        Addition | Subtraction | Multiplication => {
+            let operator = match op.op {
+                Addition => "+",
+                Subtraction => "\u{2212}",
+                _ => "\u{22c5}",
+            };
+            let parts = (0..2).map(|i| {
+                let part = arg(i, size);
+                // Fractions are visually delimited already.
+                let is_fraction = matches!(&op.arguments[i].value, Op(o) if o.op == Division);
+                if needs_parens(op.op, &op.arguments[i], i) && !is_fraction {
+                    part.parenthesized(size)
+                } else {
+                    part
+                }
+            });
+            let mut parts = parts.collect::<Vec<_>>();
+            let rhs = parts.pop().unwrap();
+            let lhs = parts.pop().unwrap();
+            Layout::row([lhs, symbol(operator), rhs])
+        }
+        Exp | Sin | Cos | Tan | Ln | Atan | Sign => {
+            let name = Layout::text(&op.op.to_string(), size, false);
+            let args = Layout::row((0..op.arguments.len()).map(|i| arg(i, size)));
+            Layout::row([name, args.parenthesized(size)])
+        }
+    }
+}
+
+fn layout(expr: &OpArgument, size: f64) -> Layout {
+    match &expr.value {
+        Op(op) => layout_operation(op, size),
+        Leaf(value) => layout_value(value, size),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Lays `expr` out on a page with a margin around it, returning the layout with the width and
+/// height of the page.
+fn page(expr: &OpArgument, font_size: f64) -> (Layout, f64, f64) {
+    let mut layout = layout(expr, font_size);
+    let margin = 0.25 * font_size;
+    layout
+        .items
+        .iter_mut()
+        .for_each(|item| item.translate(margin, margin + layout.ascent));
+
+    let width = layout.width + 2.0 * margin;
+    let height = layout.ascent + layout.descent + 2.0 * margin;
+    (layout, width, height)
+}
+
+/// Typesets `expr` as a standalone SVG document, using `font_size` user units per em.
+pub fn to_svg(expr: &OpArgument, font_size: f64) -> String {
+    let (layout, width, height) = page(expr, font_size);
+
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width:.2}" height="{height:.2}" viewBox="0 0 {width:.2} {height:.2}">"#
+    );
+    for item in &layout.items {
+        let _ = match item {
+            Item::Text {
+                x,
+                y,
+                size,
+                italic,
+                text,
+            } => write!(
+                svg,
+                r#"<text x="{x:.2}" y="{y:.2}" font-family="serif" font-size="{size:.2}"{} textLength="{:.2}" lengthAdjust="spacingAndGlyphs">{}</text>"#,
+                if *italic {
+                    r#" font-style="italic""#
+                } else {
+                    ""
+                },
+                GLYPH_WIDTH * size * text.chars().count() as f64,
+                escape(text),
+            ),
+            Item::Line { x1, x2, y, stroke } => write!(
+                svg,
+                r#"<line x1="{x1:.2}" y1="{y:.2}" x2="{x2:.2}" y2="{y:.2}" stroke="black" stroke-width="{stroke:.2}"/>"#
+            ),
+            Item::Paren {
+                x,
+                top,
+                bottom,
+                width,
+                open,
+                stroke,
+            } => {
+                let [(outer, top), (inner, middle), (_, bottom)] =
+                    Item::paren_curve(*x, *top, *bottom, *width, *open);
+                write!(
+                    svg,
+                    r#"<path d="M {outer:.2} {top:.2} Q {inner:.2} {middle:.2} {outer:.2} {bottom:.2}" fill="none" stroke="black" stroke-width="{stroke:.2}"/>"#
+                )
+            }
+        };
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// The distance from `p` to the segment from `a` to `b`.
+fn distance((ax, ay): (f64, f64), (bx, by): (f64, f64), (px, py): (f64, f64)) -> f64 {
+    let (dx, dy) = (bx - ax, by - ay);
+    let t = (((px - ax) * dx + (py - ay) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+    (px - ax - t * dx).hypot(py - ay - t * dy)
+}
+
+/// A grayscale image, with how much ink covers each pixel from 0 to 1.
+struct Canvas {
+    width: usize,
+    height: usize,
+    ink: Vec<f64>,
+}
+
+impl Canvas {
+    /// Inks the pixels between the corners `(x0, y0)` and `(x1, y1)` by the share of their
+    /// samples that are `inside`.
+    fn paint(
+        &mut self,
+        (x0, y0): (f64, f64),
+        (x1, y1): (f64, f64),
+        inside: impl Fn(f64, f64) -> bool,
+    ) {
+        let pixels = |lo: f64, hi: f64, count: usize| {
+            lo.floor().max(0.0) as usize..(hi.ceil().max(0.0) as usize).min(count)
+        };
+        let step = 1.0 / SUPERSAMPLING as f64;
+        for row in pixels(y0, y1, self.height) {
+            for column in pixels(x0, x1, self.width) {
+                let hits = (0..SUPERSAMPLING * SUPERSAMPLING)
+                    .filter(|k| {
+                        let x = column as f64 + (0.5 + (k % SUPERSAMPLING) as f64) * step;
+                        let y = row as f64 + (0.5 + (k / SUPERSAMPLING) as f64) * step;
+                        inside(x, y)
+                    })
+                    .count();
+                let ink = &mut self.ink[row * self.width + column];
+                *ink = ink.max(hits as f64 * step * step);
+            }
+        }
+    }
+
+    fn draw(&mut self, item: &Item) {
+        match *item {
+            Item::Text {
+                x,
+                y,
+                size,
+                italic,
+                ref text,
+            } => {
+                // Five columns of pixels and a gap make up the advance of a glyph.
+                let unit = GLYPH_WIDTH * size / 6.0;
+                let slant = if italic { ITALIC_SLANT } else { 0.0 };
+                let glyphs = text.chars().map(glyph).collect::<Vec<_>>();
+                let top = y - 7.0 * unit;
+                let right = x + 6.0 * unit * glyphs.len() as f64 + slant * 7.0 * unit;
+                let corners = ((x - slant * 2.0 * unit, top), (right, y + 2.0 * unit));
+                self.paint(corners.0, corners.1, |px, py| {
+                    let dx = px - x - slant * (y - py);
+                    let (column, row) = ((dx / unit).floor(), ((py - top) / unit).floor());
+                    if column < 0.0 || !(0.0..9.0).contains(&row) {
+                        return false;
+                    }
+                    let (index, column) = (column as usize / 6, column as usize % 6);
+                    column < 5
+                        && glyphs
+                            .get(index)
+                            .is_some_and(|glyph| glyph[row as usize] & (0b10000 >> column) != 0)
+                });
+            }
+            Item::Line { x1, x2, y, stroke } => {
+                let half = stroke / 2.0;
+                self.paint((x1, y - half), (x2, y + half), |px, py| {
+                    (x1..=x2).contains(&px) && (py - y).abs() <= half
+                });
+            }
+            Item::Paren {
+                x,
+                top,
+                bottom,
+                width,
+                open,
+                stroke,
+            } => {
+                let [start, control, end] = Item::paren_curve(x, top, bottom, width, open);
+                let points = (0..=24)
+                    .map(|k| {
+                        let t = k as f64 / 24.0;
+                        let (a, b, c) = ((1.0 - t) * (1.0 - t), 2.0 * (1.0 - t) * t, t * t);
+                        (
+                            a * start.0 + b * control.0 + c * end.0,
+                            a * start.1 + b * control.1 + c * end.1,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                let half = stroke / 2.0;
+                let corners = ((x - half, top - half), (x + width + half, bottom + half));
+                self.paint(corners.0, corners.1, |px, py| {
+                    points
+                        .windows(2)
+                        .any(|segment| distance(segment[0], segment[1], (px, py)) <= half)
+                });
+            }
+        }
+    }
+}
+
+fn crc32<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> u32 {
+    !bytes.into_iter().fold(!0, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| match crc & 1 {
+            1 => (crc >> 1) ^ 0xedb8_8320,
+            _ => crc >> 1,
+        })
+    })
+}
+
+/// `data` in the zlib format, as stored blocks without compression.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if data.is_empty() {
+        zlib.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let length = block.len() as u16;
+        zlib.push(blocks.peek().is_none() as u8);
+        zlib.extend(length.to_le_bytes());
+        zlib.extend((!length).to_le_bytes());
+        zlib.extend(block);
+    }
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    zlib.extend((b << 16 | a).to_be_bytes());
+    zlib
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    png.extend(kind);
+    png.extend(data);
+    png.extend(crc32(kind.iter().chain(data)).to_be_bytes());
+}
+
+/// Typesets `expr` as a grayscale PNG image, black on white, using `font_size` pixels per em.
+///
+/// The glyphs come from a built-in bitmap font, scaled and antialiased, and the image data is
+/// stored uncompressed, so this needs no fonts or libraries either.
+pub fn to_png(expr: &OpArgument, font_size: f64) -> Vec<u8> {
+    let (layout, width, height) = page(expr, font_size);
+    let (width, height) = (width.ceil() as usize, height.ceil() as usize);
+    let mut canvas = Canvas {
+        width,
+        height,
+        ink: vec![0.0; width * height],
+    };
+    layout.items.iter().for_each(|item| canvas.draw(item));
+
+    // Every scanline starts with its filter, which is none.
+    let mut scanlines = Vec::with_capacity((width + 1) * height);
+    for row in canvas.ink.chunks(width) {
+        scanlines.push(0);
+        scanlines.extend(row.iter().map(|ink| 255 - (255.0 * ink).round() as u8));
+    }
+
+    let mut header = Vec::new();
+    header.extend((width as u32).to_be_bytes());
+    header.extend((height as u32).to_be_bytes());
+    // Eight bits of gray, deflate, adaptive filtering and no interlacing.
+    header.extend([8, 0, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png_chunk(&mut png, b"IHDR", &header);
+    png_chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+    png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_png, to_svg};
+    use crate::symbols::variable;
+
+    #[test]
+    fn test_to_svg() {
+        let x = variable("x");
+        let y = variable("y");
+
+        let svg = to_svg(&(x.sin() / (&x + &y)), 20.0);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>"));
+        assert!(svg.contains(">sin</text>"));
+        assert_eq!(svg.matches("<line").count(), 1);
+        assert_eq!(svg.matches("<path").count(), 2);
+
+        let power = to_svg(&(&x + &y).pow(&y), 20.0);
+        assert_eq!(power.matches("<path").count(), 2);
+        assert!(power.contains(r#"font-size="14.00""#));
+    }
+
+    #[test]
+    fn test_to_png() {
+        let x = variable("x");
+        let y = variable("y");
+        let png = to_png(&(x.sin() / (&x + &y)).pow(&y), 40.0);
+
+        let decoder = png::Decoder::new(png.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!(frame.color_type, png::ColorType::Grayscale);
+        let svg = to_svg(&(x.sin() / (&x + &y)).pow(&y), 40.0);
+        assert!(svg.contains(&format!("width=\"{}.", frame.width - 1)));
+
+        // A white margin around black ink, with gray at the antialiased edges.
+        let pixels = &pixels[..frame.buffer_size()];
+        let row = |i: usize| &pixels[i * frame.width as usize..][..frame.width as usize];
+        assert!(row(0).iter().all(|&p| p == 255));
+        assert!(row(frame.height as usize - 1).iter().all(|&p| p == 255));
+        assert!(pixels.iter().filter(|&&p| p == 0).count() > 200);
+        assert!(pixels.iter().any(|&p| p > 0 && p < 255));
+    }
+}
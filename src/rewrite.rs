@@ -1 +1,309 @@
 //! This module describes how to perform graph rewrites on our computational graph.
+
+use ahash::HashMap;
+
+use crate::{
+    budget::{Budget, Exhausted, Partial},
+    constants::Value,
+    instrument,
+    metadata::VariableSet,
+    provenance::{self, Step},
+    symbols::{
+        intern, OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        Operation, StackVec,
+    },
+    template::{parse_hole, TemplateError},
+};
+
+/// A map from variable names to the expressions that replace them.
+pub type Substitution<'a> = HashMap<&'a str, OpArgument>;
+
+struct Substituter<'s> {
+    substitution: &'s Substitution<'s>,
+    targets: VariableSet,
+    memo: HashMap<u64, OpArgument>,
+}
+
+impl Substituter<'_> {
+    fn substitute(&mut self, node: &OpArgument) -> OpArgument {
+        match &node.value {
+            Leaf(value) => match **value {
+                Value::Variable(name) => self.substitution.get(name).unwrap_or(node).clone(),
+                _ => node.clone(),
+            },
+            Op(op) if !op.metadata().free_variables.intersects(&self.targets) => node.clone(),
+            Op(op) => {
+                if let Some(done) = self.memo.get(&node.hash()) {
+                    return done.clone();
+                }
+                let arguments = op
+                    .arguments
+                    .iter()
+                    .map(|arg| self.substitute(arg))
+                    .collect::<StackVec<_>>();
+                let result: OpArgument = Operation::new(op.op, arguments).into();
+                self.memo.insert(node.hash(), result.clone());
+                result
+            }
+        }
+    }
+}
+
+impl OpArgument {
+    /// Replaces every occurrence of the variables in `substitution` with their replacements,
+    /// all at once, so replacements are not themselves substituted into. Subexpressions that
+    /// mention none of the variables are shared with `self` rather than copied.
+    pub fn substitute(&self, substitution: &Substitution) -> OpArgument {
+        // Interning registers names that no node's metadata has mentioned yet, which a lookup
+        // would miss.
+        let targets = substitution.keys().map(|name| intern(name)).collect();
+        Substituter {
+            substitution,
+            targets,
+            memo: HashMap::default(),
+        }
+        .substitute(self)
+    }
+}
+
+/// A rewrite rule `lhs → rhs`. The holes of `lhs` (see [`crate::template`]) match any
+/// subexpression their kind accepts, with repeated holes matching equal subexpressions, and are
+/// replaced by what they matched in `rhs`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    name: String,
+    lhs: OpArgument,
+    rhs: OpArgument,
+}
+
+impl Rule {
+    /// Checks that every hole of `rhs` also occurs in `lhs`.
+    pub fn new(
+        name: impl Into<String>,
+        lhs: OpArgument,
+        rhs: OpArgument,
+    ) -> Result<Self, TemplateError> {
+        let bound = lhs.free_variables();
+        if let Some((name, _)) = rhs
+            .free_variables()
+            .iter()
+            .filter(|var| !bound.contains(var))
+            .find_map(parse_hole)
+        {
+            return Err(TemplateError::Unfilled(name));
+        }
+        Ok(Rule {
+            name: name.into(),
+            lhs,
+            rhs,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn lhs(&self) -> &OpArgument {
+        &self.lhs
+    }
+
+    pub fn rhs(&self) -> &OpArgument {
+        &self.rhs
+    }
+
+    /// Rewrites `expr` if it matches the left-hand side, without looking at its subexpressions.
+    pub fn apply(&self, expr: &OpArgument) -> Option<OpArgument> {
+        let mut matched = Substitution::default();
+        let result =
+            matches(&self.lhs, expr, &mut matched).then(|| self.rhs.substitute(&matched))?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(rule = %self.name, from = %expr, to = %result, "rule fired");
+        instrument::rule_fired();
+        provenance::record(|| Step::Rule(self.name.clone()), expr, &result);
+        Some(result)
+    }
+}
+
+fn matches(pattern: &OpArgument, expr: &OpArgument, matched: &mut Substitution) -> bool {
+    match (&pattern.value, &expr.value) {
+        (Leaf(value), _) => match **value {
+            Value::Variable(var) => match parse_hole(var) {
+                Some((_, kind)) => match matched.get(var) {
+                    Some(previous) => previous == expr,
+                    None if kind.accepts(expr) => {
+                        matched.insert(var, expr.clone());
+                        true
+                    }
+                    None => false,
+                },
+                None => pattern == expr,
+            },
+            _ => pattern == expr,
+        },
+        (Op(p), Op(e)) => {
+            p.op == e.op
+                && p.arguments
+                    .iter()
+                    .zip(&e.arguments)
+                    .all(|(p, e)| matches(p, e, matched))
+        }
+        (Op(_), Leaf(_)) => false,
+    }
+}
+
+/// Rewrites `node` as [`OpArgument::rewrite`] does, spending a step of `budget` per node and
+/// counting the nodes rewritten against its size limit. Returns the result along with that
+/// count.
+fn rewrite_pass(
+    node: &OpArgument,
+    rules: &[Rule],
+    budget: &Budget,
+) -> Result<(OpArgument, usize), Exhausted> {
+    let mut rewritten = 0;
+    let result = node.try_map_bottom_up(|_, rebuilt| {
+        budget.spend()?;
+        instrument::node_rewritten();
+        rewritten += 1;
+        budget.fits(rewritten)?;
+        Ok(rules
+            .iter()
+            .find_map(|rule| rule.apply(&rebuilt))
+            .unwrap_or(rebuilt))
+    })?;
+    Ok((result, rewritten))
+}
+
+impl OpArgument {
+    /// Rewrites every node, from the leaves up, with the first rule in `rules` that applies to
+    /// it. Each node is rewritten at most once, so this always terminates; call it repeatedly to
+    /// reach a fixed point.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(rules = rules.len()))
+    )]
+    pub fn rewrite(&self, rules: &[Rule]) -> OpArgument {
+        rewrite_pass(self, rules, &Budget::unlimited())
+            .expect("Oops, an unlimited budget ran out")
+            .0
+    }
+
+    /// Rewrites with `rules` until nothing changes, spending a step of `budget` per node
+    /// visited and reporting each pass with the number of nodes it rewrote. Rules that undo each
+    /// other never reach a fixed point, so when the budget runs out the result of the last
+    /// complete pass is returned instead.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(rules = rules.len()))
+    )]
+    pub fn rewrite_within(
+        &self,
+        rules: &[Rule],
+        budget: &Budget,
+    ) -> Result<OpArgument, Partial<OpArgument>> {
+        let mut current = self.clone();
+        loop {
+            let pass = rewrite_pass(&current, rules, budget)
+                .and_then(|(next, rewritten)| budget.iterate(rewritten).map(|()| next));
+            match pass {
+                Ok(next) if next == current => return Ok(current),
+                Ok(next) => current = next,
+                Err(reason) => {
+                    return Err(Partial {
+                        best: current,
+                        reason,
+                    })
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{Rule, Substitution};
+    use crate::{
+        budget::{Budget, Exhausted},
+        constants::Value,
+        symbols::{variable, OpArgument, OpArgumentKind::Op},
+        template::{hole, typed_hole, HoleKind, TemplateError},
+    };
+
+    #[test]
+    fn test_substitute() {
+        let x = variable("x");
+        let y = variable("y");
+        let z = variable("z");
+
+        let shared = (&y * &z).sin();
+        let expr = &x * &y + &shared;
+
+        let mut substitution = Substitution::default();
+        substitution.insert("x", &y + &z);
+        substitution.insert("y", x.clone());
+        assert_eq!(
+            expr.substitute(&substitution),
+            (&y + &z) * &x + (&x * &z).sin()
+        );
+
+        // Untouched subexpressions are shared, not rebuilt.
+        substitution.remove("y");
+        let result = expr.substitute(&substitution);
+        assert_eq!(result, (&y + &z) * &y + &shared);
+        let (Op(sum), Op(original)) = (&result.value, &shared.value) else {
+            panic!("Oops, expected operations");
+        };
+        assert!(matches!(&sum.arguments[1].value, Op(op) if Arc::ptr_eq(op, original)));
+
+        assert_eq!(x.substitute(&Substitution::default()), x);
+
+        // A variable no expression has mentioned before is still substituted the first time.
+        let fresh = variable("substitute_fresh");
+        let once = Substitution::from_iter([("substitute_fresh", x.clone())]);
+        assert_eq!(fresh.sin().substitute(&once), x.sin());
+    }
+
+    #[test]
+    fn test_rules() {
+        let x = variable("x");
+        let y = variable("y");
+        let a = hole("a");
+        let zero = OpArgument::from(Value::integer(0));
+        let two = OpArgument::from(Value::integer(2));
+
+        let double = Rule::new("double", &a + &a, &two * &a).unwrap();
+        let cancel = Rule::new("cancel", &a - &a, zero.clone()).unwrap();
+        let fold = Rule::new("fold", typed_hole("c", HoleKind::Constant) * &a, a.clone()).unwrap();
+
+        assert_eq!(double.apply(&(x.sin() + x.sin())), Some(&two * x.sin()));
+        assert_eq!(double.apply(&(&x + &y)), None);
+        assert_eq!(fold.apply(&(&x * &y)), None);
+
+        let rules = [double, cancel, fold];
+        let expr = ((&x + &x) - (&x + &x)) + (&y + &y).exp();
+        assert_eq!(expr.rewrite(&rules), &zero + (&two * &y).exp());
+        // The constant factor introduced by `double` is only dropped by `fold` on a second pass.
+        assert_eq!(expr.rewrite(&rules).rewrite(&rules), &zero + y.exp());
+        assert_eq!(
+            expr.rewrite_within(&rules, &Budget::unlimited()),
+            Ok(&zero + y.exp())
+        );
+
+        // Commuting never settles, so only the budget stops it.
+        let b = hole("b");
+        let commute = Rule::new("commute", &a + &b, &b + &a).unwrap();
+        let budget = Budget::unlimited().with_steps(100);
+        let Err(partial) = (&x + &y).rewrite_within(std::slice::from_ref(&commute), &budget) else {
+            panic!("Whoa there, commuting should not reach a fixed point");
+        };
+        assert_eq!(partial.reason, Exhausted::OutOfSteps);
+        assert!(partial.best == &x + &y || partial.best == &y + &x);
+
+        assert_eq!(
+            Rule::new("bad", a.clone(), hole("b")),
+            Err(TemplateError::Unfilled("b"))
+        );
+    }
+}
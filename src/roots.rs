@@ -0,0 +1,346 @@
+//! This module describes how to find the roots of polynomials with rational coefficients in
+//! closed form.
+//!
+//! Rational roots are found with the rational root theorem and divided out, and what remains is
+//! split into squarefree factors. Quadratic factors are solved with the quadratic formula. The
+//! real roots of the others are isolated with Sturm sequences and kept exactly as algebraic
+//! numbers (see [`crate::algebraic`]), and if that leaves a single pair of complex roots, it
+//! follows from the sum and product of all the roots. Factors with more complex roots have no
+//! closed form here.
+
+use crate::{
+    algebraic::{algebraic_roots, squarefree_factors},
+    constants::Value,
+    fold::Ratio,
+    symbols::OpArgument,
+};
+
+/// Rational roots are only searched for when the leading and constant coefficients are at most
+/// this large, since every divisor of both is tried.
+const MAX_SEARCHED: u128 = 1 << 40;
+
+/// A root of a polynomial, or a pair of complex conjugate roots.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Root {
+    Real {
+        value: OpArgument,
+        multiplicity: usize,
+    },
+    /// The roots `modulus·e^(±i·angle)`, each of the given multiplicity.
+    Conjugate {
+        modulus: OpArgument,
+        angle: OpArgument,
+        multiplicity: usize,
+    },
+}
+
+/// Evaluates the polynomial with `coefficients`, lowest degree first, at `x`.
+pub(crate) fn evaluate(coefficients: &[Ratio], x: Ratio) -> Option<Ratio> {
+    coefficients
+        .iter()
+        .rev()
+        .try_fold(Ratio::ZERO, |acc, &c| acc.checked_mul(x)?.checked_add(c))
+}
+
+/// Divides the polynomial by `x - root`, which must be a root.
+pub(crate) fn deflate(coefficients: &[Ratio], root: Ratio) -> Option<Vec<Ratio>> {
+    let mut quotient = vec![Ratio::ZERO; coefficients.len() - 1];
+    let mut carry = Ratio::ZERO;
+    for i in (1..coefficients.len()).rev() {
+        carry = carry.checked_mul(root)?.checked_add(coefficients[i])?;
+        quotient[i - 1] = carry;
+    }
+    Some(quotient)
+}
+
+fn divisors(n: u128) -> Vec<u128> {
+    let mut small = Vec::new();
+    let mut large = Vec::new();
+    let mut d = 1;
+    while d * d <= n {
+        if n.is_multiple_of(d) {
+            small.push(d);
+            if d * d != n {
+                large.push(n / d);
+            }
+        }
+        d += 1;
+    }
+    small.extend(large.into_iter().rev());
+    small
+}
+
+/// Finds a rational root of the polynomial, if it has one.
+pub(crate) fn rational_root(coefficients: &[Ratio]) -> Option<Ratio> {
+    match coefficients {
+        [c, ..] if *c == Ratio::ZERO => return Some(Ratio::ZERO),
+        [c, b] => return c.checked_mul(b.recip()?).map(|r| -r),
+        _ => {}
+    }
+    // Scale to integer coefficients, so candidates are ±p/q for p | a₀ and q | aₙ.
+    let lcm = coefficients.iter().try_fold(1i128, |lcm, c| {
+        let (mut a, mut b) = (lcm, c.den);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        lcm.checked_mul(c.den / a)
+    })?;
+    let integer = |c: &Ratio| (c.num * (lcm / c.den)).unsigned_abs();
+    let (constant, leading) = (integer(&coefficients[0]), integer(coefficients.last()?));
+    if constant > MAX_SEARCHED || leading > MAX_SEARCHED {
+        return None;
+    }
+
+    for p in divisors(constant) {
+        for q in divisors(leading) {
+            for sign in [1, -1] {
+                let candidate = Ratio::new(sign * p as i128, q as i128)?;
+                if evaluate(coefficients, candidate) == Some(Ratio::ZERO) {
+                    return Some(candidate);
+                }
+            }
+        }
+    }
+    None
+}
+
+fn sqrt(x: OpArgument) -> OpArgument {
+    x.pow(&Value::rational(1, 2).into())
+}
+
+fn constant(r: Ratio) -> Option<OpArgument> {
+    r.to_oparg()
+}
+
+/// Solves the quadratic `x² + bx + c` with no rational roots.
+fn quadratic(b: Ratio, c: Ratio) -> Option<Vec<Root>> {
+    let two = Ratio { num: 2, den: 1 };
+    let discriminant = b
+        .checked_mul(b)?
+        .checked_add(-c.checked_mul(two.checked_mul(two)?)?)?;
+    let half = Ratio::new(1, 2)?;
+    if discriminant.num > 0 {
+        // (-b ± √D) / 2
+        let centre = constant(-b.checked_mul(half)?)?;
+        let offset = sqrt(constant(discriminant)?) / constant(two)?;
+        return Some(vec![
+            Root::Real {
+                value: (&centre + &offset).simplify_radicals(),
+                multiplicity: 1,
+            },
+            Root::Real {
+                value: (centre - offset).simplify_radicals(),
+                multiplicity: 1,
+            },
+        ]);
+    }
+
+    // The roots are √c·e^(±iθ) with cos θ = -b / (2√c), so tan θ = √(-D) / -b.
+    let height = sqrt(constant(-discriminant)?);
+    let pi = OpArgument::from(Value::Pi);
+    let angle = match b.num.signum() {
+        0 => pi / constant(two)?,
+        -1 => (height / constant(-b)?).atan(),
+        _ => pi - (height / constant(b)?).atan(),
+    };
+    Some(vec![Root::Conjugate {
+        modulus: sqrt(constant(c)?).simplify_radicals(),
+        angle,
+        multiplicity: 1,
+    }])
+}
+
+/// The roots of the monic `p` of degree at least 3, which is squarefree and has no rational
+/// roots: the real ones as algebraic numbers, and one pair of complex ones if that is all that
+/// is left.
+fn isolated(p: &[Ratio]) -> Option<Vec<Root>> {
+    let reals = algebraic_roots(p)?;
+    let mut roots = reals
+        .iter()
+        .map(|value| Root::Real {
+            value: value.clone(),
+            multiplicity: 1,
+        })
+        .collect::<Vec<_>>();
+    let n = p.len() - 1;
+    match n - reals.len() {
+        0 => return Some(roots),
+        2 => {}
+        _ => return None,
+    }
+
+    // The pair are the roots of x² + bx + c, where -b is the sum of all the roots, -pₙ₋₁, less
+    // the real ones, and c is their product, (-1)ⁿ·p₀, over the real ones.
+    let sum = reals.iter().fold(constant(p[n - 1])?, |sum, r| sum + r);
+    let product = reals
+        .iter()
+        .fold(constant(Ratio::ONE)?, |product, r| product * r);
+    let sign = if n.is_multiple_of(2) { Ratio::ONE } else { -Ratio::ONE };
+    let b = sum.fold();
+    let c = (constant(p[0].checked_mul(sign)?)? / product).fold();
+
+    // The roots are √c·e^(±iθ) with cos θ = -b / (2√c), and θ in (0, π), so
+    // tan(θ/2) = sin θ / (1 + cos θ) = √(4c - b²) / (2√c - b).
+    let four = constant(Ratio::new(4, 1)?)?;
+    let two = constant(Ratio::new(2, 1)?)?;
+    let height = sqrt((four * &c - &b * &b).fold());
+    let modulus = sqrt(c);
+    let angle = &two * (height / (&two * &modulus - b)).atan();
+    roots.push(Root::Conjugate {
+        modulus,
+        angle,
+        multiplicity: 1,
+    });
+    Some(roots)
+}
+
+/// The roots of the polynomial, which has no rational roots, by its squarefree factors.
+fn irrational(coefficients: &[Ratio]) -> Option<Vec<Root>> {
+    let mut roots = Vec::new();
+    for (factor, multiplicity) in squarefree_factors(coefficients)? {
+        let found = match factor[..] {
+            // Only if the search for rational roots gave up.
+            [c, _] => vec![Root::Real {
+                value: constant(-c)?,
+                multiplicity: 1,
+            }],
+            [c, b, _] => quadratic(b, c)?,
+            _ => isolated(&factor)?,
+        };
+        roots.extend(found.into_iter().map(|mut root| {
+            match &mut root {
+                Root::Real {
+                    multiplicity: m, ..
+                }
+                | Root::Conjugate {
+                    multiplicity: m, ..
+                } => *m = multiplicity,
+            }
+            root
+        }));
+    }
+    Some(roots)
+}
+
+/// The roots of the polynomial with rational `coefficients`, lowest degree first, if they
+/// all have closed forms.
+pub(crate) fn roots(coefficients: &[Ratio]) -> Option<Vec<Root>> {
+    let mut coefficients = coefficients.to_vec();
+    while coefficients.last() == Some(&Ratio::ZERO) {
+        coefficients.pop();
+    }
+
+    let mut roots: Vec<Root> = Vec::new();
+    while coefficients.len() > 1 {
+        let Some(root) = rational_root(&coefficients) else {
+            roots.extend(irrational(&coefficients)?);
+            break;
+        };
+        coefficients = deflate(&coefficients, root)?;
+        let value = constant(root)?;
+        match roots
+            .iter_mut()
+            .find(|r| matches!(r, Root::Real { value: v, .. } if *v == value))
+        {
+            Some(Root::Real { multiplicity, .. }) => *multiplicity += 1,
+            _ => roots.push(Root::Real {
+                value,
+                multiplicity: 1,
+            }),
+        }
+    }
+    Some(roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{roots, Root};
+    use crate::{constants::Value, fold::Ratio, parse::parse, symbols::OpArgumentKind::Leaf};
+
+    #[test]
+    fn test_roots() {
+        let ratios = |cs: &[i128]| {
+            cs.iter()
+                .map(|&num| Ratio { num, den: 1 })
+                .collect::<Vec<_>>()
+        };
+        let real = |value: &str, multiplicity| Root::Real {
+            value: parse(value).unwrap().fold(),
+            multiplicity,
+        };
+
+        // (x - 1)(x - 2)(2x + 3) = 2x³ - 3x² - 5x + 6
+        assert_eq!(
+            roots(&ratios(&[6, -5, -3, 2])).unwrap(),
+            [real("1", 1), real("2", 1), real("-3/2", 1)]
+        );
+        // (x - 2)²
+        assert_eq!(roots(&ratios(&[4, -4, 1])).unwrap(), [real("2", 2)]);
+
+        // x² - x - 1, whose roots are the golden ratio and its conjugate.
+        let golden = roots(&ratios(&[-1, -1, 1])).unwrap();
+        let values = golden
+            .iter()
+            .map(|root| match root {
+                Root::Real { value, .. } => value.evaluate(&Default::default()).unwrap(),
+                _ => panic!("x² - x - 1 has real roots"),
+            })
+            .collect::<Vec<_>>();
+        let phi = (1.0 + 5f64.sqrt()) / 2.0;
+        assert!((values[0] - phi).abs() < 1e-15 && (values[1] - (1.0 - phi)).abs() < 1e-15);
+
+        // x² + 1 = (x - i)(x + i)
+        let Root::Conjugate { modulus, angle, .. } = &roots(&ratios(&[1, 0, 1])).unwrap()[0] else {
+            panic!("x² + 1 has complex roots");
+        };
+        assert_eq!(modulus.evaluate(&Default::default()), Ok(1.0));
+        assert_eq!(
+            angle.evaluate(&Default::default()),
+            Ok(std::f64::consts::FRAC_PI_2)
+        );
+
+        // x³ - 2 has the real root ∛2 and the complex ones ∛2·e^(±2πi/3).
+        let none = Default::default();
+        let cube = roots(&ratios(&[-2, 0, 0, 1])).unwrap();
+        let [Root::Real { value, .. }, Root::Conjugate { modulus, angle, .. }] = &cube[..] else {
+            panic!("x³ - 2 has one real root, got {:?}", cube);
+        };
+        let cbrt2 = 2f64.cbrt();
+        assert!(matches!(&value.value, Leaf(v) if matches!(**v, Value::Algebraic(_))));
+        assert!((value.evaluate(&none).unwrap() - cbrt2).abs() < 1e-15);
+        assert!((modulus.evaluate(&none).unwrap() - cbrt2).abs() < 1e-15);
+        let third = 2.0 * std::f64::consts::FRAC_PI_3;
+        assert!((angle.evaluate(&none).unwrap() - third).abs() < 1e-15);
+
+        // (x - 1)·(x³ - 3x + 1)², whose cubic has the three real roots 2·cos(2πk/9) for
+        // k = 1, 2, 4, each twice.
+        let times = |a: &[i128], b: &[i128]| {
+            let mut product = vec![0; a.len() + b.len() - 1];
+            for (i, x) in a.iter().enumerate() {
+                for (j, y) in b.iter().enumerate() {
+                    product[i + j] += x * y;
+                }
+            }
+            product
+        };
+        let cubic = [1, -3, 0, 1];
+        let found = roots(&ratios(&times(&[-1, 1], &times(&cubic, &cubic)))).unwrap();
+        assert_eq!(found[0], real("1", 1));
+        let mut expected = [2, 4, 8].map(|k| 2.0 * (k as f64 * std::f64::consts::PI / 9.0).cos());
+        expected.sort_by(f64::total_cmp);
+        for (root, expected) in found[1..].iter().zip(expected) {
+            let Root::Real {
+                value,
+                multiplicity: 2,
+            } = root
+            else {
+                panic!("{:?} is not a double real root", root);
+            };
+            assert!((value.evaluate(&none).unwrap() - expected).abs() < 1e-15);
+        }
+        assert_eq!(found.len(), 4);
+
+        // x⁴ + x + 1 has no real roots, so two pairs of complex ones.
+        assert_eq!(roots(&ratios(&[1, 1, 0, 0, 1])), None);
+    }
+}
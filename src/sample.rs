@@ -0,0 +1,97 @@
+//! This module describes sampling expressions along one variable, as plotting front ends do.
+//!
+//! A [`Curve`] compiles an expression once, with the sampled variable as its first parameter
+//! and every other free variable after it in name order, and then evaluates it at evenly
+//! spaced points without touching the symbolic graph again.
+
+use std::ops::Range;
+
+use crate::{
+    codegen::CodegenError,
+    compile::{compile, Program},
+    evaluation::Bindings,
+    symbols::OpArgument,
+};
+
+/// An expression compiled for sampling along its abscissa.
+#[derive(Clone, Debug)]
+pub struct Curve {
+    program: Program,
+}
+
+impl Curve {
+    pub fn new(expr: &OpArgument, abscissa: &'static str) -> Result<Self, CodegenError> {
+        let mut params = expr
+            .free_variables()
+            .iter()
+            .filter(|&name| name != abscissa)
+            .collect::<Vec<_>>();
+        params.sort_unstable();
+        params.insert(0, abscissa);
+        Ok(Curve {
+            program: compile(expr, &params)?,
+        })
+    }
+
+    pub fn abscissa(&self) -> &'static str {
+        self.program.params()[0]
+    }
+
+    /// The free variables other than the abscissa, in name order.
+    pub fn parameters(&self) -> &[&'static str] {
+        &self.program.params()[1..]
+    }
+
+    /// The points `[x, y]` of the curve at `count` evenly spaced abscissae spanning `span`, with
+    /// the other parameters taken from `bindings`. Points depending on an unbound parameter
+    /// are NaN.
+    pub fn sample(&self, span: Range<f64>, count: usize, bindings: &Bindings) -> Vec<[f64; 2]> {
+        let mut params = std::iter::once(0.0)
+            .chain(
+                self.parameters()
+                    .iter()
+                    .map(|name| bindings.get(name).copied().unwrap_or(f64::NAN)),
+            )
+            .collect::<Vec<_>>();
+        let (mut registers, mut out) = (Vec::new(), [0.0]);
+        let step = (span.end - span.start) / (count.max(2) - 1) as f64;
+        (0..count)
+            .map(|i| {
+                let x = span.start + i as f64 * step;
+                params[0] = x;
+                self.program
+                    .evaluate_into(&params, &mut registers, &mut out);
+                [x, out[0]]
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Curve;
+    use crate::{evaluation::Bindings, parse::parse};
+
+    #[test]
+    fn test_curve() {
+        let curve = Curve::new(&parse("a * x^2 + b").unwrap(), "x").unwrap();
+        assert_eq!(curve.abscissa(), "x");
+        assert_eq!(curve.parameters(), ["a", "b"]);
+
+        let bindings = Bindings::from_iter([("a", 2.0), ("b", -1.0)]);
+        assert_eq!(
+            curve.sample(-1.0..1.0, 3, &bindings),
+            [[-1.0, 1.0], [0.0, -1.0], [1.0, 1.0]]
+        );
+
+        let points = curve.sample(0.0..1.0, 2, &Bindings::from_iter([("a", 1.0)]));
+        assert!(points.iter().all(|[_, y]| y.is_nan()));
+
+        // The abscissa need not appear at all.
+        let constant = Curve::new(&parse("3").unwrap(), "x").unwrap();
+        assert_eq!(
+            constant.sample(0.0..1.0, 1, &Bindings::default()),
+            [[0.0, 3.0]]
+        );
+    }
+}
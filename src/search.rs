@@ -0,0 +1,209 @@
+//! This module describes symbolic regression: finding a formula that fits sample data.
+//!
+//! [`search`] enumerates expressions bottom up in order of size, the number of nodes in their
+//! tree, so the first one that fits is as compact as any. Candidates are evaluated on the
+//! samples as they are built, and a candidate whose values match those of a smaller one is
+//! dropped, since it could only ever be replaced by it. This keeps every level of the search
+//! down to the behaviours that are actually new.
+
+use ahash::HashSet;
+use smallvec::smallvec;
+
+use crate::{
+    constants::Value,
+    symbols::{variable, OpArgument, Operation, OperationKind},
+};
+
+/// Values are compared for pruning after rounding to this many significant bits.
+const SIGNIFICANT_BITS: u32 = 36;
+
+/// What [`search`] builds formulas from, and how hard it tries.
+#[derive(Clone, Debug)]
+pub struct SearchOptions {
+    /// The largest formula tried, in nodes.
+    pub max_size: usize,
+    /// The largest absolute error at any sample that still counts as a fit.
+    pub tolerance: f64,
+    /// The most distinct candidates kept for each size.
+    pub max_candidates: usize,
+    pub constants: Vec<OpArgument>,
+    pub operations: Vec<OperationKind>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        use OperationKind::*;
+        SearchOptions {
+            max_size: 7,
+            tolerance: 1e-9,
+            max_candidates: 20_000,
+            constants: vec![Value::integer(1).into(), Value::integer(2).into()],
+            operations: vec![
+                Addition,
+                Subtraction,
+                Multiplication,
+                Division,
+                Pow,
+                Negation,
+                Exp,
+                Ln,
+                Sin,
+                Cos,
+            ],
+        }
+    }
+}
+
+struct Candidate {
+    expr: OpArgument,
+    values: Vec<f64>,
+}
+
+/// A key identifying the values of a candidate up to rounding.
+fn signature(values: &[f64]) -> Vec<u64> {
+    let mask = !((1u64 << (52 - SIGNIFICANT_BITS)) - 1);
+    values
+        .iter()
+        .map(|v| {
+            (v + 0.0)
+                .to_bits()
+                .wrapping_add(1 << (51 - SIGNIFICANT_BITS))
+                & mask
+        })
+        .collect()
+}
+
+/// The smallest formula over the `columns` of samples, each named by the variable it binds,
+/// that reproduces `targets` within the tolerance, or `None` if there is none up to the
+/// maximum size.
+pub fn search(
+    columns: &[(&'static str, &[f64])],
+    targets: &[f64],
+    options: &SearchOptions,
+) -> Option<OpArgument> {
+    assert!(
+        columns
+            .iter()
+            .all(|(_, column)| column.len() == targets.len()),
+        "Hold on, every column passed to search needs as many samples as there are targets"
+    );
+    let fits = |values: &[f64]| {
+        values
+            .iter()
+            .zip(targets)
+            .all(|(v, t)| (v - t).abs() <= options.tolerance)
+    };
+
+    let mut seen = HashSet::default();
+    let mut levels: Vec<Vec<Candidate>> = vec![Vec::new()];
+    let leaves = columns
+        .iter()
+        .map(|&(name, column)| (variable(name), column.to_vec()))
+        .chain(options.constants.iter().filter_map(|c| {
+            let value = c.evaluate(&Default::default()).ok()?;
+            Some((c.clone(), vec![value; targets.len()]))
+        }))
+        .collect::<Vec<_>>();
+
+    for size in 1..=options.max_size {
+        let mut level = Vec::new();
+        let mut admit = |expr: OpArgument, values: Vec<f64>| {
+            if level.len() >= options.max_candidates
+                || !values.iter().all(|v| v.is_finite())
+                || !seen.insert(signature(&values))
+            {
+                return None;
+            }
+            if fits(&values) {
+                return Some(expr);
+            }
+            level.push(Candidate { expr, values });
+            None
+        };
+
+        let found = match size {
+            1 => leaves
+                .iter()
+                .find_map(|(expr, values)| admit(expr.clone(), values.clone())),
+            _ => options
+                .operations
+                .iter()
+                .find_map(|&op| match op.argcount() {
+                    1 => levels[size - 1].iter().find_map(|a| {
+                        let values = a.values.iter().map(|&x| op.apply(&[x])).collect();
+                        admit(Operation::new(op, smallvec![a.expr.clone()]).into(), values)
+                    }),
+                    _ => (1..size - 1).find_map(|left| {
+                        levels[left].iter().find_map(|a| {
+                            levels[size - 1 - left].iter().find_map(|b| {
+                                let values = a
+                                    .values
+                                    .iter()
+                                    .zip(&b.values)
+                                    .map(|(&x, &y)| op.apply(&[x, y]))
+                                    .collect();
+                                let arguments = smallvec![a.expr.clone(), b.expr.clone()];
+                                admit(Operation::new(op, arguments).into(), values)
+                            })
+                        })
+                    }),
+                }),
+        };
+        if found.is_some() {
+            return found;
+        }
+        levels.push(level);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{search, SearchOptions};
+    use crate::{
+        evaluation::Bindings,
+        symbols::{OpArgument, OpArgumentKind::Op},
+    };
+
+    fn size(expr: &OpArgument) -> usize {
+        match &expr.value {
+            Op(op) => 1 + op.arguments.iter().map(size).sum::<usize>(),
+            _ => 1,
+        }
+    }
+
+    #[test]
+    fn test_search() {
+        let xs = [-1.5, -0.5, 0.25, 1.0, 2.0, 3.5];
+        let options = SearchOptions::default();
+        let check = |f: fn(f64) -> f64, expected_size: usize| {
+            let targets = xs.iter().map(|&x| f(x)).collect::<Vec<_>>();
+            let found = search(&[("x", &xs)], &targets, &options).unwrap();
+            assert_eq!(size(&found), expected_size, "{} is not minimal", found);
+            // The formula must generalize beyond the samples.
+            let at = Bindings::from_iter([("x", 5.0)]);
+            assert!((found.evaluate(&at).unwrap() - f(5.0)).abs() < 1e-9);
+        };
+        check(|x| x * x + 1.0, 5);
+        check(|x| x * x.sin(), 4);
+        check(|x| (2.0 * x).exp(), 4);
+
+        // Two variables, with the fit checked at a point that was not sampled.
+        let vs = [0.5, 1.0, 2.0, 4.0, 0.5, 1.0];
+        let targets = xs
+            .iter()
+            .zip(&vs)
+            .map(|(u, v)| (u - v) / v)
+            .collect::<Vec<_>>();
+        let ratio = search(&[("u", &xs), ("v", &vs)], &targets, &options).unwrap();
+        let at = Bindings::from_iter([("u", 7.0), ("v", 3.0)]);
+        assert!((ratio.evaluate(&at).unwrap() - 4.0 / 3.0).abs() < 1e-12);
+
+        let small = SearchOptions {
+            max_size: 3,
+            ..SearchOptions::default()
+        };
+        let targets = xs.iter().map(|&x| x.powi(3) - x).collect::<Vec<_>>();
+        assert_eq!(search(&[("x", &xs)], &targets, &small), None);
+    }
+}
@@ -0,0 +1,344 @@
+//! This module describes a compact binary format for our computational graph.
+//!
+//! A stream starts with a four byte magic number and a version byte. Expressions are stored as
+//! a table of nodes, in which every node comes after its arguments and refers to them by their
+//! position, so shared subexpressions are stored once. The table is followed by the positions of
+//! the root expressions. Integers and floats are little endian, and strings are a `u32` length
+//! followed by UTF-8 bytes.
+
+use std::{
+    fmt::Display,
+    io::{self, Read, Write},
+    num::NonZeroU64,
+};
+
+use ahash::HashMap;
+
+use crate::{
+    algebraic,
+    constants::Value,
+    fold::Ratio,
+    symbols::{
+        intern, variable, OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        Operation, OperationKind, StackVec,
+    },
+};
+
+/// The magic number of a stream of expressions.
+pub const EXPRESSIONS_MAGIC: [u8; 4] = *b"SYME";
+pub const VERSION: u8 = 1;
+
+/// The reasons reading the binary format can fail.
+#[derive(Debug)]
+pub enum DecodeError {
+    Io(io::Error),
+    /// The stream does not start with the expected magic number.
+    BadMagic,
+    UnsupportedVersion(u8),
+    Malformed(&'static str),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Io(err) => Display::fmt(err, f),
+            DecodeError::BadMagic => f.write_str("not a symbolica file"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {}", version)
+            }
+            DecodeError::Malformed(what) => write!(f, "malformed {}", what),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<io::Error> for DecodeError {
+    fn from(err: io::Error) -> Self {
+        DecodeError::Io(err)
+    }
+}
+
+pub(crate) fn write_u32(w: &mut impl Write, n: u32) -> io::Result<()> {
+    w.write_all(&n.to_le_bytes())
+}
+
+pub(crate) fn write_f64(w: &mut impl Write, x: f64) -> io::Result<()> {
+    w.write_all(&x.to_le_bytes())
+}
+
+pub(crate) fn write_str(w: &mut impl Write, s: &str) -> io::Result<()> {
+    write_u32(w, s.len() as u32)?;
+    w.write_all(s.as_bytes())
+}
+
+pub(crate) fn write_header(w: &mut impl Write, magic: [u8; 4]) -> io::Result<()> {
+    w.write_all(&magic)?;
+    w.write_all(&[VERSION])
+}
+
+fn read_array<const N: usize>(r: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    r.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+pub(crate) fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    Ok(read_array::<1>(r)?[0])
+}
+
+pub(crate) fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    read_array(r).map(u32::from_le_bytes)
+}
+
+pub(crate) fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    read_array(r).map(u64::from_le_bytes)
+}
+
+pub(crate) fn read_f64(r: &mut impl Read) -> io::Result<f64> {
+    read_array(r).map(f64::from_le_bytes)
+}
+
+pub(crate) fn read_string(r: &mut impl Read) -> Result<String, DecodeError> {
+    let len = read_u32(r)? as usize;
+    let mut bytes = Vec::new();
+    r.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(DecodeError::Io(io::ErrorKind::UnexpectedEof.into()));
+    }
+    String::from_utf8(bytes).map_err(|_| DecodeError::Malformed("string"))
+}
+
+pub(crate) fn read_header(r: &mut impl Read, magic: [u8; 4]) -> Result<(), DecodeError> {
+    if read_array(r)? != magic {
+        return Err(DecodeError::BadMagic);
+    }
+    match read_u8(r)? {
+        VERSION => Ok(()),
+        version => Err(DecodeError::UnsupportedVersion(version)),
+    }
+}
+
+mod tag {
+    pub const RATIONAL: u8 = 0;
+    pub const PI: u8 = 1;
+    pub const E: u8 = 2;
+    pub const I: u8 = 3;
+    pub const INF: u8 = 4;
+    pub const VARIABLE: u8 = 5;
+    /// A variable that belongs to the context being saved, stored by its unscoped name.
+    pub const LOCAL: u8 = 6;
+    pub const OPERATION: u8 = 7;
+    pub const UNDEFINED: u8 = 8;
+    /// An algebraic number, stored as the coefficients of its polynomial followed by the
+    /// endpoints of its interval, each an `i128` numerator and denominator.
+    pub const ALGEBRAIC: u8 = 9;
+}
+
+/// Builds a node table, storing each distinct subexpression once.
+pub(crate) struct Encoder<'s> {
+    table: Vec<u8>,
+    count: u32,
+    indices: HashMap<u64, u32>,
+    /// Returns the unscoped name of variables local to the context being saved.
+    local: &'s dyn Fn(&'static str) -> Option<&'static str>,
+}
+
+impl<'s> Encoder<'s> {
+    pub(crate) fn new(local: &'s dyn Fn(&'static str) -> Option<&'static str>) -> Self {
+        Encoder {
+            table: Vec::new(),
+            count: 0,
+            indices: HashMap::default(),
+            local,
+        }
+    }
+
+    /// Adds `expr` to the table, returning its position.
+    pub(crate) fn node(&mut self, expr: &OpArgument) -> u32 {
+        if let Some(&index) = self.indices.get(&expr.hash()) {
+            return index;
+        }
+
+        let mut bytes = Vec::new();
+        match &expr.value {
+            Leaf(value) => match **value {
+                Value::Rational(num, den) => {
+                    bytes.push(tag::RATIONAL);
+                    bytes.extend(num.to_le_bytes());
+                    bytes.extend(den.get().to_le_bytes());
+                }
+                Value::Pi => bytes.push(tag::PI),
+                Value::E => bytes.push(tag::E),
+                Value::I => bytes.push(tag::I),
+                Value::Inf => bytes.push(tag::INF),
+                Value::Undefined => bytes.push(tag::UNDEFINED),
+                Value::Algebraic(number) => {
+                    let (polynomial, (lo, hi)) = algebraic::encode(number);
+                    bytes.push(tag::ALGEBRAIC);
+                    bytes.extend((polynomial.len() as u32).to_le_bytes());
+                    for r in polynomial.iter().chain([&lo, &hi]) {
+                        bytes.extend(r.num.to_le_bytes());
+                        bytes.extend(r.den.to_le_bytes());
+                    }
+                }
+                Value::Variable(name) => {
+                    let (tag, name) = match (self.local)(name) {
+                        Some(local) => (tag::LOCAL, local),
+                        None => (tag::VARIABLE, name),
+                    };
+                    bytes.push(tag);
+                    let _ = write_str(&mut bytes, name);
+                }
+            },
+            Op(op) => {
+                let args = op
+                    .arguments
+                    .iter()
+                    .map(|arg| self.node(arg))
+                    .collect::<StackVec<_>>();
+                bytes.push(tag::OPERATION);
+                bytes.push(op.op.opcode() as u8);
+                args.iter().for_each(|arg| bytes.extend(arg.to_le_bytes()));
+            }
+        }
+
+        self.table.extend(bytes);
+        let index = self.count;
+        self.count += 1;
+        self.indices.insert(expr.hash(), index);
+        index
+    }
+
+    /// Writes the table, followed by the given root positions.
+    pub(crate) fn finish(self, w: &mut impl Write, roots: &[u32]) -> io::Result<()> {
+        write_u32(w, self.count)?;
+        w.write_all(&self.table)?;
+        write_u32(w, roots.len() as u32)?;
+        roots.iter().try_for_each(|&root| write_u32(w, root))
+    }
+}
+
+/// Reads a node table and its roots, resolving local variables with `local`, which may refuse
+/// names it cannot take.
+pub(crate) fn decode(
+    r: &mut impl Read,
+    local: &mut dyn FnMut(&str) -> Result<OpArgument, DecodeError>,
+) -> Result<Vec<OpArgument>, DecodeError> {
+    let count = read_u32(r)? as usize;
+    let mut nodes: Vec<OpArgument> = Vec::new();
+    for _ in 0..count {
+        let node = match read_u8(r)? {
+            tag::RATIONAL => {
+                let num = read_u64(r)?;
+                let den =
+                    NonZeroU64::new(read_u64(r)?).ok_or(DecodeError::Malformed("rational"))?;
+                Value::Rational(num, den).into()
+            }
+            tag::PI => Value::Pi.into(),
+            tag::E => Value::E.into(),
+            tag::I => Value::I.into(),
+            tag::INF => Value::Inf.into(),
+            tag::UNDEFINED => Value::Undefined.into(),
+            tag::ALGEBRAIC => {
+                let len = read_u32(r)? as usize;
+                let mut read_ratio = || {
+                    let num = i128::from_le_bytes(read_array(r)?);
+                    let den = i128::from_le_bytes(read_array(r)?);
+                    Ratio::new(num, den).ok_or(DecodeError::Malformed("algebraic number"))
+                };
+                let polynomial = (0..len).map(|_| read_ratio()).collect::<Result<_, _>>()?;
+                let interval = (read_ratio()?, read_ratio()?);
+                algebraic::decode(polynomial, interval)
+                    .ok_or(DecodeError::Malformed("algebraic number"))?
+            }
+            tag::VARIABLE => variable(intern(&read_string(r)?)),
+            tag::LOCAL => local(&read_string(r)?)?,
+            tag::OPERATION => {
+                let op = OperationKind::from_opcode(read_u8(r)? as u32)
+                    .ok_or(DecodeError::Malformed("operation"))?;
+                let arguments = (0..op.argcount())
+                    .map(|_| {
+                        let index = read_u32(r)? as usize;
+                        nodes
+                            .get(index)
+                            .cloned()
+                            .ok_or(DecodeError::Malformed("argument"))
+                    })
+                    .collect::<Result<StackVec<_>, _>>()?;
+                Operation::new(op, arguments).into()
+            }
+            _ => return Err(DecodeError::Malformed("node")),
+        };
+        nodes.push(node);
+    }
+
+    let roots = read_u32(r)? as usize;
+    (0..roots)
+        .map(|_| {
+            let index = read_u32(r)? as usize;
+            nodes
+                .get(index)
+                .cloned()
+                .ok_or(DecodeError::Malformed("root"))
+        })
+        .collect()
+}
+
+/// Writes `exprs` in the binary format.
+pub fn write_expressions(mut w: impl Write, exprs: &[&OpArgument]) -> io::Result<()> {
+    write_header(&mut w, EXPRESSIONS_MAGIC)?;
+    let mut encoder = Encoder::new(&|_| None);
+    let roots = exprs
+        .iter()
+        .map(|expr| encoder.node(expr))
+        .collect::<Vec<_>>();
+    encoder.finish(&mut w, &roots)
+}
+
+/// Reads expressions written by [`write_expressions`].
+pub fn read_expressions(mut r: impl Read) -> Result<Vec<OpArgument>, DecodeError> {
+    read_header(&mut r, EXPRESSIONS_MAGIC)?;
+    decode(&mut r, &mut |name| Ok(variable(intern(name))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_expressions, write_expressions, DecodeError};
+    use crate::{
+        constants::Value,
+        symbols::{variable, OpArgument},
+    };
+
+    #[test]
+    fn test_roundtrip() {
+        let x = variable("x");
+        let y = variable("y");
+        let shared = (&x * &y).sin();
+        let exprs = [
+            &shared + &shared,
+            (x.pow(&-OpArgument::from(Value::rational(3, 7))) - OpArgument::from(Value::Pi)).atan(),
+            OpArgument::from(Value::I) * OpArgument::from(Value::E) / OpArgument::from(Value::Inf),
+        ];
+
+        let mut bytes = Vec::new();
+        write_expressions(&mut bytes, &exprs.iter().collect::<Vec<_>>()).unwrap();
+        assert_eq!(read_expressions(bytes.as_slice()).unwrap(), exprs);
+
+        // The shared subexpression is stored once: 2 variables, the product, the sine and the sum.
+        let mut single = Vec::new();
+        write_expressions(&mut single, &[&exprs[0]]).unwrap();
+        assert_eq!(u32::from_le_bytes(single[5..9].try_into().unwrap()), 5);
+
+        assert!(matches!(
+            read_expressions(&b"nope!"[..]),
+            Err(DecodeError::BadMagic)
+        ));
+        bytes.truncate(bytes.len() - 2);
+        assert!(matches!(
+            read_expressions(bytes.as_slice()),
+            Err(DecodeError::Io(_))
+        ));
+    }
+}
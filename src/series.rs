@@ -0,0 +1,523 @@
+//! This module describes truncated formal power series with symbolic coefficients.
+//!
+//! A [`Series`] keeps the coefficients of `x⁰, x¹, …` up to its order and forgets every higher
+//! one, so sums, products and quotients of series are exact up to the smaller order of their
+//! operands. Coefficients are folded as they are computed, so series of constants stay
+//! constants.
+//!
+//! [`OpArgument::taylor`] expands an expression around a point without differentiating it:
+//! every operation is applied to the series of its arguments, composing the known series of
+//! `exp`, `sin`, `cos`, `ln`, `atan` and powers with the part of the argument that vanishes at
+//! the point.
+
+use std::{
+    fmt::Display,
+    ops::{Add, Mul, Neg, Sub},
+};
+
+use crate::{
+    constants::Value,
+    fold::Ratio,
+    symbols::{
+        OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        OperationKind::*,
+    },
+};
+
+/// The reasons a series operation can fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SeriesError {
+    /// The divisor has no constant term, so it has no inverse as a power series.
+    NotInvertible,
+    /// The function jumps at the point of expansion, so it has no power series there.
+    Discontinuous,
+}
+
+impl Display for SeriesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SeriesError::NotInvertible => {
+                f.write_str("the divisor has no constant term, so it cannot be inverted")
+            }
+            SeriesError::Discontinuous => {
+                f.write_str("the function jumps at the point, so it has no power series there")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SeriesError {}
+
+/// The power series `Σ coefficients[k]·xᵏ`, truncated after `coefficients.len()` terms.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Series {
+    coefficients: Vec<OpArgument>,
+}
+
+pub(crate) fn zero() -> OpArgument {
+    Value::integer(0).into()
+}
+
+pub(crate) fn one() -> OpArgument {
+    Value::integer(1).into()
+}
+
+/// `a + b`, folded, leaving out a zero term so symbolic coefficients stay small.
+pub(crate) fn plus(a: &OpArgument, b: &OpArgument) -> OpArgument {
+    match (*a == zero(), *b == zero()) {
+        (true, _) => b.clone(),
+        (_, true) => a.clone(),
+        _ => (a + b).fold(),
+    }
+}
+
+/// `a·b`, folded, leaving out a unit factor and dropping products with zero.
+pub(crate) fn times(a: &OpArgument, b: &OpArgument) -> OpArgument {
+    if *a == zero() || *b == zero() {
+        return zero();
+    }
+    match (*a == one(), *b == one()) {
+        (true, _) => b.clone(),
+        (_, true) => a.clone(),
+        _ => (a * b).fold(),
+    }
+}
+
+impl Series {
+    pub fn new(coefficients: Vec<OpArgument>) -> Self {
+        Series {
+            coefficients: coefficients.iter().map(OpArgument::fold).collect(),
+        }
+    }
+
+    /// The series of the polynomial with `coefficients`, lowest degree first, up to `order`.
+    pub fn polynomial(coefficients: &[OpArgument], order: usize) -> Self {
+        Series::new(
+            (0..order)
+                .map(|k| coefficients.get(k).cloned().unwrap_or_else(zero))
+                .collect(),
+        )
+    }
+
+    pub fn coefficients(&self) -> &[OpArgument] {
+        &self.coefficients
+    }
+
+    /// The number of terms kept.
+    pub fn order(&self) -> usize {
+        self.coefficients.len()
+    }
+
+    /// The coefficient of `xᵏ`, if it is kept.
+    pub fn coefficient(&self, k: usize) -> Option<&OpArgument> {
+        self.coefficients.get(k)
+    }
+
+    /// The quotient `self / divisor`, by long division from the lowest degree up.
+    pub fn checked_div(&self, divisor: &Series) -> Result<Series, SeriesError> {
+        let order = self.order().min(divisor.order());
+        let lead = divisor
+            .coefficients
+            .first()
+            .filter(|lead| **lead != zero())
+            .ok_or(SeriesError::NotInvertible)?;
+        let mut quotient: Vec<OpArgument> = Vec::with_capacity(order);
+        for n in 0..order {
+            let known = (1..=n)
+                .map(|k| times(&divisor.coefficients[k], &quotient[n - k]))
+                .fold(self.coefficients[n].clone(), |acc, term| {
+                    plus(&acc, &(-term).fold())
+                });
+            quotient.push(match *lead == one() {
+                true => known,
+                false => (known / lead).fold(),
+            });
+        }
+        Ok(Series {
+            coefficients: quotient,
+        })
+    }
+
+    /// The series multiplied by the constant `c`.
+    pub fn scale(&self, c: &OpArgument) -> Series {
+        Series {
+            coefficients: self
+                .coefficients
+                .iter()
+                .map(|a| times(a, &c.fold()))
+                .collect(),
+        }
+    }
+
+    /// The termwise derivative, which is known to one order less.
+    pub fn derivative(&self) -> Series {
+        Series {
+            coefficients: self
+                .coefficients
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(k, a)| times(&Value::integer(k as u64).into(), a))
+                .collect(),
+        }
+    }
+
+    /// The termwise antiderivative with constant term `constant`, which is known to one order
+    /// more.
+    pub fn integral(&self, constant: &OpArgument) -> Series {
+        Series {
+            coefficients: std::iter::once(constant.fold())
+                .chain(
+                    self.coefficients
+                        .iter()
+                        .enumerate()
+                        .map(|(k, a)| times(a, &Value::rational(1, k as u64 + 1).into())),
+                )
+                .collect(),
+        }
+    }
+
+    /// Splits the series into its constant term and the rest, which vanishes at `x = 0`.
+    fn split(&self) -> (OpArgument, Series) {
+        let mut rest = self.clone();
+        let constant = std::mem::replace(&mut rest.coefficients[0], zero());
+        (constant, rest)
+    }
+
+    fn is_constant(&self) -> bool {
+        self.coefficients.iter().skip(1).all(|c| *c == zero())
+    }
+
+    /// `Σ a(k)·selfᵏ`, for a series without a constant term.
+    fn compose(&self, a: impl Fn(usize, &OpArgument) -> OpArgument) -> Series {
+        let mut power = Series::polynomial(&[one()], self.order());
+        let mut previous = OpArgument::from(Value::integer(1));
+        let mut sum = Series::polynomial(&[], self.order());
+        for k in 0..self.order() {
+            let coefficient = a(k, &previous).fold();
+            sum = &sum + &power.scale(&coefficient);
+            power = &power * self;
+            previous = coefficient;
+        }
+        sum
+    }
+
+    /// The truncated series as a polynomial in `x`.
+    pub fn to_polynomial(&self, x: &OpArgument) -> OpArgument {
+        self.coefficients
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c != zero())
+            .map(|(k, c)| match k {
+                0 => c.clone(),
+                1 => c * x,
+                k => c * x.pow(&Value::integer(k as u64).into()),
+            })
+            .reduce(|acc, term| acc + term)
+            .unwrap_or_else(zero)
+    }
+}
+
+fn integer(k: usize) -> OpArgument {
+    Value::integer(k as u64).into()
+}
+
+fn exp(s: &Series) -> Series {
+    let (c, u) = s.split();
+    // a(k) = a(k - 1) / k
+    u.compose(|k, previous| match k {
+        0 => one(),
+        k => previous / integer(k),
+    })
+    .scale(&c.exp())
+}
+
+/// The series of `cos(u)` and `sin(u)` for `u` without a constant term.
+fn cos_sin(u: &Series) -> (Series, Series) {
+    // The coefficients of e^(iu) are iᵏ/k!, split by the parity of k. Each 1/k! is built from
+    // the previous one, as in `exp`, so folding keeps it exact once k! outgrows a `u64`.
+    let mut reciprocals = vec![one()];
+    for k in 1..u.order() {
+        let next = (&reciprocals[k - 1] / integer(k)).fold();
+        reciprocals.push(next);
+    }
+    let wave = |k: usize, parity: usize| match k % 2 == parity {
+        true => {
+            let magnitude = reciprocals[k].clone();
+            match (k / 2) % 2 {
+                0 => magnitude,
+                _ => -magnitude,
+            }
+        }
+        false => zero(),
+    };
+    (u.compose(|k, _| wave(k, 0)), u.compose(|k, _| wave(k, 1)))
+}
+
+fn sin_cos(s: &Series) -> (Series, Series) {
+    let (c, u) = s.split();
+    let (cos_u, sin_u) = cos_sin(&u);
+    (
+        &sin_u.scale(&c.cos()) + &cos_u.scale(&c.sin()),
+        &cos_u.scale(&c.cos()) - &sin_u.scale(&c.sin()),
+    )
+}
+
+fn ln(s: &Series) -> Result<Series, SeriesError> {
+    let (c, _) = s.split();
+    let quotient = s.derivative().checked_div(s)?;
+    Ok(quotient.integral(&c.ln()))
+}
+
+fn atan(s: &Series) -> Result<Series, SeriesError> {
+    let (c, _) = s.split();
+    let one = Series::polynomial(&[one()], s.order());
+    let quotient = s.derivative().checked_div(&(&one + &(s * s)))?;
+    Ok(quotient.integral(&c.atan()))
+}
+
+/// The sign is constant around every point where its argument is not zero.
+fn sign(s: &Series) -> Result<Series, SeriesError> {
+    let (c, _) = s.split();
+    if c.fold() == zero() {
+        return Err(SeriesError::Discontinuous);
+    }
+    Ok(Series::polynomial(&[c.sign()], s.order()))
+}
+
+fn pow(base: &Series, exponent: &Series) -> Result<Series, SeriesError> {
+    if !exponent.is_constant() {
+        return Ok(exp(&(exponent * &ln(base)?)));
+    }
+    let alpha = &exponent.coefficients[0];
+    if let Some(n) = Ratio::of(alpha).filter(|r| r.den == 1 && r.num >= 0) {
+        let mut result = Series::polynomial(&[one()], base.order());
+        for _ in 0..n.num {
+            result = &result * base;
+        }
+        return Ok(result);
+    }
+
+    // c^α·(1 + u/c)^α, with binomial coefficients a(k) = a(k - 1)·(α - k + 1)/k.
+    let (c, u) = base.split();
+    if c == zero() {
+        return Err(SeriesError::NotInvertible);
+    }
+    let ratio = u.scale(&(OpArgument::from(Value::integer(1)) / &c));
+    Ok(ratio
+        .compose(|k, previous| match k {
+            0 => one(),
+            k => previous * (alpha - integer(k - 1)) / integer(k),
+        })
+        .scale(&c.pow(alpha)))
+}
+
+fn taylor(
+    node: &OpArgument,
+    var: &str,
+    point: &OpArgument,
+    order: usize,
+) -> Result<Series, SeriesError> {
+    let op = match &node.value {
+        Leaf(value) => {
+            return Ok(match **value {
+                Value::Variable(name) if name == var => {
+                    Series::polynomial(&[point.clone(), one()], order)
+                }
+                _ => Series::polynomial(std::slice::from_ref(node), order),
+            })
+        }
+        Op(op) => op,
+    };
+    let args = op
+        .arguments
+        .iter()
+        .map(|arg| taylor(arg, var, point, order))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(match (op.op, &args[..]) {
+        (Addition, [a, b]) => a + b,
+        (Subtraction, [a, b]) => a - b,
+        (Multiplication, [a, b]) => a * b,
+        (Division, [a, b]) => a.checked_div(b)?,
+        (Negation, [a]) => -a,
+        (Pow, [a, b]) => pow(a, b)?,
+        (Exp, [a]) => exp(a),
+        (Sin, [a]) => sin_cos(a).0,
+        (Cos, [a]) => sin_cos(a).1,
+        (Tan, [a]) => {
+            let (sin, cos) = sin_cos(a);
+            sin.checked_div(&cos)?
+        }
+        (Ln, [a]) => ln(a)?,
+        (Atan, [a]) => atan(a)?,
+        (Sign, [a]) => sign(a)?,
+        (op, args) => unreachable!("Whoa there, {} can't take {} arguments", op, args.len()),
+    })
+}
+
+impl OpArgument {
+    /// The Taylor series of the expression in `var` around `point`, to `order` terms, so the
+    /// coefficient of `xᵏ` is that of `(var - point)ᵏ`. It fails where the expression or one
+    /// of its subexpressions is singular at `point`, even if the singularity cancels.
+    pub fn taylor(
+        &self,
+        var: &str,
+        point: &OpArgument,
+        order: usize,
+    ) -> Result<Series, SeriesError> {
+        match order {
+            0 => Ok(Series::new(Vec::new())),
+            order => taylor(self, var, point, order),
+        }
+    }
+}
+
+impl Add<&Series> for &Series {
+    type Output = Series;
+
+    fn add(self, rhs: &Series) -> Series {
+        Series {
+            coefficients: self
+                .coefficients
+                .iter()
+                .zip(&rhs.coefficients)
+                .map(|(a, b)| plus(a, b))
+                .collect(),
+        }
+    }
+}
+
+impl Sub<&Series> for &Series {
+    type Output = Series;
+
+    fn sub(self, rhs: &Series) -> Series {
+        self + &-rhs
+    }
+}
+
+impl Neg for &Series {
+    type Output = Series;
+
+    fn neg(self) -> Series {
+        Series {
+            coefficients: self.coefficients.iter().map(|a| (-a).fold()).collect(),
+        }
+    }
+}
+
+impl Mul<&Series> for &Series {
+    type Output = Series;
+
+    fn mul(self, rhs: &Series) -> Series {
+        let order = self.order().min(rhs.order());
+        Series {
+            coefficients: (0..order)
+                .map(|n| {
+                    (0..=n)
+                        .map(|k| times(&self.coefficients[k], &rhs.coefficients[n - k]))
+                        .fold(zero(), |acc, term| plus(&acc, &term))
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Series, SeriesError};
+    use crate::{parse::parse, symbols::variable};
+
+    #[test]
+    fn test_series() {
+        let p = |input: &str| parse(input).unwrap().fold();
+        let series = |cs: &[&str]| Series::new(cs.iter().map(|c| p(c)).collect());
+
+        // 1 / (1 - x) = 1 + x + x² + …
+        let one = series(&["1", "0", "0", "0", "0"]);
+        let geometric = one.checked_div(&series(&["1", "-1"])).unwrap();
+        assert_eq!(geometric, series(&["1", "1"]));
+        let geometric = one
+            .checked_div(&Series::polynomial(&[p("1"), p("-1")], 5))
+            .unwrap();
+        assert_eq!(geometric, series(&["1", "1", "1", "1", "1"]));
+
+        // (1 + x)·(1 - x + x² - …) = 1
+        let alternating = one
+            .checked_div(&Series::polynomial(&[p("1"), p("1")], 5))
+            .unwrap();
+        assert_eq!(
+            &Series::polynomial(&[p("1"), p("1")], 5) * &alternating,
+            one
+        );
+        assert_eq!(
+            &geometric + &alternating,
+            series(&["2", "0", "2", "0", "2"])
+        );
+
+        // Symbolic coefficients are kept.
+        let a = variable("a");
+        let scaled = Series::new(vec![a.clone(), a.clone()]);
+        assert_eq!(
+            scaled.to_polynomial(&variable("x")),
+            &a + &a * variable("x")
+        );
+
+        assert_eq!(
+            one.checked_div(&series(&["0", "1"])),
+            Err(SeriesError::NotInvertible)
+        );
+
+        // Taylor series compose the series of their subexpressions.
+        let zero = p("0");
+        let taylor =
+            |input: &str, point: &str, order| parse(input).unwrap().taylor("x", &p(point), order);
+        assert_eq!(
+            taylor("exp(x)", "0", 5),
+            Ok(series(&["1", "1", "1/2", "1/6", "1/24"]))
+        );
+        assert_eq!(
+            taylor("sin(x)", "0", 6),
+            Ok(series(&["0", "1", "0", "-1/6", "0", "1/120"]))
+        );
+        assert_eq!(
+            taylor("exp(sin(x))", "0", 5),
+            Ok(series(&["1", "1", "1/2", "0", "-1/8"]))
+        );
+        assert_eq!(
+            taylor("ln(x)", "1", 4),
+            Ok(series(&["0", "1", "-1/2", "1/3"]))
+        );
+        assert_eq!(taylor("x^(1/2)", "1", 3), Ok(series(&["1", "1/2", "-1/8"])));
+        assert_eq!(
+            taylor("(1 + x)^3 / (1 - x)", "0", 4),
+            Ok(series(&["1", "4", "7", "8"]))
+        );
+        assert_eq!(
+            taylor("tan(x) - x^x", "0", 2),
+            Err(SeriesError::NotInvertible)
+        );
+        let atan = taylor("atan(x)", "0", 4).unwrap();
+        assert_eq!(atan.coefficients()[1..], [p("1"), zero, p("-1/3")]);
+        // Other variables are constants.
+        assert_eq!(
+            taylor("a * x^2", "0", 3),
+            Ok(Series::new(vec![p("0"), p("0"), variable("a")]))
+        );
+
+        // Past 20! the factorials no longer fit in a u64, but the coefficients stay exact.
+        let none = crate::evaluation::Bindings::default();
+        let cos = taylor("cos(x)", "0", 30).unwrap();
+        let sin = taylor("sin(x)", "0", 30).unwrap();
+        let mut factorial = 1.0;
+        for k in 1..30 {
+            factorial *= k as f64;
+            let (even, odd) = (&cos.coefficients()[k], &sin.coefficients()[k]);
+            let (wave, other) = if k % 2 == 0 { (even, odd) } else { (odd, even) };
+            let sign = if (k / 2) % 2 == 0 { 1.0 } else { -1.0 };
+            let value = wave.evaluate(&none).unwrap();
+            assert!((value * factorial - sign).abs() < 1e-12, "{}", k);
+            assert_eq!(*other, p("0"));
+        }
+    }
+}
@@ -0,0 +1,564 @@
+//! This module describes sets of values: intervals and finite sets of expressions.
+//!
+//! Sets are what domains, solution sets of inequalities and assumptions are made of. Their
+//! endpoints and elements are arbitrary expressions, so unions and intersections are only
+//! simplified where the order of the expressions involved can be decided, by folding and then
+//! numerically evaluating constant expressions. Anything undecidable is kept as an unevaluated
+//! [`Set::Union`] or [`Set::Intersection`].
+
+use std::{cmp::Ordering, fmt::Display};
+
+use crate::{
+    constants::Value,
+    evaluation::{Bindings, EvaluationError},
+    interval::Interval,
+    symbols::OpArgument,
+};
+
+/// One end of an interval.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Endpoint {
+    pub value: OpArgument,
+    /// Whether the endpoint belongs to the interval.
+    pub closed: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Set {
+    Empty,
+    /// Distinct elements, in the order they were first added.
+    Finite(Vec<OpArgument>),
+    Interval {
+        lo: Endpoint,
+        hi: Endpoint,
+    },
+    Union(Vec<Set>),
+    Intersection(Vec<Set>),
+}
+
+/// Compares two expressions, if their order can be decided.
+fn compare(a: &OpArgument, b: &OpArgument) -> Option<Ordering> {
+    let (a, b) = (a.fold(), b.fold());
+    if a == b {
+        return Some(Ordering::Equal);
+    }
+    let bindings = Bindings::default();
+    let (a, b) = (a.evaluate(&bindings).ok()?, b.evaluate(&bindings).ok()?);
+    // Equal floats may still be different reals, and different floats may have been rounded
+    // apart from equal ones unless they are well separated.
+    let tolerance = 4.0 * f64::EPSILON * a.abs().max(b.abs());
+    match a.partial_cmp(&b)? {
+        Ordering::Equal => None,
+        _ if a.is_finite() && b.is_finite() && (a - b).abs() <= tolerance => None,
+        order => Some(order),
+    }
+}
+
+impl Set {
+    /// The interval between `lo` and `hi`, closed at each end as given.
+    pub fn interval(lo: OpArgument, lo_closed: bool, hi: OpArgument, hi_closed: bool) -> Set {
+        Set::Interval {
+            lo: Endpoint {
+                value: lo,
+                closed: lo_closed,
+            },
+            hi: Endpoint {
+                value: hi,
+                closed: hi_closed,
+            },
+        }
+    }
+
+    /// The closed interval `[lo, hi]`.
+    pub fn closed(lo: OpArgument, hi: OpArgument) -> Set {
+        Set::interval(lo, true, hi, true)
+    }
+
+    /// The open interval `(lo, hi)`.
+    pub fn open(lo: OpArgument, hi: OpArgument) -> Set {
+        Set::interval(lo, false, hi, false)
+    }
+
+    /// The real line `(-∞, ∞)`.
+    pub fn reals() -> Set {
+        let inf = OpArgument::from(Value::Inf);
+        Set::open(-&inf, inf)
+    }
+
+    /// The finite set of `elements`, with structural duplicates removed.
+    pub fn finite(elements: impl IntoIterator<Item = OpArgument>) -> Set {
+        let mut distinct = Vec::new();
+        for element in elements {
+            if !distinct.contains(&element) {
+                distinct.push(element);
+            }
+        }
+        match distinct.is_empty() {
+            true => Set::Empty,
+            false => Set::Finite(distinct),
+        }
+    }
+
+    /// Whether `x` belongs to the set, if that can be decided.
+    pub fn contains(&self, x: &OpArgument) -> Option<bool> {
+        match self {
+            Set::Empty => Some(false),
+            Set::Finite(elements) => {
+                let mut decided = Some(false);
+                for element in elements {
+                    match compare(x, element) {
+                        Some(Ordering::Equal) => return Some(true),
+                        Some(_) => {}
+                        None => decided = None,
+                    }
+                }
+                decided
+            }
+            Set::Interval { lo, hi } => {
+                let above = match compare(x, &lo.value)? {
+                    Ordering::Equal => lo.closed,
+                    order => order == Ordering::Greater,
+                };
+                let below = match compare(x, &hi.value)? {
+                    Ordering::Equal => hi.closed,
+                    order => order == Ordering::Less,
+                };
+                Some(above && below)
+            }
+            Set::Union(sets) => sets
+                .iter()
+                .try_fold(false, |found, set| Some(found || set.contains(x)?)),
+            Set::Intersection(sets) => sets
+                .iter()
+                .try_fold(true, |found, set| Some(found && set.contains(x)?)),
+        }
+    }
+
+    /// Whether the set is decidably empty.
+    pub fn is_empty(&self) -> bool {
+        *self == Set::Empty
+    }
+
+    /// The set `x ∈ self` is tested against, as a membership expression.
+    pub fn member(&self, x: OpArgument) -> Membership {
+        Membership {
+            element: x,
+            set: self.clone(),
+        }
+    }
+
+    /// The union of two sets, simplified where the order of their elements is decidable.
+    pub fn union(&self, other: &Set) -> Set {
+        match (self, other) {
+            (Set::Empty, set) | (set, Set::Empty) => set.clone(),
+            (Set::Finite(a), Set::Finite(b)) => Set::finite(a.iter().chain(b).cloned()),
+            (Set::Finite(elements), Set::Interval { lo, hi })
+            | (Set::Interval { lo, hi }, Set::Finite(elements))
+                if elements.iter().any(|x| {
+                    (!lo.closed && compare(x, &lo.value) == Some(Ordering::Equal))
+                        || (!hi.closed && compare(x, &hi.value) == Some(Ordering::Equal))
+                }) =>
+            {
+                // Elements at open ends close them.
+                let at = |end: &Endpoint| {
+                    elements
+                        .iter()
+                        .any(|x| compare(x, &end.value) == Some(Ordering::Equal))
+                };
+                let closed = Set::interval(
+                    lo.value.clone(),
+                    lo.closed || at(lo),
+                    hi.value.clone(),
+                    hi.closed || at(hi),
+                );
+                closed.union(&Set::finite(elements.iter().cloned()))
+            }
+            (Set::Finite(elements), set) | (set, Set::Finite(elements))
+                if !matches!(set, Set::Union(_)) =>
+            {
+                let outside = elements
+                    .iter()
+                    .filter(|x| set.contains(x) != Some(true))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                match outside.is_empty() {
+                    true => set.clone(),
+                    false => Set::Union(vec![set.clone(), Set::Finite(outside)]),
+                }
+            }
+            (Set::Interval { lo: a_lo, .. }, Set::Interval { lo: b_lo, .. }) => {
+                // Order the intervals by their lower ends, then merge them if they touch.
+                let (first, second) = match compare(&a_lo.value, &b_lo.value) {
+                    Some(Ordering::Greater) => (other, self),
+                    Some(_) => (self, other),
+                    None => return Set::Union(vec![self.clone(), other.clone()]),
+                };
+                let (
+                    Set::Interval { lo, hi },
+                    Set::Interval {
+                        lo: next_lo,
+                        hi: next_hi,
+                    },
+                ) = (first, second)
+                else {
+                    unreachable!()
+                };
+                let touching = match compare(&hi.value, &next_lo.value) {
+                    Some(Ordering::Greater) => true,
+                    Some(Ordering::Equal) => hi.closed || next_lo.closed,
+                    Some(Ordering::Less) => false,
+                    None => return Set::Union(vec![first.clone(), second.clone()]),
+                };
+                if !touching {
+                    return Set::Union(vec![first.clone(), second.clone()]);
+                }
+                let lo = match compare(&lo.value, &next_lo.value) {
+                    Some(Ordering::Equal) => Endpoint {
+                        closed: lo.closed || next_lo.closed,
+                        ..lo.clone()
+                    },
+                    _ => lo.clone(),
+                };
+                let hi = match compare(&hi.value, &next_hi.value) {
+                    Some(Ordering::Greater) => hi.clone(),
+                    Some(Ordering::Less) => next_hi.clone(),
+                    Some(Ordering::Equal) => Endpoint {
+                        closed: hi.closed || next_hi.closed,
+                        ..hi.clone()
+                    },
+                    None => return Set::Union(vec![first.clone(), second.clone()]),
+                };
+                Set::Interval { lo, hi }
+            }
+            (Set::Union(_), _) | (_, Set::Union(_)) => {
+                let mut merged = Vec::new();
+                for set in [self, other] {
+                    match set {
+                        Set::Union(sets) => sets.iter().for_each(|set| absorb(&mut merged, set)),
+                        set => absorb(&mut merged, set),
+                    }
+                }
+                match merged.len() {
+                    1 => merged.pop().unwrap(),
+                    _ => Set::Union(merged),
+                }
+            }
+            _ => Set::Union(vec![self.clone(), other.clone()]),
+        }
+    }
+
+    /// The intersection of two sets, simplified where the order of their elements is decidable.
+    pub fn intersect(&self, other: &Set) -> Set {
+        let unevaluated = || Set::Intersection(vec![self.clone(), other.clone()]);
+        match (self, other) {
+            (Set::Empty, _) | (_, Set::Empty) => Set::Empty,
+            (Set::Finite(elements), set) | (set, Set::Finite(elements)) => {
+                let mut kept = Vec::new();
+                for x in elements {
+                    match set.contains(x) {
+                        Some(true) => kept.push(x.clone()),
+                        Some(false) => {}
+                        None => return unevaluated(),
+                    }
+                }
+                Set::finite(kept)
+            }
+            (Set::Interval { lo: a_lo, hi: a_hi }, Set::Interval { lo: b_lo, hi: b_hi }) => {
+                let lo = match compare(&a_lo.value, &b_lo.value) {
+                    Some(Ordering::Greater) => a_lo.clone(),
+                    Some(Ordering::Less) => b_lo.clone(),
+                    Some(Ordering::Equal) => Endpoint {
+                        closed: a_lo.closed && b_lo.closed,
+                        ..a_lo.clone()
+                    },
+                    None => return unevaluated(),
+                };
+                let hi = match compare(&a_hi.value, &b_hi.value) {
+                    Some(Ordering::Less) => a_hi.clone(),
+                    Some(Ordering::Greater) => b_hi.clone(),
+                    Some(Ordering::Equal) => Endpoint {
+                        closed: a_hi.closed && b_hi.closed,
+                        ..a_hi.clone()
+                    },
+                    None => return unevaluated(),
+                };
+                match compare(&lo.value, &hi.value) {
+                    Some(Ordering::Less) => Set::Interval { lo, hi },
+                    Some(Ordering::Equal) if lo.closed && hi.closed => Set::finite([lo.value]),
+                    Some(_) => Set::Empty,
+                    None => unevaluated(),
+                }
+            }
+            // Intersection distributes over union.
+            (Set::Union(sets), other) | (other, Set::Union(sets)) => sets
+                .iter()
+                .map(|set| set.intersect(other))
+                .fold(Set::Empty, |acc, set| acc.union(&set)),
+            _ => unevaluated(),
+        }
+    }
+}
+
+/// Adds `set` to the members of a union, merging it with every member it simplifies against.
+fn absorb(members: &mut Vec<Set>, set: &Set) {
+    let mut set = set.clone();
+    while let Some(i) = members
+        .iter()
+        .position(|member| !matches!(member.union(&set), Set::Union(_)))
+    {
+        set = members.remove(i).union(&set);
+    }
+    members.push(set);
+}
+
+/// The exact value of the float `x`, as a rational or, if that does not fit, as `m·2^e`.
+pub(crate) fn exact(x: f64) -> OpArgument {
+    if x.is_nan() {
+        return OpArgument::from(Value::Undefined);
+    }
+    if x.is_infinite() {
+        let inf = OpArgument::from(Value::Inf);
+        return if x < 0.0 { -inf } else { inf };
+    }
+    if x == 0.0 {
+        return OpArgument::from(Value::integer(0));
+    }
+    let bits = x.abs().to_bits();
+    let biased = (bits >> 52) as i64;
+    let fraction = bits & ((1 << 52) - 1);
+    let (mut mantissa, mut exponent) = match biased {
+        0 => (fraction, -1074),
+        _ => (fraction | 1 << 52, biased - 1075),
+    };
+    // Normalized to an odd mantissa, so `m·2^e` is written with the smallest numbers.
+    let zeros = mantissa.trailing_zeros();
+    mantissa >>= zeros;
+    exponent += zeros as i64;
+
+    let value = match exponent {
+        0.. if exponent < mantissa.leading_zeros() as i64 => {
+            OpArgument::from(Value::integer(mantissa << exponent))
+        }
+        ..=-1 if exponent > -64 => OpArgument::from(Value::rational(mantissa, 1 << -exponent)),
+        _ => {
+            let two = OpArgument::from(Value::integer(2));
+            let power = OpArgument::from(Value::integer(exponent.unsigned_abs()));
+            let power = if exponent < 0 { -power } else { power };
+            match mantissa {
+                1 => two.pow(&power),
+                _ => OpArgument::from(Value::integer(mantissa)) * two.pow(&power),
+            }
+        }
+    };
+    if x < 0.0 {
+        -value
+    } else {
+        value
+    }
+}
+
+impl From<Interval> for Set {
+    /// Converts the interval exactly; its infinite ends become open.
+    fn from(interval: Interval) -> Self {
+        if interval.is_empty() {
+            return Set::Empty;
+        }
+        let endpoint = |x: f64| Endpoint {
+            value: exact(x),
+            closed: x.is_finite(),
+        };
+        Set::Interval {
+            lo: endpoint(interval.lo),
+            hi: endpoint(interval.hi),
+        }
+    }
+}
+
+impl Display for Set {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |f: &mut std::fmt::Formatter<'_>, sets: &[Set], operator: &str| {
+            for (i, set) in sets.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " {} ", operator)?;
+                }
+                match set {
+                    Set::Union(_) | Set::Intersection(_) => write!(f, "({})", set)?,
+                    _ => write!(f, "{}", set)?,
+                }
+            }
+            Ok(())
+        };
+        match self {
+            Set::Empty => f.write_str("∅"),
+            Set::Finite(elements) => {
+                f.write_str("{")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                f.write_str("}")
+            }
+            Set::Interval { lo, hi } => write!(
+                f,
+                "{}{}, {}{}",
+                if lo.closed { '[' } else { '(' },
+                lo.value,
+                hi.value,
+                if hi.closed { ']' } else { ')' },
+            ),
+            Set::Union(sets) => join(f, sets, "∪"),
+            Set::Intersection(sets) => join(f, sets, "∩"),
+        }
+    }
+}
+
+/// The statement `element ∈ set`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Membership {
+    pub element: OpArgument,
+    pub set: Set,
+}
+
+impl Membership {
+    /// Decides the statement symbolically, if possible.
+    pub fn simplify(&self) -> Option<bool> {
+        self.set.contains(&self.element)
+    }
+
+    /// Decides the statement with the variables given in `bindings`, which must also bind the
+    /// variables in the endpoints and elements of the set.
+    pub fn evaluate(&self, bindings: &Bindings) -> Result<bool, EvaluationError> {
+        let x = self.element.evaluate(bindings)?;
+        fn go(set: &Set, x: f64, bindings: &Bindings) -> Result<bool, EvaluationError> {
+            Ok(match set {
+                Set::Empty => false,
+                Set::Finite(elements) => {
+                    for element in elements {
+                        if element.evaluate(bindings)? == x {
+                            return Ok(true);
+                        }
+                    }
+                    false
+                }
+                Set::Interval { lo, hi } => {
+                    let (a, b) = (lo.value.evaluate(bindings)?, hi.value.evaluate(bindings)?);
+                    (a < x || (lo.closed && a == x)) && (x < b || (hi.closed && x == b))
+                }
+                Set::Union(sets) => {
+                    for set in sets {
+                        if go(set, x, bindings)? {
+                            return Ok(true);
+                        }
+                    }
+                    false
+                }
+                Set::Intersection(sets) => {
+                    for set in sets {
+                        if !go(set, x, bindings)? {
+                            return Ok(false);
+                        }
+                    }
+                    true
+                }
+            })
+        }
+        go(&self.set, x, bindings)
+    }
+}
+
+impl Display for Membership {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ∈ {}", self.element, self.set)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Set;
+    use crate::{evaluation::Bindings, interval::Interval, parse::parse, symbols::variable};
+
+    #[test]
+    fn test_sets() {
+        let p = |input: &str| parse(input).unwrap();
+        let x = variable("x");
+
+        let circle = Set::interval(p("0"), true, p("pi"), false);
+        let small = Set::finite([p("1"), p("2"), p("3"), p("2")]);
+        assert_eq!(small, Set::finite([p("1"), p("2"), p("3")]));
+        assert_eq!(circle.contains(&p("pi")), Some(false));
+        assert_eq!(circle.contains(&p("pi/2")), Some(true));
+        assert_eq!(circle.contains(&x), None);
+
+        assert_eq!(
+            small.intersect(&circle),
+            Set::finite([p("1"), p("2"), p("3")])
+        );
+        assert_eq!(
+            small.intersect(&Set::closed(p("3/2"), p("10"))),
+            Set::finite([p("2"), p("3")])
+        );
+        assert_eq!(
+            circle.intersect(&Set::closed(p("3"), p("4"))),
+            Set::interval(p("3"), true, p("pi"), false)
+        );
+        assert_eq!(circle.intersect(&Set::closed(p("4"), p("5"))), Set::Empty);
+        assert_eq!(
+            Set::closed(p("0"), p("1")).intersect(&Set::closed(p("1"), p("2"))),
+            Set::finite([p("1")])
+        );
+
+        assert_eq!(
+            circle.union(&Set::closed(p("pi"), p("4"))),
+            Set::closed(p("0"), p("4"))
+        );
+        assert_eq!(circle.union(&small), circle);
+        assert_eq!(
+            circle.union(&Set::finite([p("4")])),
+            Set::Union(vec![circle.clone(), Set::finite([p("4")])])
+        );
+        let gap = Set::open(p("0"), p("1")).union(&Set::open(p("1"), p("2")));
+        assert!(matches!(gap, Set::Union(_)));
+        assert_eq!(gap.union(&Set::finite([p("1")])), Set::open(p("0"), p("2")));
+        assert_eq!(
+            gap.to_string(),
+            format!("({}, {}) ∪ ({}, {})", p("0"), p("1"), p("1"), p("2"))
+        );
+
+        // Symbolic endpoints stay unevaluated.
+        let symbolic = Set::closed(p("0"), x.clone()).intersect(&circle);
+        assert!(matches!(symbolic, Set::Intersection(_)));
+        let membership = symbolic.member(p("1"));
+        assert_eq!(membership.simplify(), None);
+        assert_eq!(
+            membership.evaluate(&Bindings::from_iter([("x", 2.0)])),
+            Ok(true)
+        );
+        assert_eq!(
+            membership.evaluate(&Bindings::from_iter([("x", 0.5)])),
+            Ok(false)
+        );
+
+        assert_eq!(
+            Set::from(Interval::new(0.5, f64::INFINITY)),
+            Set::interval(p("0.5"), true, p("inf"), false)
+        );
+        assert!(Set::from(Interval::EMPTY).is_empty());
+        let tiny = Set::from(Interval::point(-1e-300));
+        assert_eq!(tiny.contains(&p("-1/10^300")), None);
+        assert_eq!(tiny.contains(&p("0")), Some(false));
+
+        // Endpoints are written with the smallest numbers that give them exactly.
+        let display = |lo: f64, hi: f64| Set::from(Interval::new(lo, hi)).to_string();
+        assert_eq!(display(0.0, 1.0), "[0/1, 1/1]");
+        assert_eq!(
+            display(-0.75, 2f64.powi(60)),
+            "[-3/4, 1152921504606846976/1]"
+        );
+        assert_eq!(
+            display(2f64.powi(80), 2f64.powi(80)),
+            "[2/1^80/1, 2/1^80/1]"
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//! This module describes discrete-time signals and the linear filters that act on them.
+//!
+//! A [`Sequence`] is a signal `x[n]` that is zero outside a finite window, with symbolic
+//! samples, so filter responses can be derived for symbolic coefficients and then compiled
+//! with [`crate::compile::compile_many`]. A [`Filter`] is a difference equation
+//! `a₀·y[n] + a₁·y[n-1] + … = b₀·x[n] + b₁·x[n-1] + …`, whose transfer function is the ratio
+//! of the Z-transforms of its output and input.
+
+use std::ops::Add;
+
+use crate::{
+    constants::Value,
+    series::{plus, times, zero, Series, SeriesError},
+    symbols::OpArgument,
+};
+
+/// The sequence whose samples are `terms`, starting at index `start`, and zero elsewhere.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sequence {
+    start: i64,
+    terms: Vec<OpArgument>,
+}
+
+impl Sequence {
+    pub fn new(start: i64, terms: Vec<OpArgument>) -> Self {
+        Sequence {
+            start,
+            terms: terms.iter().map(OpArgument::fold).collect(),
+        }
+    }
+
+    /// The unit impulse `δ[n]`.
+    pub fn impulse() -> Self {
+        Sequence::new(0, vec![Value::integer(1).into()])
+    }
+
+    /// The index of the first sample in the window.
+    pub fn start(&self) -> i64 {
+        self.start
+    }
+
+    pub fn terms(&self) -> &[OpArgument] {
+        &self.terms
+    }
+
+    /// The sample `x[n]`.
+    pub fn at(&self, n: i64) -> OpArgument {
+        usize::try_from(n - self.start)
+            .ok()
+            .and_then(|i| self.terms.get(i).cloned())
+            .unwrap_or_else(zero)
+    }
+
+    /// The delayed sequence `x[n - k]`.
+    pub fn shift(&self, k: i64) -> Sequence {
+        Sequence {
+            start: self.start + k,
+            terms: self.terms.clone(),
+        }
+    }
+
+    /// The sequence with every sample multiplied by `c`.
+    pub fn scale(&self, c: &OpArgument) -> Sequence {
+        Sequence {
+            start: self.start,
+            terms: self.terms.iter().map(|x| times(x, &c.fold())).collect(),
+        }
+    }
+
+    /// The convolution `(x ∗ h)[n] = Σ x[k]·h[n - k]`.
+    pub fn convolve(&self, other: &Sequence) -> Sequence {
+        if self.terms.is_empty() || other.terms.is_empty() {
+            return Sequence::new(self.start + other.start, Vec::new());
+        }
+        let mut terms = vec![zero(); self.terms.len() + other.terms.len() - 1];
+        for (i, x) in self.terms.iter().enumerate() {
+            for (j, h) in other.terms.iter().enumerate() {
+                terms[i + j] = plus(&terms[i + j], &times(x, h));
+            }
+        }
+        Sequence {
+            start: self.start + other.start,
+            terms,
+        }
+    }
+
+    /// The Z-transform `Σ x[n]·z⁻ⁿ`.
+    pub fn z_transform(&self, z: &OpArgument) -> OpArgument {
+        (self.start..)
+            .zip(&self.terms)
+            .filter(|(_, x)| **x != zero())
+            .map(|(n, x)| {
+                let power = z.pow(&Value::integer(n.unsigned_abs()).into());
+                match n {
+                    0 => x.clone(),
+                    n if n > 0 => x / power,
+                    _ => x * power,
+                }
+            })
+            .reduce(|acc, term| acc + term)
+            .unwrap_or_else(zero)
+    }
+}
+
+impl Add<&Sequence> for &Sequence {
+    type Output = Sequence;
+
+    fn add(self, rhs: &Sequence) -> Sequence {
+        let start = self.start.min(rhs.start);
+        let end = (self.start + self.terms.len() as i64).max(rhs.start + rhs.terms.len() as i64);
+        Sequence {
+            start,
+            terms: (start..end)
+                .map(|n| plus(&self.at(n), &rhs.at(n)))
+                .collect(),
+        }
+    }
+}
+
+/// The linear time-invariant filter `Σ feedback[k]·y[n-k] = Σ feedforward[k]·x[n-k]`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Filter {
+    feedforward: Vec<OpArgument>,
+    feedback: Vec<OpArgument>,
+}
+
+impl Filter {
+    pub fn new(feedforward: Vec<OpArgument>, feedback: Vec<OpArgument>) -> Self {
+        assert!(
+            !feedback.is_empty(),
+            "Hold on, a filter needs at least the coefficient of y[n]"
+        );
+        Filter {
+            feedforward: feedforward.iter().map(OpArgument::fold).collect(),
+            feedback: feedback.iter().map(OpArgument::fold).collect(),
+        }
+    }
+
+    /// The finite impulse response filter `y[n] = Σ taps[k]·x[n-k]`.
+    pub fn fir(taps: Vec<OpArgument>) -> Self {
+        Filter::new(taps, vec![Value::integer(1).into()])
+    }
+
+    pub fn feedforward(&self) -> &[OpArgument] {
+        &self.feedforward
+    }
+
+    pub fn feedback(&self) -> &[OpArgument] {
+        &self.feedback
+    }
+
+    /// The transfer function `H(z) = Σ bₖ·z⁻ᵏ / Σ aₖ·z⁻ᵏ`.
+    pub fn transfer_function(&self, z: &OpArgument) -> OpArgument {
+        let numerator = Sequence::new(0, self.feedforward.clone()).z_transform(z);
+        match &self.feedback[..] {
+            [one] if *one == Value::integer(1).into() => numerator,
+            feedback => numerator / Sequence::new(0, feedback.to_vec()).z_transform(z),
+        }
+    }
+
+    /// The first `count` samples of the response to `δ[n]`, the power series of `H` in `z⁻¹`.
+    pub fn impulse_response(&self, count: usize) -> Result<Sequence, SeriesError> {
+        let response = Series::polynomial(&self.feedforward, count)
+            .checked_div(&Series::polynomial(&self.feedback, count))?;
+        Ok(Sequence {
+            start: 0,
+            terms: response.coefficients().to_vec(),
+        })
+    }
+
+    /// The first `count` samples of the response to `input`, from its start on, assuming the
+    /// filter is at rest before it.
+    pub fn apply(&self, input: &Sequence, count: usize) -> Result<Sequence, SeriesError> {
+        let mut output = input.convolve(&self.impulse_response(count)?);
+        output.terms.truncate(count);
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Filter, Sequence};
+    use crate::{compile::compile_many, parse::parse, symbols::variable};
+
+    #[test]
+    fn test_signals() {
+        let p = |input: &str| parse(input).unwrap().fold();
+        let sequence =
+            |start, terms: &[&str]| Sequence::new(start, terms.iter().map(|t| p(t)).collect());
+
+        let pair = sequence(0, &["1", "1"]);
+        assert_eq!(pair.convolve(&pair), sequence(0, &["1", "2", "1"]));
+        assert_eq!(pair.shift(2).at(3), p("1"));
+        assert_eq!(pair.shift(2).at(1), p("0"));
+        assert_eq!(&pair + &pair.shift(1), sequence(0, &["1", "2", "1"]));
+        assert_eq!(pair.convolve(&Sequence::impulse().shift(-1)).start(), -1);
+        assert_eq!(pair.convolve(&pair).z_transform(&p("2")).fold(), p("9/4"));
+
+        // y[n] = a·y[n-1] + x[n] responds to δ[n] with aⁿ, for any a.
+        let a = variable("a");
+        let smoothing = Filter::new(vec![p("1")], vec![p("1"), -a.clone()]);
+        let response = smoothing.impulse_response(4).unwrap();
+        let program = compile_many(response.terms(), &["a"]).unwrap();
+        assert_eq!(program.evaluate(&[0.5]), [1.0, 0.5, 0.25, 0.125]);
+        assert_eq!(
+            smoothing
+                .transfer_function(&p("2"))
+                .evaluate(&[("a", 0.5)].into_iter().collect())
+                .unwrap(),
+            4.0 / 3.0
+        );
+
+        // A moving average smooths a step.
+        let average = Filter::fir(vec![p("1/2"), p("1/2")]);
+        let step = sequence(0, &["1", "1", "1", "1"]);
+        assert_eq!(
+            average.apply(&step, 4).unwrap(),
+            sequence(0, &["1/2", "1", "1", "1"])
+        );
+        assert_eq!(average.transfer_function(&p("1")).fold(), p("1"));
+    }
+}
@@ -0,0 +1,159 @@
+//! This module describes how to solve equations over our computational graph.
+
+use std::fmt::Display;
+
+use crate::{
+    constants::Value,
+    lambda::Lambda,
+    symbols::{
+        variable, OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        OperationKind::*,
+    },
+};
+
+/// The reasons [`invert`] can fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvertError {
+    /// The expression does not depend on the variable.
+    Independent,
+    /// The subexpression cannot be undone: the variable occurs in more than one of its
+    /// arguments, or its operation is not one-to-one.
+    NotInvertible(OpArgument),
+}
+
+impl Display for InvertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvertError::Independent => f.write_str("expression does not depend on the variable"),
+            InvertError::NotInvertible(node) => write!(f, "{} is not invertible", node),
+        }
+    }
+}
+
+impl std::error::Error for InvertError {}
+
+/// Returns `(negative, num, den)` if `node` is the constant `±num/den`.
+fn signed_rational(node: &OpArgument) -> Option<(bool, u64, u64)> {
+    match &node.value {
+        Leaf(value) => match **value {
+            Value::Rational(num, den) => Some((false, num, den.get())),
+            _ => None,
+        },
+        Op(op) if op.op == Negation => {
+            let (negative, num, den) = signed_rational(&op.arguments[0])?;
+            Some((!negative, num, den))
+        }
+        Op(_) => None,
+    }
+}
+
+/// Solves `y = expr` for `var`, returning the inverse function `y ↦ var`. Its parameter is
+/// named `var`, so `invert(expr, var)?.apply(&[expr])` simplifies to `var`.
+///
+/// Each operation on the path from the root to `var` is undone in turn, so `var` must occur
+/// exactly once in `expr` and every operation on the path must be one-to-one: sums,
+/// differences, products and quotients with expressions independent of `var`, negation, `exp`,
+/// `ln`, `tan`, `atan`, odd powers, and powers of a constant base. Inverses of odd powers are
+/// principal roots, which are real only for nonnegative arguments.
+pub fn invert(expr: &OpArgument, var: &'static str) -> Result<Lambda, InvertError> {
+    let mut node = expr;
+    let mut rhs = variable(var);
+
+    loop {
+        let op = match &node.value {
+            Leaf(value) if **value == Value::Variable(var) => {
+                return Ok(Lambda::new(vec![var], rhs))
+            }
+            Leaf(_) => return Err(InvertError::Independent),
+            Op(op) => op,
+        };
+
+        let mut dependent = op
+            .arguments
+            .iter()
+            .enumerate()
+            .filter(|(_, arg)| arg.free_variables().contains(var));
+        let (i, inner) = match (dependent.next(), dependent.next()) {
+            (None, _) => return Err(InvertError::Independent),
+            (Some(arg), None) => arg,
+            (Some(_), Some(_)) => return Err(InvertError::NotInvertible(node.clone())),
+        };
+        let other = op.arguments.get(1 - i);
+        let not_invertible = || InvertError::NotInvertible(node.clone());
+
+        rhs = match (op.op, i, other) {
+            (Addition, _, Some(other)) => &rhs - other,
+            (Subtraction, 0, Some(other)) => &rhs + other,
+            (Subtraction, _, Some(other)) => other - &rhs,
+            (Multiplication, _, Some(other)) => &rhs / other,
+            (Division, 0, Some(other)) => &rhs * other,
+            (Division, _, Some(other)) => other / &rhs,
+            (Negation, _, _) => -&rhs,
+            (Exp, _, _) => rhs.ln(),
+            (Ln, _, _) => rhs.exp(),
+            (Tan, _, _) => rhs.atan(),
+            (Atan, _, _) => rhs.tan(),
+            (Pow, 0, Some(exponent)) => match signed_rational(exponent) {
+                Some((negative, num, den)) if num % 2 == 1 => {
+                    let root = OpArgument::from(Value::rational(den, num));
+                    rhs.pow(&if negative { -root } else { root })
+                }
+                _ => return Err(not_invertible()),
+            },
+            (Pow, _, Some(base)) => rhs.ln() / base.ln(),
+            _ => return Err(not_invertible()),
+        };
+        node = inner;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{invert, InvertError};
+    use crate::{
+        constants::Value,
+        evaluation::Bindings,
+        symbols::{variable, OpArgument},
+    };
+
+    #[test]
+    fn test_invert() {
+        let x = variable("x");
+        let y = variable("y");
+        let int = |n| OpArgument::from(Value::integer(n));
+
+        assert_eq!(
+            invert(&(int(2) * &x + int(1)), "x").unwrap().body(),
+            &((&x - int(1)) / int(2))
+        );
+        assert_eq!(
+            invert(&(int(3) * &x).exp(), "x").unwrap().body(),
+            &(x.ln() / int(3))
+        );
+        assert_eq!(
+            invert(&x.pow(&-int(3)), "x").unwrap().body(),
+            &x.pow(&-OpArgument::from(Value::rational(1, 3)))
+        );
+
+        // Round trip through every kind of step, with another variable along for the ride.
+        let f = ((&y / (x.tan() - &y)).exp().pow(&int(5)) + int(7)).ln();
+        let f = int(2).pow(&(&y - f));
+        let inverse = invert(&f, "x").unwrap();
+        let mut bindings = Bindings::default();
+        bindings.insert("x", 0.3);
+        bindings.insert("y", -0.7);
+        let roundtrip = inverse.apply(std::slice::from_ref(&f));
+        assert!((roundtrip.evaluate(&bindings).unwrap() - 0.3).abs() < 1e-12);
+
+        assert_eq!(
+            invert(&(x.pow(&int(2)) + int(1)), "x"),
+            Err(InvertError::NotInvertible(x.pow(&int(2))))
+        );
+        assert_eq!(
+            invert(&(&x * &x).sin(), "x"),
+            Err(InvertError::NotInvertible((&x * &x).sin()))
+        );
+        assert_eq!(invert(&y.exp(), "x"), Err(InvertError::Independent));
+    }
+}
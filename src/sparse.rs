@@ -0,0 +1,553 @@
+//! This module describes sparse polynomials with rational coefficients, for expanding very large
+//! products.
+//!
+//! [`OpArgument::collect`] keeps expression coefficients, which is general but slow once a
+//! product has tens of thousands of terms. A [`SparsePolynomial`] instead packs the exponents
+//! of each monomial into one machine word, so multiplying monomials is an integer addition and
+//! comparing them an integer comparison, and keeps its terms in a hash map from packed monomials
+//! to exact rationals. Products use Johnson's heap multiplication: the terms of `f·g` are merged
+//! from a heap holding at most one candidate per term of `f`, so they come out in order and
+//! like terms are combined as they appear, instead of all `|f|·|g|` products being built first.
+//! Packed monomials forget the order of their factors, so products that would move symbols
+//! declared non-commutative past each other are refused, as [`OpArgument::collect`] refuses them.
+
+use std::{
+    collections::{binary_heap::PeekMut, BinaryHeap},
+    fmt::Display,
+};
+
+use ahash::HashMap;
+use smallvec::smallvec;
+
+use crate::{
+    collect::Collected,
+    constants::Value,
+    fold::Ratio,
+    series::zero,
+    symbols::{
+        is_commutative_symbol, variable, OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        Operation,
+        OperationKind::*,
+    },
+};
+
+/// The reasons an expression can fail to become a sparse polynomial.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SparseError {
+    /// The subexpression is not a polynomial in the variables, such as `sin(x)` or `1/x`.
+    NotPolynomial(OpArgument),
+    /// The subexpression is a coefficient that is not a rational number.
+    NotRationalCoefficient(OpArgument),
+    /// A coefficient grew too large to represent exactly.
+    Overflow,
+    /// An exponent grew too large for its share of a packed monomial.
+    DegreeTooLarge,
+    /// The product moves symbols declared non-commutative past each other, which packed
+    /// monomials cannot keep in order.
+    NonCommutative(OpArgument),
+}
+
+impl Display for SparseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SparseError::NotPolynomial(expr) => write!(f, "{} is not a polynomial", expr),
+            SparseError::NotRationalCoefficient(expr) => {
+                write!(f, "{} is not a rational coefficient", expr)
+            }
+            SparseError::Overflow => f.write_str("a coefficient overflowed"),
+            SparseError::DegreeTooLarge => f.write_str("an exponent is too large to pack"),
+            SparseError::NonCommutative(expr) => {
+                write!(f, "{} multiplies factors that do not commute", expr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SparseError {}
+
+/// A polynomial with rational coefficients in a fixed list of variables.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparsePolynomial {
+    variables: Vec<&'static str>,
+    /// Nonzero coefficients by packed monomial. The exponent of the first variable is in the
+    /// highest bits, so comparing packed monomials compares them lexicographically.
+    terms: HashMap<u64, Ratio>,
+}
+
+/// The term `f[i]·g[j]` of a product, ordered by its monomial first for the max-heap.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Candidate {
+    monomial: u64,
+    i: usize,
+    j: usize,
+}
+
+impl SparsePolynomial {
+    fn with_terms(variables: &[&'static str], terms: HashMap<u64, Ratio>) -> Self {
+        SparsePolynomial {
+            variables: variables.to_vec(),
+            terms,
+        }
+    }
+
+    fn constant(variables: &[&'static str], c: Ratio) -> Self {
+        let mut terms = HashMap::default();
+        if c != Ratio::ZERO {
+            terms.insert(0, c);
+        }
+        SparsePolynomial::with_terms(variables, terms)
+    }
+
+    /// The bits each exponent takes in a packed monomial.
+    fn bits(&self) -> u32 {
+        64 / self.variables.len().max(1) as u32
+    }
+
+    fn exponent(&self, monomial: u64, var: usize) -> u64 {
+        let bits = self.bits();
+        let shift = bits * (self.variables.len() - 1 - var) as u32;
+        (monomial >> shift) & (u64::MAX >> (64 - bits))
+    }
+
+    fn unpack(&self, monomial: u64) -> Vec<u32> {
+        (0..self.variables.len())
+            .map(|var| self.exponent(monomial, var) as u32)
+            .collect()
+    }
+
+    /// The monomial of the variable `var`.
+    fn variable(&self, var: usize) -> u64 {
+        1 << (self.bits() * (self.variables.len() - 1 - var) as u32)
+    }
+
+    /// The largest exponent of each variable.
+    fn degrees(&self) -> Vec<u64> {
+        (0..self.variables.len())
+            .map(|var| {
+                self.terms
+                    .keys()
+                    .map(|&m| self.exponent(m, var))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+
+    /// Reads `expr` as a polynomial in `variables` with rational coefficients, expanding
+    /// products as it goes.
+    pub fn from_expression(
+        expr: &OpArgument,
+        variables: &[&'static str],
+    ) -> Result<Self, SparseError> {
+        assert!(
+            variables.len() <= 64,
+            "Whoa there, a packed monomial has room for at most 64 variables, not {}",
+            variables.len()
+        );
+        let vars = expr.free_variables();
+        if variables.iter().all(|var| !vars.contains(var)) {
+            let c = Ratio::of(&expr.fold())
+                .ok_or_else(|| SparseError::NotRationalCoefficient(expr.clone()))?;
+            return Ok(SparsePolynomial::constant(variables, c));
+        }
+        let not_polynomial = || SparseError::NotPolynomial(expr.clone());
+        let op = match &expr.value {
+            Leaf(_) => {
+                let var = variables
+                    .iter()
+                    .position(|var| vars.contains(var))
+                    .ok_or_else(not_polynomial)?;
+                let mut p = SparsePolynomial::constant(variables, Ratio::ZERO);
+                p.terms.insert(p.variable(var), Ratio::ONE);
+                return Ok(p);
+            }
+            Op(op) => op,
+        };
+        let arg = |i: usize| SparsePolynomial::from_expression(&op.arguments[i], variables);
+        // Report the whole product, as collecting does, rather than its expanded factors.
+        let here = |error| match error {
+            SparseError::NonCommutative(_) => SparseError::NonCommutative(expr.clone()),
+            error => error,
+        };
+        match op.op {
+            Addition => arg(0)?.add(&arg(1)?),
+            Subtraction => arg(0)?.add(&arg(1)?.neg()),
+            Negation => Ok(arg(0)?.neg()),
+            Multiplication => arg(0)?.mul(&arg(1)?).map_err(here),
+            Division => {
+                let divisor = &op.arguments[1];
+                let scale = Ratio::of(&divisor.fold())
+                    .and_then(Ratio::recip)
+                    .ok_or_else(not_polynomial)?;
+                arg(0)?.scale(scale)
+            }
+            Pow => {
+                let k = Ratio::of(&op.arguments[1].fold())
+                    .filter(|k| k.den == 1 && k.num >= 0)
+                    .and_then(|k| u32::try_from(k.num).ok())
+                    .ok_or_else(not_polynomial)?;
+                arg(0)?.pow(k).map_err(here)
+            }
+            _ => Err(not_polynomial()),
+        }
+    }
+
+    pub fn variables(&self) -> &[&'static str] {
+        &self.variables
+    }
+
+    /// The number of nonzero terms.
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// The monomials with a nonzero coefficient and their coefficients as a numerator and a
+    /// positive denominator in lowest terms, in decreasing lexicographic order of the exponents.
+    pub fn terms(&self) -> Vec<(Vec<u32>, (i128, i128))> {
+        self.sorted()
+            .into_iter()
+            .map(|(m, c)| (self.unpack(m), (c.num, c.den)))
+            .collect()
+    }
+
+    /// The coefficient of the monomial with `exponents` as a numerator and a denominator, which
+    /// is `(0, 1)` if it does not occur.
+    pub fn coefficient(&self, exponents: &[u32]) -> (i128, i128) {
+        assert_eq!(
+            exponents.len(),
+            self.variables.len(),
+            "Hold on, a monomial needs one exponent per variable"
+        );
+        let limit = u64::MAX >> (64 - self.bits());
+        if exponents.iter().any(|&k| k as u64 > limit) {
+            return (0, 1);
+        }
+        let monomial = exponents
+            .iter()
+            .enumerate()
+            .fold(0, |m, (var, &k)| m + k as u64 * self.variable(var));
+        let c = self.terms.get(&monomial).unwrap_or(&Ratio::ZERO);
+        (c.num, c.den)
+    }
+
+    /// The terms, in decreasing order of their packed monomials.
+    fn sorted(&self) -> Vec<(u64, Ratio)> {
+        let mut terms = self.terms.iter().map(|(&m, &c)| (m, c)).collect::<Vec<_>>();
+        terms.sort_unstable_by_key(|&(m, _)| std::cmp::Reverse(m));
+        terms
+    }
+
+    pub fn add(&self, other: &SparsePolynomial) -> Result<Self, SparseError> {
+        let mut terms = self.terms.clone();
+        for (&m, &c) in &other.terms {
+            let sum = match terms.remove(&m) {
+                Some(previous) => previous.checked_add(c).ok_or(SparseError::Overflow)?,
+                None => c,
+            };
+            if sum != Ratio::ZERO {
+                terms.insert(m, sum);
+            }
+        }
+        Ok(SparsePolynomial::with_terms(&self.variables, terms))
+    }
+
+    pub fn neg(&self) -> Self {
+        let terms = self.terms.iter().map(|(&m, &c)| (m, -c)).collect();
+        SparsePolynomial::with_terms(&self.variables, terms)
+    }
+
+    fn scale(&self, factor: Ratio) -> Result<Self, SparseError> {
+        let terms = self
+            .terms
+            .iter()
+            .map(|(&m, &c)| Some((m, c.checked_mul(factor)?)))
+            .collect::<Option<HashMap<_, _>>>()
+            .ok_or(SparseError::Overflow)?;
+        Ok(SparsePolynomial::with_terms(&self.variables, terms))
+    }
+
+    /// The variables of `monomial` that do not commute.
+    fn non_commuting(&self, monomial: u64) -> Vec<usize> {
+        (0..self.variables.len())
+            .filter(|&var| {
+                self.exponent(monomial, var) > 0 && !is_commutative_symbol(self.variables[var])
+            })
+            .collect()
+    }
+
+    /// Whether every term of `self` times every term of `other` is the same product in either
+    /// order, as [`crate::collect`] requires: coefficients are rational, so only the monomials
+    /// matter, and two of them commute unless both hold a non-commutative symbol, other than
+    /// powers of one and the same.
+    fn commutes_with(&self, other: &SparsePolynomial) -> bool {
+        if self.variables.iter().all(|var| is_commutative_symbol(var)) {
+            return true;
+        }
+        let ms: Vec<_> = self.terms.keys().map(|&m| self.non_commuting(m)).collect();
+        let ns: Vec<_> = other
+            .terms
+            .keys()
+            .map(|&n| other.non_commuting(n))
+            .collect();
+        ms.iter().all(|m| {
+            ns.iter()
+                .all(|n| m.is_empty() || n.is_empty() || (m.len() == 1 && m == n))
+        })
+    }
+
+    /// The product, by Johnson's heap multiplication.
+    pub fn mul(&self, other: &SparsePolynomial) -> Result<Self, SparseError> {
+        assert_eq!(
+            self.variables, other.variables,
+            "Oops, only polynomials in the same variables can be multiplied"
+        );
+        // Packed monomials add field by field as long as no exponent carries into the next.
+        let limit = u64::MAX >> (64 - self.bits());
+        let fits = self
+            .degrees()
+            .iter()
+            .zip(other.degrees())
+            .all(|(a, b)| a.checked_add(b).is_some_and(|sum| sum <= limit));
+        if !fits {
+            return Err(SparseError::DegreeTooLarge);
+        }
+        if !self.commutes_with(other) {
+            let product = &self.to_expression()? * &other.to_expression()?;
+            return Err(SparseError::NonCommutative(product));
+        }
+
+        let (f, g) = (self.sorted(), other.sorted());
+        let mut terms = HashMap::default();
+        if f.is_empty() || g.is_empty() {
+            return Ok(SparsePolynomial::with_terms(&self.variables, terms));
+        }
+        // The heap holds `f[i]·g[j]` for the next `j` of each `i` that has started, and row
+        // `i + 1` starts once `f[i]·g[0]` has been taken, as it can't be larger before that.
+        let mut heap = BinaryHeap::with_capacity(f.len());
+        heap.push(Candidate {
+            monomial: f[0].0 + g[0].0,
+            i: 0,
+            j: 0,
+        });
+        let mut current: Option<(u64, Ratio)> = None;
+        loop {
+            let Some(mut top) = heap.peek_mut() else {
+                break;
+            };
+            let Candidate { monomial, i, j } = *top;
+            // Replacing the top with the next term of its row sifts once instead of twice.
+            match j + 1 < g.len() {
+                true => {
+                    *top = Candidate {
+                        monomial: f[i].0 + g[j + 1].0,
+                        i,
+                        j: j + 1,
+                    };
+                    drop(top);
+                }
+                false => {
+                    PeekMut::pop(top);
+                }
+            }
+            if j == 0 && i + 1 < f.len() {
+                heap.push(Candidate {
+                    monomial: f[i + 1].0 + g[0].0,
+                    i: i + 1,
+                    j: 0,
+                });
+            }
+
+            let product = multiply(f[i].1, g[j].1).ok_or(SparseError::Overflow)?;
+            current = match current {
+                Some((m, c)) if m == monomial => {
+                    Some((m, add(c, product).ok_or(SparseError::Overflow)?))
+                }
+                Some((m, c)) => {
+                    if c != Ratio::ZERO {
+                        terms.insert(m, c);
+                    }
+                    Some((monomial, product))
+                }
+                None => Some((monomial, product)),
+            };
+        }
+        if let Some((m, c)) = current.filter(|&(_, c)| c != Ratio::ZERO) {
+            terms.insert(m, c);
+        }
+        Ok(SparsePolynomial::with_terms(&self.variables, terms))
+    }
+
+    /// `self^k`, by repeated squaring.
+    pub fn pow(&self, mut k: u32) -> Result<Self, SparseError> {
+        let mut result = SparsePolynomial::constant(&self.variables, Ratio::ONE);
+        let mut base = self.clone();
+        while k > 0 {
+            if k & 1 == 1 {
+                result = result.mul(&base)?;
+            }
+            k >>= 1;
+            if k > 0 {
+                base = base.mul(&base)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// The same terms with expression coefficients, as [`OpArgument::collect`] gives them.
+    /// Coefficients too large for a rational constant are reported as overflowing.
+    pub fn to_collected(&self) -> Result<Collected, SparseError> {
+        let terms = self
+            .terms
+            .iter()
+            .map(|(&m, &c)| Ok((self.unpack(m), coefficient(c)?)))
+            .collect::<Result<_, SparseError>>()?;
+        Ok(Collected::from_terms(&self.variables, terms))
+    }
+
+    /// The sum of the terms, in decreasing lexicographic order. Coefficients too large for a
+    /// rational constant are reported as overflowing.
+    pub fn to_expression(&self) -> Result<OpArgument, SparseError> {
+        let terms = self
+            .sorted()
+            .into_iter()
+            .map(|(m, c)| {
+                let powers = self.unpack(m).into_iter().zip(&self.variables).filter_map(
+                    |(k, &var)| match k {
+                        0 => None,
+                        1 => Some(variable(var)),
+                        k => Some(variable(var).pow(&Value::integer(k as u64).into())),
+                    },
+                );
+                Ok(
+                    powers.fold(coefficient(c)?, |term, power| match Ratio::of(&term) {
+                        Some(Ratio::ONE) => power,
+                        _ => Operation::new(Multiplication, smallvec![term, power]).into(),
+                    }),
+                )
+            })
+            .collect::<Result<Vec<_>, SparseError>>()?;
+        Ok(terms
+            .into_iter()
+            .reduce(|sum, term| &sum + &term)
+            .unwrap_or_else(zero))
+    }
+}
+
+/// `a·b`, skipping the reduction to lowest terms for integers, which are the usual case.
+fn multiply(a: Ratio, b: Ratio) -> Option<Ratio> {
+    match (a.den, b.den) {
+        (1, 1) => Some(Ratio {
+            num: a.num.checked_mul(b.num)?,
+            den: 1,
+        }),
+        _ => a.checked_mul(b),
+    }
+}
+
+/// `a + b`, skipping the reduction to lowest terms for integers.
+fn add(a: Ratio, b: Ratio) -> Option<Ratio> {
+    match (a.den, b.den) {
+        (1, 1) => Some(Ratio {
+            num: a.num.checked_add(b.num)?,
+            den: 1,
+        }),
+        _ => a.checked_add(b),
+    }
+}
+
+fn coefficient(c: Ratio) -> Result<OpArgument, SparseError> {
+    c.to_oparg().ok_or(SparseError::Overflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SparseError, SparsePolynomial};
+    use crate::{
+        parse::parse,
+        symbols::{declare_non_commutative, intern},
+    };
+
+    #[test]
+    fn test_sparse_polynomials() {
+        let p = |input: &str| parse(input).unwrap();
+        let sparse = |input: &str, vars: &[&'static str]| {
+            SparsePolynomial::from_expression(&p(input), vars).unwrap()
+        };
+
+        // The same terms as collecting with expression coefficients.
+        let expr = "(x + y + 2*z - 1/2)^5 * (x - y + 3)^3 / 4";
+        let small = sparse(expr, &["x", "y", "z"]);
+        assert_eq!(
+            small.to_collected().unwrap(),
+            p(expr).collect(&["x", "y", "z"]).unwrap()
+        );
+        assert_eq!(small.coefficient(&[8, 0, 0]), (1, 4));
+        assert_eq!(small.terms()[0], (vec![8, 0, 0], (1, 4)));
+        let roundtrip = small.to_expression().unwrap();
+        assert_eq!(sparse(&roundtrip.to_string(), &["x", "y", "z"]), small);
+
+        // A product with thousands of terms, whose middle coefficient is 16!/(4!)⁴.
+        let vars = ["x", "y", "z", "t"];
+        let eighth = sparse("(1 + x + y + z + t)^8", &vars);
+        assert_eq!(eighth.len(), 495);
+        let product = eighth.mul(&eighth).unwrap();
+        assert_eq!(product.len(), 4845);
+        assert_eq!(product, eighth.pow(2).unwrap());
+        assert_eq!(product.coefficient(&[4, 4, 4, 4]), (63063000, 1));
+        let cancelled = product.add(&product.neg()).unwrap();
+        assert!(cancelled.is_empty());
+
+        let xy: [&'static str; 2] = ["x", "y"];
+        assert_eq!(
+            SparsePolynomial::from_expression(&p("sin(x) + y"), &xy),
+            Err(SparseError::NotPolynomial(p("sin(x)")))
+        );
+        assert_eq!(
+            SparsePolynomial::from_expression(&p("a*x"), &xy),
+            Err(SparseError::NotRationalCoefficient(p("a")))
+        );
+        assert_eq!(
+            SparsePolynomial::from_expression(&p("x/y"), &xy),
+            Err(SparseError::NotPolynomial(p("x/y")))
+        );
+        // With 32 variables, each exponent gets two bits.
+        let many = (0..32)
+            .map(|i| intern(&format!("v{}", i)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            SparsePolynomial::from_expression(&p("v0^3"), &many).map(|p| p.len()),
+            Ok(1)
+        );
+        assert_eq!(
+            SparsePolynomial::from_expression(&p("v0^4"), &many),
+            Err(SparseError::DegreeTooLarge)
+        );
+    }
+
+    #[test]
+    fn test_non_commutative() {
+        let a = declare_non_commutative("sparse_A");
+        let b = declare_non_commutative("sparse_B");
+        let p = |input: &str| parse(input).unwrap();
+        let sparse = |input: &str| SparsePolynomial::from_expression(&p(input), &[a, b, "x"]);
+
+        // AB - BA is not zero, however the packed monomials would have it.
+        assert_eq!(
+            sparse("sparse_A*sparse_B - sparse_B*sparse_A"),
+            Err(SparseError::NonCommutative(p("sparse_A*sparse_B")))
+        );
+        assert_eq!(
+            sparse("(sparse_A + sparse_B)^2"),
+            Err(SparseError::NonCommutative(p("(sparse_A + sparse_B)^2")))
+        );
+        // Powers of one operator still multiply, and commuting variables move freely.
+        let fine = sparse("(sparse_A + x) * (sparse_A*x - x)").unwrap();
+        assert_eq!(fine.coefficient(&[2, 0, 1]), (1, 1));
+        assert_eq!(fine.coefficient(&[0, 0, 2]), (-1, 1));
+    }
+}
@@ -0,0 +1,806 @@
+//! This module describes special functions of mathematical physics: Bessel functions of the
+//! first kind, Legendre polynomials and generalized hypergeometric functions.
+//!
+//! A [`Special`] is kept outside the computational graph, like a [`crate::sum::Sum`], with
+//! expressions for its parameters and argument. It evaluates numerically, and
+//! [`Special::closed_form`] writes it as an ordinary expression where one is known: every
+//! Legendre polynomial, Bessel functions of half-integer order, and hypergeometric functions
+//! that terminate or reduce to exponentials, powers and logarithms. The three-term recurrences
+//! are exposed by [`Special::recurrence`], and [`Special::reduce`] applies them until only the
+//! lowest orders are left, which is how identities between Bessel functions of different
+//! orders are usually simplified.
+//!
+//! The classical orthogonal polynomials, [`chebyshev_t`], [`chebyshev_u`], [`legendre_p`],
+//! [`hermite_h`] and [`laguerre_l`], are generated from their recurrences as expanded
+//! expressions with exact coefficients, ready to be evaluated, differentiated or compared with
+//! the approximations of [`crate::approx`].
+
+use std::fmt::Display;
+
+use crate::{
+    evaluation::{Bindings, EvaluationError},
+    fold::Ratio,
+    symbols::{OpArgument, OpArgumentKind::Op, OperationKind::Negation},
+};
+
+/// Series are summed until their terms fall below this fraction of the sum.
+const EPSILON: f64 = 1e-17;
+
+/// Series that have not converged after this many terms evaluate to NaN.
+const MAX_TERMS: usize = 10_000;
+
+/// A special function applied to an argument.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Special {
+    /// The Bessel function of the first kind `J_order(arg)`.
+    BesselJ { order: OpArgument, arg: OpArgument },
+    /// The Legendre polynomial `P_degree(arg)`.
+    Legendre { degree: u32, arg: OpArgument },
+    /// The generalized hypergeometric function `pFq(upper; lower; arg)`.
+    Hypergeometric {
+        upper: Vec<OpArgument>,
+        lower: Vec<OpArgument>,
+        arg: OpArgument,
+    },
+}
+
+/// A sum of special functions with expressions as coefficients.
+pub type Combination = Vec<(OpArgument, Special)>;
+
+fn constant(r: Ratio) -> OpArgument {
+    r.to_oparg()
+        .expect("Oops, a coefficient of a recurrence overflowed")
+}
+
+fn integer(n: i128) -> OpArgument {
+    constant(Ratio { num: n, den: 1 })
+}
+
+/// The rational constant `node` folds to.
+fn ratio(node: &OpArgument) -> Option<Ratio> {
+    Ratio::of(&node.fold())
+}
+
+/// Adds `coefficient·function` to `terms`, merging it with an equal function.
+fn accumulate(terms: &mut Combination, coefficient: OpArgument, function: Special) {
+    match terms.iter_mut().find(|(_, f)| *f == function) {
+        Some((c, _)) => *c = (&*c + &coefficient).fold(),
+        None => terms.push((coefficient.fold(), function)),
+    }
+}
+
+impl Special {
+    pub fn bessel_j(order: OpArgument, arg: OpArgument) -> Self {
+        Special::BesselJ { order, arg }
+    }
+
+    pub fn legendre(degree: u32, arg: OpArgument) -> Self {
+        Special::Legendre { degree, arg }
+    }
+
+    pub fn hypergeometric(upper: Vec<OpArgument>, lower: Vec<OpArgument>, arg: OpArgument) -> Self {
+        Special::Hypergeometric { upper, lower, arg }
+    }
+
+    /// Evaluates the function with the variables given in `bindings`. Values that are not
+    /// real, such as Bessel functions of fractional order at negative arguments, and
+    /// hypergeometric series outside their disc of convergence are NaN.
+    pub fn evaluate(&self, bindings: &Bindings) -> Result<f64, EvaluationError> {
+        Ok(match self {
+            Special::BesselJ { order, arg } => {
+                bessel_j(order.evaluate(bindings)?, arg.evaluate(bindings)?)
+            }
+            Special::Legendre { degree, arg } => legendre(*degree, arg.evaluate(bindings)?),
+            Special::Hypergeometric { upper, lower, arg } => {
+                let upper = upper
+                    .iter()
+                    .map(|a| a.evaluate(bindings))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let lower = lower
+                    .iter()
+                    .map(|b| b.evaluate(bindings))
+                    .collect::<Result<Vec<_>, _>>()?;
+                hypergeometric(&upper, &lower, arg.evaluate(bindings)?)
+            }
+        })
+    }
+
+    /// The function as an expression, if it has a known closed form.
+    pub fn closed_form(&self) -> Option<OpArgument> {
+        match self {
+            Special::BesselJ { order, arg } => bessel_half_integer(ratio(order)?, arg),
+            Special::Legendre { degree, arg } => legendre_p(*degree, arg),
+            Special::Hypergeometric { upper, lower, arg } => {
+                hypergeometric_closed_form(upper, lower, arg)
+            }
+        }
+    }
+
+    /// Writes the function in terms of the same function of lower order, with the three-term
+    /// recurrence of its family, or `None` if it is already of lowest order. Bessel functions
+    /// of negative integer order are reflected to positive order.
+    pub fn recurrence(&self) -> Option<Combination> {
+        match self {
+            Special::BesselJ { order, arg } => {
+                let nu = ratio(order)?;
+                let bessel = |shift: i128| {
+                    let order = nu.checked_add(Ratio { num: shift, den: 1 })?;
+                    Some(Special::bessel_j(constant(order), arg.clone()))
+                };
+                let two = Ratio { num: 2, den: 1 };
+                if nu.den == 1 && nu.num < 0 {
+                    // J₋ₙ(x) = (-1)ⁿ·Jₙ(x)
+                    let sign = integer(if nu.num % 2 == 0 { 1 } else { -1 });
+                    Some(vec![(sign, Special::bessel_j(constant(-nu), arg.clone()))])
+                } else if nu.num >= 2 * nu.den {
+                    // J_ν(x) = 2(ν-1)/x·J_ν₋₁(x) - J_ν₋₂(x)
+                    let scale = constant(two.checked_mul(nu.checked_add(-Ratio::ONE)?)?) / arg;
+                    Some(vec![(scale, bessel(-1)?), (integer(-1), bessel(-2)?)])
+                } else if nu.num < -nu.den {
+                    // J_ν(x) = 2(ν+1)/x·J_ν₊₁(x) - J_ν₊₂(x)
+                    let scale = constant(two.checked_mul(nu.checked_add(Ratio::ONE)?)?) / arg;
+                    Some(vec![(scale, bessel(1)?), (integer(-1), bessel(2)?)])
+                } else {
+                    None
+                }
+            }
+            // n·Pₙ(x) = (2n-1)·x·Pₙ₋₁(x) - (n-1)·Pₙ₋₂(x)
+            &Special::Legendre { degree: n, ref arg } if n >= 2 => {
+                let n = n as i128;
+                Some(vec![
+                    (
+                        constant(Ratio::new(2 * n - 1, n)?) * arg,
+                        Special::legendre(n as u32 - 1, arg.clone()),
+                    ),
+                    (
+                        constant(Ratio::new(1 - n, n)?),
+                        Special::legendre(n as u32 - 2, arg.clone()),
+                    ),
+                ])
+            }
+            _ => None,
+        }
+    }
+
+    /// Applies [`Special::recurrence`] until every function is of lowest order, collecting the
+    /// coefficients of equal functions and folding them.
+    pub fn reduce(&self) -> Combination {
+        let mut done = Combination::new();
+        let mut pending = vec![(integer(1), self.clone())];
+        while let Some((coefficient, function)) = pending.pop() {
+            match function.recurrence() {
+                Some(terms) => pending.extend(
+                    terms
+                        .into_iter()
+                        .map(|(c, f)| ((&coefficient * &c).fold(), f)),
+                ),
+                None => accumulate(&mut done, coefficient, function),
+            }
+        }
+        done
+    }
+}
+
+impl Display for Special {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let list = |params: &[OpArgument]| {
+            params
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            Special::BesselJ { order, arg } => write!(f, "besselj({}, {})", order, arg),
+            Special::Legendre { degree, arg } => write!(f, "legendre({}, {})", degree, arg),
+            Special::Hypergeometric { upper, lower, arg } => write!(
+                f,
+                "hypergeometric([{}], [{}], {})",
+                list(upper),
+                list(lower),
+                arg
+            ),
+        }
+    }
+}
+
+const LANCZOS: [f64; 9] = [
+    0.999_999_999_999_809_9,
+    676.520_368_121_885_1,
+    -1_259.139_216_722_402_8,
+    771.323_428_777_653_1,
+    -176.615_029_162_140_6,
+    12.507_343_278_686_905,
+    -0.138_571_095_265_720_12,
+    9.984_369_578_019_572e-6,
+    1.505_632_735_149_311_6e-7,
+];
+
+/// The Lanczos approximation `Γ(x + 1) = √(2π)·t^(x + 1/2)·e^(-t)·sum` as `(t, sum)`.
+fn lanczos(x: f64) -> (f64, f64) {
+    let t = x + 7.5;
+    let sum = LANCZOS[1..]
+        .iter()
+        .enumerate()
+        .fold(LANCZOS[0], |sum, (i, c)| sum + c / (x + i as f64 + 1.0));
+    (t, sum)
+}
+
+/// The gamma function, by the Lanczos approximation and the reflection formula.
+pub(crate) fn gamma(x: f64) -> f64 {
+    if x < 0.5 {
+        return std::f64::consts::PI / ((std::f64::consts::PI * x).sin() * gamma(1.0 - x));
+    }
+    let x = x - 1.0;
+    let (t, sum) = lanczos(x);
+    (2.0 * std::f64::consts::PI).sqrt() * t.powf(x + 0.5) * (-t).exp() * sum
+}
+
+/// `ln Γ(x)` for `x ≥ 1/2`, which stays finite long after `Γ(x)` overflows.
+fn ln_gamma(x: f64) -> f64 {
+    let x = x - 1.0;
+    let (t, sum) = lanczos(x);
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+/// Recurrences longer than this are not attempted, and evaluate to NaN.
+const MAX_RECURRENCE: usize = 10_000_000;
+
+/// Whether Hankel's expansion is accurate for `J_ν(x)`, which needs `x` large against `ν²`.
+fn asymptotic(nu: f64, x: f64) -> bool {
+    x >= 25.0 + nu * nu / 2.0
+}
+
+/// `J_ν(x)` for large `x` by Hankel's asymptotic expansion `√(2/(πx))·(P·cos χ - Q·sin χ)`,
+/// `χ = x - (ν/2 + 1/4)·π`, summed until its terms stop shrinking. The phase is split off with
+/// the addition formulas, so that only `x` itself needs reducing.
+fn bessel_j_asymptotic(nu: f64, x: f64) -> f64 {
+    let mu = 4.0 * nu * nu;
+    let (mut p, mut q, mut term) = (1.0, 0.0, 1.0f64);
+    for k in 1..MAX_TERMS {
+        let odd = (2 * k - 1) as f64;
+        let next = term * (mu - odd * odd) / (8.0 * k as f64 * x);
+        if next.abs() >= term.abs() {
+            break;
+        }
+        term = next;
+        match k % 4 {
+            1 => q += term,
+            2 => p -= term,
+            3 => q -= term,
+            _ => p += term,
+        }
+        if term.abs() <= EPSILON * p.abs().max(q.abs()) {
+            break;
+        }
+    }
+    let phase = (nu / 2.0 + 0.25) * std::f64::consts::PI;
+    let (sin, cos) = x.sin_cos();
+    let (sin_phase, cos_phase) = phase.sin_cos();
+    let cos_chi = cos * cos_phase + sin * sin_phase;
+    let sin_chi = sin * cos_phase - cos * sin_phase;
+    (2.0 / (std::f64::consts::PI * x)).sqrt() * (p * cos_chi - q * sin_chi)
+}
+
+/// `J_ν(x)` for `x ≥ 0` by its power series, which is only summed where `x²/4 ≤ ν + 1`, so
+/// that its terms shrink from the first and nothing cancels.
+fn bessel_j_series(nu: f64, x: f64) -> f64 {
+    let half = x / 2.0;
+    let mut term = match nu < 170.0 {
+        true => half.powf(nu) / gamma(nu + 1.0),
+        false => (nu * half.ln() - ln_gamma(nu + 1.0)).exp(),
+    };
+    let mut sum = term;
+    for k in 1..MAX_TERMS {
+        term *= -half * half / (k as f64 * (k as f64 + nu));
+        sum += term;
+        if term.abs() <= EPSILON * sum.abs() {
+            return sum;
+        }
+    }
+    f64::NAN
+}
+
+/// `[J_{μ+n}(x), J_{μ+n+1}(x)]` for `0 ≤ μ < 1` and `x > 0`, by Miller's backward recurrence
+/// from far above both `n` and `x`, normalized with the Neumann series
+/// `(x/2)^μ = Γ(μ + 1)·(J_μ + Σₖ (μ + 2k)·(μ + 1)ₖ₋₁/k!·J_{μ+2k})`, which for `μ = 0` is
+/// `J₀ + 2·(J₂ + J₄ + …) = 1`. The recurrence is stable downwards for every `x`.
+fn bessel_j_miller(mu: f64, n: usize, x: f64) -> [f64; 2] {
+    let reach = (n + 1).max(x.ceil() as usize);
+    let start = 2 * ((reach + 20 + (40.0 * reach as f64).sqrt() as usize) / 2);
+    if start > MAX_RECURRENCE {
+        return [f64::NAN; 2];
+    }
+    let (mut above, mut current) = (0.0, 1.0);
+    let mut result = [0.0; 2];
+    // The ratios `(μ + 1)ₖ₋₁/(k - 1)!` up to a common factor, which is their value at `k = 1`.
+    let (mut pochhammer, mut norm) = (1.0, 0.0);
+    for m in (1..=start).rev() {
+        if m == n + 1 {
+            result = [0.0, current];
+        }
+        if m % 2 == 0 {
+            let k = (m / 2) as f64;
+            norm += (mu + 2.0 * k) / k * pochhammer * current;
+            if m > 2 {
+                pochhammer *= (k - 1.0) / (mu + k - 1.0);
+            }
+        }
+        let below = 2.0 * (mu + m as f64) / x * current - above;
+        (above, current) = (current, below);
+        // Rescale before the values overflow, keeping their ratios.
+        if current.abs() > 1e250 {
+            (above, current, norm) = (above * 1e-250, current * 1e-250, norm * 1e-250);
+            result = result.map(|r| r * 1e-250);
+        }
+        if m - 1 == n {
+            result[0] = current;
+        }
+    }
+    let norm = current + norm / pochhammer;
+    let scale = (x / 2.0).powf(mu) / (gamma(mu + 1.0) * norm);
+    result.map(|r| r * scale)
+}
+
+/// `J_ν(x)` for `ν ≥ 0` and `x ≥ 0`: by Hankel's expansion for large `x`, the power series for
+/// small `x`, and Miller's algorithm in between.
+fn bessel_j_nonnegative(nu: f64, x: f64) -> f64 {
+    if asymptotic(nu, x) {
+        return bessel_j_asymptotic(nu, x);
+    }
+    if x * x / 4.0 <= nu + 1.0 {
+        return bessel_j_series(nu, x);
+    }
+    bessel_j_miller(nu.fract(), nu as usize, x)[0]
+}
+
+/// `J_ν(x)`, which is real for every `x` at integer orders and only for `x ≥ 0` otherwise.
+fn bessel_j(nu: f64, x: f64) -> f64 {
+    if !nu.is_finite() || !x.is_finite() {
+        return f64::NAN;
+    }
+    if nu.fract() == 0.0 {
+        // J₋ₙ(x) = (-1)ⁿ·Jₙ(x) and Jₙ(-x) = (-1)ⁿ·Jₙ(x).
+        let sign = match (nu < 0.0) != (x < 0.0) && nu % 2.0 != 0.0 {
+            true => -1.0,
+            false => 1.0,
+        };
+        return sign * bessel_j_nonnegative(nu.abs(), x.abs());
+    }
+    if x < 0.0 {
+        return f64::NAN;
+    }
+    if nu > 0.0 {
+        return bessel_j_nonnegative(nu, x);
+    }
+    if asymptotic(nu, x) {
+        return bessel_j_asymptotic(nu, x);
+    }
+    if x * x / 4.0 <= 1.0 {
+        return bessel_j_series(nu, x);
+    }
+    // Negative orders grow downwards, so the recurrence is stable down from `μ = ν - ⌊ν⌋`.
+    let mu = nu - nu.floor();
+    let [mut current, mut above] = bessel_j_miller(mu, 0, x);
+    for m in 0..(-nu.floor()) as usize {
+        let order = mu - m as f64;
+        (above, current) = (current, 2.0 * order / x * current - above);
+    }
+    current
+}
+
+/// `Pₙ(x)`, by Bonnet's recurrence.
+fn legendre(n: u32, x: f64) -> f64 {
+    let (mut previous, mut current) = (1.0, x);
+    if n == 0 {
+        return previous;
+    }
+    for k in 1..n {
+        let k = k as f64;
+        (previous, current) = (
+            current,
+            ((2.0 * k + 1.0) * x * current - k * previous) / (k + 1.0),
+        );
+    }
+    current
+}
+
+/// `pFq(upper; lower; z)` by summing its series, which converges everywhere when `p ≤ q`, in
+/// the unit disc when `p = q + 1`, and only when it terminates otherwise.
+fn hypergeometric(upper: &[f64], lower: &[f64], z: f64) -> f64 {
+    let (mut term, mut sum) = (1.0, 1.0);
+    for k in 0..MAX_TERMS {
+        let k = k as f64;
+        let numerator = upper.iter().map(|a| a + k).product::<f64>();
+        if numerator == 0.0 {
+            return sum;
+        }
+        let denominator = lower.iter().map(|b| b + k).product::<f64>();
+        if denominator == 0.0 {
+            return f64::NAN;
+        }
+        term *= numerator / denominator * z / (k + 1.0);
+        sum += term;
+        if !sum.is_finite() {
+            // The terms of a divergent series eventually overflow.
+            return f64::NAN;
+        }
+        if term.abs() <= EPSILON * sum.abs() && k > 0.0 {
+            return sum;
+        }
+    }
+    f64::NAN
+}
+
+/// `J_ν(x)` for half-integers `ν`, from `J_½(x) = √(2/πx)·sin(x)` and
+/// `J₋½(x) = √(2/πx)·cos(x)` by the recurrence `J_ν₊₁ = 2ν/x·J_ν - J_ν₋₁`.
+fn bessel_half_integer(nu: Ratio, x: &OpArgument) -> Option<OpArgument> {
+    if nu.den != 2 {
+        return None;
+    }
+    let scale = (integer(2) / (OpArgument::from(crate::constants::Value::Pi) * x))
+        .pow(&constant(Ratio::new(1, 2)?));
+    let (mut below, mut current) = (&scale * x.cos(), &scale * x.sin());
+    let mut order = Ratio::new(1, 2)?;
+    let two = Ratio { num: 2, den: 1 };
+    if nu.num < 0 {
+        // Run the recurrence downwards instead: J_ν₋₁ = 2ν/x·J_ν - J_ν₊₁.
+        (below, current) = (current, below);
+        order = -order;
+        while order != nu {
+            let next = constant(two.checked_mul(order)?) / x * &current - &below;
+            (below, current) = (current, next);
+            order = order.checked_add(-Ratio::ONE)?;
+        }
+    } else {
+        while order != nu {
+            let next = constant(two.checked_mul(order)?) / x * &current - &below;
+            (below, current) = (current, next);
+            order = order.checked_add(Ratio::ONE)?;
+        }
+    }
+    Some(current.fold())
+}
+
+/// The polynomial `c₀ + c₁·x + …` with rational coefficients, lowest degree first, or `None`
+/// if a coefficient is too large for a constant.
+fn polynomial(coefficients: &[Ratio], x: &OpArgument) -> Option<OpArgument> {
+    let mut terms = Vec::new();
+    for (k, c) in coefficients.iter().enumerate() {
+        if *c == Ratio::ZERO {
+            continue;
+        }
+        let c = c.to_oparg()?;
+        terms.push(match k {
+            0 => c,
+            1 => c * x,
+            k => c * x.pow(&integer(k as i128)),
+        });
+    }
+    Some(
+        terms
+            .into_iter()
+            .reduce(|sum, term| sum + term)
+            .unwrap_or_else(|| integer(0)),
+    )
+}
+
+/// The coefficients of `pₙ`, lowest degree first, for the family of polynomials with
+/// `p₀ = 1`, `p₁ = first` and `pₖ₊₁ = (aₖ·x + bₖ)·pₖ - cₖ·pₖ₋₁` where `step(k) = (aₖ, bₖ, cₖ)`.
+fn three_term(
+    n: u32,
+    first: [Ratio; 2],
+    step: impl Fn(i128) -> Option<(Ratio, Ratio, Ratio)>,
+) -> Option<Vec<Ratio>> {
+    let (mut previous, mut current) = (vec![Ratio::ONE], first.to_vec());
+    if n == 0 {
+        return Some(previous);
+    }
+    for k in 1..n as i128 {
+        let (a, b, c) = step(k)?;
+        let mut next = vec![Ratio::ZERO; current.len() + 1];
+        for (i, p) in current.iter().enumerate() {
+            next[i + 1] = next[i + 1].checked_add(a.checked_mul(*p)?)?;
+            next[i] = next[i].checked_add(b.checked_mul(*p)?)?;
+        }
+        for (i, p) in previous.iter().enumerate() {
+            next[i] = next[i].checked_add(-c.checked_mul(*p)?)?;
+        }
+        (previous, current) = (current, next);
+    }
+    Some(current)
+}
+
+fn integers(a: i128, b: i128, c: i128) -> Option<(Ratio, Ratio, Ratio)> {
+    Some((Ratio::new(a, 1)?, Ratio::new(b, 1)?, Ratio::new(c, 1)?))
+}
+
+/// The Chebyshev polynomial of the first kind `Tₙ(x)`, with `Tₙ(cos θ) = cos(nθ)`, expanded.
+/// All the polynomials here are `None` once a coefficient overflows, which happens at around
+/// degree sixty.
+pub fn chebyshev_t(n: u32, x: &OpArgument) -> Option<OpArgument> {
+    // Tₖ₊₁ = 2x·Tₖ - Tₖ₋₁
+    let coefficients = three_term(n, [Ratio::ZERO, Ratio::ONE], |_| integers(2, 0, 1))?;
+    polynomial(&coefficients, x)
+}
+
+/// The Chebyshev polynomial of the second kind `Uₙ(x)`, with
+/// `Uₙ(cos θ)·sin θ = sin((n+1)θ)`, expanded.
+pub fn chebyshev_u(n: u32, x: &OpArgument) -> Option<OpArgument> {
+    // Uₖ₊₁ = 2x·Uₖ - Uₖ₋₁
+    let first = [Ratio::ZERO, Ratio::new(2, 1)?];
+    let coefficients = three_term(n, first, |_| integers(2, 0, 1))?;
+    polynomial(&coefficients, x)
+}
+
+/// The Legendre polynomial `Pₙ(x)`, orthogonal on `[-1, 1]`, expanded.
+pub fn legendre_p(n: u32, x: &OpArgument) -> Option<OpArgument> {
+    // (k+1)·Pₖ₊₁ = (2k+1)·x·Pₖ - k·Pₖ₋₁
+    let coefficients = three_term(n, [Ratio::ZERO, Ratio::ONE], |k| {
+        Some((
+            Ratio::new(2 * k + 1, k + 1)?,
+            Ratio::ZERO,
+            Ratio::new(k, k + 1)?,
+        ))
+    })?;
+    polynomial(&coefficients, x)
+}
+
+/// The physicists' Hermite polynomial `Hₙ(x)`, orthogonal with weight `exp(-x²)`, expanded.
+pub fn hermite_h(n: u32, x: &OpArgument) -> Option<OpArgument> {
+    // Hₖ₊₁ = 2x·Hₖ - 2k·Hₖ₋₁
+    let first = [Ratio::ZERO, Ratio::new(2, 1)?];
+    let coefficients = three_term(n, first, |k| integers(2, 0, 2 * k))?;
+    polynomial(&coefficients, x)
+}
+
+/// The Laguerre polynomial `Lₙ(x)`, orthogonal on `[0, ∞)` with weight `exp(-x)`, expanded.
+pub fn laguerre_l(n: u32, x: &OpArgument) -> Option<OpArgument> {
+    // (k+1)·Lₖ₊₁ = (2k+1-x)·Lₖ - k·Lₖ₋₁
+    let first = [Ratio::ONE, Ratio::new(-1, 1)?];
+    let coefficients = three_term(n, first, |k| {
+        Some((
+            Ratio::new(-1, k + 1)?,
+            Ratio::new(2 * k + 1, k + 1)?,
+            Ratio::new(k, k + 1)?,
+        ))
+    })?;
+    polynomial(&coefficients, x)
+}
+
+fn is_negation_of(node: &OpArgument, other: &OpArgument) -> bool {
+    matches!(&node.value, Op(op) if op.op == Negation && op.arguments[0] == *other)
+}
+
+fn hypergeometric_closed_form(
+    upper: &[OpArgument],
+    lower: &[OpArgument],
+    z: &OpArgument,
+) -> Option<OpArgument> {
+    // Equal upper and lower parameters cancel from every term of the series.
+    let mut upper = upper.iter().map(OpArgument::fold).collect::<Vec<_>>();
+    let mut lower = lower.iter().map(OpArgument::fold).collect::<Vec<_>>();
+    let mut i = 0;
+    while i < upper.len() {
+        match lower.iter().position(|b| *b == upper[i]) {
+            Some(j) => {
+                upper.remove(i);
+                lower.remove(j);
+            }
+            None => i += 1,
+        }
+    }
+
+    let ratios = |params: &[OpArgument]| params.iter().map(ratio).collect::<Option<Vec<_>>>();
+    if let (Some(a), Some(b)) = (ratios(&upper), ratios(&lower)) {
+        // A nonpositive integer upper parameter makes the series a polynomial.
+        if let Some(m) = a
+            .iter()
+            .filter(|a| a.den == 1 && a.num <= 0)
+            .map(|a| -a.num)
+            .min()
+        {
+            let mut coefficients = vec![Ratio::ONE];
+            for k in 0..m {
+                let k = Ratio { num: k, den: 1 };
+                let mut c = *coefficients.last()?;
+                for a in &a {
+                    c = c.checked_mul(a.checked_add(k)?)?;
+                }
+                for b in &b {
+                    c = c.checked_mul(b.checked_add(k)?.recip()?)?;
+                }
+                coefficients.push(c.checked_mul(k.checked_add(Ratio::ONE)?.recip()?)?);
+            }
+            return Some(polynomial(&coefficients, z)?.fold());
+        }
+        let one = Ratio::ONE;
+        let two = Ratio { num: 2, den: 1 };
+        match (a.as_slice(), b.as_slice()) {
+            // ₂F₁(1, 1; 2; z) = -ln(1 - z)/z
+            ([a1, a2], [b1]) if *a1 == one && *a2 == one && *b1 == two => {
+                return Some(-((integer(1) - z).ln() / z));
+            }
+            // ₁F₁(1; 2; z) = (exp(z) - 1)/z
+            ([a1], [b1]) if *a1 == one && *b1 == two => {
+                return Some((z.exp() - integer(1)) / z);
+            }
+            _ => {}
+        }
+    }
+    match (upper.as_slice(), lower.as_slice()) {
+        // ₀F₀(; ; z) = exp(z)
+        ([], []) => Some(z.exp()),
+        // ₁F₀(a; ; z) = (1 - z)^-a
+        ([a], []) if !is_negation_of(a, z) => Some((integer(1) - z).pow(&(-a).fold())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chebyshev_t, chebyshev_u, gamma, hermite_h, laguerre_l, legendre_p, Special};
+    use crate::{evaluation::Bindings, parse::parse};
+
+    #[test]
+    fn test_special_functions() {
+        let p = |input: &str| parse(input).unwrap();
+        let at = |x: f64| Bindings::from_iter([("x", x)]);
+        let close = |a: f64, b: f64| (a - b).abs() <= 1e-13 * b.abs().max(1.0);
+        let bessel = |order: &str| Special::bessel_j(p(order), p("x"));
+
+        assert!(close(gamma(5.0), 24.0));
+        assert!(close(gamma(0.5), std::f64::consts::PI.sqrt()));
+
+        for (order, x, expected) in [
+            ("0", 1.0, 0.765_197_686_557_966_6),
+            ("1", 2.5, 0.497_094_102_464_274),
+            ("5", 10.0, -0.234_061_528_186_793_64),
+            ("0", 50.0, 0.055_812_327_669_251_815),
+            ("3", -30.0, -0.129_211_228_759_725),
+            ("5/2", 3.0, 0.412_710_032_209_716),
+        ] {
+            let value = bessel(order).evaluate(&at(x)).unwrap();
+            assert!(close(value, expected), "J_{}({}) = {}", order, x, value);
+        }
+        assert!(close(
+            Special::legendre(7, p("x")).evaluate(&at(0.3)).unwrap(),
+            -0.224_072_981_25
+        ));
+        let series = Special::hypergeometric(vec![p("1/2")], vec![p("3/2")], p("x"));
+        assert!(close(
+            series.evaluate(&at(-2.0)).unwrap(),
+            0.598_144_006_661_304_1
+        ));
+        let outside = Special::hypergeometric(vec![p("1"), p("1")], vec![p("2")], p("x"));
+        assert!(outside.evaluate(&at(1.5)).unwrap().is_nan());
+
+        // Closed forms agree with the numeric evaluation.
+        assert_eq!(
+            Special::legendre(2, p("x")).closed_form(),
+            Some(p("-1/2 + 3/2 * x^2").fold())
+        );
+        for special in [
+            bessel("5/2"),
+            bessel("-3/2"),
+            Special::legendre(9, p("x")),
+            outside.clone(),
+            Special::hypergeometric(vec![p("-3"), p("2")], vec![p("1/2")], p("x")),
+            Special::hypergeometric(vec![p("1/3"), p("4")], vec![p("4")], p("x")),
+            Special::hypergeometric(vec![], vec![], p("x")),
+        ] {
+            let closed = special.closed_form().unwrap();
+            let (expected, value) = (
+                special.evaluate(&at(0.7)).unwrap(),
+                closed.evaluate(&at(0.7)).unwrap(),
+            );
+            assert!(close(value, expected), "{} = {} at 0.7", special, closed);
+        }
+        assert_eq!(bessel("1").closed_form(), None);
+
+        // The recurrence reduces every integer order to J₀ and J₁.
+        let reduced = bessel("-4").reduce();
+        let mut orders = reduced
+            .iter()
+            .map(|(_, f)| match f {
+                Special::BesselJ { order, .. } => order.clone(),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        orders.sort_by_key(|order| order.to_string());
+        assert_eq!(orders, [p("0").fold(), p("1").fold()]);
+        let x = 3.7;
+        let total = reduced
+            .iter()
+            .map(|(c, f)| c.evaluate(&at(x)).unwrap() * f.evaluate(&at(x)).unwrap())
+            .sum::<f64>();
+        assert!(close(total, bessel("-4").evaluate(&at(x)).unwrap()));
+        assert_eq!(Special::legendre(1, p("x")).recurrence(), None);
+    }
+
+    #[test]
+    fn test_bessel_accuracy() {
+        let p = |input: &str| parse(input).unwrap();
+        let at = |x: f64| Bindings::from_iter([("x", x)]);
+        let bessel = |order: &str| Special::bessel_j(p(order), p("x"));
+        // Relative to the envelope √(2/(πx)) of the oscillations, or to the value beyond them.
+        let close = |a: f64, b: f64, x: f64| {
+            let scale = b
+                .abs()
+                .max((2.0 / (std::f64::consts::PI * x)).sqrt().min(1.0));
+            (a - b).abs() <= 1e-12 * scale
+        };
+
+        for (order, x, expected) in [
+            ("0", 1e20, 6.698_009_040_703_424e-12),
+            ("1", 1e6, -7.259_683_568_137_63e-4),
+            ("0", 30.0, -0.086_367_983_581_040_21),
+            ("23/10", 17.5, 0.148_997_627_198_027_6),
+            ("-23/10", 17.5, -0.009_771_196_911_251_468),
+            ("107/10", 13.0, 0.286_409_220_540_368_5),
+            ("13/4", 200.0, 0.045_139_831_132_468_74),
+            ("100", 80.0, 4.606_553_064_823_477e-6),
+            ("3/10", 0.1, 0.452_725_745_994_596_6),
+            ("40", 3.0, 1.282_792_651_080_675_1e-41),
+        ] {
+            let value = bessel(order).evaluate(&at(x)).unwrap();
+            assert!(close(value, expected, x), "J_{}({}) = {}", order, x, value);
+        }
+        for x in [f64::INFINITY, f64::NEG_INFINITY, f64::NAN] {
+            assert!(bessel("0").evaluate(&at(x)).unwrap().is_nan());
+            assert!(bessel("1/2").evaluate(&at(x)).unwrap().is_nan());
+        }
+
+        // Half-integer orders agree with their closed forms, near the origin and far from it.
+        for order in ["1/2", "-1/2", "3/2", "-3/2", "5/2", "7/2", "-7/2"] {
+            let special = bessel(order);
+            let closed = special.closed_form().unwrap();
+            for x in [1.5, 6.0, 10.0, 27.0, 40.0, 60.0, 1e4, 1e15] {
+                let (value, expected) = (
+                    special.evaluate(&at(x)).unwrap(),
+                    closed.evaluate(&at(x)).unwrap(),
+                );
+                assert!(
+                    close(value, expected, x),
+                    "J_{}({}) = {}, not {}",
+                    order,
+                    x,
+                    value,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_orthogonal_polynomials() {
+        let p = |input: &str| parse(input).unwrap();
+        let x = p("x");
+        let at = |x: f64| Bindings::from_iter([("x", x)]);
+
+        assert_eq!(chebyshev_t(0, &x), Some(p("1")));
+        assert_eq!(chebyshev_t(3, &x), Some(p("-3*x + 4*x^3")));
+        assert_eq!(chebyshev_u(2, &x), Some(p("-1 + 4*x^2")));
+        assert_eq!(legendre_p(3, &x), Some(p("-3/2*x + 5/2*x^3").fold()));
+        assert_eq!(hermite_h(3, &x), Some(p("-12*x + 8*x^3")));
+        assert_eq!(laguerre_l(2, &x), Some(p("1 + -2*x + 1/2*x^2").fold()));
+
+        let theta = 0.4_f64;
+        let t = chebyshev_t(11, &x)
+            .unwrap()
+            .evaluate(&at(theta.cos()))
+            .unwrap();
+        assert!((t - (11.0 * theta).cos()).abs() < 1e-12);
+        let u = chebyshev_u(6, &x)
+            .unwrap()
+            .evaluate(&at(theta.cos()))
+            .unwrap();
+        assert!((u * theta.sin() - (7.0 * theta).sin()).abs() < 1e-12);
+        let legendre = Special::legendre(12, x.clone()).evaluate(&at(0.3)).unwrap();
+        let expanded = legendre_p(12, &x).unwrap().evaluate(&at(0.3)).unwrap();
+        assert!((legendre - expanded).abs() < 1e-12);
+
+        // Coefficients eventually outgrow constants.
+        assert_eq!(hermite_h(200, &x), None);
+    }
+}
@@ -0,0 +1,169 @@
+//! This module describes how to measure the size of our computational graph.
+
+use std::{fmt::Display, mem::size_of, sync::Arc};
+
+use ahash::{HashMap, HashSet};
+
+use crate::{
+    constants::Value,
+    symbols::{
+        interner_usage, OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        Operation, OperationKind,
+    },
+};
+
+/// Bookkeeping overhead of an [`Arc`] allocation: the strong and weak counts.
+const ARC_HEADER: usize = 2 * size_of::<usize>();
+
+/// Node counts and memory estimates for one expression.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NodeStats {
+    /// Distinct operation nodes, by kind.
+    pub operations: HashMap<OperationKind, usize>,
+    /// Distinct leaves holding a constant.
+    pub constants: usize,
+    /// Distinct leaves holding a variable.
+    pub variables: usize,
+    /// Distinct nodes referenced from more than one parent.
+    pub shared_nodes: usize,
+    /// Nodes the expression would have if every shared subexpression were copied out.
+    pub tree_nodes: u128,
+    /// Estimated bytes of heap memory owned by the distinct nodes.
+    pub heap_bytes: usize,
+    pub max_depth: usize,
+}
+
+impl NodeStats {
+    /// The number of distinct nodes.
+    pub fn unique_nodes(&self) -> usize {
+        self.operations.values().sum::<usize>() + self.constants + self.variables
+    }
+}
+
+impl Display for NodeStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} unique nodes ({} as a tree), {} shared, depth {}, ~{} heap bytes",
+            self.unique_nodes(),
+            self.tree_nodes,
+            self.shared_nodes,
+            self.max_depth,
+            self.heap_bytes
+        )?;
+        let mut operations = self.operations.iter().collect::<Vec<_>>();
+        operations.sort_by_key(|(kind, count)| (usize::MAX - **count, kind.opcode()));
+        for (kind, count) in operations {
+            writeln!(f, "  {:>8} {}", count, kind)?;
+        }
+        writeln!(f, "  {:>8} constants", self.constants)?;
+        write!(f, "  {:>8} variables", self.variables)
+    }
+}
+
+#[derive(Default)]
+struct Walker {
+    stats: NodeStats,
+    seen: HashSet<usize>,
+    shared: HashSet<usize>,
+    tree_sizes: HashMap<usize, u128>,
+}
+
+impl Walker {
+    /// Visits `node`, returning the number of nodes in it when written out as a tree.
+    fn visit(&mut self, node: &OpArgument) -> u128 {
+        let address = match &node.value {
+            Op(op) => Arc::as_ptr(op) as usize,
+            Leaf(value) => Arc::as_ptr(value) as usize,
+        };
+
+        if !self.seen.insert(address) {
+            if self.shared.insert(address) {
+                self.stats.shared_nodes += 1;
+            }
+            return self.tree_sizes.get(&address).copied().unwrap_or(1);
+        }
+
+        match &node.value {
+            Op(op) => {
+                *self.stats.operations.entry(op.op).or_default() += 1;
+                self.stats.heap_bytes += ARC_HEADER + size_of::<Operation>();
+                if op.arguments.spilled() {
+                    self.stats.heap_bytes += op.arguments.capacity() * size_of::<OpArgument>();
+                }
+
+                let size = op
+                    .arguments
+                    .iter()
+                    .fold(1u128, |size, arg| size.saturating_add(self.visit(arg)));
+                self.tree_sizes.insert(address, size);
+                size
+            }
+            Leaf(value) => {
+                match **value {
+                    Value::Variable(_) => self.stats.variables += 1,
+                    _ => self.stats.constants += 1,
+                }
+                self.stats.heap_bytes += ARC_HEADER + size_of::<Value>();
+                1
+            }
+        }
+    }
+}
+
+impl OpArgument {
+    /// Counts the nodes of the expression and estimates the memory they occupy.
+    pub fn stats(&self) -> NodeStats {
+        let mut walker = Walker::default();
+        walker.stats.tree_nodes = walker.visit(self);
+        walker.stats.max_depth = self.depth();
+        walker.stats
+    }
+}
+
+/// Process-wide usage of the symbol interner.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct InternerStats {
+    pub symbols: usize,
+    /// Bytes taken by the interned names, excluding the interner's own tables.
+    pub name_bytes: usize,
+}
+
+pub fn interner_stats() -> InternerStats {
+    let (symbols, name_bytes) = interner_usage();
+    InternerStats {
+        symbols,
+        name_bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::interner_stats;
+    use crate::symbols::{variable, OperationKind};
+
+    #[test]
+    fn test_stats() {
+        let x = variable("x");
+        let y = variable("y");
+        let shared = x.sin() * y.cos();
+        let expr = &shared + &shared;
+
+        let stats = expr.stats();
+        assert_eq!(stats.operations[&OperationKind::Addition], 1);
+        assert_eq!(stats.operations[&OperationKind::Multiplication], 1);
+        assert_eq!(stats.operations[&OperationKind::Sin], 1);
+        assert_eq!(stats.variables, 2);
+        assert_eq!(stats.constants, 0);
+        assert_eq!(stats.unique_nodes(), 6);
+        assert_eq!(stats.shared_nodes, 1);
+        assert_eq!(stats.tree_nodes, 11);
+        assert_eq!(stats.max_depth, 3);
+        assert!(stats.heap_bytes > 0);
+        assert!(stats.to_string().contains("6 unique nodes (11 as a tree)"));
+
+        x.free_variables();
+        assert!(interner_stats().symbols >= 1);
+    }
+}
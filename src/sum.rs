@@ -0,0 +1,565 @@
+//! This module describes finite sums over an integer index, and their closed forms.
+//!
+//! A [`Sum`] binds its index in its body, like a [`crate::lambda::Lambda`] binds its
+//! parameters. [`sum_closed_form`] recognizes summands that are polynomials in the index
+//! (summed with Faulhaber's formula), geometric terms `c·r^(αk + β)` and telescoping
+//! differences `f(k) - f(k + 1)`, summing each term of a sum separately. Anything else is kept
+//! as an unevaluated [`Sum`]. The body may use indexed symbols such as `a[k]` (see
+//! [`crate::indexed`]), which a telescoping sum `a[k + 1] - a[k]` collapses and which
+//! [`Sum::expand`] writes out as `a[lo] + … + a[hi]`. Closed forms are [`Conditional`]s, since
+//! a geometric sum is only valid where its ratio is not `1`.
+
+use std::fmt::Display;
+
+use crate::{
+    condition::{Condition, Conditional, ConditionalError},
+    constants::Value,
+    evaluation::{Bindings, EvaluationError},
+    fold::Ratio,
+    indexed::{as_indexed, depends},
+    polynomial::{add, mul, neg},
+    rewrite::Substitution,
+    series::{plus, zero},
+    symbols::{intern, variable, OpArgument, OpArgumentKind::Op, OperationKind::*},
+};
+
+/// Summands are only expanded with Faulhaber's formula up to this degree.
+const MAX_DEGREE: usize = 16;
+
+/// Sums are only written out term by term up to this many terms.
+pub const MAX_TERMS: i128 = 1 << 12;
+
+/// The unevaluated sum of `body` for `index` from `lo` to `hi`, both inclusive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sum {
+    pub body: OpArgument,
+    pub index: &'static str,
+    pub lo: OpArgument,
+    pub hi: OpArgument,
+}
+
+impl Sum {
+    /// Evaluates the sum term by term. The bounds must evaluate to integers of magnitude at
+    /// most `2⁵³`, and the sum is empty if `hi < lo`.
+    pub fn evaluate(&self, bindings: &Bindings) -> Result<f64, EvaluationError> {
+        let lo = integer_bound(self.index, self.lo.evaluate(bindings)?)?;
+        let hi = integer_bound(self.index, self.hi.evaluate(bindings)?)?;
+        let mut bindings = bindings.clone();
+        let indexed = self.body.index_variables().contains(self.index);
+        let mut total = 0.0;
+        for k in lo..=hi {
+            bindings.insert(self.index, k as f64);
+            total += match indexed {
+                true => self.term(integer(k as i128)).evaluate(&bindings)?,
+                false => self.body.evaluate(&bindings)?,
+            };
+        }
+        Ok(total)
+    }
+
+    /// The body with the index replaced by `k`, in indexed symbols too.
+    fn term(&self, k: OpArgument) -> OpArgument {
+        self.body
+            .substitute_indices(&Substitution::from_iter([(self.index, k)]))
+    }
+
+    /// The sum written out term by term, if the bounds are integers with at most
+    /// [`MAX_TERMS`] terms between them.
+    pub fn expand(&self) -> Option<OpArgument> {
+        let bound = |b: &OpArgument| Ratio::of(&b.fold()).filter(|b| b.den == 1);
+        let (lo, hi) = (bound(&self.lo)?, bound(&self.hi)?);
+        if hi.num.checked_sub(lo.num)? >= MAX_TERMS {
+            return None;
+        }
+        Some((lo.num..=hi.num).fold(zero(), |sum, k| plus(&sum, &self.term(integer(k)).fold())))
+    }
+
+    /// The derivative by `var`, which may be an indexed symbol such as `a[3]`. When the body
+    /// has a symbol with the same base indexed by the index, such as `a[k]`, which terms
+    /// depend on `var` is only known once the sum is expanded, so that needs integer bounds
+    /// [`Sum::expand`] accepts; otherwise the body is differentiated under the sum.
+    pub fn derivative(&self, var: &'static str) -> Option<Summation> {
+        let aliased = as_indexed(var).is_some_and(|target| {
+            self.body.free_variables().iter().any(|name| {
+                as_indexed(name).is_some_and(|symbol| {
+                    symbol.base == target.base
+                        && symbol.indices.iter().any(|i| depends(i, self.index))
+                })
+            })
+        });
+        match aliased {
+            true => Some(Summation::Closed(
+                self.expand()?.derivative(var).fold().into(),
+            )),
+            false => Some(sum_closed_form(
+                &self.body.derivative(var),
+                self.index,
+                &self.lo,
+                &self.hi,
+            )),
+        }
+    }
+}
+
+impl Display for Sum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Σ[{} = {}..{}] {}",
+            self.index, self.lo, self.hi, self.body
+        )
+    }
+}
+
+/// A sum, either in closed form or left unevaluated.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Summation {
+    Closed(Conditional),
+    Unevaluated(Sum),
+}
+
+impl Summation {
+    pub fn evaluate(&self, bindings: &Bindings) -> Result<f64, ConditionalError> {
+        match self {
+            Summation::Closed(closed) => closed.evaluate(bindings),
+            Summation::Unevaluated(sum) => Ok(sum.evaluate(bindings)?),
+        }
+    }
+}
+
+impl Display for Summation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Summation::Closed(closed) => closed.fmt(f),
+            Summation::Unevaluated(sum) => sum.fmt(f),
+        }
+    }
+}
+
+/// The largest magnitude of a bound, beyond which an `f64` index stops counting by ones.
+const MAX_BOUND: f64 = (1u64 << f64::MANTISSA_DIGITS) as f64;
+
+/// The value `bound` of a bound of a sum or product over `index` as an integer, which it must
+/// be, of magnitude at most `2⁵³`.
+pub(crate) fn integer_bound(index: &'static str, bound: f64) -> Result<i64, EvaluationError> {
+    match bound.fract() == 0.0 && bound.abs() <= MAX_BOUND {
+        true => Ok(bound as i64),
+        false => Err(EvaluationError::InvalidBound(index)),
+    }
+}
+
+fn integer(n: i128) -> OpArgument {
+    Ratio { num: n, den: 1 }
+        .to_oparg()
+        .expect("Oops, small integers always fit in a rational")
+}
+
+fn depends_on(node: &OpArgument, index: &str) -> bool {
+    depends(node, index)
+}
+
+fn add_terms(a: Option<OpArgument>, b: Option<OpArgument>) -> Option<OpArgument> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, b) => a.or(b),
+    }
+}
+
+/// The product of `a` and `b`, unless its degree is more than Faulhaber's formula is used for.
+fn bounded_mul(a: &[OpArgument], b: &[OpArgument]) -> Option<Vec<OpArgument>> {
+    (a.len() + b.len() <= MAX_DEGREE + 2).then(|| mul(a, b))
+}
+
+/// Reads `node` as a polynomial in `index` with coefficients independent of it, lowest degree
+/// first.
+pub(crate) fn polynomial(node: &OpArgument, index: &str) -> Option<Vec<OpArgument>> {
+    if !depends_on(node, index) {
+        return Some(vec![node.clone()]);
+    }
+    let Op(op) = &node.value else {
+        // The index itself, unless it is an indexed symbol such as `a[k]`.
+        return match *node == variable(intern(index)) {
+            true => Some(vec![zero(), integer(1)]),
+            false => None,
+        };
+    };
+    let arg = |i: usize| &op.arguments[i];
+    match op.op {
+        Addition => Some(add(
+            &polynomial(arg(0), index)?,
+            &polynomial(arg(1), index)?,
+        )),
+        Subtraction => Some(add(
+            &polynomial(arg(0), index)?,
+            &neg(&polynomial(arg(1), index)?),
+        )),
+        Negation => Some(neg(&polynomial(arg(0), index)?)),
+        Multiplication => bounded_mul(&polynomial(arg(0), index)?, &polynomial(arg(1), index)?),
+        Division if !depends_on(arg(1), index) => {
+            let p = polynomial(arg(0), index)?;
+            Some(
+                p.iter()
+                    .map(|c| match *c == zero() {
+                        true => zero(),
+                        false => c / arg(1),
+                    })
+                    .collect(),
+            )
+        }
+        Pow => {
+            let n = Ratio::of(arg(1))?;
+            if n.den != 1 || !(0..=MAX_DEGREE as i128).contains(&n.num) {
+                return None;
+            }
+            let base = polynomial(arg(0), index)?;
+            (0..n.num).try_fold(vec![integer(1)], |acc, _| bounded_mul(&acc, &base))
+        }
+        _ => None,
+    }
+}
+
+/// The Bernoulli numbers `B₀, …, B_n`, with `B₁ = +1/2`.
+fn bernoulli(n: usize) -> Option<Vec<Ratio>> {
+    let mut numbers: Vec<Ratio> = Vec::with_capacity(n + 1);
+    for m in 0..=n {
+        // B_m = 1 - Σ_{j<m} C(m, j) B_j / (m - j + 1)
+        let mut b = Ratio::ONE;
+        let mut binomial = 1i128;
+        for (j, bj) in numbers.iter().enumerate() {
+            let term = Ratio::new(binomial, (m - j + 1) as i128)?.checked_mul(*bj)?;
+            b = b.checked_add(-term)?;
+            binomial = binomial.checked_mul((m - j) as i128)? / (j as i128 + 1);
+        }
+        numbers.push(b);
+    }
+    Some(numbers)
+}
+
+/// `Σ_{k=1}^{n} k^p`, by Faulhaber's formula.
+fn power_sum(p: usize, n: &OpArgument) -> Option<OpArgument> {
+    let bernoulli = bernoulli(p)?;
+    let mut total: Option<OpArgument> = None;
+    let mut binomial = 1i128;
+    for (j, bj) in bernoulli.iter().enumerate() {
+        // C(p + 1, j) B_j n^(p + 1 - j) / (p + 1)
+        let coefficient = Ratio::new(binomial, p as i128 + 1)?.checked_mul(*bj)?;
+        binomial = binomial.checked_mul((p + 1 - j) as i128)? / (j as i128 + 1);
+        if coefficient == Ratio::ZERO {
+            continue;
+        }
+        let power = match p + 1 - j {
+            1 => n.clone(),
+            e => n.pow(&integer(e as i128)),
+        };
+        let term = match coefficient == Ratio::ONE {
+            true => power,
+            false => coefficient.to_oparg()? * power,
+        };
+        total = add_terms(total, Some(term));
+    }
+    total
+}
+
+/// Sums a polynomial summand from `lo` to `hi`.
+fn sum_polynomial(p: &[OpArgument], lo: &OpArgument, hi: &OpArgument) -> Option<OpArgument> {
+    let before = lo - integer(1);
+    let mut total = None;
+    for (degree, coefficient) in p.iter().enumerate() {
+        let coefficient = coefficient.fold();
+        if coefficient == zero() {
+            continue;
+        }
+        let sum = power_sum(degree, hi)? - power_sum(degree, &before)?;
+        let term = match Ratio::of(&coefficient) {
+            Some(Ratio::ONE) => sum,
+            _ => coefficient * sum,
+        };
+        total = add_terms(total, Some(term));
+    }
+    Some(total.unwrap_or_else(|| integer(0)))
+}
+
+/// Reads `(c, r)` out of a geometric term `c·r^k`, with `c` and `r` independent of `index`.
+fn geometric(node: &OpArgument, index: &str) -> Option<(OpArgument, OpArgument)> {
+    let Op(op) = &node.value else {
+        return None;
+    };
+    let arg = |i: usize| &op.arguments[i];
+    let power = |base: &OpArgument, exponent: &OpArgument| {
+        // r^(αk + β) = r^β · (r^α)^k
+        let p = polynomial(exponent, index)?;
+        if p.len() != 2 || depends_on(base, index) {
+            return None;
+        }
+        let alpha = p[1].fold();
+        if alpha == zero() {
+            return None;
+        }
+        let ratio = match Ratio::of(&alpha) {
+            Some(Ratio::ONE) => base.clone(),
+            _ => base.pow(&alpha),
+        };
+        let scale = match p[0].fold() == zero() {
+            true => integer(1),
+            false => base.pow(&p[0]),
+        };
+        Some((scale, ratio))
+    };
+    match op.op {
+        Pow => power(arg(0), arg(1)),
+        Exp => power(&Value::E.into(), arg(0)),
+        Negation => geometric(arg(0), index).map(|(c, r)| (-c, r)),
+        Multiplication if !depends_on(arg(0), index) => {
+            geometric(arg(1), index).map(|(c, r)| (arg(0) * c, r))
+        }
+        Multiplication if !depends_on(arg(1), index) => {
+            geometric(arg(0), index).map(|(c, r)| (c * arg(1), r))
+        }
+        Division if !depends_on(arg(1), index) => {
+            geometric(arg(0), index).map(|(c, r)| (c / arg(1), r))
+        }
+        _ => None,
+    }
+}
+
+/// Sums `c·r^k` from `lo` to `hi` as `c·(r^lo - r^(hi + 1)) / (1 - r)`, adding the condition
+/// `r ≠ 1` it is valid under.
+fn sum_geometric(
+    c: &OpArgument,
+    r: &OpArgument,
+    lo: &OpArgument,
+    hi: &OpArgument,
+    conditions: &mut Vec<Condition>,
+) -> Option<OpArgument> {
+    if Ratio::of(&r.fold()) == Some(Ratio::ONE) {
+        return None;
+    }
+    let difference = r.pow(lo) - r.pow(&(hi + integer(1)));
+    conditions.push(Condition::Nonzero(integer(1) - r));
+    Some(c * (difference / (integer(1) - r)))
+}
+
+/// Sums a telescoping difference `f(k) - f(k + 1)` or `f(k + 1) - f(k)`.
+fn sum_telescoping(
+    node: &OpArgument,
+    index: &'static str,
+    lo: &OpArgument,
+    hi: &OpArgument,
+) -> Option<OpArgument> {
+    let Op(op) = &node.value else {
+        return None;
+    };
+    if op.op != Subtraction {
+        return None;
+    }
+    let (f, g) = (&op.arguments[0], &op.arguments[1]);
+    let at = |expr: &OpArgument, value: OpArgument| {
+        expr.substitute_indices(&Substitution::from_iter([(index, value)]))
+    };
+    let next = |expr: &OpArgument| at(expr, variable(index) + integer(1)).fold();
+
+    if next(f) == g.fold() {
+        Some(at(f, lo.clone()) - at(f, hi + integer(1)))
+    } else if next(g) == f.fold() {
+        Some(at(g, hi + integer(1)) - at(g, lo.clone()))
+    } else {
+        None
+    }
+}
+
+/// Sums a single term, which is not itself a sum, adding the conditions the sum is valid
+/// under to `conditions`.
+fn sum_term(
+    node: &OpArgument,
+    index: &'static str,
+    lo: &OpArgument,
+    hi: &OpArgument,
+    conditions: &mut Vec<Condition>,
+) -> Option<OpArgument> {
+    if let Some(p) = polynomial(node, index) {
+        return sum_polynomial(&p, lo, hi);
+    }
+    if let Some((c, r)) = geometric(node, index) {
+        return sum_geometric(&c, &r, lo, hi, conditions);
+    }
+    sum_telescoping(node, index, lo, hi)
+}
+
+fn sum_terms(
+    node: &OpArgument,
+    index: &'static str,
+    lo: &OpArgument,
+    hi: &OpArgument,
+    conditions: &mut Vec<Condition>,
+) -> Option<OpArgument> {
+    if let Some(sum) = sum_term(node, index, lo, hi, conditions) {
+        return Some(sum);
+    }
+    let Op(op) = &node.value else {
+        return None;
+    };
+    let mut arg = |i: usize| sum_terms(&op.arguments[i], index, lo, hi, conditions);
+    match op.op {
+        Addition => Some(arg(0)? + arg(1)?),
+        Subtraction => Some(arg(0)? - arg(1)?),
+        Negation => Some(-arg(0)?),
+        _ => None,
+    }
+}
+
+/// Sums `body` for `index` from `lo` to `hi` in closed form, or leaves the sum unevaluated.
+/// The closed forms assume `hi ≥ lo - 1`, and carry the condition that the ratio of every
+/// geometric term is not `1`.
+pub fn sum_closed_form(
+    body: &OpArgument,
+    index: &'static str,
+    lo: &OpArgument,
+    hi: &OpArgument,
+) -> Summation {
+    assert!(
+        !depends_on(lo, index) && !depends_on(hi, index),
+        "Whoa there, the bounds of a sum can't depend on its index {}",
+        index
+    );
+    let mut conditions = Vec::new();
+    match sum_terms(body, index, lo, hi, &mut conditions) {
+        Some(closed) => Summation::Closed(
+            conditions
+                .into_iter()
+                .fold(Conditional::from(closed), Conditional::with)
+                .fold(),
+        ),
+        None => Summation::Unevaluated(Sum {
+            body: body.clone(),
+            index,
+            lo: lo.clone(),
+            hi: hi.clone(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sum_closed_form, Sum, Summation, MAX_TERMS};
+    use crate::{
+        condition::{Condition, ConditionalError},
+        evaluation::{Bindings, EvaluationError},
+        parse::parse,
+    };
+
+    #[test]
+    fn test_sum_closed_form() {
+        let p = |input: &str| parse(input).unwrap();
+        let check = |body: &str, lo: &str, hi: &str| {
+            let summation = sum_closed_form(&p(body), "k", &p(lo), &p(hi));
+            for (n, x) in [(0.0, 0.5), (1.0, 2.0), (4.0, 3.0), (9.0, -0.25)] {
+                let bindings = Bindings::from_iter([("n", n), ("x", x)]);
+                let Summation::Closed(closed) = &summation else {
+                    panic!("{} was not summed in closed form", body);
+                };
+                let expected = Sum {
+                    body: p(body),
+                    index: "k",
+                    lo: p(lo),
+                    hi: p(hi),
+                }
+                .evaluate(&bindings)
+                .unwrap();
+                let actual = closed.evaluate(&bindings).unwrap();
+                assert!(
+                    (actual - expected).abs() <= 1e-9 * expected.abs().max(1.0),
+                    "{} summed to {} at n = {}, not {}",
+                    body,
+                    actual,
+                    n,
+                    expected
+                );
+            }
+        };
+
+        check("k", "1", "n");
+        check("k^2 + 3*k - x", "1", "n");
+        check("(2*k + 1)^3 / 5", "2", "n + 2");
+        check("k^7", "0", "n");
+        check("x^k", "0", "n");
+        check("3 * 2^(2*k + 1)", "1", "n");
+        check("exp(k) - k", "1", "n");
+        check("1/k - 1/(k + 1)", "1", "n + 1");
+        check("ln(k + 1) - ln(k)", "1", "n + 1");
+
+        let unevaluated = sum_closed_form(&p("1/k^2"), "k", &p("1"), &p("n"));
+        assert!(matches!(unevaluated, Summation::Unevaluated(_)));
+        assert_eq!(
+            unevaluated.evaluate(&Bindings::from_iter([("n", 2.0)])),
+            Ok(1.25)
+        );
+        // Bounds that are not integers an f64 can count to are errors, not panics or hangs.
+        for n in [2.5, f64::INFINITY, f64::NAN, 1e300] {
+            assert_eq!(
+                unevaluated.evaluate(&Bindings::from_iter([("n", n)])),
+                Err(ConditionalError::Evaluation(EvaluationError::InvalidBound(
+                    "k"
+                )))
+            );
+        }
+
+        // A geometric sum is only valid where its ratio is not one.
+        let Summation::Closed(geometric) = sum_closed_form(&p("x^k"), "k", &p("0"), &p("n")) else {
+            panic!("x^k should be summed in closed form");
+        };
+        assert_eq!(geometric.conditions(), [Condition::Nonzero(p("1 - x"))]);
+        assert!(matches!(
+            geometric.evaluate(&Bindings::from_iter([("n", 3.0), ("x", 1.0)])),
+            Err(ConditionalError::Violated(_))
+        ));
+        let Summation::Closed(halves) = sum_closed_form(&p("2^k"), "k", &p("0"), &p("n")) else {
+            panic!("2^k should be summed in closed form");
+        };
+        assert!(halves.conditions().is_empty());
+
+        // Indexed symbols are not polynomials in the index, but telescope and expand.
+        let telescoped = sum_closed_form(&p("a[k + 1] - a[k]"), "k", &p("1"), &p("n"));
+        assert_eq!(
+            telescoped,
+            Summation::Closed(p("a[n + 1] - a[1]").fold().into())
+        );
+        let stencil = Sum {
+            body: p("(u[k + 1] - u[k])^2"),
+            index: "k",
+            lo: p("0"),
+            hi: p("2"),
+        };
+        let expanded = stencil.expand().unwrap();
+        let bindings =
+            Bindings::from_iter([("u[0]", 1.0), ("u[1]", 3.0), ("u[2]", 2.0), ("u[3]", 5.0)]);
+        assert_eq!(expanded.evaluate(&bindings), Ok(14.0));
+        assert_eq!(stencil.evaluate(&bindings), Ok(14.0));
+        // ∂/∂u[1] = 2(u[1] - u[0]) - 2(u[2] - u[1])
+        let Some(Summation::Closed(gradient)) = stencil.derivative("u[1]") else {
+            panic!("the derivative of an expandable sum should be closed");
+        };
+        assert_eq!(gradient.evaluate(&bindings), Ok(6.0));
+        let symbolic = Sum {
+            hi: p("n"),
+            ..stencil.clone()
+        };
+        assert_eq!(symbolic.derivative("u[1]"), None);
+        // Too many terms to write out, rather than a hang.
+        let long = Sum {
+            hi: p(&format!("{}", MAX_TERMS)),
+            ..stencil.clone()
+        };
+        assert_eq!(long.expand(), None);
+        assert_eq!(long.derivative("u[1]"), None);
+        let huge = Sum {
+            hi: p("1000000000000"),
+            ..stencil.clone()
+        };
+        assert_eq!(huge.expand(), None);
+        let unrelated = symbolic.derivative("x").unwrap();
+        assert_eq!(
+            unrelated.evaluate(&Bindings::from_iter([("n", 5.0)])),
+            Ok(0.0)
+        );
+    }
+}
@@ -1,11 +1,13 @@
 //! This module defines the structures that comprise our computational graphs (except for
 //! constants).
 
-use ahash::{HashSet, HashSetExt};
-use once_cell::sync::OnceCell;
+use ahash::{HashMap, HashSet, HashSetExt};
+use once_cell::sync::{Lazy, OnceCell};
+use parking_lot::Mutex;
 use smallvec::SmallVec;
 use std::{
     cmp::Ordering,
+    convert::Infallible,
     fmt::{Debug, Display, Write},
     hash::{Hash, Hasher},
     sync::Arc,
@@ -14,9 +16,15 @@ use std::{
 pub(crate) type OpHasher = ahash::AHasher;
 pub(crate) type StackVec<T> = SmallVec<[T; 2]>;
 
-use crate::{constants::Value, equivalencies::hash_oparg, operation_properties::Associativity};
+use crate::{
+    constants::Value,
+    equivalencies::hash_oparg,
+    instrument,
+    metadata::{Metadata, VariableSet},
+    operation_properties::Associativity,
+};
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub enum OperationKind {
     Addition,
     Subtraction,
@@ -29,6 +37,9 @@ pub enum OperationKind {
     Cos,
     Tan,
     Ln,
+    Atan,
+    /// `-1`, `0` or `1`, with the sign of the argument.
+    Sign,
 }
 
 impl Display for OperationKind {
@@ -45,40 +56,102 @@ impl Display for OperationKind {
             OperationKind::Cos => f.write_str("cos"),
             OperationKind::Tan => f.write_str("tan"),
             OperationKind::Ln => f.write_str("ln"),
+            OperationKind::Atan => f.write_str("atan"),
+            OperationKind::Sign => f.write_str("sign"),
         }
     }
 }
 
 impl Debug for OperationKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        <Self as Display>::fmt(&self, f)
+        <Self as Display>::fmt(self, f)
     }
 }
 
 #[cfg_attr(not(feature = "pretty_debug"), derive(Debug))]
-#[derive(PartialEq, Eq)]
 pub struct Operation {
     pub(crate) op: OperationKind,
     pub(crate) arguments: StackVec<OpArgument>,
+    pub(crate) metadata: OnceCell<Metadata>,
+    pub(crate) digest: OnceCell<u128>,
+}
+
+impl Operation {
+    pub(crate) fn new(op: OperationKind, arguments: StackVec<OpArgument>) -> Self {
+        Operation {
+            op,
+            arguments,
+            metadata: OnceCell::new(),
+            digest: OnceCell::new(),
+        }
+    }
+
+    /// The facts about this node that are computed once and then cached, since nodes are
+    /// immutable.
+    pub(crate) fn metadata(&self) -> &Metadata {
+        self.metadata
+            .get_or_init(|| Metadata::of_operation(&self.arguments))
+    }
+}
+
+impl PartialEq for Operation {
+    fn eq(&self, other: &Self) -> bool {
+        self.op == other.op && self.arguments == other.arguments
+    }
+}
+
+impl Eq for Operation {}
+
+impl OperationKind {
+    /// A stable code identifying the operation, mixed into node hashes.
+    #[inline]
+    pub(crate) fn opcode(self) -> u32 {
+        match self {
+            OperationKind::Addition => 1,
+            OperationKind::Subtraction => 2,
+            OperationKind::Multiplication => 3,
+            OperationKind::Division => 4,
+            OperationKind::Negation => 5,
+            OperationKind::Pow => 6,
+            OperationKind::Exp => 7,
+            OperationKind::Sin => 8,
+            OperationKind::Cos => 9,
+            OperationKind::Tan => 10,
+            OperationKind::Ln => 11,
+            OperationKind::Atan => 12,
+            OperationKind::Sign => 13,
+        }
+    }
+
+    /// The operation with the given [`OperationKind::opcode`].
+    pub(crate) fn from_opcode(opcode: u32) -> Option<Self> {
+        use OperationKind::*;
+
+        [
+            Addition,
+            Subtraction,
+            Multiplication,
+            Division,
+            Negation,
+            Pow,
+            Exp,
+            Sin,
+            Cos,
+            Tan,
+            Ln,
+            Atan,
+            Sign,
+        ]
+        .into_iter()
+        .find(|op| op.opcode() == opcode)
+    }
 }
 
 impl Hash for Operation {
+    /// Hashes the operation from its opcode and the cached hashes of its arguments, so hashing
+    /// a node never walks further than its direct children once they have been hashed.
     fn hash<H: Hasher>(&self, state: &mut H) {
-        let opcode = match self.op {
-            crate::symbols::OperationKind::Addition => 1,
-            crate::symbols::OperationKind::Subtraction => 2,
-            crate::symbols::OperationKind::Multiplication => 3,
-            crate::symbols::OperationKind::Division => 4,
-            crate::symbols::OperationKind::Negation => 5,
-            crate::symbols::OperationKind::Pow => 6,
-            crate::symbols::OperationKind::Exp => 7,
-            crate::symbols::OperationKind::Sin => 8,
-            crate::symbols::OperationKind::Cos => 9,
-            crate::symbols::OperationKind::Tan => 10,
-            crate::symbols::OperationKind::Ln => 11,
-        };
-
-        state.write_u32(opcode);
+        state.write_u32(self.op.opcode());
 
         let args = self.arguments.len() as u64;
         state.write_u64(args);
@@ -89,6 +162,7 @@ impl Hash for Operation {
     }
 }
 
+#[derive(Clone)]
 #[cfg_attr(not(feature = "pretty_debug"), derive(Debug))]
 pub enum OpArgumentKind {
     Op(Arc<Operation>),
@@ -97,6 +171,7 @@ pub enum OpArgumentKind {
 
 impl From<OpArgumentKind> for OpArgument {
     fn from(op: OpArgumentKind) -> Self {
+        instrument::node_allocated();
         OpArgument {
             hash: OnceCell::new(),
             value: op,
@@ -106,6 +181,8 @@ impl From<OpArgumentKind> for OpArgument {
 
 use OpArgumentKind::{Leaf, Op};
 
+/// Cloning is cheap: it shares the underlying node and its cached hash.
+#[derive(Clone)]
 #[cfg_attr(not(feature = "pretty_debug"), derive(Debug))]
 pub struct OpArgument {
     pub(crate) value: OpArgumentKind,
@@ -115,6 +192,7 @@ pub struct OpArgument {
 impl OpArgument {
     pub fn hash(&self) -> u64 {
         *self.hash.get_or_init(|| {
+            instrument::hash_computed();
             let mut hasher = OpHasher::default();
             hash_oparg(&self.value, &mut hasher);
             hasher.finish()
@@ -134,11 +212,89 @@ impl OpArgument {
         }
     }
 
-    pub fn variables<'a>(&'a self) -> HashSet<&'a Value> {
+    pub fn variables(&self) -> HashSet<&Value> {
         let mut v = HashSet::new();
         self.fill_variables(&mut v);
         v
     }
+
+    /// The set of variables the expression depends on. This is cached on every operation node.
+    pub fn free_variables(&self) -> VariableSet {
+        match &self.value {
+            Op(op) => op.metadata().free_variables.clone(),
+            Leaf(value) => Metadata::of_value(value).free_variables,
+        }
+    }
+
+    /// Whether the expression contains no variables.
+    pub fn is_constant(&self) -> bool {
+        match &self.value {
+            Op(op) => op.metadata().is_constant(),
+            Leaf(value) => !matches!(**value, Value::Variable(_)),
+        }
+    }
+
+    /// Whether every variable of the expression commutes under multiplication, so that it can
+    /// be moved past any other factor. See [`declare_non_commutative`].
+    pub fn is_commutative(&self) -> bool {
+        !self.free_variables().intersects(&NON_COMMUTATIVE.lock())
+    }
+
+    /// The length of the longest path from this node to a leaf, so leaves have depth zero.
+    pub fn depth(&self) -> usize {
+        match &self.value {
+            Op(op) => op.metadata().depth,
+            Leaf(_) => 0,
+        }
+    }
+
+    /// Rebuilds the expression from the leaves up, replacing every node with `f(node, rebuilt)`,
+    /// where `rebuilt` is `node` with its arguments already replaced. Nodes whose arguments are
+    /// all unchanged are reused rather than reallocated, and each distinct subexpression is
+    /// only replaced once.
+    pub(crate) fn map_bottom_up(
+        &self,
+        mut f: impl FnMut(&OpArgument, OpArgument) -> OpArgument,
+    ) -> OpArgument {
+        self.try_map_bottom_up(|node, rebuilt| Ok::<_, Infallible>(f(node, rebuilt)))
+            .unwrap_or_else(|never| match never {})
+    }
+
+    /// Like [`OpArgument::map_bottom_up`], stopping at the first error `f` returns.
+    pub(crate) fn try_map_bottom_up<E>(
+        &self,
+        mut f: impl FnMut(&OpArgument, OpArgument) -> Result<OpArgument, E>,
+    ) -> Result<OpArgument, E> {
+        fn go<E, F: FnMut(&OpArgument, OpArgument) -> Result<OpArgument, E>>(
+            node: &OpArgument,
+            f: &mut F,
+            memo: &mut HashMap<u64, OpArgument>,
+        ) -> Result<OpArgument, E> {
+            if let Some(done) = memo.get(&node.hash()) {
+                return Ok(done.clone());
+            }
+            let rebuilt = match &node.value {
+                Op(op) => {
+                    let arguments = op
+                        .arguments
+                        .iter()
+                        .map(|arg| go(arg, f, memo))
+                        .collect::<Result<StackVec<_>, _>>()?;
+                    if arguments == op.arguments {
+                        node.clone()
+                    } else {
+                        Operation::new(op.op, arguments).into()
+                    }
+                }
+                Leaf(_) => node.clone(),
+            };
+            let result = f(node, rebuilt)?;
+            memo.insert(node.hash(), result.clone());
+            Ok(result)
+        }
+
+        go(self, &mut f, &mut HashMap::default())
+    }
 }
 
 impl Hash for OpArgument {
@@ -148,9 +304,16 @@ impl Hash for OpArgument {
 }
 
 impl PartialEq for OpArgument {
+    #[cfg(not(feature = "wide_hash"))]
     fn eq(&self, other: &Self) -> bool {
         self.hash() == other.hash()
     }
+
+    /// Compares the 128-bit digests, after the cheaper hashes.
+    #[cfg(feature = "wide_hash")]
+    fn eq(&self, other: &Self) -> bool {
+        self.hash() == other.hash() && self.digest() == other.digest()
+    }
 }
 
 impl Eq for OpArgument {}
@@ -167,7 +330,10 @@ impl Display for OpArgument {
 #[cfg(feature = "pretty_debug")]
 impl Debug for OpArgument {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        Display::fmt(self, f)
+        match f.alternate() {
+            true => Display::fmt(&self.pretty(Default::default()), f),
+            false => Display::fmt(self, f),
+        }
     }
 }
 
@@ -182,6 +348,12 @@ impl Display for Operation {
             );
         }
 
+        if self.op == OperationKind::Pow
+            && matches!(&self.arguments[1].value, Leaf(v) if **v == Value::rational(1, 2))
+        {
+            return write!(f, "sqrt({})", self.arguments[0]);
+        }
+
         if self.op.is_prefix() {
             let precedence = match &self.arguments[0].value {
                 Op(op) => self.op.cmp(&op.op),
@@ -230,7 +402,7 @@ impl Display for Operation {
             <OperationKind as Display>::fmt(&self.op, f)?;
             f.write_char('(')?;
             self.arguments.iter().enumerate().try_for_each(|(i, arg)| {
-                <OpArgument as Display>::fmt(&arg, f).and_then(|_| {
+                <OpArgument as Display>::fmt(arg, f).and_then(|_| {
                     if i < self.arguments.len() - 1 {
                         f.write_char(',')
                     } else {
@@ -254,9 +426,7 @@ impl Debug for Operation {
 
 impl From<Value> for OpArgument {
     fn from(value: Value) -> Self {
-        let oparg = Leaf(Arc::new(value)).into();
-
-        oparg
+        Leaf(Arc::new(value)).into()
     }
 }
 
@@ -266,6 +436,92 @@ impl From<Operation> for OpArgument {
     }
 }
 
+#[derive(Default)]
+struct Interner {
+    names: Vec<&'static str>,
+    indices: HashMap<&'static str, u32>,
+}
+
+impl Interner {
+    fn insert(&mut self, name: &'static str) -> u32 {
+        let index = self.names.len() as u32;
+        self.names.push(name);
+        self.indices.insert(name, index);
+        index
+    }
+}
+
+static INTERNER: Lazy<Mutex<Interner>> = Lazy::new(Default::default);
+
+/// Returns a `'static` copy of `name`, leaking each distinct name at most once.
+pub fn intern(name: &str) -> &'static str {
+    let mut interner = INTERNER.lock();
+    match interner.indices.get_key_value(name) {
+        Some((interned, _)) => interned,
+        None => {
+            let interned: &'static str = Box::leak(name.into());
+            interner.insert(interned);
+            interned
+        }
+    }
+}
+
+/// Returns the dense index of the symbol `name`, assigning the next free one on first use.
+pub(crate) fn symbol_index(name: &'static str) -> u32 {
+    let mut interner = INTERNER.lock();
+    match interner.indices.get(name) {
+        Some(&index) => index,
+        None => interner.insert(name),
+    }
+}
+
+/// Returns the dense index of the symbol `name` if it has been assigned one.
+pub(crate) fn lookup_symbol(name: &str) -> Option<u32> {
+    INTERNER.lock().indices.get(name).copied()
+}
+
+/// Returns the number of interned symbols and the bytes taken by their names.
+pub(crate) fn interner_usage() -> (usize, usize) {
+    let interner = INTERNER.lock();
+    let bytes = interner.names.iter().map(|name| name.len()).sum();
+    (interner.names.len(), bytes)
+}
+
+/// Returns the symbol with the dense index `index`.
+pub(crate) fn symbol_name(index: u32) -> &'static str {
+    INTERNER.lock().names[index as usize]
+}
+
+/// Separates the name of a symbol from the scope of the [`Context`](crate::context::Context)
+/// that owns it. The parser never produces it, so scoped symbols cannot clash with global ones.
+pub(crate) const SCOPE_SEPARATOR: char = '@';
+
+/// The name of `symbol` as the user wrote it, without the scope it belongs to.
+pub fn base_name(symbol: &str) -> &str {
+    symbol
+        .split_once(SCOPE_SEPARATOR)
+        .map_or(symbol, |(name, _)| name)
+}
+
+/// The symbols declared with [`declare_non_commutative`].
+static NON_COMMUTATIVE: Lazy<Mutex<VariableSet>> = Lazy::new(Default::default);
+
+/// Declares that the symbol `name` does not commute under multiplication, as operators,
+/// matrices and quantum observables do not, and returns its interned name. Passes that would
+/// reorder products, such as collecting terms, refuse to move such symbols past each other.
+/// Declarations are global and cannot be undone.
+pub fn declare_non_commutative(name: &str) -> &'static str {
+    let name = intern(name);
+    NON_COMMUTATIVE.lock().insert(name);
+    name
+}
+
+/// Whether the symbol `name` commutes under multiplication, which it does unless declared with
+/// [`declare_non_commutative`].
+pub fn is_commutative_symbol(name: &str) -> bool {
+    !NON_COMMUTATIVE.lock().contains(name)
+}
+
 pub fn variable(name: &'static str) -> OpArgument {
-    Leaf(Arc::new(Value::Variable(name.as_ref()))).into()
+    Leaf(Arc::new(Value::Variable(name))).into()
 }
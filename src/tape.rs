@@ -0,0 +1,209 @@
+//! This module describes reverse-mode differentiation of compiled programs.
+//!
+//! [`Program::with_gradient`] treats the instructions of a program as a tape: after copying
+//! them, it walks them backwards from each output, emitting instructions that accumulate the
+//! adjoint of every register from those of the registers that read it. The adjoint
+//! instructions read the forward values directly, so a gradient costs a small constant
+//! multiple of the value, however many parameters there are, and the result is a single fused
+//! program with no trip back through the symbolic layer.
+
+use crate::{
+    compile::{Instruction, Program, Rewriter},
+    symbols::OperationKind::{self, *},
+};
+
+impl Rewriter {
+    fn constant(&mut self, c: f64) -> usize {
+        self.emit(Instruction::Constant(c))
+    }
+
+    fn unary(&mut self, op: OperationKind, a: usize) -> usize {
+        self.emit(Instruction::Unary(op, a))
+    }
+
+    fn binary(&mut self, op: OperationKind, a: usize, b: usize) -> usize {
+        self.emit(Instruction::Binary(op, a, b))
+    }
+
+    /// Adds `contribution` to the adjoint `adjoint`.
+    fn accumulate(&mut self, adjoint: &mut Option<usize>, contribution: usize) {
+        *adjoint = Some(match *adjoint {
+            Some(sum) => self.binary(Addition, sum, contribution),
+            None => contribution,
+        });
+    }
+}
+
+impl Program {
+    /// A program computing, for each output of `self`, its value followed by its partial
+    /// derivatives with respect to every parameter in order.
+    pub fn with_gradient(&self) -> Program {
+        let mut tape = Rewriter::default();
+        let mut value = Vec::with_capacity(self.instructions.len());
+        for instruction in &self.instructions {
+            let instruction = match *instruction {
+                Instruction::Unary(op, a) => Instruction::Unary(op, value[a]),
+                Instruction::Binary(op, a, b) => Instruction::Binary(op, value[a], value[b]),
+                other => other,
+            };
+            value.push(tape.emit(instruction));
+        }
+
+        let mut outputs = Vec::new();
+        for &output in &self.outputs {
+            let mut adjoints = vec![None; output + 1];
+            adjoints[output] = Some(tape.constant(1.0));
+            let mut gradient = vec![None; self.params.len()];
+            for r in (0..=output).rev() {
+                let Some(g) = adjoints[r] else {
+                    continue;
+                };
+                match self.instructions[r] {
+                    Instruction::Input(i) => tape.accumulate(&mut gradient[i], g),
+                    Instruction::Constant(_) => {}
+                    Instruction::Unary(op, a) => {
+                        let x = value[a];
+                        let contribution = match op {
+                            Negation => tape.unary(Negation, g),
+                            Exp => tape.binary(Multiplication, g, value[r]),
+                            Ln => tape.binary(Division, g, x),
+                            Sin => {
+                                let cos = tape.unary(Cos, x);
+                                tape.binary(Multiplication, g, cos)
+                            }
+                            Cos => {
+                                let sin = tape.unary(Sin, x);
+                                let product = tape.binary(Multiplication, g, sin);
+                                tape.unary(Negation, product)
+                            }
+                            Tan => {
+                                // tan' = 1 + tan²
+                                let square = tape.binary(Multiplication, value[r], value[r]);
+                                let one = tape.constant(1.0);
+                                let derivative = tape.binary(Addition, one, square);
+                                tape.binary(Multiplication, g, derivative)
+                            }
+                            Atan => {
+                                let square = tape.binary(Multiplication, x, x);
+                                let one = tape.constant(1.0);
+                                let denominator = tape.binary(Addition, one, square);
+                                tape.binary(Division, g, denominator)
+                            }
+                            // The sign is flat wherever it has a derivative.
+                            Sign => tape.constant(0.0),
+                            _ => unreachable!("Uh-oh, {} is not a unary operation", op),
+                        };
+                        tape.accumulate(&mut adjoints[a], contribution);
+                    }
+                    Instruction::Binary(op, a, b) => {
+                        let (x, y) = (value[a], value[b]);
+                        let (da, db) = match op {
+                            Addition => (g, Some(g)),
+                            Subtraction => (g, Some(tape.unary(Negation, g))),
+                            Multiplication => (
+                                tape.binary(Multiplication, g, y),
+                                Some(tape.binary(Multiplication, g, x)),
+                            ),
+                            Division => {
+                                // (x/y)' = x'/y - (x/y)·y'/y
+                                let quotient = tape.binary(Multiplication, g, value[r]);
+                                let quotient = tape.binary(Division, quotient, y);
+                                (
+                                    tape.binary(Division, g, y),
+                                    Some(tape.unary(Negation, quotient)),
+                                )
+                            }
+                            Pow => {
+                                // (xʸ)' = y·x^(y - 1)·x' + xʸ·ln(x)·y'
+                                let one = tape.constant(1.0);
+                                let exponent = tape.binary(Subtraction, y, one);
+                                let power = tape.binary(Pow, x, exponent);
+                                let scaled = tape.binary(Multiplication, y, power);
+                                let da = tape.binary(Multiplication, g, scaled);
+                                // A constant exponent has no adjoint, so leave out its ln(x).
+                                let db = match self.instructions[b] {
+                                    Instruction::Constant(_) => None,
+                                    _ => {
+                                        let ln = tape.unary(Ln, x);
+                                        let product = tape.binary(Multiplication, value[r], ln);
+                                        Some(tape.binary(Multiplication, g, product))
+                                    }
+                                };
+                                (da, db)
+                            }
+                            _ => unreachable!("Uh-oh, {} is not a binary operation", op),
+                        };
+                        tape.accumulate(&mut adjoints[a], da);
+                        if let Some(db) = db {
+                            tape.accumulate(&mut adjoints[b], db);
+                        }
+                    }
+                }
+            }
+
+            outputs.push(value[output]);
+            for partial in gradient {
+                let partial = partial.unwrap_or_else(|| tape.constant(0.0));
+                outputs.push(partial);
+            }
+        }
+
+        Program {
+            params: self.params.clone(),
+            instructions: tape.instructions,
+            outputs,
+        }
+        .peephole()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        compile::{compile, compile_many},
+        evaluation::Bindings,
+        parse::parse,
+    };
+
+    #[test]
+    fn test_with_gradient() {
+        let p = |input: &str| parse(input).unwrap();
+        let exprs = [
+            "x * sin(y) + exp(x * y) / y",
+            "atan(x - y)^3 - cos(x) * tan(y)",
+            "x^y + ln(x) * y",
+        ];
+        for expr in exprs {
+            let f = p(expr);
+            let program = compile(&f, &["x", "y"]).unwrap().with_gradient();
+            assert_eq!(program.outputs().len(), 3);
+            for (x, y) in [(0.7, 1.3), (1.9, 0.4)] {
+                let at = Bindings::from_iter([("x", x), ("y", y)]);
+                let out = program.evaluate(&[x, y]);
+                let expected = [
+                    f.evaluate(&at).unwrap(),
+                    f.derivative("x").evaluate(&at).unwrap(),
+                    f.derivative("y").evaluate(&at).unwrap(),
+                ];
+                for (out, expected) in out.iter().zip(expected) {
+                    assert!(
+                        (out - expected).abs() < 1e-12 * (1.0 + expected.abs()),
+                        "{} gave {} instead of {}",
+                        expr,
+                        out,
+                        expected
+                    );
+                }
+            }
+        }
+
+        // Each output gets its own gradient, and parameters it does not read get zero.
+        let program = compile_many(&[p("x * x"), p("3 * z")], &["x", "z"])
+            .unwrap()
+            .with_gradient();
+        assert_eq!(
+            program.evaluate(&[2.0, 5.0]),
+            [4.0, 4.0, 0.0, 15.0, 0.0, 3.0]
+        );
+    }
+}
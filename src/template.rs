@@ -0,0 +1,230 @@
+//! This module describes expression templates, which are expressions with named holes that are
+//! filled in later.
+//!
+//! Holes are variables in a reserved namespace (their names start with `?`, which the parser
+//! never produces), so templates are built with the usual operators and share all of the
+//! machinery of ordinary expressions.
+
+use std::fmt::Display;
+
+use ahash::HashMap;
+
+use crate::{
+    constants::Value,
+    rewrite::Substitution,
+    symbols::{intern, variable, OpArgument, OpArgumentKind::Leaf},
+};
+
+/// What a hole may be filled with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum HoleKind {
+    /// Any expression.
+    Expression,
+    /// An expression without variables.
+    Constant,
+    /// A single variable.
+    Variable,
+    /// An expression whose variables all commute, which rules can move past other factors.
+    Commuting,
+}
+
+impl HoleKind {
+    fn prefix(self) -> &'static str {
+        match self {
+            HoleKind::Expression => "?",
+            HoleKind::Constant => "?const:",
+            HoleKind::Variable => "?var:",
+            HoleKind::Commuting => "?comm:",
+        }
+    }
+
+    pub(crate) fn accepts(self, expr: &OpArgument) -> bool {
+        match self {
+            HoleKind::Expression => true,
+            HoleKind::Constant => expr.is_constant(),
+            HoleKind::Variable => {
+                matches!(&expr.value, Leaf(value) if matches!(**value, Value::Variable(_)))
+            }
+            HoleKind::Commuting => expr.is_commutative(),
+        }
+    }
+}
+
+impl Display for HoleKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            HoleKind::Expression => "expression",
+            HoleKind::Constant => "constant",
+            HoleKind::Variable => "variable",
+            HoleKind::Commuting => "commuting expression",
+        })
+    }
+}
+
+/// A hole named `name` that accepts any expression.
+pub fn hole(name: &str) -> OpArgument {
+    typed_hole(name, HoleKind::Expression)
+}
+
+/// A hole named `name` that only accepts expressions of the given kind.
+pub fn typed_hole(name: &str, kind: HoleKind) -> OpArgument {
+    variable(intern(&format!("{}{}", kind.prefix(), name)))
+}
+
+/// Splits the name of a hole variable into the hole's name and kind.
+pub(crate) fn parse_hole(name: &'static str) -> Option<(&'static str, HoleKind)> {
+    [
+        HoleKind::Constant,
+        HoleKind::Variable,
+        HoleKind::Commuting,
+        HoleKind::Expression,
+    ]
+    .into_iter()
+    .find_map(|kind| Some((name.strip_prefix(kind.prefix())?, kind)))
+}
+
+/// The reasons building or instantiating a [`Template`] can fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TemplateError {
+    /// The hole has no binding.
+    Unfilled(&'static str),
+    /// A binding names a hole the template does not have.
+    UnknownHole(String),
+    /// The hole was bound to an expression its kind does not accept.
+    WrongKind(&'static str, HoleKind),
+    /// The template uses the same hole name with two different kinds.
+    ConflictingKinds(&'static str),
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateError::Unfilled(name) => write!(f, "hole {} is not filled", name),
+            TemplateError::UnknownHole(name) => write!(f, "template has no hole {}", name),
+            TemplateError::WrongKind(name, kind) => {
+                write!(f, "hole {} must be filled with a {}", name, kind)
+            }
+            TemplateError::ConflictingKinds(name) => {
+                write!(f, "hole {} is used with two different kinds", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// A map from hole names to the expressions that fill them.
+pub type TemplateBindings<'a> = HashMap<&'a str, OpArgument>;
+
+/// An expression with holes, to be instantiated with [`Template::instantiate`].
+#[derive(Clone, Debug)]
+pub struct Template {
+    expr: OpArgument,
+    /// Each hole's name, kind, and the variable standing for it in `expr`.
+    holes: Vec<(&'static str, HoleKind, &'static str)>,
+}
+
+impl Template {
+    pub fn new(expr: OpArgument) -> Result<Self, TemplateError> {
+        let mut holes: Vec<(&'static str, HoleKind, &'static str)> = Vec::new();
+        for var in expr.free_variables().iter() {
+            let Some((name, kind)) = parse_hole(var) else {
+                continue;
+            };
+            if holes.iter().any(|&(other, _, _)| other == name) {
+                return Err(TemplateError::ConflictingKinds(name));
+            }
+            holes.push((name, kind, var));
+        }
+        Ok(Template { expr, holes })
+    }
+
+    pub fn expr(&self) -> &OpArgument {
+        &self.expr
+    }
+
+    /// The name and kind of every hole in the template.
+    pub fn holes(&self) -> impl Iterator<Item = (&'static str, HoleKind)> + '_ {
+        self.holes.iter().map(|&(name, kind, _)| (name, kind))
+    }
+
+    /// Fills every hole with its expression in `bindings`, checking that each hole is bound
+    /// exactly once to an expression of the right kind.
+    pub fn instantiate(&self, bindings: &TemplateBindings) -> Result<OpArgument, TemplateError> {
+        if let Some(unknown) = bindings
+            .keys()
+            .find(|key| !self.holes.iter().any(|(name, _, _)| name == *key))
+        {
+            return Err(TemplateError::UnknownHole(unknown.to_string()));
+        }
+
+        let mut substitution = Substitution::default();
+        for &(name, kind, var) in &self.holes {
+            let expr = bindings.get(name).ok_or(TemplateError::Unfilled(name))?;
+            if !kind.accepts(expr) {
+                return Err(TemplateError::WrongKind(name, kind));
+            }
+            substitution.insert(var, expr.clone());
+        }
+        Ok(self.expr.substitute(&substitution))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hole, typed_hole, HoleKind, Template, TemplateBindings, TemplateError};
+    use crate::{
+        constants::Value,
+        symbols::{variable, OpArgument},
+    };
+
+    #[test]
+    fn test_template() {
+        let x = variable("x");
+        let y = variable("y");
+        let scale = typed_hole("scale", HoleKind::Constant);
+        let var = typed_hole("var", HoleKind::Variable);
+
+        let template = Template::new(&scale * (&var + hole("offset")).sin() + &x).unwrap();
+        let mut names = template.holes().map(|(name, _)| name).collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(names, ["offset", "scale", "var"]);
+
+        let mut bindings = TemplateBindings::default();
+        bindings.insert("scale", OpArgument::from(Value::integer(3)));
+        bindings.insert("var", y.clone());
+        assert_eq!(
+            template.instantiate(&bindings),
+            Err(TemplateError::Unfilled("offset"))
+        );
+
+        bindings.insert("offset", x.exp());
+        assert_eq!(
+            template.instantiate(&bindings).unwrap(),
+            OpArgument::from(Value::integer(3)) * (&y + x.exp()).sin() + &x
+        );
+
+        bindings.insert("scale", x.clone());
+        assert_eq!(
+            template.instantiate(&bindings),
+            Err(TemplateError::WrongKind("scale", HoleKind::Constant))
+        );
+        bindings.insert("scale", OpArgument::from(Value::Pi));
+        bindings.insert("var", y.sin());
+        assert_eq!(
+            template.instantiate(&bindings),
+            Err(TemplateError::WrongKind("var", HoleKind::Variable))
+        );
+        bindings.insert("var", y.clone());
+        bindings.insert("missing", y.clone());
+        assert_eq!(
+            template.instantiate(&bindings),
+            Err(TemplateError::UnknownHole("missing".to_owned()))
+        );
+
+        assert_eq!(
+            Template::new(hole("a") + typed_hole("a", HoleKind::Constant)).unwrap_err(),
+            TemplateError::ConflictingKinds("a")
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! This module describes converting between trigonometric functions and complex exponentials
+//! with Euler's formula `exp(i·x) = cos(x) + i·sin(x)`.
+//!
+//! [`rewrite_trig_as_exp`] writes every `sin`, `cos`, `tan` and `atan` in terms of `exp` and
+//! `ln` of imaginary arguments, where products of trigonometric functions become sums of
+//! exponentials. [`rewrite_exp_as_trig`] goes back, splitting the exponent of every `exp` and
+//! every power of `e` into its real and imaginary parts `a + i·b` and rewriting it as
+//! `exp(a)·(cos(b) + i·sin(b))`. Exponents are split syntactically, through sums,
+//! differences, negations and products or quotients of one imaginary factor with real ones,
+//! so it is best applied to folded expressions. Neither pass folds its result.
+
+use crate::{
+    constants::Value,
+    symbols::{
+        OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        OperationKind::*,
+    },
+};
+
+fn i() -> OpArgument {
+    Value::I.into()
+}
+
+fn integer(n: u64) -> OpArgument {
+    Value::integer(n).into()
+}
+
+fn trig_as_exp(node: &OpArgument) -> Option<OpArgument> {
+    let Op(op) = &node.value else {
+        return None;
+    };
+    let u = &op.arguments[0];
+    let iu = i() * u;
+    let (ahead, behind) = (iu.exp(), (-&iu).exp());
+    Some(match op.op {
+        // sin(u) = (exp(iu) - exp(-iu)) / 2i
+        Sin => (ahead - behind) / (integer(2) * i()),
+        // cos(u) = (exp(iu) + exp(-iu)) / 2
+        Cos => (ahead + behind) / integer(2),
+        // tan(u) = (exp(iu) - exp(-iu)) / i(exp(iu) + exp(-iu))
+        Tan => (&ahead - &behind) / (i() * (&ahead + &behind)),
+        // atan(u) = i/2 · (ln(1 - iu) - ln(1 + iu))
+        Atan => (i() / integer(2)) * ((integer(1) - &iu).ln() - (integer(1) + &iu).ln()),
+        _ => return None,
+    })
+}
+
+/// Writes every trigonometric function of `expr` in terms of complex exponentials and
+/// logarithms.
+pub fn rewrite_trig_as_exp(expr: &OpArgument) -> OpArgument {
+    expr.map_bottom_up(|_, rebuilt| trig_as_exp(&rebuilt).unwrap_or(rebuilt))
+}
+
+fn is_i(node: &OpArgument) -> bool {
+    matches!(&node.value, Leaf(value) if **value == Value::I)
+}
+
+fn mentions_i(node: &OpArgument) -> bool {
+    match &node.value {
+        Leaf(value) => **value == Value::I,
+        Op(op) => op.arguments.iter().any(mentions_i),
+    }
+}
+
+fn add(a: Option<OpArgument>, b: Option<OpArgument>) -> Option<OpArgument> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, b) => a.or(b),
+    }
+}
+
+fn negate(a: Option<OpArgument>) -> Option<OpArgument> {
+    a.map(|a| -a)
+}
+
+/// Splits `u` into its real and imaginary parts `a + i·b`, with parts that are zero absent.
+/// Whatever cannot be split counts as real.
+fn split(u: &OpArgument) -> (Option<OpArgument>, Option<OpArgument>) {
+    if is_i(u) {
+        return (None, Some(integer(1)));
+    }
+    let Op(op) = &u.value else {
+        return (Some(u.clone()), None);
+    };
+    let args = &op.arguments;
+    // `i·b`, `b·i` or `i·b / c`, with no other imaginary factor.
+    let imaginary_times = |b: &OpArgument, c: &OpArgument| match split(b) {
+        (None, Some(b)) if !mentions_i(c) && b == integer(1) => Some(c.clone()),
+        (None, Some(b)) if !mentions_i(c) => Some(b * c),
+        _ => None,
+    };
+    let imaginary = match op.op {
+        Multiplication => {
+            imaginary_times(&args[0], &args[1]).or_else(|| imaginary_times(&args[1], &args[0]))
+        }
+        Division => match split(&args[0]) {
+            (None, Some(b)) if !mentions_i(&args[1]) => Some(b / &args[1]),
+            _ => None,
+        },
+        _ => None,
+    };
+    if let Some(b) = imaginary {
+        return (None, Some(b));
+    }
+    match op.op {
+        Negation => {
+            let (a, b) = split(&args[0]);
+            (negate(a), negate(b))
+        }
+        Addition | Subtraction => {
+            let ((a, b), (c, d)) = (split(&args[0]), split(&args[1]));
+            match op.op {
+                Addition => (add(a, c), add(b, d)),
+                _ => (add(a, negate(c)), add(b, negate(d))),
+            }
+        }
+        _ => (Some(u.clone()), None),
+    }
+}
+
+/// `exp(i·b) = cos(b) + i·sin(b)`, written as `cos(b) - i·sin(b)` when `b` is negated.
+fn euler(b: &OpArgument) -> OpArgument {
+    match &b.value {
+        Op(op) if op.op == Negation => {
+            let b = &op.arguments[0];
+            b.cos() - i() * b.sin()
+        }
+        _ => b.cos() + i() * b.sin(),
+    }
+}
+
+fn exp_as_trig(node: &OpArgument) -> Option<OpArgument> {
+    let Op(op) = &node.value else {
+        return None;
+    };
+    let exponent = match op.op {
+        Exp => &op.arguments[0],
+        Pow if matches!(&op.arguments[0].value, Leaf(value) if **value == Value::E) => {
+            &op.arguments[1]
+        }
+        _ => return None,
+    };
+    match split(exponent) {
+        (_, None) => None,
+        (None, Some(b)) => Some(euler(&b)),
+        (Some(a), Some(b)) => Some(a.exp() * euler(&b)),
+    }
+}
+
+/// Writes every exponential of `expr` with an imaginary part in its exponent in terms of
+/// trigonometric functions.
+pub fn rewrite_exp_as_trig(expr: &OpArgument) -> OpArgument {
+    expr.map_bottom_up(|_, rebuilt| exp_as_trig(&rebuilt).unwrap_or(rebuilt))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rewrite_exp_as_trig, rewrite_trig_as_exp};
+    use crate::{constants::Value, parse::parse, symbols::OpArgument};
+
+    #[test]
+    fn test_euler() {
+        let p = |input: &str| parse(input).unwrap();
+        let i = OpArgument::from(Value::I);
+        let x = p("x");
+        let y = p("y");
+
+        let sin = rewrite_trig_as_exp(&x.sin());
+        assert_eq!(sin, p("(exp(i*x) - exp(-(i*x))) / (2*i)"));
+        assert_eq!(
+            rewrite_trig_as_exp(&p("cos(x) * y")),
+            p("(exp(i*x) + exp(-(i*x))) / 2 * y")
+        );
+        assert_eq!(rewrite_trig_as_exp(&p("exp(x) + y")), p("exp(x) + y"));
+
+        assert_eq!(rewrite_exp_as_trig(&p("exp(i*x)")), x.cos() + &i * x.sin());
+        assert_eq!(
+            rewrite_exp_as_trig(&p("e^(y - x*i/2)")),
+            y.exp() * ((&x / p("2")).cos() - &i * (&x / p("2")).sin())
+        );
+        assert_eq!(
+            rewrite_exp_as_trig(&p("exp(x) * exp(i)")),
+            x.exp() * (p("1").cos() + &i * p("1").sin())
+        );
+        assert_eq!(rewrite_exp_as_trig(&p("exp(x*y)")), p("exp(x*y)"));
+
+        // There and back again.
+        assert_eq!(
+            rewrite_exp_as_trig(&sin),
+            ((x.cos() + &i * x.sin()) - (x.cos() - &i * x.sin())) / p("2*i")
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! This module describes how to write our computational graph as Typst math.
+//!
+//! [`OpArgument::to_typst`] emits the body of a `$ ... $` block. Quotients become `frac`, so
+//! they need no parentheses, products are juxtaposed unless a number follows, and names longer
+//! than a letter are quoted, since Typst reads bare words in math as symbols. Names that are
+//! Typst symbols, such as `alpha`, are kept bare, and a letter followed by digits, such as
+//! `x1`, gets the digits as a subscript.
+
+use crate::{
+    constants::Value,
+    render::needs_parens,
+    symbols::{
+        base_name, OpArgument,
+        OpArgumentKind::{Leaf, Op},
+        Operation,
+        OperationKind::*,
+    },
+};
+
+/// The Greek letters, which are Typst symbols of the same name.
+const GREEK: [&str; 24] = [
+    "alpha", "beta", "gamma", "delta", "epsilon", "zeta", "eta", "theta", "iota", "kappa",
+    "lambda", "mu", "nu", "xi", "omicron", "pi", "rho", "sigma", "tau", "upsilon", "phi", "chi",
+    "psi", "omega",
+];
+
+fn name(name: &str) -> String {
+    let (head, digits) = name.split_at(
+        name.find(|c: char| c.is_ascii_digit())
+            .unwrap_or(name.len()),
+    );
+    let digits_only = !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit());
+    let bare =
+        |head: &str| head.chars().count() == 1 || GREEK.contains(&head.to_lowercase().as_str());
+    match (bare(head), digits_only) {
+        (true, true) => format!("{}_{}", head, digits),
+        (true, false) if digits.is_empty() => head.to_string(),
+        _ => format!("{:?}", name),
+    }
+}
+
+fn value(value: &Value) -> String {
+    match *value {
+        Value::Rational(num, den) if den.get() == 1 => num.to_string(),
+        Value::Rational(num, den) => format!("frac({}, {})", num, den),
+        Value::Pi => "pi".to_string(),
+        Value::E => "e".to_string(),
+        Value::I => "i".to_string(),
+        Value::Inf => "infinity".to_string(),
+        Value::Undefined => "\"undefined\"".to_string(),
+        Value::Variable(v) => name(base_name(v)),
+        Value::Algebraic(number) => format!("{:?}", number.to_string()),
+    }
+}
+
+fn parenthesized(text: String) -> String {
+    format!("({})", text)
+}
+
+fn operation(op: &Operation) -> String {
+    let arg = |i: usize| typst(&op.arguments[i]);
+    match op.op {
+        Division => format!("frac({}, {})", arg(0), arg(1)),
+        Pow if matches!(&op.arguments[1].value, Leaf(v) if **v == Value::rational(1, 2)) => {
+            format!("sqrt({})", arg(0))
+        }
+        Pow => {
+            let base = match &op.arguments[0].value {
+                Op(_) => parenthesized(arg(0)),
+                Leaf(_) => arg(0),
+            };
+            let exponent = match &op.arguments[1].value {
+                Leaf(v) if matches!(**v, Value::Rational(_, den) if den.get() == 1) => arg(1),
+                _ => parenthesized(arg(1)),
+            };
+            format!("{}^{}", base, exponent)
+        }
+        Negation => match &op.arguments[0].value {
+            Op(child) if matches!(child.op, Addition | Subtraction | Multiplication) => {
+                format!("-{}", parenthesized(arg(0)))
+            }
+            _ => format!("-{}", arg(0)),
+        },
+        Addition | Subtraction | Multiplication => {
+            let part = |i: usize| {
+                let is_fraction = matches!(&op.arguments[i].value, Op(o) if o.op == Division);
+                match needs_parens(op.op, &op.arguments[i], i) && !is_fraction {
+                    true => parenthesized(arg(i)),
+                    false => arg(i),
+                }
+            };
+            let (lhs, rhs) = (part(0), part(1));
+            let operator = match op.op {
+                Addition => " + ",
+                Subtraction => " - ",
+                // A number after a factor would run into it.
+                _ if rhs.starts_with(|c: char| c.is_ascii_digit()) => " dot ",
+                _ => " ",
+            };
+            format!("{}{}{}", lhs, operator, rhs)
+        }
+        Exp => format!("e^({})", arg(0)),
+        Atan => format!("arctan({})", arg(0)),
+        Sign => format!("op(\"sgn\")({})", arg(0)),
+        Sin | Cos | Tan | Ln => format!("{}({})", op.op, arg(0)),
+    }
+}
+
+fn typst(expr: &OpArgument) -> String {
+    match &expr.value {
+        Op(op) => operation(op),
+        Leaf(v) => value(v),
+    }
+}
+
+impl OpArgument {
+    /// Writes `self` as Typst math, to go between the dollar signs of an equation.
+    pub fn to_typst(&self) -> String {
+        typst(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::parse;
+
+    #[test]
+    fn test_to_typst() {
+        let typst = |input: &str| parse(input).unwrap().to_typst();
+
+        assert_eq!(typst("(x + 1) / (2 * y)"), "frac(x + 1, 2 y)");
+        assert_eq!(typst("3 * x^2 - sin(theta)"), "3 x^2 - sin(theta)");
+        assert_eq!(typst("x * 2 * (a - b)"), "x dot 2 (a - b)");
+        assert_eq!(typst("(a + b)^(n + 1)"), "(a + b)^(n + 1)");
+        assert_eq!(typst("exp(-x1) + sqrt(rate)"), "e^(-x_1) + sqrt(\"rate\")");
+        assert_eq!(typst("-(a + b) + atan(pi)"), "-(a + b) + arctan(pi)");
+        assert_eq!(typst("0.25 * inf"), "frac(1, 4) infinity");
+    }
+}
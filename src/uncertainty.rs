@@ -0,0 +1,197 @@
+//! This module describes how measurement uncertainties propagate through expressions.
+//!
+//! The first-order method linearizes the expression at the measured values, so its standard
+//! deviation is `√Σ (∂f/∂xᵢ·σᵢ)²` for independent inputs, with the derivatives taken
+//! symbolically. It is exact for linear expressions and good when the uncertainties are small
+//! compared to the curvature. The Monte Carlo method samples every input from a normal
+//! distribution instead and measures the spread of the compiled expression over the samples,
+//! which also captures nonlinear effects and shifts of the mean.
+
+use std::fmt::Display;
+
+use crate::{
+    codegen::CodegenError,
+    compile::compile,
+    evaluation::{Bindings, EvaluationError},
+    random::Rng,
+    symbols::OpArgument,
+};
+
+/// The reasons uncertainty propagation can fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum UncertaintyError {
+    Evaluation(EvaluationError),
+    Codegen(CodegenError),
+    /// Monte Carlo propagation was asked for fewer than the two samples a spread needs.
+    TooFewSamples(usize),
+}
+
+impl Display for UncertaintyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UncertaintyError::Evaluation(err) => err.fmt(f),
+            UncertaintyError::Codegen(err) => err.fmt(f),
+            UncertaintyError::TooFewSamples(samples) => write!(
+                f,
+                "a spread needs at least two Monte Carlo samples, not {}",
+                samples
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UncertaintyError {}
+
+impl From<EvaluationError> for UncertaintyError {
+    fn from(err: EvaluationError) -> Self {
+        UncertaintyError::Evaluation(err)
+    }
+}
+
+impl From<CodegenError> for UncertaintyError {
+    fn from(err: CodegenError) -> Self {
+        UncertaintyError::Codegen(err)
+    }
+}
+
+/// A value with the standard deviation of its normally distributed error.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Measurement {
+    pub value: f64,
+    pub sigma: f64,
+}
+
+impl Measurement {
+    pub fn new(value: f64, sigma: f64) -> Self {
+        Measurement { value, sigma }
+    }
+}
+
+impl Display for Measurement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ± {}", self.value, self.sigma)
+    }
+}
+
+/// How uncertainties are propagated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Propagation {
+    /// Linearization through the gradient.
+    FirstOrder,
+    /// The sample mean and standard deviation over `samples` draws from a generator seeded
+    /// with `seed`.
+    MonteCarlo { samples: usize, seed: u64 },
+}
+
+/// The value of `expr` with its uncertainty, given independent measurements of its variables.
+pub fn propagate_uncertainty(
+    expr: &OpArgument,
+    measurements: &[(&'static str, Measurement)],
+    propagation: Propagation,
+) -> Result<Measurement, UncertaintyError> {
+    match propagation {
+        Propagation::FirstOrder => {
+            let bindings = measurements
+                .iter()
+                .map(|&(name, m)| (name, m.value))
+                .collect::<Bindings>();
+            let variance = measurements
+                .iter()
+                .filter(|(_, m)| m.sigma != 0.0)
+                .map(|&(name, m)| {
+                    let slope = expr.derivative(name).evaluate(&bindings)?;
+                    Ok((slope * m.sigma).powi(2))
+                })
+                .sum::<Result<f64, EvaluationError>>()?;
+            Ok(Measurement::new(expr.evaluate(&bindings)?, variance.sqrt()))
+        }
+        Propagation::MonteCarlo { samples, seed } => {
+            if samples < 2 {
+                return Err(UncertaintyError::TooFewSamples(samples));
+            }
+            let names = measurements
+                .iter()
+                .map(|&(name, _)| name)
+                .collect::<Vec<_>>();
+            let program = compile(expr, &names)?;
+            let mut rng = Rng::new(seed);
+            let (mut inputs, mut registers, mut out) = (vec![0.0; names.len()], Vec::new(), [0.0]);
+            // Welford's running mean and sum of squared deviations.
+            let (mut mean, mut squares) = (0.0, 0.0);
+            for k in 1..=samples {
+                for (input, (_, m)) in inputs.iter_mut().zip(measurements) {
+                    *input = m.value + m.sigma * rng.normal();
+                }
+                program.evaluate_into(&inputs, &mut registers, &mut out);
+                let delta = out[0] - mean;
+                mean += delta / k as f64;
+                squares += delta * (out[0] - mean);
+            }
+            Ok(Measurement::new(
+                mean,
+                (squares / (samples - 1) as f64).sqrt(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{propagate_uncertainty, Measurement, Propagation, UncertaintyError};
+    use crate::{evaluation::EvaluationError, parse::parse};
+
+    #[test]
+    fn test_propagate_uncertainty() {
+        let p = |input: &str| parse(input).unwrap();
+        let monte_carlo = Propagation::MonteCarlo {
+            samples: 200_000,
+            seed: 7,
+        };
+
+        // Independent errors add in quadrature through a sum.
+        let sum = [
+            ("a", Measurement::new(1.0, 0.3)),
+            ("b", Measurement::new(2.0, 0.4)),
+        ];
+        let linear = propagate_uncertainty(&p("a + b"), &sum, Propagation::FirstOrder).unwrap();
+        assert_eq!(linear.value, 3.0);
+        assert!((linear.sigma - 0.5).abs() < 1e-15);
+        let sampled = propagate_uncertainty(&p("a + b"), &sum, monte_carlo).unwrap();
+        assert!((sampled.value - 3.0).abs() < 0.01 && (sampled.sigma - 0.5).abs() < 0.01);
+
+        // Relative errors add in quadrature through a product: g = 4π²L/T².
+        let pendulum = [
+            ("l", Measurement::new(1.0, 0.01)),
+            ("t", Measurement::new(2.0, 0.02)),
+        ];
+        let g = p("4 * pi^2 * l / t^2");
+        let first = propagate_uncertainty(&g, &pendulum, Propagation::FirstOrder).unwrap();
+        let relative = (0.01f64.powi(2) + (2.0 * 0.01f64).powi(2)).sqrt();
+        assert!((first.sigma / first.value - relative).abs() < 1e-12);
+        let sampled = propagate_uncertainty(&g, &pendulum, monte_carlo).unwrap();
+        assert!((sampled.sigma / first.sigma - 1.0).abs() < 0.02);
+
+        // Only sampling sees the mean of x² shift by σ².
+        let square = [("x", Measurement::new(0.0, 1.0))];
+        let first = propagate_uncertainty(&p("x^2"), &square, Propagation::FirstOrder).unwrap();
+        assert_eq!(first, Measurement::new(0.0, 0.0));
+        let sampled = propagate_uncertainty(&p("x^2"), &square, monte_carlo).unwrap();
+        assert!((sampled.value - 1.0).abs() < 0.02);
+
+        assert_eq!(
+            propagate_uncertainty(&p("x + y"), &square, Propagation::FirstOrder),
+            Err(UncertaintyError::Evaluation(EvaluationError::Unbound("y")))
+        );
+        assert_eq!(
+            propagate_uncertainty(
+                &p("x^2"),
+                &square,
+                Propagation::MonteCarlo {
+                    samples: 1,
+                    seed: 0
+                }
+            ),
+            Err(UncertaintyError::TooFewSamples(1))
+        );
+    }
+}
@@ -0,0 +1,364 @@
+//! This module describes checking derivatives three independent ways.
+//!
+//! [`verify_derivative`] samples random points and compares, at each, the symbolic derivative
+//! from [`OpArgument::derivative`], forward-mode automatic differentiation of the compiled
+//! [`Program`] with [`Dual`] numbers, and a central finite difference of the expression. The
+//! first two should agree to rounding, and the last to the accuracy of the difference, so a
+//! disagreement points at the differentiation rules, the compiler or the evaluator of whatever
+//! operation is involved. Points where the expression or its derivative is not finite, such as
+//! outside the domain of `ln`, are skipped.
+
+use std::{
+    fmt::Display,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+use crate::{
+    codegen::CodegenError,
+    compile::{compile, Program},
+    evaluation::{Bindings, EvaluationError, Scalar},
+    random::Rng,
+    symbols::OpArgument,
+};
+
+/// A value together with its derivative, which every operation carries along by the chain
+/// rule.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Dual {
+    pub value: f64,
+    pub derivative: f64,
+}
+
+impl Dual {
+    pub fn constant(value: f64) -> Self {
+        Dual {
+            value,
+            derivative: 0.0,
+        }
+    }
+
+    /// The variable being differentiated by, at `value`.
+    pub fn variable(value: f64) -> Self {
+        Dual {
+            value,
+            derivative: 1.0,
+        }
+    }
+
+    /// `f(self)`, given `df = f'(self)`.
+    fn chain(self, f: f64, df: f64) -> Self {
+        Dual {
+            value: f,
+            derivative: df * self.derivative,
+        }
+    }
+}
+
+impl Add for Dual {
+    type Output = Dual;
+
+    fn add(self, rhs: Dual) -> Dual {
+        Dual {
+            value: self.value + rhs.value,
+            derivative: self.derivative + rhs.derivative,
+        }
+    }
+}
+
+impl Sub for Dual {
+    type Output = Dual;
+
+    fn sub(self, rhs: Dual) -> Dual {
+        Dual {
+            value: self.value - rhs.value,
+            derivative: self.derivative - rhs.derivative,
+        }
+    }
+}
+
+impl Mul for Dual {
+    type Output = Dual;
+
+    fn mul(self, rhs: Dual) -> Dual {
+        Dual {
+            value: self.value * rhs.value,
+            derivative: self.derivative * rhs.value + self.value * rhs.derivative,
+        }
+    }
+}
+
+impl Div for Dual {
+    type Output = Dual;
+
+    fn div(self, rhs: Dual) -> Dual {
+        Dual {
+            value: self.value / rhs.value,
+            derivative: (self.derivative * rhs.value - self.value * rhs.derivative)
+                / (rhs.value * rhs.value),
+        }
+    }
+}
+
+impl Neg for Dual {
+    type Output = Dual;
+
+    fn neg(self) -> Dual {
+        Dual {
+            value: -self.value,
+            derivative: -self.derivative,
+        }
+    }
+}
+
+impl Scalar for Dual {
+    fn from_f64(value: f64) -> Self {
+        Dual::constant(value)
+    }
+
+    fn powf(self, exponent: Self) -> Self {
+        let value = self.value.powf(exponent.value);
+        let mut derivative = 0.0;
+        if self.derivative != 0.0 {
+            derivative += exponent.value * self.value.powf(exponent.value - 1.0) * self.derivative;
+        }
+        // Only a varying exponent needs the logarithm, which is undefined for negative bases.
+        if exponent.derivative != 0.0 {
+            derivative += value * self.value.ln() * exponent.derivative;
+        }
+        Dual { value, derivative }
+    }
+
+    fn exp(self) -> Self {
+        let value = self.value.exp();
+        self.chain(value, value)
+    }
+
+    fn sin(self) -> Self {
+        self.chain(self.value.sin(), self.value.cos())
+    }
+
+    fn cos(self) -> Self {
+        self.chain(self.value.cos(), -self.value.sin())
+    }
+
+    fn tan(self) -> Self {
+        let value = self.value.tan();
+        self.chain(value, 1.0 + value * value)
+    }
+
+    fn ln(self) -> Self {
+        self.chain(self.value.ln(), 1.0 / self.value)
+    }
+
+    fn atan(self) -> Self {
+        self.chain(self.value.atan(), 1.0 / (1.0 + self.value * self.value))
+    }
+
+    fn sign(self) -> Self {
+        self.chain(self.value.sign(), 0.0)
+    }
+}
+
+/// How [`verify_derivative`] samples and compares.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VerifyOptions {
+    /// How many random points to check.
+    pub points: usize,
+    /// Every variable is sampled uniformly from this interval.
+    pub span: (f64, f64),
+    pub seed: u64,
+    /// The relative error allowed between the symbolic derivative and the finite difference.
+    pub tolerance: f64,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        VerifyOptions {
+            points: 32,
+            span: (-2.0, 2.0),
+            seed: 0,
+            tolerance: 1e-5,
+        }
+    }
+}
+
+/// The three derivatives at a point where they disagree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mismatch {
+    /// The value of every variable of the expression.
+    pub point: Vec<(&'static str, f64)>,
+    pub symbolic: f64,
+    pub dual: f64,
+    pub finite_difference: f64,
+}
+
+/// The reasons verifying a derivative can fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum VerifyError {
+    Codegen(CodegenError),
+    Evaluation(EvaluationError),
+    /// Every sampled point was outside the domain of the expression or its derivative.
+    NoValidPoints,
+    Mismatch(Mismatch),
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::Codegen(err) => err.fmt(f),
+            VerifyError::Evaluation(err) => err.fmt(f),
+            VerifyError::NoValidPoints => f.write_str("no sampled point was in the domain"),
+            VerifyError::Mismatch(mismatch) => write!(
+                f,
+                "at {:?} the symbolic derivative is {}, the dual number one {} and the finite \
+                 difference {}",
+                mismatch.point, mismatch.symbolic, mismatch.dual, mismatch.finite_difference
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+impl From<CodegenError> for VerifyError {
+    fn from(err: CodegenError) -> Self {
+        VerifyError::Codegen(err)
+    }
+}
+
+impl From<EvaluationError> for VerifyError {
+    fn from(err: EvaluationError) -> Self {
+        VerifyError::Evaluation(err)
+    }
+}
+
+/// Whether `a` and `b` agree to `tolerance`, relative to their size or absolutely near zero.
+fn close(a: f64, b: f64, tolerance: f64) -> bool {
+    (a - b).abs() <= tolerance * a.abs().max(b.abs()).max(1.0)
+}
+
+/// The derivative of `program` in parameter `index` at `params`, by forward mode.
+fn dual(program: &Program, params: &[f64], index: usize) -> f64 {
+    let params = params
+        .iter()
+        .enumerate()
+        .map(|(i, &value)| match i == index {
+            true => Dual::variable(value),
+            false => Dual::constant(value),
+        })
+        .collect::<Vec<_>>();
+    let mut out = [Dual::constant(0.0)];
+    program.evaluate_into(&params, &mut Vec::new(), &mut out);
+    out[0].derivative
+}
+
+/// Checks `derivative` as the derivative of `expr` with respect to `var`.
+fn check(
+    expr: &OpArgument,
+    derivative: &OpArgument,
+    var: &'static str,
+    options: VerifyOptions,
+) -> Result<(), VerifyError> {
+    let mut variables = expr.free_variables().iter().collect::<Vec<_>>();
+    if !variables.contains(&var) {
+        variables.push(var);
+    }
+    let index = variables.iter().position(|&v| v == var).unwrap();
+    let program = compile(expr, &variables)?;
+
+    let mut rng = Rng::new(options.seed);
+    let (low, high) = options.span;
+    let mut checked = 0;
+    for _ in 0..options.points {
+        let params = variables
+            .iter()
+            .map(|_| low + (high - low) * rng.uniform())
+            .collect::<Vec<_>>();
+        let mut bindings =
+            Bindings::from_iter(variables.iter().copied().zip(params.iter().copied()));
+        let value = expr.evaluate(&bindings)?;
+        let symbolic = derivative.evaluate(&bindings)?;
+        let dual = dual(&program, &params, index);
+
+        // A step balancing truncation against cancellation, for a central difference.
+        let x = params[index];
+        let h = f64::EPSILON.cbrt() * x.abs().max(1.0);
+        bindings.insert(var, x + h);
+        let ahead = expr.evaluate(&bindings)?;
+        bindings.insert(var, x - h);
+        let behind = expr.evaluate(&bindings)?;
+        let finite_difference = (ahead - behind) / (2.0 * h);
+
+        if ![value, symbolic, dual, finite_difference]
+            .iter()
+            .all(|v| v.is_finite())
+        {
+            continue;
+        }
+        checked += 1;
+        if !close(symbolic, dual, 1e-9) || !close(symbolic, finite_difference, options.tolerance) {
+            return Err(VerifyError::Mismatch(Mismatch {
+                point: variables.iter().copied().zip(params).collect(),
+                symbolic,
+                dual,
+                finite_difference,
+            }));
+        }
+    }
+    match checked {
+        0 => Err(VerifyError::NoValidPoints),
+        _ => Ok(()),
+    }
+}
+
+/// Checks [`OpArgument::derivative`] of `expr` with respect to `var` against dual numbers and
+/// finite differences at random points, returning the first point where they disagree.
+pub fn verify_derivative(
+    expr: &OpArgument,
+    var: &'static str,
+    options: VerifyOptions,
+) -> Result<(), VerifyError> {
+    check(expr, &expr.derivative(var), var, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, verify_derivative, VerifyError, VerifyOptions};
+    use crate::parse::parse;
+
+    #[test]
+    fn test_verify_derivative() {
+        let p = |input: &str| parse(input).unwrap();
+        let options = VerifyOptions::default();
+        for expr in [
+            "sin(x^2) * exp(y) + x^3 / (1 + y^2)",
+            "atan(x * y) - tan(x / 3)",
+            "ln(x) * x^x + sqrt(x)",
+            "y^2",
+        ] {
+            assert_eq!(
+                verify_derivative(&p(expr), "x", options),
+                Ok(()),
+                "{}",
+                expr
+            );
+        }
+
+        let Err(VerifyError::Mismatch(mismatch)) = check(&p("x^3"), &p("2 * x^2"), "x", options)
+        else {
+            panic!("Whoa there, a wrong derivative should not pass");
+        };
+        let (_, x) = mismatch.point[0];
+        assert_eq!(mismatch.symbolic, 2.0 * x * x);
+        assert!((mismatch.dual - 3.0 * x * x).abs() < 1e-12);
+
+        let options = VerifyOptions {
+            span: (-2.0, -1.0),
+            ..options
+        };
+        assert_eq!(
+            verify_derivative(&p("ln(x)"), "x", options),
+            Err(VerifyError::NoValidPoints)
+        );
+    }
+}